@@ -24,6 +24,7 @@ pub fn cache_benchmark(c: &mut Criterion) {
                         cleanup_interval_seconds: 60,
                         shards_count: 16,
                         eviction_policy: EvictionPolicy::LeastRecentlyUsed,
+                        ..Default::default()
                     };
                     let cache = Arc::new(ExampleCache::new(config));
 
@@ -0,0 +1,74 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use rand::{thread_rng, Rng};
+use std::sync::Arc;
+use std::thread;
+use travel_tech_assessment::part1_cache::{AvailabilityCache, CacheConfig, CacheKey};
+use travel_tech_assessment::part1_cache::{ExampleCache, ShardedCache};
+
+const HOTELS: usize = 1000;
+const OPS_PER_THREAD: usize = 2000;
+// Share of operations that are writes, the rest are reads
+const WRITE_RATIO: f64 = 0.05;
+
+fn key(i: usize) -> CacheKey {
+    CacheKey::new(format!("hotel{}", i), "2025-06-01", "2025-06-05")
+}
+
+fn populated<C: AvailabilityCache>() -> Arc<C> {
+    let cache = C::new(CacheConfig::default());
+    for i in 0..HOTELS {
+        cache.store_key(key(i), vec![0; 1024], None);
+    }
+    Arc::new(cache)
+}
+
+// Read-heavy mix from `threads` threads at once, mostly hitting the cache
+fn run<C: AvailabilityCache>(cache: &Arc<C>, threads: usize) {
+    let keys: Arc<Vec<CacheKey>> = Arc::new((0..HOTELS).map(key).collect());
+    let handles: Vec<_> = (0..threads)
+        .map(|_| {
+            let cache = Arc::clone(cache);
+            let keys = Arc::clone(&keys);
+            thread::spawn(move || {
+                let mut rng = thread_rng();
+                for _ in 0..OPS_PER_THREAD {
+                    let key = &keys[rng.gen_range(0..HOTELS)];
+                    if rng.gen_bool(WRITE_RATIO) {
+                        cache.store_key(key.clone(), vec![1; 1024], None);
+                    } else {
+                        black_box(cache.lookup(key));
+                    }
+                }
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+}
+
+// Compares the global-mutex ExampleCache with the sharded cache as threads are added
+pub fn contention_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("cache_contention");
+
+    for threads in [1, 4, 8, 16] {
+        let example = populated::<ExampleCache>();
+        group.bench_with_input(
+            BenchmarkId::new("example_mutex", threads),
+            &threads,
+            |b, &threads| b.iter(|| run(&example, threads)),
+        );
+
+        let sharded = populated::<ShardedCache>();
+        group.bench_with_input(
+            BenchmarkId::new("sharded_rwlock", threads),
+            &threads,
+            |b, &threads| b.iter(|| run(&sharded, threads)),
+        );
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, contention_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,47 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use travel_tech_assessment::part2_xml::{synthetic_supplier_json, HotelSearchProcessor};
+
+// Benchmarks for the XML processor, over synthetic responses of 10, 1k and 50k options
+const SIZES: [usize; 3] = [10, 1_000, 50_000];
+const SEED: u64 = 42;
+
+pub fn xml_benchmark(c: &mut Criterion) {
+    let processor = HotelSearchProcessor::new();
+    let mut group = c.benchmark_group("xml_processing");
+
+    for options in SIZES {
+        let json = synthetic_supplier_json(SEED, options);
+        let xml = processor.convert_json_to_xml(&json).unwrap();
+        // The largest responses take long enough per iteration that fewer samples will do
+        group.sample_size(if options >= 50_000 { 10 } else { 50 });
+        group.throughput(Throughput::Elements(options as u64));
+
+        group.bench_with_input(BenchmarkId::new("process", options), &xml, |b, xml| {
+            b.iter(|| processor.process(black_box(xml)).unwrap())
+        });
+
+        group.bench_with_input(
+            BenchmarkId::new("process_stream", options),
+            &xml,
+            |b, xml| {
+                b.iter(|| {
+                    processor
+                        .process_stream(black_box(xml.as_bytes()))
+                        .map(Result::unwrap)
+                        .count()
+                })
+            },
+        );
+
+        group.bench_with_input(
+            BenchmarkId::new("convert_json_to_xml", options),
+            &json,
+            |b, json| b.iter(|| processor.convert_json_to_xml(black_box(json)).unwrap()),
+        );
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, xml_benchmark);
+criterion_main!(benches);
@@ -1,6 +1,7 @@
 // Main library file for the travel tech assessment
 
 // Export modules for each part of the assessment
+pub mod money;
 pub mod part1_cache;
 pub mod part2_xml;
 pub mod part3_api;
@@ -9,12 +10,16 @@ pub mod supplier;
 pub mod xml_response;
 
 // Re-export key types for convenience
-pub use part1_cache::{AvailabilityCache, CacheStats};
+pub use money::Money;
+pub use part1_cache::{
+    AvailabilityCache, CacheKey, CacheLookup, CacheStats, CacheVersion, SearchContext,
+};
 pub use part2_xml::{
     FilterCriteria, HotelOption, HotelSearchProcessor, ProcessedResponse, ProcessingError,
 };
 pub use part3_api::{
-    ApiClient, ApiError, BookingApiClient, ClientConfig, ClientError, ClientStats,
+    ApiClient, ApiError, BookingApiClient, CachedSearchClient, ClientConfig, ClientError,
+    ClientStats,
 };
 pub use xml_response::{
     XmlHotel, XmlHotels, XmlMealPlan, XmlMealPlans, XmlOption, XmlOptions, XmlProcessedResponse,
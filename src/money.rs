@@ -0,0 +1,82 @@
+// Money
+// Prices used to flow through `f64`, which can't represent most cents exactly and drifts when
+// amounts are added up or compared, and were written to the XML as whatever `f64`'s Display
+// produced ("120.5"). Amounts are `Decimal`s now; they are parsed exactly from both JSON numbers
+// and XML strings and always written to the XML with two decimals.
+// JSON serialization keeps the amount as a string ("84.82") so no precision is lost.
+
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default, Deserialize, Serialize)]
+pub struct Money {
+    pub amount: Decimal,
+    pub currency: String,
+}
+
+impl Money {
+    pub fn new(amount: Decimal, currency: impl Into<String>) -> Self {
+        Self {
+            amount,
+            currency: currency.into(),
+        }
+    }
+
+    // Parse an XML amount attribute; None if it isn't a number
+    pub fn parse(amount: &str, currency: impl Into<String>) -> Option<Self> {
+        Decimal::from_str(amount.trim())
+            .ok()
+            .map(|amount| Self::new(amount, currency))
+    }
+
+    // The amount as written to the XML, e.g. "120.50"
+    pub fn xml_amount(&self) -> String {
+        format_amount(self.amount)
+    }
+}
+
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.xml_amount(), self.currency)
+    }
+}
+
+// Two decimals, half-cents rounded away from zero
+pub fn format_amount(amount: Decimal) -> String {
+    format!(
+        "{:.2}",
+        amount.round_dp_with_strategy(2, RoundingStrategy::MidpointAwayFromZero)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_xml_amount_has_two_decimals() {
+        let amount = |value: &str| Money::parse(value, "EUR").unwrap().xml_amount();
+        assert_eq!(amount("120.5"), "120.50");
+        assert_eq!(amount("84.82"), "84.82");
+        assert_eq!(amount("100"), "100.00");
+        assert_eq!(amount("0.125"), "0.13");
+        assert_eq!(amount("-0.125"), "-0.13");
+        assert_eq!(Money::parse("N/A", "EUR"), None);
+    }
+
+    #[test]
+    fn test_no_float_drift() {
+        let total: Decimal = ["0.1", "0.2"]
+            .iter()
+            .map(|value| Money::parse(value, "EUR").unwrap().amount)
+            .sum();
+        assert_eq!(total, Decimal::from_str("0.3").unwrap());
+
+        let json = serde_json::to_string(&Money::parse("84.82", "GBP").unwrap()).unwrap();
+        assert_eq!(json, r#"{"amount":"84.82","currency":"GBP"}"#);
+        let money: Money = serde_json::from_str(r#"{"amount":84.82,"currency":"GBP"}"#).unwrap();
+        assert_eq!(money.to_string(), "84.82 GBP");
+    }
+}
@@ -1,10 +1,64 @@
 // Part 1: Hotel Availability Cache Implementation
 // This component serves as the middleware between our high-traffic customer-facing API and supplier systems
 
-use std::collections::HashMap;
-use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
-use std::sync::{Arc, Mutex};
-use std::time::{Duration, Instant};
+use std::collections::{hash_map, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant, SystemTime};
+
+pub mod admission;
+pub mod compression;
+pub mod events;
+pub mod eviction;
+pub mod histogram;
+pub mod inspect;
+pub mod key;
+pub mod loader;
+#[cfg(test)]
+mod model;
+pub mod persistence;
+pub mod quota;
+#[cfg(feature = "redis-backend")]
+pub mod redis_backend;
+pub mod sharded;
+pub mod size;
+pub mod snapshot;
+pub mod spill;
+#[cfg(feature = "sqlite-store")]
+pub mod sqlite_store;
+pub mod tiered;
+pub mod typed;
+pub mod warmup;
+pub mod write_behind;
+
+pub use admission::AdmissionConfig;
+use admission::AdmissionFilter;
+pub use compression::Compression;
+use events::CacheListeners;
+pub use events::EntryEvent;
+pub use eviction::{EvictionCandidate, EvictionStrategy};
+pub use histogram::{LatencyHistogram, LatencySummary};
+pub use inspect::{CacheInspect, EntryFilter, EntryInfo, EntryMetadata, SUPPLIER_TAG_PREFIX};
+pub use key::{CacheKey, SearchContext};
+pub use loader::{AsyncLoader, CacheLoader, LoadError};
+use persistence::PersistedEntry;
+use quota::QuotaTracker;
+pub use quota::{Quota, QuotaReport, QuotaScope};
+pub use sharded::ShardedCache;
+use size::hash_map_slot_size;
+pub use size::EstimateSize;
+pub use snapshot::{CacheSnapshot, SnapshotEntry};
+pub use spill::SpillStore;
+#[cfg(feature = "sqlite-store")]
+pub use sqlite_store::SqliteStore;
+pub use tiered::TieredCache;
+pub use typed::TypedCache;
+pub use warmup::{AccessRecord, WarmupCandidate, WarmupPlan};
+pub use write_behind::{
+    FileStore, PersistentStore, StoreOp, StoredEntry, WriteBehindConfig, WriteBehindStats,
+};
+use write_behind::{Queued, WriteBehind};
 
 // Enhanced stats for the cache
 #[derive(Debug, Default)]
@@ -12,12 +66,74 @@ pub struct CacheStats {
     pub size_bytes: AtomicUsize,
     pub items_count: AtomicUsize,
     pub hit_count: AtomicUsize,
+    pub negative_hit_count: AtomicUsize,
     pub miss_count: AtomicUsize,
     pub eviction_count: AtomicUsize,
     pub expired_count: AtomicUsize,
     pub rejected_count: AtomicUsize,
-    pub average_lookup_time_ns: AtomicU64,
+    pub spilled_count: AtomicUsize,
+    pub version_conflict_count: AtomicUsize,
+    pub lookup_latency: LatencyHistogram,
     pub total_lookups: AtomicUsize,
+    pub batch_count: AtomicUsize,
+    pub batch_keys: AtomicUsize,
+    pub batch_hits: AtomicUsize,
+    pub full_hit_batch_count: AtomicUsize,
+    pub raw_bytes: AtomicUsize,
+    pub compressed_bytes: AtomicUsize,
+}
+
+impl CacheStats {
+    // Record the duration of a lookup started at `started`
+    pub fn record_lookup_time(&self, started: Instant) {
+        self.lookup_latency
+            .record(started.elapsed().as_nanos() as u64);
+    }
+
+    // Count one `get_many` batch with the given results
+    pub fn record_batch(&self, results: &[CacheLookup]) {
+        let hits = results.iter().filter(|found| found.is_hit()).count();
+        self.batch_count.fetch_add(1, Ordering::SeqCst);
+        self.batch_keys.fetch_add(results.len(), Ordering::SeqCst);
+        self.batch_hits.fetch_add(hits, Ordering::SeqCst);
+        if hits == results.len() {
+            self.full_hit_batch_count.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    // Report of the counters kept here; backends fill in what they track elsewhere
+    // (e.g. sizes held by a remote store, per-tier figures)
+    pub fn report(&self) -> CacheStatsReport {
+        let latency = self.lookup_latency.summary();
+        CacheStatsReport {
+            size_bytes: self.size_bytes.load(Ordering::SeqCst),
+            items_count: self.items_count.load(Ordering::SeqCst),
+            hit_count: self.hit_count.load(Ordering::SeqCst),
+            negative_hit_count: self.negative_hit_count.load(Ordering::SeqCst),
+            miss_count: self.miss_count.load(Ordering::SeqCst),
+            eviction_count: self.eviction_count.load(Ordering::SeqCst),
+            expired_count: self.expired_count.load(Ordering::SeqCst),
+            rejected_count: self.rejected_count.load(Ordering::SeqCst),
+            spilled_count: self.spilled_count.load(Ordering::SeqCst),
+            version_conflict_count: self.version_conflict_count.load(Ordering::SeqCst),
+            average_lookup_time_ns: latency.mean_ns,
+            p50_lookup_time_ns: latency.p50_ns,
+            p95_lookup_time_ns: latency.p95_ns,
+            p99_lookup_time_ns: latency.p99_ns,
+            max_lookup_time_ns: latency.max_ns,
+            total_lookups: self.total_lookups.load(Ordering::SeqCst),
+            batch_count: self.batch_count.load(Ordering::SeqCst),
+            batch_keys: self.batch_keys.load(Ordering::SeqCst),
+            batch_hits: self.batch_hits.load(Ordering::SeqCst),
+            full_hit_batch_count: self.full_hit_batch_count.load(Ordering::SeqCst),
+            raw_bytes: self.raw_bytes.load(Ordering::SeqCst),
+            compressed_bytes: self.compressed_bytes.load(Ordering::SeqCst),
+            quota_rejected_count: 0,
+            quotas: Vec::new(),
+            admission_rejected_count: 0,
+            tiers: Vec::new(),
+        }
+    }
 }
 
 // Enhanced stats for the cache
@@ -26,12 +142,60 @@ pub struct CacheStatsReport {
     pub size_bytes: usize,
     pub items_count: usize,
     pub hit_count: usize,
+    // Lookups answered by a negative entry, included in hit_count
+    pub negative_hit_count: usize,
     pub miss_count: usize,
     pub eviction_count: usize,
     pub expired_count: usize,
     pub rejected_count: usize,
+    // Stores handed to the spill store for lack of room, see `CapacityPolicy::SpillToSecondary`
+    pub spilled_count: usize,
+    // Conditional stores refused because the entry changed, see `store_if_version`
+    pub version_conflict_count: usize,
+    // Lookup latency from a histogram with ~3% resolution, see `histogram`
     pub average_lookup_time_ns: u64,
+    pub p50_lookup_time_ns: u64,
+    pub p95_lookup_time_ns: u64,
+    pub p99_lookup_time_ns: u64,
+    pub max_lookup_time_ns: u64,
     pub total_lookups: usize,
+    // `get_many` batches, the keys they asked for and how many of those were hits
+    // (included in the lookup counters above); a full-hit batch needs no supplier call at all
+    pub batch_count: usize,
+    pub batch_keys: usize,
+    pub batch_hits: usize,
+    pub full_hit_batch_count: usize,
+    // Payload bytes as passed to `store` vs. as held after compression
+    // (equal when compression is off); the ratio is the effective compression ratio
+    pub raw_bytes: usize,
+    pub compressed_bytes: usize,
+    // Stores refused by a quota (included in rejected_count) and usage per configured quota
+    pub quota_rejected_count: usize,
+    pub quotas: Vec<QuotaReport>,
+    // Stores of keys not requested often enough yet (included in rejected_count)
+    pub admission_rejected_count: usize,
+    // Per-level counters for layered caches (empty for single-level caches)
+    pub tiers: Vec<TierStatsReport>,
+}
+
+impl CacheStatsReport {
+    // Share of keys requested through `get_many` that were hits
+    pub fn batch_hit_ratio(&self) -> f64 {
+        if self.batch_keys == 0 {
+            return 0.0;
+        }
+        self.batch_hits as f64 / self.batch_keys as f64
+    }
+}
+
+// Counters for one level of a layered cache, see `TieredCache`
+#[derive(Debug, Default, Clone)]
+pub struct TierStatsReport {
+    pub tier: String,
+    pub hit_count: usize,
+    pub miss_count: usize,
+    pub size_bytes: usize,
+    pub items_count: usize,
 }
 
 // Cache configuration options
@@ -39,9 +203,32 @@ pub struct CacheStatsReport {
 pub struct CacheConfig {
     pub max_size_mb: usize,
     pub default_ttl_seconds: u64,
+    // Entries not read for this long expire before their TTL, None to only expire by TTL
+    // (not supported by the Redis backend)
+    pub max_idle_seconds: Option<u64>,
+    // TTL of negative ("no availability") entries, usually much shorter than the default
+    pub negative_ttl_seconds: u64,
     pub cleanup_interval_seconds: u64,
     pub shards_count: usize,
     pub eviction_policy: EvictionPolicy,
+    // What a store does when the cache is full (not supported by the Redis backend)
+    pub capacity_policy: CapacityPolicy,
+    // When set, contents are saved to this file on shutdown and reloaded by `new()`
+    pub persistence_path: Option<PathBuf>,
+    // Soft limit: a store that would grow the cache past this share of max_size_mb first
+    // evicts down to `low_watermark_percent` in one pass. max_size_mb itself is the hard
+    // limit, stores that still don't fit after evicting are rejected
+    pub high_watermark_percent: usize,
+    pub low_watermark_percent: usize,
+    // Values of at least `compression_threshold_bytes` are stored compressed with this codec
+    pub compression: Compression,
+    pub compression_threshold_bytes: usize,
+    // Maximum number of concurrent loader calls during `prefetch`
+    pub prefetch_parallelism: usize,
+    // Per-namespace limits enforced on store, see `quota` (not supported by the Redis backend)
+    pub quotas: Vec<Quota>,
+    // Only cache keys requested repeatedly, see `admission` (not supported by the Redis backend)
+    pub admission: Option<AdmissionConfig>,
 }
 
 impl Default for CacheConfig {
@@ -49,19 +236,78 @@ impl Default for CacheConfig {
         Self {
             max_size_mb: 100,
             default_ttl_seconds: 300,
+            max_idle_seconds: None,
+            negative_ttl_seconds: 30,
             cleanup_interval_seconds: 60,
             shards_count: 16,
             eviction_policy: EvictionPolicy::LeastRecentlyUsed,
+            capacity_policy: CapacityPolicy::EvictToFit,
+            persistence_path: None,
+            high_watermark_percent: 100,
+            low_watermark_percent: 90,
+            compression: Compression::None,
+            compression_threshold_bytes: 16 * 1024,
+            prefetch_parallelism: 8,
+            quotas: Vec::new(),
+            admission: None,
+        }
+    }
+}
+
+// Result of a cache lookup; `get_ref` reads values as shared `Arc<[u8]>` handles
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CacheLookup<V = Vec<u8>> {
+    // Availability data stored for the key
+    Hit(V),
+    // The supplier is known to have no availability for the key, see `store_empty`
+    HitEmpty,
+    // Nothing known about the key, the supplier has to be asked
+    Miss,
+}
+
+impl<V> CacheLookup<V> {
+    pub fn is_hit(&self) -> bool {
+        !matches!(self, CacheLookup::Miss)
+    }
+
+    // The stored data, None for negative entries and misses
+    pub fn into_data(self) -> Option<V> {
+        match self {
+            CacheLookup::Hit(data) => Some(data),
+            CacheLookup::HitEmpty | CacheLookup::Miss => None,
         }
     }
 }
 
-// Eviction policy to use
+// Identifies one stored value of a key, see `get_versioned`
+// Every store of a key gets a new, higher version
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct CacheVersion(pub(crate) u64);
+
+impl CacheVersion {
+    // Version of a key with no (live) entry
+    pub const ABSENT: CacheVersion = CacheVersion(0);
+}
+
+// Eviction policy to use, see `eviction` for the strategies behind each policy
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum EvictionPolicy {
     LeastRecentlyUsed,
     LeastFrequentlyUsed,
     TimeToLive,
+    FirstInFirstOut,
+    WindowTinyLfu,
+}
+
+// What a store does when it would take the cache past its size limit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CapacityPolicy {
+    // Evict entries in eviction policy order to make room
+    EvictToFit,
+    // Keep what is cached and reject the new entry
+    RejectNew,
+    // Hand the new entry to the cache's `SpillStore`, rejecting it if there is none
+    SpillToSecondary,
 }
 
 // Cache trait to implement with enhanced requirements
@@ -71,9 +317,53 @@ pub trait AvailabilityCache: Send + Sync + 'static {
     where
         Self: Sized;
 
-    // Store availability data for a hotel on specific dates
+    // Store availability data under `key`, labelled with `tags` (e.g. supplier ID,
+    // destination code, rate plan) for `invalidate_by_tag`
+    // TTL and return value as for `store_key`
+    fn store_tagged(
+        &self,
+        key: CacheKey,
+        data: Vec<u8>,
+        ttl: Option<Duration>,
+        tags: &[&str],
+    ) -> bool;
+
+    // Store availability data under `key`
     // TTL specifies how long the item should remain in the cache (None uses default from config)
     // Returns true if stored successfully, false if rejected (e.g., capacity limits)
+    fn store_key(&self, key: CacheKey, data: Vec<u8>, ttl: Option<Duration>) -> bool {
+        self.store_tagged(key, data, ttl, &[])
+    }
+
+    // Remember that the supplier returned no availability for `key`, so repeated searches
+    // don't re-query it. TTL None uses `negative_ttl_seconds` from config
+    fn store_empty(&self, key: CacheKey, ttl: Option<Duration>) -> bool;
+
+    // Look up `key`, telling known-empty entries apart from unknown keys
+    fn lookup(&self, key: &CacheKey) -> CacheLookup;
+
+    // Retrieve availability data if it exists and is not expired
+    // The bool in the tuple indicates if this was a cache hit
+    // Negative entries carry no data and are returned as None, use `lookup` to see them
+    fn get_key(&self, key: &CacheKey) -> Option<(Vec<u8>, bool)> {
+        self.lookup(key).into_data().map(|data| (data, true))
+    }
+
+    // Like `lookup`, also returning the version of the entry (`CacheVersion::ABSENT` on a miss)
+    fn get_versioned(&self, key: &CacheKey) -> (CacheLookup, CacheVersion);
+
+    // Optimistic concurrency for workers refreshing the same key: store only if the entry
+    // still has `version` as returned by `get_versioned` (ABSENT: only if there is none),
+    // so a slower response can't overwrite a newer one. Returns false on a version conflict
+    fn store_if_version(
+        &self,
+        key: CacheKey,
+        data: Vec<u8>,
+        ttl: Option<Duration>,
+        version: CacheVersion,
+    ) -> bool;
+
+    // Store availability data for a hotel on specific dates (no extra key dimensions)
     fn store(
         &self,
         hotel_id: &str,
@@ -81,11 +371,47 @@ pub trait AvailabilityCache: Send + Sync + 'static {
         check_out: &str,
         data: Vec<u8>,
         ttl: Option<Duration>,
-    ) -> bool;
+    ) -> bool {
+        self.store_key(CacheKey::new(hotel_id, check_in, check_out), data, ttl)
+    }
 
-    // Retrieve availability data if it exists and is not expired
-    // The bool in the tuple indicates if this was a cache hit
-    fn get(&self, hotel_id: &str, check_in: &str, check_out: &str) -> Option<(Vec<u8>, bool)>;
+    // Retrieve availability data for a hotel on specific dates (no extra key dimensions)
+    fn get(&self, hotel_id: &str, check_in: &str, check_out: &str) -> Option<(Vec<u8>, bool)> {
+        self.get_key(&CacheKey::new(hotel_id, check_in, check_out))
+    }
+
+    // Like `get_key`, but returns a shared handle to the value instead of a copy
+    // In-memory backends hand out the stored bytes themselves (unless compressed), so reading a
+    // multi-megabyte blob doesn't allocate; other backends fall back to a copy
+    fn get_ref(&self, key: &CacheKey) -> Option<Arc<[u8]>> {
+        self.lookup(key).into_data().map(Arc::from)
+    }
+
+    // Look up a batch of keys, results are in the order of `keys`
+    // Backends override this to serve the whole batch with one lock or round trip
+    fn get_many_keys(&self, keys: &[CacheKey]) -> Vec<CacheLookup> {
+        keys.iter().map(|key| self.lookup(key)).collect()
+    }
+
+    // Retrieve availability for many hotels/dates at once (no extra key dimensions)
+    fn get_many(&self, keys: &[(&str, &str, &str)]) -> Vec<Option<(Vec<u8>, bool)>> {
+        let keys: Vec<CacheKey> = keys
+            .iter()
+            .map(|(hotel_id, check_in, check_out)| CacheKey::new(*hotel_id, *check_in, *check_out))
+            .collect();
+        self.get_many_keys(&keys)
+            .into_iter()
+            .map(|found| found.into_data().map(|data| (data, true)))
+            .collect()
+    }
+
+    // Store a batch of entries with the same TTL, returns per entry whether it was stored
+    fn store_many(&self, entries: Vec<(CacheKey, Vec<u8>)>, ttl: Option<Duration>) -> Vec<bool> {
+        entries
+            .into_iter()
+            .map(|(key, data)| self.store_key(key, data, ttl))
+            .collect()
+    }
 
     // Get cache statistics
     fn stats(&self) -> CacheStatsReport;
@@ -93,268 +419,1183 @@ pub trait AvailabilityCache: Send + Sync + 'static {
     // Set the eviction policy to use
     fn set_eviction_policy(&self, policy: EvictionPolicy);
 
+    // Store availability priced for a specific market, currency and occupancy
+    // The context is part of the key, so each combination is cached separately
+    fn store_in_context(
+        &self,
+        hotel_id: &str,
+        check_in: &str,
+        check_out: &str,
+        context: Option<&SearchContext>,
+        data: Vec<u8>,
+        ttl: Option<Duration>,
+    ) -> bool {
+        let key = CacheKey::new(hotel_id, check_in, check_out);
+        let key = match context {
+            Some(context) => key.with_context(context),
+            None => key,
+        };
+        self.store_key(key, data, ttl)
+    }
+
+    fn get_in_context(
+        &self,
+        hotel_id: &str,
+        check_in: &str,
+        check_out: &str,
+        context: Option<&SearchContext>,
+    ) -> Option<(Vec<u8>, bool)> {
+        let key = CacheKey::new(hotel_id, check_in, check_out);
+        let key = match context {
+            Some(context) => key.with_context(context),
+            None => key,
+        };
+        self.get_key(&key)
+    }
+
     // Prefetch data for given keys - useful for warming the cache ahead of expected demand
-    fn prefetch(&self, keys: Vec<(String, String, String)>, ttl: Option<Duration>) -> usize;
+    // Data is fetched through the backend's `CacheLoader`; returns the number of keys stored
+    fn prefetch_keys(&self, keys: Vec<CacheKey>, ttl: Option<Duration>) -> usize;
+
+    // Prefetch by (hotel_id, check_in, check_out)
+    fn prefetch(&self, keys: Vec<(String, String, String)>, ttl: Option<Duration>) -> usize {
+        let keys = keys
+            .into_iter()
+            .map(|(hotel_id, check_in, check_out)| CacheKey::new(hotel_id, check_in, check_out))
+            .collect();
+        self.prefetch_keys(keys, ttl)
+    }
 
     // Bulk invalidate entries matching a pattern
     // For example, invalidate all entries for a specific hotel
+    // Entries are removed in every search context
     fn invalidate(
         &self,
         hotel_id: Option<&str>,
         check_in: Option<&str>,
         check_out: Option<&str>,
+    ) -> usize {
+        self.invalidate_in_context(hotel_id, check_in, check_out, None)
+    }
+
+    // Like `invalidate`, additionally restricted to keys carrying every dimension set in
+    // `context`, e.g. all EUR prices of a hotel regardless of market and occupancy
+    fn invalidate_in_context(
+        &self,
+        hotel_id: Option<&str>,
+        check_in: Option<&str>,
+        check_out: Option<&str>,
+        context: Option<&SearchContext>,
     ) -> usize;
 
+    // Remove every entry stored with `tag`, returns the number of entries removed
+    fn invalidate_by_tag(&self, tag: &str) -> usize;
+
+    // Keys of the entries stored with `tag`, the ones `invalidate_by_tag` would remove
+    fn tagged_keys(&self, tag: &str) -> Vec<CacheKey>;
+
     // Resize the cache (this might drop items if downsizing)
     fn resize(&self, new_max_size_mb: usize) -> bool;
+
+    // Stop background work and save what should survive a restart, e.g. before a service
+    // exits. The cache stays readable; stores after shutdown are only kept in memory.
+    // Calling it again does nothing; backends without background work have nothing to do
+    fn shutdown(&self) -> std::io::Result<()> {
+        Ok(())
+    }
 }
 
 // Helper function to create a cache key (you may modify this as needed)
-pub fn create_cache_key(hotel_id: &str, check_in: &str, check_out: &str) -> String {
-    format!("{}:{}:{}", hotel_id, check_in, check_out)
+pub fn create_cache_key(hotel_id: &str, check_in: &str, check_out: &str) -> CacheKey {
+    CacheKey::new(hotel_id, check_in, check_out)
 }
 
-// Optional: Helper for calculating item size - implement if useful for your solution
-pub fn calculate_item_size(key: &str, data: &[u8]) -> usize {
-    key.len() + data.len() + std::mem::size_of::<Instant>() // Add more fields as needed for your implementation
+// Estimated memory needed to keep `data` under `key`, see `EstimateSize`
+pub fn calculate_item_size(key: &CacheKey, data: &[u8]) -> usize {
+    key.estimated_size() + data.len() + std::mem::size_of::<Instant>()
 }
 
 pub struct ExampleCache {
-    cache: Arc<Mutex<HashMap<String, CacheEntry>>>,
+    cache: Arc<Mutex<HashMap<CacheKey, CacheEntry>>>,
     config: Arc<Mutex<CacheConfig>>,
+    eviction: RwLock<Arc<dyn EvictionStrategy>>,
+    listeners: CacheListeners,
+    loader: Option<Arc<dyn CacheLoader>>,
+    // Takes over stores that don't fit, see `CapacityPolicy::SpillToSecondary`
+    spill: Option<Arc<dyn SpillStore>>,
+    // Source of entry versions, shared by all keys so a replaced entry never reuses one
+    next_version: AtomicU64,
     stats: CacheStats,
+    quotas: QuotaTracker,
+    admission: Option<AdmissionFilter>,
+    max_idle: Option<Duration>,
+    // Mirrors inserts and removals to a persistent store, see `with_write_behind`
+    write_behind: Option<WriteBehind>,
+    // Set by the first `shutdown`, so dropping a shut down cache doesn't persist again
+    shut_down: AtomicBool,
 }
 
 struct CacheEntry {
-    data: Vec<u8>,
+    // Stored bytes, compressed with `compression`; shared with `get_ref` callers
+    data: Arc<[u8]>,
+    compression: Compression,
+    raw_size: usize,
+    // Bytes accounted against the cache size, fixed when the entry is created so adding
+    // and removing it always moves `size_bytes` by the same amount
+    size: usize,
+    tags: Vec<String>,
+    // Known "no availability" marker without data, see `store_empty`
+    negative: bool,
+    // Assigned when the entry is inserted
+    version: u64,
     created_at: Instant,
     ttl: Duration,
+    // Set from the cache config when the entry is inserted
+    max_idle: Option<Duration>,
     access_count: usize,
     last_accessed: Instant,
 }
 
 impl CacheEntry {
+    fn new(
+        key: &CacheKey,
+        data: Vec<u8>,
+        ttl: Duration,
+        tags: Vec<String>,
+        compression: Compression,
+        threshold: usize,
+    ) -> Self {
+        let raw_size = data.len();
+        let (data, compression) = compression.compress_above(data, threshold);
+        let now = Instant::now();
+        let mut entry = Self {
+            data: data.into(),
+            compression,
+            raw_size,
+            size: 0,
+            tags,
+            negative: false,
+            version: 0,
+            created_at: now,
+            ttl,
+            max_idle: None,
+            access_count: 0,
+            last_accessed: now,
+        };
+        // The map slot holds key and entry inline, both own further heap allocations
+        entry.size =
+            hash_map_slot_size::<CacheKey, CacheEntry>() + key.heap_size() + entry.heap_size();
+        entry
+    }
+
+    fn negative(key: &CacheKey, ttl: Duration) -> Self {
+        Self {
+            negative: true,
+            ..Self::new(key, Vec::new(), ttl, Vec::new(), Compression::None, 0)
+        }
+    }
+
     fn is_expired(&self) -> bool {
         self.created_at.elapsed() > self.ttl
+            || self
+                .max_idle
+                .is_some_and(|max_idle| self.last_accessed.elapsed() > max_idle)
+    }
+
+    // Bytes accounted against the cache size
+    fn size(&self) -> usize {
+        self.size
+    }
+
+    // The value as it was passed to `store`
+    fn value(&self) -> std::io::Result<Vec<u8>> {
+        self.compression.decompress(&self.data)
+    }
+
+    fn remaining_ttl(&self) -> Duration {
+        self.ttl.saturating_sub(self.created_at.elapsed())
+    }
+
+    // When the entry expires unless it is read before
+    fn expires_at(&self) -> Instant {
+        let expires_at = self.created_at + self.ttl;
+        self.max_idle.map_or(expires_at, |max_idle| {
+            expires_at.min(self.last_accessed + max_idle)
+        })
+    }
+
+    fn eviction_candidate<'a>(&self, key: &'a CacheKey) -> EvictionCandidate<'a> {
+        EvictionCandidate {
+            key,
+            size: self.size(),
+            created_at: self.created_at,
+            last_accessed: self.last_accessed,
+            access_count: self.access_count,
+            expires_at: self.expires_at(),
+        }
+    }
+
+    fn event(&self, key: CacheKey) -> EntryEvent {
+        EntryEvent {
+            size: self.size(),
+            access_count: self.access_count,
+            last_accessed: self.last_accessed,
+            key,
+        }
+    }
+}
+
+impl EstimateSize for CacheEntry {
+    fn heap_size(&self) -> usize {
+        self.data.heap_size() + self.tags.heap_size()
     }
 }
 
 impl ExampleCache {
-    fn remove_oldest_entry(&self) {
-        let cache = self.cache.lock().unwrap();
-        let policy = self.config.lock().unwrap().eviction_policy;
-
-        let oldest_key = match policy {
-            EvictionPolicy::LeastRecentlyUsed => cache
-                .iter()
-                .min_by(|stat1, stat2| stat1.1.access_count.cmp(&stat2.1.access_count))
-                .map(|(k, _)| k.clone()),
-            EvictionPolicy::LeastFrequentlyUsed => cache
-                .iter()
-                .min_by(|stat1, stat2| stat1.1.last_accessed.cmp(&stat2.1.last_accessed))
-                .map(|(k, _)| k.clone()),
-            EvictionPolicy::TimeToLive => cache
-                .iter()
-                .min_by(|stat1, stat2| stat1.1.created_at.cmp(&stat2.1.created_at))
-                .map(|(k, _)| k.clone()),
+    // Save all live entries to `persistence_path` (if configured), returns the number written
+    pub fn persist(&self) -> std::io::Result<usize> {
+        let Some(path) = self.config.lock().unwrap().persistence_path.clone() else {
+            return Ok(0);
         };
-        drop(cache);
 
-        if let Some(oldest_key) = oldest_key {
-            self.remove_entry(oldest_key, false);
-        }
+        // Values are written uncompressed so the file doesn't depend on the codec settings
+        // Negative entries are short-lived and not worth keeping across restarts
+        let entries = self
+            .cache
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, entry)| !entry.is_expired() && !entry.negative)
+            .map(|(key, entry)| {
+                Ok(PersistedEntry {
+                    key: key.to_string(),
+                    data: entry.value()?,
+                    remaining_ttl: entry.remaining_ttl(),
+                    tags: entry.tags.clone(),
+                })
+            })
+            .collect::<std::io::Result<Vec<PersistedEntry>>>()?;
+
+        persistence::save_entries(&path, &entries)?;
+        Ok(entries.len())
     }
 
-    fn remove_entry(&self, key: String, expired: bool) {
+    // Warm restart: reload entries saved by a previous instance, up to the size limit
+    fn restore(&self, path: &Path) {
+        let entries = match persistence::load_entries(path) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return,
+            Err(e) => {
+                tracing::warn!("Ignoring cache file {}: {}", path.display(), e);
+                return;
+            }
+        };
+
+        let (max_size_bytes, compression, threshold) = {
+            let config = self.config.lock().unwrap();
+            (
+                config.max_size_mb * 1024 * 1024,
+                config.compression,
+                config.compression_threshold_bytes,
+            )
+        };
         let mut cache = self.cache.lock().unwrap();
-        if let Some(removed_data) = cache.remove(&key) {
-            self.stats.size_bytes.fetch_sub(
-                calculate_item_size(&key, &removed_data.data),
-                Ordering::SeqCst,
+        for entry in entries {
+            let key: CacheKey = match entry.key.parse() {
+                Ok(key) => key,
+                Err(e) => {
+                    tracing::warn!("Skipping persisted entry: {}", e);
+                    continue;
+                }
+            };
+            let cache_entry = CacheEntry::new(
+                &key,
+                entry.data,
+                entry.remaining_ttl,
+                entry.tags,
+                compression,
+                threshold,
             );
-            self.stats.eviction_count.fetch_add(1, Ordering::SeqCst);
-            self.stats.items_count.fetch_sub(1, Ordering::SeqCst);
-
-            if expired {
-                self.stats.expired_count.fetch_add(1, Ordering::SeqCst);
+            let item_size = cache_entry.size();
+            if self.stats.size_bytes.load(Ordering::SeqCst) + item_size > max_size_bytes {
+                break;
             }
+
+            let mut cache_entry = cache_entry;
+            cache_entry.version = self.next_version.fetch_add(1, Ordering::SeqCst);
+            cache_entry.max_idle = self.max_idle;
+            self.stats.items_count.fetch_add(1, Ordering::SeqCst);
+            self.account_added(&cache_entry);
+            self.quotas
+                .account_added(&key, &cache_entry.tags, cache_entry.size());
+            cache.insert(key, cache_entry);
         }
     }
 
-    fn store_lookup_time(&self, now: Instant) {
-        let duration_ns: u64 = now.elapsed().as_nanos() as u64;
-        let total_lookups = self.stats.total_lookups.load(Ordering::SeqCst);
-        let current_avg = self.stats.average_lookup_time_ns.load(Ordering::SeqCst);
-
-        let new_avg = if total_lookups == 1 {
-            duration_ns
-        } else {
-            (current_avg * (total_lookups as u64 - 1) + duration_ns) / (total_lookups as u64)
-        };
+    fn account_added(&self, entry: &CacheEntry) {
+        self.stats
+            .size_bytes
+            .fetch_add(entry.size(), Ordering::SeqCst);
+        self.stats
+            .raw_bytes
+            .fetch_add(entry.raw_size, Ordering::SeqCst);
+        self.stats
+            .compressed_bytes
+            .fetch_add(entry.data.len(), Ordering::SeqCst);
+    }
 
+    fn account_removed(&self, entry: &CacheEntry) {
+        self.stats
+            .size_bytes
+            .fetch_sub(entry.size(), Ordering::SeqCst);
+        self.stats
+            .raw_bytes
+            .fetch_sub(entry.raw_size, Ordering::SeqCst);
         self.stats
-            .average_lookup_time_ns
-            .store(new_avg, Ordering::SeqCst);
+            .compressed_bytes
+            .fetch_sub(entry.data.len(), Ordering::SeqCst);
     }
-}
 
-impl AvailabilityCache for ExampleCache {
-    fn new(config: CacheConfig) -> Self {
-        Self {
-            cache: Arc::new(Mutex::new(HashMap::new())),
-            config: Arc::new(Mutex::new(config)),
-            stats: CacheStats::default(),
+    // Create a cache that fetches prefetched keys through `loader`
+    pub fn with_loader(config: CacheConfig, loader: impl CacheLoader + 'static) -> Self {
+        let mut cache = Self::new(config);
+        cache.loader = Some(Arc::new(loader));
+        cache
+    }
+
+    // Create a cache handing stores that don't fit to `spill`, used with
+    // `CapacityPolicy::SpillToSecondary`
+    pub fn with_spill_store(config: CacheConfig, spill: impl SpillStore + 'static) -> Self {
+        let mut cache = Self::new(config);
+        cache.spill = Some(Arc::new(spill));
+        cache
+    }
+
+    // Create a cache mirrored to `store` in the background, first rebuilt from what the store
+    // holds (entries that don't fit are skipped)
+    pub fn with_write_behind(
+        config: CacheConfig,
+        store: impl PersistentStore + 'static,
+        write_behind: WriteBehindConfig,
+    ) -> Self {
+        let mut cache = Self::new(config);
+        match store.load() {
+            Ok(entries) => {
+                let restored = cache.import_snapshot(
+                    entries
+                        .into_iter()
+                        .map(StoredEntry::into_snapshot)
+                        .collect(),
+                );
+                tracing::info!("Rebuilt {} cache entries from persistent store", restored);
+            }
+            Err(e) => tracing::warn!("Starting with an empty cache, store not readable: {}", e),
         }
+        cache.write_behind = Some(WriteBehind::start(Arc::new(store), write_behind));
+        cache
     }
 
-    fn store(
-        &self,
-        hotel_id: &str,
-        check_in: &str,
-        check_out: &str,
-        data: Vec<u8>,
-        ttl: Option<Duration>,
-    ) -> bool {
-        println!("Storing data for {} {}-{}", hotel_id, check_in, check_out);
+    // Wait until all inserts and removals so far were written to the persistent store
+    pub fn flush_write_behind(&self) {
+        if let Some(write_behind) = &self.write_behind {
+            write_behind.flush();
+        }
+    }
 
-        let default_ttl_seconds = self.config.lock().unwrap().default_ttl_seconds;
-        let max_size_mb = self.config.lock().unwrap().max_size_mb;
-        let key = create_cache_key(hotel_id, check_in, check_out);
-        let ttl = ttl.unwrap_or_else(|| Duration::from_secs(default_ttl_seconds));
+    pub fn write_behind_stats(&self) -> Option<WriteBehindStats> {
+        self.write_behind.as_ref().map(WriteBehind::stats)
+    }
 
-        // Simple size check (not perfect but demonstrates the concept)
-        let item_size = calculate_item_size(&key, &data);
-        let max_size_bytes = max_size_mb * 1024 * 1024;
+    // Create a cache using a custom eviction strategy instead of `config.eviction_policy`
+    pub fn with_eviction_strategy(
+        config: CacheConfig,
+        strategy: Box<dyn EvictionStrategy>,
+    ) -> Self {
+        let cache = Self::new(config);
+        *cache.eviction.write().unwrap() = Arc::from(strategy);
+        cache
+    }
+
+    // Called for every entry evicted to make room, after the cache lock was released
+    pub fn on_evict(&self, listener: impl Fn(&EntryEvent) + Send + Sync + 'static) {
+        self.listeners.on_evict(listener);
+    }
+
+    // Called for every entry removed because its TTL ran out
+    pub fn on_expire(&self, listener: impl Fn(&EntryEvent) + Send + Sync + 'static) {
+        self.listeners.on_expire(listener);
+    }
+
+    // Called for every store the cache refused
+    pub fn on_reject(&self, listener: impl Fn(&EntryEvent) + Send + Sync + 'static) {
+        self.listeners.on_reject(listener);
+    }
+
+    fn eviction_strategy(&self) -> Arc<dyn EvictionStrategy> {
+        Arc::clone(&self.eviction.read().unwrap())
+    }
+
+    // Evict entries in eviction order until the cache holds at most `target_bytes`
+    // The whole victim set is chosen and removed under a single lock acquisition
+    fn evict_down_to(&self, target_bytes: usize) -> usize {
+        let mut cache = self.cache.lock().unwrap();
         let current_size_bytes = self.stats.size_bytes.load(Ordering::SeqCst);
+        if current_size_bytes <= target_bytes {
+            return 0;
+        }
 
-        if current_size_bytes + item_size > max_size_bytes {
-            println!(
-                "Cache size limit exceeded ({} + {} > {}), evicting oldest entry",
-                current_size_bytes, item_size, max_size_bytes
-            );
-            self.remove_oldest_entry();
+        let strategy = self.eviction_strategy();
+        let mut candidates: Vec<EvictionCandidate> = cache
+            .iter()
+            .map(|(key, entry)| entry.eviction_candidate(key))
+            .collect();
+        strategy.order(&mut candidates);
+
+        let bytes_to_free = current_size_bytes - target_bytes;
+        let mut freed = 0;
+        let mut victims = Vec::new();
+        for candidate in &candidates {
+            if freed >= bytes_to_free {
+                break;
+            }
+            freed += candidate.size;
+            victims.push(candidate.key.clone());
         }
+        drop(candidates);
 
-        println!("Inserting item of size {} bytes into cache", item_size);
+        let evicted: Vec<EntryEvent> = victims
+            .into_iter()
+            .filter_map(|key| {
+                let removed = self.remove_locked(&mut cache, &key, false)?;
+                Some(removed.event(key))
+            })
+            .collect();
+        drop(cache); // Listeners run outside the lock
 
-        let entry = CacheEntry {
-            data,
-            created_at: Instant::now(),
-            ttl,
-            access_count: 0,
-            last_accessed: Instant::now(),
-        };
-        self.cache.lock().unwrap().insert(key.clone(), entry);
-        self.stats.items_count.fetch_add(1, Ordering::SeqCst);
-        self.stats.size_bytes.fetch_add(item_size, Ordering::SeqCst);
+        self.listeners.notify_evicted(&evicted);
+        evicted.len()
+    }
 
-        true
+    // Build the entry for a store, TTL None uses the configured default
+    // Values are compressed here, before taking any lock; capacity is accounted in stored bytes
+    fn new_entry(
+        &self,
+        key: &CacheKey,
+        data: Vec<u8>,
+        ttl: Option<Duration>,
+        tags: &[&str],
+    ) -> CacheEntry {
+        let (default_ttl_seconds, compression, threshold) = {
+            let config = self.config.lock().unwrap();
+            (
+                config.default_ttl_seconds,
+                config.compression,
+                config.compression_threshold_bytes,
+            )
+        };
+        let ttl = ttl.unwrap_or_else(|| Duration::from_secs(default_ttl_seconds));
+        let tags = tags.iter().map(|tag| tag.to_string()).collect();
+        CacheEntry::new(key, data, ttl, tags, compression, threshold)
     }
 
-    fn get(&self, hotel_id: &str, check_in: &str, check_out: &str) -> Option<(Vec<u8>, bool)> {
+    // A lookup, optionally also returning the metadata of the entry found
+    // `decode` turns the stored bytes into the value handed out, after the lock is released
+    fn read<V>(
+        &self,
+        key: &CacheKey,
+        with_metadata: bool,
+        decode: impl FnOnce(&Compression, &Arc<[u8]>) -> std::io::Result<V>,
+    ) -> (CacheLookup<V>, CacheVersion, Option<EntryMetadata>) {
         let now = Instant::now();
-        let key = create_cache_key(hotel_id, check_in, check_out);
 
         self.stats.total_lookups.fetch_add(1, Ordering::SeqCst);
 
         let mut cache = self.cache.lock().unwrap();
-        if let Some(entry) = cache.get_mut(&key) {
+        if let Some(entry) = cache.get_mut(key) {
             if entry.is_expired() {
                 drop(cache); // Release lock before calling remove_entry
-                self.remove_entry(key, true);
-                self.store_lookup_time(now);
-                return None;
+                if let Some(removed) = self.remove_entry(key, true) {
+                    self.listeners.notify_expired(&[removed.event(key.clone())]);
+                }
+                self.stats.record_lookup_time(now);
+                return (CacheLookup::Miss, CacheVersion::ABSENT, None);
             }
 
             entry.access_count += 1;
             entry.last_accessed = Instant::now();
-            self.stats.hit_count.fetch_add(1, Ordering::SeqCst);
-            self.store_lookup_time(now);
-            Some((entry.data.clone(), true))
+            let (data, compression, negative, version) = (
+                Arc::clone(&entry.data),
+                entry.compression,
+                entry.negative,
+                CacheVersion(entry.version),
+            );
+            let metadata = with_metadata.then(|| {
+                EntryMetadata::new(key, &entry.tags, entry.created_at, entry.ttl, entry.size())
+            });
+            drop(cache); // Decompress without holding the lock
+            self.eviction_strategy().record_access(key);
+
+            let found = if negative {
+                self.stats.hit_count.fetch_add(1, Ordering::SeqCst);
+                self.stats.negative_hit_count.fetch_add(1, Ordering::SeqCst);
+                CacheLookup::HitEmpty
+            } else {
+                match decode(&compression, &data) {
+                    Ok(data) => {
+                        self.stats.hit_count.fetch_add(1, Ordering::SeqCst);
+                        CacheLookup::Hit(data)
+                    }
+                    Err(e) => {
+                        tracing::warn!("Dropping undecodable cache entry {}: {}", key, e);
+                        self.remove_entry(key, false);
+                        self.stats.miss_count.fetch_add(1, Ordering::SeqCst);
+                        CacheLookup::Miss
+                    }
+                }
+            };
+            self.stats.record_lookup_time(now);
+            if found.is_hit() {
+                (found, version, metadata)
+            } else {
+                (found, CacheVersion::ABSENT, None)
+            }
         } else {
             self.stats.miss_count.fetch_add(1, Ordering::SeqCst);
-            self.store_lookup_time(now);
-            None
+            self.stats.record_lookup_time(now);
+            (CacheLookup::Miss, CacheVersion::ABSENT, None)
         }
     }
 
-    fn stats(&self) -> CacheStatsReport {
-        CacheStatsReport {
-            size_bytes: self.stats.size_bytes.load(Ordering::SeqCst),
-            items_count: self.stats.items_count.load(Ordering::SeqCst),
-            hit_count: self.stats.hit_count.load(Ordering::SeqCst),
-            miss_count: self.stats.miss_count.load(Ordering::SeqCst),
-            eviction_count: self.stats.eviction_count.load(Ordering::SeqCst),
-            expired_count: self.stats.expired_count.load(Ordering::SeqCst),
-            rejected_count: self.stats.rejected_count.load(Ordering::SeqCst),
-            average_lookup_time_ns: self.stats.average_lookup_time_ns.load(Ordering::SeqCst),
-            total_lookups: self.stats.total_lookups.load(Ordering::SeqCst),
+    // Store a prepared entry if the admission filter lets it in
+    fn store_entry(
+        &self,
+        key: CacheKey,
+        entry: CacheEntry,
+        expected: Option<CacheVersion>,
+    ) -> bool {
+        if self.admission.is_some() && !self.admitted_locked(&self.cache.lock().unwrap(), &key) {
+            self.reject(key, entry);
+            return false;
         }
+        self.insert_entry(key, entry, expected)
     }
 
-    fn set_eviction_policy(&self, policy: EvictionPolicy) {
-        let mut config = self.config.lock().unwrap();
-        config.eviction_policy = policy;
+    // Insert a prepared entry, evicting to make room if needed
+    // With `expected` set, the entry is only inserted if the current one has that version
+    fn insert_entry(
+        &self,
+        key: CacheKey,
+        entry: CacheEntry,
+        expected: Option<CacheVersion>,
+    ) -> bool {
+        let (max_size_bytes, high_watermark, low_watermark) = self.size_limits();
+        let capacity_policy = self.config.lock().unwrap().capacity_policy;
+
+        // Simple size check (not perfect but demonstrates the concept)
+        let item_size = entry.size();
+        let current_size_bytes = self.stats.size_bytes.load(Ordering::SeqCst);
+
+        // Evicting everything still wouldn't make room
+        if item_size > max_size_bytes {
+            self.overflow(key, entry);
+            return false;
+        }
+
+        if capacity_policy == CapacityPolicy::EvictToFit
+            && current_size_bytes + item_size > high_watermark
+        {
+            // Make room for a burst of writes at once instead of evicting on every store
+            let evicted = self.evict_down_to(low_watermark.saturating_sub(item_size));
+            println!(
+                "Cache high watermark exceeded ({} + {} > {}), evicted {} entries",
+                current_size_bytes, item_size, high_watermark, evicted
+            );
+
+            // Concurrent stores may have refilled the space freed above
+            if self.stats.size_bytes.load(Ordering::SeqCst) + item_size > max_size_bytes {
+                self.overflow(key, entry);
+                return false;
+            }
+        }
+
+        println!("Inserting item of size {} bytes into cache", item_size);
+
+        // The version check and the insert happen under the same lock
+        let mut cache = self.cache.lock().unwrap();
+        if let Some(expected) = expected {
+            let current = cache
+                .get(&key)
+                .filter(|entry| !entry.is_expired())
+                .map_or(CacheVersion::ABSENT, |entry| CacheVersion(entry.version));
+            if current != expected {
+                drop(cache);
+                self.stats
+                    .version_conflict_count
+                    .fetch_add(1, Ordering::SeqCst);
+                return false;
+            }
+        }
+        // Concurrent stores may have used up the room checked for above
+        let replaced_size = cache.get(&key).map_or(0, CacheEntry::size);
+        if self.stats.size_bytes.load(Ordering::SeqCst) - replaced_size + item_size > max_size_bytes
+        {
+            drop(cache);
+            self.overflow(key, entry);
+            return false;
+        }
+        if !self.admit_locked(&cache, &key, &entry) {
+            drop(cache);
+            self.reject(key, entry);
+            return false;
+        }
+
+        self.insert_locked(&mut cache, key, entry);
+        true
     }
 
-    fn prefetch(&self, keys: Vec<(String, String, String)>, ttl: Option<Duration>) -> usize {
-        // Simple implementation - in real system this would trigger backend calls
-        let mut count = 0;
-        for (hotel_id, check_in, check_out) in keys {
-            // Simulate fetching data
-            let dummy_data = vec![1, 2, 3, 4, 5];
-            if self.store(&hotel_id, &check_in, &check_out, dummy_data, ttl) {
-                count += 1;
+    fn insert_locked(
+        &self,
+        cache: &mut HashMap<CacheKey, CacheEntry>,
+        key: CacheKey,
+        mut entry: CacheEntry,
+    ) {
+        entry.version = self.next_version.fetch_add(1, Ordering::SeqCst);
+        entry.max_idle = self.max_idle;
+        self.eviction_strategy().record_insert(&key);
+        self.account_added(&entry);
+        self.quotas.account_added(&key, &entry.tags, entry.size());
+        if let Some(write_behind) = &self.write_behind {
+            write_behind.send(Queued::Put {
+                key: key.clone(),
+                data: Arc::clone(&entry.data),
+                compression: entry.compression,
+                expires_at: SystemTime::now() + entry.remaining_ttl(),
+                tags: entry.tags.clone(),
+                negative: entry.negative,
+            });
+        }
+        match cache.entry(key) {
+            // Overwriting an entry replaces its size instead of adding a new item
+            hash_map::Entry::Occupied(mut occupied) => {
+                let old = occupied.insert(entry);
+                self.account_removed(&old);
+                self.quotas
+                    .account_removed(occupied.key(), &old.tags, old.size());
+            }
+            hash_map::Entry::Vacant(vacant) => {
+                vacant.insert(entry);
+                self.stats.items_count.fetch_add(1, Ordering::SeqCst);
             }
         }
-        count
     }
 
-    fn invalidate(
+    // Whether `entry` fits the quotas it falls under, counting the entry it replaces as freed
+    fn admit_locked(
         &self,
-        hotel_id: Option<&str>,
+        cache: &HashMap<CacheKey, CacheEntry>,
+        key: &CacheKey,
+        entry: &CacheEntry,
+    ) -> bool {
+        let replaced = cache.get(key).map(|old| (old.size(), old.tags.as_slice()));
+        self.quotas.admit(key, &entry.tags, entry.size(), replaced)
+    }
+
+    // Whether the admission filter lets a store of `key` in; updates of cached keys always pass
+    fn admitted_locked(&self, cache: &HashMap<CacheKey, CacheEntry>, key: &CacheKey) -> bool {
+        self.admission.as_ref().is_none_or(|admission| {
+            cache.get(key).is_some_and(|entry| !entry.is_expired()) || admission.admit(key)
+        })
+    }
+
+    // (hard limit, high watermark, low watermark) in bytes
+    fn size_limits(&self) -> (usize, usize, usize) {
+        let config = self.config.lock().unwrap();
+        (
+            config.max_size_mb * 1024 * 1024,
+            high_watermark_bytes(&config),
+            low_watermark_bytes(&config),
+        )
+    }
+
+    fn reject(&self, key: CacheKey, entry: CacheEntry) {
+        self.stats.rejected_count.fetch_add(1, Ordering::SeqCst);
+        self.listeners.notify_rejected(&[entry.event(key)]);
+    }
+
+    // A store there is no room for: spilled if the capacity policy says so, rejected otherwise
+    fn overflow(&self, key: CacheKey, entry: CacheEntry) {
+        let capacity_policy = self.config.lock().unwrap().capacity_policy;
+        if let (CapacityPolicy::SpillToSecondary, Some(spill)) = (capacity_policy, &self.spill) {
+            match entry.value() {
+                Ok(data) => {
+                    let spilled = spill.spill(SnapshotEntry {
+                        key: key.clone(),
+                        data,
+                        remaining_ttl: entry.remaining_ttl(),
+                        tags: entry.tags.clone(),
+                        negative: entry.negative,
+                    });
+                    if spilled {
+                        self.stats.spilled_count.fetch_add(1, Ordering::SeqCst);
+                        return;
+                    }
+                }
+                Err(e) => tracing::warn!("Not spilling undecodable entry {}: {}", key, e),
+            }
+        }
+        self.reject(key, entry);
+    }
+
+    fn remove_entry(&self, key: &CacheKey, expired: bool) -> Option<CacheEntry> {
+        let mut cache = self.cache.lock().unwrap();
+        self.remove_locked(&mut cache, key, expired)
+    }
+
+    fn remove_locked(
+        &self,
+        cache: &mut HashMap<CacheKey, CacheEntry>,
+        key: &CacheKey,
+        expired: bool,
+    ) -> Option<CacheEntry> {
+        let removed_data = cache.remove(key)?;
+        self.account_removed(&removed_data);
+        self.quotas
+            .account_removed(key, &removed_data.tags, removed_data.size());
+        if let Some(write_behind) = &self.write_behind {
+            write_behind.send(Queued::Remove(key.clone()));
+        }
+        self.stats.eviction_count.fetch_add(1, Ordering::SeqCst);
+        self.stats.items_count.fetch_sub(1, Ordering::SeqCst);
+
+        if expired {
+            self.stats.expired_count.fetch_add(1, Ordering::SeqCst);
+        }
+        Some(removed_data)
+    }
+}
+
+// Size above which stores start evicting
+fn high_watermark_bytes(config: &CacheConfig) -> usize {
+    config.max_size_mb * 1024 * 1024 / 100 * config.high_watermark_percent.min(100)
+}
+
+// Size the cache is evicted down to once the high watermark is hit
+fn low_watermark_bytes(config: &CacheConfig) -> usize {
+    config.max_size_mb * 1024 * 1024 / 100
+        * config
+            .low_watermark_percent
+            .min(config.high_watermark_percent)
+            .min(100)
+}
+
+impl AvailabilityCache for ExampleCache {
+    fn new(config: CacheConfig) -> Self {
+        let persistence_path = config.persistence_path.clone();
+        let cache = Self {
+            quotas: QuotaTracker::new(config.quotas.clone()),
+            admission: config.admission.as_ref().map(AdmissionFilter::new),
+            max_idle: config.max_idle_seconds.map(Duration::from_secs),
+            cache: Arc::new(Mutex::new(HashMap::new())),
+            eviction: RwLock::new(Arc::from(config.eviction_policy.strategy())),
+            listeners: CacheListeners::default(),
+            loader: None,
+            spill: None,
+            next_version: AtomicU64::new(1),
+            config: Arc::new(Mutex::new(config)),
+            stats: CacheStats::default(),
+            write_behind: None,
+            shut_down: AtomicBool::new(false),
+        };
+        if let Some(path) = persistence_path {
+            cache.restore(&path);
+        }
+        cache
+    }
+
+    fn store_tagged(
+        &self,
+        key: CacheKey,
+        data: Vec<u8>,
+        ttl: Option<Duration>,
+        tags: &[&str],
+    ) -> bool {
+        println!("Storing data for {}", key);
+
+        let entry = self.new_entry(&key, data, ttl, tags);
+        self.store_entry(key, entry, None)
+    }
+
+    fn store_empty(&self, key: CacheKey, ttl: Option<Duration>) -> bool {
+        let negative_ttl_seconds = self.config.lock().unwrap().negative_ttl_seconds;
+        let ttl = ttl.unwrap_or_else(|| Duration::from_secs(negative_ttl_seconds));
+        let entry = CacheEntry::negative(&key, ttl);
+        self.store_entry(key, entry, None)
+    }
+
+    fn lookup(&self, key: &CacheKey) -> CacheLookup {
+        self.get_versioned(key).0
+    }
+
+    fn store_if_version(
+        &self,
+        key: CacheKey,
+        data: Vec<u8>,
+        ttl: Option<Duration>,
+        version: CacheVersion,
+    ) -> bool {
+        let entry = self.new_entry(&key, data, ttl, &[]);
+        self.store_entry(key, entry, Some(version))
+    }
+
+    fn get_versioned(&self, key: &CacheKey) -> (CacheLookup, CacheVersion) {
+        let (found, version, _) =
+            self.read(key, false, |compression, data| compression.decompress(data));
+        (found, version)
+    }
+
+    // Uncompressed values are handed out without copying them
+    fn get_ref(&self, key: &CacheKey) -> Option<Arc<[u8]>> {
+        self.read(key, false, Compression::decompress_shared)
+            .0
+            .into_data()
+    }
+
+    // The whole batch is read under one lock acquisition, decompression happens after
+    fn get_many_keys(&self, keys: &[CacheKey]) -> Vec<CacheLookup> {
+        let now = Instant::now();
+        self.stats
+            .total_lookups
+            .fetch_add(keys.len(), Ordering::SeqCst);
+
+        let mut expired = Vec::new();
+        let mut cache = self.cache.lock().unwrap();
+        let found: Vec<Option<_>> = keys
+            .iter()
+            .map(|key| {
+                let entry = cache.get_mut(key)?;
+                if entry.is_expired() {
+                    let removed = self.remove_locked(&mut cache, key, true)?;
+                    expired.push(removed.event(key.clone()));
+                    return None;
+                }
+                entry.access_count += 1;
+                entry.last_accessed = Instant::now();
+                Some((Arc::clone(&entry.data), entry.compression, entry.negative))
+            })
+            .collect();
+        drop(cache);
+        self.listeners.notify_expired(&expired);
+
+        let strategy = self.eviction_strategy();
+        let results: Vec<CacheLookup> = keys
+            .iter()
+            .zip(found)
+            .map(|(key, found)| match found {
+                Some((_, _, true)) => {
+                    strategy.record_access(key);
+                    self.stats.hit_count.fetch_add(1, Ordering::SeqCst);
+                    self.stats.negative_hit_count.fetch_add(1, Ordering::SeqCst);
+                    CacheLookup::HitEmpty
+                }
+                Some((data, compression, false)) => {
+                    strategy.record_access(key);
+                    match compression.decompress(&data) {
+                        Ok(data) => {
+                            self.stats.hit_count.fetch_add(1, Ordering::SeqCst);
+                            CacheLookup::Hit(data)
+                        }
+                        Err(e) => {
+                            tracing::warn!("Dropping undecodable cache entry {}: {}", key, e);
+                            self.remove_entry(key, false);
+                            self.stats.miss_count.fetch_add(1, Ordering::SeqCst);
+                            CacheLookup::Miss
+                        }
+                    }
+                }
+                None => {
+                    self.stats.miss_count.fetch_add(1, Ordering::SeqCst);
+                    CacheLookup::Miss
+                }
+            })
+            .collect();
+
+        // Every key is charged its share of the batch time
+        let per_key_ns = now.elapsed().as_nanos() as u64 / keys.len().max(1) as u64;
+        for _ in keys {
+            self.stats.lookup_latency.record(per_key_ns);
+        }
+        self.stats.record_batch(&results);
+        results
+    }
+
+    // Room for the whole batch is made in one eviction pass, then all entries are
+    // inserted under one lock acquisition
+    fn store_many(&self, entries: Vec<(CacheKey, Vec<u8>)>, ttl: Option<Duration>) -> Vec<bool> {
+        let (max_size_bytes, high_watermark, low_watermark) = self.size_limits();
+        let capacity_policy = self.config.lock().unwrap().capacity_policy;
+        let entries: Vec<(CacheKey, CacheEntry)> = entries
+            .into_iter()
+            .map(|(key, data)| {
+                let entry = self.new_entry(&key, data, ttl, &[]);
+                (key, entry)
+            })
+            .collect();
+
+        let batch_size: usize = entries
+            .iter()
+            .map(|(_, entry)| entry.size())
+            .filter(|size| *size <= max_size_bytes)
+            .sum();
+        if capacity_policy == CapacityPolicy::EvictToFit
+            && self.stats.size_bytes.load(Ordering::SeqCst) + batch_size > high_watermark
+        {
+            self.evict_down_to(low_watermark.saturating_sub(batch_size));
+        }
+
+        let mut rejected = Vec::new();
+        let mut overflowed = Vec::new();
+        let mut cache = self.cache.lock().unwrap();
+        let stored = entries
+            .into_iter()
+            .map(|(key, entry)| {
+                if self.stats.size_bytes.load(Ordering::SeqCst) + entry.size() > max_size_bytes {
+                    overflowed.push((key, entry));
+                    return false;
+                }
+                if !self.admitted_locked(&cache, &key) || !self.admit_locked(&cache, &key, &entry) {
+                    rejected.push(entry.event(key));
+                    return false;
+                }
+                self.insert_locked(&mut cache, key, entry);
+                true
+            })
+            .collect();
+        drop(cache);
+
+        self.stats
+            .rejected_count
+            .fetch_add(rejected.len(), Ordering::SeqCst);
+        self.listeners.notify_rejected(&rejected);
+        for (key, entry) in overflowed {
+            self.overflow(key, entry);
+        }
+        stored
+    }
+
+    fn stats(&self) -> CacheStatsReport {
+        CacheStatsReport {
+            quota_rejected_count: self.quotas.rejected_count(),
+            quotas: self.quotas.report(),
+            admission_rejected_count: self
+                .admission
+                .as_ref()
+                .map_or(0, AdmissionFilter::rejected_count),
+            ..self.stats.report()
+        }
+    }
+
+    fn set_eviction_policy(&self, policy: EvictionPolicy) {
+        let mut config = self.config.lock().unwrap();
+        config.eviction_policy = policy;
+        *self.eviction.write().unwrap() = Arc::from(policy.strategy());
+    }
+
+    fn prefetch_keys(&self, keys: Vec<CacheKey>, ttl: Option<Duration>) -> usize {
+        let parallelism = self.config.lock().unwrap().prefetch_parallelism;
+        loader::prefetch_into(self, self.loader.as_deref(), keys, ttl, parallelism)
+    }
+
+    fn invalidate_in_context(
+        &self,
+        hotel_id: Option<&str>,
         check_in: Option<&str>,
         check_out: Option<&str>,
+        context: Option<&SearchContext>,
     ) -> usize {
         let cache = self.cache.lock().unwrap();
-        let keys_to_remove: Vec<String> = cache
+        let keys_to_remove: Vec<CacheKey> = cache
             .keys()
             .filter(|key| {
-                let parts: Vec<&str> = key.split(':').collect();
-                if parts.len() != 3 {
-                    return false;
-                }
-
-                let matches_hotel = hotel_id.map_or(true, |h| parts[0] == h);
-                let matches_checkin = check_in.map_or(true, |c| parts[1] == c);
-                let matches_checkout = check_out.map_or(true, |c| parts[2] == c);
-
-                matches_hotel && matches_checkin && matches_checkout
+                key.matches(hotel_id, check_in, check_out)
+                    && context.is_none_or(|context| key.matches_context(context))
             })
             .cloned()
             .collect();
         drop(cache); // Release lock before removing entries
 
         let count = keys_to_remove.len();
-        for key in keys_to_remove {
+        for key in &keys_to_remove {
+            self.remove_entry(key, false);
+        }
+        count
+    }
+
+    fn invalidate_by_tag(&self, tag: &str) -> usize {
+        let cache = self.cache.lock().unwrap();
+        let keys_to_remove: Vec<CacheKey> = cache
+            .iter()
+            .filter(|(_, entry)| entry.tags.iter().any(|t| t == tag))
+            .map(|(key, _)| key.clone())
+            .collect();
+        drop(cache); // Release lock before removing entries
+
+        let count = keys_to_remove.len();
+        for key in &keys_to_remove {
             self.remove_entry(key, false);
         }
         count
     }
 
+    fn tagged_keys(&self, tag: &str) -> Vec<CacheKey> {
+        let cache = self.cache.lock().unwrap();
+        cache
+            .iter()
+            .filter(|(_, entry)| entry.tags.iter().any(|t| t == tag))
+            .map(|(key, _)| key.clone())
+            .collect()
+    }
+
     fn resize(&self, new_max_size_mb: usize) -> bool {
-        self.config.lock().unwrap().max_size_mb = new_max_size_mb;
+        let (new_max_size_bytes, low_watermark) = {
+            let mut config = self.config.lock().unwrap();
+            config.max_size_mb = new_max_size_mb;
+            (new_max_size_mb * 1024 * 1024, low_watermark_bytes(&config))
+        };
 
-        let current_size_bytes = self.stats.size_bytes.load(Ordering::SeqCst);
-        let new_max_size_bytes = new_max_size_mb * 1024 * 1024;
+        if self.stats.size_bytes.load(Ordering::SeqCst) > new_max_size_bytes {
+            self.evict_down_to(low_watermark);
+        }
+
+        true
+    }
+
+    // The write-behind queue is drained into the store before its writer thread is stopped,
+    // then the contents are saved to `persistence_path` (if configured)
+    fn shutdown(&self) -> std::io::Result<()> {
+        if self.shut_down.swap(true, Ordering::SeqCst) {
+            return Ok(());
+        }
+        if let Some(write_behind) = &self.write_behind {
+            write_behind.shutdown();
+        }
+        let persisted = self.persist()?;
+        if persisted > 0 {
+            tracing::info!("Persisted {} cache entries on shutdown", persisted);
+        }
+        Ok(())
+    }
+}
 
-        if current_size_bytes > new_max_size_bytes {
-            while self.stats.size_bytes.load(Ordering::SeqCst) > new_max_size_bytes {
-                self.remove_oldest_entry();
+impl CacheSnapshot for ExampleCache {
+    // Entries are copied under the lock and decompressed after releasing it
+    fn export_snapshot(&self) -> Vec<SnapshotEntry> {
+        let copied: Vec<_> = self
+            .cache
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, entry)| !entry.is_expired())
+            .map(|(key, entry)| {
+                (
+                    key.clone(),
+                    Arc::clone(&entry.data),
+                    entry.compression,
+                    entry.remaining_ttl(),
+                    entry.tags.clone(),
+                    entry.negative,
+                )
+            })
+            .collect();
+
+        copied
+            .into_iter()
+            .filter_map(
+                |(key, data, compression, remaining_ttl, tags, negative)| match compression
+                    .decompress(&data)
+                {
+                    Ok(data) => Some(SnapshotEntry {
+                        key,
+                        data,
+                        remaining_ttl,
+                        tags,
+                        negative,
+                    }),
+                    Err(e) => {
+                        tracing::warn!("Skipping {} in snapshot: {}", key, e);
+                        None
+                    }
+                },
+            )
+            .collect()
+    }
+
+    fn import_snapshot(&self, entries: Vec<SnapshotEntry>) -> usize {
+        let (compression, threshold) = {
+            let config = self.config.lock().unwrap();
+            (config.compression, config.compression_threshold_bytes)
+        };
+
+        let mut imported = 0;
+        for entry in entries
+            .into_iter()
+            .filter(|entry| !entry.remaining_ttl.is_zero())
+        {
+            let cache_entry = if entry.negative {
+                CacheEntry::negative(&entry.key, entry.remaining_ttl)
+            } else {
+                CacheEntry::new(
+                    &entry.key,
+                    entry.data,
+                    entry.remaining_ttl,
+                    entry.tags,
+                    compression,
+                    threshold,
+                )
+            };
+            if self.insert_entry(entry.key, cache_entry, None) {
+                imported += 1;
             }
         }
+        imported
+    }
+}
 
-        true
+impl CacheInspect for ExampleCache {
+    fn iter_entries(&self, filter: &EntryFilter) -> impl Iterator<Item = EntryInfo> {
+        let entries: Vec<EntryInfo> = self
+            .cache
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(key, entry)| !entry.is_expired() && filter.matches(key, &entry.tags))
+            .map(|(key, entry)| EntryInfo {
+                key: key.clone(),
+                size_bytes: entry.size,
+                age: entry.created_at.elapsed(),
+                remaining_ttl: entry.remaining_ttl(),
+                access_count: entry.access_count,
+                idle: entry.last_accessed.elapsed(),
+                tags: entry.tags.clone(),
+                negative: entry.negative,
+            })
+            .collect();
+        entries.into_iter()
+    }
+
+    fn get_with_metadata(&self, key: &CacheKey) -> Option<(Vec<u8>, EntryMetadata)> {
+        match self.read(key, true, |compression, data| compression.decompress(data)) {
+            (CacheLookup::Hit(data), _, Some(metadata)) => Some((data, metadata)),
+            _ => None,
+        }
+    }
+}
+
+// Dropping a cache that wasn't shut down explicitly shuts it down
+impl Drop for ExampleCache {
+    fn drop(&mut self) {
+        if let Err(e) = self.shutdown() {
+            tracing::warn!("Failed to persist cache contents: {}", e);
+        }
     }
 }
 
@@ -374,6 +1615,7 @@ mod tests {
             cleanup_interval_seconds: 60,
             shards_count: 8,
             eviction_policy: EvictionPolicy::LeastFrequentlyUsed,
+            ..Default::default()
         };
 
         println!("Starting contention test with config: {:?}", config);
@@ -473,6 +1715,9 @@ mod tests {
             "Average lookup time too high: {}ns",
             stats.average_lookup_time_ns
         );
+        assert!(stats.p50_lookup_time_ns <= stats.p95_lookup_time_ns);
+        assert!(stats.p95_lookup_time_ns <= stats.p99_lookup_time_ns);
+        assert!(stats.p99_lookup_time_ns <= stats.max_lookup_time_ns);
     }
 
     #[test]
@@ -483,6 +1728,7 @@ mod tests {
             cleanup_interval_seconds: 1,
             shards_count: 4,
             eviction_policy: EvictionPolicy::LeastRecentlyUsed,
+            ..Default::default()
         };
 
         let cache = ExampleCache::new(config);
@@ -531,6 +1777,27 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_idle_entries_expire_before_ttl() {
+        let cache = ExampleCache::new(CacheConfig {
+            max_idle_seconds: Some(1),
+            ..Default::default()
+        });
+        let (hot, cold) = (
+            CacheKey::new("hotel1", "2025-06-01", "2025-06-05"),
+            CacheKey::new("hotel2", "2025-06-01", "2025-06-05"),
+        );
+        cache.store_key(hot.clone(), vec![1], None);
+        cache.store_key(cold.clone(), vec![2], None);
+
+        for _ in 0..3 {
+            thread::sleep(Duration::from_millis(500));
+            assert!(cache.lookup(&hot).is_hit());
+        }
+        assert_eq!(cache.lookup(&cold), CacheLookup::Miss);
+        assert_eq!(cache.stats().expired_count, 1);
+    }
+
     #[test]
     fn test_eviction_policy_lru() {
         let config = CacheConfig {
@@ -539,6 +1806,7 @@ mod tests {
             cleanup_interval_seconds: 60,
             shards_count: 2,
             eviction_policy: EvictionPolicy::LeastRecentlyUsed,
+            ..Default::default()
         };
 
         let cache = ExampleCache::new(config);
@@ -589,7 +1857,10 @@ mod tests {
     #[test]
     fn test_prefetch_and_invalidate() {
         let config = CacheConfig::default();
-        let cache = ExampleCache::new(config);
+        let cache = ExampleCache::with_loader(config, |key: &CacheKey| {
+            // Stands in for a supplier call
+            Ok(key.hotel_id.clone().into_bytes())
+        });
 
         // Define some keys to prefetch
         let keys = vec![
@@ -633,7 +1904,10 @@ mod tests {
         assert_eq!(prefetched, 3, "Expected 3 items to be prefetched");
 
         // All items should be in cache now
-        assert!(cache.get("hotel1", "2025-06-01", "2025-06-05").is_some());
+        assert_eq!(
+            cache.get("hotel1", "2025-06-01", "2025-06-05"),
+            Some((b"hotel1".to_vec(), true))
+        );
         assert!(cache.get("hotel1", "2025-06-10", "2025-06-15").is_some());
         assert!(cache.get("hotel2", "2025-06-01", "2025-06-05").is_some());
     }
@@ -646,6 +1920,7 @@ mod tests {
             cleanup_interval_seconds: 60,
             shards_count: 4,
             eviction_policy: EvictionPolicy::LeastRecentlyUsed,
+            ..Default::default()
         };
 
         let cache = ExampleCache::new(config);
@@ -702,4 +1977,986 @@ mod tests {
             "Cache should accommodate more items after upsizing"
         );
     }
+
+    #[test]
+    fn test_warm_restart_from_disk() {
+        let path = std::env::temp_dir().join(format!("avail-cache-{}.bin", rand::random::<u64>()));
+        let config = CacheConfig {
+            persistence_path: Some(path.clone()),
+            ..Default::default()
+        };
+
+        {
+            let cache = ExampleCache::new(config.clone());
+            cache.store("hotel1", "2025-06-01", "2025-06-05", vec![1, 2, 3], None);
+            cache.store_tagged(
+                CacheKey::new("hotel2", "2025-06-01", "2025-06-05"),
+                vec![4, 5],
+                Some(Duration::from_secs(60)),
+                &["supplier:acme"],
+            );
+            cache.store(
+                "hotel3",
+                "2025-06-01",
+                "2025-06-05",
+                vec![6],
+                Some(Duration::from_millis(1)),
+            );
+            thread::sleep(Duration::from_millis(5));
+            // Contents are written when the cache is dropped
+        }
+
+        let cache = ExampleCache::new(config);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            cache.get("hotel1", "2025-06-01", "2025-06-05"),
+            Some((vec![1, 2, 3], true))
+        );
+        assert_eq!(
+            cache.get("hotel2", "2025-06-01", "2025-06-05"),
+            Some((vec![4, 5], true))
+        );
+        // Expired before shutdown, so not restored
+        assert!(cache.get("hotel3", "2025-06-01", "2025-06-05").is_none());
+        assert_eq!(cache.stats().items_count, 2);
+        // Tags survive the restart
+        assert_eq!(cache.invalidate_by_tag("supplier:acme"), 1);
+    }
+
+    #[test]
+    fn test_shutdown_persists_once() {
+        let path = std::env::temp_dir().join(format!("avail-cache-{}.bin", rand::random::<u64>()));
+        let cache = ExampleCache::new(CacheConfig {
+            persistence_path: Some(path.clone()),
+            ..Default::default()
+        });
+        cache.store("hotel1", "2025-06-01", "2025-06-05", vec![1, 2, 3], None);
+
+        cache.shutdown().unwrap();
+        assert!(path.exists());
+        std::fs::remove_file(&path).unwrap();
+
+        // Neither a second shutdown nor the drop write the file again
+        cache.shutdown().unwrap();
+        drop(cache);
+        assert!(!path.exists());
+    }
+
+    // Fill a 1MB cache with four 250KB entries (hotel0..hotel3), read them in the order given
+    // by `reads`, then store hotel4 and return the hotels evicted to make room for it.
+    // Eviction goes down to the 90% low watermark, so the first two victims are evicted.
+    fn evicted_by(cache: &ExampleCache, ttls_seconds: [u64; 4], reads: &[usize]) -> Vec<String> {
+        let large_data = vec![0; 250 * 1024];
+        for (i, ttl) in ttls_seconds.iter().enumerate() {
+            let hotel_id = format!("hotel{}", i);
+            let ttl = Some(Duration::from_secs(*ttl));
+            assert!(cache.store(
+                &hotel_id,
+                "2025-06-01",
+                "2025-06-05",
+                large_data.clone(),
+                ttl
+            ));
+            thread::sleep(Duration::from_millis(1));
+        }
+        for i in reads {
+            assert!(cache
+                .get(&format!("hotel{}", i), "2025-06-01", "2025-06-05")
+                .is_some());
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        assert!(cache.store("hotel4", "2025-06-01", "2025-06-05", large_data, None));
+        let cache_map = cache.cache.lock().unwrap();
+        (0..4)
+            .map(|i| format!("hotel{}", i))
+            .filter(|hotel| {
+                !cache_map.contains_key(&create_cache_key(hotel, "2025-06-01", "2025-06-05"))
+            })
+            .collect()
+    }
+
+    fn small_cache(policy: EvictionPolicy) -> ExampleCache {
+        ExampleCache::new(CacheConfig {
+            max_size_mb: 1,
+            default_ttl_seconds: 3600,
+            eviction_policy: policy,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_eviction_victim_lru() {
+        let cache = small_cache(EvictionPolicy::LeastRecentlyUsed);
+        // hotel0 is read most often but hotel2 was read least recently
+        let evicted = evicted_by(&cache, [3600; 4], &[0, 0, 0, 2, 1, 3, 0]);
+        assert_eq!(evicted, vec!["hotel1", "hotel2"]);
+    }
+
+    #[test]
+    fn test_eviction_victim_lfu() {
+        let cache = small_cache(EvictionPolicy::LeastFrequentlyUsed);
+        // hotel1 is read once, the most recently of all, but still least often;
+        // hotel0 is next as the least recently read of the entries read twice
+        let evicted = evicted_by(&cache, [3600; 4], &[0, 0, 2, 2, 3, 3, 1]);
+        assert_eq!(evicted, vec!["hotel0", "hotel1"]);
+    }
+
+    #[test]
+    fn test_eviction_victim_fifo() {
+        let cache = small_cache(EvictionPolicy::FirstInFirstOut);
+        // Reads don't matter, the first inserted entry goes
+        let evicted = evicted_by(&cache, [3600; 4], &[0, 0, 0, 0]);
+        assert_eq!(evicted, vec!["hotel0", "hotel1"]);
+    }
+
+    #[test]
+    fn test_eviction_victim_ttl() {
+        let cache = small_cache(EvictionPolicy::TimeToLive);
+        let evicted = evicted_by(&cache, [3600, 3600, 60, 600], &[2]);
+        assert_eq!(evicted, vec!["hotel2", "hotel3"]);
+    }
+
+    #[test]
+    fn test_eviction_victim_window_tinylfu() {
+        let cache = small_cache(EvictionPolicy::WindowTinyLfu);
+        // hotel3 is the newest entry and sits in the admission window, LRU would evict it.
+        // Among the rest hotel1 has the lowest estimated frequency.
+        let evicted = evicted_by(&cache, [3600; 4], &[0, 0, 0, 0, 0, 2, 2, 1]);
+        assert_eq!(evicted, vec!["hotel1", "hotel2"]);
+    }
+
+    #[test]
+    fn test_custom_eviction_strategy() {
+        // Evict the lexicographically largest key
+        struct LargestKeyFirst;
+        impl EvictionStrategy for LargestKeyFirst {
+            fn name(&self) -> &'static str {
+                "largest-key"
+            }
+            fn compare(&self, a: &EvictionCandidate, b: &EvictionCandidate) -> std::cmp::Ordering {
+                b.key.cmp(a.key)
+            }
+        }
+
+        let cache = ExampleCache::with_eviction_strategy(
+            CacheConfig {
+                max_size_mb: 1,
+                ..Default::default()
+            },
+            Box::new(LargestKeyFirst),
+        );
+        let evicted = evicted_by(&cache, [3600; 4], &[3]);
+        assert_eq!(evicted, vec!["hotel2", "hotel3"]);
+    }
+
+    #[test]
+    fn test_batch_eviction_to_low_watermark() {
+        let cache = ExampleCache::new(CacheConfig {
+            max_size_mb: 2,
+            low_watermark_percent: 80,
+            ..Default::default()
+        });
+        let low_watermark = 2 * 1024 * 1024 / 100 * 80;
+
+        let medium_data = vec![0; 100 * 1024];
+        for i in 0..20 {
+            let hotel_id = format!("hotel{}", i);
+            cache.store(
+                &hotel_id,
+                "2025-06-01",
+                "2025-06-05",
+                medium_data.clone(),
+                None,
+            );
+        }
+        assert_eq!(cache.stats().eviction_count, 0);
+
+        // The 21st item doesn't fit: one pass frees enough for it and the watermark headroom
+        cache.store(
+            "hotel20",
+            "2025-06-01",
+            "2025-06-05",
+            medium_data.clone(),
+            None,
+        );
+        let stats = cache.stats();
+        assert!(
+            stats.size_bytes <= low_watermark,
+            "{} > {}",
+            stats.size_bytes,
+            low_watermark
+        );
+        assert_eq!(stats.eviction_count, 5);
+        assert_eq!(stats.items_count, 16);
+
+        // The following writes fit without evicting again
+        for i in 21..24 {
+            let hotel_id = format!("hotel{}", i);
+            cache.store(
+                &hotel_id,
+                "2025-06-01",
+                "2025-06-05",
+                medium_data.clone(),
+                None,
+            );
+        }
+        assert_eq!(cache.stats().eviction_count, 5);
+
+        // Shrinking evicts down to the watermark of the new size in one pass
+        assert!(cache.resize(1));
+        let stats = cache.stats();
+        assert!(stats.size_bytes <= 1024 * 1024 / 100 * 80);
+        assert_eq!(stats.items_count, 8);
+    }
+
+    #[test]
+    fn test_high_watermark_evicts_early() {
+        let cache = ExampleCache::new(CacheConfig {
+            max_size_mb: 2,
+            high_watermark_percent: 50,
+            low_watermark_percent: 30,
+            ..Default::default()
+        });
+        let high_watermark = 2 * 1024 * 1024 / 100 * 50;
+        let low_watermark = 2 * 1024 * 1024 / 100 * 30;
+
+        // 12 items are above the high watermark but well below the hard limit
+        let medium_data = vec![0; 100 * 1024];
+        for i in 0..12 {
+            let hotel_id = format!("hotel{}", i);
+            assert!(cache.store(
+                &hotel_id,
+                "2025-06-01",
+                "2025-06-05",
+                medium_data.clone(),
+                None,
+            ));
+            assert!(cache.stats().size_bytes <= high_watermark);
+        }
+
+        let stats = cache.stats();
+        assert!(stats.eviction_count > 0);
+        assert!(stats.size_bytes > low_watermark);
+        assert_eq!(stats.rejected_count, 0);
+
+        // The hard limit still rejects items that can never fit
+        assert!(!cache.store(
+            "hotel12",
+            "2025-06-01",
+            "2025-06-05",
+            vec![0; 3 * 1024 * 1024],
+            None,
+        ));
+        assert_eq!(cache.stats().rejected_count, 1);
+    }
+
+    #[test]
+    fn test_compression_above_threshold() {
+        let cache = ExampleCache::new(CacheConfig {
+            compression: Compression::Zstd { level: 3 },
+            compression_threshold_bytes: 1024,
+            ..Default::default()
+        });
+        let large = "<RoomRate><Price>84.82</Price></RoomRate>"
+            .repeat(4096)
+            .into_bytes();
+        let small = vec![1, 2, 3];
+
+        cache.store("hotel1", "2025-06-01", "2025-06-05", large.clone(), None);
+        cache.store("hotel2", "2025-06-01", "2025-06-05", small.clone(), None);
+
+        assert_eq!(
+            cache.get("hotel1", "2025-06-01", "2025-06-05"),
+            Some((large.clone(), true))
+        );
+        assert_eq!(
+            cache.get("hotel2", "2025-06-01", "2025-06-05"),
+            Some((small.clone(), true))
+        );
+
+        let stats = cache.stats();
+        assert_eq!(stats.raw_bytes, large.len() + small.len());
+        assert!(stats.compressed_bytes < large.len() / 10);
+        assert!(stats.size_bytes < large.len() / 10);
+
+        // Removing entries releases the compressed size
+        cache.invalidate(Some("hotel1"), None, None);
+        let stats = cache.stats();
+        assert_eq!(stats.raw_bytes, small.len());
+        assert_eq!(stats.compressed_bytes, small.len());
+    }
+
+    #[test]
+    fn test_structured_keys() {
+        let cache = ExampleCache::new(CacheConfig::default());
+        // Hotel IDs containing the old ':' separator are just another key
+        cache.store("ACME:1", "2025-06-01", "2025-06-05", vec![1], None);
+        cache.store("ACME", "1:2025-06-01", "2025-06-05", vec![2], None);
+
+        let key = CacheKey::new("ACME:1", "2025-06-01", "2025-06-05");
+        let eur = key.clone().with_dimension(CacheKey::CURRENCY, "EUR");
+        assert!(cache.store_key(eur.clone(), vec![3], None));
+
+        assert_eq!(cache.get_key(&key), Some((vec![1], true)));
+        assert_eq!(cache.get_key(&eur), Some((vec![3], true)));
+        assert_eq!(
+            cache.get("ACME", "1:2025-06-01", "2025-06-05"),
+            Some((vec![2], true))
+        );
+
+        assert_eq!(cache.invalidate(Some("ACME:1"), None, None), 2);
+        assert!(cache.get_key(&eur).is_none());
+        assert!(cache.get("ACME", "1:2025-06-01", "2025-06-05").is_some());
+    }
+
+    #[test]
+    fn test_search_context_keys() {
+        let cache = ExampleCache::new(CacheConfig::default());
+        let gb_eur = SearchContext::new()
+            .nationality("GB")
+            .currency("EUR")
+            .occupancy(2, 0);
+        let de_eur = SearchContext::new()
+            .nationality("DE")
+            .currency("EUR")
+            .occupancy(2, 0);
+        let gb_gbp = SearchContext::new()
+            .nationality("GB")
+            .currency("GBP")
+            .occupancy(2, 0);
+
+        for (context, price) in [(&gb_eur, 1), (&de_eur, 2), (&gb_gbp, 3)] {
+            cache.store_in_context(
+                "hotel1",
+                "2025-06-01",
+                "2025-06-05",
+                Some(context),
+                vec![price],
+                None,
+            );
+        }
+
+        // Each market/currency gets its own prices
+        assert_eq!(
+            cache.get_in_context("hotel1", "2025-06-01", "2025-06-05", Some(&de_eur)),
+            Some((vec![2], true))
+        );
+        assert_eq!(
+            cache.get_in_context("hotel1", "2025-06-01", "2025-06-05", Some(&gb_gbp)),
+            Some((vec![3], true))
+        );
+        let family = gb_eur.clone().occupancy(2, 2);
+        assert!(cache
+            .get_in_context("hotel1", "2025-06-01", "2025-06-05", Some(&family))
+            .is_none());
+        assert!(cache.get("hotel1", "2025-06-01", "2025-06-05").is_none());
+
+        // Drop every EUR price of the hotel, whatever the market
+        let eur = SearchContext::new().currency("EUR");
+        assert_eq!(
+            cache.invalidate_in_context(Some("hotel1"), None, None, Some(&eur)),
+            2
+        );
+        assert!(cache
+            .get_in_context("hotel1", "2025-06-01", "2025-06-05", Some(&gb_gbp))
+            .is_some());
+        assert_eq!(cache.invalidate(Some("hotel1"), None, None), 1);
+    }
+
+    #[test]
+    fn test_invalidate_by_tag() {
+        let cache = ExampleCache::new(CacheConfig::default());
+        let tagged = [
+            ("hotel1", vec!["supplier:acme", "dest:PAR"]),
+            ("hotel2", vec!["supplier:acme", "dest:LON"]),
+            ("hotel3", vec!["supplier:globex", "dest:PAR"]),
+            ("hotel4", vec![]),
+        ];
+        for (hotel_id, tags) in &tagged {
+            let key = CacheKey::new(*hotel_id, "2025-06-01", "2025-06-05");
+            assert!(cache.store_tagged(key, vec![1, 2, 3], None, tags));
+        }
+
+        assert_eq!(cache.invalidate_by_tag("dest:PAR"), 2);
+        assert!(cache.get("hotel1", "2025-06-01", "2025-06-05").is_none());
+        assert!(cache.get("hotel3", "2025-06-01", "2025-06-05").is_none());
+
+        assert_eq!(cache.invalidate_by_tag("supplier:acme"), 1);
+        assert_eq!(cache.invalidate_by_tag("supplier:acme"), 0);
+        assert!(cache.get("hotel4", "2025-06-01", "2025-06-05").is_some());
+
+        // Overwriting an entry replaces its tags
+        let key = CacheKey::new("hotel4", "2025-06-01", "2025-06-05");
+        cache.store_tagged(key, vec![4], None, &["dest:ROM"]);
+        assert_eq!(cache.invalidate_by_tag("dest:ROM"), 1);
+        assert_eq!(cache.stats().items_count, 0);
+        assert_eq!(cache.stats().size_bytes, 0);
+    }
+
+    #[test]
+    fn test_event_listeners() {
+        let cache = Arc::new(small_cache(EvictionPolicy::FirstInFirstOut));
+        let evicted = Arc::new(Mutex::new(Vec::new()));
+        let expired = Arc::new(Mutex::new(Vec::new()));
+        let rejected = Arc::new(Mutex::new(Vec::new()));
+
+        let log = Arc::clone(&evicted);
+        // Listeners run outside the cache lock, so they may use the cache
+        let weak_cache = Arc::downgrade(&cache);
+        cache.on_evict(move |event| {
+            let cache = weak_cache.upgrade().unwrap();
+            assert!(cache.get_key(&event.key).is_none());
+            log.lock().unwrap().push(event.key.hotel_id.clone());
+        });
+        let log = Arc::clone(&expired);
+        cache.on_expire(move |event| log.lock().unwrap().push(event.key.hotel_id.clone()));
+        let log = Arc::clone(&rejected);
+        cache.on_reject(move |event| log.lock().unwrap().push(event.size));
+
+        let large_data = vec![0; 250 * 1024];
+        for i in 0..5 {
+            let hotel_id = format!("hotel{}", i);
+            cache.store(
+                &hotel_id,
+                "2025-06-01",
+                "2025-06-05",
+                large_data.clone(),
+                None,
+            );
+        }
+        assert_eq!(*evicted.lock().unwrap(), vec!["hotel0", "hotel1"]);
+
+        cache.store(
+            "hotel5",
+            "2025-06-01",
+            "2025-06-05",
+            vec![1],
+            Some(Duration::from_millis(1)),
+        );
+        thread::sleep(Duration::from_millis(5));
+        assert!(cache.get("hotel5", "2025-06-01", "2025-06-05").is_none());
+        assert_eq!(*expired.lock().unwrap(), vec!["hotel5"]);
+
+        // Larger than the whole cache
+        assert!(!cache.store(
+            "hotel6",
+            "2025-06-01",
+            "2025-06-05",
+            vec![0; 2 * 1024 * 1024],
+            None
+        ));
+        assert_eq!(rejected.lock().unwrap().len(), 1);
+        assert_eq!(cache.stats().rejected_count, 1);
+    }
+
+    #[test]
+    fn test_negative_entries() {
+        let config = CacheConfig {
+            negative_ttl_seconds: 1,
+            ..Default::default()
+        };
+        let cache = ExampleCache::new(config);
+        let sold_out = CacheKey::new("hotel1", "2025-06-01", "2025-06-05");
+        let available = CacheKey::new("hotel2", "2025-06-01", "2025-06-05");
+        let unknown = CacheKey::new("hotel3", "2025-06-01", "2025-06-05");
+
+        assert!(cache.store_empty(sold_out.clone(), None));
+        assert!(cache.store_key(available.clone(), b"rooms".to_vec(), None));
+
+        assert_eq!(cache.lookup(&sold_out), CacheLookup::HitEmpty);
+        assert_eq!(
+            cache.lookup(&available),
+            CacheLookup::Hit(b"rooms".to_vec())
+        );
+        assert_eq!(cache.lookup(&unknown), CacheLookup::Miss);
+        // Known-empty entries carry no data for `get`
+        assert_eq!(cache.get_key(&sold_out), None);
+
+        let stats = cache.stats();
+        assert_eq!(stats.hit_count, 3);
+        assert_eq!(stats.negative_hit_count, 2);
+        assert_eq!(stats.miss_count, 1);
+
+        // The negative TTL is shorter than the default one
+        thread::sleep(Duration::from_millis(1100));
+        assert_eq!(cache.lookup(&sold_out), CacheLookup::Miss);
+        assert!(cache.lookup(&available).is_hit());
+
+        // Finding availability later replaces the negative entry
+        cache.store_empty(sold_out.clone(), None);
+        cache.store_key(sold_out.clone(), b"rooms".to_vec(), None);
+        assert_eq!(cache.lookup(&sold_out), CacheLookup::Hit(b"rooms".to_vec()));
+    }
+
+    #[test]
+    fn test_store_if_version() {
+        let cache = ExampleCache::new(CacheConfig::default());
+        let key = CacheKey::new("hotel1", "2025-06-01", "2025-06-05");
+
+        // Two workers refresh the same missing key
+        let (found, version) = cache.get_versioned(&key);
+        assert_eq!((found, version), (CacheLookup::Miss, CacheVersion::ABSENT));
+        assert!(cache.store_if_version(key.clone(), b"fast".to_vec(), None, version));
+        assert!(!cache.store_if_version(key.clone(), b"slow".to_vec(), None, version));
+
+        let (found, current) = cache.get_versioned(&key);
+        assert_eq!(found, CacheLookup::Hit(b"fast".to_vec()));
+        assert!(current > version);
+
+        // Any other store changes the version
+        cache.store_key(key.clone(), b"plain".to_vec(), None);
+        assert!(!cache.store_if_version(key.clone(), b"stale".to_vec(), None, current));
+        let (_, latest) = cache.get_versioned(&key);
+        assert!(cache.store_if_version(key.clone(), b"fresh".to_vec(), None, latest));
+        assert_eq!(cache.lookup(&key), CacheLookup::Hit(b"fresh".to_vec()));
+
+        let stats = cache.stats();
+        assert_eq!(stats.version_conflict_count, 2);
+        assert_eq!(stats.items_count, 1);
+    }
+
+    #[test]
+    fn test_batch_get_and_store() {
+        let cache = ExampleCache::new(CacheConfig::default());
+        let entries = (0..20)
+            .map(|i| {
+                let key = CacheKey::new(format!("hotel{}", i), "2025-06-01", "2025-06-05");
+                (key, format!("rooms{}", i).into_bytes())
+            })
+            .collect();
+        let stored = cache.store_many(entries, None);
+        assert_eq!(stored, vec![true; 20]);
+        assert_eq!(cache.stats().items_count, 20);
+
+        let found = cache.get_many(&[
+            ("hotel3", "2025-06-01", "2025-06-05"),
+            ("hotel42", "2025-06-01", "2025-06-05"),
+            ("hotel7", "2025-06-01", "2025-06-05"),
+        ]);
+        assert_eq!(
+            found,
+            vec![
+                Some((b"rooms3".to_vec(), true)),
+                None,
+                Some((b"rooms7".to_vec(), true))
+            ]
+        );
+        cache.get_many(&[("hotel1", "2025-06-01", "2025-06-05")]);
+
+        let stats = cache.stats();
+        assert_eq!((stats.hit_count, stats.miss_count), (3, 1));
+        assert_eq!(stats.batch_count, 2);
+        assert_eq!(stats.batch_keys, 4);
+        assert_eq!(stats.full_hit_batch_count, 1);
+        assert_eq!(stats.batch_hit_ratio(), 0.75);
+    }
+
+    #[test]
+    fn test_store_many_rejects_what_does_not_fit() {
+        let cache = ExampleCache::new(CacheConfig {
+            max_size_mb: 1,
+            ..Default::default()
+        });
+        let entries = vec![
+            (
+                CacheKey::new("hotel1", "2025-06-01", "2025-06-05"),
+                vec![0; 1024],
+            ),
+            (
+                CacheKey::new("hotel2", "2025-06-01", "2025-06-05"),
+                vec![0; 2 * 1024 * 1024],
+            ),
+            (
+                CacheKey::new("hotel3", "2025-06-01", "2025-06-05"),
+                vec![0; 1024],
+            ),
+        ];
+        assert_eq!(cache.store_many(entries, None), vec![true, false, true]);
+
+        let stats = cache.stats();
+        assert_eq!(stats.items_count, 2);
+        assert_eq!(stats.rejected_count, 1);
+    }
+
+    #[test]
+    fn test_prefetch_without_loader_stores_nothing() {
+        let cache = ExampleCache::new(CacheConfig::default());
+        let keys = vec![CacheKey::new("hotel1", "2025-06-01", "2025-06-05")];
+        assert_eq!(cache.prefetch_keys(keys, None), 0);
+        assert_eq!(cache.stats().items_count, 0);
+    }
+
+    #[test]
+    fn test_quotas_reject_stores_past_the_limit() {
+        let cache = ExampleCache::new(CacheConfig {
+            quotas: vec![
+                Quota::hotel_prefix("ACME:").max_items(2),
+                Quota::tag("supplier:bulk").max_bytes(64 * 1024),
+            ],
+            ..Default::default()
+        });
+        let key = |hotel_id: &str| CacheKey::new(hotel_id, "2025-06-01", "2025-06-05");
+
+        assert!(cache.store_key(key("ACME:1"), vec![1], None));
+        assert!(cache.store_key(key("ACME:2"), vec![2], None));
+        assert!(!cache.store_key(key("ACME:3"), vec![3], None));
+        // Replacing an entry doesn't need extra room, other hotels are not limited
+        assert!(cache.store_key(key("ACME:2"), vec![2, 2], None));
+        assert!(cache.store_key(key("OTHER:1"), vec![1], None));
+
+        assert!(cache.store_tagged(key("BULK:1"), vec![0; 40 * 1024], None, &["supplier:bulk"]));
+        assert!(!cache.store_tagged(key("BULK:2"), vec![0; 40 * 1024], None, &["supplier:bulk"]));
+        assert_eq!(
+            cache.store_many(
+                vec![(key("ACME:4"), vec![4]), (key("OTHER:2"), vec![2])],
+                None
+            ),
+            vec![false, true]
+        );
+
+        // Removing an entry frees its share of the quota
+        cache.invalidate(Some("ACME:1"), None, None);
+        assert!(cache.store_key(key("ACME:3"), vec![3], None));
+
+        let stats = cache.stats();
+        assert_eq!(stats.quota_rejected_count, 3);
+        assert_eq!(stats.rejected_count, 3);
+        assert_eq!(
+            stats.quotas[0].scope,
+            QuotaScope::HotelPrefix("ACME:".to_string())
+        );
+        assert_eq!(
+            (stats.quotas[0].items_count, stats.quotas[0].rejected_count),
+            (2, 2)
+        );
+        assert_eq!(stats.quotas[1].items_count, 1);
+        assert!(stats.quotas[1].size_bytes > 40 * 1024);
+    }
+
+    #[test]
+    fn test_snapshot_roundtrip() {
+        let source = ExampleCache::new(CacheConfig {
+            compression: Compression::Zstd { level: 3 },
+            compression_threshold_bytes: 1024,
+            ..Default::default()
+        });
+        let big = vec![7u8; 4096];
+        source.store_tagged(
+            CacheKey::new("hotel1", "2025-06-01", "2025-06-05"),
+            big.clone(),
+            Some(Duration::from_secs(60)),
+            &["supplier:acme"],
+        );
+        source.store_empty(
+            CacheKey::new("hotel2", "2025-06-01", "2025-06-05"),
+            Some(Duration::from_secs(60)),
+        );
+        source.store(
+            "hotel3",
+            "2025-06-01",
+            "2025-06-05",
+            vec![3],
+            Some(Duration::from_millis(50)),
+        );
+        thread::sleep(Duration::from_millis(100));
+
+        let mut snapshot = source.export_snapshot();
+        snapshot.sort_by(|a, b| a.key.hotel_id.cmp(&b.key.hotel_id));
+        assert_eq!(snapshot.len(), 2);
+        assert_eq!(snapshot[0].data, big);
+        assert_eq!(snapshot[0].tags, vec!["supplier:acme".to_string()]);
+        assert!(snapshot[0].remaining_ttl <= Duration::from_secs(60));
+        assert!(snapshot[0].remaining_ttl > Duration::from_secs(50));
+        assert!(snapshot[1].negative);
+
+        // An entry that expired in transit is not imported
+        snapshot.push(SnapshotEntry {
+            key: CacheKey::new("hotel4", "2025-06-01", "2025-06-05"),
+            data: vec![4],
+            remaining_ttl: Duration::ZERO,
+            tags: Vec::new(),
+            negative: false,
+        });
+
+        let target = ExampleCache::new(CacheConfig::default());
+        assert_eq!(target.import_snapshot(snapshot), 2);
+        assert_eq!(
+            target.lookup(&CacheKey::new("hotel1", "2025-06-01", "2025-06-05")),
+            CacheLookup::Hit(big)
+        );
+        assert_eq!(
+            target.lookup(&CacheKey::new("hotel2", "2025-06-01", "2025-06-05")),
+            CacheLookup::HitEmpty
+        );
+        assert_eq!(target.invalidate_by_tag("supplier:acme"), 1);
+        assert_eq!(target.stats().items_count, 1);
+    }
+
+    #[test]
+    fn test_admission_filter_skips_one_shot_keys() {
+        let cache = ExampleCache::new(CacheConfig {
+            admission: Some(AdmissionConfig::default()),
+            ..Default::default()
+        });
+        let key1 = CacheKey::new("hotel1", "2025-06-01", "2025-06-05");
+
+        assert!(!cache.store_key(key1.clone(), vec![1], None));
+        assert!(!cache.store_empty(CacheKey::new("hotel2", "2025-06-01", "2025-06-05"), None));
+        assert_eq!(cache.lookup(&key1), CacheLookup::Miss);
+        assert!(cache.store_key(key1.clone(), vec![1], None));
+        // Updates of cached keys are not filtered
+        assert!(cache.store_key(key1.clone(), vec![2], None));
+        assert_eq!(cache.lookup(&key1), CacheLookup::Hit(vec![2]));
+
+        let stored = cache.store_many(
+            vec![
+                (CacheKey::new("hotel2", "2025-06-01", "2025-06-05"), vec![2]),
+                (CacheKey::new("hotel3", "2025-06-01", "2025-06-05"), vec![3]),
+            ],
+            None,
+        );
+        assert_eq!(stored, vec![true, false]);
+
+        let stats = cache.stats();
+        assert_eq!(stats.admission_rejected_count, 3);
+        assert_eq!(stats.rejected_count, 3);
+        assert_eq!(stats.items_count, 2);
+
+        // Imported entries were admitted by the cache they came from
+        let target = ExampleCache::new(CacheConfig {
+            admission: Some(AdmissionConfig::default()),
+            ..Default::default()
+        });
+        assert_eq!(target.import_snapshot(cache.export_snapshot()), 2);
+    }
+
+    #[test]
+    fn test_get_with_metadata() {
+        let cache = ExampleCache::new(CacheConfig::default());
+        let key = CacheKey::new("hotel1", "2025-06-01", "2025-06-05")
+            .with_dimension(CacheKey::CURRENCY, "EUR");
+        let before = SystemTime::now();
+        cache.store_tagged(
+            key.clone(),
+            vec![1, 2, 3],
+            Some(Duration::from_secs(60)),
+            &["supplier:acme"],
+        );
+
+        let (data, metadata) = cache.get_with_metadata(&key).unwrap();
+        assert_eq!(data, vec![1, 2, 3]);
+        assert_eq!(metadata.supplier.as_deref(), Some("acme"));
+        assert_eq!(metadata.currency.as_deref(), Some("EUR"));
+        assert!(metadata.stored_at >= before - Duration::from_millis(1));
+        assert!(metadata.remaining_ttl <= Duration::from_secs(60));
+        assert_eq!(metadata.size_bytes, cache.stats().size_bytes);
+
+        let empty = CacheKey::new("hotel2", "2025-06-01", "2025-06-05");
+        cache.store_empty(empty.clone(), None);
+        assert!(cache.get_with_metadata(&empty).is_none());
+        let stats = cache.stats();
+        assert_eq!((stats.hit_count, stats.negative_hit_count), (2, 1));
+    }
+
+    #[test]
+    fn test_iter_entries_lists_metadata() {
+        let cache = ExampleCache::new(CacheConfig::default());
+        let key1 = CacheKey::new("hotel1", "2025-06-01", "2025-06-05");
+        cache.store_tagged(
+            key1.clone(),
+            vec![1; 100],
+            Some(Duration::from_secs(60)),
+            &["supplier:acme"],
+        );
+        cache.store_empty(CacheKey::new("hotel1", "2025-07-01", "2025-07-05"), None);
+        cache.store("hotel2", "2025-06-01", "2025-06-05", vec![2], None);
+        cache.get("hotel1", "2025-06-01", "2025-06-05");
+        cache.get("hotel1", "2025-06-01", "2025-06-05");
+
+        assert_eq!(cache.iter_entries(&EntryFilter::new()).count(), 3);
+        assert_eq!(
+            cache
+                .iter_entries(&EntryFilter::new().hotel_id("hotel1"))
+                .count(),
+            2
+        );
+        assert_eq!(
+            cache
+                .iter_entries(&EntryFilter::new().check_in("2025-07-01"))
+                .filter(|info| info.negative)
+                .count(),
+            1
+        );
+
+        let tagged: Vec<EntryInfo> = cache
+            .iter_entries(&EntryFilter::new().tag("supplier:acme"))
+            .collect();
+        assert_eq!(tagged.len(), 1);
+        assert_eq!(tagged[0].key, key1);
+        assert_eq!(tagged[0].access_count, 2);
+        assert!(tagged[0].size_bytes >= 100);
+        assert!(tagged[0].remaining_ttl <= Duration::from_secs(60));
+        assert!(tagged[0].age < Duration::from_secs(10));
+    }
+
+    #[test]
+    fn test_overwrite_keeps_size_accounting() {
+        let cache = ExampleCache::new(CacheConfig::default());
+        cache.store("hotel1", "2025-06-01", "2025-06-05", vec![0; 100], None);
+        cache.store("hotel1", "2025-06-01", "2025-06-05", vec![0; 40], None);
+
+        let fresh = ExampleCache::new(CacheConfig::default());
+        fresh.store("hotel1", "2025-06-01", "2025-06-05", vec![0; 40], None);
+
+        let stats = cache.stats();
+        assert_eq!(stats.items_count, 1);
+        assert_eq!(stats.size_bytes, fresh.stats().size_bytes);
+
+        // Struct and map overhead are accounted on top of key and payload bytes
+        assert!(
+            stats.size_bytes
+                > calculate_item_size(
+                    &create_cache_key("hotel1", "2025-06-01", "2025-06-05"),
+                    &[0; 40]
+                )
+        );
+
+        cache.invalidate(None, None, None);
+        assert_eq!(cache.stats().size_bytes, 0);
+    }
+
+    #[test]
+    fn test_get_ref_shares_stored_bytes() {
+        let cache = ExampleCache::new(CacheConfig {
+            compression: Compression::Zstd { level: 3 },
+            compression_threshold_bytes: 1024,
+            ..Default::default()
+        });
+        let small = CacheKey::new("hotel1", "2025-06-01", "2025-06-05");
+        let large = CacheKey::new("hotel2", "2025-06-01", "2025-06-05");
+        cache.store_key(small.clone(), vec![1; 100], None);
+        cache.store_key(large.clone(), vec![2; 64 * 1024], None);
+        cache.store_empty(CacheKey::new("hotel3", "2025-06-01", "2025-06-05"), None);
+
+        // Uncompressed values are the stored allocation itself
+        let first = cache.get_ref(&small).unwrap();
+        assert!(Arc::ptr_eq(&first, &cache.get_ref(&small).unwrap()));
+        assert_eq!(&first[..], &[1; 100][..]);
+        // Compressed ones are decoded per read
+        assert_eq!(&cache.get_ref(&large).unwrap()[..], &vec![2; 64 * 1024][..]);
+        assert_eq!(
+            cache.get_ref(&CacheKey::new("hotel3", "2025-06-01", "2025-06-05")),
+            None
+        );
+
+        // Handles stay valid after the entry is gone
+        cache.invalidate(Some("hotel1"), None, None);
+        assert_eq!(cache.get_ref(&small), None);
+        assert_eq!(first.len(), 100);
+
+        let stats = cache.stats();
+        assert_eq!((stats.hit_count, stats.miss_count), (4, 1));
+    }
+
+    #[test]
+    fn test_reject_new_keeps_cached_entries() {
+        let cache = ExampleCache::new(CacheConfig {
+            max_size_mb: 1,
+            capacity_policy: CapacityPolicy::RejectNew,
+            ..Default::default()
+        });
+        assert!(cache.store(
+            "hotel1",
+            "2025-06-01",
+            "2025-06-05",
+            vec![1; 400 * 1024],
+            None
+        ));
+        assert!(cache.store(
+            "hotel2",
+            "2025-06-01",
+            "2025-06-05",
+            vec![2; 400 * 1024],
+            None
+        ));
+        assert!(!cache.store(
+            "hotel3",
+            "2025-06-01",
+            "2025-06-05",
+            vec![3; 400 * 1024],
+            None
+        ));
+        let entries = vec![(
+            CacheKey::new("hotel4", "2025-06-01", "2025-06-05"),
+            vec![4; 400 * 1024],
+        )];
+        assert_eq!(cache.store_many(entries, None), vec![false]);
+
+        let stats = cache.stats();
+        assert_eq!(stats.items_count, 2);
+        assert_eq!(stats.eviction_count, 0);
+        assert_eq!(stats.rejected_count, 2);
+        assert!(cache.get("hotel1", "2025-06-01", "2025-06-05").is_some());
+    }
+
+    #[test]
+    fn test_spill_to_secondary() {
+        let secondary = Arc::new(ExampleCache::new(CacheConfig::default()));
+        let config = CacheConfig {
+            max_size_mb: 1,
+            capacity_policy: CapacityPolicy::SpillToSecondary,
+            ..Default::default()
+        };
+        let cache = ExampleCache::with_spill_store(config.clone(), {
+            let secondary = Arc::clone(&secondary);
+            move |entry: SnapshotEntry| secondary.import_snapshot(vec![entry]) == 1
+        });
+        assert!(cache.store_tagged(
+            CacheKey::new("hotel1", "2025-06-01", "2025-06-05"),
+            vec![1; 600 * 1024],
+            None,
+            &["supplier:acme"],
+        ));
+        // Spilled stores are not cached here
+        assert!(!cache.store(
+            "hotel2",
+            "2025-06-01",
+            "2025-06-05",
+            vec![2; 600 * 1024],
+            None
+        ));
+        assert_eq!(
+            cache.lookup(&CacheKey::new("hotel2", "2025-06-01", "2025-06-05")),
+            CacheLookup::Miss
+        );
+        assert_eq!(
+            secondary.lookup(&CacheKey::new("hotel2", "2025-06-01", "2025-06-05")),
+            CacheLookup::Hit(vec![2; 600 * 1024])
+        );
+
+        let stats = cache.stats();
+        assert_eq!((stats.spilled_count, stats.rejected_count), (1, 0));
+        assert_eq!(stats.items_count, 1);
+
+        // Without a spill store the entry is rejected
+        let cache = ExampleCache::new(config);
+        cache.store(
+            "hotel1",
+            "2025-06-01",
+            "2025-06-05",
+            vec![1; 600 * 1024],
+            None,
+        );
+        assert!(!cache.store(
+            "hotel2",
+            "2025-06-01",
+            "2025-06-05",
+            vec![2; 600 * 1024],
+            None
+        ));
+        let stats = cache.stats();
+        assert_eq!((stats.spilled_count, stats.rejected_count), (0, 1));
+    }
 }
@@ -0,0 +1,164 @@
+// Admission filter
+// Long-tail hotel/date combinations are often requested once and never again; caching them
+// evicts hot entries for nothing. With an admission filter (a TinyLFU-style doorkeeper) a key
+// that is not cached yet is only stored once it was requested `min_requests` times within the
+// window. Every store attempt counts as a request, as read-through callers store after each
+// miss; updates of keys already cached always pass.
+// Request counts are kept in a `FrequencySketch` that never ages: fixed memory, never
+// undercounts, and collisions can only let a key in early. The sketch is cleared at the end of
+// every window. Its counters stop at 15, so `min_requests` can't be higher; a higher value is
+// lowered to 15 with a warning.
+
+use super::eviction::FrequencySketch;
+use super::CacheKey;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AdmissionConfig {
+    // Requests within the window before a key is admitted, at most 15 (higher is lowered)
+    pub min_requests: u8,
+    pub window: Duration,
+    // Distinct keys expected per window, sizes the sketch (4 bytes per key)
+    pub expected_keys: usize,
+}
+
+impl Default for AdmissionConfig {
+    fn default() -> Self {
+        Self {
+            min_requests: 2,
+            window: Duration::from_secs(600),
+            expected_keys: 100_000,
+        }
+    }
+}
+
+pub(crate) struct AdmissionFilter {
+    min_requests: u8,
+    window_ns: u64,
+    sketch: FrequencySketch,
+    epoch: Instant,
+    window_started_ns: AtomicU64,
+    rejected_count: AtomicUsize,
+}
+
+impl AdmissionFilter {
+    pub(crate) fn new(config: &AdmissionConfig) -> Self {
+        let min_requests = if config.min_requests > FrequencySketch::MAX_COUNT {
+            tracing::warn!(
+                min_requests = config.min_requests,
+                max = FrequencySketch::MAX_COUNT,
+                "Admission min_requests above the counter limit, lowered to it"
+            );
+            FrequencySketch::MAX_COUNT
+        } else {
+            config.min_requests
+        };
+        Self {
+            min_requests,
+            window_ns: config.window.as_nanos() as u64,
+            sketch: FrequencySketch::without_aging(config.expected_keys),
+            epoch: Instant::now(),
+            window_started_ns: AtomicU64::new(0),
+            rejected_count: AtomicUsize::new(0),
+        }
+    }
+
+    // Count a request for `key` and decide whether it may be cached; counts a rejection if not
+    pub(crate) fn admit(&self, key: &CacheKey) -> bool {
+        self.roll_window();
+        self.sketch.increment(key);
+        if self.sketch.estimate(key) >= self.min_requests {
+            true
+        } else {
+            self.rejected_count.fetch_add(1, Ordering::SeqCst);
+            false
+        }
+    }
+
+    pub(crate) fn rejected_count(&self) -> usize {
+        self.rejected_count.load(Ordering::SeqCst)
+    }
+
+    // Start a new window once the current one is over; only one caller clears the sketch
+    fn roll_window(&self) {
+        let now_ns = self.epoch.elapsed().as_nanos() as u64;
+        let started_ns = self.window_started_ns.load(Ordering::Relaxed);
+        if now_ns.saturating_sub(started_ns) < self.window_ns {
+            return;
+        }
+        if self
+            .window_started_ns
+            .compare_exchange(started_ns, now_ns, Ordering::SeqCst, Ordering::Relaxed)
+            .is_ok()
+        {
+            self.sketch.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    fn key(i: usize) -> CacheKey {
+        CacheKey::new(format!("hotel{}", i), "2025-06-01", "2025-06-05")
+    }
+
+    #[test]
+    fn test_admits_on_second_request_within_window() {
+        let filter = AdmissionFilter::new(&AdmissionConfig {
+            window: Duration::from_millis(100),
+            ..Default::default()
+        });
+
+        assert!(!filter.admit(&key(1)));
+        assert!(!filter.admit(&key(2)));
+        assert!(filter.admit(&key(1)));
+        assert!(filter.admit(&key(1)));
+
+        // Requests from an earlier window are forgotten
+        thread::sleep(Duration::from_millis(150));
+        assert!(!filter.admit(&key(2)));
+        assert_eq!(filter.rejected_count(), 3);
+    }
+
+    #[test]
+    fn test_small_sketch_never_undercounts() {
+        let filter = AdmissionFilter::new(&AdmissionConfig {
+            min_requests: 3,
+            expected_keys: 16,
+            ..Default::default()
+        });
+        for i in 0..1000 {
+            filter.admit(&key(i));
+            filter.admit(&key(i));
+            assert!(filter.admit(&key(i)));
+        }
+    }
+
+    #[test]
+    fn test_admits_at_the_counter_limit() {
+        let filter = AdmissionFilter::new(&AdmissionConfig {
+            min_requests: 15,
+            ..Default::default()
+        });
+        for _ in 0..14 {
+            assert!(!filter.admit(&key(1)));
+        }
+        assert!(filter.admit(&key(1)));
+    }
+
+    #[test]
+    fn test_clamps_min_requests_above_the_counter_limit() {
+        let filter = AdmissionFilter::new(&AdmissionConfig {
+            min_requests: 200,
+            ..Default::default()
+        });
+        for _ in 0..14 {
+            assert!(!filter.admit(&key(1)));
+        }
+        assert!(filter.admit(&key(1)));
+    }
+}
@@ -0,0 +1,117 @@
+// Value compression
+// Availability payloads are mostly repetitive XML/JSON and shrink 5-10x, which lets the same
+// memory budget hold far more entries. Small values are left alone: the CPU cost isn't worth it.
+
+use std::io::{self, Read, Write};
+use std::sync::Arc;
+
+// Codec used for stored values
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Compression {
+    #[default]
+    None,
+    // flate2 level 0-9
+    Gzip {
+        level: u32,
+    },
+    // zstd level 1-22 (0 uses the zstd default)
+    Zstd {
+        level: i32,
+    },
+}
+
+impl Compression {
+    pub fn compress(&self, data: &[u8]) -> io::Result<Vec<u8>> {
+        match self {
+            Compression::None => Ok(data.to_vec()),
+            Compression::Gzip { level } => {
+                let mut encoder = flate2::write::GzEncoder::new(
+                    Vec::with_capacity(data.len() / 4),
+                    flate2::Compression::new((*level).min(9)),
+                );
+                encoder.write_all(data)?;
+                encoder.finish()
+            }
+            Compression::Zstd { level } => zstd::encode_all(data, *level),
+        }
+    }
+
+    pub fn decompress(&self, data: &[u8]) -> io::Result<Vec<u8>> {
+        match self {
+            Compression::None => Ok(data.to_vec()),
+            Compression::Gzip { .. } => {
+                let mut decoded = Vec::with_capacity(data.len() * 4);
+                flate2::read::GzDecoder::new(data).read_to_end(&mut decoded)?;
+                Ok(decoded)
+            }
+            Compression::Zstd { .. } => zstd::decode_all(data),
+        }
+    }
+
+    // Like `decompress`, but uncompressed values are shared instead of copied
+    pub fn decompress_shared(&self, data: &Arc<[u8]>) -> io::Result<Arc<[u8]>> {
+        match self {
+            Compression::None => Ok(Arc::clone(data)),
+            _ => self.decompress(data).map(Arc::from),
+        }
+    }
+
+    // Compress `data` if it is at least `threshold` bytes long and actually gets smaller
+    // Returns the stored bytes together with the codec needed to read them back
+    pub fn compress_above(&self, data: Vec<u8>, threshold: usize) -> (Vec<u8>, Compression) {
+        if *self == Compression::None || data.len() < threshold {
+            return (data, Compression::None);
+        }
+        match self.compress(&data) {
+            Ok(compressed) if compressed.len() < data.len() => (compressed, *self),
+            Ok(_) => (data, Compression::None),
+            Err(e) => {
+                tracing::warn!("Storing value uncompressed, {:?} failed: {}", self, e);
+                (data, Compression::None)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn payload() -> Vec<u8> {
+        "<hotel code=\"39776757\"><room>DBL.ST</room><price>84.82</price></hotel>"
+            .repeat(200)
+            .into_bytes()
+    }
+
+    #[test]
+    fn test_roundtrip_all_codecs() {
+        for codec in [
+            Compression::None,
+            Compression::Gzip { level: 6 },
+            Compression::Zstd { level: 3 },
+        ] {
+            let compressed = codec.compress(&payload()).unwrap();
+            assert_eq!(codec.decompress(&compressed).unwrap(), payload());
+            if codec != Compression::None {
+                assert!(compressed.len() < payload().len() / 5);
+            }
+        }
+    }
+
+    #[test]
+    fn test_compress_above_threshold_only() {
+        let codec = Compression::Zstd { level: 3 };
+
+        let (stored, used) = codec.compress_above(vec![1, 2, 3], 1024);
+        assert_eq!((stored, used), (vec![1, 2, 3], Compression::None));
+
+        let (stored, used) = codec.compress_above(payload(), 1024);
+        assert_eq!(used, codec);
+        assert!(stored.len() < payload().len());
+
+        // Incompressible data is kept as is
+        let noise: Vec<u8> = (0..4096).map(|_| rand::random::<u8>()).collect();
+        let (stored, used) = codec.compress_above(noise.clone(), 1024);
+        assert_eq!((stored, used), (noise, Compression::None));
+    }
+}
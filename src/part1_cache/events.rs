@@ -0,0 +1,70 @@
+// Cache event listeners
+// Downstream components register callbacks to hear about entries leaving (or never entering)
+// the cache, e.g. to emit metrics or re-prefetch hot entries. Callbacks are always invoked
+// after the cache lock was released, so they may call back into the cache.
+
+use super::CacheKey;
+use std::sync::{Arc, RwLock};
+use std::time::Instant;
+
+// An entry that was evicted, expired or rejected
+#[derive(Debug, Clone)]
+pub struct EntryEvent {
+    pub key: CacheKey,
+    // Bytes accounted for the entry
+    pub size: usize,
+    // Reads while cached (0 for rejected entries)
+    pub access_count: usize,
+    pub last_accessed: Instant,
+}
+
+pub type Listener = Arc<dyn Fn(&EntryEvent) + Send + Sync>;
+
+#[derive(Default)]
+pub struct CacheListeners {
+    evict: RwLock<Vec<Listener>>,
+    expire: RwLock<Vec<Listener>>,
+    reject: RwLock<Vec<Listener>>,
+}
+
+impl CacheListeners {
+    // Entry removed to make room for new ones
+    pub fn on_evict(&self, listener: impl Fn(&EntryEvent) + Send + Sync + 'static) {
+        self.evict.write().unwrap().push(Arc::new(listener));
+    }
+
+    // Entry found expired and removed
+    pub fn on_expire(&self, listener: impl Fn(&EntryEvent) + Send + Sync + 'static) {
+        self.expire.write().unwrap().push(Arc::new(listener));
+    }
+
+    // Store refused by the cache
+    pub fn on_reject(&self, listener: impl Fn(&EntryEvent) + Send + Sync + 'static) {
+        self.reject.write().unwrap().push(Arc::new(listener));
+    }
+
+    pub fn notify_evicted(&self, events: &[EntryEvent]) {
+        notify(&self.evict, events);
+    }
+
+    pub fn notify_expired(&self, events: &[EntryEvent]) {
+        notify(&self.expire, events);
+    }
+
+    pub fn notify_rejected(&self, events: &[EntryEvent]) {
+        notify(&self.reject, events);
+    }
+}
+
+fn notify(listeners: &RwLock<Vec<Listener>>, events: &[EntryEvent]) {
+    if events.is_empty() {
+        return;
+    }
+    // Snapshot the list so listeners can register further listeners
+    let listeners: Vec<Listener> = listeners.read().unwrap().clone();
+    for event in events {
+        for listener in &listeners {
+            listener(event);
+        }
+    }
+}
@@ -0,0 +1,291 @@
+// Eviction strategies
+// A strategy ranks cache entries so the cache can pick which ones to drop when it runs out of space.
+// Strategies only see entry bookkeeping (never payloads) and may keep their own state,
+// e.g. the frequency sketch used by W-TinyLFU.
+
+use super::{CacheKey, EvictionPolicy};
+use std::cmp::Ordering;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicU8, AtomicUsize, Ordering as AtomicOrdering};
+use std::time::Instant;
+
+// Bookkeeping of one cache entry, as seen by an eviction strategy
+#[derive(Debug, Clone)]
+pub struct EvictionCandidate<'a> {
+    pub key: &'a CacheKey,
+    pub size: usize,
+    pub created_at: Instant,
+    pub last_accessed: Instant,
+    pub access_count: usize,
+    pub expires_at: Instant,
+}
+
+pub trait EvictionStrategy: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    // Compare two entries: the one ordered first (Less) is evicted first
+    fn compare(&self, a: &EvictionCandidate, b: &EvictionCandidate) -> Ordering;
+
+    // Sort candidates into eviction order (first element is evicted first)
+    // Strategies that need a global view of the entries can override this
+    fn order(&self, candidates: &mut [EvictionCandidate]) {
+        candidates.sort_by(|a, b| self.compare(a, b));
+    }
+
+    // Called when a key is inserted into the cache
+    fn record_insert(&self, _key: &CacheKey) {}
+
+    // Called on every cache hit
+    fn record_access(&self, _key: &CacheKey) {}
+}
+
+impl EvictionPolicy {
+    // Build the strategy implementing this policy
+    pub fn strategy(&self) -> Box<dyn EvictionStrategy> {
+        match self {
+            EvictionPolicy::LeastRecentlyUsed => Box::new(LruStrategy),
+            EvictionPolicy::LeastFrequentlyUsed => Box::new(LfuStrategy),
+            EvictionPolicy::TimeToLive => Box::new(TtlStrategy),
+            EvictionPolicy::FirstInFirstOut => Box::new(FifoStrategy),
+            EvictionPolicy::WindowTinyLfu => Box::new(WindowTinyLfuStrategy::default()),
+        }
+    }
+}
+
+// Evicts the entry that has not been read for the longest time
+pub struct LruStrategy;
+
+impl EvictionStrategy for LruStrategy {
+    fn name(&self) -> &'static str {
+        "lru"
+    }
+
+    fn compare(&self, a: &EvictionCandidate, b: &EvictionCandidate) -> Ordering {
+        a.last_accessed.cmp(&b.last_accessed)
+    }
+}
+
+// Evicts the entry with the fewest reads, least recently used first on ties
+pub struct LfuStrategy;
+
+impl EvictionStrategy for LfuStrategy {
+    fn name(&self) -> &'static str {
+        "lfu"
+    }
+
+    fn compare(&self, a: &EvictionCandidate, b: &EvictionCandidate) -> Ordering {
+        a.access_count
+            .cmp(&b.access_count)
+            .then(a.last_accessed.cmp(&b.last_accessed))
+    }
+}
+
+// Evicts the oldest inserted entry regardless of reads
+pub struct FifoStrategy;
+
+impl EvictionStrategy for FifoStrategy {
+    fn name(&self) -> &'static str {
+        "fifo"
+    }
+
+    fn compare(&self, a: &EvictionCandidate, b: &EvictionCandidate) -> Ordering {
+        a.created_at.cmp(&b.created_at)
+    }
+}
+
+// Evicts the entry closest to expiring
+pub struct TtlStrategy;
+
+impl EvictionStrategy for TtlStrategy {
+    fn name(&self) -> &'static str {
+        "ttl"
+    }
+
+    fn compare(&self, a: &EvictionCandidate, b: &EvictionCandidate) -> Ordering {
+        a.expires_at
+            .cmp(&b.expires_at)
+            .then(a.created_at.cmp(&b.created_at))
+    }
+}
+
+// Window TinyLFU
+// The most recently inserted entries form an admission window that is never evicted, so new
+// keys get a chance to build up reads. Everything else is ranked by its estimated popularity
+// from a count-min sketch, which remembers keys even after they were evicted.
+pub struct WindowTinyLfuStrategy {
+    sketch: FrequencySketch,
+    // Share of the entries (in percent) protected as the admission window
+    window_percent: usize,
+}
+
+impl WindowTinyLfuStrategy {
+    pub fn new(sketch_width: usize, window_percent: usize) -> Self {
+        Self {
+            sketch: FrequencySketch::new(sketch_width),
+            window_percent: window_percent.min(100),
+        }
+    }
+
+    pub fn frequency(&self, key: &CacheKey) -> u8 {
+        self.sketch.estimate(key)
+    }
+}
+
+impl Default for WindowTinyLfuStrategy {
+    fn default() -> Self {
+        Self::new(4096, 1)
+    }
+}
+
+impl EvictionStrategy for WindowTinyLfuStrategy {
+    fn name(&self) -> &'static str {
+        "w-tinylfu"
+    }
+
+    fn compare(&self, a: &EvictionCandidate, b: &EvictionCandidate) -> Ordering {
+        self.sketch
+            .estimate(a.key)
+            .cmp(&self.sketch.estimate(b.key))
+            .then(a.last_accessed.cmp(&b.last_accessed))
+    }
+
+    fn order(&self, candidates: &mut [EvictionCandidate]) {
+        // Newest entries (the window) go to the back, the main region is ranked by frequency
+        let window = (candidates.len() * self.window_percent).div_ceil(100);
+        candidates.sort_by_key(|c| std::cmp::Reverse(c.created_at));
+        let (window_region, main_region) = candidates.split_at_mut(window);
+        main_region.sort_by(|a, b| self.compare(a, b));
+        window_region.sort_by_key(|c| c.last_accessed);
+        candidates.rotate_left(window);
+    }
+
+    fn record_insert(&self, key: &CacheKey) {
+        self.sketch.increment(key);
+    }
+
+    fn record_access(&self, key: &CacheKey) {
+        self.sketch.increment(key);
+    }
+}
+
+// Count-min sketch with small saturating counters and periodic aging
+// Counters are halved once `width * 10` increments were recorded, so old popularity fades.
+// Without aging (`without_aging`) counts only drop on `clear`, and an estimate is never below
+// the true count (up to `MAX_COUNT`).
+pub struct FrequencySketch {
+    rows: [Vec<AtomicU8>; SKETCH_DEPTH],
+    mask: usize,
+    additions: AtomicUsize,
+    // Increments between agings, None to never age
+    sample_size: Option<usize>,
+}
+
+const SKETCH_DEPTH: usize = 4;
+const SKETCH_MAX_COUNT: u8 = 15;
+
+impl FrequencySketch {
+    // Counters saturate here
+    pub const MAX_COUNT: u8 = SKETCH_MAX_COUNT;
+
+    pub fn new(width: usize) -> Self {
+        let mut sketch = Self::without_aging(width);
+        sketch.sample_size = Some((sketch.mask + 1) * 10);
+        sketch
+    }
+
+    pub fn without_aging(width: usize) -> Self {
+        let width = width.max(16).next_power_of_two();
+        Self {
+            rows: std::array::from_fn(|_| (0..width).map(|_| AtomicU8::new(0)).collect()),
+            mask: width - 1,
+            additions: AtomicUsize::new(0),
+            sample_size: None,
+        }
+    }
+
+    pub fn increment<K: Hash + ?Sized>(&self, key: &K) {
+        for (row, counters) in self.rows.iter().enumerate() {
+            let counter = &counters[self.index(key, row)];
+            let _ = counter.fetch_update(AtomicOrdering::Relaxed, AtomicOrdering::Relaxed, |c| {
+                (c < SKETCH_MAX_COUNT).then_some(c + 1)
+            });
+        }
+
+        let Some(sample_size) = self.sample_size else {
+            return;
+        };
+        if self.additions.fetch_add(1, AtomicOrdering::Relaxed) + 1 >= sample_size {
+            self.age();
+        }
+    }
+
+    pub fn estimate<K: Hash + ?Sized>(&self, key: &K) -> u8 {
+        self.rows
+            .iter()
+            .enumerate()
+            .map(|(row, counters)| counters[self.index(key, row)].load(AtomicOrdering::Relaxed))
+            .min()
+            .unwrap_or(0)
+    }
+
+    // Forget all counts
+    pub fn clear(&self) {
+        self.additions.store(0, AtomicOrdering::Relaxed);
+        for counters in &self.rows {
+            for counter in counters {
+                counter.store(0, AtomicOrdering::Relaxed);
+            }
+        }
+    }
+
+    fn age(&self) {
+        self.additions.store(0, AtomicOrdering::Relaxed);
+        for counters in &self.rows {
+            for counter in counters {
+                let _ =
+                    counter.fetch_update(AtomicOrdering::Relaxed, AtomicOrdering::Relaxed, |c| {
+                        Some(c / 2)
+                    });
+            }
+        }
+    }
+
+    fn index<K: Hash + ?Sized>(&self, key: &K, row: usize) -> usize {
+        let mut hasher = DefaultHasher::new();
+        row.hash(&mut hasher);
+        key.hash(&mut hasher);
+        hasher.finish() as usize & self.mask
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frequency_sketch_counts_and_ages() {
+        let sketch = FrequencySketch::new(1024);
+        for _ in 0..8 {
+            sketch.increment("hot");
+        }
+        sketch.increment("cold");
+
+        assert_eq!(sketch.estimate("hot"), 8);
+        assert_eq!(sketch.estimate("cold"), 1);
+        assert_eq!(sketch.estimate("unknown"), 0);
+
+        // Counters saturate instead of overflowing
+        for _ in 0..100 {
+            sketch.increment("filler");
+        }
+        assert_eq!(sketch.estimate("filler"), SKETCH_MAX_COUNT);
+
+        // Reaching the sample size halves every counter
+        for _ in 0..(sketch.sample_size.unwrap() - 109) {
+            sketch.increment("filler");
+        }
+        assert_eq!(sketch.estimate("hot"), 4);
+        assert_eq!(sketch.estimate("cold"), 0);
+    }
+}
@@ -0,0 +1,263 @@
+// Lock-free latency histogram
+// HDR-style log-linear buckets: values below 2^SUB_BUCKET_BITS are counted exactly, above that
+// every power of two is split into 2^SUB_BUCKET_BITS linear sub-buckets, so any recorded value
+// is reported with a relative error below 1/32 (~3%) across the whole u64 range.
+// Recording is a couple of relaxed atomic adds, cheap enough for every cache lookup.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+const SUB_BUCKET_BITS: u32 = 5;
+const SUB_BUCKETS: usize = 1 << SUB_BUCKET_BITS;
+const BUCKETS: usize = SUB_BUCKETS + (64 - SUB_BUCKET_BITS as usize) * SUB_BUCKETS;
+
+pub struct LatencyHistogram {
+    buckets: Box<[AtomicU64]>,
+    count: AtomicU64,
+    sum: AtomicU64,
+    max: AtomicU64,
+}
+
+// Point-in-time view of a histogram, all values in nanoseconds
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct LatencySummary {
+    pub count: u64,
+    pub mean_ns: u64,
+    pub p50_ns: u64,
+    pub p95_ns: u64,
+    pub p99_ns: u64,
+    pub max_ns: u64,
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        Self {
+            buckets: (0..BUCKETS).map(|_| AtomicU64::new(0)).collect(),
+            count: AtomicU64::new(0),
+            sum: AtomicU64::new(0),
+            max: AtomicU64::new(0),
+        }
+    }
+}
+
+impl std::fmt::Debug for LatencyHistogram {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("LatencyHistogram")
+            .field(&self.summary())
+            .finish()
+    }
+}
+
+impl LatencyHistogram {
+    pub fn record(&self, value_ns: u64) {
+        self.buckets[bucket_index(value_ns)].fetch_add(1, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum.fetch_add(value_ns, Ordering::Relaxed);
+        self.max.fetch_max(value_ns, Ordering::Relaxed);
+    }
+
+    // Add the values recorded in `other`
+    pub fn merge(&self, other: &LatencyHistogram) {
+        for (bucket, recorded) in self.buckets.iter().zip(other.buckets.iter()) {
+            let recorded = recorded.load(Ordering::Relaxed);
+            if recorded > 0 {
+                bucket.fetch_add(recorded, Ordering::Relaxed);
+            }
+        }
+        self.count
+            .fetch_add(other.count.load(Ordering::Relaxed), Ordering::Relaxed);
+        self.sum
+            .fetch_add(other.sum.load(Ordering::Relaxed), Ordering::Relaxed);
+        self.max
+            .fetch_max(other.max.load(Ordering::Relaxed), Ordering::Relaxed);
+    }
+
+    // Smallest recorded value v such that at least `quantile` of all values are <= v,
+    // reported as the upper bound of its bucket
+    pub fn value_at_quantile(&self, quantile: f64) -> u64 {
+        let counts: Vec<u64> = self
+            .buckets
+            .iter()
+            .map(|bucket| bucket.load(Ordering::Relaxed))
+            .collect();
+        value_at_quantile(&counts, quantile).min(self.max.load(Ordering::Relaxed))
+    }
+
+    // How many recorded values are at most each of `bounds` (ascending), for cumulative buckets
+    // such as Prometheus's: a bucket of the histogram counts once all of it is within the bound,
+    // so values up to ~3% under a bound may be counted above it
+    pub fn cumulative_counts(&self, bounds: &[u64]) -> Vec<u64> {
+        let mut counts = Vec::with_capacity(bounds.len());
+        let mut seen = 0;
+        let mut buckets = self.buckets.iter().enumerate().peekable();
+        for bound in bounds {
+            while let Some((_, bucket)) =
+                buckets.next_if(|(index, _)| bucket_upper_bound(*index) <= *bound)
+            {
+                seen += bucket.load(Ordering::Relaxed);
+            }
+            counts.push(seen);
+        }
+        counts
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    // Of all recorded values
+    pub fn sum(&self) -> u64 {
+        self.sum.load(Ordering::Relaxed)
+    }
+
+    pub fn summary(&self) -> LatencySummary {
+        // Buckets are read once, so all percentiles come from the same snapshot
+        let counts: Vec<u64> = self
+            .buckets
+            .iter()
+            .map(|bucket| bucket.load(Ordering::Relaxed))
+            .collect();
+        let max_ns = self.max.load(Ordering::Relaxed);
+        let count = self.count.load(Ordering::Relaxed);
+
+        LatencySummary {
+            count,
+            mean_ns: self
+                .sum
+                .load(Ordering::Relaxed)
+                .checked_div(count)
+                .unwrap_or(0),
+            p50_ns: value_at_quantile(&counts, 0.50).min(max_ns),
+            p95_ns: value_at_quantile(&counts, 0.95).min(max_ns),
+            p99_ns: value_at_quantile(&counts, 0.99).min(max_ns),
+            max_ns,
+        }
+    }
+}
+
+fn bucket_index(value: u64) -> usize {
+    if value < SUB_BUCKETS as u64 {
+        return value as usize;
+    }
+    // Position of the highest set bit decides the power of two, the next
+    // SUB_BUCKET_BITS bits the linear sub-bucket within it
+    let exponent = 63 - value.leading_zeros();
+    let shift = exponent - SUB_BUCKET_BITS;
+    let mantissa = (value >> shift) as usize; // in [SUB_BUCKETS, 2 * SUB_BUCKETS)
+    SUB_BUCKETS + shift as usize * SUB_BUCKETS + (mantissa - SUB_BUCKETS)
+}
+
+// Largest value that falls into bucket `index`
+fn bucket_upper_bound(index: usize) -> u64 {
+    if index < SUB_BUCKETS {
+        return index as u64;
+    }
+    let shift = ((index - SUB_BUCKETS) / SUB_BUCKETS) as u32;
+    let mantissa = (SUB_BUCKETS + (index - SUB_BUCKETS) % SUB_BUCKETS) as u64;
+    // Wraps to u64::MAX for the topmost bucket
+    ((mantissa + 1) << shift).wrapping_sub(1)
+}
+
+fn value_at_quantile(counts: &[u64], quantile: f64) -> u64 {
+    let total: u64 = counts.iter().sum();
+    if total == 0 {
+        return 0;
+    }
+    let rank = ((quantile.clamp(0.0, 1.0) * total as f64).ceil() as u64).max(1);
+
+    let mut seen = 0;
+    for (index, count) in counts.iter().enumerate() {
+        seen += count;
+        if seen >= rank {
+            return bucket_upper_bound(index);
+        }
+    }
+    bucket_upper_bound(counts.len() - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket_bounds_cover_values() {
+        for value in [
+            0,
+            1,
+            31,
+            32,
+            33,
+            63,
+            64,
+            1000,
+            123_456_789,
+            u64::MAX / 3,
+            u64::MAX,
+        ] {
+            let index = bucket_index(value);
+            assert!(index < BUCKETS);
+            let upper = bucket_upper_bound(index);
+            assert!(upper >= value, "{} > {}", value, upper);
+            // Relative error bounded by the sub-bucket resolution
+            assert!((upper - value) as f64 <= value as f64 / SUB_BUCKETS as f64);
+        }
+    }
+
+    #[test]
+    fn test_percentiles() {
+        let histogram = LatencyHistogram::default();
+        // 1..=1000 µs
+        for micros in 1..=1000u64 {
+            histogram.record(micros * 1000);
+        }
+
+        let summary = histogram.summary();
+        assert_eq!(summary.count, 1000);
+        assert_eq!(summary.max_ns, 1_000_000);
+        assert_eq!(summary.mean_ns, 500_500);
+        for (reported, expected) in [
+            (summary.p50_ns, 500_000.0),
+            (summary.p95_ns, 950_000.0),
+            (summary.p99_ns, 990_000.0),
+        ] {
+            let error = (reported as f64 - expected).abs() / expected;
+            assert!(error < 0.04, "{} vs {}", reported, expected);
+        }
+
+        assert_eq!(
+            LatencyHistogram::default().summary(),
+            LatencySummary::default()
+        );
+    }
+
+    #[test]
+    fn test_merge() {
+        let (low, high) = (LatencyHistogram::default(), LatencyHistogram::default());
+        for micros in 1..=500u64 {
+            low.record(micros * 1000);
+            high.record((micros + 500) * 1000);
+        }
+        let merged = LatencyHistogram::default();
+        merged.merge(&low);
+        merged.merge(&high);
+
+        let summary = merged.summary();
+        assert_eq!(summary.count, 1000);
+        assert_eq!(summary.max_ns, 1_000_000);
+        assert_eq!(summary.mean_ns, 500_500);
+        let error = (summary.p95_ns as f64 - 950_000.0).abs() / 950_000.0;
+        assert!(error < 0.04);
+    }
+    #[test]
+    fn test_cumulative_counts() {
+        let histogram = LatencyHistogram::default();
+        for value in [1, 5, 10, 20, 31, 1_000, 1_000_000] {
+            histogram.record(value);
+        }
+        assert_eq!(
+            histogram.cumulative_counts(&[0, 5, 20, 31, 2_000, u64::MAX]),
+            [0, 2, 4, 5, 6, 7]
+        );
+        assert_eq!(histogram.count(), 7);
+        assert_eq!(histogram.sum(), 1_001_067);
+    }
+}
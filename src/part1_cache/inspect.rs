@@ -0,0 +1,159 @@
+// Cache inspection
+// Lists what is currently cached with per-entry metadata, e.g. for an admin endpoint.
+// Only metadata is copied (under the cache lock, without payloads), so listing a large cache
+// is cheap; the entries are a point-in-time view and may be gone by the time they are used.
+// `get_with_metadata` reads a single entry together with its metadata, e.g. to stamp
+// "price as of" information onto search results.
+
+use super::CacheKey;
+use std::time::{Duration, Instant, SystemTime};
+
+// Entries are attributed to a supplier by tagging them "supplier:<name>" on store
+pub const SUPPLIER_TAG_PREFIX: &str = "supplier:";
+
+// Which entries to list; unset fields match everything
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EntryFilter {
+    pub hotel_id: Option<String>,
+    pub check_in: Option<String>,
+    pub check_out: Option<String>,
+    pub tag: Option<String>,
+}
+
+impl EntryFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn hotel_id(mut self, hotel_id: impl Into<String>) -> Self {
+        self.hotel_id = Some(hotel_id.into());
+        self
+    }
+
+    pub fn check_in(mut self, check_in: impl Into<String>) -> Self {
+        self.check_in = Some(check_in.into());
+        self
+    }
+
+    pub fn check_out(mut self, check_out: impl Into<String>) -> Self {
+        self.check_out = Some(check_out.into());
+        self
+    }
+
+    pub fn tag(mut self, tag: impl Into<String>) -> Self {
+        self.tag = Some(tag.into());
+        self
+    }
+
+    pub fn matches(&self, key: &CacheKey, tags: &[String]) -> bool {
+        key.matches(
+            self.hotel_id.as_deref(),
+            self.check_in.as_deref(),
+            self.check_out.as_deref(),
+        ) && self
+            .tag
+            .as_ref()
+            .is_none_or(|tag| tags.iter().any(|t| t == tag))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EntryInfo {
+    pub key: CacheKey,
+    // Bytes accounted against the cache size, see `EstimateSize`
+    pub size_bytes: usize,
+    pub age: Duration,
+    pub remaining_ttl: Duration,
+    pub access_count: usize,
+    // Time since the entry was last read (or stored, if never read)
+    pub idle: Duration,
+    pub tags: Vec<String>,
+    pub negative: bool,
+}
+
+// What is known about a stored value, see `get_with_metadata`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntryMetadata {
+    pub stored_at: SystemTime,
+    pub remaining_ttl: Duration,
+    // From the entry's supplier tag
+    pub supplier: Option<String>,
+    // The key's currency dimension
+    pub currency: Option<String>,
+    // Bytes accounted against the cache size, see `EstimateSize`
+    pub size_bytes: usize,
+}
+
+impl EntryMetadata {
+    pub(crate) fn new(
+        key: &CacheKey,
+        tags: &[String],
+        created_at: Instant,
+        ttl: Duration,
+        size_bytes: usize,
+    ) -> Self {
+        let age = created_at.elapsed();
+        Self {
+            stored_at: SystemTime::now() - age,
+            remaining_ttl: ttl.saturating_sub(age),
+            supplier: tags
+                .iter()
+                .find_map(|tag| tag.strip_prefix(SUPPLIER_TAG_PREFIX))
+                .map(str::to_string),
+            currency: key.dimension(CacheKey::CURRENCY).map(str::to_string),
+            size_bytes,
+        }
+    }
+}
+
+pub trait CacheInspect {
+    // Metadata of all live entries matching `filter`, in no particular order
+    fn iter_entries(&self, filter: &EntryFilter) -> impl Iterator<Item = EntryInfo>;
+
+    // Like `get_key`, also returning the entry's metadata; counts as a regular lookup
+    fn get_with_metadata(&self, key: &CacheKey) -> Option<(Vec<u8>, EntryMetadata)>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter_matches_key_and_tag() {
+        let key = CacheKey::new("hotel1", "2025-06-01", "2025-06-05");
+        let tags = vec!["supplier:acme".to_string()];
+
+        assert!(EntryFilter::new().matches(&key, &[]));
+        assert!(EntryFilter::new()
+            .hotel_id("hotel1")
+            .check_out("2025-06-05")
+            .tag("supplier:acme")
+            .matches(&key, &tags));
+        assert!(!EntryFilter::new()
+            .check_in("2025-06-02")
+            .matches(&key, &tags));
+        assert!(!EntryFilter::new().tag("supplier:acme").matches(&key, &[]));
+    }
+
+    #[test]
+    fn test_metadata_from_key_and_tags() {
+        let key = CacheKey::new("hotel1", "2025-06-01", "2025-06-05")
+            .with_dimension(CacheKey::CURRENCY, "EUR");
+        let tags = vec!["dest:PAR".to_string(), "supplier:acme".to_string()];
+        let metadata = EntryMetadata::new(&key, &tags, Instant::now(), Duration::from_secs(60), 10);
+
+        assert_eq!(metadata.supplier.as_deref(), Some("acme"));
+        assert_eq!(metadata.currency.as_deref(), Some("EUR"));
+        assert!(metadata.stored_at <= SystemTime::now());
+        assert!(metadata.remaining_ttl <= Duration::from_secs(60));
+
+        let untagged = EntryMetadata::new(
+            &CacheKey::new("hotel1", "2025-06-01", "2025-06-05"),
+            &[],
+            Instant::now(),
+            Duration::from_secs(60),
+            10,
+        );
+        assert_eq!((untagged.supplier, untagged.currency), (None, None));
+    }
+}
@@ -0,0 +1,341 @@
+// Structured cache keys
+// Entries are keyed by hotel and stay dates plus any number of named search dimensions
+// (nationality, currency, occupancy, ...) that change the availability returned by suppliers.
+// The textual form is only used where a string is required (Redis keys, the persistence file):
+//
+//   hotel_id:check_in:check_out[;name=value]*
+//
+// with ':', ';', '=' and '%' percent-encoded inside each component, so IDs containing
+// separators round-trip safely.
+
+use super::size::EstimateSize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct CacheKey {
+    pub hotel_id: String,
+    pub check_in: String,
+    pub check_out: String,
+    // Sorted by name, so the same search always builds the same key
+    dimensions: BTreeMap<String, String>,
+}
+
+// Search parameters besides hotel and dates that suppliers price differently
+// Unset fields are left out of the key, so a context with nothing set is the plain key
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct SearchContext {
+    // ISO 3166-1 alpha-2 country of the guest (source market)
+    pub nationality: Option<String>,
+    // ISO 4217 currency the prices are requested in
+    pub currency: Option<String>,
+    pub adults: Option<u32>,
+    pub children: Option<u32>,
+    // ISO 3166-1 alpha-2 markets the search was made for; in any order, the key sorts them
+    pub markets: Vec<String>,
+    // The supplier searched, when the search was routed to one
+    pub supplier: Option<String>,
+}
+
+impl SearchContext {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn nationality(mut self, nationality: impl Into<String>) -> Self {
+        self.nationality = Some(nationality.into());
+        self
+    }
+
+    pub fn currency(mut self, currency: impl Into<String>) -> Self {
+        self.currency = Some(currency.into());
+        self
+    }
+
+    pub fn occupancy(mut self, adults: u32, children: u32) -> Self {
+        self.adults = Some(adults);
+        self.children = Some(children);
+        self
+    }
+
+    pub fn market(mut self, market: impl Into<String>) -> Self {
+        self.markets.push(market.into());
+        self
+    }
+
+    pub fn supplier(mut self, supplier: impl Into<String>) -> Self {
+        self.supplier = Some(supplier.into());
+        self
+    }
+
+    // The key dimensions this context contributes
+    // Occupancy is a single "adults+children" dimension, missing counts are 0
+    pub fn dimensions(&self) -> Vec<(&'static str, String)> {
+        let mut dimensions = Vec::new();
+        if let Some(nationality) = &self.nationality {
+            dimensions.push((CacheKey::NATIONALITY, nationality.clone()));
+        }
+        if let Some(currency) = &self.currency {
+            dimensions.push((CacheKey::CURRENCY, currency.clone()));
+        }
+        if self.adults.is_some() || self.children.is_some() {
+            dimensions.push((
+                CacheKey::OCCUPANCY,
+                format!(
+                    "{}+{}",
+                    self.adults.unwrap_or(0),
+                    self.children.unwrap_or(0)
+                ),
+            ));
+        }
+        if !self.markets.is_empty() {
+            let mut markets = self.markets.clone();
+            markets.sort();
+            markets.dedup();
+            dimensions.push((CacheKey::MARKETS, markets.join(",")));
+        }
+        if let Some(supplier) = &self.supplier {
+            dimensions.push((CacheKey::SUPPLIER, supplier.clone()));
+        }
+        dimensions
+    }
+}
+
+#[derive(Error, Debug, PartialEq)]
+#[error("Invalid cache key: {0}")]
+pub struct ParseCacheKeyError(String);
+
+impl CacheKey {
+    // Well-known dimension names
+    pub const NATIONALITY: &'static str = "nationality";
+    pub const CURRENCY: &'static str = "currency";
+    pub const OCCUPANCY: &'static str = "occupancy";
+    pub const MARKETS: &'static str = "markets";
+    pub const SUPPLIER: &'static str = "supplier";
+
+    pub fn new(
+        hotel_id: impl Into<String>,
+        check_in: impl Into<String>,
+        check_out: impl Into<String>,
+    ) -> Self {
+        Self {
+            hotel_id: hotel_id.into(),
+            check_in: check_in.into(),
+            check_out: check_out.into(),
+            dimensions: BTreeMap::new(),
+        }
+    }
+
+    // Add (or replace) a search dimension that is part of the key
+    pub fn with_dimension(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.dimensions.insert(name.into(), value.into());
+        self
+    }
+
+    // Add every dimension set in `context`
+    pub fn with_context(self, context: &SearchContext) -> Self {
+        context
+            .dimensions()
+            .into_iter()
+            .fold(self, |key, (name, value)| key.with_dimension(name, value))
+    }
+
+    pub fn dimension(&self, name: &str) -> Option<&str> {
+        self.dimensions.get(name).map(String::as_str)
+    }
+
+    pub fn dimensions(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.dimensions
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+
+    // True if the key matches every given component (None matches anything)
+    pub fn matches(
+        &self,
+        hotel_id: Option<&str>,
+        check_in: Option<&str>,
+        check_out: Option<&str>,
+    ) -> bool {
+        hotel_id.is_none_or(|h| self.hotel_id == h)
+            && check_in.is_none_or(|c| self.check_in == c)
+            && check_out.is_none_or(|c| self.check_out == c)
+    }
+
+    // True if the key carries every dimension set in `context` (an empty context matches
+    // all keys), e.g. a context with only a currency matches that currency in every market
+    pub fn matches_context(&self, context: &SearchContext) -> bool {
+        context
+            .dimensions()
+            .iter()
+            .all(|(name, value)| self.dimension(name) == Some(value.as_str()))
+    }
+}
+
+impl EstimateSize for CacheKey {
+    fn heap_size(&self) -> usize {
+        self.hotel_id.heap_size()
+            + self.check_in.heap_size()
+            + self.check_out.heap_size()
+            + self.dimensions.heap_size()
+    }
+}
+
+impl fmt::Display for CacheKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}",
+            escape(&self.hotel_id),
+            escape(&self.check_in),
+            escape(&self.check_out)
+        )?;
+        for (name, value) in &self.dimensions {
+            write!(f, ";{}={}", escape(name), escape(value))?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for CacheKey {
+    type Err = ParseCacheKeyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || ParseCacheKeyError(s.to_string());
+
+        let mut sections = s.split(';');
+        let parts: Vec<&str> = sections.next().unwrap_or_default().split(':').collect();
+        let [hotel_id, check_in, check_out] = parts[..] else {
+            return Err(invalid());
+        };
+        let mut key = CacheKey::new(
+            unescape(hotel_id).ok_or_else(invalid)?,
+            unescape(check_in).ok_or_else(invalid)?,
+            unescape(check_out).ok_or_else(invalid)?,
+        );
+
+        for dimension in sections {
+            let (name, value) = dimension.split_once('=').ok_or_else(invalid)?;
+            key = key.with_dimension(
+                unescape(name).ok_or_else(invalid)?,
+                unescape(value).ok_or_else(invalid)?,
+            );
+        }
+        Ok(key)
+    }
+}
+
+// Percent-encode the separators inside one key component
+pub(crate) fn escape(component: &str) -> String {
+    let mut escaped = String::with_capacity(component.len());
+    for c in component.chars() {
+        match c {
+            ':' | ';' | '=' | '%' => escaped.push_str(&format!("%{:02X}", c as u8)),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn unescape(component: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(component.len());
+    let mut iter = component.bytes();
+    while let Some(b) = iter.next() {
+        if b == b'%' {
+            let hex = [iter.next()?, iter.next()?];
+            bytes.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+        } else {
+            bytes.push(b);
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display_and_parse_roundtrip() {
+        let key = CacheKey::new("hotel1", "2025-06-01", "2025-06-05");
+        assert_eq!(key.to_string(), "hotel1:2025-06-01:2025-06-05");
+        assert_eq!(key.to_string().parse::<CacheKey>().unwrap(), key);
+
+        // Separators inside components don't break the key apart
+        let key = CacheKey::new("ACME:123;x=1%", "2025-06-01", "2025-06-05")
+            .with_dimension(CacheKey::NATIONALITY, "GB")
+            .with_dimension(CacheKey::CURRENCY, "EUR");
+        assert_eq!(
+            key.to_string(),
+            "ACME%3A123%3Bx%3D1%25:2025-06-01:2025-06-05;currency=EUR;nationality=GB"
+        );
+        assert_eq!(key.to_string().parse::<CacheKey>().unwrap(), key);
+
+        assert!("hotel1:2025-06-01".parse::<CacheKey>().is_err());
+        assert!("hotel1:2025-06-01:2025-06-05;currency"
+            .parse::<CacheKey>()
+            .is_err());
+        assert!("hotel%4:2025-06-01:2025-06-05".parse::<CacheKey>().is_err());
+    }
+
+    #[test]
+    fn test_dimensions_are_part_of_identity() {
+        let base = CacheKey::new("hotel1", "2025-06-01", "2025-06-05");
+        let gb = base.clone().with_dimension(CacheKey::NATIONALITY, "GB");
+        let gb_eur = gb.clone().with_dimension(CacheKey::CURRENCY, "EUR");
+        let eur_gb = base
+            .clone()
+            .with_dimension(CacheKey::CURRENCY, "EUR")
+            .with_dimension(CacheKey::NATIONALITY, "GB");
+
+        assert_ne!(base, gb);
+        assert_ne!(gb, gb_eur);
+        assert_eq!(gb_eur, eur_gb);
+        assert_eq!(gb_eur.dimension(CacheKey::CURRENCY), Some("EUR"));
+        assert!(gb_eur.matches(Some("hotel1"), None, Some("2025-06-05")));
+        assert!(!gb_eur.matches(Some("hotel2"), None, None));
+    }
+
+    #[test]
+    fn test_search_context_dimensions() {
+        let base = CacheKey::new("hotel1", "2025-06-01", "2025-06-05");
+        assert_eq!(base.clone().with_context(&SearchContext::new()), base);
+
+        let context = SearchContext::new()
+            .nationality("GB")
+            .currency("EUR")
+            .occupancy(2, 1);
+        let key = base.clone().with_context(&context);
+        assert_eq!(
+            key.to_string(),
+            "hotel1:2025-06-01:2025-06-05;currency=EUR;nationality=GB;occupancy=2+1"
+        );
+
+        assert!(key.matches_context(&context));
+        assert!(key.matches_context(&SearchContext::new().currency("EUR")));
+        assert!(key.matches_context(&SearchContext::new()));
+        assert!(!key.matches_context(&SearchContext::new().currency("USD")));
+        assert!(!base.matches_context(&SearchContext::new().currency("EUR")));
+
+        let markets = SearchContext::new().market("US").market("CA").market("US");
+        assert_eq!(
+            base.clone()
+                .with_context(&markets)
+                .dimension(CacheKey::MARKETS),
+            Some("CA,US")
+        );
+        assert_eq!(
+            base.clone().with_context(&markets),
+            base.clone()
+                .with_context(&SearchContext::new().market("CA").market("US"))
+        );
+
+        assert_eq!(
+            base.with_context(&SearchContext::new().supplier("alpha"))
+                .to_string(),
+            "hotel1:2025-06-01:2025-06-05;supplier=alpha"
+        );
+    }
+}
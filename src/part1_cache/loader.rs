@@ -0,0 +1,182 @@
+// Cache loaders
+// A loader fetches the availability for a key from the supplier side, so `prefetch` can warm
+// the cache with real data. Loads run on a bounded pool of scoped threads; results are handed
+// back to the calling thread, which stores them as they arrive.
+
+use super::{AvailabilityCache, CacheKey};
+use std::future::Future;
+use std::sync::mpsc;
+use std::sync::Mutex;
+use std::time::Duration;
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, PartialEq)]
+#[error("Failed to load {key}: {reason}")]
+pub struct LoadError {
+    pub key: String,
+    pub reason: String,
+}
+
+impl LoadError {
+    pub fn new(key: &CacheKey, reason: impl Into<String>) -> Self {
+        Self {
+            key: key.to_string(),
+            reason: reason.into(),
+        }
+    }
+}
+
+pub trait CacheLoader: Send + Sync {
+    fn load(&self, key: &CacheKey) -> Result<Vec<u8>, LoadError>;
+}
+
+// Plain closures can be used as loaders
+impl<F> CacheLoader for F
+where
+    F: Fn(&CacheKey) -> Result<Vec<u8>, LoadError> + Send + Sync,
+{
+    fn load(&self, key: &CacheKey) -> Result<Vec<u8>, LoadError> {
+        self(key)
+    }
+}
+
+// Adapts an async loading function (e.g. an HTTP call to the supplier) by driving it
+// on the given Tokio runtime from the prefetch worker threads
+pub struct AsyncLoader<F> {
+    runtime: tokio::runtime::Handle,
+    load: F,
+}
+
+impl<F> AsyncLoader<F> {
+    pub fn new(runtime: tokio::runtime::Handle, load: F) -> Self {
+        Self { runtime, load }
+    }
+}
+
+impl<F, Fut> CacheLoader for AsyncLoader<F>
+where
+    F: Fn(CacheKey) -> Fut + Send + Sync,
+    Fut: Future<Output = Result<Vec<u8>, LoadError>> + Send,
+{
+    fn load(&self, key: &CacheKey) -> Result<Vec<u8>, LoadError> {
+        self.runtime.block_on((self.load)(key.clone()))
+    }
+}
+
+// Load all `keys` with at most `parallelism` loads in flight, calling `on_loaded` on the
+// current thread for every successful load. Failed loads are logged and skipped.
+// Blocks until every key was attempted; from async code call it via `spawn_blocking`.
+pub fn load_all(
+    loader: &dyn CacheLoader,
+    keys: Vec<CacheKey>,
+    parallelism: usize,
+    mut on_loaded: impl FnMut(CacheKey, Vec<u8>),
+) {
+    let workers = parallelism.clamp(1, keys.len().max(1));
+    let queue = Mutex::new(keys.into_iter());
+    let (sender, receiver) = mpsc::channel();
+
+    std::thread::scope(|scope| {
+        for _ in 0..workers {
+            let sender = sender.clone();
+            let queue = &queue;
+            scope.spawn(move || loop {
+                let Some(key) = queue.lock().unwrap().next() else {
+                    break;
+                };
+                let result = loader.load(&key);
+                if sender.send((key, result)).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(sender);
+
+        for (key, result) in receiver {
+            match result {
+                Ok(data) => on_loaded(key, data),
+                Err(e) => tracing::warn!("Prefetch skipped: {}", e),
+            }
+        }
+    });
+}
+
+// `prefetch_keys` for backends holding an optional loader: loads every key and stores the
+// results in `cache`, returning the number stored
+pub(crate) fn prefetch_into(
+    cache: &impl AvailabilityCache,
+    loader: Option<&dyn CacheLoader>,
+    keys: Vec<CacheKey>,
+    ttl: Option<Duration>,
+    parallelism: usize,
+) -> usize {
+    let Some(loader) = loader else {
+        tracing::warn!(
+            "No cache loader configured, skipped prefetching {} keys",
+            keys.len()
+        );
+        return 0;
+    };
+
+    let mut count = 0;
+    load_all(loader, keys, parallelism, |key, data| {
+        if cache.store_key(key, data, ttl) {
+            count += 1;
+        }
+    });
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    fn keys(count: usize) -> Vec<CacheKey> {
+        (0..count)
+            .map(|i| CacheKey::new(format!("hotel{}", i), "2025-06-01", "2025-06-05"))
+            .collect()
+    }
+
+    #[test]
+    fn test_load_all_bounds_parallelism() {
+        let in_flight = AtomicUsize::new(0);
+        let max_in_flight = AtomicUsize::new(0);
+        let loader = |key: &CacheKey| {
+            let now = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            max_in_flight.fetch_max(now, Ordering::SeqCst);
+            std::thread::sleep(Duration::from_millis(10));
+            in_flight.fetch_sub(1, Ordering::SeqCst);
+
+            if key.hotel_id == "hotel3" {
+                Err(LoadError::new(key, "supplier timeout"))
+            } else {
+                Ok(key.hotel_id.clone().into_bytes())
+            }
+        };
+
+        let mut loaded = Vec::new();
+        load_all(&loader, keys(12), 4, |key, data| {
+            assert_eq!(key.hotel_id.as_bytes(), data.as_slice());
+            loaded.push(key);
+        });
+
+        assert_eq!(loaded.len(), 11);
+        assert!(max_in_flight.load(Ordering::SeqCst) <= 4);
+        assert!(max_in_flight.load(Ordering::SeqCst) > 1);
+    }
+
+    #[test]
+    fn test_async_loader() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let loader = AsyncLoader::new(runtime.handle().clone(), |key: CacheKey| async move {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+            Ok(key.hotel_id.into_bytes())
+        });
+
+        let mut loaded = 0;
+        load_all(&loader, keys(5), 2, |_, _| loaded += 1);
+        assert_eq!(loaded, 5);
+    }
+}
@@ -0,0 +1,315 @@
+// Model-based tests
+// Random sequences of store/get/invalidate/resize are applied to a real cache and to a
+// single-threaded reference model. Evictions are the only freedom the cache has: they are
+// taken from the eviction listener, checked against the policy's order and applied to the
+// model, after which both must agree on every lookup, the entry count and size accounting.
+
+use super::{
+    AvailabilityCache, CacheConfig, CacheInspect, CacheKey, CacheLookup, CacheStatsReport,
+    EntryFilter, EvictionPolicy, ExampleCache, ShardedCache,
+};
+use proptest::prelude::*;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+const HOTELS: u8 = 8;
+const CHECK_OUTS: u8 = 3;
+const MIB: usize = 1024 * 1024;
+
+fn key(id: u8) -> CacheKey {
+    CacheKey::new(
+        format!("hotel{}", id % HOTELS),
+        "2025-06-01",
+        format!("2025-06-0{}", 2 + id / HOTELS),
+    )
+}
+
+#[derive(Debug, Clone)]
+enum Op {
+    Store { key: u8, byte: u8, len: usize },
+    StoreEmpty { key: u8 },
+    Get { key: u8 },
+    Invalidate { hotel: u8 },
+    Resize { max_size_mb: usize },
+}
+
+fn op() -> impl Strategy<Value = Op> {
+    let key = 0..HOTELS * CHECK_OUTS;
+    prop_oneof![
+        // Large values, so a 1-2 MiB cache has to evict
+        4 => (key.clone(), any::<u8>(), 0..96 * 1024usize)
+            .prop_map(|(key, byte, len)| Op::Store { key, byte, len }),
+        1 => key.clone().prop_map(|key| Op::StoreEmpty { key }),
+        4 => key.prop_map(|key| Op::Get { key }),
+        1 => (0..HOTELS).prop_map(|hotel| Op::Invalidate { hotel }),
+        1 => (1..=2usize).prop_map(|max_size_mb| Op::Resize { max_size_mb }),
+    ]
+}
+
+struct ModelEntry {
+    // None for negative entries
+    value: Option<Vec<u8>>,
+    // Logical time of the store or last read, and reads since the store
+    touched: u64,
+    reads: usize,
+}
+
+#[derive(Default)]
+struct Model {
+    entries: HashMap<CacheKey, ModelEntry>,
+    clock: u64,
+}
+
+impl Model {
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+
+    // Position in eviction order, lower is evicted first; None if the policy isn't modelled
+    fn rank(&self, policy: EvictionPolicy, key: &CacheKey) -> Option<(usize, u64)> {
+        let entry = &self.entries[key];
+        match policy {
+            EvictionPolicy::LeastRecentlyUsed => Some((0, entry.touched)),
+            EvictionPolicy::LeastFrequentlyUsed => Some((entry.reads, entry.touched)),
+            _ => None,
+        }
+    }
+
+    // Check that `evicted` were first in eviction order among the entries, then drop them
+    fn evict(&mut self, policy: EvictionPolicy, evicted: &[CacheKey]) {
+        for key in evicted {
+            assert!(self.entries.contains_key(key), "evicted unknown {}", key);
+        }
+        let evicted_rank = evicted
+            .iter()
+            .filter_map(|key| self.rank(policy, key))
+            .max();
+        let survivor_rank = self
+            .entries
+            .keys()
+            .filter(|key| !evicted.contains(key))
+            .filter_map(|key| self.rank(policy, key))
+            .min();
+        if let (Some(evicted_rank), Some(survivor_rank)) = (evicted_rank, survivor_rank) {
+            assert!(
+                evicted_rank <= survivor_rank,
+                "{:?} evicted {:?} before {:?}",
+                policy,
+                evicted_rank,
+                survivor_rank
+            );
+        }
+        for key in evicted {
+            self.entries.remove(key);
+        }
+    }
+}
+
+// Caches under test, with their eviction listener wired to `evicted`
+trait ModelledCache: AvailabilityCache + CacheInspect {
+    fn create(config: CacheConfig, evicted: Arc<Mutex<Vec<CacheKey>>>) -> Self;
+}
+
+impl ModelledCache for ExampleCache {
+    fn create(config: CacheConfig, evicted: Arc<Mutex<Vec<CacheKey>>>) -> Self {
+        let cache = ExampleCache::new(config);
+        cache.on_evict(move |event| evicted.lock().unwrap().push(event.key.clone()));
+        cache
+    }
+}
+
+impl ModelledCache for ShardedCache {
+    fn create(config: CacheConfig, evicted: Arc<Mutex<Vec<CacheKey>>>) -> Self {
+        let cache = ShardedCache::new(config);
+        cache.on_evict(move |event| evicted.lock().unwrap().push(event.key.clone()));
+        cache
+    }
+}
+
+fn assert_monotonic(before: &CacheStatsReport, after: &CacheStatsReport) {
+    let counters = |stats: &CacheStatsReport| {
+        [
+            stats.hit_count,
+            stats.negative_hit_count,
+            stats.miss_count,
+            stats.eviction_count,
+            stats.expired_count,
+            stats.rejected_count,
+            stats.total_lookups,
+        ]
+    };
+    let (before, after) = (counters(before), counters(after));
+    assert!(
+        before.iter().zip(&after).all(|(b, a)| b <= a),
+        "stats went backwards: {:?} -> {:?}",
+        before,
+        after
+    );
+}
+
+fn run_model<C: ModelledCache>(policy: EvictionPolicy, ops: Vec<Op>) {
+    let evicted = Arc::new(Mutex::new(Vec::new()));
+    let cache = C::create(
+        CacheConfig {
+            max_size_mb: 1,
+            shards_count: 4,
+            eviction_policy: policy,
+            ..Default::default()
+        },
+        Arc::clone(&evicted),
+    );
+    let mut model = Model::default();
+    let mut max_size_mb = 1;
+    let mut stats = cache.stats();
+
+    for op in ops {
+        let result = match &op {
+            Op::Store { key: id, byte, len } => {
+                Some(cache.store_key(key(*id), vec![*byte; *len], None))
+            }
+            Op::StoreEmpty { key: id } => Some(cache.store_empty(key(*id), None)),
+            Op::Get { key: id } => {
+                let found = cache.lookup(&key(*id));
+                let tick = model.tick();
+                match model.entries.get_mut(&key(*id)) {
+                    Some(entry) => {
+                        let expected = match &entry.value {
+                            Some(value) => CacheLookup::Hit(value.clone()),
+                            None => CacheLookup::HitEmpty,
+                        };
+                        assert_eq!(found, expected, "{:?}", op);
+                        entry.touched = tick;
+                        entry.reads += 1;
+                    }
+                    None => assert_eq!(found, CacheLookup::Miss, "{:?}", op),
+                }
+                None
+            }
+            Op::Invalidate { hotel } => {
+                let hotel_id = format!("hotel{}", hotel);
+                let removed = cache.invalidate(Some(&hotel_id), None, None);
+                let before = model.entries.len();
+                model.entries.retain(|key, _| key.hotel_id != hotel_id);
+                assert_eq!(removed, before - model.entries.len(), "{:?}", op);
+                None
+            }
+            Op::Resize { max_size_mb: size } => {
+                max_size_mb = *size;
+                cache.resize(*size);
+                None
+            }
+        };
+
+        let evicted: Vec<CacheKey> = evicted.lock().unwrap().drain(..).collect();
+        model.evict(policy, &evicted);
+
+        // Rejected stores leave the previous entry (if it survived eviction) in place
+        if result == Some(true) {
+            let (id, value) = match op {
+                Op::Store { key, byte, len } => (key, Some(vec![byte; len])),
+                Op::StoreEmpty { key } => (key, None),
+                _ => unreachable!(),
+            };
+            let touched = model.tick();
+            model.entries.insert(
+                key(id),
+                ModelEntry {
+                    value,
+                    touched,
+                    reads: 0,
+                },
+            );
+        }
+
+        let after = cache.stats();
+        assert_monotonic(&stats, &after);
+        assert!(after.size_bytes <= max_size_mb * MIB);
+        assert_eq!(after.items_count, model.entries.len());
+        let listed: Vec<_> = cache.iter_entries(&EntryFilter::new()).collect();
+        assert_eq!(listed.len(), model.entries.len());
+        assert_eq!(
+            listed.iter().map(|info| info.size_bytes).sum::<usize>(),
+            after.size_bytes
+        );
+        stats = after;
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(32))]
+
+    #[test]
+    fn prop_example_cache_lru(ops in prop::collection::vec(op(), 1..120)) {
+        run_model::<ExampleCache>(EvictionPolicy::LeastRecentlyUsed, ops);
+    }
+
+    #[test]
+    fn prop_example_cache_lfu(ops in prop::collection::vec(op(), 1..120)) {
+        run_model::<ExampleCache>(EvictionPolicy::LeastFrequentlyUsed, ops);
+    }
+
+    #[test]
+    fn prop_example_cache_tinylfu(ops in prop::collection::vec(op(), 1..120)) {
+        run_model::<ExampleCache>(EvictionPolicy::WindowTinyLfu, ops);
+    }
+
+    #[test]
+    fn prop_sharded_cache_lru(ops in prop::collection::vec(op(), 1..120)) {
+        run_model::<ShardedCache>(EvictionPolicy::LeastRecentlyUsed, ops);
+    }
+
+    #[test]
+    fn prop_sharded_cache_lfu(ops in prop::collection::vec(op(), 1..120)) {
+        run_model::<ShardedCache>(EvictionPolicy::LeastFrequentlyUsed, ops);
+    }
+}
+
+// Concurrent stores and reads under eviction pressure; values are filled with their key's
+// id, so a read returning another key's (or a torn) value is caught
+// `overshoot` is how far past the limit racing stores may take the cache
+fn run_concurrent<C: ModelledCache + Sync>(overshoot: usize) {
+    let cache = C::create(
+        CacheConfig {
+            max_size_mb: 1,
+            shards_count: 4,
+            ..Default::default()
+        },
+        Arc::new(Mutex::new(Vec::new())),
+    );
+    thread::scope(|scope| {
+        for thread_id in 0..8u8 {
+            let cache = &cache;
+            scope.spawn(move || {
+                for i in 0..300usize {
+                    let id = ((i * 7 + thread_id as usize) % (HOTELS * CHECK_OUTS) as usize) as u8;
+                    if i % 3 == 0 {
+                        cache.store_key(key(id), vec![id; 64 * 1024 + i], None);
+                    } else if let CacheLookup::Hit(data) = cache.lookup(&key(id)) {
+                        assert!(data.iter().all(|&b| b == id));
+                    }
+                }
+            });
+        }
+    });
+
+    let stats = cache.stats();
+    assert!(stats.size_bytes <= MIB + overshoot);
+    assert!(stats.eviction_count > 0);
+    assert_eq!(stats.hit_count + stats.miss_count, stats.total_lookups);
+    let listed: Vec<_> = cache.iter_entries(&EntryFilter::new()).collect();
+    assert_eq!(listed.len(), stats.items_count);
+    assert_eq!(
+        listed.iter().map(|info| info.size_bytes).sum::<usize>(),
+        stats.size_bytes
+    );
+}
+
+#[test]
+fn test_concurrent_eviction_keeps_accounting() {
+    // One lock: the limit is exact
+    run_concurrent::<ExampleCache>(0);
+    // Capacity is checked per shard lock, each of the 8 threads may be a store ahead
+    run_concurrent::<ShardedCache>(8 * 70 * 1024);
+}
@@ -0,0 +1,206 @@
+// Disk persistence for warm restarts
+// Cache contents are written to a single file on shutdown and read back by `new()`.
+// Records are appended one after another, each carrying the TTL that was left at save time:
+//
+//   header: b"AVC2" | saved_at (u64, unix ms)
+//   record: key_len (u32) | key | remaining_ttl (u64, ms) | data_len (u32) | data
+//           | tag_count (u32) | (tag_len (u32) | tag)*
+//
+// Version 1 files (b"AVC1", records without tags) are still read.
+// All integers are little-endian. The file is written to a temporary path and renamed,
+// so a crash during shutdown never leaves a truncated cache file behind.
+
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const MAGIC: &[u8; 4] = b"AVC2";
+const MAGIC_V1: &[u8; 4] = b"AVC1";
+
+// One cache entry as stored on disk
+#[derive(Debug, Clone, PartialEq)]
+pub struct PersistedEntry {
+    pub key: String,
+    pub data: Vec<u8>,
+    pub remaining_ttl: Duration,
+    pub tags: Vec<String>,
+}
+
+// Write all entries to `path`, replacing any previous file
+pub fn save_entries(path: &Path, entries: &[PersistedEntry]) -> io::Result<()> {
+    let tmp_path = path.with_extension("tmp");
+    {
+        let mut writer = BufWriter::new(File::create(&tmp_path)?);
+        writer.write_all(MAGIC)?;
+        writer.write_all(&unix_millis(SystemTime::now()).to_le_bytes())?;
+
+        for entry in entries {
+            write_bytes(&mut writer, entry.key.as_bytes())?;
+            writer.write_all(&(entry.remaining_ttl.as_millis() as u64).to_le_bytes())?;
+            write_bytes(&mut writer, &entry.data)?;
+            writer.write_all(&(entry.tags.len() as u32).to_le_bytes())?;
+            for tag in &entry.tags {
+                write_bytes(&mut writer, tag.as_bytes())?;
+            }
+        }
+        writer.flush()?;
+    }
+    fs::rename(tmp_path, path)
+}
+
+// Read entries back, subtracting the time elapsed since they were saved
+// Entries whose TTL ran out while the service was down are skipped
+pub fn load_entries(path: &Path) -> io::Result<Vec<PersistedEntry>> {
+    let mut reader = BufReader::new(File::open(path)?);
+
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic)?;
+    let with_tags = match &magic {
+        MAGIC => true,
+        MAGIC_V1 => false,
+        _ => {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "not an availability cache file",
+            ))
+        }
+    };
+    let saved_at = read_u64(&mut reader)?;
+    let downtime = Duration::from_millis(unix_millis(SystemTime::now()).saturating_sub(saved_at));
+
+    let mut entries = Vec::new();
+    loop {
+        let key = match read_bytes(&mut reader) {
+            Ok(key) => key,
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e),
+        };
+        let key = read_string(key)?;
+        let remaining_ttl = Duration::from_millis(read_u64(&mut reader)?);
+        let data = read_bytes(&mut reader)?;
+        let mut tags = Vec::new();
+        if with_tags {
+            let mut count = [0u8; 4];
+            reader.read_exact(&mut count)?;
+            for _ in 0..u32::from_le_bytes(count) {
+                tags.push(read_string(read_bytes(&mut reader)?)?);
+            }
+        }
+
+        if let Some(remaining_ttl) = remaining_ttl.checked_sub(downtime) {
+            if !remaining_ttl.is_zero() {
+                entries.push(PersistedEntry {
+                    key,
+                    data,
+                    remaining_ttl,
+                    tags,
+                });
+            }
+        }
+    }
+    Ok(entries)
+}
+
+pub(crate) fn unix_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+pub(crate) fn write_bytes(writer: &mut impl Write, bytes: &[u8]) -> io::Result<()> {
+    writer.write_all(&(bytes.len() as u32).to_le_bytes())?;
+    writer.write_all(bytes)
+}
+
+pub(crate) fn read_bytes(reader: &mut impl Read) -> io::Result<Vec<u8>> {
+    let mut len = [0u8; 4];
+    reader.read_exact(&mut len)?;
+    let mut bytes = vec![0u8; u32::from_le_bytes(len) as usize];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+pub(crate) fn read_string(bytes: Vec<u8>) -> io::Result<String> {
+    String::from_utf8(bytes).map_err(|e| io::Error::new(ErrorKind::InvalidData, e))
+}
+
+pub(crate) fn read_u64(reader: &mut impl Read) -> io::Result<u64> {
+    let mut buf = [0u8; 8];
+    reader.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save_and_load_roundtrip() {
+        let path =
+            std::env::temp_dir().join(format!("avail-persist-{}.bin", rand::random::<u64>()));
+        let entries = vec![
+            PersistedEntry {
+                key: "hotel1:2025-06-01:2025-06-05".to_string(),
+                data: vec![1, 2, 3],
+                remaining_ttl: Duration::from_secs(300),
+                tags: vec!["supplier:acme".to_string(), "dest:PAR".to_string()],
+            },
+            PersistedEntry {
+                key: "hotel2:2025-06-01:2025-06-05".to_string(),
+                data: vec![],
+                remaining_ttl: Duration::from_secs(60),
+                tags: vec![],
+            },
+            // Already expired by the time it is loaded
+            PersistedEntry {
+                key: "hotel3:2025-06-01:2025-06-05".to_string(),
+                data: vec![4],
+                remaining_ttl: Duration::ZERO,
+                tags: vec![],
+            },
+        ];
+
+        save_entries(&path, &entries).unwrap();
+        let loaded = load_entries(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(loaded[0].key, entries[0].key);
+        assert_eq!(loaded[0].data, entries[0].data);
+        assert!(loaded[0].remaining_ttl <= entries[0].remaining_ttl);
+        assert!(loaded[0].remaining_ttl > Duration::from_secs(290));
+        assert_eq!(loaded[0].tags, entries[0].tags);
+        assert_eq!(loaded[1].data, Vec::<u8>::new());
+    }
+
+    #[test]
+    fn test_load_version_1_file() {
+        let path =
+            std::env::temp_dir().join(format!("avail-persist-{}.bin", rand::random::<u64>()));
+        let mut file = MAGIC_V1.to_vec();
+        file.extend_from_slice(&unix_millis(SystemTime::now()).to_le_bytes());
+        write_bytes(&mut file, b"hotel1:2025-06-01:2025-06-05").unwrap();
+        file.extend_from_slice(&60_000u64.to_le_bytes());
+        write_bytes(&mut file, &[1, 2, 3]).unwrap();
+        fs::write(&path, file).unwrap();
+
+        let loaded = load_entries(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].data, vec![1, 2, 3]);
+        assert!(loaded[0].tags.is_empty());
+    }
+
+    #[test]
+    fn test_load_rejects_foreign_file() {
+        let path =
+            std::env::temp_dir().join(format!("avail-persist-{}.bin", rand::random::<u64>()));
+        fs::write(&path, b"not a cache file").unwrap();
+        let result = load_entries(&path);
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,213 @@
+// Capacity quotas
+// Bound how much of the cache a namespace (hotel ID prefix, e.g. one supplier's "ACME:" IDs,
+// or a tag) may hold, so one supplier returning huge responses can't evict everyone else.
+// Stores that would push a namespace past its quota are rejected; entries they replace
+// count as freed. An entry can fall under several quotas and has to fit all of them.
+
+use super::CacheKey;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum QuotaScope {
+    // Entries whose hotel ID starts with the prefix
+    HotelPrefix(String),
+    // Entries stored with the tag
+    Tag(String),
+}
+
+impl QuotaScope {
+    fn matches(&self, key: &CacheKey, tags: &[String]) -> bool {
+        match self {
+            QuotaScope::HotelPrefix(prefix) => key.hotel_id.starts_with(prefix.as_str()),
+            QuotaScope::Tag(tag) => tags.iter().any(|t| t == tag),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Quota {
+    pub scope: QuotaScope,
+    // Limits on accounted bytes and entries, None for no limit
+    pub max_bytes: Option<usize>,
+    pub max_items: Option<usize>,
+}
+
+impl Quota {
+    pub fn hotel_prefix(prefix: impl Into<String>) -> Self {
+        Self::new(QuotaScope::HotelPrefix(prefix.into()))
+    }
+
+    pub fn tag(tag: impl Into<String>) -> Self {
+        Self::new(QuotaScope::Tag(tag.into()))
+    }
+
+    fn new(scope: QuotaScope) -> Self {
+        Self {
+            scope,
+            max_bytes: None,
+            max_items: None,
+        }
+    }
+
+    pub fn max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    pub fn max_items(mut self, max_items: usize) -> Self {
+        self.max_items = Some(max_items);
+        self
+    }
+}
+
+// Usage of one quota
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuotaReport {
+    pub scope: QuotaScope,
+    pub size_bytes: usize,
+    pub items_count: usize,
+    pub rejected_count: usize,
+}
+
+#[derive(Debug, Default)]
+struct QuotaUsage {
+    size_bytes: AtomicUsize,
+    items_count: AtomicUsize,
+    rejected_count: AtomicUsize,
+}
+
+// Usage counters for the configured quotas
+// Backends call `admit` and the accounting methods under the lock guarding the entry, so
+// with a single lock quotas are exact; with several (shards) concurrent stores into one
+// namespace can overshoot by a few entries
+#[derive(Debug, Default)]
+pub(crate) struct QuotaTracker {
+    quotas: Vec<Quota>,
+    usage: Vec<QuotaUsage>,
+}
+
+impl QuotaTracker {
+    pub(crate) fn new(quotas: Vec<Quota>) -> Self {
+        let usage = quotas.iter().map(|_| QuotaUsage::default()).collect();
+        Self { quotas, usage }
+    }
+
+    fn matching<'a>(
+        &'a self,
+        key: &'a CacheKey,
+        tags: &'a [String],
+    ) -> impl Iterator<Item = (&'a Quota, &'a QuotaUsage)> + 'a {
+        self.quotas
+            .iter()
+            .zip(&self.usage)
+            .filter(move |(quota, _)| quota.scope.matches(key, tags))
+    }
+
+    // Whether an entry of `size` bytes fits every quota it falls under, given the
+    // (size, tags) of the entry it replaces; counts a rejection on the quota that refused it
+    pub(crate) fn admit(
+        &self,
+        key: &CacheKey,
+        tags: &[String],
+        size: usize,
+        replaced: Option<(usize, &[String])>,
+    ) -> bool {
+        for (quota, usage) in self.matching(key, tags) {
+            let (freed_bytes, freed_items) = match replaced {
+                Some((replaced_size, replaced_tags)) if quota.scope.matches(key, replaced_tags) => {
+                    (replaced_size, 1)
+                }
+                _ => (0, 0),
+            };
+            let bytes = usage
+                .size_bytes
+                .load(Ordering::SeqCst)
+                .saturating_sub(freed_bytes)
+                + size;
+            let items = usage
+                .items_count
+                .load(Ordering::SeqCst)
+                .saturating_sub(freed_items)
+                + 1;
+            if quota.max_bytes.is_some_and(|max| bytes > max)
+                || quota.max_items.is_some_and(|max| items > max)
+            {
+                usage.rejected_count.fetch_add(1, Ordering::SeqCst);
+                return false;
+            }
+        }
+        true
+    }
+
+    pub(crate) fn account_added(&self, key: &CacheKey, tags: &[String], size: usize) {
+        for (_, usage) in self.matching(key, tags) {
+            usage.size_bytes.fetch_add(size, Ordering::SeqCst);
+            usage.items_count.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    pub(crate) fn account_removed(&self, key: &CacheKey, tags: &[String], size: usize) {
+        for (_, usage) in self.matching(key, tags) {
+            usage.size_bytes.fetch_sub(size, Ordering::SeqCst);
+            usage.items_count.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    pub(crate) fn rejected_count(&self) -> usize {
+        self.usage
+            .iter()
+            .map(|usage| usage.rejected_count.load(Ordering::SeqCst))
+            .sum()
+    }
+
+    pub(crate) fn report(&self) -> Vec<QuotaReport> {
+        self.quotas
+            .iter()
+            .zip(&self.usage)
+            .map(|(quota, usage)| QuotaReport {
+                scope: quota.scope.clone(),
+                size_bytes: usage.size_bytes.load(Ordering::SeqCst),
+                items_count: usage.items_count.load(Ordering::SeqCst),
+                rejected_count: usage.rejected_count.load(Ordering::SeqCst),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(hotel_id: &str) -> CacheKey {
+        CacheKey::new(hotel_id, "2025-06-01", "2025-06-05")
+    }
+
+    #[test]
+    fn test_admit_counts_replaced_entries_as_freed() {
+        let tracker = QuotaTracker::new(vec![
+            Quota::hotel_prefix("ACME:").max_bytes(100),
+            Quota::tag("bulk").max_items(1),
+        ]);
+        let tags = vec!["bulk".to_string()];
+
+        assert!(tracker.admit(&key("ACME:1"), &[], 80, None));
+        tracker.account_added(&key("ACME:1"), &[], 80);
+        assert!(!tracker.admit(&key("ACME:2"), &[], 30, None));
+        // Replacing the entry itself only needs room for the difference
+        assert!(tracker.admit(&key("ACME:1"), &[], 90, Some((80, &[]))));
+        // Other namespaces are not affected
+        assert!(tracker.admit(&key("OTHER:1"), &[], 1000, None));
+
+        tracker.account_added(&key("OTHER:1"), &tags, 10);
+        assert!(!tracker.admit(&key("OTHER:2"), &tags, 10, None));
+        assert!(tracker.admit(&key("OTHER:1"), &tags, 10, Some((10, &tags))));
+
+        tracker.account_removed(&key("ACME:1"), &[], 80);
+        assert!(tracker.admit(&key("ACME:2"), &[], 30, None));
+
+        let report = tracker.report();
+        assert_eq!((report[0].size_bytes, report[0].rejected_count), (0, 1));
+        assert_eq!((report[1].items_count, report[1].rejected_count), (1, 1));
+        assert_eq!(tracker.rejected_count(), 2);
+    }
+}
@@ -0,0 +1,765 @@
+// Redis-backed availability cache
+// Entries live in a shared Redis instance so every API node sees the same availability data.
+// Expiration is delegated to Redis (PX on SET); hit/miss/lookup statistics are tracked per node.
+// Entries expire inside Redis without telling any node, so no node can keep an item or byte
+// count; `stats` leaves them at 0 and `scan_stats` counts them with a SCAN of the namespace.
+// Tags are Redis sets of entry keys, living outside the entry key space ("{namespace}#tag:{tag}")
+// and expiring with their longest-lived member. Trimming tag TTLs needs Redis 7 (PEXPIRE NX/GT).
+// Every value starts with a marker byte telling a payload (`+`) from a negative entry (`-`), so
+// an empty payload still reads back as a `Hit`. Values without one read back as misses.
+// Entry versions are counters next to the entry ("{namespace}#ver:{key}"), bumped by every
+// store, expiring with it and deleted with it when it's invalidated; entries without a counter
+// count as absent for `store_if_version`.
+
+use super::key::escape as escape_key_component;
+use super::loader::{self, CacheLoader};
+use super::{
+    calculate_item_size, AvailabilityCache, CacheConfig, CacheKey, CacheLookup, CacheStats,
+    CacheStatsReport, CacheVersion, EvictionPolicy, SearchContext,
+};
+use redis::{Client, Commands, Connection, RedisResult};
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// Used by `AvailabilityCache::new` when the REDIS_URL environment variable is not set
+pub const DEFAULT_REDIS_URL: &str = "redis://127.0.0.1:6379/";
+
+// Prefix for every key written by the cache, so it can share a Redis database with other data
+pub const DEFAULT_KEY_NAMESPACE: &str = "avail";
+
+// Number of keys requested per SCAN round trip
+const SCAN_BATCH_SIZE: usize = 500;
+
+// First byte of every stored value, see `encode`
+const PAYLOAD_MARKER: u8 = b'+';
+const NEGATIVE_MARKER: u8 = b'-';
+
+// KEYS: entry, version counter; ARGV: expected version, value, TTL in ms
+// Returns the new version, nil if the entry changed
+const STORE_IF_VERSION_SCRIPT: &str = r"
+local current = 0
+if redis.call('EXISTS', KEYS[1]) == 1 then
+    current = tonumber(redis.call('GET', KEYS[2]) or '0')
+end
+if current ~= tonumber(ARGV[1]) then
+    return false
+end
+redis.call('SET', KEYS[1], ARGV[2], 'PX', ARGV[3])
+local version = redis.call('INCR', KEYS[2])
+redis.call('PEXPIRE', KEYS[2], ARGV[3])
+return version
+";
+
+pub struct RedisCache {
+    client: Client,
+    // Lazily (re)connected; dropped after an I/O failure so the next call reconnects
+    connection: Mutex<Option<Connection>>,
+    namespace: String,
+    config: Mutex<CacheConfig>,
+    loader: Option<Arc<dyn CacheLoader>>,
+    stats: CacheStats,
+}
+
+impl RedisCache {
+    // Create a cache talking to the Redis server at `url` (e.g. "redis://cache-host:6379/0")
+    // The connection is opened on first use, so this only fails for malformed URLs
+    pub fn connect(config: CacheConfig, url: &str) -> RedisResult<Self> {
+        Ok(Self {
+            client: Client::open(url)?,
+            connection: Mutex::new(None),
+            namespace: DEFAULT_KEY_NAMESPACE.to_string(),
+            config: Mutex::new(config),
+            loader: None,
+            stats: CacheStats::default(),
+        })
+    }
+
+    // Use a different key namespace (e.g. per environment when sharing one Redis)
+    pub fn with_namespace(mut self, namespace: &str) -> Self {
+        self.namespace = namespace.to_string();
+        self
+    }
+
+    // Fetch prefetched keys through `loader`
+    pub fn with_loader(mut self, loader: impl CacheLoader + 'static) -> Self {
+        self.loader = Some(Arc::new(loader));
+        self
+    }
+
+    // `stats` with the item count and size of the whole namespace (shared by all nodes)
+    // Walks every key with SCAN and a STRLEN each while holding the connection, which stalls
+    // this node's cache calls: for dashboards and diagnostics, not for request paths
+    pub fn scan_stats(&self) -> RedisResult<CacheStatsReport> {
+        let pattern = format!("{}:*", escape_glob(&self.namespace));
+        let keys = self.scan_keys(&pattern)?;
+        let lengths: Vec<usize> = self.with_connection(|con| {
+            let mut pipe = redis::pipe();
+            for key in &keys {
+                pipe.cmd("STRLEN").arg(key);
+            }
+            pipe.query(con)
+        })?;
+        let key_bytes: usize = keys.iter().map(|key| key.len()).sum();
+        let value_bytes: usize = lengths.iter().sum();
+        // Less a marker byte per value
+        let payload_bytes = value_bytes.saturating_sub(keys.len());
+        Ok(CacheStatsReport {
+            size_bytes: key_bytes + value_bytes,
+            items_count: keys.len(),
+            // Values are stored in Redis as given
+            raw_bytes: payload_bytes,
+            compressed_bytes: payload_bytes,
+            ..self.stats.report()
+        })
+    }
+
+    fn redis_key(&self, key: &CacheKey) -> String {
+        format!("{}:{}", self.namespace, key)
+    }
+
+    fn version_key(&self, key: &CacheKey) -> String {
+        format!("{}#ver:{}", self.namespace, key)
+    }
+
+    fn tag_key(&self, tag: &str) -> String {
+        format!("{}#tag:{}", self.namespace, tag)
+    }
+
+    // Inverse of `redis_key`, None for keys that weren't written by this cache
+    fn parse_redis_key(&self, redis_key: &str) -> Option<CacheKey> {
+        redis_key
+            .strip_prefix(&self.namespace)?
+            .strip_prefix(':')?
+            .parse()
+            .ok()
+    }
+
+    // Build a SCAN MATCH pattern for `invalidate`, using `*` for unspecified parts
+    // The trailing `*` also matches keys with extra dimensions, so results are
+    // re-checked with `CacheKey::matches`
+    fn invalidate_pattern(
+        &self,
+        hotel_id: Option<&str>,
+        check_in: Option<&str>,
+        check_out: Option<&str>,
+    ) -> String {
+        let part = |value: Option<&str>| {
+            value.map_or_else(
+                || "*".to_string(),
+                |v| escape_glob(&escape_key_component(v)),
+            )
+        };
+        let mut pattern = format!(
+            "{}:{}:{}:{}",
+            escape_glob(&self.namespace),
+            part(hotel_id),
+            part(check_in),
+            part(check_out)
+        );
+        if check_out.is_some() {
+            pattern.push('*');
+        }
+        pattern
+    }
+
+    // Add the commands storing one entry (value, version counter, tag sets) to `pipe`;
+    // a negative entry without `data`
+    fn queue_store(
+        &self,
+        pipe: &mut redis::Pipeline,
+        key: &CacheKey,
+        data: Option<Vec<u8>>,
+        ttl: Duration,
+        tags: &[&str],
+    ) {
+        let version_key = self.version_key(key);
+        let key = self.redis_key(key);
+
+        // PX takes milliseconds and rejects 0
+        let ttl_ms = (ttl.as_millis() as u64).max(1);
+        pipe.pset_ex(&key, encode(data), ttl_ms).ignore();
+        pipe.incr(&version_key, 1).ignore();
+        pipe.cmd("PEXPIRE").arg(&version_key).arg(ttl_ms).ignore();
+        for tag in tags {
+            let tag_key = self.tag_key(tag);
+            pipe.sadd(&tag_key, &key).ignore();
+            // Set a TTL on new sets, then only ever extend it
+            for option in ["NX", "GT"] {
+                pipe.cmd("PEXPIRE")
+                    .arg(&tag_key)
+                    .arg(ttl_ms)
+                    .arg(option)
+                    .ignore();
+            }
+        }
+    }
+
+    // Count a lookup started at `started` that found the stored value `found`
+    fn record_lookup(&self, found: Option<Vec<u8>>, started: Instant) -> CacheLookup {
+        let found = decode(found);
+        match found {
+            CacheLookup::HitEmpty => {
+                self.stats.hit_count.fetch_add(1, Ordering::SeqCst);
+                self.stats.negative_hit_count.fetch_add(1, Ordering::SeqCst);
+            }
+            CacheLookup::Hit(_) => {
+                self.stats.hit_count.fetch_add(1, Ordering::SeqCst);
+            }
+            CacheLookup::Miss => {
+                self.stats.miss_count.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+        self.stats.record_lookup_time(started);
+        found
+    }
+
+    // Store one entry in an atomic pipeline; a negative entry without `data`
+    fn store_entry(
+        &self,
+        key: &CacheKey,
+        data: Option<Vec<u8>>,
+        ttl: Duration,
+        tags: &[&str],
+    ) -> bool {
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+        self.queue_store(&mut pipe, key, data, ttl, tags);
+        match self.with_connection(|con| pipe.query::<()>(con)) {
+            Ok(()) => true,
+            Err(e) => {
+                tracing::warn!("Redis store failed for {}: {}", self.redis_key(key), e);
+                self.stats.rejected_count.fetch_add(1, Ordering::SeqCst);
+                false
+            }
+        }
+    }
+
+    // Delete entries with their version counters, and `extra` keys, in one atomic pipeline;
+    // returns how many entries were live
+    fn delete_entries(&self, keys: &[CacheKey], extra: &[String]) -> RedisResult<usize> {
+        let entry_keys: Vec<String> = keys.iter().map(|key| self.redis_key(key)).collect();
+        let version_keys: Vec<String> = keys.iter().map(|key| self.version_key(key)).collect();
+        let mut pipe = redis::pipe();
+        pipe.atomic().del(&entry_keys);
+        if !version_keys.is_empty() {
+            pipe.del(&version_keys).ignore();
+        }
+        if !extra.is_empty() {
+            pipe.del(extra).ignore();
+        }
+        let (removed,): (usize,) = self.with_connection(|con| pipe.query(con))?;
+        Ok(removed)
+    }
+
+    fn with_connection<T>(
+        &self,
+        op: impl FnOnce(&mut Connection) -> RedisResult<T>,
+    ) -> RedisResult<T> {
+        let mut guard = self.connection.lock().unwrap();
+        if guard.is_none() {
+            *guard = Some(self.client.get_connection()?);
+        }
+
+        let result = op(guard.as_mut().unwrap());
+        if let Err(e) = &result {
+            if e.is_io_error() || e.is_connection_dropped() {
+                *guard = None;
+            }
+        }
+        result
+    }
+
+    fn scan_keys(&self, pattern: &str) -> RedisResult<Vec<String>> {
+        self.with_connection(|con| {
+            let mut keys = Vec::new();
+            let mut cursor: u64 = 0;
+            loop {
+                let (next, batch): (u64, Vec<String>) = redis::cmd("SCAN")
+                    .arg(cursor)
+                    .arg("MATCH")
+                    .arg(pattern)
+                    .arg("COUNT")
+                    .arg(SCAN_BATCH_SIZE)
+                    .query(con)?;
+                keys.extend(batch);
+                if next == 0 {
+                    break;
+                }
+                cursor = next;
+            }
+            Ok(keys)
+        })
+    }
+}
+
+// The stored value of an entry: its marker, then the payload of a positive one
+fn encode(data: Option<Vec<u8>>) -> Vec<u8> {
+    match data {
+        Some(data) => {
+            let mut value = Vec::with_capacity(data.len() + 1);
+            value.push(PAYLOAD_MARKER);
+            value.extend(data);
+            value
+        }
+        None => vec![NEGATIVE_MARKER],
+    }
+}
+
+// Inverse of `encode`; unmarked values are misses
+fn decode(value: Option<Vec<u8>>) -> CacheLookup {
+    match value {
+        Some(mut value) if value.first() == Some(&PAYLOAD_MARKER) => {
+            value.remove(0);
+            CacheLookup::Hit(value)
+        }
+        Some(value) if value == [NEGATIVE_MARKER] => CacheLookup::HitEmpty,
+        _ => CacheLookup::Miss,
+    }
+}
+
+// Escape the characters Redis treats specially in MATCH patterns
+fn escape_glob(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '*' | '?' | '[' | ']' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+impl AvailabilityCache for RedisCache {
+    // Connects to REDIS_URL, falling back to DEFAULT_REDIS_URL
+    fn new(config: CacheConfig) -> Self {
+        let url = std::env::var("REDIS_URL").unwrap_or_else(|_| DEFAULT_REDIS_URL.to_string());
+        Self::connect(config, &url).expect("Invalid Redis URL")
+    }
+
+    fn store_tagged(
+        &self,
+        key: CacheKey,
+        data: Vec<u8>,
+        ttl: Option<Duration>,
+        tags: &[&str],
+    ) -> bool {
+        let (default_ttl_seconds, max_size_mb) = {
+            let config = self.config.lock().unwrap();
+            (config.default_ttl_seconds, config.max_size_mb)
+        };
+        let ttl = ttl.unwrap_or_else(|| Duration::from_secs(default_ttl_seconds));
+
+        // Memory limits are enforced by the Redis server (maxmemory), we only refuse
+        // single items that could never fit into the configured budget
+        if calculate_item_size(&key, &data) > max_size_mb * 1024 * 1024 {
+            self.stats.rejected_count.fetch_add(1, Ordering::SeqCst);
+            return false;
+        }
+
+        self.store_entry(&key, Some(data), ttl, tags)
+    }
+
+    fn store_empty(&self, key: CacheKey, ttl: Option<Duration>) -> bool {
+        let negative_ttl_seconds = self.config.lock().unwrap().negative_ttl_seconds;
+        let ttl = ttl.unwrap_or_else(|| Duration::from_secs(negative_ttl_seconds));
+        self.store_entry(&key, None, ttl, &[])
+    }
+
+    fn lookup(&self, key: &CacheKey) -> CacheLookup {
+        let now = Instant::now();
+        let key = self.redis_key(key);
+
+        self.stats.total_lookups.fetch_add(1, Ordering::SeqCst);
+
+        let result = self.with_connection(|con| con.get::<_, Option<Vec<u8>>>(&key));
+        let found = match result {
+            Ok(found) => found,
+            Err(e) => {
+                tracing::warn!("Redis get failed for {}: {}", key, e);
+                None
+            }
+        };
+        self.record_lookup(found, now)
+    }
+
+    // One MGET for the whole batch
+    fn get_many_keys(&self, keys: &[CacheKey]) -> Vec<CacheLookup> {
+        if keys.is_empty() {
+            return Vec::new();
+        }
+        let now = Instant::now();
+        let redis_keys: Vec<String> = keys.iter().map(|key| self.redis_key(key)).collect();
+
+        self.stats
+            .total_lookups
+            .fetch_add(keys.len(), Ordering::SeqCst);
+
+        let found = match self.with_connection(|con| {
+            redis::cmd("MGET")
+                .arg(&redis_keys)
+                .query::<Vec<Option<Vec<u8>>>>(con)
+        }) {
+            Ok(found) => found,
+            Err(e) => {
+                tracing::warn!("Redis batch get of {} keys failed: {}", keys.len(), e);
+                vec![None; keys.len()]
+            }
+        };
+
+        let results: Vec<CacheLookup> = found
+            .into_iter()
+            .map(|found| self.record_lookup(found, now))
+            .collect();
+        self.stats.record_batch(&results);
+        results
+    }
+
+    // All entries go out in one atomic pipeline, so they are stored or fail together
+    fn store_many(&self, entries: Vec<(CacheKey, Vec<u8>)>, ttl: Option<Duration>) -> Vec<bool> {
+        let (default_ttl_seconds, max_size_bytes) = {
+            let config = self.config.lock().unwrap();
+            (config.default_ttl_seconds, config.max_size_mb * 1024 * 1024)
+        };
+        let ttl = ttl.unwrap_or_else(|| Duration::from_secs(default_ttl_seconds));
+
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+        let mut stored: Vec<bool> = entries
+            .into_iter()
+            .map(|(key, data)| {
+                if calculate_item_size(&key, &data) > max_size_bytes {
+                    return false;
+                }
+                self.queue_store(&mut pipe, &key, Some(data), ttl, &[]);
+                true
+            })
+            .collect();
+
+        if stored.contains(&true) {
+            if let Err(e) = self.with_connection(|con| pipe.query::<()>(con)) {
+                tracing::warn!("Redis batch store failed: {}", e);
+                stored.iter_mut().for_each(|stored| *stored = false);
+            }
+        }
+        self.stats.rejected_count.fetch_add(
+            stored.iter().filter(|stored| !**stored).count(),
+            Ordering::SeqCst,
+        );
+        stored
+    }
+
+    fn get_versioned(&self, key: &CacheKey) -> (CacheLookup, CacheVersion) {
+        let now = Instant::now();
+        let version_key = self.version_key(key);
+        let key = self.redis_key(key);
+
+        self.stats.total_lookups.fetch_add(1, Ordering::SeqCst);
+
+        let result = self.with_connection(|con| {
+            redis::pipe()
+                .get(&key)
+                .get(&version_key)
+                .query::<(Option<Vec<u8>>, Option<u64>)>(con)
+        });
+        let (found, version) = match result {
+            Ok(found) => found,
+            Err(e) => {
+                tracing::warn!("Redis get failed for {}: {}", key, e);
+                (None, None)
+            }
+        };
+
+        let found = self.record_lookup(found, now);
+        let version = match (&found, version) {
+            (CacheLookup::Miss, _) | (_, None) => CacheVersion::ABSENT,
+            (_, Some(version)) => CacheVersion(version),
+        };
+        (found, version)
+    }
+
+    fn store_if_version(
+        &self,
+        key: CacheKey,
+        data: Vec<u8>,
+        ttl: Option<Duration>,
+        version: CacheVersion,
+    ) -> bool {
+        let (default_ttl_seconds, max_size_mb) = {
+            let config = self.config.lock().unwrap();
+            (config.default_ttl_seconds, config.max_size_mb)
+        };
+        let ttl = ttl.unwrap_or_else(|| Duration::from_secs(default_ttl_seconds));
+        if calculate_item_size(&key, &data) > max_size_mb * 1024 * 1024 {
+            self.stats.rejected_count.fetch_add(1, Ordering::SeqCst);
+            return false;
+        }
+        let version_key = self.version_key(&key);
+        let key = self.redis_key(&key);
+
+        let ttl_ms = (ttl.as_millis() as u64).max(1);
+        let result = self.with_connection(|con| {
+            redis::cmd("EVAL")
+                .arg(STORE_IF_VERSION_SCRIPT)
+                .arg(2)
+                .arg(&key)
+                .arg(&version_key)
+                .arg(version.0)
+                .arg(encode(Some(data)))
+                .arg(ttl_ms)
+                .query::<Option<u64>>(con)
+        });
+        match result {
+            Ok(Some(_)) => true,
+            Ok(None) => {
+                self.stats
+                    .version_conflict_count
+                    .fetch_add(1, Ordering::SeqCst);
+                false
+            }
+            Err(e) => {
+                tracing::warn!("Redis conditional store failed for {}: {}", key, e);
+                self.stats.rejected_count.fetch_add(1, Ordering::SeqCst);
+                false
+            }
+        }
+    }
+
+    // The traffic seen by this node only, without a Redis round trip; item count and sizes
+    // are left at 0, see `scan_stats`
+    fn stats(&self) -> CacheStatsReport {
+        self.stats.report()
+    }
+
+    // Eviction is performed by the Redis server according to its maxmemory-policy,
+    // which is shared with other tenants, so the policy is only recorded locally
+    fn set_eviction_policy(&self, policy: EvictionPolicy) {
+        self.config.lock().unwrap().eviction_policy = policy;
+    }
+
+    fn prefetch_keys(&self, keys: Vec<CacheKey>, ttl: Option<Duration>) -> usize {
+        let parallelism = self.config.lock().unwrap().prefetch_parallelism;
+        loader::prefetch_into(self, self.loader.as_deref(), keys, ttl, parallelism)
+    }
+
+    // Dimensions can't be matched reliably with a glob, so the search context is
+    // checked on the scanned keys
+    fn invalidate_in_context(
+        &self,
+        hotel_id: Option<&str>,
+        check_in: Option<&str>,
+        check_out: Option<&str>,
+        context: Option<&SearchContext>,
+    ) -> usize {
+        let pattern = self.invalidate_pattern(hotel_id, check_in, check_out);
+        let keys: Vec<CacheKey> = match self.scan_keys(&pattern) {
+            Ok(keys) => keys
+                .iter()
+                .filter_map(|key| self.parse_redis_key(key))
+                .filter(|key| {
+                    key.matches(hotel_id, check_in, check_out)
+                        && context.is_none_or(|context| key.matches_context(context))
+                })
+                .collect(),
+            Err(e) => {
+                tracing::warn!("Redis invalidate scan failed for {}: {}", pattern, e);
+                return 0;
+            }
+        };
+        if keys.is_empty() {
+            return 0;
+        }
+
+        match self.delete_entries(&keys, &[]) {
+            Ok(removed) => {
+                self.stats
+                    .eviction_count
+                    .fetch_add(removed, Ordering::SeqCst);
+                removed
+            }
+            Err(e) => {
+                tracing::warn!("Redis invalidate failed for {}: {}", pattern, e);
+                0
+            }
+        }
+    }
+
+    fn invalidate_by_tag(&self, tag: &str) -> usize {
+        let tag_key = self.tag_key(tag);
+        let result = self
+            .with_connection(|con| con.smembers::<_, Vec<String>>(&tag_key))
+            .and_then(|members| {
+                let keys: Vec<CacheKey> = members
+                    .iter()
+                    .filter_map(|member| self.parse_redis_key(member))
+                    .collect();
+                if keys.is_empty() {
+                    return Ok(0);
+                }
+                // Members may have expired or been invalidated already, only live ones count
+                self.delete_entries(&keys, std::slice::from_ref(&tag_key))
+            });
+
+        match result {
+            Ok(removed) => {
+                self.stats
+                    .eviction_count
+                    .fetch_add(removed, Ordering::SeqCst);
+                removed
+            }
+            Err(e) => {
+                tracing::warn!("Redis invalidate failed for tag {}: {}", tag, e);
+                0
+            }
+        }
+    }
+
+    // Members may have expired already
+    fn tagged_keys(&self, tag: &str) -> Vec<CacheKey> {
+        let tag_key = self.tag_key(tag);
+        match self.with_connection(|con| con.smembers::<_, Vec<String>>(&tag_key)) {
+            Ok(members) => members
+                .iter()
+                .filter_map(|member| self.parse_redis_key(member))
+                .collect(),
+            Err(e) => {
+                tracing::warn!("Redis tag read failed for {}: {}", tag, e);
+                Vec::new()
+            }
+        }
+    }
+
+    // The budget is only used to reject oversized items, see `store`
+    fn resize(&self, new_max_size_mb: usize) -> bool {
+        self.config.lock().unwrap().max_size_mb = new_max_size_mb;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_cache() -> RedisCache {
+        RedisCache::connect(CacheConfig::default(), DEFAULT_REDIS_URL).unwrap()
+    }
+
+    #[test]
+    fn test_invalidate_pattern() {
+        let cache = test_cache().with_namespace("test");
+
+        assert_eq!(
+            cache.invalidate_pattern(Some("hotel1"), None, None),
+            "test:hotel1:*:*"
+        );
+        assert_eq!(
+            cache.invalidate_pattern(None, Some("2025-06-01"), Some("2025-06-05")),
+            "test:*:2025-06-01:2025-06-05*"
+        );
+        assert_eq!(
+            cache.invalidate_pattern(Some("h*[1]?:x"), None, None),
+            "test:h\\*\\[1\\]\\?%3Ax:*:*"
+        );
+    }
+
+    #[test]
+    fn test_redis_key_roundtrip() {
+        let cache = test_cache().with_namespace("test");
+        let key = CacheKey::new("ACME:1", "2025-06-01", "2025-06-05")
+            .with_dimension(CacheKey::CURRENCY, "EUR");
+
+        let redis_key = cache.redis_key(&key);
+        assert_eq!(
+            redis_key,
+            "test:ACME%3A1:2025-06-01:2025-06-05;currency=EUR"
+        );
+        assert_eq!(cache.parse_redis_key(&redis_key), Some(key));
+        assert_eq!(
+            cache.parse_redis_key("other:hotel1:2025-06-01:2025-06-05"),
+            None
+        );
+        // Tag sets never collide with entry keys
+        assert_eq!(cache.parse_redis_key(&cache.tag_key("a:b:c")), None);
+    }
+
+    #[test]
+    fn test_values_are_marked() {
+        assert_eq!(
+            decode(Some(encode(Some(vec![1, 2])))),
+            CacheLookup::Hit(vec![1, 2])
+        );
+        // An empty payload isn't taken for a negative entry
+        assert_eq!(
+            decode(Some(encode(Some(Vec::new())))),
+            CacheLookup::Hit(Vec::new())
+        );
+        assert_eq!(decode(Some(encode(None))), CacheLookup::HitEmpty);
+        assert_eq!(decode(Some(Vec::new())), CacheLookup::Miss);
+        assert_eq!(decode(Some(vec![1, 2])), CacheLookup::Miss);
+        assert_eq!(decode(None), CacheLookup::Miss);
+    }
+
+    // Requires a running Redis server: REDIS_URL=redis://... cargo test --features redis-backend -- --ignored
+    #[test]
+    #[ignore]
+    fn test_store_get_invalidate_roundtrip() {
+        let cache = RedisCache::new(CacheConfig::default()).with_namespace("avail-test");
+        cache.invalidate(None, None, None);
+
+        assert!(cache.store("hotel1", "2025-06-01", "2025-06-05", vec![1, 2, 3], None));
+        assert!(cache.store("hotel1", "2025-06-10", "2025-06-15", vec![4, 5], None));
+        assert!(cache.store("hotel2", "2025-06-01", "2025-06-05", vec![6], None));
+
+        assert_eq!(
+            cache.get("hotel1", "2025-06-01", "2025-06-05"),
+            Some((vec![1, 2, 3], true))
+        );
+        assert_eq!(cache.scan_stats().unwrap().items_count, 3);
+        assert_eq!(cache.stats().items_count, 0);
+
+        assert_eq!(cache.invalidate(Some("hotel1"), None, None), 2);
+        assert!(cache.get("hotel1", "2025-06-10", "2025-06-15").is_none());
+        assert!(cache.get("hotel2", "2025-06-01", "2025-06-05").is_some());
+
+        let key = CacheKey::new("hotel4", "2025-06-01", "2025-06-05");
+        assert!(cache.store_tagged(key.clone(), vec![8], None, &["supplier:acme"]));
+        assert_ne!(cache.get_versioned(&key).1, CacheVersion::ABSENT);
+        assert_eq!(cache.invalidate_by_tag("supplier:acme"), 1);
+        assert_eq!(cache.invalidate_by_tag("supplier:acme"), 0);
+        // Its version counter went with it
+        let exists = |redis_key: String| {
+            cache
+                .with_connection(|con| con.exists::<_, bool>(redis_key))
+                .unwrap()
+        };
+        assert!(!exists(cache.version_key(&key)));
+        assert!(!exists(cache.version_key(&CacheKey::new(
+            "hotel1",
+            "2025-06-01",
+            "2025-06-05"
+        ))));
+
+        // Empty payloads and negative entries are told apart
+        let empty = CacheKey::new("hotel5", "2025-06-01", "2025-06-05");
+        assert!(cache.store_key(empty.clone(), Vec::new(), None));
+        assert_eq!(cache.lookup(&empty), CacheLookup::Hit(Vec::new()));
+        assert!(cache.store_empty(empty.clone(), None));
+        assert_eq!(cache.lookup(&empty), CacheLookup::HitEmpty);
+
+        // Expiry is handled by Redis
+        assert!(cache.store(
+            "hotel3",
+            "2025-06-01",
+            "2025-06-05",
+            vec![7],
+            Some(Duration::from_millis(50))
+        ));
+        std::thread::sleep(Duration::from_millis(100));
+        assert!(cache.get("hotel3", "2025-06-01", "2025-06-05").is_none());
+
+        let stats = cache.stats();
+        assert_eq!(stats.hit_count, 5);
+        assert_eq!(stats.miss_count, 2);
+        cache.invalidate(None, None, None);
+    }
+}
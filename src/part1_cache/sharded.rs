@@ -0,0 +1,1242 @@
+// Sharded high-concurrency cache
+// Keys are spread over `shards_count` independently locked maps, so writers only contend with
+// operations on the same shard. Reads take a shard's read lock only: the access bookkeeping
+// used by eviction lives in atomics on the entry, so concurrent hits never serialize. The size
+// limit still holds across shards: a store reserves its bytes atomically before it takes its
+// shard's lock.
+// Entries behave as in `ExampleCache` (tags, negative entries, versions, compression,
+// listeners); contents are not persisted across restarts, but can be moved between instances
+// with `CacheSnapshot`.
+
+use super::admission::AdmissionFilter;
+use super::events::CacheListeners;
+use super::inspect::{CacheInspect, EntryFilter, EntryInfo, EntryMetadata};
+use super::loader::{self, CacheLoader};
+use super::quota::QuotaTracker;
+use super::size::{hash_map_slot_size, EstimateSize};
+use super::snapshot::{CacheSnapshot, SnapshotEntry};
+use super::spill::SpillStore;
+use super::{
+    high_watermark_bytes, low_watermark_bytes, AvailabilityCache, CacheConfig, CacheKey,
+    CacheLookup, CacheStats, CacheStatsReport, CacheVersion, CapacityPolicy, Compression,
+    EntryEvent, EvictionCandidate, EvictionPolicy, EvictionStrategy, SearchContext,
+};
+use parking_lot::RwLock;
+use std::collections::hash_map::RandomState;
+use std::collections::{hash_map, HashMap};
+use std::hash::BuildHasher;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+type Shard = RwLock<HashMap<CacheKey, ShardEntry>>;
+
+pub struct ShardedCache {
+    shards: Box<[Shard]>,
+    hasher: RandomState,
+    config: RwLock<CacheConfig>,
+    eviction: RwLock<Arc<dyn EvictionStrategy>>,
+    listeners: CacheListeners,
+    loader: Option<Arc<dyn CacheLoader>>,
+    spill: Option<Arc<dyn SpillStore>>,
+    next_version: AtomicU64,
+    stats: CacheStats,
+    quotas: QuotaTracker,
+    admission: Option<AdmissionFilter>,
+}
+
+struct ShardEntry {
+    // Stored bytes, compressed with `compression`; shared with `get_ref` callers
+    data: Arc<[u8]>,
+    compression: Compression,
+    raw_size: usize,
+    // Bytes accounted against the cache size, see `EstimateSize`
+    size: usize,
+    tags: Vec<String>,
+    negative: bool,
+    version: u64,
+    created_at: Instant,
+    ttl: Duration,
+    max_idle: Option<Duration>,
+    // Updated under the shard read lock; the access time is kept as nanoseconds after created_at
+    access_count: AtomicUsize,
+    last_accessed_ns: AtomicU64,
+}
+
+// What a read found in a shard, copied out so the lock is released before decoding
+enum ShardRead {
+    Missing,
+    Expired,
+    Found {
+        data: Arc<[u8]>,
+        compression: Compression,
+        negative: bool,
+        version: u64,
+    },
+}
+
+impl ShardEntry {
+    fn is_expired(&self) -> bool {
+        self.created_at.elapsed() > self.ttl
+            || self.max_idle.is_some_and(|max_idle| self.idle() > max_idle)
+    }
+
+    fn last_accessed(&self) -> Instant {
+        self.created_at + Duration::from_nanos(self.last_accessed_ns.load(Ordering::Relaxed))
+    }
+
+    // Time since the last read (or the store)
+    fn idle(&self) -> Duration {
+        self.last_accessed().elapsed()
+    }
+
+    fn touch(&self) {
+        self.access_count.fetch_add(1, Ordering::Relaxed);
+        self.last_accessed_ns.store(
+            self.created_at.elapsed().as_nanos() as u64,
+            Ordering::Relaxed,
+        );
+    }
+
+    // When the entry expires unless it is read before
+    fn expires_at(&self) -> Instant {
+        let expires_at = self.created_at + self.ttl;
+        self.max_idle.map_or(expires_at, |max_idle| {
+            expires_at.min(self.last_accessed() + max_idle)
+        })
+    }
+}
+
+impl EstimateSize for ShardEntry {
+    fn heap_size(&self) -> usize {
+        self.data.heap_size() + self.tags.heap_size()
+    }
+}
+
+impl ShardedCache {
+    // Create a cache that fetches prefetched keys through `loader`
+    pub fn with_loader(config: CacheConfig, loader: impl CacheLoader + 'static) -> Self {
+        let mut cache = Self::new(config);
+        cache.loader = Some(Arc::new(loader));
+        cache
+    }
+
+    // Create a cache handing stores that don't fit to `spill`, used with
+    // `CapacityPolicy::SpillToSecondary`
+    pub fn with_spill_store(config: CacheConfig, spill: impl SpillStore + 'static) -> Self {
+        let mut cache = Self::new(config);
+        cache.spill = Some(Arc::new(spill));
+        cache
+    }
+
+    // Called for every entry evicted to make room, after the shard locks were released
+    pub fn on_evict(&self, listener: impl Fn(&EntryEvent) + Send + Sync + 'static) {
+        self.listeners.on_evict(listener);
+    }
+
+    // Called for every entry removed because its TTL ran out
+    pub fn on_expire(&self, listener: impl Fn(&EntryEvent) + Send + Sync + 'static) {
+        self.listeners.on_expire(listener);
+    }
+
+    // Called for every store the cache refused
+    pub fn on_reject(&self, listener: impl Fn(&EntryEvent) + Send + Sync + 'static) {
+        self.listeners.on_reject(listener);
+    }
+
+    pub fn shards_count(&self) -> usize {
+        self.shards.len()
+    }
+
+    fn shard_index(&self, key: &CacheKey) -> usize {
+        self.hasher.hash_one(key) as usize % self.shards.len()
+    }
+
+    fn shard(&self, key: &CacheKey) -> &Shard {
+        &self.shards[self.shard_index(key)]
+    }
+
+    fn eviction_strategy(&self) -> Arc<dyn EvictionStrategy> {
+        Arc::clone(&self.eviction.read())
+    }
+
+    fn new_entry(
+        &self,
+        key: &CacheKey,
+        data: Vec<u8>,
+        ttl: Option<Duration>,
+        tags: &[&str],
+        negative: bool,
+    ) -> ShardEntry {
+        let (ttl, max_idle, compression, threshold) = {
+            let config = self.config.read();
+            let default_ttl_seconds = if negative {
+                config.negative_ttl_seconds
+            } else {
+                config.default_ttl_seconds
+            };
+            (
+                ttl.unwrap_or_else(|| Duration::from_secs(default_ttl_seconds)),
+                config.max_idle_seconds.map(Duration::from_secs),
+                config.compression,
+                config.compression_threshold_bytes,
+            )
+        };
+
+        let raw_size = data.len();
+        let (data, compression) = compression.compress_above(data, threshold);
+        let mut entry = ShardEntry {
+            data: data.into(),
+            compression,
+            raw_size,
+            size: 0,
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            negative,
+            version: 0,
+            created_at: Instant::now(),
+            ttl,
+            max_idle,
+            access_count: AtomicUsize::new(0),
+            last_accessed_ns: AtomicU64::new(0),
+        };
+        entry.size =
+            hash_map_slot_size::<CacheKey, ShardEntry>() + key.heap_size() + entry.heap_size();
+        entry
+    }
+
+    fn event(&self, key: CacheKey, entry: &ShardEntry) -> EntryEvent {
+        EntryEvent {
+            size: entry.size,
+            access_count: entry.access_count.load(Ordering::Relaxed),
+            last_accessed: entry.last_accessed(),
+            key,
+        }
+    }
+
+    fn eviction_candidate<'a>(
+        &self,
+        key: &'a CacheKey,
+        entry: &ShardEntry,
+    ) -> EvictionCandidate<'a> {
+        EvictionCandidate {
+            key,
+            size: entry.size,
+            created_at: entry.created_at,
+            last_accessed: entry.last_accessed(),
+            access_count: entry.access_count.load(Ordering::Relaxed),
+            expires_at: entry.expires_at(),
+        }
+    }
+
+    // (hard limit, high watermark, low watermark) in bytes
+    fn size_limits(&self) -> (usize, usize, usize) {
+        let config = self.config.read();
+        (
+            config.max_size_mb * 1024 * 1024,
+            high_watermark_bytes(&config),
+            low_watermark_bytes(&config),
+        )
+    }
+
+    // The entry's size is already counted in `size_bytes` by `reserve`
+    fn account_added(&self, entry: &ShardEntry) {
+        self.stats
+            .raw_bytes
+            .fetch_add(entry.raw_size, Ordering::SeqCst);
+        self.stats
+            .compressed_bytes
+            .fetch_add(entry.data.len(), Ordering::SeqCst);
+    }
+
+    fn account_removed(&self, entry: &ShardEntry) {
+        self.stats
+            .size_bytes
+            .fetch_sub(entry.size, Ordering::SeqCst);
+        self.stats
+            .raw_bytes
+            .fetch_sub(entry.raw_size, Ordering::SeqCst);
+        self.stats
+            .compressed_bytes
+            .fetch_sub(entry.data.len(), Ordering::SeqCst);
+    }
+
+    // Count `size` bytes as used if they fit within `max_size_bytes`, before the entry is
+    // inserted: stores in different shards don't share a lock, so checking the size and adding
+    // it later would let concurrent stores each see room and together exceed the limit
+    fn reserve(&self, size: usize, max_size_bytes: usize) -> bool {
+        self.stats
+            .size_bytes
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |used| {
+                Some(used + size).filter(|total| *total <= max_size_bytes)
+            })
+            .is_ok()
+    }
+
+    // Give back a reservation whose entry wasn't inserted
+    fn release(&self, size: usize) {
+        self.stats.size_bytes.fetch_sub(size, Ordering::SeqCst);
+    }
+
+    // The entry's size must have been reserved
+    fn insert_locked(
+        &self,
+        shard: &mut HashMap<CacheKey, ShardEntry>,
+        key: CacheKey,
+        mut entry: ShardEntry,
+    ) {
+        entry.version = self.next_version.fetch_add(1, Ordering::SeqCst);
+        self.eviction_strategy().record_insert(&key);
+        self.account_added(&entry);
+        self.quotas.account_added(&key, &entry.tags, entry.size);
+        match shard.entry(key) {
+            hash_map::Entry::Occupied(mut occupied) => {
+                let old = occupied.insert(entry);
+                self.account_removed(&old);
+                self.quotas
+                    .account_removed(occupied.key(), &old.tags, old.size);
+            }
+            hash_map::Entry::Vacant(vacant) => {
+                vacant.insert(entry);
+                self.stats.items_count.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+    }
+
+    // Whether `entry` fits the quotas it falls under, counting the entry it replaces as freed
+    fn admit_locked(
+        &self,
+        shard: &HashMap<CacheKey, ShardEntry>,
+        key: &CacheKey,
+        entry: &ShardEntry,
+    ) -> bool {
+        let replaced = shard.get(key).map(|old| (old.size, old.tags.as_slice()));
+        self.quotas.admit(key, &entry.tags, entry.size, replaced)
+    }
+
+    // Whether the admission filter lets a store of `key` in; updates of cached keys always pass
+    fn admitted_locked(&self, shard: &HashMap<CacheKey, ShardEntry>, key: &CacheKey) -> bool {
+        self.admission.as_ref().is_none_or(|admission| {
+            shard.get(key).is_some_and(|entry| !entry.is_expired()) || admission.admit(key)
+        })
+    }
+
+    fn remove_locked(
+        &self,
+        shard: &mut HashMap<CacheKey, ShardEntry>,
+        key: &CacheKey,
+        expired: bool,
+    ) -> Option<ShardEntry> {
+        let removed = shard.remove(key)?;
+        self.account_removed(&removed);
+        self.quotas
+            .account_removed(key, &removed.tags, removed.size);
+        self.stats.eviction_count.fetch_add(1, Ordering::SeqCst);
+        self.stats.items_count.fetch_sub(1, Ordering::SeqCst);
+        if expired {
+            self.stats.expired_count.fetch_add(1, Ordering::SeqCst);
+        }
+        Some(removed)
+    }
+
+    // Remove `key` if it is (still) expired and tell the expiry listeners
+    fn remove_expired(&self, key: &CacheKey) {
+        let mut shard = self.shard(key).write();
+        if !shard.get(key).is_some_and(ShardEntry::is_expired) {
+            return;
+        }
+        let removed = self.remove_locked(&mut shard, key, true);
+        drop(shard);
+        if let Some(removed) = removed {
+            self.listeners
+                .notify_expired(&[self.event(key.clone(), &removed)]);
+        }
+    }
+
+    fn reject(&self, key: CacheKey, entry: &ShardEntry) {
+        self.stats.rejected_count.fetch_add(1, Ordering::SeqCst);
+        self.listeners.notify_rejected(&[self.event(key, entry)]);
+    }
+
+    // A store there is no room for: spilled if the capacity policy says so, rejected otherwise
+    fn overflow(&self, key: CacheKey, entry: ShardEntry) {
+        let capacity_policy = self.config.read().capacity_policy;
+        if let (CapacityPolicy::SpillToSecondary, Some(spill)) = (capacity_policy, &self.spill) {
+            match entry.compression.decompress(&entry.data) {
+                Ok(data) => {
+                    let spilled = spill.spill(SnapshotEntry {
+                        key: key.clone(),
+                        data,
+                        remaining_ttl: entry.ttl.saturating_sub(entry.created_at.elapsed()),
+                        tags: entry.tags.clone(),
+                        negative: entry.negative,
+                    });
+                    if spilled {
+                        self.stats.spilled_count.fetch_add(1, Ordering::SeqCst);
+                        return;
+                    }
+                }
+                Err(e) => tracing::warn!("Not spilling undecodable entry {}: {}", key, e),
+            }
+        }
+        self.reject(key, &entry);
+    }
+
+    // Store a prepared entry if the admission filter lets it in
+    fn store_entry(
+        &self,
+        key: CacheKey,
+        entry: ShardEntry,
+        expected: Option<CacheVersion>,
+    ) -> bool {
+        if self.admission.is_some() && !self.admitted_locked(&self.shard(&key).read(), &key) {
+            self.reject(key, &entry);
+            return false;
+        }
+        self.insert_entry(key, entry, expected)
+    }
+
+    fn insert_entry(
+        &self,
+        key: CacheKey,
+        entry: ShardEntry,
+        expected: Option<CacheVersion>,
+    ) -> bool {
+        let (max_size_bytes, high_watermark, low_watermark) = self.size_limits();
+        let capacity_policy = self.config.read().capacity_policy;
+        let item_size = entry.size;
+
+        // Evicting everything still wouldn't make room
+        if item_size > max_size_bytes {
+            self.overflow(key, entry);
+            return false;
+        }
+        if capacity_policy == CapacityPolicy::EvictToFit
+            && self.stats.size_bytes.load(Ordering::SeqCst) + item_size > high_watermark
+        {
+            self.evict_down_to(low_watermark.saturating_sub(item_size));
+        }
+        if !self.reserve(item_size, max_size_bytes) {
+            self.overflow(key, entry);
+            return false;
+        }
+
+        let mut shard = self.shard(&key).write();
+        if let Some(expected) = expected {
+            let current = shard
+                .get(&key)
+                .filter(|entry| !entry.is_expired())
+                .map_or(CacheVersion::ABSENT, |entry| CacheVersion(entry.version));
+            if current != expected {
+                drop(shard);
+                self.release(item_size);
+                self.stats
+                    .version_conflict_count
+                    .fetch_add(1, Ordering::SeqCst);
+                return false;
+            }
+        }
+        if !self.admit_locked(&shard, &key, &entry) {
+            drop(shard);
+            self.release(item_size);
+            self.reject(key, &entry);
+            return false;
+        }
+        self.insert_locked(&mut shard, key, entry);
+        true
+    }
+
+    // Evict entries in eviction order until the cache holds at most `target_bytes`
+    // Victims are chosen from a consistent view of all shards (read locks), then removed
+    // shard by shard
+    fn evict_down_to(&self, target_bytes: usize) -> usize {
+        let victims: Vec<CacheKey> = {
+            let shards: Vec<_> = self.shards.iter().map(|shard| shard.read()).collect();
+            let current_size_bytes = self.stats.size_bytes.load(Ordering::SeqCst);
+            if current_size_bytes <= target_bytes {
+                return 0;
+            }
+
+            let mut candidates: Vec<EvictionCandidate> = shards
+                .iter()
+                .flat_map(|shard| shard.iter())
+                .map(|(key, entry)| self.eviction_candidate(key, entry))
+                .collect();
+            self.eviction_strategy().order(&mut candidates);
+
+            let bytes_to_free = current_size_bytes - target_bytes;
+            let mut freed = 0;
+            candidates
+                .iter()
+                .take_while(|candidate| {
+                    let needed = freed < bytes_to_free;
+                    freed += candidate.size;
+                    needed
+                })
+                .map(|candidate| candidate.key.clone())
+                .collect()
+        };
+
+        let evicted: Vec<EntryEvent> = victims
+            .into_iter()
+            .filter_map(|key| {
+                let removed = self.remove_locked(&mut self.shard(&key).write(), &key, false)?;
+                Some(self.event(key, &removed))
+            })
+            .collect();
+        self.listeners.notify_evicted(&evicted);
+        evicted.len()
+    }
+
+    // Remove every entry matching `predicate`, one shard write lock at a time
+    fn remove_where(&self, predicate: impl Fn(&CacheKey, &ShardEntry) -> bool) -> usize {
+        let mut count = 0;
+        for shard in self.shards.iter() {
+            let mut shard = shard.write();
+            let keys: Vec<CacheKey> = shard
+                .iter()
+                .filter(|(key, entry)| predicate(key, entry))
+                .map(|(key, _)| key.clone())
+                .collect();
+            for key in &keys {
+                self.remove_locked(&mut shard, key, false);
+            }
+            count += keys.len();
+        }
+        count
+    }
+
+    // Read `key` under its shard read lock
+    fn read_entry(&self, shard: &HashMap<CacheKey, ShardEntry>, key: &CacheKey) -> ShardRead {
+        let Some(entry) = shard.get(key) else {
+            return ShardRead::Missing;
+        };
+        if entry.is_expired() {
+            return ShardRead::Expired;
+        }
+        entry.touch();
+        ShardRead::Found {
+            data: Arc::clone(&entry.data),
+            compression: entry.compression,
+            negative: entry.negative,
+            version: entry.version,
+        }
+    }
+
+    // Turn what `read_entry` copied out of the shard into a lookup result, updating stats
+    // `decode` turns the stored bytes into the value handed out
+    fn finish_lookup<V>(
+        &self,
+        key: &CacheKey,
+        found: ShardRead,
+        decode: impl FnOnce(&Compression, &Arc<[u8]>) -> std::io::Result<V>,
+    ) -> (CacheLookup<V>, CacheVersion) {
+        let (data, compression, negative, version) = match found {
+            ShardRead::Found {
+                data,
+                compression,
+                negative,
+                version,
+            } => (data, compression, negative, version),
+            // Expired entries count as expired, not as misses
+            ShardRead::Expired => {
+                self.remove_expired(key);
+                return (CacheLookup::Miss, CacheVersion::ABSENT);
+            }
+            ShardRead::Missing => {
+                self.stats.miss_count.fetch_add(1, Ordering::SeqCst);
+                return (CacheLookup::Miss, CacheVersion::ABSENT);
+            }
+        };
+        self.eviction_strategy().record_access(key);
+
+        if negative {
+            self.stats.hit_count.fetch_add(1, Ordering::SeqCst);
+            self.stats.negative_hit_count.fetch_add(1, Ordering::SeqCst);
+            return (CacheLookup::HitEmpty, CacheVersion(version));
+        }
+        match decode(&compression, &data) {
+            Ok(data) => {
+                self.stats.hit_count.fetch_add(1, Ordering::SeqCst);
+                (CacheLookup::Hit(data), CacheVersion(version))
+            }
+            Err(e) => {
+                tracing::warn!("Dropping undecodable cache entry {}: {}", key, e);
+                self.remove_locked(&mut self.shard(key).write(), key, false);
+                self.stats.miss_count.fetch_add(1, Ordering::SeqCst);
+                (CacheLookup::Miss, CacheVersion::ABSENT)
+            }
+        }
+    }
+
+    // Indices of `keys` grouped by shard, so every shard is locked once per batch
+    fn group_by_shard<'a>(&self, keys: impl Iterator<Item = &'a CacheKey>) -> Vec<Vec<usize>> {
+        let mut groups = vec![Vec::new(); self.shards.len()];
+        for (i, key) in keys.enumerate() {
+            groups[self.shard_index(key)].push(i);
+        }
+        groups
+    }
+}
+
+impl AvailabilityCache for ShardedCache {
+    fn new(config: CacheConfig) -> Self {
+        let shards_count = config.shards_count.max(1);
+        Self {
+            shards: (0..shards_count)
+                .map(|_| RwLock::new(HashMap::new()))
+                .collect(),
+            hasher: RandomState::new(),
+            quotas: QuotaTracker::new(config.quotas.clone()),
+            admission: config.admission.as_ref().map(AdmissionFilter::new),
+            eviction: RwLock::new(Arc::from(config.eviction_policy.strategy())),
+            config: RwLock::new(config),
+            listeners: CacheListeners::default(),
+            loader: None,
+            spill: None,
+            next_version: AtomicU64::new(1),
+            stats: CacheStats::default(),
+        }
+    }
+
+    fn store_tagged(
+        &self,
+        key: CacheKey,
+        data: Vec<u8>,
+        ttl: Option<Duration>,
+        tags: &[&str],
+    ) -> bool {
+        let entry = self.new_entry(&key, data, ttl, tags, false);
+        self.store_entry(key, entry, None)
+    }
+
+    fn store_empty(&self, key: CacheKey, ttl: Option<Duration>) -> bool {
+        let entry = self.new_entry(&key, Vec::new(), ttl, &[], true);
+        self.store_entry(key, entry, None)
+    }
+
+    fn lookup(&self, key: &CacheKey) -> CacheLookup {
+        self.get_versioned(key).0
+    }
+
+    fn get_versioned(&self, key: &CacheKey) -> (CacheLookup, CacheVersion) {
+        let now = Instant::now();
+        self.stats.total_lookups.fetch_add(1, Ordering::SeqCst);
+
+        let found = self.read_entry(&self.shard(key).read(), key);
+        let found =
+            self.finish_lookup(key, found, |compression, data| compression.decompress(data));
+        self.stats.record_lookup_time(now);
+        found
+    }
+
+    // Uncompressed values are handed out without copying them
+    fn get_ref(&self, key: &CacheKey) -> Option<Arc<[u8]>> {
+        let now = Instant::now();
+        self.stats.total_lookups.fetch_add(1, Ordering::SeqCst);
+
+        let found = self.read_entry(&self.shard(key).read(), key);
+        let (found, _) = self.finish_lookup(key, found, Compression::decompress_shared);
+        self.stats.record_lookup_time(now);
+        found.into_data()
+    }
+
+    fn store_if_version(
+        &self,
+        key: CacheKey,
+        data: Vec<u8>,
+        ttl: Option<Duration>,
+        version: CacheVersion,
+    ) -> bool {
+        let entry = self.new_entry(&key, data, ttl, &[], false);
+        self.store_entry(key, entry, Some(version))
+    }
+
+    // Each shard is read-locked at most once per batch
+    fn get_many_keys(&self, keys: &[CacheKey]) -> Vec<CacheLookup> {
+        let now = Instant::now();
+        self.stats
+            .total_lookups
+            .fetch_add(keys.len(), Ordering::SeqCst);
+
+        let mut found: Vec<ShardRead> = keys.iter().map(|_| ShardRead::Missing).collect();
+        for (shard, indices) in self.shards.iter().zip(self.group_by_shard(keys.iter())) {
+            if indices.is_empty() {
+                continue;
+            }
+            let shard = shard.read();
+            for i in indices {
+                found[i] = self.read_entry(&shard, &keys[i]);
+            }
+        }
+
+        let results: Vec<CacheLookup> = keys
+            .iter()
+            .zip(found)
+            .map(|(key, found)| {
+                self.finish_lookup(key, found, |compression, data| compression.decompress(data))
+                    .0
+            })
+            .collect();
+
+        // Every key is charged its share of the batch time
+        let per_key_ns = now.elapsed().as_nanos() as u64 / keys.len().max(1) as u64;
+        for _ in keys {
+            self.stats.lookup_latency.record(per_key_ns);
+        }
+        self.stats.record_batch(&results);
+        results
+    }
+
+    // One eviction pass for the whole batch, then each shard is write-locked once
+    fn store_many(&self, entries: Vec<(CacheKey, Vec<u8>)>, ttl: Option<Duration>) -> Vec<bool> {
+        let (max_size_bytes, high_watermark, low_watermark) = self.size_limits();
+        let capacity_policy = self.config.read().capacity_policy;
+        let mut entries: Vec<Option<(CacheKey, ShardEntry)>> = entries
+            .into_iter()
+            .map(|(key, data)| {
+                let entry = self.new_entry(&key, data, ttl, &[], false);
+                Some((key, entry))
+            })
+            .collect();
+
+        let batch_size: usize = entries
+            .iter()
+            .flatten()
+            .map(|(_, entry)| entry.size)
+            .filter(|size| *size <= max_size_bytes)
+            .sum();
+        if capacity_policy == CapacityPolicy::EvictToFit
+            && self.stats.size_bytes.load(Ordering::SeqCst) + batch_size > high_watermark
+        {
+            self.evict_down_to(low_watermark.saturating_sub(batch_size));
+        }
+
+        let groups = self.group_by_shard(entries.iter().flatten().map(|(key, _)| key));
+        let mut stored = vec![false; entries.len()];
+        let mut rejected = Vec::new();
+        let mut overflowed = Vec::new();
+        for (shard, indices) in self.shards.iter().zip(groups) {
+            if indices.is_empty() {
+                continue;
+            }
+            let mut shard = shard.write();
+            for i in indices {
+                let (key, entry) = entries[i].take().unwrap();
+                if !self.reserve(entry.size, max_size_bytes) {
+                    overflowed.push((key, entry));
+                    continue;
+                }
+                if !self.admitted_locked(&shard, &key) || !self.admit_locked(&shard, &key, &entry) {
+                    self.release(entry.size);
+                    rejected.push(self.event(key, &entry));
+                    continue;
+                }
+                self.insert_locked(&mut shard, key, entry);
+                stored[i] = true;
+            }
+        }
+
+        self.stats
+            .rejected_count
+            .fetch_add(rejected.len(), Ordering::SeqCst);
+        self.listeners.notify_rejected(&rejected);
+        for (key, entry) in overflowed {
+            self.overflow(key, entry);
+        }
+        stored
+    }
+
+    fn stats(&self) -> CacheStatsReport {
+        CacheStatsReport {
+            quota_rejected_count: self.quotas.rejected_count(),
+            quotas: self.quotas.report(),
+            admission_rejected_count: self
+                .admission
+                .as_ref()
+                .map_or(0, AdmissionFilter::rejected_count),
+            ..self.stats.report()
+        }
+    }
+
+    fn set_eviction_policy(&self, policy: EvictionPolicy) {
+        self.config.write().eviction_policy = policy;
+        *self.eviction.write() = Arc::from(policy.strategy());
+    }
+
+    fn prefetch_keys(&self, keys: Vec<CacheKey>, ttl: Option<Duration>) -> usize {
+        let parallelism = self.config.read().prefetch_parallelism;
+        loader::prefetch_into(self, self.loader.as_deref(), keys, ttl, parallelism)
+    }
+
+    fn invalidate_in_context(
+        &self,
+        hotel_id: Option<&str>,
+        check_in: Option<&str>,
+        check_out: Option<&str>,
+        context: Option<&SearchContext>,
+    ) -> usize {
+        self.remove_where(|key, _| {
+            key.matches(hotel_id, check_in, check_out)
+                && context.is_none_or(|context| key.matches_context(context))
+        })
+    }
+
+    fn invalidate_by_tag(&self, tag: &str) -> usize {
+        self.remove_where(|_, entry| entry.tags.iter().any(|t| t == tag))
+    }
+
+    fn tagged_keys(&self, tag: &str) -> Vec<CacheKey> {
+        let mut keys = Vec::new();
+        for shard in self.shards.iter() {
+            keys.extend(
+                shard
+                    .read()
+                    .iter()
+                    .filter(|(_, entry)| entry.tags.iter().any(|t| t == tag))
+                    .map(|(key, _)| key.clone()),
+            );
+        }
+        keys
+    }
+
+    fn resize(&self, new_max_size_mb: usize) -> bool {
+        let (new_max_size_bytes, low_watermark) = {
+            let mut config = self.config.write();
+            config.max_size_mb = new_max_size_mb;
+            (new_max_size_mb * 1024 * 1024, low_watermark_bytes(&config))
+        };
+
+        if self.stats.size_bytes.load(Ordering::SeqCst) > new_max_size_bytes {
+            self.evict_down_to(low_watermark);
+        }
+        true
+    }
+}
+
+impl CacheInspect for ShardedCache {
+    // Shards are read one at a time
+    fn iter_entries(&self, filter: &EntryFilter) -> impl Iterator<Item = EntryInfo> {
+        let mut entries = Vec::new();
+        for shard in self.shards.iter() {
+            entries.extend(
+                shard
+                    .read()
+                    .iter()
+                    .filter(|(key, entry)| !entry.is_expired() && filter.matches(key, &entry.tags))
+                    .map(|(key, entry)| EntryInfo {
+                        key: key.clone(),
+                        size_bytes: entry.size,
+                        age: entry.created_at.elapsed(),
+                        remaining_ttl: entry.ttl.saturating_sub(entry.created_at.elapsed()),
+                        access_count: entry.access_count.load(Ordering::Relaxed),
+                        idle: entry.idle(),
+                        tags: entry.tags.clone(),
+                        negative: entry.negative,
+                    }),
+            );
+        }
+        entries.into_iter()
+    }
+
+    fn get_with_metadata(&self, key: &CacheKey) -> Option<(Vec<u8>, EntryMetadata)> {
+        let now = Instant::now();
+        self.stats.total_lookups.fetch_add(1, Ordering::SeqCst);
+
+        let (found, metadata) = {
+            let shard = self.shard(key).read();
+            let found = self.read_entry(&shard, key);
+            let metadata = match (&found, shard.get(key)) {
+                (ShardRead::Found { .. }, Some(entry)) => Some(EntryMetadata::new(
+                    key,
+                    &entry.tags,
+                    entry.created_at,
+                    entry.ttl,
+                    entry.size,
+                )),
+                _ => None,
+            };
+            (found, metadata)
+        };
+        let (found, _) =
+            self.finish_lookup(key, found, |compression, data| compression.decompress(data));
+        self.stats.record_lookup_time(now);
+        match (found, metadata) {
+            (CacheLookup::Hit(data), Some(metadata)) => Some((data, metadata)),
+            _ => None,
+        }
+    }
+}
+
+impl CacheSnapshot for ShardedCache {
+    // Shards are copied one at a time, so the snapshot is consistent per shard only
+    fn export_snapshot(&self) -> Vec<SnapshotEntry> {
+        let mut snapshot = Vec::new();
+        for shard in self.shards.iter() {
+            let copied: Vec<_> = shard
+                .read()
+                .iter()
+                .filter(|(_, entry)| !entry.is_expired())
+                .map(|(key, entry)| {
+                    (
+                        SnapshotEntry {
+                            key: key.clone(),
+                            data: Vec::new(),
+                            remaining_ttl: entry.ttl.saturating_sub(entry.created_at.elapsed()),
+                            tags: entry.tags.clone(),
+                            negative: entry.negative,
+                        },
+                        Arc::clone(&entry.data),
+                        entry.compression,
+                    )
+                })
+                .collect();
+
+            for (mut entry, data, compression) in copied {
+                match compression.decompress(&data) {
+                    Ok(data) => {
+                        entry.data = data;
+                        snapshot.push(entry);
+                    }
+                    Err(e) => tracing::warn!("Skipping {} in snapshot: {}", entry.key, e),
+                }
+            }
+        }
+        snapshot
+    }
+
+    fn import_snapshot(&self, entries: Vec<SnapshotEntry>) -> usize {
+        let mut imported = 0;
+        for entry in entries
+            .into_iter()
+            .filter(|entry| !entry.remaining_ttl.is_zero())
+        {
+            let tags: Vec<&str> = entry.tags.iter().map(String::as_str).collect();
+            let shard_entry = self.new_entry(
+                &entry.key,
+                entry.data,
+                Some(entry.remaining_ttl),
+                &tags,
+                entry.negative,
+            );
+            if self.insert_entry(entry.key, shard_entry, None) {
+                imported += 1;
+            }
+        }
+        imported
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::part1_cache::{AdmissionConfig, Quota};
+    use std::thread;
+
+    fn key(i: usize) -> CacheKey {
+        CacheKey::new(format!("hotel{}", i), "2025-06-01", "2025-06-05")
+    }
+
+    #[test]
+    fn test_store_get_across_shards() {
+        let cache = ShardedCache::new(CacheConfig {
+            shards_count: 4,
+            ..Default::default()
+        });
+        assert_eq!(cache.shards_count(), 4);
+
+        for i in 0..100 {
+            assert!(cache.store_key(key(i), vec![i as u8], None));
+        }
+        for i in 0..100 {
+            assert_eq!(cache.lookup(&key(i)), CacheLookup::Hit(vec![i as u8]));
+        }
+        assert_eq!(cache.lookup(&key(100)), CacheLookup::Miss);
+        assert_eq!(
+            cache.get_many_keys(&[key(5), key(100), key(50)]),
+            vec![
+                CacheLookup::Hit(vec![5]),
+                CacheLookup::Miss,
+                CacheLookup::Hit(vec![50])
+            ]
+        );
+
+        let stats = cache.stats();
+        assert_eq!(stats.items_count, 100);
+        assert_eq!((stats.hit_count, stats.miss_count), (102, 2));
+
+        assert_eq!(cache.invalidate(Some("hotel7"), None, None), 1);
+        assert_eq!(cache.stats().items_count, 99);
+    }
+
+    #[test]
+    fn test_concurrent_readers_and_writers() {
+        let cache = Arc::new(ShardedCache::new(CacheConfig::default()));
+        for i in 0..50 {
+            cache.store_key(key(i), vec![0; 64], None);
+        }
+
+        let handles: Vec<_> = (0..8)
+            .map(|t| {
+                let cache = Arc::clone(&cache);
+                thread::spawn(move || {
+                    for i in 0..1000 {
+                        if t == 0 && i % 10 == 0 {
+                            cache.store_key(key(i / 10 % 80), vec![1; 64], None);
+                        } else {
+                            cache.lookup(&key(i % 50));
+                        }
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let stats = cache.stats();
+        assert_eq!(stats.items_count, 80);
+        assert_eq!(stats.hit_count + stats.miss_count, stats.total_lookups);
+        assert_eq!(stats.miss_count, 0);
+        let sizes: usize = cache
+            .shards
+            .iter()
+            .flat_map(|shard| {
+                shard
+                    .read()
+                    .values()
+                    .map(|entry| entry.size)
+                    .collect::<Vec<_>>()
+            })
+            .sum();
+        assert_eq!(stats.size_bytes, sizes);
+    }
+
+    #[test]
+    fn test_concurrent_stores_stay_within_max_size() {
+        for capacity_policy in [CapacityPolicy::EvictToFit, CapacityPolicy::RejectNew] {
+            let cache = Arc::new(ShardedCache::new(CacheConfig {
+                max_size_mb: 1,
+                capacity_policy,
+                ..Default::default()
+            }));
+            let barrier = Arc::new(std::sync::Barrier::new(8));
+            let handles: Vec<_> = (0..8)
+                .map(|t| {
+                    let cache = Arc::clone(&cache);
+                    let barrier = Arc::clone(&barrier);
+                    thread::spawn(move || {
+                        barrier.wait();
+                        for i in 0..500 {
+                            cache.store_key(key(t * 1000 + i), vec![0; 16 * 1024], None);
+                            assert!(cache.stats().size_bytes <= 1024 * 1024);
+                        }
+                    })
+                })
+                .collect();
+            for handle in handles {
+                handle.join().unwrap();
+            }
+            assert!(cache.stats().size_bytes <= 1024 * 1024);
+        }
+    }
+
+    #[test]
+    fn test_eviction_across_shards() {
+        let cache = ShardedCache::new(CacheConfig {
+            max_size_mb: 1,
+            low_watermark_percent: 50,
+            ..Default::default()
+        });
+        let evicted = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&evicted);
+        cache.on_evict(move |_| {
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+
+        for i in 0..30 {
+            cache.store_key(key(i), vec![0; 50 * 1024], None);
+            // Keep the first entries hot, LRU evicts the others first
+            for hot in 0..3.min(i + 1) {
+                cache.lookup(&key(hot));
+            }
+        }
+
+        let stats = cache.stats();
+        assert!(stats.size_bytes <= 1024 * 1024);
+        assert!(stats.eviction_count > 0);
+        assert_eq!(evicted.load(Ordering::SeqCst), stats.eviction_count);
+        for hot in 0..3 {
+            assert!(cache.lookup(&key(hot)).is_hit());
+        }
+    }
+
+    #[test]
+    fn test_expired_entries_are_removed_on_read() {
+        let cache = ShardedCache::new(CacheConfig::default());
+        cache.store_key(key(1), vec![1], Some(Duration::from_millis(10)));
+        cache.store_empty(key(2), None);
+        thread::sleep(Duration::from_millis(20));
+
+        assert_eq!(cache.lookup(&key(1)), CacheLookup::Miss);
+        assert_eq!(cache.lookup(&key(2)), CacheLookup::HitEmpty);
+        let stats = cache.stats();
+        assert_eq!((stats.expired_count, stats.items_count), (1, 1));
+    }
+
+    #[test]
+    fn test_quota_across_shards() {
+        let cache = ShardedCache::new(CacheConfig {
+            quotas: vec![Quota::tag("supplier:acme").max_items(10)],
+            ..Default::default()
+        });
+        let stored = (0..20)
+            .filter(|&i| cache.store_tagged(key(i), vec![1], None, &["supplier:acme"]))
+            .count();
+        assert_eq!(stored, 10);
+        assert!(cache.store_key(key(20), vec![1], None));
+
+        let stats = cache.stats();
+        assert_eq!(stats.quota_rejected_count, 10);
+        assert_eq!(stats.quotas[0].items_count, 10);
+        assert_eq!(cache.invalidate_by_tag("supplier:acme"), 10);
+        assert_eq!(cache.stats().quotas[0].size_bytes, 0);
+    }
+
+    #[test]
+    fn test_get_ref_without_copy() {
+        let cache = ShardedCache::new(CacheConfig::default());
+        cache.store_key(key(1), vec![7; 1024], None);
+
+        let value = cache.get_ref(&key(1)).unwrap();
+        assert!(Arc::ptr_eq(&value, &cache.get_ref(&key(1)).unwrap()));
+        assert_eq!(&value[..], &[7; 1024][..]);
+        assert_eq!(cache.get_ref(&key(2)), None);
+        assert_eq!(cache.stats().total_lookups, 3);
+    }
+
+    #[test]
+    fn test_capacity_policy_spills_instead_of_evicting() {
+        let spilled = Arc::new(parking_lot::Mutex::new(Vec::new()));
+        let cache = ShardedCache::with_spill_store(
+            CacheConfig {
+                max_size_mb: 1,
+                capacity_policy: CapacityPolicy::SpillToSecondary,
+                ..Default::default()
+            },
+            {
+                let spilled = Arc::clone(&spilled);
+                move |entry: SnapshotEntry| {
+                    spilled.lock().push(entry.key);
+                    true
+                }
+            },
+        );
+        let stored = (0..8)
+            .filter(|&i| cache.store_key(key(i), vec![1; 200 * 1024], None))
+            .count();
+        assert_eq!(
+            cache.store_many(vec![(key(8), vec![1; 200 * 1024])], None),
+            vec![false]
+        );
+
+        let stats = cache.stats();
+        assert_eq!(stats.eviction_count, 0);
+        assert_eq!(stats.items_count, stored);
+        assert_eq!(stats.spilled_count, 9 - stored);
+        assert_eq!(spilled.lock().last(), Some(&key(8)));
+    }
+
+    #[test]
+    fn test_snapshot_moves_entries_between_caches() {
+        let source = ShardedCache::new(CacheConfig::default());
+        for i in 0..20 {
+            source.store_tagged(key(i), vec![i as u8], None, &["supplier:acme"]);
+        }
+        source.store_empty(key(20), None);
+
+        let snapshot = source.export_snapshot();
+        assert_eq!(snapshot.len(), 21);
+
+        let target = ShardedCache::new(CacheConfig {
+            shards_count: 2,
+            ..Default::default()
+        });
+        assert_eq!(target.import_snapshot(snapshot), 21);
+        assert_eq!(target.lookup(&key(7)), CacheLookup::Hit(vec![7]));
+        assert_eq!(target.lookup(&key(20)), CacheLookup::HitEmpty);
+        assert_eq!(target.invalidate_by_tag("supplier:acme"), 20);
+    }
+
+    #[test]
+    fn test_idle_entries_expire() {
+        let cache = ShardedCache::new(CacheConfig {
+            max_idle_seconds: Some(1),
+            ..Default::default()
+        });
+        cache.store_key(key(1), vec![1], None);
+        cache.store_key(key(2), vec![2], None);
+
+        for _ in 0..3 {
+            thread::sleep(Duration::from_millis(500));
+            assert!(cache.lookup(&key(1)).is_hit());
+        }
+        assert_eq!(cache.lookup(&key(2)), CacheLookup::Miss);
+        assert_eq!(cache.iter_entries(&EntryFilter::new()).count(), 1);
+    }
+
+    #[test]
+    fn test_admission_filter() {
+        let cache = ShardedCache::new(CacheConfig {
+            admission: Some(AdmissionConfig::default()),
+            ..Default::default()
+        });
+        let first: Vec<bool> = (0..20)
+            .map(|i| cache.store_key(key(i), vec![1], None))
+            .collect();
+        assert!(first.iter().all(|stored| !stored));
+        assert!((0..10).all(|i| cache.store_key(key(i), vec![1], None)));
+        assert!(cache.store_key(key(3), vec![2], None));
+
+        let stats = cache.stats();
+        assert_eq!(stats.items_count, 10);
+        assert_eq!(stats.admission_rejected_count, 20);
+    }
+
+    #[test]
+    fn test_get_with_metadata() {
+        let cache = ShardedCache::new(CacheConfig::default());
+        cache.store_tagged(key(1), vec![1], None, &["supplier:acme"]);
+
+        let (data, metadata) = cache.get_with_metadata(&key(1)).unwrap();
+        assert_eq!(data, vec![1]);
+        assert_eq!(metadata.supplier.as_deref(), Some("acme"));
+        assert_eq!(metadata.currency, None);
+        assert!(cache.get_with_metadata(&key(2)).is_none());
+
+        let stats = cache.stats();
+        assert_eq!((stats.hit_count, stats.miss_count), (1, 1));
+    }
+
+    #[test]
+    fn test_iter_entries_across_shards() {
+        let cache = ShardedCache::new(CacheConfig::default());
+        for i in 0..20 {
+            let tags: &[&str] = if i % 2 == 0 { &["even"] } else { &[] };
+            cache.store_tagged(key(i), vec![i as u8], None, tags);
+        }
+        cache.get_key(&key(4));
+
+        let even: Vec<EntryInfo> = cache
+            .iter_entries(&EntryFilter::new().tag("even"))
+            .collect();
+        assert_eq!(even.len(), 10);
+        let hotel4 = even.iter().find(|info| info.key == key(4)).unwrap();
+        assert_eq!(hotel4.access_count, 1);
+        assert_eq!(
+            cache
+                .iter_entries(&EntryFilter::new().hotel_id("hotel7"))
+                .count(),
+            1
+        );
+    }
+}
@@ -0,0 +1,130 @@
+// Memory size estimates
+// Cache limits are only as good as the sizes they are computed from. Counting payload bytes
+// alone undercounts small entries badly, so estimates include allocation capacity (not just
+// length), the inline size of structs and the per-slot overhead of the containers holding them.
+
+use std::collections::BTreeMap;
+use std::mem::size_of;
+use std::sync::Arc;
+
+pub trait EstimateSize {
+    // Bytes allocated on the heap for this value, excluding its own inline size
+    fn heap_size(&self) -> usize;
+
+    // Inline plus heap bytes
+    fn estimated_size(&self) -> usize
+    where
+        Self: Sized,
+    {
+        size_of::<Self>() + self.heap_size()
+    }
+}
+
+// B-tree nodes hold up to 11 entries and are between half and completely full
+const BTREE_NODE_CAPACITY: usize = 11;
+// Node header (parent pointer, indices, length) plus the edge pointers of internal nodes
+const BTREE_NODE_OVERHEAD: usize = 16 + (BTREE_NODE_CAPACITY + 1) * size_of::<usize>();
+
+// Bytes a HashMap<K, V> spends per entry: the (K, V) slot plus one control byte,
+// at the maximum load factor of 7/8 (tables are usually emptier right after growing)
+pub fn hash_map_slot_size<K, V>() -> usize {
+    (size_of::<(K, V)>() + 1) * 8 / 7
+}
+
+macro_rules! no_heap {
+    ($($ty:ty),*) => {
+        $(impl EstimateSize for $ty {
+            fn heap_size(&self) -> usize {
+                0
+            }
+        })*
+    };
+}
+
+no_heap!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize, bool, char, f32, f64);
+
+impl EstimateSize for String {
+    fn heap_size(&self) -> usize {
+        self.capacity()
+    }
+}
+
+impl<T: EstimateSize> EstimateSize for Vec<T> {
+    fn heap_size(&self) -> usize {
+        self.capacity() * size_of::<T>() + self.iter().map(T::heap_size).sum::<usize>()
+    }
+}
+
+// The whole allocation is counted, even while other handles share it
+impl<T: EstimateSize> EstimateSize for Arc<[T]> {
+    fn heap_size(&self) -> usize {
+        // Strong and weak reference counts precede the elements
+        2 * size_of::<usize>()
+            + self.len() * size_of::<T>()
+            + self.iter().map(T::heap_size).sum::<usize>()
+    }
+}
+
+impl<T: EstimateSize> EstimateSize for Option<T> {
+    fn heap_size(&self) -> usize {
+        self.as_ref().map_or(0, T::heap_size)
+    }
+}
+
+impl<K: EstimateSize, V: EstimateSize> EstimateSize for BTreeMap<K, V> {
+    fn heap_size(&self) -> usize {
+        if self.is_empty() {
+            return 0;
+        }
+        // Assume nodes are 2/3 full on average
+        let nodes = (self.len() * 3 / 2).div_ceil(BTREE_NODE_CAPACITY);
+        let slots = nodes * BTREE_NODE_CAPACITY * (size_of::<K>() + size_of::<V>());
+        nodes * BTREE_NODE_OVERHEAD
+            + slots
+            + self
+                .iter()
+                .map(|(key, value)| key.heap_size() + value.heap_size())
+                .sum::<usize>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::part1_cache::CacheKey;
+
+    #[test]
+    fn test_capacity_is_counted() {
+        let mut data: Vec<u8> = Vec::with_capacity(1024);
+        data.extend_from_slice(&[1, 2, 3]);
+        assert_eq!(data.heap_size(), 1024);
+        assert_eq!(data.estimated_size(), 1024 + size_of::<Vec<u8>>());
+
+        let tags = vec!["supplier:acme".to_string(), String::with_capacity(64)];
+        assert_eq!(
+            tags.heap_size(),
+            tags.capacity() * size_of::<String>() + tags[0].capacity() + 64
+        );
+        assert_eq!(None::<String>.heap_size(), 0);
+
+        let shared: Arc<[u8]> = Arc::from(vec![0; 100]);
+        assert_eq!(shared.heap_size(), 100 + 2 * size_of::<usize>());
+    }
+
+    #[test]
+    fn test_key_size_grows_with_dimensions() {
+        let key = CacheKey::new("hotel1", "2025-06-01", "2025-06-05");
+        assert_eq!(key.heap_size(), 6 + 10 + 10);
+
+        let with_context = key
+            .clone()
+            .with_dimension(CacheKey::CURRENCY, "EUR")
+            .with_dimension(CacheKey::NATIONALITY, "GB");
+        // Dimension strings plus at least one B-tree node
+        assert!(
+            with_context.heap_size()
+                >= key.heap_size() + "currencyEURnationalityGB".len() + BTREE_NODE_OVERHEAD
+        );
+        assert!(hash_map_slot_size::<CacheKey, Vec<u8>>() > size_of::<(CacheKey, Vec<u8>)>());
+    }
+}
@@ -0,0 +1,63 @@
+// Cache snapshots
+// A snapshot is a point-in-time copy of the live entries with their remaining TTLs, used to
+// inspect a cache while debugging, move its contents to another node or seed a staging
+// environment. Values are exported decompressed, so snapshots don't depend on codec settings.
+// Snapshots serialize with serde (keys in their textual form), e.g. to JSON for debugging.
+
+use super::CacheKey;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::time::Duration;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SnapshotEntry {
+    #[serde(serialize_with = "serialize_key", deserialize_with = "deserialize_key")]
+    pub key: CacheKey,
+    pub data: Vec<u8>,
+    // TTL left at export time; importing restarts the clock
+    pub remaining_ttl: Duration,
+    pub tags: Vec<String>,
+    // Known "no availability" entry, see `AvailabilityCache::store_empty`
+    pub negative: bool,
+}
+
+pub trait CacheSnapshot {
+    // Copy all live entries
+    fn export_snapshot(&self) -> Vec<SnapshotEntry>;
+
+    // Store `entries` with their remaining TTLs, replacing entries with the same key
+    // Returns the number of entries imported; expired and rejected entries are skipped
+    fn import_snapshot(&self, entries: Vec<SnapshotEntry>) -> usize;
+}
+
+fn serialize_key<S: Serializer>(key: &CacheKey, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(key)
+}
+
+fn deserialize_key<'de, D: Deserializer<'de>>(deserializer: D) -> Result<CacheKey, D::Error> {
+    let key = String::deserialize(deserializer)?;
+    key.parse().map_err(serde::de::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_entry_json_roundtrip() {
+        let entry = SnapshotEntry {
+            key: CacheKey::new("hotel:1", "2025-06-01", "2025-06-05")
+                .with_dimension(CacheKey::CURRENCY, "EUR"),
+            data: b"rooms".to_vec(),
+            remaining_ttl: Duration::from_millis(1500),
+            tags: vec!["supplier:acme".to_string()],
+            negative: false,
+        };
+
+        let json = serde_json::to_string(&entry).unwrap();
+        assert!(json.contains(r#""key":"hotel%3A1:2025-06-01:2025-06-05;currency=EUR""#));
+        assert_eq!(serde_json::from_str::<SnapshotEntry>(&json).unwrap(), entry);
+
+        let invalid = json.replace("hotel%3A1:2025-06-01:", "hotel1;");
+        assert!(serde_json::from_str::<SnapshotEntry>(&invalid).is_err());
+    }
+}
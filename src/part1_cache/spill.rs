@@ -0,0 +1,21 @@
+// Spill stores
+// With `CapacityPolicy::SpillToSecondary`, a store that doesn't fit into the full cache is handed
+// to a secondary store (a larger, slower cache, a disk store, ...) instead of evicting other
+// entries. Spilled entries are not readable through the cache that spilled them.
+
+use super::SnapshotEntry;
+
+pub trait SpillStore: Send + Sync {
+    // Take over an entry the cache has no room for; false if it can't be kept there either
+    fn spill(&self, entry: SnapshotEntry) -> bool;
+}
+
+// Plain closures can be used as spill stores, e.g. importing into another cache
+impl<F> SpillStore for F
+where
+    F: Fn(SnapshotEntry) -> bool + Send + Sync,
+{
+    fn spill(&self, entry: SnapshotEntry) -> bool {
+        self(entry)
+    }
+}
@@ -0,0 +1,167 @@
+// SQLite write-behind store
+// Keeps mirrored cache entries in a single table, one row per key, so the database never
+// grows beyond the cache contents. Each batch is applied in one transaction; expired rows are
+// deleted when the store is loaded.
+
+use super::write_behind::{PersistentStore, StoreOp, StoredEntry};
+use super::CacheKey;
+use rusqlite::{params, Connection};
+use std::io;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const SCHEMA: &str = "CREATE TABLE IF NOT EXISTS cache_entries (
+    key TEXT PRIMARY KEY,
+    data BLOB NOT NULL,
+    expires_at_ms INTEGER NOT NULL,
+    tags TEXT NOT NULL,
+    negative INTEGER NOT NULL
+)";
+
+pub struct SqliteStore {
+    connection: Mutex<Connection>,
+}
+
+impl SqliteStore {
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::with_connection(Connection::open(path).map_err(io::Error::other)?)
+    }
+
+    pub fn open_in_memory() -> io::Result<Self> {
+        Self::with_connection(Connection::open_in_memory().map_err(io::Error::other)?)
+    }
+
+    fn with_connection(connection: Connection) -> io::Result<Self> {
+        connection.execute(SCHEMA, []).map_err(io::Error::other)?;
+        Ok(Self {
+            connection: Mutex::new(connection),
+        })
+    }
+}
+
+impl PersistentStore for SqliteStore {
+    fn apply(&self, batch: &[StoreOp]) -> io::Result<()> {
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection.transaction().map_err(io::Error::other)?;
+        {
+            let mut put = transaction
+                .prepare_cached(
+                    "INSERT OR REPLACE INTO cache_entries (key, data, expires_at_ms, tags, negative)
+                     VALUES (?1, ?2, ?3, ?4, ?5)",
+                )
+                .map_err(io::Error::other)?;
+            let mut remove = transaction
+                .prepare_cached("DELETE FROM cache_entries WHERE key = ?1")
+                .map_err(io::Error::other)?;
+
+            for op in batch {
+                match op {
+                    StoreOp::Put(entry) => put.execute(params![
+                        entry.key.to_string(),
+                        entry.data,
+                        unix_millis(entry.expires_at),
+                        serde_json::to_string(&entry.tags)?,
+                        entry.negative,
+                    ]),
+                    StoreOp::Remove(key) => remove.execute(params![key.to_string()]),
+                }
+                .map_err(io::Error::other)?;
+            }
+        }
+        transaction.commit().map_err(io::Error::other)
+    }
+
+    fn load(&self) -> io::Result<Vec<StoredEntry>> {
+        let connection = self.connection.lock().unwrap();
+        connection
+            .execute(
+                "DELETE FROM cache_entries WHERE expires_at_ms <= ?1",
+                params![unix_millis(SystemTime::now())],
+            )
+            .map_err(io::Error::other)?;
+
+        let mut select = connection
+            .prepare("SELECT key, data, expires_at_ms, tags, negative FROM cache_entries")
+            .map_err(io::Error::other)?;
+        let rows = select
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, Vec<u8>>(1)?,
+                    row.get::<_, i64>(2)?,
+                    row.get::<_, String>(3)?,
+                    row.get::<_, bool>(4)?,
+                ))
+            })
+            .map_err(io::Error::other)?;
+
+        let mut entries = Vec::new();
+        for row in rows {
+            let (key, data, expires_at_ms, tags, negative) = row.map_err(io::Error::other)?;
+            let key: CacheKey = match key.parse() {
+                Ok(key) => key,
+                Err(e) => {
+                    tracing::warn!("Skipping stored entry: {}", e);
+                    continue;
+                }
+            };
+            entries.push(StoredEntry {
+                key,
+                data,
+                expires_at: UNIX_EPOCH + Duration::from_millis(expires_at_ms.max(0) as u64),
+                tags: serde_json::from_str(&tags)?,
+                negative,
+            });
+        }
+        Ok(entries)
+    }
+}
+
+// SQLite integers are signed
+fn unix_millis(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn put(hotel_id: &str, ttl: Duration) -> StoreOp {
+        StoreOp::Put(StoredEntry {
+            key: CacheKey::new(hotel_id, "2025-06-01", "2025-06-05")
+                .with_dimension(CacheKey::CURRENCY, "EUR"),
+            data: vec![1, 2, 3],
+            expires_at: SystemTime::now() + ttl,
+            tags: vec!["supplier:acme".to_string()],
+            negative: false,
+        })
+    }
+
+    #[test]
+    fn test_apply_and_load() {
+        let store = SqliteStore::open_in_memory().unwrap();
+        store
+            .apply(&[
+                put("hotel1", Duration::from_secs(60)),
+                put("hotel2", Duration::from_secs(60)),
+                put("hotel3", Duration::ZERO),
+            ])
+            .unwrap();
+        store
+            .apply(&[StoreOp::Remove(
+                CacheKey::new("hotel2", "2025-06-01", "2025-06-05")
+                    .with_dimension(CacheKey::CURRENCY, "EUR"),
+            )])
+            .unwrap();
+
+        let loaded = store.load().unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].key.hotel_id, "hotel1");
+        assert_eq!(loaded[0].key.dimension(CacheKey::CURRENCY), Some("EUR"));
+        assert_eq!(loaded[0].data, vec![1, 2, 3]);
+        assert_eq!(loaded[0].tags, vec!["supplier:acme".to_string()]);
+    }
+}
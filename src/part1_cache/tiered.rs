@@ -0,0 +1,547 @@
+// Two-level cache composition
+// A fast node-local cache (L1) sits in front of a slower shared backend (L2, e.g. Redis).
+// Lookups check L1 first and promote L2 hits into L1; stores are written through to both.
+// Promoted entries don't bring their tags along, so invalidating a tag also drops from L1 the
+// hotels and stays L2 has under it, in every search context.
+
+use super::loader::{self, CacheLoader};
+use super::snapshot::{CacheSnapshot, SnapshotEntry};
+use super::{
+    AvailabilityCache, CacheConfig, CacheKey, CacheLookup, CacheStats, CacheStatsReport,
+    CacheVersion, EvictionPolicy, SearchContext, TierStatsReport,
+};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+pub struct TieredCache<L1, L2> {
+    l1: L1,
+    l2: L2,
+    // TTL used for entries promoted from L2 (None uses the L1 default TTL)
+    // The remaining L2 TTL is unknown, so keep this short to bound staleness
+    promotion_ttl: Option<Duration>,
+    // Loads prefetched keys into both tiers, see `prefetch_keys`
+    loader: Option<Arc<dyn CacheLoader>>,
+    // From the configuration given to `new`, the default one with `from_tiers`
+    prefetch_parallelism: usize,
+    stats: CacheStats,
+    l1_hits: AtomicUsize,
+    l1_misses: AtomicUsize,
+    l2_hits: AtomicUsize,
+    l2_misses: AtomicUsize,
+}
+
+impl<L1: AvailabilityCache, L2: AvailabilityCache> TieredCache<L1, L2> {
+    // Compose two already configured caches
+    pub fn from_tiers(l1: L1, l2: L2) -> Self {
+        Self {
+            l1,
+            l2,
+            promotion_ttl: None,
+            loader: None,
+            prefetch_parallelism: CacheConfig::default().prefetch_parallelism,
+            stats: CacheStats::default(),
+            l1_hits: AtomicUsize::new(0),
+            l1_misses: AtomicUsize::new(0),
+            l2_hits: AtomicUsize::new(0),
+            l2_misses: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn with_promotion_ttl(mut self, ttl: Duration) -> Self {
+        self.promotion_ttl = Some(ttl);
+        self
+    }
+
+    // Fetch prefetched keys through `loader`, into both tiers
+    pub fn with_loader(mut self, loader: impl CacheLoader + 'static) -> Self {
+        self.loader = Some(Arc::new(loader));
+        self
+    }
+
+    fn record_hit(&self, found: &CacheLookup) {
+        self.stats.hit_count.fetch_add(1, Ordering::SeqCst);
+        if *found == CacheLookup::HitEmpty {
+            self.stats.negative_hit_count.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    pub fn l1(&self) -> &L1 {
+        &self.l1
+    }
+
+    pub fn l2(&self) -> &L2 {
+        &self.l2
+    }
+}
+
+impl<L1: AvailabilityCache, L2: AvailabilityCache> AvailabilityCache for TieredCache<L1, L2> {
+    // Both tiers are created from the same configuration, use `from_tiers` to size them separately
+    // Only L2 persists its contents, L1 is rebuilt from it after a restart
+    fn new(config: CacheConfig) -> Self {
+        let l1_config = CacheConfig {
+            persistence_path: None,
+            ..config.clone()
+        };
+        let prefetch_parallelism = config.prefetch_parallelism;
+        Self {
+            prefetch_parallelism,
+            ..Self::from_tiers(L1::new(l1_config), L2::new(config))
+        }
+    }
+
+    // Write-through: L2 is the source of truth, so its result decides success
+    fn store_tagged(
+        &self,
+        key: CacheKey,
+        data: Vec<u8>,
+        ttl: Option<Duration>,
+        tags: &[&str],
+    ) -> bool {
+        let stored = self.l2.store_tagged(key.clone(), data.clone(), ttl, tags);
+        if stored {
+            self.l1.store_tagged(key, data, ttl, tags);
+        } else {
+            self.stats.rejected_count.fetch_add(1, Ordering::SeqCst);
+        }
+        stored
+    }
+
+    fn store_empty(&self, key: CacheKey, ttl: Option<Duration>) -> bool {
+        let stored = self.l2.store_empty(key.clone(), ttl);
+        if stored {
+            self.l1.store_empty(key, ttl);
+        } else {
+            self.stats.rejected_count.fetch_add(1, Ordering::SeqCst);
+        }
+        stored
+    }
+
+    fn lookup(&self, key: &CacheKey) -> CacheLookup {
+        let now = Instant::now();
+        self.stats.total_lookups.fetch_add(1, Ordering::SeqCst);
+
+        let found = self.l1.lookup(key);
+        if found.is_hit() {
+            self.l1_hits.fetch_add(1, Ordering::SeqCst);
+            self.record_hit(&found);
+            self.stats.record_lookup_time(now);
+            return found;
+        }
+        self.l1_misses.fetch_add(1, Ordering::SeqCst);
+
+        let found = self.l2.lookup(key);
+        match &found {
+            CacheLookup::Hit(data) => {
+                self.l2_hits.fetch_add(1, Ordering::SeqCst);
+                self.record_hit(&found);
+                self.l1
+                    .store_key(key.clone(), data.clone(), self.promotion_ttl);
+            }
+            // Negative entries are promoted with the L1 negative TTL unless a promotion TTL is set
+            CacheLookup::HitEmpty => {
+                self.l2_hits.fetch_add(1, Ordering::SeqCst);
+                self.record_hit(&found);
+                self.l1.store_empty(key.clone(), self.promotion_ttl);
+            }
+            CacheLookup::Miss => {
+                self.l2_misses.fetch_add(1, Ordering::SeqCst);
+                self.stats.miss_count.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+        self.stats.record_lookup_time(now);
+        found
+    }
+
+    // L1 serves what it can, the remaining keys are fetched from L2 in one batch
+    fn get_many_keys(&self, keys: &[CacheKey]) -> Vec<CacheLookup> {
+        let now = Instant::now();
+        self.stats
+            .total_lookups
+            .fetch_add(keys.len(), Ordering::SeqCst);
+
+        let mut results = self.l1.get_many_keys(keys);
+        let missing: Vec<usize> = (0..keys.len()).filter(|&i| !results[i].is_hit()).collect();
+        self.l1_hits
+            .fetch_add(keys.len() - missing.len(), Ordering::SeqCst);
+        self.l1_misses.fetch_add(missing.len(), Ordering::SeqCst);
+
+        if !missing.is_empty() {
+            let missing_keys: Vec<CacheKey> = missing.iter().map(|&i| keys[i].clone()).collect();
+            let mut promoted = Vec::new();
+            for (i, found) in missing
+                .into_iter()
+                .zip(self.l2.get_many_keys(&missing_keys))
+            {
+                match &found {
+                    CacheLookup::Hit(data) => {
+                        self.l2_hits.fetch_add(1, Ordering::SeqCst);
+                        promoted.push((keys[i].clone(), data.clone()));
+                    }
+                    CacheLookup::HitEmpty => {
+                        self.l2_hits.fetch_add(1, Ordering::SeqCst);
+                        self.l1.store_empty(keys[i].clone(), self.promotion_ttl);
+                    }
+                    CacheLookup::Miss => {
+                        self.l2_misses.fetch_add(1, Ordering::SeqCst);
+                    }
+                }
+                results[i] = found;
+            }
+            self.l1.store_many(promoted, self.promotion_ttl);
+        }
+
+        for found in &results {
+            match found {
+                CacheLookup::Miss => {
+                    self.stats.miss_count.fetch_add(1, Ordering::SeqCst);
+                }
+                found => self.record_hit(found),
+            }
+        }
+        let per_key_ns = now.elapsed().as_nanos() as u64 / keys.len().max(1) as u64;
+        for _ in keys {
+            self.stats.lookup_latency.record(per_key_ns);
+        }
+        self.stats.record_batch(&results);
+        results
+    }
+
+    // Write-through like `store_tagged`, L1 only gets the entries L2 accepted
+    fn store_many(&self, entries: Vec<(CacheKey, Vec<u8>)>, ttl: Option<Duration>) -> Vec<bool> {
+        let stored = self.l2.store_many(entries.clone(), ttl);
+        let accepted = entries
+            .into_iter()
+            .zip(&stored)
+            .filter(|(_, stored)| **stored)
+            .map(|(entry, _)| entry)
+            .collect();
+        self.l1.store_many(accepted, ttl);
+        self.stats.rejected_count.fetch_add(
+            stored.iter().filter(|stored| !**stored).count(),
+            Ordering::SeqCst,
+        );
+        stored
+    }
+
+    // Versions belong to L2, the source of truth; L1 copies don't carry them
+    fn get_versioned(&self, key: &CacheKey) -> (CacheLookup, CacheVersion) {
+        self.l2.get_versioned(key)
+    }
+
+    fn store_if_version(
+        &self,
+        key: CacheKey,
+        data: Vec<u8>,
+        ttl: Option<Duration>,
+        version: CacheVersion,
+    ) -> bool {
+        let stored = self
+            .l2
+            .store_if_version(key.clone(), data.clone(), ttl, version);
+        if stored {
+            self.l1.store_key(key, data, ttl);
+        }
+        stored
+    }
+
+    // Size, eviction and expiry figures describe the local L1 memory,
+    // per-tier details are available in `tiers`
+    fn stats(&self) -> CacheStatsReport {
+        let l1 = self.l1.stats();
+        let l2 = self.l2.stats();
+
+        CacheStatsReport {
+            size_bytes: l1.size_bytes,
+            items_count: l1.items_count,
+            eviction_count: l1.eviction_count,
+            expired_count: l1.expired_count,
+            raw_bytes: l1.raw_bytes,
+            compressed_bytes: l1.compressed_bytes,
+            version_conflict_count: l2.version_conflict_count,
+            quota_rejected_count: l2.quota_rejected_count,
+            quotas: l2.quotas,
+            admission_rejected_count: l1.admission_rejected_count,
+            spilled_count: l1.spilled_count,
+            tiers: vec![
+                TierStatsReport {
+                    tier: "L1".to_string(),
+                    hit_count: self.l1_hits.load(Ordering::SeqCst),
+                    miss_count: self.l1_misses.load(Ordering::SeqCst),
+                    size_bytes: l1.size_bytes,
+                    items_count: l1.items_count,
+                },
+                TierStatsReport {
+                    tier: "L2".to_string(),
+                    hit_count: self.l2_hits.load(Ordering::SeqCst),
+                    miss_count: self.l2_misses.load(Ordering::SeqCst),
+                    size_bytes: l2.size_bytes,
+                    items_count: l2.items_count,
+                },
+            ],
+            ..self.stats.report()
+        }
+    }
+
+    fn set_eviction_policy(&self, policy: EvictionPolicy) {
+        self.l1.set_eviction_policy(policy);
+        self.l2.set_eviction_policy(policy);
+    }
+
+    // Keys are loaded once through our loader and written through to both tiers. Without one
+    // they're left to the L2 loader, and reach L1 by promotion
+    fn prefetch_keys(&self, keys: Vec<CacheKey>, ttl: Option<Duration>) -> usize {
+        match &self.loader {
+            Some(loader) => {
+                loader::prefetch_into(self, Some(&**loader), keys, ttl, self.prefetch_parallelism)
+            }
+            None => self.l2.prefetch_keys(keys, ttl),
+        }
+    }
+
+    // Entries are removed from both tiers; L1 is usually a subset of L2
+    fn invalidate_in_context(
+        &self,
+        hotel_id: Option<&str>,
+        check_in: Option<&str>,
+        check_out: Option<&str>,
+        context: Option<&SearchContext>,
+    ) -> usize {
+        let l1_removed = self
+            .l1
+            .invalidate_in_context(hotel_id, check_in, check_out, context);
+        let l2_removed = self
+            .l2
+            .invalidate_in_context(hotel_id, check_in, check_out, context);
+        l1_removed.max(l2_removed)
+    }
+
+    // Entries promoted from L2 carry no tags in L1, so L1 also drops the hotels and stays of
+    // the keys L2 has under the tag; entries invalidated on another node still stay in L1
+    // until `promotion_ttl` runs out
+    fn invalidate_by_tag(&self, tag: &str) -> usize {
+        let l1_removed = self.l1.invalidate_by_tag(tag);
+        for key in self.l2.tagged_keys(tag) {
+            self.l1.invalidate(
+                Some(&key.hotel_id),
+                Some(&key.check_in),
+                Some(&key.check_out),
+            );
+        }
+        let l2_removed = self.l2.invalidate_by_tag(tag);
+        l1_removed.max(l2_removed)
+    }
+
+    // L2 holds every tagged entry, L1 only copies
+    fn tagged_keys(&self, tag: &str) -> Vec<CacheKey> {
+        self.l2.tagged_keys(tag)
+    }
+
+    fn resize(&self, new_max_size_mb: usize) -> bool {
+        let l1_resized = self.l1.resize(new_max_size_mb);
+        let l2_resized = self.l2.resize(new_max_size_mb);
+        l1_resized && l2_resized
+    }
+
+    // Both tiers are shut down even if the first one fails
+    fn shutdown(&self) -> std::io::Result<()> {
+        let l1_result = self.l1.shutdown();
+        self.l2.shutdown()?;
+        l1_result
+    }
+}
+
+// Snapshots cover L2, the source of truth; L1 refills from it through promotion
+impl<L1, L2> CacheSnapshot for TieredCache<L1, L2>
+where
+    L1: AvailabilityCache,
+    L2: AvailabilityCache + CacheSnapshot,
+{
+    fn export_snapshot(&self) -> Vec<SnapshotEntry> {
+        self.l2.export_snapshot()
+    }
+
+    fn import_snapshot(&self, entries: Vec<SnapshotEntry>) -> usize {
+        self.l2.import_snapshot(entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::part1_cache::ExampleCache;
+
+    fn tiered() -> TieredCache<ExampleCache, ExampleCache> {
+        TieredCache::new(CacheConfig::default())
+    }
+
+    #[test]
+    fn test_store_writes_through_both_tiers() {
+        let cache = tiered();
+        assert!(cache.store("hotel1", "2025-06-01", "2025-06-05", vec![1, 2, 3], None));
+
+        assert!(cache
+            .l1()
+            .get("hotel1", "2025-06-01", "2025-06-05")
+            .is_some());
+        assert!(cache
+            .l2()
+            .get("hotel1", "2025-06-01", "2025-06-05")
+            .is_some());
+    }
+
+    #[test]
+    fn test_l2_hit_is_promoted_into_l1() {
+        let cache = tiered();
+        assert!(cache.store("hotel1", "2025-06-01", "2025-06-05", vec![1, 2, 3], None));
+
+        // Simulate L1 losing the entry (eviction or another node wrote to L2)
+        cache.l1().invalidate(Some("hotel1"), None, None);
+
+        assert_eq!(
+            cache.get("hotel1", "2025-06-01", "2025-06-05"),
+            Some((vec![1, 2, 3], true))
+        );
+        assert_eq!(
+            cache.get("hotel1", "2025-06-01", "2025-06-05"),
+            Some((vec![1, 2, 3], true))
+        );
+        assert!(cache.get("hotel2", "2025-06-01", "2025-06-05").is_none());
+
+        let stats = cache.stats();
+        assert_eq!(stats.hit_count, 2);
+        assert_eq!(stats.miss_count, 1);
+        assert_eq!(stats.total_lookups, 3);
+
+        let l1 = &stats.tiers[0];
+        let l2 = &stats.tiers[1];
+        assert_eq!(
+            (l1.tier.as_str(), l1.hit_count, l1.miss_count),
+            ("L1", 1, 2)
+        );
+        assert_eq!(
+            (l2.tier.as_str(), l2.hit_count, l2.miss_count),
+            ("L2", 1, 1)
+        );
+    }
+
+    #[test]
+    fn test_negative_entry_is_promoted_into_l1() {
+        let cache = tiered();
+        let key = CacheKey::new("hotel1", "2025-06-01", "2025-06-05");
+        assert!(cache.l2().store_empty(key.clone(), None));
+
+        assert_eq!(cache.lookup(&key), CacheLookup::HitEmpty);
+        assert_eq!(cache.l1().lookup(&key), CacheLookup::HitEmpty);
+        assert_eq!(cache.stats().negative_hit_count, 1);
+    }
+
+    #[test]
+    fn test_store_if_version_checks_l2() {
+        let cache = tiered();
+        let key = CacheKey::new("hotel1", "2025-06-01", "2025-06-05");
+        let (_, version) = cache.get_versioned(&key);
+
+        // Another node refreshed the key in L2 meanwhile
+        cache.l2().store_key(key.clone(), vec![1], None);
+        assert!(!cache.store_if_version(key.clone(), vec![2], None, version));
+
+        let (found, version) = cache.get_versioned(&key);
+        assert_eq!(found, CacheLookup::Hit(vec![1]));
+        assert!(cache.store_if_version(key.clone(), vec![3], None, version));
+        assert_eq!(cache.l1().lookup(&key), CacheLookup::Hit(vec![3]));
+        assert_eq!(cache.stats().version_conflict_count, 1);
+    }
+
+    #[test]
+    fn test_get_many_fills_l1_from_l2() {
+        let cache = tiered();
+        let keys: Vec<CacheKey> = (0..4)
+            .map(|i| CacheKey::new(format!("hotel{}", i), "2025-06-01", "2025-06-05"))
+            .collect();
+        cache.store_many(vec![(keys[0].clone(), vec![0])], None);
+        cache
+            .l2()
+            .store_many(vec![(keys[1].clone(), vec![1])], None);
+        cache.l2().store_empty(keys[2].clone(), None);
+
+        assert_eq!(
+            cache.get_many_keys(&keys),
+            vec![
+                CacheLookup::Hit(vec![0]),
+                CacheLookup::Hit(vec![1]),
+                CacheLookup::HitEmpty,
+                CacheLookup::Miss
+            ]
+        );
+        assert_eq!(cache.l1().lookup(&keys[1]), CacheLookup::Hit(vec![1]));
+        assert_eq!(cache.l1().lookup(&keys[2]), CacheLookup::HitEmpty);
+
+        let stats = cache.stats();
+        assert_eq!((stats.hit_count, stats.miss_count), (3, 1));
+        assert_eq!((stats.tiers[0].hit_count, stats.tiers[1].hit_count), (1, 2));
+        assert_eq!(stats.batch_hits, 3);
+    }
+
+    #[test]
+    fn test_invalidate_by_tag_clears_promoted_entries() {
+        let cache = tiered();
+        let tagged = CacheKey::new("hotel1", "2025-06-01", "2025-06-05");
+        let other = CacheKey::new("hotel2", "2025-06-01", "2025-06-05");
+        assert!(cache.store_tagged(tagged.clone(), vec![1], None, &["supplier:acme"]));
+        assert!(cache.store_key(other.clone(), vec![2], None));
+
+        // Promoted back into L1 without its tag
+        cache.l1().invalidate(None, None, None);
+        assert_eq!(cache.lookup(&tagged), CacheLookup::Hit(vec![1]));
+        assert_eq!(cache.lookup(&other), CacheLookup::Hit(vec![2]));
+        assert_eq!(cache.l1().tagged_keys("supplier:acme"), Vec::new());
+        assert_eq!(cache.tagged_keys("supplier:acme"), vec![tagged.clone()]);
+
+        assert_eq!(cache.invalidate_by_tag("supplier:acme"), 1);
+        assert_eq!(cache.l1().lookup(&tagged), CacheLookup::Miss);
+        assert_eq!(cache.lookup(&tagged), CacheLookup::Miss);
+        assert_eq!(cache.l1().lookup(&other), CacheLookup::Hit(vec![2]));
+    }
+
+    #[test]
+    fn test_prefetch_loads_once_into_both_tiers() {
+        let loads = Arc::new(AtomicUsize::new(0));
+        let cache = tiered().with_loader({
+            let loads = loads.clone();
+            move |key: &CacheKey| {
+                loads.fetch_add(1, Ordering::SeqCst);
+                Ok(key.hotel_id.clone().into_bytes())
+            }
+        });
+        let keys: Vec<CacheKey> = (0..3)
+            .map(|i| CacheKey::new(format!("hotel{}", i), "2025-06-01", "2025-06-05"))
+            .collect();
+
+        assert_eq!(cache.prefetch_keys(keys.clone(), None), 3);
+        assert_eq!(loads.load(Ordering::SeqCst), 3);
+        for key in &keys {
+            let data = CacheLookup::Hit(key.hotel_id.clone().into_bytes());
+            assert_eq!(cache.l1().lookup(key), data);
+            assert_eq!(cache.l2().lookup(key), data);
+        }
+        // Stored, never read back
+        assert_eq!(cache.l2().stats().total_lookups, 3);
+    }
+
+    #[test]
+    fn test_invalidate_clears_both_tiers() {
+        let cache = tiered();
+        cache.store("hotel1", "2025-06-01", "2025-06-05", vec![1], None);
+        cache.store("hotel1", "2025-06-10", "2025-06-15", vec![2], None);
+        cache.store("hotel2", "2025-06-01", "2025-06-05", vec![3], None);
+
+        assert_eq!(cache.invalidate(Some("hotel1"), None, None), 2);
+        assert!(cache.get("hotel1", "2025-06-01", "2025-06-05").is_none());
+        assert!(cache
+            .l2()
+            .get("hotel1", "2025-06-10", "2025-06-15")
+            .is_none());
+        assert!(cache.get("hotel2", "2025-06-01", "2025-06-05").is_some());
+    }
+}
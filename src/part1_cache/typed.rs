@@ -0,0 +1,169 @@
+// Typed cache values
+// Wraps any `AvailabilityCache` and (de)serializes values with serde, so callers can cache
+// `ProcessedResponse`-like structures without hand-rolling the Vec<u8> encoding.
+
+use super::AvailabilityCache;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::marker::PhantomData;
+use std::time::Duration;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum TypedCacheError {
+    #[error("Failed to serialize cache value: {0}")]
+    Serialize(String),
+
+    #[error("Failed to deserialize cache value: {0}")]
+    Deserialize(String),
+}
+
+// Wire format of the cached bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Codec {
+    // Human readable, tolerant to added fields
+    #[default]
+    Json,
+    // Compact and fast, but the type must not change between writers and readers
+    Bincode,
+}
+
+impl Codec {
+    pub fn encode<T: Serialize>(&self, value: &T) -> Result<Vec<u8>, TypedCacheError> {
+        match self {
+            Codec::Json => {
+                serde_json::to_vec(value).map_err(|e| TypedCacheError::Serialize(e.to_string()))
+            }
+            Codec::Bincode => {
+                bincode::serialize(value).map_err(|e| TypedCacheError::Serialize(e.to_string()))
+            }
+        }
+    }
+
+    pub fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, TypedCacheError> {
+        match self {
+            Codec::Json => serde_json::from_slice(bytes)
+                .map_err(|e| TypedCacheError::Deserialize(e.to_string())),
+            Codec::Bincode => {
+                bincode::deserialize(bytes).map_err(|e| TypedCacheError::Deserialize(e.to_string()))
+            }
+        }
+    }
+}
+
+pub struct TypedCache<T, C> {
+    cache: C,
+    codec: Codec,
+    _value: PhantomData<fn() -> T>,
+}
+
+impl<T: Serialize + DeserializeOwned, C: AvailabilityCache> TypedCache<T, C> {
+    // Typed view over `cache` using JSON encoding
+    pub fn new(cache: C) -> Self {
+        Self::with_codec(cache, Codec::Json)
+    }
+
+    pub fn with_codec(cache: C, codec: Codec) -> Self {
+        Self {
+            cache,
+            codec,
+            _value: PhantomData,
+        }
+    }
+
+    // The underlying byte cache, e.g. for stats or invalidation
+    pub fn inner(&self) -> &C {
+        &self.cache
+    }
+
+    // Returns Ok(false) if the cache rejected the entry
+    pub fn store_typed(
+        &self,
+        hotel_id: &str,
+        check_in: &str,
+        check_out: &str,
+        value: &T,
+        ttl: Option<Duration>,
+    ) -> Result<bool, TypedCacheError> {
+        let data = self.codec.encode(value)?;
+        Ok(self.cache.store(hotel_id, check_in, check_out, data, ttl))
+    }
+
+    // Entries that can't be decoded (e.g. written by an older version of T) are reported as
+    // errors rather than misses, so schema drift doesn't go unnoticed
+    pub fn get_typed(
+        &self,
+        hotel_id: &str,
+        check_in: &str,
+        check_out: &str,
+    ) -> Result<Option<T>, TypedCacheError> {
+        match self.cache.get(hotel_id, check_in, check_out) {
+            Some((data, _)) => self.codec.decode(&data).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::part1_cache::{CacheConfig, ExampleCache};
+    use serde::Deserialize;
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Availability {
+        hotel_name: String,
+        prices: Vec<f64>,
+        refundable: bool,
+    }
+
+    fn sample() -> Availability {
+        Availability {
+            hotel_name: "Days Inn By Wyndham Fargo".to_string(),
+            prices: vec![84.82, 120.5],
+            refundable: true,
+        }
+    }
+
+    #[test]
+    fn test_typed_roundtrip_with_both_codecs() {
+        for codec in [Codec::Json, Codec::Bincode] {
+            let cache: TypedCache<Availability, _> =
+                TypedCache::with_codec(ExampleCache::new(CacheConfig::default()), codec);
+
+            assert!(cache
+                .store_typed("hotel1", "2025-06-01", "2025-06-05", &sample(), None)
+                .unwrap());
+            assert_eq!(
+                cache
+                    .get_typed("hotel1", "2025-06-01", "2025-06-05")
+                    .unwrap(),
+                Some(sample())
+            );
+            assert_eq!(
+                cache
+                    .get_typed("hotel2", "2025-06-01", "2025-06-05")
+                    .unwrap(),
+                None
+            );
+        }
+    }
+
+    #[test]
+    fn test_undecodable_entry_is_an_error() {
+        let cache: TypedCache<Availability, _> =
+            TypedCache::new(ExampleCache::new(CacheConfig::default()));
+        cache.inner().store(
+            "hotel1",
+            "2025-06-01",
+            "2025-06-05",
+            b"not json".to_vec(),
+            None,
+        );
+
+        assert!(matches!(
+            cache.get_typed("hotel1", "2025-06-01", "2025-06-05"),
+            Err(TypedCacheError::Deserialize(_))
+        ));
+    }
+}
@@ -0,0 +1,255 @@
+// Cache warming planner
+// Ranks the keys of a historical access log by how likely they are to be requested at a target
+// time (e.g. the start of the morning peak) so the best ones can be prefetched beforehand.
+// Every past access of a key adds to its score, weighted by
+// - recency: halved for every `half_life` between the access and the target
+// - seasonality: boosted when the access happened at the target's time of day and/or weekday
+// so the score combines recency, frequency and seasonality. Stays checking in before the
+// target date can't be booked any more and are left out.
+
+use super::{AvailabilityCache, CacheKey};
+use chrono::{DateTime, Datelike, NaiveDate, Timelike, Utc};
+use std::collections::HashMap;
+use std::time::Duration;
+
+const SECONDS_PER_DAY: u32 = 24 * 60 * 60;
+
+// One past lookup of a key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccessRecord {
+    pub hotel_id: String,
+    pub check_in: String,
+    pub check_out: String,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl AccessRecord {
+    pub fn new(
+        hotel_id: impl Into<String>,
+        check_in: impl Into<String>,
+        check_out: impl Into<String>,
+        timestamp: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            hotel_id: hotel_id.into(),
+            check_in: check_in.into(),
+            check_out: check_out.into(),
+            timestamp,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WarmupCandidate {
+    pub key: CacheKey,
+    pub score: f64,
+    pub access_count: usize,
+}
+
+pub struct WarmupPlan {
+    // When the warmed entries are needed
+    target: DateTime<Utc>,
+    half_life: Duration,
+    // How much more an access at the target's time of day and weekday counts (0 disables)
+    seasonality_weight: f64,
+    // Accesses this close to the target's time of day count as the same time
+    peak_window: Duration,
+    // Expected cache size of one prefetched entry, used to fit the plan into the byte budget
+    entry_size_bytes: usize,
+    byte_budget: Option<usize>,
+    accesses: Vec<AccessRecord>,
+}
+
+impl WarmupPlan {
+    pub fn new(target: DateTime<Utc>) -> Self {
+        Self {
+            target,
+            half_life: Duration::from_secs(24 * 60 * 60),
+            seasonality_weight: 1.0,
+            peak_window: Duration::from_secs(60 * 60),
+            entry_size_bytes: 4 * 1024,
+            byte_budget: None,
+            accesses: Vec::new(),
+        }
+    }
+
+    pub fn half_life(mut self, half_life: Duration) -> Self {
+        self.half_life = half_life;
+        self
+    }
+
+    pub fn seasonality_weight(mut self, weight: f64) -> Self {
+        self.seasonality_weight = weight;
+        self
+    }
+
+    pub fn peak_window(mut self, window: Duration) -> Self {
+        self.peak_window = window;
+        self
+    }
+
+    pub fn entry_size_bytes(mut self, bytes: usize) -> Self {
+        self.entry_size_bytes = bytes;
+        self
+    }
+
+    pub fn byte_budget(mut self, bytes: usize) -> Self {
+        self.byte_budget = Some(bytes);
+        self
+    }
+
+    pub fn accesses(mut self, accesses: impl IntoIterator<Item = AccessRecord>) -> Self {
+        self.accesses.extend(accesses);
+        self
+    }
+
+    // Candidates by descending score, cut off at the byte budget
+    pub fn ranked(&self) -> Vec<WarmupCandidate> {
+        let target_date = self.target.date_naive();
+        let mut candidates: HashMap<(&str, &str, &str), WarmupCandidate> = HashMap::new();
+        for access in &self.accesses {
+            let past_stay = NaiveDate::parse_from_str(&access.check_in, "%Y-%m-%d")
+                .is_ok_and(|check_in| check_in < target_date);
+            if past_stay {
+                continue;
+            }
+            let candidate = candidates
+                .entry((&access.hotel_id, &access.check_in, &access.check_out))
+                .or_insert_with(|| WarmupCandidate {
+                    key: CacheKey::new(&access.hotel_id, &access.check_in, &access.check_out),
+                    score: 0.0,
+                    access_count: 0,
+                });
+            candidate.score += self.access_score(access.timestamp);
+            candidate.access_count += 1;
+        }
+
+        let mut ranked: Vec<WarmupCandidate> = candidates.into_values().collect();
+        ranked.sort_by(|a, b| {
+            b.score
+                .total_cmp(&a.score)
+                .then_with(|| b.access_count.cmp(&a.access_count))
+                .then_with(|| a.key.to_string().cmp(&b.key.to_string()))
+        });
+        if let Some(budget) = self.byte_budget {
+            ranked.truncate(budget / self.entry_size_bytes.max(1));
+        }
+        ranked
+    }
+
+    pub fn keys(&self) -> Vec<CacheKey> {
+        self.ranked()
+            .into_iter()
+            .map(|candidate| candidate.key)
+            .collect()
+    }
+
+    // Prefetch the plan, best keys first; returns the number of keys stored
+    pub fn prefetch(&self, cache: &impl AvailabilityCache, ttl: Option<Duration>) -> usize {
+        cache.prefetch_keys(self.keys(), ttl)
+    }
+
+    fn access_score(&self, timestamp: DateTime<Utc>) -> f64 {
+        // Accesses after the target (e.g. when planning for a past peak) count as fresh
+        let age_secs = (self.target - timestamp).num_milliseconds().max(0) as f64 / 1000.0;
+        let recency = 0.5f64.powf(age_secs / self.half_life.as_secs_f64().max(1.0));
+
+        // Distance between the times of day, going around midnight if that is shorter
+        let (access_secs, target_secs) = (
+            timestamp.num_seconds_from_midnight(),
+            self.target.num_seconds_from_midnight(),
+        );
+        let distance = access_secs.abs_diff(target_secs);
+        let distance = distance.min(SECONDS_PER_DAY - distance) as f64;
+        let window = self.peak_window.as_secs_f64().max(1.0);
+        let time_match = (1.0 - distance / window).max(0.0);
+        let weekday_match = if timestamp.weekday() == self.target.weekday() {
+            1.0
+        } else {
+            0.0
+        };
+
+        recency * (1.0 + self.seasonality_weight * (time_match + weekday_match) / 2.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::part1_cache::{CacheConfig, ExampleCache, LoadError};
+    use chrono::TimeZone;
+
+    // Monday 2025-05-05, 08:00
+    fn target() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 5, 5, 8, 0, 0).unwrap()
+    }
+
+    fn access(hotel_id: &str, hours_before: i64) -> AccessRecord {
+        AccessRecord::new(
+            hotel_id,
+            "2025-06-01",
+            "2025-06-05",
+            target() - chrono::Duration::hours(hours_before),
+        )
+    }
+
+    fn hotel_ids(plan: &WarmupPlan) -> Vec<String> {
+        plan.keys().into_iter().map(|key| key.hotel_id).collect()
+    }
+
+    #[test]
+    fn test_ranks_by_frequency_and_recency() {
+        let plan = WarmupPlan::new(target())
+            .seasonality_weight(0.0)
+            .accesses(vec![
+                access("old", 24 * 30),
+                access("old", 24 * 30),
+                access("old", 24 * 30),
+                access("recent", 1),
+                access("frequent", 2),
+                access("frequent", 3),
+            ]);
+        assert_eq!(hotel_ids(&plan), vec!["frequent", "recent", "old"]);
+
+        let ranked = plan.ranked();
+        assert_eq!(ranked[0].access_count, 2);
+        assert!(ranked[2].score < 0.01);
+    }
+
+    #[test]
+    fn test_prefers_accesses_at_the_target_time() {
+        // Last night vs. a week ago, at 08:00 on the target's weekday
+        let accesses = vec![access("evening", 12), access("morning", 24 * 7)];
+        let plan = |seasonality_weight| {
+            WarmupPlan::new(target())
+                .half_life(Duration::from_secs(7 * 24 * 60 * 60))
+                .seasonality_weight(seasonality_weight)
+                .accesses(accesses.clone())
+        };
+        assert_eq!(hotel_ids(&plan(1.0)), vec!["morning", "evening"]);
+        assert_eq!(hotel_ids(&plan(0.0)), vec!["evening", "morning"]);
+    }
+
+    #[test]
+    fn test_budget_and_past_stays() {
+        let mut accesses: Vec<AccessRecord> =
+            (0..10).map(|i| access(&format!("hotel{}", i), i)).collect();
+        accesses.push(AccessRecord::new(
+            "past",
+            "2025-05-01",
+            "2025-05-03",
+            target(),
+        ));
+        let plan = WarmupPlan::new(target())
+            .entry_size_bytes(1000)
+            .byte_budget(3500)
+            .accesses(accesses);
+        assert_eq!(hotel_ids(&plan), vec!["hotel0", "hotel1", "hotel2"]);
+
+        let cache = ExampleCache::with_loader(CacheConfig::default(), |key: &CacheKey| {
+            Ok::<_, LoadError>(key.hotel_id.as_bytes().to_vec())
+        });
+        assert_eq!(plan.prefetch(&cache, None), 3);
+        assert_eq!(cache.stats().items_count, 3);
+    }
+}
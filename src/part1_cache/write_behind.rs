@@ -0,0 +1,667 @@
+// Write-behind persistence
+// `persist` only runs on a clean shutdown. With a `PersistentStore` attached, every insert into
+// and removal from the cache is also queued and mirrored to the store by a background thread,
+// so the cache can be rebuilt after a crash without refetching everything from suppliers.
+// Ops are written in batches (up to `batch_size` ops or `flush_interval`) and retried with
+// exponential backoff. Stores never wait for the store: when the queue is full, ops are
+// dropped and counted.
+
+use super::persistence::{read_bytes, read_string, read_u64, unix_millis, write_bytes};
+use super::snapshot::SnapshotEntry;
+use super::{CacheKey, Compression};
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+// One cache entry as mirrored to the store, with its data uncompressed
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoredEntry {
+    pub key: CacheKey,
+    pub data: Vec<u8>,
+    pub expires_at: SystemTime,
+    pub tags: Vec<String>,
+    pub negative: bool,
+}
+
+impl StoredEntry {
+    fn is_expired(&self) -> bool {
+        self.expires_at <= SystemTime::now()
+    }
+
+    // The entry with the TTL left from now, for `CacheSnapshot::import_snapshot`
+    pub fn into_snapshot(self) -> SnapshotEntry {
+        SnapshotEntry {
+            remaining_ttl: self
+                .expires_at
+                .duration_since(SystemTime::now())
+                .unwrap_or(Duration::ZERO),
+            key: self.key,
+            data: self.data,
+            tags: self.tags,
+            negative: self.negative,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StoreOp {
+    // Insert or replace the entry for its key
+    Put(StoredEntry),
+    Remove(CacheKey),
+}
+
+pub trait PersistentStore: Send + Sync {
+    // Apply the ops in order; a failed batch is retried as a whole, so applying the same
+    // ops twice must be harmless
+    fn apply(&self, batch: &[StoreOp]) -> io::Result<()>;
+
+    // All entries that have not expired yet
+    fn load(&self) -> io::Result<Vec<StoredEntry>>;
+}
+
+#[derive(Debug, Clone)]
+pub struct WriteBehindConfig {
+    // Most ops written per batch
+    pub batch_size: usize,
+    // How long the writer waits to fill a batch
+    pub flush_interval: Duration,
+    // Ops waiting to be written; beyond this new ops are dropped
+    pub queue_capacity: usize,
+    // Retries of a failed batch before it is dropped, with the backoff doubling each time
+    pub max_retries: u32,
+    pub retry_backoff: Duration,
+}
+
+impl Default for WriteBehindConfig {
+    fn default() -> Self {
+        Self {
+            batch_size: 256,
+            flush_interval: Duration::from_millis(100),
+            queue_capacity: 10_000,
+            max_retries: 3,
+            retry_backoff: Duration::from_millis(50),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WriteBehindStats {
+    // Ops the store acknowledged
+    pub written: usize,
+    // Ops not queued because the queue was full
+    pub dropped: usize,
+    // Ops lost because their batch still failed after all retries
+    pub failed: usize,
+    pub retries: usize,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    written: AtomicUsize,
+    dropped: AtomicUsize,
+    failed: AtomicUsize,
+    retries: AtomicUsize,
+}
+
+// Queued puts share the stored (possibly compressed) bytes with the cache entry, they are
+// decompressed on the writer thread rather than on the store path
+pub(crate) enum Queued {
+    Put {
+        key: CacheKey,
+        data: Arc<[u8]>,
+        compression: Compression,
+        expires_at: SystemTime,
+        tags: Vec<String>,
+        negative: bool,
+    },
+    Remove(CacheKey),
+    // Acknowledged once everything queued before it was written
+    Flush(mpsc::Sender<()>),
+}
+
+impl Queued {
+    fn into_op(self) -> Option<StoreOp> {
+        match self {
+            Queued::Put {
+                key,
+                data,
+                compression,
+                expires_at,
+                tags,
+                negative,
+            } => match compression.decompress(&data) {
+                Ok(data) => Some(StoreOp::Put(StoredEntry {
+                    key,
+                    data,
+                    expires_at,
+                    tags,
+                    negative,
+                })),
+                Err(e) => {
+                    tracing::warn!("Not persisting undecodable entry {}: {}", key, e);
+                    None
+                }
+            },
+            Queued::Remove(key) => Some(StoreOp::Remove(key)),
+            Queued::Flush(_) => None,
+        }
+    }
+}
+
+// Queue plus the writer thread draining it; shutting it down (or dropping it) writes what is
+// still queued
+pub(crate) struct WriteBehind {
+    // None once shut down, later ops are not mirrored
+    sender: RwLock<Option<SyncSender<Queued>>>,
+    writer: Mutex<Option<JoinHandle<()>>>,
+    counters: Arc<Counters>,
+}
+
+impl WriteBehind {
+    pub(crate) fn start(store: Arc<dyn PersistentStore>, config: WriteBehindConfig) -> Self {
+        let (sender, receiver) = mpsc::sync_channel(config.queue_capacity.max(1));
+        let counters = Arc::new(Counters::default());
+        let writer = {
+            let counters = Arc::clone(&counters);
+            std::thread::spawn(move || run_writer(receiver, store.as_ref(), &config, &counters))
+        };
+        Self {
+            sender: RwLock::new(Some(sender)),
+            writer: Mutex::new(Some(writer)),
+            counters,
+        }
+    }
+
+    pub(crate) fn send(&self, op: Queued) {
+        let sender = self.sender.read().unwrap();
+        let Some(sender) = sender.as_ref() else {
+            return;
+        };
+        if let Err(TrySendError::Full(_)) = sender.try_send(op) {
+            self.counters.dropped.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    // Block until everything queued so far was written (or given up on)
+    pub(crate) fn flush(&self) {
+        let Some(sender) = self.sender.read().unwrap().clone() else {
+            return;
+        };
+        let (ack, done) = mpsc::channel();
+        if sender.send(Queued::Flush(ack)).is_ok() {
+            let _ = done.recv();
+        }
+    }
+
+    // Write what is still queued and stop the writer thread; later calls do nothing
+    pub(crate) fn shutdown(&self) {
+        // Closing the queue lets the writer finish the remaining ops and exit
+        drop(self.sender.write().unwrap().take());
+        if let Some(writer) = self.writer.lock().unwrap().take() {
+            let _ = writer.join();
+        }
+    }
+
+    pub(crate) fn stats(&self) -> WriteBehindStats {
+        WriteBehindStats {
+            written: self.counters.written.load(Ordering::SeqCst),
+            dropped: self.counters.dropped.load(Ordering::SeqCst),
+            failed: self.counters.failed.load(Ordering::SeqCst),
+            retries: self.counters.retries.load(Ordering::SeqCst),
+        }
+    }
+}
+
+impl Drop for WriteBehind {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+fn run_writer(
+    receiver: Receiver<Queued>,
+    store: &dyn PersistentStore,
+    config: &WriteBehindConfig,
+    counters: &Counters,
+) {
+    while let Ok(first) = receiver.recv() {
+        let deadline = Instant::now() + config.flush_interval;
+        let mut batch = Vec::new();
+        let mut acks = Vec::new();
+        let mut next = Some(first);
+        while let Some(queued) = next.take() {
+            if let Queued::Flush(ack) = queued {
+                acks.push(ack);
+                break;
+            }
+            batch.extend(queued.into_op());
+            if batch.len() >= config.batch_size {
+                break;
+            }
+            next = receiver
+                .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                .ok();
+        }
+
+        write_batch(store, &batch, config, counters);
+        for ack in acks {
+            let _ = ack.send(());
+        }
+    }
+}
+
+fn write_batch(
+    store: &dyn PersistentStore,
+    batch: &[StoreOp],
+    config: &WriteBehindConfig,
+    counters: &Counters,
+) {
+    if batch.is_empty() {
+        return;
+    }
+    let mut backoff = config.retry_backoff;
+    for attempt in 0..=config.max_retries {
+        match store.apply(batch) {
+            Ok(()) => {
+                counters.written.fetch_add(batch.len(), Ordering::SeqCst);
+                return;
+            }
+            Err(e) if attempt < config.max_retries => {
+                tracing::warn!("Write-behind batch failed, retrying: {}", e);
+                counters.retries.fetch_add(1, Ordering::SeqCst);
+                std::thread::sleep(backoff);
+                backoff *= 2;
+            }
+            Err(e) => {
+                tracing::warn!("Dropping write-behind batch of {} ops: {}", batch.len(), e);
+                counters.failed.fetch_add(batch.len(), Ordering::SeqCst);
+            }
+        }
+    }
+}
+
+// Append-only log file store
+// Ops are appended and synced per batch; the log is compacted to the live entries when it is
+// opened and whenever it holds more than twice as many records as live keys:
+//
+//   header: b"AVW1"
+//   record: op (u8, 1 = put, 2 = remove) | key_len (u32) | key
+//   put:    expires_at (u64, unix ms) | data_len (u32) | data | negative (u8)
+//           | tag_count (u32) | (tag_len (u32) | tag)*
+//
+// A record cut short by a crash ends the log, it is dropped by the next compaction.
+pub struct FileStore {
+    path: PathBuf,
+    log: Mutex<FileLog>,
+}
+
+struct FileLog {
+    file: File,
+    records: usize,
+    live: usize,
+}
+
+const LOG_MAGIC: &[u8; 4] = b"AVW1";
+const OP_PUT: u8 = 1;
+const OP_REMOVE: u8 = 2;
+// Logs shorter than this are never compacted while open
+const COMPACT_MIN_RECORDS: usize = 1024;
+
+impl FileStore {
+    pub fn open(path: impl Into<PathBuf>) -> io::Result<Self> {
+        let path = path.into();
+        let entries = match File::open(&path) {
+            Ok(file) => replay(BufReader::new(file))?,
+            Err(e) if e.kind() == ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(e),
+        };
+        let log = compact(&path, entries.values())?;
+        Ok(Self {
+            path,
+            log: Mutex::new(log),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl PersistentStore for FileStore {
+    fn apply(&self, batch: &[StoreOp]) -> io::Result<()> {
+        let mut buffer = Vec::new();
+        for op in batch {
+            write_op(&mut buffer, op)?;
+        }
+
+        let mut log = self.log.lock().unwrap();
+        log.file.write_all(&buffer)?;
+        log.file.sync_data()?;
+        log.records += batch.len();
+        for op in batch {
+            match op {
+                StoreOp::Put(_) => log.live += 1,
+                StoreOp::Remove(_) => log.live = log.live.saturating_sub(1),
+            }
+        }
+
+        // `live` over-counts overwrites, so compaction is never triggered too early
+        if log.records > COMPACT_MIN_RECORDS && log.records > 2 * log.live {
+            let entries = replay(BufReader::new(File::open(&self.path)?))?;
+            *log = compact(&self.path, entries.values())?;
+        }
+        Ok(())
+    }
+
+    fn load(&self) -> io::Result<Vec<StoredEntry>> {
+        let _log = self.log.lock().unwrap();
+        let entries = replay(BufReader::new(File::open(&self.path)?))?;
+        Ok(entries.into_values().collect())
+    }
+}
+
+// Rewrite the log with one put per entry and reopen it for appending
+fn compact<'a>(path: &Path, entries: impl Iterator<Item = &'a StoredEntry>) -> io::Result<FileLog> {
+    let tmp_path = path.with_extension("tmp");
+    let mut records = 0;
+    {
+        let mut writer = BufWriter::new(File::create(&tmp_path)?);
+        writer.write_all(LOG_MAGIC)?;
+        for entry in entries {
+            write_op(&mut writer, &StoreOp::Put(entry.clone()))?;
+            records += 1;
+        }
+        writer.flush()?;
+        writer.get_ref().sync_all()?;
+    }
+    fs::rename(&tmp_path, path)?;
+
+    Ok(FileLog {
+        file: OpenOptions::new().append(true).open(path)?,
+        records,
+        live: records,
+    })
+}
+
+// The live entries in a log, expired ones left out
+fn replay(mut reader: impl Read) -> io::Result<HashMap<CacheKey, StoredEntry>> {
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic)?;
+    if &magic != LOG_MAGIC {
+        return Err(io::Error::new(
+            ErrorKind::InvalidData,
+            "not a write-behind log",
+        ));
+    }
+
+    let mut entries = HashMap::new();
+    loop {
+        match read_op(&mut reader) {
+            Ok(StoreOp::Put(entry)) => {
+                entries.insert(entry.key.clone(), entry);
+            }
+            Ok(StoreOp::Remove(key)) => {
+                entries.remove(&key);
+            }
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e),
+        }
+    }
+    entries.retain(|_, entry| !entry.is_expired());
+    Ok(entries)
+}
+
+fn write_op(writer: &mut impl Write, op: &StoreOp) -> io::Result<()> {
+    match op {
+        StoreOp::Put(entry) => {
+            writer.write_all(&[OP_PUT])?;
+            write_bytes(writer, entry.key.to_string().as_bytes())?;
+            writer.write_all(&unix_millis(entry.expires_at).to_le_bytes())?;
+            write_bytes(writer, &entry.data)?;
+            writer.write_all(&[entry.negative as u8])?;
+            writer.write_all(&(entry.tags.len() as u32).to_le_bytes())?;
+            for tag in &entry.tags {
+                write_bytes(writer, tag.as_bytes())?;
+            }
+        }
+        StoreOp::Remove(key) => {
+            writer.write_all(&[OP_REMOVE])?;
+            write_bytes(writer, key.to_string().as_bytes())?;
+        }
+    }
+    Ok(())
+}
+
+fn read_op(reader: &mut impl Read) -> io::Result<StoreOp> {
+    let mut op = [0u8; 1];
+    reader.read_exact(&mut op)?;
+    let key = read_string(read_bytes(reader)?)?
+        .parse::<CacheKey>()
+        .map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?;
+
+    match op[0] {
+        OP_PUT => {
+            let expires_at = UNIX_EPOCH + Duration::from_millis(read_u64(reader)?);
+            let data = read_bytes(reader)?;
+            let mut flags = [0u8; 5];
+            reader.read_exact(&mut flags)?;
+            let tag_count = u32::from_le_bytes([flags[1], flags[2], flags[3], flags[4]]);
+            let tags = (0..tag_count)
+                .map(|_| read_string(read_bytes(reader)?))
+                .collect::<io::Result<Vec<String>>>()?;
+            Ok(StoreOp::Put(StoredEntry {
+                key,
+                data,
+                expires_at,
+                tags,
+                negative: flags[0] != 0,
+            }))
+        }
+        OP_REMOVE => Ok(StoreOp::Remove(key)),
+        op => Err(io::Error::new(
+            ErrorKind::InvalidData,
+            format!("unknown write-behind op {}", op),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::part1_cache::{AvailabilityCache, CacheConfig, CacheLookup, ExampleCache};
+
+    fn temp_path() -> PathBuf {
+        std::env::temp_dir().join(format!("avail-write-behind-{}.log", rand::random::<u64>()))
+    }
+
+    fn key(i: usize) -> CacheKey {
+        CacheKey::new(format!("hotel{}", i), "2025-06-01", "2025-06-05")
+    }
+
+    fn put(i: usize, ttl: Duration) -> StoreOp {
+        StoreOp::Put(StoredEntry {
+            key: key(i),
+            data: vec![i as u8],
+            expires_at: SystemTime::now() + ttl,
+            tags: vec!["supplier:acme".to_string()],
+            negative: false,
+        })
+    }
+
+    #[test]
+    fn test_file_store_replays_log() {
+        let path = temp_path();
+        let store = FileStore::open(&path).unwrap();
+        store
+            .apply(&[
+                put(1, Duration::from_secs(60)),
+                put(2, Duration::from_secs(60)),
+                put(3, Duration::ZERO),
+            ])
+            .unwrap();
+        store
+            .apply(&[StoreOp::Remove(key(2)), put(1, Duration::from_secs(30))])
+            .unwrap();
+
+        // A torn record at the end is ignored
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&[OP_PUT, 40, 0]).unwrap();
+        drop(store);
+
+        let loaded = FileStore::open(&path).unwrap().load().unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].key, key(1));
+        assert_eq!(loaded[0].tags, vec!["supplier:acme".to_string()]);
+        let remaining_ttl = loaded[0].clone().into_snapshot().remaining_ttl;
+        assert!(remaining_ttl <= Duration::from_secs(30));
+        assert!(remaining_ttl > Duration::from_secs(25));
+    }
+
+    #[test]
+    fn test_file_store_compacts_churn() {
+        let path = temp_path();
+        let store = FileStore::open(&path).unwrap();
+        let batch = [put(1, Duration::from_secs(60)), StoreOp::Remove(key(1))];
+        let mut batch_bytes = Vec::new();
+        for op in &batch {
+            write_op(&mut batch_bytes, op).unwrap();
+        }
+        let batches = 4 * COMPACT_MIN_RECORDS;
+        for _ in 0..batches {
+            store.apply(&batch).unwrap();
+        }
+        let size = fs::metadata(&path).unwrap().len() as usize;
+        let loaded = store.load().unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert!(loaded.is_empty());
+        // At most one compaction interval of records is left
+        assert!(size * 4 < batches * batch_bytes.len());
+    }
+
+    #[test]
+    fn test_cache_is_rebuilt_from_store() {
+        let path = temp_path();
+        {
+            let cache = ExampleCache::with_write_behind(
+                CacheConfig {
+                    compression: Compression::Zstd { level: 3 },
+                    compression_threshold_bytes: 16,
+                    ..Default::default()
+                },
+                FileStore::open(&path).unwrap(),
+                WriteBehindConfig::default(),
+            );
+            cache.store_key(key(1), vec![1; 64], None);
+            cache.store_key(key(2), vec![2], None);
+            cache.store_empty(key(3), None);
+            cache.invalidate(Some("hotel2"), None, None);
+            cache.flush_write_behind();
+            assert_eq!(cache.write_behind_stats().unwrap().written, 4);
+        }
+
+        let rebuilt = ExampleCache::with_write_behind(
+            CacheConfig::default(),
+            FileStore::open(&path).unwrap(),
+            WriteBehindConfig::default(),
+        );
+        assert_eq!(rebuilt.lookup(&key(1)), CacheLookup::Hit(vec![1; 64]));
+        assert_eq!(rebuilt.lookup(&key(2)), CacheLookup::Miss);
+        assert_eq!(rebuilt.lookup(&key(3)), CacheLookup::HitEmpty);
+        // Rebuilding doesn't write the entries back
+        rebuilt.flush_write_behind();
+        assert_eq!(rebuilt.write_behind_stats().unwrap().written, 0);
+        drop(rebuilt);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_shutdown_drains_queue_and_stops_writer() {
+        let path = temp_path();
+        let cache = ExampleCache::with_write_behind(
+            CacheConfig::default(),
+            FileStore::open(&path).unwrap(),
+            WriteBehindConfig {
+                flush_interval: Duration::from_secs(60),
+                ..Default::default()
+            },
+        );
+        for i in 0..10 {
+            cache.store_key(key(i), vec![i as u8], None);
+        }
+        cache.shutdown().unwrap();
+        assert_eq!(cache.write_behind_stats().unwrap().written, 10);
+
+        // Still readable, but no longer mirrored
+        assert_eq!(cache.lookup(&key(1)), CacheLookup::Hit(vec![1]));
+        cache.store_key(key(10), vec![10], None);
+        cache.flush_write_behind();
+        cache.shutdown().unwrap();
+        assert_eq!(cache.write_behind_stats().unwrap().written, 10);
+        drop(cache);
+
+        let loaded = FileStore::open(&path).unwrap().load().unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.len(), 10);
+    }
+
+    // Fails the first `failures` batches
+    struct FlakyStore {
+        failures: AtomicUsize,
+        applied: Mutex<Vec<StoreOp>>,
+    }
+
+    impl PersistentStore for FlakyStore {
+        fn apply(&self, batch: &[StoreOp]) -> io::Result<()> {
+            let failures = self.failures.load(Ordering::SeqCst);
+            if failures > 0 {
+                self.failures.store(failures - 1, Ordering::SeqCst);
+                return Err(io::Error::other("database is locked"));
+            }
+            self.applied.lock().unwrap().extend_from_slice(batch);
+            Ok(())
+        }
+
+        fn load(&self) -> io::Result<Vec<StoredEntry>> {
+            Ok(Vec::new())
+        }
+    }
+
+    #[test]
+    fn test_failed_batches_are_retried() {
+        let store = Arc::new(FlakyStore {
+            failures: AtomicUsize::new(2),
+            applied: Mutex::new(Vec::new()),
+        });
+        let write_behind = WriteBehind::start(
+            Arc::clone(&store) as Arc<dyn PersistentStore>,
+            WriteBehindConfig {
+                retry_backoff: Duration::from_millis(1),
+                ..Default::default()
+            },
+        );
+        for i in 0..3 {
+            write_behind.send(Queued::Remove(key(i)));
+        }
+        write_behind.flush();
+
+        assert_eq!(
+            write_behind.stats(),
+            WriteBehindStats {
+                written: 3,
+                dropped: 0,
+                failed: 0,
+                retries: 2,
+            }
+        );
+        assert_eq!(store.applied.lock().unwrap().len(), 3);
+    }
+}
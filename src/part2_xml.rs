@@ -1,11 +1,96 @@
 // Part 2: XML Processing Implementation
+pub mod adapter;
+pub mod booking;
+pub mod cancellation;
+pub mod combination;
+pub mod content;
+pub mod dates;
+pub mod dedupe;
+pub mod diff;
+pub mod encoding;
+pub mod exchange;
+pub mod export;
+pub mod filter;
+pub mod identifier;
+pub mod input;
+pub mod lenient;
+pub mod limits;
+pub mod mapping;
+pub mod occupancy;
+pub mod ota;
+pub mod output;
+pub mod position;
+pub mod pricing;
+pub mod promotions;
+pub mod request;
+#[cfg(test)]
+mod snapshot;
+pub mod sorting;
+pub mod stream;
+pub mod summary;
+pub mod synthetic;
+pub mod taxes;
+pub mod token;
+#[cfg(feature = "validation")]
+pub mod validation;
+pub mod valuation;
+
+pub use adapter::{FlatOffersAdapter, NativeAdapter, SupplierAdapter};
+pub use booking::{BookRQ, BookRS, Guest};
+pub use cancellation::{
+    cancellation_penalty, free_cancellation_until, normalize_policies, CancelRQ, CancelRS,
+    NormalizedPolicy,
+};
+pub use combination::CombinationLimits;
+pub use content::{
+    enrich_options, ContentSource, GeoPoint, HotelContent, HotelContentProvider, InMemoryContent,
+};
+pub use dedupe::{DedupeReport, DedupeStrategy};
+pub use diff::{diff_responses, OptionKey, PolicyChange, PriceChange, ResponseDiff};
+pub use encoding::{decode_document, repair_entities};
+pub use exchange::{convert_currency, ExchangeRateProvider, StaticRates};
+pub use export::{export_options, ExportFormat, EXPORT_COLUMNS};
+pub use filter::{Filter, FilterCriteriaBuilder};
+pub use identifier::option_id;
+pub use input::InputSource;
+pub use lenient::{ProcessingWarning, WarningAction};
+pub use limits::{check_limits, ParseLimits};
+pub use mapping::{MappingAdapter, SupplierMapping};
+pub use occupancy::{match_occupancy, Occupancy, RoomOccupancy};
+pub use ota::OtaHotelAvailRs;
+pub use output::{write_response, write_xml, OutputFormat, XmlWriteOptions};
+pub use position::XmlPosition;
+pub use pricing::{Markup, PricingRules, Rounding};
+pub use promotions::Promotion;
+pub use request::{AvailRQ, AvailRQBuilder, SearchParams};
+pub use sorting::{Page, SortKey, SortOrder};
+pub use stream::HotelOptionStream;
+pub use summary::{summarize_hotels, HotelSummary};
+pub use synthetic::{synthetic_response, synthetic_supplier_json};
+pub use taxes::Tax;
+pub use token::{SearchToken, TokenSigner};
+#[cfg(feature = "validation")]
+pub use validation::{Check, Validator};
+pub use valuation::{ValuationCheck, ValuationRQ, ValuationRS};
+
 use crate::{
+    money::Money,
     supplier::{RoomCapacity, SupplierCancellationPolicy, SupplierResponse},
-    XmlProcessedResponse,
+    xml_response::{ConversionOptions, XmlCancelPenalties, XmlOption},
+    XmlHotel, XmlProcessedResponse,
 };
+use chrono::{DateTime, NaiveDate, Utc};
 use quick_xml::de::from_str;
 use quick_xml::events::Event;
 use quick_xml::reader::Reader;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::io::{BufRead, Write};
+use std::sync::Arc;
 use thiserror::Error;
 
 // Error types for XML processing
@@ -14,6 +99,13 @@ pub enum ProcessingError {
     #[error("XML parse error: {0}")]
     XmlParseError(String),
 
+    // Malformed XML, see `position`
+    #[error("XML syntax error at {position}: {message}")]
+    XmlSyntaxError {
+        message: String,
+        position: XmlPosition,
+    },
+
     #[error("JSON parse error: {0}")]
     JsonParseError(String),
 
@@ -26,6 +118,20 @@ pub enum ProcessingError {
     #[error("Conversion error: {0}")]
     ConversionError(String),
 
+    #[error("Invalid date: {0}")]
+    InvalidDate(String),
+
+    #[error("Unknown supplier: {0}")]
+    UnknownSupplier(String),
+
+    // The document is too large, too deep or has too many options, see `limits`
+    #[error("Limit exceeded: {0}")]
+    LimitExceeded(String),
+
+    // Defined with or without the `validation` feature, which alone produces it
+    #[error("Invalid document: {}", describe(.0))]
+    Invalid(Vec<Violation>),
+
     #[error("I/O error: {0}")]
     IoError(#[from] std::io::Error),
 
@@ -34,6 +140,27 @@ pub enum ProcessingError {
     Other(String),
 }
 
+// A rule a document breaks, where it breaks it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+fn describe(violations: &[Violation]) -> String {
+    violations
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
 // Data structures for XML response
 #[derive(Debug)]
 pub struct HotelAvailabilityResponse {
@@ -64,230 +191,887 @@ pub struct RoomType {
 pub struct Rate {
     pub rate_key: String,
     pub board_type: String,
-    pub price: f64,
+    pub price: Decimal,
     pub currency: String,
     pub cancellation_policies: Vec<SupplierCancellationPolicy>,
     pub booking_code: String,
 }
 
 // Structures for hotel data
-#[derive(Debug, Clone)]
+// JSON (see `HotelSearchProcessor::to_json`) has camelCase keys, amounts as strings and
+// dates and times in ISO 8601
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct ProcessedResponse {
     pub search_id: String,
     pub total_options: usize,
     pub hotels: Vec<HotelOption>,
     pub currency: String,
     pub nationality: String,
-    pub check_in: String,
-    pub check_out: String,
+    // Unknown without the request, see `process_with_request`
+    pub check_in: Option<NaiveDate>,
+    pub check_out: Option<NaiveDate>,
+    // The markets searched, empty without the request
+    #[serde(default)]
+    pub markets: Vec<String>,
 }
 
-impl From<XmlProcessedResponse> for ProcessedResponse {
-    fn from(item: XmlProcessedResponse) -> Self {
-        let mut hotels = Vec::new();
-        for xml_hotel in item.hotels.hotels {
-            for meal_plan in xml_hotel.meal_plans.meal_plans {
-                for option in meal_plan.options.options {
-                    for room in option.rooms.rooms {
-                        let cancellation_policies = room
-                            .cancel_penalties
-                            .cancel_penalties
-                            .iter()
-                            .map(|cp| ProcessedCancellationPolicy {
-                                deadline: cp.deadline.clone(),
-                                penalty_amount: cp.penalty.value.parse().unwrap_or(0.0),
-                                currency: cp.penalty.currency.clone(),
-                                hours_before: cp.hours_before.parse().unwrap_or(0),
-                                penalty_type: cp.penalty.penalty_type.clone(),
-                            })
-                            .collect();
-
-                        let hotel_option = HotelOption {
-                            hotel_id: xml_hotel.hotel_id.clone(),
-                            hotel_name: xml_hotel.hotel_name.clone(),
-                            room_type: room.code.clone(),
-                            room_description: room.description.clone(),
-                            board_type: meal_plan.code.clone(),
-                            price: Price {
-                                amount: option.price.amount.parse().unwrap_or(0.0),
-                                currency: option.price.currency.clone(),
-                            },
-                            cancellation_policies,
-                            payment_type: option.payment_type.clone(),
-                            is_refundable: room.non_refundable.to_lowercase() == "false",
-                            search_token: option
-                                .parameters
-                                .parameters
-                                .iter()
-                                .find(|p| p.key == "search_token")
-                                .map(|p| p.value.clone())
-                                .unwrap_or_default(),
-                        };
-                        hotels.push(hotel_option);
+// Header fields of a ProcessedResponse that the AvailRS document doesn't carry, usually taken
+// from the request the response answers, see `ResponseContext::from_request`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ResponseContext {
+    pub search_id: String,
+    // None: the currency of the option prices
+    pub currency: Option<String>,
+    pub nationality: String,
+    pub check_in: Option<NaiveDate>,
+    pub check_out: Option<NaiveDate>,
+    pub markets: Vec<String>,
+}
+
+impl ResponseContext {
+    // The search ID is the request's "search_id" parameter, or else derived from what was
+    // searched, so repeating a search gives the same ID
+    pub fn from_request(request: &AvailRQ) -> Self {
+        let search_id = match request.parameter_value("search_id") {
+            Some(search_id) => search_id.to_string(),
+            None => {
+                let mut hasher = DefaultHasher::new();
+                request.hotel_codes().hash(&mut hasher);
+                request.markets.markets.hash(&mut hasher);
+                (&request.start_date, &request.end_date).hash(&mut hasher);
+                (&request.currency, &request.nationality).hash(&mut hasher);
+                for candidate in &request.room_candidates.room_candidates {
+                    candidate.quantity.hash(&mut hasher);
+                    for pax in &candidate.paxes.paxes {
+                        pax.age.hash(&mut hasher);
                     }
                 }
+                format!("{:016x}", hasher.finish())
             }
+        };
+        Self {
+            search_id,
+            currency: Some(request.currency.clone()).filter(|currency| !currency.is_empty()),
+            nationality: request.nationality.clone(),
+            check_in: Some(request.start_date),
+            check_out: Some(request.end_date),
+            markets: request.markets.markets.clone(),
         }
+    }
+}
+
+impl ProcessedResponse {
+    pub fn from_xml(
+        response: XmlProcessedResponse,
+        context: ResponseContext,
+    ) -> Result<Self, ProcessingError> {
+        let mut hotels = Vec::new();
+        for hotel in &response.hotels.hotels {
+            hotels.extend(hotel_options(hotel)?);
+        }
+        Ok(Self::from_options(hotels, context))
+    }
+
+    pub fn options_iter(&self) -> std::slice::Iter<'_, HotelOption> {
+        self.hotels.iter()
+    }
+
+    // The option with `option_id`; the first room of it for multi-room options, whose rooms
+    // share the ID and search token
+    pub fn find_option(&self, option_id: &str) -> Option<&HotelOption> {
+        self.hotels
+            .iter()
+            .find(|option| option.option_id == option_id)
+    }
+
+    // The options matching `criteria`, evaluated as the iterator is consumed
+    pub fn filter_iter<'a>(
+        &'a self,
+        criteria: &'a FilterCriteria,
+    ) -> impl Iterator<Item = &'a HotelOption> {
+        let market_searched = criteria.market_searched(&self.markets);
+        self.hotels
+            .iter()
+            .filter(move |option| market_searched && criteria.matches(option, self.check_in))
+    }
+
+    pub(crate) fn from_options(mut hotels: Vec<HotelOption>, context: ResponseContext) -> Self {
+        if context.check_in.is_some() {
+            for option in &mut hotels {
+                option.resolve_free_cancellation(context.check_in);
+            }
+        }
+        let currency = context.currency.unwrap_or_else(|| {
+            hotels
+                .iter()
+                .map(|option| &option.price.currency)
+                .find(|currency| !currency.is_empty())
+                .cloned()
+                .unwrap_or_default()
+        });
 
         ProcessedResponse {
-            search_id: "example_search".to_string(),
+            search_id: context.search_id,
             total_options: hotels.len(),
             hotels,
-            currency: "GBP".to_string(), // Default from the sample
-            nationality: "US".to_string(),
-            check_in: "2025-06-11".to_string(),
-            check_out: "2025-06-12".to_string(),
+            currency,
+            nationality: context.nationality,
+            check_in: context.check_in,
+            check_out: context.check_out,
+            markets: context.markets,
+        }
+    }
+}
+
+// Without a request only the currency is known, from the option prices
+impl TryFrom<XmlProcessedResponse> for ProcessedResponse {
+    type Error = ProcessingError;
+
+    fn try_from(item: XmlProcessedResponse) -> Result<Self, Self::Error> {
+        Self::from_xml(item, ResponseContext::default())
+    }
+}
+
+// One HotelOption per room of every option of a hotel; shared by `process` and the streaming
+// path, which maps one <Hotel> at a time
+pub(crate) fn hotel_options(xml_hotel: &XmlHotel) -> Result<Vec<HotelOption>, ProcessingError> {
+    let mut options = Vec::new();
+    for meal_plan in &xml_hotel.meal_plans.meal_plans {
+        for option in &meal_plan.options.options {
+            options.extend(option_rooms(
+                xml_hotel,
+                &meal_plan.code,
+                option,
+                &mut Vec::new(),
+            )?);
+        }
+    }
+    Ok(options)
+}
+
+// A value that didn't parse and was replaced by a default: the path relative to the <Option>
+// and why
+pub(crate) type Coercion = (String, String);
+
+// One HotelOption per room of an option of `xml_hotel` (whose meal plans aren't used).
+// Malformed prices and penalty amounts are taken as zero and recorded in `coerced`, as is
+// anything else replaced by a default; deadlines that don't parse are errors.
+pub(crate) fn option_rooms(
+    xml_hotel: &XmlHotel,
+    board_type: &str,
+    option: &XmlOption,
+    coerced: &mut Vec<Coercion>,
+) -> Result<Vec<HotelOption>, ProcessingError> {
+    let price = Money::parse(&option.price.amount, &option.price.currency).unwrap_or_else(|| {
+        coerced.push((
+            "Price[1]/@amount".to_string(),
+            format!("price {:?} is not an amount, using 0", option.price.amount),
+        ));
+        Money::new(Decimal::ZERO, &option.price.currency)
+    });
+    // -1 (or anything else negative) means the supplier sets none
+    let selling_price = Money::parse(&option.price.minimum_selling_price, &option.price.currency)
+        .filter(|price| price.amount >= Decimal::ZERO);
+    let search_token = identifier::parameter(option, identifier::SEARCH_TOKEN_PARAMETER);
+    let option_id = identifier::parameter_or_option_id(&xml_hotel.hotel_id, board_type, option);
+
+    let taxes = option_taxes(option, coerced);
+    let promotions = promotions::option_promotions(option);
+
+    let mut options = Vec::new();
+    for (index, room) in (1..).zip(&option.rooms.rooms) {
+        let room_path = format!("Rooms[1]/Room[{}]", index);
+        let mut room_coerced = Vec::new();
+        let cancellation_policies =
+            parse_cancellation_policies(&room.cancel_penalties, &mut room_coerced)?;
+        if !matches!(
+            room.non_refundable.to_lowercase().as_str(),
+            "true" | "false"
+        ) {
+            room_coerced.push((
+                "@nonRefundable".to_string(),
+                format!(
+                    "nonRefundable {:?} is not a boolean, taking the room as non-refundable",
+                    room.non_refundable
+                ),
+            ));
         }
+        coerced.extend(
+            room_coerced
+                .into_iter()
+                .map(|(path, reason)| (format!("{}/{}", room_path, path), reason)),
+        );
+
+        let mut hotel_option = HotelOption {
+            option_id: option_id.clone(),
+            hotel_id: xml_hotel.hotel_id.clone(),
+            hotel_name: xml_hotel.hotel_name.clone(),
+            room_type: room.code.clone(),
+            room_description: room.description.clone(),
+            board_type: board_type.to_string(),
+            price: price.clone(),
+            selling_price: selling_price.clone(),
+            source_price: None,
+            cancellation_policies,
+            payment_type: option.payment_type.clone(),
+            is_refundable: room.non_refundable.to_lowercase() == "false",
+            category: xml_hotel.category,
+            destination_code: xml_hotel.destination_code.clone(),
+            capacity: room.adults.map(|adults| RoomCapacity {
+                adults,
+                children: room.children.unwrap_or(0),
+            }),
+            booking_code: room.booking_code.clone(),
+            room_candidate_ref_id: room.room_candidate_ref_id.trim().parse().ok(),
+            taxes: taxes.clone(),
+            promotions: promotions.clone(),
+            address: None,
+            geo: None,
+            images: Vec::new(),
+            content_source: ContentSource::Supplier,
+            token: search_token.and_then(|token| SearchToken::decode(token).ok()),
+            search_token: search_token.map(str::to_string).unwrap_or_default(),
+            free_cancellation_until: None,
+        };
+        hotel_option.resolve_free_cancellation(None);
+        options.push(hotel_option);
     }
+    Ok(options)
 }
 
-#[derive(Debug, Clone)]
+// Taxes of all rooms of an option; amounts that aren't numbers are taken as zero
+fn option_taxes(option: &XmlOption, coerced: &mut Vec<Coercion>) -> Vec<Tax> {
+    let mut taxes = Vec::new();
+    for (room_index, room) in (1..).zip(&option.rooms.rooms) {
+        for (index, tax) in (1..).zip(&room.taxes.taxes) {
+            let amount = Money::parse(&tax.amount, &tax.currency).unwrap_or_else(|| {
+                coerced.push((
+                    format!(
+                        "Rooms[1]/Room[{}]/Taxes[1]/Tax[{}]/@amount",
+                        room_index, index
+                    ),
+                    format!("tax {:?} is not an amount, using 0", tax.amount),
+                ));
+                Money::new(Decimal::ZERO, &tax.currency)
+            });
+            taxes.push(Tax {
+                name: tax.name.clone(),
+                included: tax.included.to_lowercase() == "true",
+                amount,
+                pay_at_hotel: tax.pay_at_hotel.to_lowercase() == "true",
+            });
+        }
+    }
+    taxes
+}
+
+pub(crate) fn cancellation_policies(
+    penalties: &XmlCancelPenalties,
+) -> Result<Vec<ProcessedCancellationPolicy>, ProcessingError> {
+    parse_cancellation_policies(penalties, &mut Vec::new())
+}
+
+// Penalty amounts that aren't numbers are taken as zero, hours before other than a number or
+// "N/A" as none; both are recorded in `coerced` with paths relative to <CancelPenalties>
+fn parse_cancellation_policies(
+    penalties: &XmlCancelPenalties,
+    coerced: &mut Vec<Coercion>,
+) -> Result<Vec<ProcessedCancellationPolicy>, ProcessingError> {
+    let mut policies = Vec::new();
+    for (index, cp) in (1..).zip(&penalties.cancel_penalties) {
+        let path = format!("CancelPenalties[1]/CancelPenalty[{}]", index);
+        let penalty_amount = cp.penalty.value.trim().parse().unwrap_or_else(|_| {
+            coerced.push((
+                format!("{}/Penalty[1]", path),
+                format!("penalty {:?} is not an amount, using 0", cp.penalty.value),
+            ));
+            Decimal::ZERO
+        });
+        let hours_before = match cp.hours_before.trim() {
+            "" | "N/A" => 0,
+            hours => hours.parse().unwrap_or_else(|_| {
+                coerced.push((
+                    format!("{}/HoursBefore[1]", path),
+                    format!("hours before {:?} is not a number, ignoring it", hours),
+                ));
+                0
+            }),
+        };
+        policies.push(ProcessedCancellationPolicy {
+            deadline: dates::parse_datetime(&cp.deadline)?,
+            penalty_amount,
+            currency: cp.penalty.currency.clone(),
+            hours_before,
+            penalty_type: cp.penalty.penalty_type.clone(),
+        });
+    }
+    Ok(policies)
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct HotelOption {
+    // Identifies the option from search to booking, see `identifier`
+    pub option_id: String,
     pub hotel_id: String,
     pub hotel_name: String,
     pub room_type: String,
     pub room_description: String,
     pub board_type: String,
+    // Net, what the supplier charges
     pub price: Price,
+    // What the option is sold at: the response's minimumSellingPrice or, failing that, the
+    // processor's pricing rules; None if neither gives one
+    pub selling_price: Option<Price>,
+    // The price as the supplier quoted it, once `convert_currency` has changed `price`
+    pub source_price: Option<Price>,
     pub cancellation_policies: Vec<ProcessedCancellationPolicy>,
     pub payment_type: String,
     pub is_refundable: bool,
     pub search_token: String,
+    // The decoded search token, None if the option has none or it's malformed
+    pub token: Option<SearchToken>,
+    // Only known for responses converted from supplier JSON
+    pub category: Option<i32>,
+    pub destination_code: Option<String>,
+    pub capacity: Option<RoomCapacity>,
+    pub booking_code: Option<String>,
+    // The request's RoomCandidate the room is offered for, see `occupancy`
+    pub room_candidate_ref_id: Option<u32>,
+    // Of every room of the option, see `taxes`
+    pub taxes: Vec<Tax>,
+    // Of every room of the option, see `promotions`
+    pub promotions: Vec<Promotion>,
+    // Only known from a content provider, see `content`
+    pub address: Option<String>,
+    pub geo: Option<GeoPoint>,
+    pub images: Vec<String>,
+    pub content_source: ContentSource,
+    // When cancelling starts to cost something, None if it never does; `hours_before` policies
+    // are only resolved if the check-in date was known when processing
+    pub free_cancellation_until: Option<DateTime<Utc>>,
 }
 
-#[derive(Debug, Clone)]
-pub struct Price {
-    pub amount: f64,
-    pub currency: String,
+impl HotelOption {
+    // The option's cancellation policies resolved against its price, see `normalize_policies`
+    pub fn normalized_policies(&self, check_in: Option<NaiveDate>) -> Vec<NormalizedPolicy> {
+        normalize_policies(self, &self.price, check_in)
+    }
+
+    // The price plus the taxes and fees it doesn't include, whether paid with the booking or at
+    // the hotel
+    pub fn total_including_taxes(&self) -> Price {
+        taxes::total_including_taxes(&self.price, &self.taxes)
+    }
+
+    pub fn has_promotion(&self) -> bool {
+        !self.promotions.is_empty()
+    }
+
+    pub(crate) fn resolve_free_cancellation(&mut self, check_in: Option<NaiveDate>) {
+        self.free_cancellation_until = free_cancellation_until(&self.normalized_policies(check_in));
+    }
+
+    // What cancelling this option at `cancel_at` costs, see `cancellation_penalty`
+    pub fn cancellation_penalty(
+        &self,
+        check_in: Option<NaiveDate>,
+        cancel_at: DateTime<Utc>,
+    ) -> Money {
+        cancellation_penalty(
+            &self.cancellation_policies,
+            &self.price,
+            check_in,
+            cancel_at,
+        )
+    }
 }
 
-#[derive(Debug, Clone)]
+pub type Price = Money;
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct ProcessedCancellationPolicy {
-    pub deadline: String, // ISO date format
-    pub penalty_amount: f64,
+    #[serde(with = "dates::utc_datetime")]
+    pub deadline: DateTime<Utc>,
+    pub penalty_amount: Decimal,
     pub currency: String,
     pub hours_before: i32,
     pub penalty_type: String, // "Importe" or "Porcentaje"
 }
 
-#[derive(Debug, Clone)]
+// Usually built with `FilterCriteria::builder()`, see `filter`
+#[derive(Debug, Clone, Default)]
 pub struct FilterCriteria {
-    pub max_price: Option<f64>,
+    // Compared with the option price including taxes, whatever its currency; both bounds
+    // included
+    pub min_price: Option<Decimal>,
+    pub max_price: Option<Decimal>,
     pub board_types: Option<Vec<String>>,
+    pub excluded_board_types: Vec<String>,
     pub free_cancellation: bool,
     pub hotel_ids: Option<Vec<String>>,
     pub room_type_contains: Option<String>,
+    // Only options cancellable free of charge at this moment, judged by their cancellation
+    // policies resolved against the response's check-in date
+    pub refundable_at: Option<DateTime<Utc>>,
+    pub payment_type: Option<String>,
+    // Only options with at least one promotion
+    pub has_promotion: bool,
+    // Hotel category (stars), both bounds included; options of unknown category are left out
+    // when either is set
+    pub min_category: Option<i32>,
+    pub max_category: Option<i32>,
+    // Options of unknown destination are left out when set
+    pub destination_codes: Option<Vec<String>>,
+    // Only responses searched for this market; it applies to the whole response, so
+    // `matches` doesn't check it. Responses with no markets known pass.
+    pub market: Option<String>,
+    pub custom: Vec<Arc<dyn Filter>>,
 }
 
 // Hotel search processor to implement
 pub struct HotelSearchProcessor {
-    // Add appropriate fields here
+    // Inbound JSON schemas by supplier ID, see `convert_supplier_json_to_xml`
+    adapters: HashMap<String, Arc<dyn SupplierAdapter>>,
+    // Signs the search tokens of converted responses, see `with_token_signer`
+    token_signer: Option<TokenSigner>,
+    // Markup on net prices, see `with_pricing`
+    pricing: Option<PricingRules>,
+    // Hotel details supplier payloads lack, see `with_content_provider`
+    content_provider: Option<Arc<dyn HotelContentProvider>>,
+    // How converted responses are written, see `with_write_options`
+    write_options: XmlWriteOptions,
+    // Caps the multi-room options of `convert_json_to_xml_for`
+    combination_limits: CombinationLimits,
+    // Bounds the XML documents read, see `with_limits`
+    limits: ParseLimits,
+    // Validate generated responses and parsed requests, see `with_validation`
+    #[cfg(feature = "validation")]
+    validation: bool,
+}
+
+impl Default for HotelSearchProcessor {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl HotelSearchProcessor {
     // Create a new processor
     pub fn new() -> Self {
-        Self {}
+        Self {
+            adapters: HashMap::new(),
+            token_signer: None,
+            pricing: None,
+            content_provider: None,
+            write_options: XmlWriteOptions::default(),
+            combination_limits: CombinationLimits::default(),
+            limits: ParseLimits::default(),
+            #[cfg(feature = "validation")]
+            validation: false,
+        }
+    }
+
+    // Register the adapter parsing `supplier_id`'s responses, replacing any earlier one
+    pub fn with_adapter(
+        mut self,
+        supplier_id: impl Into<String>,
+        adapter: impl SupplierAdapter + 'static,
+    ) -> Self {
+        self.adapters.insert(supplier_id.into(), Arc::new(adapter));
+        self
+    }
+
+    pub fn adapter(&self, supplier_id: &str) -> Option<&dyn SupplierAdapter> {
+        self.adapters
+            .get(supplier_id)
+            .map(|adapter| adapter.as_ref())
+    }
+
+    // Check converted responses against `Validator::avail_rs` and requests against
+    // `Validator::avail_rq`, failing with `ProcessingError::Invalid` and every violation found
+    #[cfg(feature = "validation")]
+    pub fn with_validation(mut self) -> Self {
+        self.validation = true;
+        self
+    }
+
+    // Sign search tokens in converted responses and require valid signatures in `option_token`
+    pub fn with_token_signer(mut self, signer: TokenSigner) -> Self {
+        self.token_signer = Some(signer);
+        self
+    }
+
+    // Price converted responses and processed options with `rules`
+    pub fn with_pricing(mut self, rules: PricingRules) -> Self {
+        self.pricing = Some(rules);
+        self
+    }
+
+    // Enrich processed options from `provider`, see `content`
+    pub fn with_content_provider(mut self, provider: impl HotelContentProvider + 'static) -> Self {
+        self.content_provider = Some(Arc::new(provider));
+        self
+    }
+
+    // Declaration, indentation and namespace of converted responses; compact by default
+    pub fn with_write_options(mut self, options: XmlWriteOptions) -> Self {
+        self.write_options = options;
+        self
+    }
+
+    pub fn with_combination_limits(mut self, limits: CombinationLimits) -> Self {
+        self.combination_limits = limits;
+        self
+    }
+
+    // Size, depth and option count of the documents processed; `ParseLimits::default()` unless
+    // set
+    pub fn with_limits(mut self, limits: ParseLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    // The search token an option is valued or booked with, verified if tokens are signed
+    pub fn option_token(&self, option: &HotelOption) -> Result<SearchToken, ProcessingError> {
+        match &self.token_signer {
+            Some(signer) => signer.verify(&option.search_token),
+            None => SearchToken::decode(&option.search_token),
+        }
     }
 
     // Process XML response and extract hotel options
     pub fn process(&self, xml: &str) -> Result<ProcessedResponse, ProcessingError> {
+        self.process_with_context(xml, ResponseContext::default())
+    }
+
+    // Like `process`, for a document as received, in whatever encoding it declares, see
+    // `encoding`
+    pub fn process_bytes(&self, xml: &[u8]) -> Result<ProcessedResponse, ProcessingError> {
+        self.process(&decode_document(xml)?)
+    }
+
+    // Read `source` and process it like `process_bytes`
+    pub fn process_from(&self, source: InputSource) -> Result<ProcessedResponse, ProcessingError> {
+        self.process_bytes(&source.read_bytes(&self.limits)?)
+    }
+
+    // Like `process_from`, reading without blocking; the document is processed on the calling
+    // task once read
+    pub async fn process_from_async(
+        &self,
+        source: InputSource,
+    ) -> Result<ProcessedResponse, ProcessingError> {
+        self.process_bytes(&source.read_bytes_async(&self.limits).await?)
+    }
+
+    // Like `process`, but options that can't be mapped are skipped instead of failing the
+    // response; skipped options and coerced values are returned as warnings, see `lenient`
+    pub fn process_lenient(
+        &self,
+        xml: &str,
+    ) -> Result<(ProcessedResponse, Vec<ProcessingWarning>), ProcessingError> {
+        let xml = repair_entities(xml);
+        let (mut response, warnings) =
+            lenient::process(&xml, ResponseContext::default(), self.limits)?;
+        self.complete_options(&mut response.hotels);
+        Ok((response, warnings))
+    }
+
+    // Process a response together with the request it answers, which supplies the search ID,
+    // currency, nationality and dates
+    pub fn process_with_request(
+        &self,
+        response_xml: &str,
+        request_xml: &str,
+    ) -> Result<ProcessedResponse, ProcessingError> {
+        let request = self.parse_request(request_xml)?;
+        self.process_with_context(response_xml, ResponseContext::from_request(&request))
+    }
+
+    pub fn process_with_context(
+        &self,
+        xml: &str,
+        context: ResponseContext,
+    ) -> Result<ProcessedResponse, ProcessingError> {
+        let xml = repair_entities(xml);
+        check_limits(&xml, &self.limits)?;
         let response: XmlProcessedResponse =
-            from_str(xml).map_err(|e| ProcessingError::XmlParseError(e.to_string()))?;
+            from_str(&xml).map_err(|e| position::de_error(&xml, e))?;
+
+        let mut response = ProcessedResponse::from_xml(response, context)?;
+        self.complete_options(&mut response.hotels);
+        Ok(response)
+    }
+
+    // Pricing and content enrichment of processed options
+    fn complete_options(&self, options: &mut [HotelOption]) {
+        if let Some(rules) = &self.pricing {
+            rules.apply(options);
+        }
+        if let Some(provider) = &self.content_provider {
+            enrich_options(options, provider.as_ref());
+        }
+    }
+
+    // Process a (possibly very large) AvailRS document one <Hotel> element at a time, so
+    // memory stays bounded by the largest hotel instead of the whole document
+    pub fn process_stream<R: BufRead>(&self, input: R) -> HotelOptionStream<R> {
+        HotelOptionStream::new(input)
+            .with_pricing(self.pricing.clone())
+            .with_content_provider(self.content_provider.clone())
+            .with_limits(self.limits)
+    }
 
-        Ok(response.into())
+    // Streaming variant calling `on_option` for every option, returns the number of options
+    pub fn process_each<R: BufRead>(
+        &self,
+        input: R,
+        mut on_option: impl FnMut(HotelOption),
+    ) -> Result<usize, ProcessingError> {
+        let mut count = 0;
+        for option in self.process_stream(input) {
+            on_option(option?);
+            count += 1;
+        }
+        Ok(count)
     }
 
-    // Convert supplier JSON response to XML format
+    // Convert supplier JSON response (in our own schema) to XML format
     pub fn convert_json_to_xml(&self, json_str: &str) -> Result<String, ProcessingError> {
-        // Parse the JSON string into SupplierResponse
-        let supplier_response: SupplierResponse = match serde_json::from_str(json_str) {
-            Ok(response) => response,
-            Err(e) => return Err(ProcessingError::JsonParseError(e.to_string())),
-        };
+        self.supplier_response_to_xml(NativeAdapter.parse(json_str)?, None, OutputFormat::AvailRs)
+    }
 
-        // // Convert to XML format
-        let xml_response: XmlProcessedResponse = supplier_response.into();
-        let xml = quick_xml::se::to_string(&xml_response)
-            .map_err(|e| ProcessingError::ConversionError(e.to_string()))?;
+    // Like `convert_json_to_xml`, writing the message of `format`
+    pub fn convert_json_to_xml_as(
+        &self,
+        json_str: &str,
+        format: OutputFormat,
+    ) -> Result<String, ProcessingError> {
+        self.supplier_response_to_xml(NativeAdapter.parse(json_str)?, None, format)
+    }
 
-        // println!("Converted XML: {}", xml);
+    // Convert a response to a request for `occupancy`, combining the supplier's rooms into
+    // options with a room for each room requested, see `combination`
+    pub fn convert_json_to_xml_for(
+        &self,
+        json_str: &str,
+        occupancy: &Occupancy,
+    ) -> Result<String, ProcessingError> {
+        self.supplier_response_to_xml(
+            NativeAdapter.parse(json_str)?,
+            Some(occupancy),
+            OutputFormat::AvailRs,
+        )
+    }
+
+    // Convert a JSON response of `supplier_id`, parsed by its registered adapter
+    pub fn convert_supplier_json_to_xml(
+        &self,
+        supplier_id: &str,
+        json_str: &str,
+    ) -> Result<String, ProcessingError> {
+        let adapter = self
+            .adapter(supplier_id)
+            .ok_or_else(|| ProcessingError::UnknownSupplier(supplier_id.to_string()))?;
+        self.supplier_response_to_xml(adapter.parse(json_str)?, None, OutputFormat::AvailRs)
+    }
+
+    pub fn write_xml(
+        &self,
+        response: &XmlProcessedResponse,
+        options: &XmlWriteOptions,
+    ) -> Result<String, ProcessingError> {
+        output::write_xml(response, options)
+    }
+
+    pub fn write_response(
+        &self,
+        response: &XmlProcessedResponse,
+        format: OutputFormat,
+        options: &XmlWriteOptions,
+    ) -> Result<String, ProcessingError> {
+        output::write_response(response, format, options)
+    }
+
+    // Read supplier JSON from `source` and convert it like `convert_json_to_xml`
+    pub fn convert_from(&self, source: InputSource) -> Result<String, ProcessingError> {
+        self.convert_json_to_xml(&source.read_string(&self.limits)?)
+    }
+
+    pub async fn convert_from_async(&self, source: InputSource) -> Result<String, ProcessingError> {
+        self.convert_json_to_xml(&source.read_string_async(&self.limits).await?)
+    }
+
+    // Convert an AvailRS document back to supplier JSON in our own schema, for tools that only
+    // read that, see `XmlProcessedResponse::to_supplier`
+    pub fn convert_xml_to_json(&self, xml: &str) -> Result<String, ProcessingError> {
+        check_limits(xml, &self.limits)?;
+        let response: XmlProcessedResponse =
+            from_str(xml).map_err(|e| position::de_error(xml, e))?;
+        serde_json::to_string(&response.to_supplier()?)
+            .map_err(|e| ProcessingError::ConversionError(e.to_string()))
+    }
+
+    fn supplier_response_to_xml(
+        &self,
+        supplier_response: SupplierResponse,
+        occupancy: Option<&Occupancy>,
+        format: OutputFormat,
+    ) -> Result<String, ProcessingError> {
+        let options = ConversionOptions {
+            signer: self.token_signer.as_ref(),
+            pricing: self.pricing.as_ref(),
+            occupancy,
+            limits: self.combination_limits,
+        };
+        let xml_response = XmlProcessedResponse::from_supplier(supplier_response, &options);
+        let xml = self.write_response(&xml_response, format, &self.write_options)?;
+        // The validator knows AvailRS only
+        #[cfg(feature = "validation")]
+        if self.validation && format == OutputFormat::AvailRs {
+            Validator::avail_rs().check(&xml)?;
+        }
         Ok(xml)
     }
 
+    // The response as JSON for API consumers
+    pub fn to_json(&self, response: &ProcessedResponse) -> Result<String, ProcessingError> {
+        serde_json::to_string(response).map_err(|e| ProcessingError::ConversionError(e.to_string()))
+    }
+
+    // Order the response's options by `key`, see `sorting`
+    pub fn sort_options(&self, response: &mut ProcessedResponse, key: SortKey, order: SortOrder) {
+        sorting::sort_options(response, key, order)
+    }
+
+    // Up to `limit` of the response's options from `offset` on, with the total count
+    pub fn paginate<'a>(
+        &self,
+        response: &'a ProcessedResponse,
+        offset: usize,
+        limit: usize,
+    ) -> Page<'a> {
+        sorting::paginate(&response.hotels, offset, limit)
+    }
+
+    // Remove duplicate options from the response, see `dedupe`
+    pub fn dedupe_options(
+        &self,
+        response: &mut ProcessedResponse,
+        strategy: DedupeStrategy,
+    ) -> DedupeReport {
+        dedupe::dedupe_options(response, strategy)
+    }
+
+    // What changed between two searches for the same stay, see `diff`
+    pub fn diff_responses(&self, old: &ProcessedResponse, new: &ProcessedResponse) -> ResponseDiff {
+        diff::diff_responses(old, new)
+    }
+
+    // Rewrite the response's prices and penalties into `target`, see `exchange`
+    pub fn convert_currency(
+        &self,
+        response: &mut ProcessedResponse,
+        target: &str,
+        provider: &dyn ExchangeRateProvider,
+    ) -> Result<(), ProcessingError> {
+        exchange::convert_currency(response, target, provider)
+    }
+
+    // Drop options whose room can't host the guests of the request, see `occupancy`
+    pub fn match_occupancy(
+        &self,
+        response: &mut ProcessedResponse,
+        occupancy: &Occupancy,
+    ) -> usize {
+        occupancy::match_occupancy(response, occupancy)
+    }
+
+    // One summary per hotel of the response, for showing hotels rather than options
+    pub fn summarize_hotels(&self, response: &ProcessedResponse) -> Vec<HotelSummary> {
+        summarize_hotels(&response.hotels)
+    }
+
     // Extract hotel options that match the given criteria
     pub fn filter_options(
         &self,
         response: &ProcessedResponse,
         criteria: &FilterCriteria,
     ) -> Vec<HotelOption> {
-        let mut filtered = Vec::new();
-
-        for hotel in &response.hotels {
-            // Apply filters
-            if !criteria
-                .max_price
-                .map_or(true, |max| hotel.price.amount <= max)
-            {
-                continue;
-            }
-
-            if !criteria
-                .board_types
-                .as_ref()
-                .map_or(true, |types| types.contains(&hotel.board_type))
-            {
-                continue;
-            }
-
-            if criteria.free_cancellation && !hotel.is_refundable {
-                continue;
-            }
-
-            if !criteria
-                .hotel_ids
-                .as_ref()
-                .map_or(true, |ids| ids.contains(&hotel.hotel_id))
-            {
-                continue;
-            }
-
-            if !criteria
-                .room_type_contains
-                .as_ref()
-                .map_or(true, |substring| hotel.room_type.contains(substring))
-            {
-                continue;
-            }
+        response.filter_iter(criteria).cloned().collect()
+    }
 
-            filtered.push(hotel.clone());
-        }
+    // Like `filter_options` without cloning the options, for counting or taking a few
+    pub fn filter_iter<'a>(
+        &self,
+        response: &'a ProcessedResponse,
+        criteria: &'a FilterCriteria,
+    ) -> impl Iterator<Item = &'a HotelOption> {
+        response.filter_iter(criteria)
+    }
 
-        filtered
+    // Write options to `writer` as flat rows for analytics, see `export`; returns how many
+    pub fn export_options<'a, W: Write + Send>(
+        &self,
+        options: impl IntoIterator<Item = &'a HotelOption>,
+        format: ExportFormat,
+        writer: W,
+    ) -> Result<usize, ProcessingError> {
+        export::export_options(options, format, writer)
     }
 
     // Helper method to load the sample JSON response
     pub fn load_sample_json(&self) -> Result<String, ProcessingError> {
-        match std::fs::read_to_string(SAMPLE_JSON_PATH) {
-            Ok(content) => Ok(content),
-            Err(e) => Err(ProcessingError::IoError(e)),
-        }
+        InputSource::path(SAMPLE_JSON_PATH).read_string(&self.limits)
     }
 
     // Helper method to load the sample response XML
     pub fn load_sample_response(&self) -> Result<String, ProcessingError> {
-        match std::fs::read_to_string(SAMPLE_XML_PATH) {
-            Ok(content) => Ok(content),
-            Err(e) => Err(ProcessingError::IoError(e)),
-        }
+        InputSource::path(SAMPLE_XML_PATH).read_string(&self.limits)
     }
 
     // Helper method to load the sample request XML
     pub fn load_sample_request(&self) -> Result<String, ProcessingError> {
-        match std::fs::read_to_string(SAMPLE_REQUEST_PATH) {
-            Ok(content) => Ok(content),
-            Err(e) => Err(ProcessingError::IoError(e)),
+        InputSource::path(SAMPLE_REQUEST_PATH).read_string(&self.limits)
+    }
+
+    // ValuationRQ re-checking `option` before it is booked
+    pub fn valuation_request(
+        &self,
+        option: &HotelOption,
+        check_in: NaiveDate,
+        check_out: NaiveDate,
+    ) -> Result<String, ProcessingError> {
+        ValuationRQ::for_option(option, check_in, check_out).to_xml()
+    }
+
+    // Compare the supplier's ValuationRS with the option the guest was shown
+    pub fn check_valuation(
+        &self,
+        option: &HotelOption,
+        response_xml: &str,
+    ) -> Result<ValuationCheck, ProcessingError> {
+        ValuationCheck::compare(option, &ValuationRS::from_xml(response_xml)?)
+    }
+
+    // What a search request asks for, typed, see `SearchParams`
+    pub fn search_params(&self, request_xml: &str) -> Result<SearchParams, ProcessingError> {
+        Ok(SearchParams::from_request(
+            &self.parse_request(request_xml)?,
+        ))
+    }
+
+    // Parse a search request, bare or wrapped in the hub's SOAP envelope
+    pub fn parse_request(&self, request_xml: &str) -> Result<AvailRQ, ProcessingError> {
+        check_limits(request_xml, &self.limits)?;
+        #[cfg(feature = "validation")]
+        if self.validation {
+            Validator::avail_rq().check(request_xml)?;
         }
+        AvailRQ::from_xml(request_xml)
     }
 
     // Extract search parameters from the XML request
@@ -309,32 +1093,32 @@ impl HotelSearchProcessor {
                     // read_text_into for buffered readers not implemented
                     let txt = reader
                         .read_text(e.name())
-                        .expect("Cannot decode text value");
+                        .map_err(|e| position::syntax_error(request_xml, &reader, e))?;
                     start_date = format!("{}", txt);
                 }
                 Ok(Event::Start(e)) if e.name().as_ref() == b"EndDate" => {
                     // read_text_into for buffered readers not implemented
                     let txt = reader
                         .read_text(e.name())
-                        .expect("Cannot decode text value");
+                        .map_err(|e| position::syntax_error(request_xml, &reader, e))?;
                     end_date = format!("{}", txt);
                 }
                 Ok(Event::Start(e)) if e.name().as_ref() == b"Currency" => {
                     // read_text_into for buffered readers not implemented
                     let txt = reader
                         .read_text(e.name())
-                        .expect("Cannot decode text value");
+                        .map_err(|e| position::syntax_error(request_xml, &reader, e))?;
                     currency = format!("{}", txt);
                 }
                 Ok(Event::Start(e)) if e.name().as_ref() == b"Nationality" => {
                     // read_text_into for buffered readers not implemented
                     let txt = reader
                         .read_text(e.name())
-                        .expect("Cannot decode text value");
+                        .map_err(|e| position::syntax_error(request_xml, &reader, e))?;
                     nationality = format!("{}", txt);
                 }
                 Ok(Event::Eof) => break, // exits the loop when reaching end of file
-                Err(e) => panic!("Error at position {}: {:?}", reader.error_position(), e),
+                Err(e) => return Err(position::syntax_error(request_xml, &reader, e)),
                 _ => (), // There are several other `Event`s we do not consider here
             }
         }
@@ -347,6 +1131,12 @@ impl HotelSearchProcessor {
 pub const SAMPLE_XML_PATH: &str = "samples/hotel_search_response.xml";
 pub const SAMPLE_REQUEST_PATH: &str = "samples/hotel_search_request.xml";
 pub const SAMPLE_JSON_PATH: &str = "samples/supplier_response.json";
+// `SAMPLE_JSON_PATH` converted to XML, see `test_conversion_snapshot`
+pub const SAMPLE_CONVERTED_XML_PATH: &str = "samples/supplier_response_converted.xml";
+// `SAMPLE_XML_PATH` processed with `SAMPLE_REQUEST_PATH` as JSON, see `test_json_snapshot`
+pub const SAMPLE_PROCESSED_JSON_PATH: &str = "samples/hotel_search_response.json";
+// SMALL_SAMPLE_XML for a French hotel, in ISO-8859-1 with unescaped names
+pub const SAMPLE_LATIN1_XML_PATH: &str = "samples/hotel_search_response_latin1.xml";
 
 // A small sample for inline testing
 pub const SMALL_SAMPLE_XML: &str = r#"
@@ -386,6 +1176,26 @@ pub const SMALL_SAMPLE_XML: &str = r#"
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_invalid_document_error() {
+        // Matchable whether or not the `validation` feature is on
+        let error = ProcessingError::Invalid(vec![
+            Violation {
+                path: "/AvailRS/@currency".to_string(),
+                message: "missing".to_string(),
+            },
+            Violation {
+                path: "/AvailRS/Hotels[1]".to_string(),
+                message: "empty".to_string(),
+            },
+        ]);
+        assert_eq!(
+            error.to_string(),
+            "Invalid document: /AvailRS/@currency: missing; /AvailRS/Hotels[1]: empty"
+        );
+        assert!(matches!(error, ProcessingError::Invalid(violations) if violations.len() == 2));
+    }
+
     // Test JSON to XML conversion
     #[test]
     fn test_json_to_xml_conversion() {
@@ -445,9 +1255,488 @@ mod tests {
         assert!(xml.contains("<Hotel code=\"12345\""));
         assert!(xml.contains("<MealPlan code=\"BB\">"));
         assert!(xml.contains("<Room id=\"1#DBL\""));
-        assert!(xml.contains("<Price currency=\"USD\" amount=\"120.5\""));
+        assert!(xml.contains("<Price currency=\"USD\" amount=\"120.50\""));
         assert!(xml.contains("<Deadline>2023-12-01T00:00:00Z</Deadline>"));
-        assert!(xml.contains("<Parameter key=\"search_token\" value=\"12345|||||SEARCH123\"/>"));
+        assert!(xml
+            .contains("<Parameter key=\"search_token\" value=\"12345|||2-0||USD|R1|SEARCH123\"/>"));
+    }
+
+    #[test]
+    fn test_conversion_prices_each_rate() {
+        let processor = HotelSearchProcessor::new();
+        let json = r#"{
+            "search_id": "S1", "currency": "EUR", "timestamp": "2025-06-01T12:00:00Z",
+            "hotels": [{"hotel_id": "H1", "name": "Hotel One", "category": 4,
+                "destination_code": "PAR", "rooms": [
+                {"room_id": "DBL", "name": "Double", "capacity": {"adults": 2, "children": 0},
+                 "rates": [
+                    {"rate_id": "R1", "board_type": "BB", "price": 100.0, "booking_code": "B1",
+                     "cancellation_policies": [{"from_date": "2025-06-10T00:00:00Z", "amount": 50}]},
+                    {"rate_id": "R2", "board_type": "BB", "price": 80.0, "booking_code": "B2",
+                     "cancellation_policies": [{"from_date": "2025-05-30T00:00:00Z", "amount": 80}]}
+                 ]},
+                {"room_id": "SGL", "name": "Single", "capacity": {"adults": 1, "children": 0},
+                 "rates": [{"rate_id": "R3", "board_type": "BB", "price": 60.5,
+                    "booking_code": "B3", "cancellation_policies": []}]}
+            ]}]
+        }"#;
+
+        let xml = processor.convert_json_to_xml(json).unwrap();
+        let mut options: Vec<(String, String, bool)> = processor
+            .process(&xml)
+            .unwrap()
+            .hotels
+            .into_iter()
+            .map(|option| {
+                (
+                    option.booking_code.unwrap(),
+                    option.price.xml_amount(),
+                    option.is_refundable,
+                )
+            })
+            .collect();
+        options.sort();
+        assert_eq!(
+            options,
+            vec![
+                ("B1".to_string(), "100.00".to_string(), true),
+                // Its penalty was already due when the search was made
+                ("B2".to_string(), "80.00".to_string(), false),
+                ("B3".to_string(), "60.50".to_string(), true),
+            ]
+        );
+        assert_eq!(xml.matches("<Option ").count(), 3);
+    }
+
+    #[test]
+    fn test_multi_room_options() {
+        let json = r#"{
+            "search_id": "S1", "currency": "EUR", "timestamp": "2025-06-01T12:00:00Z",
+            "hotels": [{"hotel_id": "H1", "name": "Hotel One", "category": 4,
+                "destination_code": "PAR", "rooms": [
+                {"room_id": "DBL", "name": "Double", "capacity": {"adults": 2, "children": 0},
+                 "rates": [
+                    {"rate_id": "R1", "board_type": "BB", "price": 100.0, "booking_code": "B1",
+                     "cancellation_policies": []},
+                    {"rate_id": "R2", "board_type": "BB", "price": 80.0, "booking_code": "B2",
+                     "cancellation_policies": []}
+                 ]},
+                {"room_id": "SGL", "name": "Single", "capacity": {"adults": 1, "children": 0},
+                 "rates": [{"rate_id": "R3", "board_type": "BB", "price": 60.5,
+                    "booking_code": "B3", "cancellation_policies": []}]}
+            ]}]
+        }"#;
+        let date = NaiveDate::from_ymd_opt(2025, 7, 1).unwrap();
+        let request = AvailRQ::new("EUR", "ES", date, date)
+            .room(&[30, 28])
+            .room(&[40]);
+
+        let processor = HotelSearchProcessor::new();
+        let xml = processor
+            .convert_json_to_xml_for(json, &request.occupancy())
+            .unwrap();
+        let response: XmlProcessedResponse = from_str(&xml).unwrap();
+        let options = &response.hotels.hotels[0].meal_plans.meal_plans[0]
+            .options
+            .options;
+        // Doubles for the couple, any room for the single traveller
+        assert_eq!(options.len(), 6);
+        let cheapest = &options[0];
+        assert_eq!(cheapest.price.amount, "140.50");
+        let rooms: Vec<(&str, &str, &str)> = cheapest
+            .rooms
+            .rooms
+            .iter()
+            .map(|room| {
+                (
+                    room.id.as_str(),
+                    room.room_candidate_ref_id.as_str(),
+                    room.price.amount.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            rooms,
+            vec![("1#DBL", "1", "80.00"), ("2#SGL", "2", "60.50")]
+        );
+        let token = SearchToken::decode(&cheapest.parameters.parameters[0].value).unwrap();
+        assert_eq!(
+            (token.rate_key.as_str(), token.occupancy.as_str()),
+            ("R2+R3", "2-0+1-0")
+        );
+
+        let processed = processor.process(&xml).unwrap();
+        assert_eq!(processed.total_options, 12);
+        let first: Vec<(Option<u32>, String)> = processed.hotels[..2]
+            .iter()
+            .map(|option| (option.room_candidate_ref_id, option.price.to_string()))
+            .collect();
+        assert_eq!(
+            first,
+            vec![
+                (Some(1), "140.50 EUR".to_string()),
+                (Some(2), "140.50 EUR".to_string())
+            ]
+        );
+
+        let limited = processor.with_combination_limits(CombinationLimits {
+            per_board_type: 4,
+            per_hotel: 100,
+        });
+        let xml = limited
+            .convert_json_to_xml_for(json, &request.occupancy())
+            .unwrap();
+        assert_eq!(xml.matches("<Option ").count(), 4);
+        // Back in the supplier schema every room rate is listed once
+        let supplier: SupplierResponse =
+            serde_json::from_str(&limited.convert_xml_to_json(&xml).unwrap()).unwrap();
+        let rates: Vec<usize> = supplier.hotels[0]
+            .rooms
+            .iter()
+            .map(|room| room.rates.len())
+            .collect();
+        assert_eq!(rates, vec![2, 1]);
+    }
+
+    #[test]
+    fn test_selling_prices() {
+        let json = r#"{
+            "search_id": "S1", "currency": "EUR", "timestamp": "2025-06-01T12:00:00Z",
+            "hotels": [{"hotel_id": "H1", "name": "Hotel One", "category": 4,
+                "destination_code": "PAR", "rooms": [
+                {"room_id": "DBL", "name": "Double", "capacity": {"adults": 2, "children": 0},
+                 "rates": [{"rate_id": "R1", "board_type": "BB", "price": 84.82,
+                    "booking_code": "B1", "cancellation_policies": []}]}
+            ]}]
+        }"#;
+        let rules = PricingRules::new(Markup::Percentage(Decimal::from(10)))
+            .rounding(Rounding::Up(Decimal::ONE));
+        let processor = HotelSearchProcessor::new().with_pricing(rules);
+
+        let xml = processor.convert_json_to_xml(json).unwrap();
+        assert!(xml.contains(
+            r#"amount="84.82" binding="false" commission="9.18" minimumSellingPrice="94.00""#
+        ));
+        // The selling price is read back from the response, not priced again
+        let option = &HotelSearchProcessor::new().process(&xml).unwrap().hotels[0];
+        assert_eq!(option.price.to_string(), "84.82 EUR");
+        assert_eq!(
+            option.selling_price.as_ref().unwrap().to_string(),
+            "94.00 EUR"
+        );
+
+        // Responses without one are priced by the processor's rules
+        let plain = HotelSearchProcessor::new()
+            .convert_json_to_xml(json)
+            .unwrap();
+        assert!(plain.contains(r#"commission="-1" minimumSellingPrice="-1""#));
+        assert_eq!(
+            HotelSearchProcessor::new().process(&plain).unwrap().hotels[0].selling_price,
+            None
+        );
+        let priced = processor.process(&plain).unwrap();
+        assert_eq!(
+            priced.hotels[0].selling_price.as_ref().unwrap().to_string(),
+            "94.00 EUR"
+        );
+        let streamed: Vec<HotelOption> = processor
+            .process_stream(plain.as_bytes())
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(streamed, priced.hotels);
+    }
+
+    #[test]
+    fn test_taxes() {
+        let json = r#"{
+            "search_id": "S1", "currency": "USD", "timestamp": "2025-06-01T12:00:00Z",
+            "hotels": [{"hotel_id": "H1", "name": "Hotel One", "category": 4,
+                "destination_code": "NYC", "rooms": [
+                {"room_id": "DBL", "name": "Double", "capacity": {"adults": 2, "children": 0},
+                 "rates": [
+                    {"rate_id": "R1", "board_type": "RO", "price": 100.0, "booking_code": "B1",
+                     "cancellation_policies": [], "taxes": [
+                        {"name": "Sales tax", "included": false, "percent": 14.75},
+                        {"name": "Resort fee", "included": false, "amount": 30,
+                         "pay_at_hotel": true},
+                        {"name": "City tax", "included": true, "amount": 3.5}]},
+                    {"rate_id": "R2", "board_type": "BB", "price": 130.0, "booking_code": "B2",
+                     "cancellation_policies": []}
+                 ]}
+            ]}]
+        }"#;
+        let processor = HotelSearchProcessor::new();
+        let xml = processor.convert_json_to_xml(json).unwrap();
+        assert!(xml.contains(
+            r#"<Tax name="Sales tax" included="false" amount="14.75" currency="USD" percent="14.75" payAtHotel="false"/>"#
+        ));
+
+        let response = processor.process(&xml).unwrap();
+        let taxed = response
+            .hotels
+            .iter()
+            .find(|option| option.booking_code.as_deref() == Some("B1"))
+            .unwrap();
+        assert_eq!(taxed.taxes.len(), 3);
+        assert!(taxed.taxes[1].pay_at_hotel);
+        assert_eq!(taxed.price.to_string(), "100.00 USD");
+        assert_eq!(taxed.total_including_taxes().to_string(), "144.75 USD");
+
+        // Tax-exclusive prices no longer look cheaper
+        let mut sorted = response.clone();
+        processor.sort_options(&mut sorted, SortKey::Price, SortOrder::Ascending);
+        assert_eq!(sorted.hotels[0].booking_code.as_deref(), Some("B2"));
+        let criteria = FilterCriteria::builder()
+            .max_price(Decimal::from(140))
+            .build();
+        let kept = processor.filter_options(&response, &criteria);
+        assert_eq!(kept.len(), 1);
+        assert_eq!(kept[0].booking_code.as_deref(), Some("B2"));
+
+        // And they survive the way back
+        let supplier = processor.convert_xml_to_json(&xml).unwrap();
+        assert!(supplier.contains(r#""name":"Resort fee","included":false,"amount":30.0"#));
+    }
+
+    #[test]
+    fn test_promotions() {
+        let json = r#"{
+            "search_id": "S1", "currency": "EUR", "timestamp": "2025-06-01T12:00:00Z",
+            "hotels": [{"hotel_id": "H1", "name": "Hotel One", "category": 4,
+                "destination_code": "PAR", "rooms": [
+                {"room_id": "DBL", "name": "Double", "capacity": {"adults": 2, "children": 0},
+                 "rates": [
+                    {"rate_id": "R1", "board_type": "BB", "price": 90.0, "booking_code": "B1",
+                     "cancellation_policies": [], "promotions": [
+                        {"code": "EB10", "name": "Early booking -10%"},
+                        {"code": "4X3", "name": "Stay 4, pay 3"}]},
+                    {"rate_id": "R2", "board_type": "BB", "price": 100.0, "booking_code": "B2",
+                     "cancellation_policies": []}
+                 ]}
+            ]}]
+        }"#;
+        let processor = HotelSearchProcessor::new();
+        let xml = processor.convert_json_to_xml(json).unwrap();
+        assert!(xml.contains(
+            r#"<Offers><Offer code="EB10" name="Early booking -10%"/><Offer code="4X3" name="Stay 4, pay 3"/></Offers>"#
+        ));
+
+        let response = processor.process(&xml).unwrap();
+        let promoted = processor.filter_options(
+            &response,
+            &FilterCriteria::builder().has_promotion().build(),
+        );
+        assert_eq!(promoted.len(), 1);
+        assert_eq!(promoted[0].booking_code.as_deref(), Some("B1"));
+        assert_eq!(promoted[0].promotions[1].name, "Stay 4, pay 3");
+
+        let supplier = processor.convert_xml_to_json(&xml).unwrap();
+        assert!(supplier.contains(r#""promotions":[{"code":"EB10","name":"Early booking -10%"}"#));
+    }
+
+    #[test]
+    fn test_content_enrichment() {
+        let xml = SMALL_SAMPLE_XML.replace("Days Inn By Wyndham Fargo", "39776757");
+        let provider = InMemoryContent::new().with_hotel(
+            "39776757",
+            HotelContent {
+                name: "Days Inn by Wyndham Fargo".to_string(),
+                stars: Some(2),
+                ..Default::default()
+            },
+        );
+        let processor = HotelSearchProcessor::new().with_content_provider(provider);
+
+        let response = processor.process(&xml).unwrap();
+        let option = &response.hotels[0];
+        assert_eq!(option.hotel_name, "Days Inn by Wyndham Fargo");
+        assert_eq!(option.category, Some(2));
+        assert_eq!(option.content_source, ContentSource::Provider);
+        let streamed: Vec<HotelOption> = processor
+            .process_stream(xml.as_bytes())
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(streamed, response.hotels);
+
+        let plain = &HotelSearchProcessor::new().process(&xml).unwrap().hotels[0];
+        assert_eq!(plain.hotel_name, "39776757");
+        assert_eq!(plain.content_source, ContentSource::Supplier);
+    }
+
+    #[test]
+    fn test_supplier_fields_survive_conversion() {
+        let processor = HotelSearchProcessor::new();
+        let xml = processor
+            .convert_json_to_xml(&processor.load_sample_json().unwrap())
+            .unwrap();
+        assert!(xml.contains("category=\"3\" destinationCode=\"US\""));
+
+        let response = processor.process(&xml).unwrap();
+        let option = response
+            .hotels
+            .iter()
+            .find(|option| option.room_type == "JP744949")
+            .unwrap();
+        assert_eq!(option.category, Some(3));
+        assert_eq!(option.destination_code.as_deref(), Some("US"));
+        assert_eq!(
+            option.capacity,
+            Some(RoomCapacity {
+                adults: 2,
+                children: 0
+            })
+        );
+        assert_eq!(option.booking_code.as_deref(), Some("JP744949"));
+
+        // Hub responses don't carry them
+        let option = &processor.process(SMALL_SAMPLE_XML).unwrap().hotels[0];
+        assert_eq!((option.category, option.capacity.clone()), (None, None));
+    }
+
+    // Golden file of the sample conversion; regenerate it when the output changes on purpose
+    #[test]
+    fn test_conversion_snapshot() {
+        let processor = HotelSearchProcessor::new();
+        let json = processor.load_sample_json().unwrap();
+        let xml = processor.convert_json_to_xml(&json).unwrap();
+        let expected = std::fs::read_to_string(SAMPLE_CONVERTED_XML_PATH).unwrap();
+        assert_eq!(xml, expected.trim_end());
+        for _ in 0..5 {
+            assert_eq!(processor.convert_json_to_xml(&json).unwrap(), xml);
+        }
+    }
+
+    #[test]
+    fn test_json_snapshot() {
+        let processor = HotelSearchProcessor::new();
+        let response = processor
+            .process_with_request(
+                &processor.load_sample_response().unwrap(),
+                &processor.load_sample_request().unwrap(),
+            )
+            .unwrap();
+        let json = processor.to_json(&response).unwrap();
+
+        let expected = std::fs::read_to_string(SAMPLE_PROCESSED_JSON_PATH).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            value,
+            serde_json::from_str::<serde_json::Value>(&expected).unwrap()
+        );
+        assert!(value["hotels"][0]["cancellationPolicies"][0]["penaltyAmount"].is_string());
+
+        let parsed: ProcessedResponse = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, response);
+    }
+
+    #[test]
+    fn test_xml_to_json_round_trip() {
+        let processor = HotelSearchProcessor::new();
+        let json = processor.load_sample_json().unwrap();
+        let xml = processor.convert_json_to_xml(&json).unwrap();
+        let converted = processor.convert_xml_to_json(&xml).unwrap();
+
+        let without_timestamp = |json: &str| {
+            let mut value: serde_json::Value = serde_json::from_str(json).unwrap();
+            value.as_object_mut().unwrap().remove("timestamp");
+            value
+        };
+        assert_eq!(without_timestamp(&converted), without_timestamp(&json));
+        assert_eq!(processor.convert_json_to_xml(&converted).unwrap(), xml);
+    }
+
+    #[test]
+    fn test_xml_to_json() {
+        let processor = HotelSearchProcessor::new();
+        let json = processor.convert_xml_to_json(SMALL_SAMPLE_XML).unwrap();
+        let response: SupplierResponse = serde_json::from_str(&json).unwrap();
+        assert_eq!(response.currency, "GBP");
+        let room = &response.hotels[0].rooms[0];
+        assert_eq!((room.room_id.as_str(), room.rates.len()), ("ND1", 1));
+        assert_eq!(room.rates[0].price.to_string(), "84.82");
+        assert_eq!(room.rates[0].cancellation_policies.len(), 1);
+
+        assert!(matches!(
+            processor.convert_xml_to_json("<AvailRS><Hotels></AvailRS>"),
+            Err(ProcessingError::XmlSyntaxError { .. })
+        ));
+    }
+
+    #[test]
+    fn test_conversion_write_options() {
+        let options = XmlWriteOptions::new().declaration().indent(4);
+        let processor = HotelSearchProcessor::new().with_write_options(options);
+        let json = processor.load_sample_json().unwrap();
+        let xml = processor.convert_json_to_xml(&json).unwrap();
+        assert!(
+            xml.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<AvailRS>\n    <Hotels>")
+        );
+
+        let compact = HotelSearchProcessor::new()
+            .convert_json_to_xml(&json)
+            .unwrap();
+        assert_eq!(
+            processor.process(&xml).unwrap().hotels,
+            processor.process(&compact).unwrap().hotels
+        );
+    }
+
+    #[test]
+    fn test_signed_search_tokens() {
+        let processor = HotelSearchProcessor::new().with_token_signer(TokenSigner::new("secret"));
+        let json = processor.load_sample_json().unwrap();
+        let xml = processor.convert_json_to_xml(&json).unwrap();
+        let response = processor.process(&xml).unwrap();
+
+        let option = &response.hotels[0];
+        let token = processor.option_token(option).unwrap();
+        assert_eq!(Some(&token), option.token.as_ref());
+        assert_eq!(
+            (token.rate_key.as_str(), token.search_id.as_str()),
+            ("RATE001", "SEARCH123456")
+        );
+
+        let mut tampered = option.clone();
+        tampered.search_token = tampered.search_token.replace("RATE001", "RATE002");
+        assert!(processor.option_token(&tampered).is_err());
+
+        // Hub tokens are unsigned
+        let option = &processor.process(SMALL_SAMPLE_XML).unwrap().hotels[0];
+        assert_eq!(option.token.as_ref().unwrap().currency, "GBP");
+        assert!(processor.option_token(option).is_err());
+        assert!(HotelSearchProcessor::new().option_token(option).is_ok());
+    }
+
+    #[test]
+    fn test_convert_by_supplier() {
+        let processor = HotelSearchProcessor::new()
+            .with_adapter("acme", NativeAdapter)
+            .with_adapter("flatco", FlatOffersAdapter);
+        let flat_json = r#"{"searchId": "S1", "currency": "EUR", "offers": [
+            {"hotelCode": "H1", "hotelName": "Hotel One", "roomCode": "DBL", "roomName": "Double",
+             "adults": 2, "board": "BB", "rateKey": "R1", "amount": 99.9}]}"#;
+
+        let xml = processor
+            .convert_supplier_json_to_xml("flatco", flat_json)
+            .unwrap();
+        assert!(xml.contains("<Hotel code=\"H1\" name=\"Hotel One\""));
+        assert!(xml.contains("<Price currency=\"EUR\" amount=\"99.90\""));
+
+        let native_json = processor.load_sample_json().unwrap();
+        assert_eq!(
+            processor
+                .convert_supplier_json_to_xml("acme", &native_json)
+                .unwrap(),
+            processor.convert_json_to_xml(&native_json).unwrap()
+        );
+        assert!(matches!(
+            processor.convert_supplier_json_to_xml("acme", flat_json),
+            Err(ProcessingError::JsonParseError(_))
+        ));
+        assert!(matches!(
+            processor.convert_supplier_json_to_xml("other", flat_json),
+            Err(ProcessingError::UnknownSupplier(id)) if id == "other"
+        ));
     }
 
     // Test loading the sample JSON file
@@ -514,7 +1803,7 @@ mod tests {
         assert_eq!(hotel.hotel_id, "39776757");
         assert_eq!(hotel.hotel_name, "Days Inn By Wyndham Fargo");
         assert_eq!(hotel.board_type, "RO");
-        assert_eq!(hotel.price.amount, 84.82);
+        assert_eq!(hotel.price.amount, Decimal::new(8482, 2));
         assert_eq!(hotel.price.currency, "GBP");
         assert_eq!(hotel.is_refundable, true);
 
@@ -522,23 +1811,99 @@ mod tests {
         assert_eq!(hotel.cancellation_policies.len(), 1);
         let policy = &hotel.cancellation_policies[0];
         assert_eq!(policy.hours_before, 26);
-        assert_eq!(policy.penalty_amount, 84.82);
+        assert_eq!(policy.penalty_amount, Decimal::new(8482, 2));
         assert_eq!(policy.currency, "GBP");
+        assert_eq!(
+            policy.deadline,
+            dates::parse_datetime("2025-06-10T10:00:00Z").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_option_cancellation_penalty() {
+        let response = HotelSearchProcessor::new()
+            .process(SMALL_SAMPLE_XML)
+            .unwrap();
+        let option = &response.hotels[0];
+        let deadline = option.cancellation_policies[0].deadline;
+
+        let before = option.cancellation_penalty(None, deadline - chrono::Duration::hours(1));
+        assert_eq!(before.amount, Decimal::ZERO);
+        let after = option.cancellation_penalty(None, deadline);
+        assert_eq!(after, Money::new(Decimal::new(8482, 2), "GBP"));
+        assert_eq!(option.free_cancellation_until, Some(deadline));
+
+        // Knowing the check-in date, the 26 hours before it come first
+        let context = ResponseContext {
+            check_in: NaiveDate::from_ymd_opt(2025, 6, 11),
+            ..Default::default()
+        };
+        let response = HotelSearchProcessor::new()
+            .process_with_context(SMALL_SAMPLE_XML, context)
+            .unwrap();
+        assert_eq!(
+            response.hotels[0]
+                .free_cancellation_until
+                .unwrap()
+                .to_rfc3339(),
+            "2025-06-09T22:00:00+00:00"
+        );
+    }
+
+    #[test]
+    fn test_invalid_deadline() {
+        let xml = SMALL_SAMPLE_XML.replace("2025-06-10T10:00:00Z", "soon");
+        assert!(matches!(
+            HotelSearchProcessor::new().process(&xml),
+            Err(ProcessingError::InvalidDate(value)) if value == "soon"
+        ));
     }
 
     use test_case::test_case;
 
     // Test for filtering options
-    #[test_case(FilterCriteria {max_price: Some(100.0), board_types: None, free_cancellation: false, hotel_ids: None, room_type_contains: None,},
+    #[test_case(FilterCriteria {max_price: Some(Decimal::from(100)), board_types: None, free_cancellation: false, hotel_ids: None, room_type_contains: None, refundable_at: None, ..Default::default()},
         1,  vec!["hotel2"]; "#1 Filter by max price")]
-    #[test_case(FilterCriteria {max_price: None, board_types: Some(vec!["BB".to_string(), "HB".to_string()]), free_cancellation: false, hotel_ids: None, room_type_contains: None,},
+    #[test_case(FilterCriteria {max_price: None, board_types: Some(vec!["BB".to_string(), "HB".to_string()]), free_cancellation: false, hotel_ids: None, room_type_contains: None, refundable_at: None, ..Default::default()},
         2,  vec!["hotel1", "hotel3"]; "#2 Filter by board type")]
-    #[test_case(FilterCriteria {max_price: None, board_types: None, free_cancellation: true, hotel_ids: None, room_type_contains: None,},
+    #[test_case(FilterCriteria {max_price: None, board_types: None, free_cancellation: true, hotel_ids: None, room_type_contains: None, refundable_at: None, ..Default::default()},
         2,  vec!["hotel1", "hotel3"]; "#3 Filter by free cancellation")]
-    #[test_case(FilterCriteria {max_price: None, board_types: None, free_cancellation: false, hotel_ids: None, room_type_contains: Some("Suite".to_string()),},
+    #[test_case(FilterCriteria {max_price: None, board_types: None, free_cancellation: false, hotel_ids: None, room_type_contains: Some("Suite".to_string()), refundable_at: None, ..Default::default()},
         1,  vec!["hotel3"]; "#4 Filter by room type")]
-    #[test_case(FilterCriteria {max_price: Some(300.0), board_types: Some(vec!["HB".to_string()]), free_cancellation: true, hotel_ids: None, room_type_contains: Some("Suite".to_string()),},
+    #[test_case(FilterCriteria {max_price: Some(Decimal::from(300)), board_types: Some(vec!["HB".to_string()]), free_cancellation: true, hotel_ids: None, room_type_contains: Some("Suite".to_string()), refundable_at: None, ..Default::default()},
         1,  vec!["hotel3"]; "#5 Combined filters")]
+    #[test_case(FilterCriteria {max_price: None, board_types: None, free_cancellation: false, hotel_ids: None, room_type_contains: None, refundable_at: dates::parse_datetime("2025-05-20T00:00:00Z").ok(), ..Default::default()},
+        2,  vec!["hotel1", "hotel3"]; "#6 Filter by refundable well ahead")]
+    #[test_case(FilterCriteria {max_price: None, board_types: None, free_cancellation: false, hotel_ids: None, room_type_contains: None, refundable_at: dates::parse_datetime("2025-05-27T00:00:00Z").ok(), ..Default::default()},
+        1,  vec!["hotel1"]; "#7 Filter by refundable after a deadline")]
+    #[test_case(FilterCriteria {max_price: None, board_types: None, free_cancellation: false, hotel_ids: None, room_type_contains: None, refundable_at: dates::parse_datetime("2025-05-30T00:00:00Z").ok(), ..Default::default()},
+        0,  vec![]; "#8 Filter by refundable days before check-in")]
+    #[test_case(FilterCriteria::builder().price_range(Decimal::from(100), Decimal::from(250)).build(),
+        2,  vec!["hotel1", "hotel3"]; "#9 Filter by price range")]
+    #[test_case(FilterCriteria::builder().min_price(Decimal::from(200)).build(),
+        1,  vec!["hotel3"]; "#10 Filter by min price")]
+    #[test_case(FilterCriteria::builder().exclude_board_types(["RO", "HB"]).build(),
+        1,  vec!["hotel1"]; "#11 Exclude board types")]
+    #[test_case(FilterCriteria::builder().payment_type("CardBookingPay").build(),
+        0,  vec![]; "#12 Filter by payment type")]
+    #[test_case(FilterCriteria::builder().matching(|option: &HotelOption| option.hotel_name.contains("Inn")).build(),
+        1,  vec!["hotel2"]; "#13 Custom filter")]
+    #[test_case(FilterCriteria::builder().free_cancellation().matching(|option: &HotelOption| option.price.amount < Decimal::from(200)).matching(|option: &HotelOption| option.board_type != "HB").build(),
+        1,  vec!["hotel1"]; "#14 Custom filters combined with criteria")]
+    #[test_case(FilterCriteria::builder().has_promotion().build(),
+        1,  vec!["hotel3"]; "#15 Filter by promotion")]
+    #[test_case(FilterCriteria::builder().min_category(4).build(),
+        2,  vec!["hotel1", "hotel3"]; "#16 Filter by min category")]
+    #[test_case(FilterCriteria::builder().category_range(2, 4).build(),
+        2,  vec!["hotel2", "hotel3"]; "#17 Filter by category range")]
+    #[test_case(FilterCriteria::builder().destination_codes(["PMI"]).build(),
+        2,  vec!["hotel1", "hotel3"]; "#18 Filter by destination")]
+    #[test_case(FilterCriteria::builder().destination_codes(["BCN", "PMI"]).max_category(4).max_price(Decimal::from(200)).build(),
+        1,  vec!["hotel2"]; "#19 Destination and category combined with price")]
+    #[test_case(FilterCriteria::builder().min_category(3).destination_codes(["PMI"]).board_types(["BB"]).build(),
+        1,  vec!["hotel1"]; "#20 Category and destination combined with board type")]
+    #[test_case(FilterCriteria::builder().destination_codes(["NYC"]).build(),
+        0,  vec![]; "#21 Filter by destination without options")]
     fn test_criteria_filter_options(
         criteria: FilterCriteria,
         expected_count: usize,
@@ -553,8 +1918,9 @@ mod tests {
             hotels: Vec::new(),
             currency: "GBP".to_string(),
             nationality: "GB".to_string(),
-            check_in: "2025-06-01".to_string(),
-            check_out: "2025-06-05".to_string(),
+            check_in: NaiveDate::from_ymd_opt(2025, 6, 1),
+            check_out: NaiveDate::from_ymd_opt(2025, 6, 5),
+            markets: Vec::new(),
         };
 
         // Add sample hotels with different properties
@@ -565,12 +1931,12 @@ mod tests {
             room_description: "Spacious room with king bed".to_string(),
             board_type: "BB".to_string(), // Bed & Breakfast
             price: Price {
-                amount: 150.0,
+                amount: Decimal::from(150),
                 currency: "GBP".to_string(),
             },
             cancellation_policies: vec![ProcessedCancellationPolicy {
-                deadline: "2025-05-30T00:00:00Z".to_string(),
-                penalty_amount: 75.0,
+                deadline: dates::parse_datetime("2025-05-30T00:00:00Z").unwrap(),
+                penalty_amount: Decimal::from(75),
                 currency: "GBP".to_string(),
                 hours_before: 48,
                 penalty_type: "Importe".to_string(),
@@ -578,6 +1944,9 @@ mod tests {
             payment_type: "MerchantPay".to_string(),
             is_refundable: true,
             search_token: "token1".to_string(),
+            category: Some(5),
+            destination_code: Some("PMI".to_string()),
+            ..Default::default()
         });
 
         response.hotels.push(HotelOption {
@@ -587,13 +1956,16 @@ mod tests {
             room_description: "Basic room with twin beds".to_string(),
             board_type: "RO".to_string(), // Room Only
             price: Price {
-                amount: 80.0,
+                amount: Decimal::from(80),
                 currency: "GBP".to_string(),
             },
             cancellation_policies: vec![],
             payment_type: "MerchantPay".to_string(),
             is_refundable: false,
             search_token: "token2".to_string(),
+            category: Some(2),
+            destination_code: Some("BCN".to_string()),
+            ..Default::default()
         });
 
         response.hotels.push(HotelOption {
@@ -603,12 +1975,12 @@ mod tests {
             room_description: "Luxury suite with ocean view".to_string(),
             board_type: "HB".to_string(), // Half Board
             price: Price {
-                amount: 250.0,
+                amount: Decimal::from(250),
                 currency: "GBP".to_string(),
             },
             cancellation_policies: vec![ProcessedCancellationPolicy {
-                deadline: "2025-05-25T00:00:00Z".to_string(),
-                penalty_amount: 100.0,
+                deadline: dates::parse_datetime("2025-05-25T00:00:00Z").unwrap(),
+                penalty_amount: Decimal::from(100),
                 currency: "GBP".to_string(),
                 hours_before: 168,
                 penalty_type: "Importe".to_string(),
@@ -616,6 +1988,13 @@ mod tests {
             payment_type: "MerchantPay".to_string(),
             is_refundable: true,
             search_token: "token3".to_string(),
+            category: Some(4),
+            destination_code: Some("PMI".to_string()),
+            promotions: vec![Promotion {
+                code: "EB15".to_string(),
+                name: "Early booking -15%".to_string(),
+            }],
+            ..Default::default()
         });
 
         // Test filtering
@@ -626,6 +2005,182 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_filter_iter() {
+        let processor = HotelSearchProcessor::new();
+        let response = processor
+            .process(&processor.load_sample_response().unwrap())
+            .unwrap();
+        let criteria = FilterCriteria::builder()
+            .exclude_board_types(["RO"])
+            .build();
+
+        let filtered = processor.filter_options(&response, &criteria);
+        assert!(!filtered.is_empty() && filtered.len() < response.total_options);
+        assert!(processor
+            .filter_iter(&response, &criteria)
+            .eq(filtered.iter()));
+
+        // The three cheapest without cloning every option
+        let mut cheapest: Vec<&HotelOption> = response.filter_iter(&criteria).collect();
+        cheapest.sort_by_key(|option| option.price.amount);
+        cheapest.truncate(3);
+        let lowest = response
+            .options_iter()
+            .filter(|option| option.board_type != "RO")
+            .map(|option| option.price.amount)
+            .min();
+        assert_eq!(cheapest.len(), 3);
+        assert_eq!(Some(cheapest[0].price.amount), lowest);
+    }
+
+    #[test]
+    fn test_option_ids() {
+        let processor = HotelSearchProcessor::new();
+        let response = processor
+            .process(&processor.load_sample_response().unwrap())
+            .unwrap();
+        let ids: std::collections::HashSet<&str> = response
+            .options_iter()
+            .map(|option| option.option_id.as_str())
+            .collect();
+        assert_eq!(ids.len(), response.total_options);
+        // The same in every run
+        let again = processor
+            .process(&processor.load_sample_response().unwrap())
+            .unwrap();
+        assert!(response
+            .options_iter()
+            .map(|option| &option.option_id)
+            .eq(again.options_iter().map(|option| &option.option_id)));
+
+        let option = &response.hotels[2];
+        assert_eq!(response.find_option(&option.option_id), Some(option));
+        assert_eq!(response.find_option("unknown"), None);
+
+        // Converted responses carry the ID they were given
+        let xml = processor
+            .convert_json_to_xml(&processor.load_sample_json().unwrap())
+            .unwrap();
+        let converted = processor.process(&xml).unwrap();
+        for option in converted.options_iter() {
+            assert!(xml.contains(&format!(
+                "<Parameter key=\"option_id\" value=\"{}\"/>",
+                option.option_id
+            )));
+        }
+    }
+
+    #[test]
+    fn test_category_filter_unknown() {
+        // Hub responses have neither category nor destination
+        let processor = HotelSearchProcessor::new();
+        let response = processor.process(SMALL_SAMPLE_XML).unwrap();
+        for criteria in [
+            FilterCriteria::builder().min_category(1).build(),
+            FilterCriteria::builder().max_category(5).build(),
+            FilterCriteria::builder().destination_codes(["PMI"]).build(),
+        ] {
+            assert_eq!(response.filter_iter(&criteria).count(), 0);
+        }
+
+        // Converted responses have both
+        let xml = processor
+            .convert_json_to_xml(&processor.load_sample_json().unwrap())
+            .unwrap();
+        let response = processor.process(&xml).unwrap();
+        let option = &response.hotels[0];
+        let criteria = FilterCriteria::builder()
+            .category_range(option.category.unwrap(), option.category.unwrap())
+            .destination_codes(option.destination_code.clone())
+            .build();
+        assert!(response.filter_iter(&criteria).any(|kept| kept == option));
+    }
+
+    #[test]
+    fn test_market_filter() {
+        let processor = HotelSearchProcessor::new();
+        let response = processor
+            .process_with_request(
+                &processor.load_sample_response().unwrap(),
+                &processor.load_sample_request().unwrap(),
+            )
+            .unwrap();
+        assert_eq!(response.markets, vec!["US"]);
+
+        let searched = FilterCriteria::builder().market("US").build();
+        assert_eq!(
+            response.filter_iter(&searched).count(),
+            response.total_options
+        );
+        let other = FilterCriteria::builder().market("ES").build();
+        assert_eq!(response.filter_iter(&other).count(), 0);
+
+        // Markets aren't known without the request
+        let without_request = processor
+            .process(&processor.load_sample_response().unwrap())
+            .unwrap();
+        assert_eq!(
+            without_request.filter_iter(&other).count(),
+            without_request.total_options
+        );
+    }
+
+    #[test]
+    fn test_latin1_response() {
+        let processor = HotelSearchProcessor::new();
+        let bytes = std::fs::read(SAMPLE_LATIN1_XML_PATH).unwrap();
+        assert!(std::str::from_utf8(&bytes).is_err());
+
+        let option = &processor.process_bytes(&bytes).unwrap().hotels[0];
+        assert_eq!(option.hotel_name, "Hôtel Château & Spa Mâcon");
+        assert_eq!(
+            option.room_description,
+            "Chambre Supérieure, vue jardin été"
+        );
+        assert_eq!(option.price.to_string(), "84.82 GBP");
+
+        let (lenient, warnings) = processor
+            .process_lenient(&decode_document(&bytes).unwrap())
+            .unwrap();
+        assert!(warnings.is_empty());
+        assert_eq!(lenient.hotels[0].hotel_name, option.hotel_name);
+    }
+
+    #[tokio::test]
+    async fn test_process_from_sources() {
+        let processor = HotelSearchProcessor::new();
+        let expected = processor
+            .process(&processor.load_sample_response().unwrap())
+            .unwrap();
+
+        let from_path = processor
+            .process_from(InputSource::path(SAMPLE_XML_PATH))
+            .unwrap();
+        assert_eq!(from_path, expected);
+        let file = tokio::fs::File::open(SAMPLE_XML_PATH).await.unwrap();
+        let from_async = processor
+            .process_from_async(InputSource::async_reader(file))
+            .await
+            .unwrap();
+        assert_eq!(from_async, expected);
+
+        let converted = processor
+            .convert_from_async(InputSource::path(SAMPLE_JSON_PATH))
+            .await
+            .unwrap();
+        assert_eq!(
+            converted,
+            processor
+                .convert_json_to_xml(&processor.load_sample_json().unwrap())
+                .unwrap()
+        );
+        assert!(matches!(
+            processor.convert_from(InputSource::from("{")),
+            Err(ProcessingError::JsonParseError(_))
+        ));
+    }
+
     #[test]
     fn test_load_sample_response() {
         let processor = HotelSearchProcessor::new();
@@ -644,6 +2199,62 @@ mod tests {
         assert_eq!(response.hotels.len(), 7);
     }
 
+    #[test]
+    fn test_process_with_request() {
+        let processor = HotelSearchProcessor::new();
+        let response = processor
+            .process_with_request(
+                &processor.load_sample_response().unwrap(),
+                &processor.load_sample_request().unwrap(),
+            )
+            .unwrap();
+        assert_eq!(response.currency, "GBP");
+        assert_eq!(response.nationality, "US");
+        assert_eq!(
+            (response.check_in, response.check_out),
+            (
+                NaiveDate::from_ymd_opt(2025, 6, 11),
+                NaiveDate::from_ymd_opt(2025, 6, 12)
+            )
+        );
+        assert_eq!(response.search_id.len(), 16);
+        assert_eq!(response.total_options, 7);
+
+        // The same search gets the same ID, an explicit one wins
+        let request = AvailRQ::new(
+            "EUR",
+            "ES",
+            NaiveDate::from_ymd_opt(2025, 7, 1).unwrap(),
+            NaiveDate::from_ymd_opt(2025, 7, 5).unwrap(),
+        )
+        .hotel("39776757");
+        let search_id = ResponseContext::from_request(&request).search_id;
+        assert_eq!(ResponseContext::from_request(&request).search_id, search_id);
+        assert_ne!(
+            ResponseContext::from_request(&request.clone().hotel("1")).search_id,
+            search_id
+        );
+        let request = request.parameter("search_id", "S1");
+        assert_eq!(ResponseContext::from_request(&request).search_id, "S1");
+    }
+
+    #[test]
+    fn test_currency_from_option_prices() {
+        let processor = HotelSearchProcessor::new();
+        let response = processor.process(SMALL_SAMPLE_XML).unwrap();
+        assert_eq!(response.currency, "GBP");
+        assert_eq!(response.search_id, "");
+
+        let context = ResponseContext {
+            currency: Some("EUR".to_string()),
+            ..Default::default()
+        };
+        let response = processor
+            .process_with_context(SMALL_SAMPLE_XML, context)
+            .unwrap();
+        assert_eq!(response.currency, "EUR");
+    }
+
     #[test]
     fn test_example_search_param_extraction() {
         let processor = HotelSearchProcessor::new();
@@ -668,6 +2279,32 @@ mod tests {
         assert_eq!(end_date, "12/06/2025");
     }
 
+    #[test]
+    fn test_malformed_xml_positions() {
+        let processor = HotelSearchProcessor::new();
+        let request_xml =
+            "<AvailRQ>\n  <Currency>GBP</Currency>\n  <StartDate>11/06/2025</EndDate>\n</AvailRQ>";
+        match processor.extract_search_params(request_xml) {
+            Err(ProcessingError::XmlSyntaxError { position, .. }) => {
+                assert_eq!(
+                    (position.line, position.path.as_str()),
+                    (3, "/AvailRQ/StartDate")
+                );
+            }
+            other => panic!("expected a syntax error, got {:?}", other),
+        }
+
+        let response_xml = SMALL_SAMPLE_XML.replace("</Rooms>", "</Room>");
+        let error = processor.process(&response_xml).unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .starts_with("XML syntax error at line 21, column 15"),
+            "{}",
+            error
+        );
+    }
+
     #[test]
     fn test_load_sample_request() {
         let processor = HotelSearchProcessor::new();
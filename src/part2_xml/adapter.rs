@@ -0,0 +1,204 @@
+// Supplier adapters
+// Every supplier we integrate sends availability in its own JSON schema. An adapter parses one
+// schema into the common `SupplierResponse`, from which the AvailRS XML is built the same way
+// for everyone. Adapters are registered on `HotelSearchProcessor` by supplier ID, see
+// `convert_supplier_json_to_xml`; onboarding a supplier means adding an adapter, not touching
+// the conversion.
+
+use super::ProcessingError;
+use crate::supplier::{
+    RoomCapacity, SupplierCancellationPolicy, SupplierHotel, SupplierRate, SupplierResponse,
+    SupplierRoom,
+};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+pub trait SupplierAdapter: Send + Sync {
+    fn parse(&self, raw: &str) -> Result<SupplierResponse, ProcessingError>;
+}
+
+fn json_error(error: serde_json::Error) -> ProcessingError {
+    ProcessingError::JsonParseError(error.to_string())
+}
+
+// Our own schema, which `SupplierResponse` mirrors (see samples/supplier_response.json)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NativeAdapter;
+
+impl SupplierAdapter for NativeAdapter {
+    fn parse(&self, raw: &str) -> Result<SupplierResponse, ProcessingError> {
+        serde_json::from_str(raw).map_err(json_error)
+    }
+}
+
+// Suppliers sending one flat, camelCase offer per hotel/room/rate:
+// {"searchId", "currency", "timestamp", "offers": [{"hotelCode", "hotelName", "stars",
+//  "destination", "roomCode", "roomName", "adults", "children", "board", "rateKey",
+//  "bookingCode", "amount", "cancellation": [{"from", "amount"}]}]}
+// Offers are grouped into hotels and rooms in the order they first appear.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FlatOffersAdapter;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FlatResponse {
+    search_id: String,
+    currency: String,
+    #[serde(default)]
+    timestamp: String,
+    offers: Vec<FlatOffer>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FlatOffer {
+    hotel_code: String,
+    hotel_name: String,
+    #[serde(default)]
+    stars: i32,
+    #[serde(default)]
+    destination: String,
+    room_code: String,
+    room_name: String,
+    adults: i32,
+    #[serde(default)]
+    children: i32,
+    board: String,
+    rate_key: String,
+    #[serde(default)]
+    booking_code: String,
+    #[serde(with = "rust_decimal::serde::float")]
+    amount: Decimal,
+    #[serde(default)]
+    cancellation: Vec<FlatCancellation>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FlatCancellation {
+    from: String,
+    #[serde(with = "rust_decimal::serde::float")]
+    amount: Decimal,
+}
+
+impl SupplierAdapter for FlatOffersAdapter {
+    fn parse(&self, raw: &str) -> Result<SupplierResponse, ProcessingError> {
+        let flat: FlatResponse = serde_json::from_str(raw).map_err(json_error)?;
+
+        let mut hotels: Vec<SupplierHotel> = Vec::new();
+        let mut hotel_index: HashMap<String, usize> = HashMap::new();
+        for offer in flat.offers {
+            let index = *hotel_index
+                .entry(offer.hotel_code.clone())
+                .or_insert_with(|| {
+                    hotels.push(SupplierHotel {
+                        hotel_id: offer.hotel_code.clone(),
+                        name: offer.hotel_name.clone(),
+                        category: offer.stars,
+                        rooms: Vec::new(),
+                        destination_code: offer.destination.clone(),
+                    });
+                    hotels.len() - 1
+                });
+            let rooms = &mut hotels[index].rooms;
+            let room = match rooms
+                .iter()
+                .position(|room| room.room_id == offer.room_code)
+            {
+                Some(position) => &mut rooms[position],
+                None => {
+                    rooms.push(SupplierRoom {
+                        room_id: offer.room_code,
+                        name: offer.room_name,
+                        rates: Vec::new(),
+                        capacity: RoomCapacity {
+                            adults: offer.adults,
+                            children: offer.children,
+                        },
+                    });
+                    rooms.last_mut().expect("room was just pushed")
+                }
+            };
+            room.rates.push(SupplierRate {
+                rate_id: offer.rate_key,
+                board_type: offer.board,
+                price: offer.amount,
+                cancellation_policies: offer
+                    .cancellation
+                    .into_iter()
+                    .map(|cp| SupplierCancellationPolicy {
+                        from_date: cp.from,
+                        amount: cp.amount,
+                    })
+                    .collect(),
+                booking_code: offer.booking_code,
+                taxes: Vec::new(),
+                promotions: Vec::new(),
+            });
+        }
+
+        Ok(SupplierResponse {
+            hotels,
+            search_id: flat.search_id,
+            currency: flat.currency,
+            timestamp: flat.timestamp,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FLAT_JSON: &str = r#"{
+        "searchId": "S1",
+        "currency": "EUR",
+        "offers": [
+            {"hotelCode": "H1", "hotelName": "Hotel One", "stars": 4, "roomCode": "DBL",
+             "roomName": "Double", "adults": 2, "board": "BB", "rateKey": "R1", "amount": 120.5,
+             "cancellation": [{"from": "2025-06-01T00:00:00Z", "amount": 60}]},
+            {"hotelCode": "H2", "hotelName": "Hotel Two", "roomCode": "SGL",
+             "roomName": "Single", "adults": 1, "board": "RO", "rateKey": "R2", "amount": 80},
+            {"hotelCode": "H1", "hotelName": "Hotel One", "stars": 4, "roomCode": "DBL",
+             "roomName": "Double", "adults": 2, "board": "HB", "rateKey": "R3", "amount": 150.25}
+        ]
+    }"#;
+
+    #[test]
+    fn test_flat_offers_grouped_by_hotel_and_room() {
+        let response = FlatOffersAdapter.parse(FLAT_JSON).unwrap();
+        assert_eq!(
+            (response.search_id.as_str(), response.currency.as_str()),
+            ("S1", "EUR")
+        );
+
+        let ids: Vec<&str> = response
+            .hotels
+            .iter()
+            .map(|h| h.hotel_id.as_str())
+            .collect();
+        assert_eq!(ids, vec!["H1", "H2"]);
+        let room = &response.hotels[0].rooms[0];
+        assert_eq!(response.hotels[0].rooms.len(), 1);
+        assert_eq!(room.capacity.adults, 2);
+        let rates: Vec<&str> = room.rates.iter().map(|r| r.rate_id.as_str()).collect();
+        assert_eq!(rates, vec!["R1", "R3"]);
+        assert_eq!(room.rates[1].price, Decimal::new(15025, 2));
+        assert_eq!(
+            room.rates[0].cancellation_policies[0].amount,
+            Decimal::from(60)
+        );
+    }
+
+    #[test]
+    fn test_adapters_reject_other_schemas() {
+        assert!(matches!(
+            NativeAdapter.parse(FLAT_JSON),
+            Err(ProcessingError::JsonParseError(_))
+        ));
+        assert!(matches!(
+            FlatOffersAdapter.parse(r#"{"hotels": []}"#),
+            Err(ProcessingError::JsonParseError(_))
+        ));
+    }
+}
@@ -0,0 +1,341 @@
+// BookRQ/BookRS booking messages
+// The booking leg after a search: a BookRQ confirms one option (identified by the booking code,
+// the option's search token) for a holder and the guests of each room, the BookRS returns the
+// supplier's locator and the confirmed price. Like AvailRQ, messages are parsed bare or out of
+// the hub's SOAP envelope.
+
+use super::dates::{self, request_date};
+use super::request::XmlConfiguration;
+use super::stream::find_element;
+use super::{HotelOption, ProcessingError};
+use crate::money::Money;
+use crate::xml_response::XmlParameter;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+// Status of a confirmed booking in BookRS
+pub const BOOKING_CONFIRMED: &str = "OK";
+
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+#[serde(default, rename = "BookRQ", rename_all = "PascalCase")]
+pub struct BookRQ {
+    pub configuration: XmlConfiguration,
+    // Our reference for the booking, echoed in BookRS
+    pub client_locator: String,
+    #[serde(with = "request_date")]
+    pub start_date: NaiveDate,
+    #[serde(with = "request_date")]
+    pub end_date: NaiveDate,
+    pub hotel_code: String,
+    pub meal_plan_code: String,
+    pub payment_type: String,
+    // The option's search token
+    pub booking_code: String,
+    // Price the option was quoted at; suppliers reject the booking if it changed
+    pub price: XmlAmount,
+    pub holder: XmlHolder,
+    pub rooms: XmlBookRooms,
+    pub remarks: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct XmlAmount {
+    #[serde(rename = "@currency")]
+    pub currency: String,
+    #[serde(rename = "@amount")]
+    pub amount: String,
+}
+
+impl XmlAmount {
+    pub fn money(&self) -> Option<Money> {
+        Money::parse(&self.amount, &self.currency)
+    }
+}
+
+impl From<&Money> for XmlAmount {
+    fn from(money: &Money) -> Self {
+        Self {
+            currency: money.currency.clone(),
+            amount: money.xml_amount(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct XmlHolder {
+    #[serde(rename = "@name")]
+    pub name: String,
+    #[serde(rename = "@surname")]
+    pub surname: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct XmlBookRooms {
+    #[serde(rename = "Room")]
+    pub rooms: Vec<XmlBookRoom>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+#[serde(default, rename_all = "PascalCase")]
+pub struct XmlBookRoom {
+    #[serde(rename = "@id")]
+    pub id: u32,
+    #[serde(rename = "@code")]
+    pub code: String,
+    pub paxes: XmlBookPaxes,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct XmlBookPaxes {
+    #[serde(rename = "Pax")]
+    pub paxes: Vec<XmlBookPax>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct XmlBookPax {
+    #[serde(rename = "@id")]
+    pub id: u32,
+    #[serde(rename = "@age")]
+    pub age: u32,
+    #[serde(rename = "@name")]
+    pub name: String,
+    #[serde(rename = "@surname")]
+    pub surname: String,
+}
+
+// Guest details collected at checkout
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Guest {
+    pub name: String,
+    pub surname: String,
+    pub age: u32,
+}
+
+impl Guest {
+    pub fn new(name: impl Into<String>, surname: impl Into<String>, age: u32) -> Self {
+        Self {
+            name: name.into(),
+            surname: surname.into(),
+            age,
+        }
+    }
+}
+
+impl BookRQ {
+    pub fn new(
+        hotel_code: impl Into<String>,
+        booking_code: impl Into<String>,
+        start_date: NaiveDate,
+        end_date: NaiveDate,
+    ) -> Self {
+        Self {
+            hotel_code: hotel_code.into(),
+            booking_code: booking_code.into(),
+            start_date,
+            end_date,
+            ..Default::default()
+        }
+    }
+
+    // Book a confirmed search option for its room's guests; the first guest is the holder
+    pub fn from_option(
+        option: &HotelOption,
+        start_date: NaiveDate,
+        end_date: NaiveDate,
+        guests: &[Guest],
+    ) -> Result<Self, ProcessingError> {
+        let holder = guests
+            .first()
+            .ok_or_else(|| ProcessingError::MissingRequiredField("guests".to_string()))?;
+        Ok(
+            Self::new(&option.hotel_id, &option.search_token, start_date, end_date)
+                .meal_plan(&option.board_type)
+                .payment_type(&option.payment_type)
+                .price(&option.price)
+                .holder(&holder.name, &holder.surname)
+                .room(&option.room_type, guests),
+        )
+    }
+
+    pub fn client_locator(mut self, locator: impl Into<String>) -> Self {
+        self.client_locator = locator.into();
+        self
+    }
+
+    pub fn meal_plan(mut self, code: impl Into<String>) -> Self {
+        self.meal_plan_code = code.into();
+        self
+    }
+
+    pub fn payment_type(mut self, payment_type: impl Into<String>) -> Self {
+        self.payment_type = payment_type.into();
+        self
+    }
+
+    pub fn price(mut self, price: &Money) -> Self {
+        self.price = price.into();
+        self
+    }
+
+    pub fn holder(mut self, name: impl Into<String>, surname: impl Into<String>) -> Self {
+        self.holder = XmlHolder {
+            name: name.into(),
+            surname: surname.into(),
+        };
+        self
+    }
+
+    // One room of type `code` for `guests`; rooms and paxes are numbered from 1
+    pub fn room(mut self, code: impl Into<String>, guests: &[Guest]) -> Self {
+        let rooms = &mut self.rooms.rooms;
+        rooms.push(XmlBookRoom {
+            id: rooms.len() as u32 + 1,
+            code: code.into(),
+            paxes: XmlBookPaxes {
+                paxes: (1..)
+                    .zip(guests)
+                    .map(|(id, guest)| XmlBookPax {
+                        id,
+                        age: guest.age,
+                        name: guest.name.clone(),
+                        surname: guest.surname.clone(),
+                    })
+                    .collect(),
+            },
+        });
+        self
+    }
+
+    pub fn remarks(mut self, remarks: impl Into<String>) -> Self {
+        self.remarks = remarks.into();
+        self
+    }
+
+    pub fn parameter(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.configuration.parameters.parameters.push(XmlParameter {
+            key: key.into(),
+            value: value.into(),
+        });
+        self
+    }
+
+    pub fn from_xml(xml: &str) -> Result<Self, ProcessingError> {
+        let request = find_element(xml.as_bytes(), "BookRQ")?
+            .ok_or_else(|| ProcessingError::MissingRequiredField("BookRQ".to_string()))?;
+        quick_xml::de::from_str(&request).map_err(dates::de_error)
+    }
+
+    pub fn to_xml(&self) -> Result<String, ProcessingError> {
+        quick_xml::se::to_string(self).map_err(|e| ProcessingError::ConversionError(e.to_string()))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+#[serde(default, rename = "BookRS", rename_all = "PascalCase")]
+pub struct BookRS {
+    // The supplier's reference, needed to cancel
+    pub locator: String,
+    pub client_locator: String,
+    // BOOKING_CONFIRMED, or the supplier's status (e.g. "KO", "ON_REQUEST")
+    pub status: String,
+    pub hotel_code: String,
+    pub price: XmlAmount,
+    pub holder: XmlHolder,
+    pub remarks: String,
+}
+
+impl BookRS {
+    pub fn from_xml(xml: &str) -> Result<Self, ProcessingError> {
+        let response = find_element(xml.as_bytes(), "BookRS")?
+            .ok_or_else(|| ProcessingError::MissingRequiredField("BookRS".to_string()))?;
+        quick_xml::de::from_str(&response)
+            .map_err(|e| ProcessingError::XmlParseError(e.to_string()))
+    }
+
+    pub fn to_xml(&self) -> Result<String, ProcessingError> {
+        quick_xml::se::to_string(self).map_err(|e| ProcessingError::ConversionError(e.to_string()))
+    }
+
+    pub fn is_confirmed(&self) -> bool {
+        self.status == BOOKING_CONFIRMED && !self.locator.is_empty()
+    }
+
+    // The price the booking was confirmed at, None if it's missing or malformed
+    pub fn price(&self) -> Option<Money> {
+        self.price.money()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::part2_xml::{HotelSearchProcessor, SMALL_SAMPLE_XML};
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, 6, day).unwrap()
+    }
+
+    #[test]
+    fn test_book_request_from_option() {
+        let response = HotelSearchProcessor::new()
+            .process(SMALL_SAMPLE_XML)
+            .unwrap();
+        let option = &response.hotels[0];
+        let guests = [
+            Guest::new("Ada", "Smith", 30),
+            Guest::new("Bob", "Smith", 32),
+        ];
+
+        let request = BookRQ::from_option(option, date(11), date(12), &guests)
+            .unwrap()
+            .client_locator("B2B-1");
+        assert_eq!(request.booking_code, option.search_token);
+        assert_eq!(request.holder.name, "Ada");
+        assert_eq!(request.rooms.rooms[0].code, "ND1");
+        assert_eq!(request.rooms.rooms[0].paxes.paxes[1].id, 2);
+
+        let xml = request.to_xml().unwrap();
+        assert!(xml.contains("<Price currency=\"GBP\" amount=\"84.82\"/>"));
+        assert!(xml.contains("<StartDate>11/06/2025</StartDate>"));
+        assert_eq!(BookRQ::from_xml(&xml).unwrap(), request);
+
+        assert!(matches!(
+            BookRQ::from_option(option, date(11), date(12), &[]),
+            Err(ProcessingError::MissingRequiredField(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_book_response() {
+        let xml = r#"
+        <soapenv:Envelope xmlns:soapenv="http://schemas.xmlsoap.org/soap/envelope/">
+          <soapenv:Body>
+            <BookRS>
+              <Locator>SUP-778</Locator>
+              <ClientLocator>B2B-1</ClientLocator>
+              <Status>OK</Status>
+              <HotelCode>39776757</HotelCode>
+              <Price currency="GBP" amount="84.82"/>
+              <Holder name="Ada" surname="Smith"/>
+            </BookRS>
+          </soapenv:Body>
+        </soapenv:Envelope>"#;
+        let response = BookRS::from_xml(xml).unwrap();
+        assert!(response.is_confirmed());
+        assert_eq!(response.locator, "SUP-778");
+        assert_eq!(response.price().unwrap().to_string(), "84.82 GBP");
+        assert_eq!(
+            BookRS::from_xml(&response.to_xml().unwrap()).unwrap(),
+            response
+        );
+
+        let rejected = BookRS::from_xml("<BookRS><Status>KO</Status></BookRS>").unwrap();
+        assert!(!rejected.is_confirmed());
+        assert_eq!(rejected.price(), None);
+    }
+}
@@ -0,0 +1,303 @@
+// CancelRQ/CancelRS cancellation messages and penalty calculation
+// A CancelRQ cancels a booking by the supplier's locator, the CancelRS reports the status and
+// the penalty the supplier charged. `cancellation_penalty` quotes that penalty from the
+// booking's cancellation policies, so the middleware can show cancellation costs without
+// calling the supplier.
+// A policy applies once its deadline has passed, or, if it has `hours_before` and the check-in
+// date is known, once check-in is less than that many hours away. Of the applicable policies
+// the most expensive one is charged; "Importe" penalties are amounts, "Porcentaje" penalties a
+// percentage of the booking price. `normalize_policies` resolves both up front, into the moment
+// each policy starts to apply and the amount it charges.
+
+use super::booking::XmlAmount;
+use super::request::XmlConfiguration;
+use super::stream::find_element;
+use super::{HotelOption, ProcessedCancellationPolicy, ProcessingError};
+use crate::money::Money;
+use crate::xml_response::XmlParameter;
+use chrono::{DateTime, Duration, NaiveDate, NaiveTime, Utc};
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::{Deserialize, Serialize};
+
+pub const PENALTY_AMOUNT: &str = "Importe";
+pub const PENALTY_PERCENTAGE: &str = "Porcentaje";
+
+// Status of a successful cancellation in CancelRS
+pub const CANCELLATION_CONFIRMED: &str = "CANCELLED";
+
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+#[serde(default, rename = "CancelRQ", rename_all = "PascalCase")]
+pub struct CancelRQ {
+    pub configuration: XmlConfiguration,
+    // The supplier's locator from BookRS
+    pub locator: String,
+    pub client_locator: String,
+    pub hotel_code: String,
+}
+
+impl CancelRQ {
+    pub fn new(locator: impl Into<String>) -> Self {
+        Self {
+            locator: locator.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn client_locator(mut self, locator: impl Into<String>) -> Self {
+        self.client_locator = locator.into();
+        self
+    }
+
+    pub fn hotel(mut self, code: impl Into<String>) -> Self {
+        self.hotel_code = code.into();
+        self
+    }
+
+    pub fn parameter(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.configuration.parameters.parameters.push(XmlParameter {
+            key: key.into(),
+            value: value.into(),
+        });
+        self
+    }
+
+    pub fn from_xml(xml: &str) -> Result<Self, ProcessingError> {
+        let request = find_element(xml.as_bytes(), "CancelRQ")?
+            .ok_or_else(|| ProcessingError::MissingRequiredField("CancelRQ".to_string()))?;
+        quick_xml::de::from_str(&request).map_err(|e| ProcessingError::XmlParseError(e.to_string()))
+    }
+
+    pub fn to_xml(&self) -> Result<String, ProcessingError> {
+        quick_xml::se::to_string(self).map_err(|e| ProcessingError::ConversionError(e.to_string()))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+#[serde(default, rename = "CancelRS", rename_all = "PascalCase")]
+pub struct CancelRS {
+    pub locator: String,
+    pub client_locator: String,
+    // CANCELLATION_CONFIRMED, or the supplier's status
+    pub status: String,
+    // What the supplier charged for the cancellation
+    pub penalty: XmlAmount,
+}
+
+impl CancelRS {
+    pub fn from_xml(xml: &str) -> Result<Self, ProcessingError> {
+        let response = find_element(xml.as_bytes(), "CancelRS")?
+            .ok_or_else(|| ProcessingError::MissingRequiredField("CancelRS".to_string()))?;
+        quick_xml::de::from_str(&response)
+            .map_err(|e| ProcessingError::XmlParseError(e.to_string()))
+    }
+
+    pub fn to_xml(&self) -> Result<String, ProcessingError> {
+        quick_xml::se::to_string(self).map_err(|e| ProcessingError::ConversionError(e.to_string()))
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.status == CANCELLATION_CONFIRMED
+    }
+
+    pub fn penalty(&self) -> Option<Money> {
+        self.penalty.money()
+    }
+}
+
+// A cancellation policy resolved against a booking: charges `penalty` from `applies_from` on
+#[derive(Debug, Clone, PartialEq)]
+pub struct NormalizedPolicy {
+    pub applies_from: DateTime<Utc>,
+    pub penalty: Money,
+}
+
+// The option's policies for a booking of `total_price` checking in on `check_in`, earliest
+// first; without the check-in date `hours_before` is ignored
+pub fn normalize_policies(
+    option: &HotelOption,
+    total_price: &Money,
+    check_in: Option<NaiveDate>,
+) -> Vec<NormalizedPolicy> {
+    let check_in = check_in.map(start_of_day);
+    let mut policies: Vec<NormalizedPolicy> = option
+        .cancellation_policies
+        .iter()
+        .map(|policy| NormalizedPolicy {
+            applies_from: applies_from(policy, check_in),
+            penalty: penalty(policy, total_price),
+        })
+        .collect();
+    policies.sort_by_key(|policy| policy.applies_from);
+    policies
+}
+
+// When the first policy charging anything starts to apply; None if none ever does
+pub fn free_cancellation_until(policies: &[NormalizedPolicy]) -> Option<DateTime<Utc>> {
+    policies
+        .iter()
+        .filter(|policy| policy.penalty.amount > Decimal::ZERO)
+        .map(|policy| policy.applies_from)
+        .min()
+}
+
+// The penalty for cancelling a booking of `price` at `cancel_at`; zero in the booking's
+// currency if no policy applies yet
+pub fn cancellation_penalty(
+    policies: &[ProcessedCancellationPolicy],
+    price: &Money,
+    check_in: Option<NaiveDate>,
+    cancel_at: DateTime<Utc>,
+) -> Money {
+    let check_in = check_in.map(start_of_day);
+    policies
+        .iter()
+        .filter(|policy| cancel_at >= applies_from(policy, check_in))
+        .map(|policy| penalty(policy, price))
+        .max_by(|a, b| a.amount.cmp(&b.amount))
+        .unwrap_or_else(|| Money::new(Decimal::ZERO, &price.currency))
+}
+
+fn start_of_day(date: NaiveDate) -> DateTime<Utc> {
+    date.and_time(NaiveTime::MIN).and_utc()
+}
+
+// The deadline, or `hours_before` check-in if that's earlier
+fn applies_from(
+    policy: &ProcessedCancellationPolicy,
+    check_in: Option<DateTime<Utc>>,
+) -> DateTime<Utc> {
+    match check_in {
+        Some(check_in) if policy.hours_before > 0 => policy
+            .deadline
+            .min(check_in - Duration::hours(policy.hours_before.into())),
+        _ => policy.deadline,
+    }
+}
+
+fn penalty(policy: &ProcessedCancellationPolicy, price: &Money) -> Money {
+    if policy.penalty_type == PENALTY_PERCENTAGE {
+        let amount = price.amount * policy.penalty_amount / Decimal::ONE_HUNDRED;
+        Money::new(
+            amount.round_dp_with_strategy(2, RoundingStrategy::MidpointAwayFromZero),
+            &price.currency,
+        )
+    } else {
+        let currency = if policy.currency.is_empty() {
+            &price.currency
+        } else {
+            &policy.currency
+        };
+        Money::new(policy.penalty_amount, currency)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(day: u32, hour: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 6, day, hour, 0, 0).unwrap()
+    }
+
+    fn policy(
+        deadline: DateTime<Utc>,
+        hours_before: i32,
+        amount: i64,
+        kind: &str,
+    ) -> ProcessedCancellationPolicy {
+        ProcessedCancellationPolicy {
+            deadline,
+            penalty_amount: Decimal::from(amount),
+            currency: if kind == PENALTY_AMOUNT {
+                "EUR".to_string()
+            } else {
+                String::new()
+            },
+            hours_before,
+            penalty_type: kind.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_penalty_by_deadline() {
+        let price = Money::new(Decimal::from(200), "EUR");
+        let policies = [
+            policy(at(1, 0), 0, 50, PENALTY_AMOUNT),
+            policy(at(8, 0), 0, 100, PENALTY_PERCENTAGE),
+        ];
+        let quote = |cancel_at| cancellation_penalty(&policies, &price, None, cancel_at).amount;
+
+        assert_eq!(quote(at(1, 0) - Duration::seconds(1)), Decimal::ZERO);
+        assert_eq!(quote(at(1, 0)), Decimal::from(50));
+        assert_eq!(quote(at(9, 12)), Decimal::from(200));
+    }
+
+    #[test]
+    fn test_penalty_by_hours_before_check_in() {
+        let price = Money::new(Decimal::new(8482, 2), "GBP");
+        let check_in = NaiveDate::from_ymd_opt(2025, 6, 11);
+        // 50% from 48 hours before check-in, deadline far after
+        let policies = [policy(at(30, 0), 48, 50, PENALTY_PERCENTAGE)];
+
+        let quote = |cancel_at| cancellation_penalty(&policies, &price, check_in, cancel_at);
+        assert_eq!(quote(at(8, 23)).amount, Decimal::ZERO);
+        assert_eq!(quote(at(9, 0)), Money::new(Decimal::new(4241, 2), "GBP"));
+        // Without the check-in date only the deadline counts
+        assert_eq!(
+            cancellation_penalty(&policies, &price, None, at(9, 0)).amount,
+            Decimal::ZERO
+        );
+    }
+
+    #[test]
+    fn test_normalize_policies() {
+        let option = HotelOption {
+            price: Money::new(Decimal::from(100), "EUR"),
+            cancellation_policies: vec![
+                policy(at(9, 0), 0, 100, PENALTY_PERCENTAGE),
+                policy(at(30, 0), 72, 25, PENALTY_AMOUNT),
+                policy(at(1, 0), 0, 0, PENALTY_AMOUNT),
+            ],
+            ..Default::default()
+        };
+        let total = Money::new(Decimal::from(300), "EUR");
+        let policies = normalize_policies(&option, &total, NaiveDate::from_ymd_opt(2025, 6, 11));
+
+        let resolved: Vec<(DateTime<Utc>, Decimal)> = policies
+            .iter()
+            .map(|policy| (policy.applies_from, policy.penalty.amount))
+            .collect();
+        assert_eq!(
+            resolved,
+            vec![
+                (at(1, 0), Decimal::ZERO),
+                (at(8, 0), Decimal::from(25)),
+                (at(9, 0), Decimal::from(300)),
+            ]
+        );
+        assert_eq!(free_cancellation_until(&policies), Some(at(8, 0)));
+        // Without the check-in date the 72 hours can't be resolved
+        let policies = normalize_policies(&option, &total, None);
+        assert_eq!(free_cancellation_until(&policies), Some(at(9, 0)));
+        assert_eq!(free_cancellation_until(&policies[..1]), None);
+    }
+
+    #[test]
+    fn test_cancel_messages() {
+        let request = CancelRQ::new("SUP-778")
+            .client_locator("B2B-1")
+            .hotel("39776757");
+        let xml = request.to_xml().unwrap();
+        assert!(xml.contains("<Locator>SUP-778</Locator>"));
+        assert_eq!(CancelRQ::from_xml(&xml).unwrap(), request);
+
+        let response = CancelRS::from_xml(
+            r#"<CancelRS><Locator>SUP-778</Locator><Status>CANCELLED</Status>
+               <Penalty currency="GBP" amount="42.41"/></CancelRS>"#,
+        )
+        .unwrap();
+        assert!(response.is_cancelled());
+        assert_eq!(response.penalty().unwrap().amount, Decimal::new(4241, 2));
+    }
+}
@@ -0,0 +1,192 @@
+// Multi-room option assembly
+// A request for several rooms (several RoomCandidates, or a candidate with cantidade > 1) is
+// answered with options holding one <Room> per requested room, each referring to its
+// candidate, priced at the sum of the rooms. The supplier prices rooms one by one, so the
+// conversion combines them: every requested room gets a room of the same board type that can
+// host its guests, of the same or a different room type. Rooms are tried cheapest first and
+// identical requested rooms are filled in order, so no combination is repeated in another
+// order. The number of combinations grows quickly with the rooms requested; `CombinationLimits`
+// caps what is generated.
+
+use super::occupancy::{Occupancy, RoomOccupancy};
+use crate::supplier::{SupplierRate, SupplierRoom};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CombinationLimits {
+    // Options per hotel and board type
+    pub per_board_type: usize,
+    pub per_hotel: usize,
+}
+
+impl Default for CombinationLimits {
+    fn default() -> Self {
+        Self {
+            per_board_type: 20,
+            per_hotel: 100,
+        }
+    }
+}
+
+// A supplier room and rate filling a requested room, with the candidate's id
+pub(crate) type RoomPick<'a> = (u32, &'a SupplierRoom, &'a SupplierRate);
+
+// One entry per room requested, candidates repeated by their quantity
+pub(crate) fn requested_rooms(occupancy: &Occupancy) -> Vec<&RoomOccupancy> {
+    occupancy
+        .rooms
+        .iter()
+        .flat_map(|room| std::iter::repeat_n(room, room.quantity as usize))
+        .collect()
+}
+
+// Up to `limit` combinations of `room_rates` (all of one board type) filling `requested`
+pub(crate) fn combine_rooms<'a>(
+    requested: &[&RoomOccupancy],
+    room_rates: &[(&'a SupplierRoom, &'a SupplierRate)],
+    limit: usize,
+) -> Vec<Vec<RoomPick<'a>>> {
+    // Per requested room, the room rates that can host it, cheapest first
+    let fitting: Vec<Vec<usize>> = requested
+        .iter()
+        .map(|occupancy| {
+            let mut fitting: Vec<usize> = (0..room_rates.len())
+                .filter(|&i| occupancy.fits(&room_rates[i].0.capacity))
+                .collect();
+            fitting.sort_by_key(|&i| room_rates[i].1.price);
+            fitting
+        })
+        .collect();
+
+    let mut picks = Vec::new();
+    if !requested.is_empty() {
+        pick(requested, &fitting, &mut Vec::new(), limit, &mut picks);
+    }
+    picks
+        .into_iter()
+        .map(|positions| {
+            positions
+                .iter()
+                .enumerate()
+                .map(|(slot, &position)| {
+                    let (room, rate) = room_rates[fitting[slot][position]];
+                    (requested[slot].candidate_id, room, rate)
+                })
+                .collect()
+        })
+        .collect()
+}
+
+// Extend `picked` (positions in `fitting`, one per requested room so far) in every way
+fn pick(
+    requested: &[&RoomOccupancy],
+    fitting: &[Vec<usize>],
+    picked: &mut Vec<usize>,
+    limit: usize,
+    combinations: &mut Vec<Vec<usize>>,
+) {
+    let slot = picked.len();
+    if slot == requested.len() {
+        combinations.push(picked.clone());
+        return;
+    }
+    // Rooms of the same candidate have the same fitting rooms, taken in order
+    let first = match slot {
+        0 => 0,
+        _ if requested[slot].candidate_id == requested[slot - 1].candidate_id => picked[slot - 1],
+        _ => 0,
+    };
+    for position in first..fitting[slot].len() {
+        if combinations.len() >= limit {
+            return;
+        }
+        picked.push(position);
+        pick(requested, fitting, picked, limit, combinations);
+        picked.pop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::supplier::RoomCapacity;
+    use rust_decimal::Decimal;
+
+    fn room(id: &str, adults: i32, children: i32, price: i64) -> SupplierRoom {
+        SupplierRoom {
+            room_id: id.to_string(),
+            name: String::new(),
+            rates: vec![SupplierRate {
+                rate_id: format!("R-{}", id),
+                board_type: "BB".to_string(),
+                price: Decimal::from(price),
+                cancellation_policies: vec![],
+                booking_code: String::new(),
+                taxes: vec![],
+                promotions: vec![],
+            }],
+            capacity: RoomCapacity { adults, children },
+        }
+    }
+
+    fn occupancy(id: u32, quantity: u32, adults: i32, child_ages: Vec<u32>) -> RoomOccupancy {
+        RoomOccupancy {
+            candidate_id: id,
+            quantity,
+            adults,
+            child_ages,
+        }
+    }
+
+    fn describe(combinations: &[Vec<RoomPick>]) -> Vec<String> {
+        combinations
+            .iter()
+            .map(|rooms| {
+                rooms
+                    .iter()
+                    .map(|(candidate, room, _)| format!("{}:{}", candidate, room.room_id))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_combine_rooms() {
+        let rooms = [
+            room("DBL", 2, 0, 100),
+            room("FAM", 2, 2, 150),
+            room("SGL", 1, 0, 60),
+        ];
+        let room_rates: Vec<(&SupplierRoom, &SupplierRate)> =
+            rooms.iter().map(|room| (room, &room.rates[0])).collect();
+
+        let couple = occupancy(1, 1, 2, vec![]);
+        let family = occupancy(2, 1, 2, vec![6]);
+        let combinations = combine_rooms(&[&couple, &family], &room_rates, 10);
+        assert_eq!(describe(&combinations), vec!["1:DBL 2:FAM", "1:FAM 2:FAM"]);
+
+        // Two identical rooms aren't repeated the other way round
+        let singles = occupancy(1, 2, 1, vec![]);
+        let occupancy_of_two = Occupancy {
+            rooms: vec![singles],
+        };
+        let requested = requested_rooms(&occupancy_of_two);
+        let combinations = combine_rooms(&requested, &room_rates, 10);
+        assert_eq!(
+            describe(&combinations),
+            vec![
+                "1:SGL 1:SGL",
+                "1:SGL 1:DBL",
+                "1:SGL 1:FAM",
+                "1:DBL 1:DBL",
+                "1:DBL 1:FAM",
+                "1:FAM 1:FAM"
+            ]
+        );
+        assert_eq!(combine_rooms(&requested, &room_rates, 2).len(), 2);
+
+        // Nobody fits a room for five
+        let crowd = occupancy(1, 1, 5, vec![]);
+        assert!(combine_rooms(&[&crowd], &room_rates, 10).is_empty());
+    }
+}
@@ -0,0 +1,190 @@
+// Hotel content enrichment
+// Supplier payloads describe hotels poorly: names come empty, as the supplier's code, or
+// garbled by a wrong encoding, and address, location and pictures are missing. A
+// `HotelContentProvider` looks hotels up by ID in our own content (a static catalogue, a content
+// API). Enrichment replaces names that look broken, fills in what the supplier left out and
+// never overwrites anything else the supplier sent. Options record whether the provider
+// contributed in `content_source`. The provider is asked once per hotel, not per option.
+
+use super::HotelOption;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize, Serialize)]
+pub struct GeoPoint {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct HotelContent {
+    pub name: String,
+    pub stars: Option<i32>,
+    pub address: Option<String>,
+    pub geo: Option<GeoPoint>,
+    pub images: Vec<String>,
+}
+
+pub trait HotelContentProvider: Send + Sync {
+    fn content(&self, hotel_id: &str) -> Option<HotelContent>;
+}
+
+// Where an option's hotel details come from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ContentSource {
+    #[default]
+    Supplier,
+    // At least one detail was taken from the content provider
+    Provider,
+}
+
+// Content held in memory, e.g. loaded from a catalogue at startup
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryContent {
+    hotels: HashMap<String, HotelContent>,
+}
+
+impl InMemoryContent {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_hotel(mut self, hotel_id: impl Into<String>, content: HotelContent) -> Self {
+        self.hotels.insert(hotel_id.into(), content);
+        self
+    }
+}
+
+impl HotelContentProvider for InMemoryContent {
+    fn content(&self, hotel_id: &str) -> Option<HotelContent> {
+        self.hotels.get(hotel_id).cloned()
+    }
+}
+
+// Empty, just the hotel's code, without a single letter or with characters lost to decoding
+pub fn is_garbled_name(name: &str, hotel_id: &str) -> bool {
+    let name = name.trim();
+    name.is_empty()
+        || name == hotel_id
+        || name.contains('\u{FFFD}')
+        || !name.chars().any(char::is_alphabetic)
+}
+
+// Enrich `options` from `provider`, returns how many options it contributed to
+pub fn enrich_options(options: &mut [HotelOption], provider: &dyn HotelContentProvider) -> usize {
+    let mut contents: HashMap<String, Option<HotelContent>> = HashMap::new();
+    let mut enriched = 0;
+    for option in options {
+        let content = contents
+            .entry(option.hotel_id.clone())
+            .or_insert_with(|| provider.content(&option.hotel_id));
+        if let Some(content) = content {
+            if enrich(option, content) {
+                option.content_source = ContentSource::Provider;
+                enriched += 1;
+            }
+        }
+    }
+    enriched
+}
+
+fn enrich(option: &mut HotelOption, content: &HotelContent) -> bool {
+    let mut changed = false;
+    if is_garbled_name(&option.hotel_name, &option.hotel_id) && !content.name.trim().is_empty() {
+        option.hotel_name = content.name.clone();
+        changed = true;
+    }
+    if option.category.is_none() && content.stars.is_some() {
+        option.category = content.stars;
+        changed = true;
+    }
+    if option.address.is_none() && content.address.is_some() {
+        option.address = content.address.clone();
+        changed = true;
+    }
+    if option.geo.is_none() && content.geo.is_some() {
+        option.geo = content.geo;
+        changed = true;
+    }
+    if option.images.is_empty() && !content.images.is_empty() {
+        option.images = content.images.clone();
+        changed = true;
+    }
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn option(hotel_id: &str, name: &str) -> HotelOption {
+        HotelOption {
+            hotel_id: hotel_id.to_string(),
+            hotel_name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_garbled_names() {
+        assert!(is_garbled_name("  ", "H1"));
+        assert!(is_garbled_name("H1", "H1"));
+        assert!(is_garbled_name("Hotel Espa\u{FFFD}a", "H1"));
+        assert!(is_garbled_name("#12-3", "H1"));
+        assert!(!is_garbled_name("Hôtel de la Poste", "H1"));
+    }
+
+    #[test]
+    fn test_enrich_options() {
+        let provider = InMemoryContent::new().with_hotel(
+            "H1",
+            HotelContent {
+                name: "Hotel España".to_string(),
+                stars: Some(4),
+                address: Some("Gran Vía 1, Madrid".to_string()),
+                geo: Some(GeoPoint {
+                    latitude: 40.42,
+                    longitude: -3.70,
+                }),
+                images: vec!["https://img.example.com/h1.jpg".to_string()],
+            },
+        );
+        let mut options = vec![
+            option("H1", "Hotel Espa\u{FFFD}a"),
+            HotelOption {
+                category: Some(3),
+                ..option("H1", "Hotel España Centro")
+            },
+            option("H2", ""),
+        ];
+
+        assert_eq!(enrich_options(&mut options, &provider), 2);
+        assert_eq!(options[0].hotel_name, "Hotel España");
+        assert_eq!(options[0].category, Some(4));
+        assert_eq!(options[0].content_source, ContentSource::Provider);
+        // What the supplier sent is kept
+        assert_eq!(options[1].hotel_name, "Hotel España Centro");
+        assert_eq!(options[1].category, Some(3));
+        assert_eq!(options[1].address.as_deref(), Some("Gran Vía 1, Madrid"));
+        // Unknown hotels are left alone
+        assert_eq!(options[2].hotel_name, "");
+        assert_eq!(options[2].content_source, ContentSource::Supplier);
+    }
+
+    #[test]
+    fn test_provider_asked_once_per_hotel() {
+        struct Counting(AtomicUsize);
+        impl HotelContentProvider for Counting {
+            fn content(&self, _hotel_id: &str) -> Option<HotelContent> {
+                self.0.fetch_add(1, Ordering::SeqCst);
+                None
+            }
+        }
+        let provider = Counting(AtomicUsize::new(0));
+        let mut options = vec![option("H1", ""), option("H1", ""), option("H2", "")];
+        assert_eq!(enrich_options(&mut options, &provider), 0);
+        assert_eq!(provider.0.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,119 @@
+// Dates and times
+// Suppliers and the hub don't agree on formats: requests carry dd/mm/yyyy dates, responses ISO
+// dates and RFC 3339 deadlines, sometimes without a zone. Everything is parsed into chrono types
+// here, accepting any of these; unparseable values are `ProcessingError::InvalidDate`.
+// The serde adapters are used with `#[serde(with = "...")]` on the XML models.
+
+use super::ProcessingError;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+
+// Date format of AvailRQ requests
+pub const REQUEST_DATE_FORMAT: &str = "%d/%m/%Y";
+pub const ISO_DATE_FORMAT: &str = "%Y-%m-%d";
+const DATE_FORMATS: [&str; 2] = [ISO_DATE_FORMAT, REQUEST_DATE_FORMAT];
+
+// Accepts yyyy-mm-dd and dd/mm/yyyy
+pub fn parse_date(value: &str) -> Result<NaiveDate, ProcessingError> {
+    let value = value.trim();
+    DATE_FORMATS
+        .iter()
+        .find_map(|format| NaiveDate::parse_from_str(value, format).ok())
+        .ok_or_else(|| ProcessingError::InvalidDate(value.to_string()))
+}
+
+// Accepts RFC 3339, date-times without a zone (taken as UTC) and plain dates (midnight UTC)
+pub fn parse_datetime(value: &str) -> Result<DateTime<Utc>, ProcessingError> {
+    let value = value.trim();
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(value) {
+        return Ok(datetime.with_timezone(&Utc));
+    }
+    if let Ok(datetime) = NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S") {
+        return Ok(datetime.and_utc());
+    }
+    parse_date(value).map(|date| date.and_time(NaiveTime::MIN).and_utc())
+}
+
+// Deserialization errors raised by the adapters are reported as `InvalidDate`
+pub(crate) fn de_error(error: quick_xml::DeError) -> ProcessingError {
+    match &error {
+        quick_xml::DeError::Custom(message) => match message.strip_prefix(INVALID_DATE_PREFIX) {
+            Some(value) => ProcessingError::InvalidDate(value.to_string()),
+            None => ProcessingError::XmlParseError(error.to_string()),
+        },
+        _ => ProcessingError::XmlParseError(error.to_string()),
+    }
+}
+
+const INVALID_DATE_PREFIX: &str = "invalid date: ";
+
+fn invalid<E: serde::de::Error>(value: &str) -> E {
+    E::custom(format!("{}{}", INVALID_DATE_PREFIX, value))
+}
+
+// dd/mm/yyyy on the wire, either format accepted
+pub mod request_date {
+    use super::*;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(date: &NaiveDate, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&date.format(REQUEST_DATE_FORMAT))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<NaiveDate, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        parse_date(&value).map_err(|_| invalid(&value))
+    }
+}
+
+// RFC 3339 in UTC on the wire, see `parse_datetime` for what is accepted
+pub mod utc_datetime {
+    use super::*;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        datetime: &DateTime<Utc>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&datetime.format("%Y-%m-%dT%H:%M:%SZ"))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<DateTime<Utc>, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        parse_datetime(&value).map_err(|_| invalid(&value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_both_date_formats() {
+        let date = NaiveDate::from_ymd_opt(2025, 6, 11).unwrap();
+        assert_eq!(parse_date("2025-06-11").unwrap(), date);
+        assert_eq!(parse_date("11/06/2025").unwrap(), date);
+        assert!(matches!(
+            parse_date("06-11-2025"),
+            Err(ProcessingError::InvalidDate(value)) if value == "06-11-2025"
+        ));
+    }
+
+    #[test]
+    fn test_datetimes() {
+        let deadline = Utc.with_ymd_and_hms(2025, 6, 10, 10, 0, 0).unwrap();
+        assert_eq!(parse_datetime("2025-06-10T10:00:00Z").unwrap(), deadline);
+        assert_eq!(
+            parse_datetime("2025-06-10T12:00:00+02:00").unwrap(),
+            deadline
+        );
+        assert_eq!(parse_datetime("2025-06-10T10:00:00").unwrap(), deadline);
+        assert_eq!(
+            parse_datetime("10/06/2025").unwrap(),
+            Utc.with_ymd_and_hms(2025, 6, 10, 0, 0, 0).unwrap()
+        );
+        assert!(parse_datetime("N/A").is_err());
+    }
+}
@@ -0,0 +1,138 @@
+// Duplicate option removal
+// Suppliers often return the same room and board several times, from different rate plans or
+// contracts, at the same or at different prices. `Exact` drops options repeating the hotel,
+// room, board and price of an earlier one; `CheapestPerRoomBoard` keeps only the cheapest
+// option of each hotel, room and board. Either way the options kept stay in the order their
+// group first appeared.
+
+use super::{HotelOption, ProcessedResponse};
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DedupeStrategy {
+    Exact,
+    CheapestPerRoomBoard,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DedupeReport {
+    pub kept: usize,
+    pub removed: usize,
+}
+
+// Hotel, room, board and, for `Exact`, the price
+type GroupKey<'a> = (&'a str, &'a str, &'a str, Option<(Decimal, &'a str)>);
+
+pub fn dedupe_options(response: &mut ProcessedResponse, strategy: DedupeStrategy) -> DedupeReport {
+    let options = std::mem::take(&mut response.hotels);
+    let total = options.len();
+
+    let mut kept: Vec<HotelOption> = Vec::with_capacity(total);
+    let mut groups: HashMap<GroupKey, usize> = HashMap::new();
+    for option in &options {
+        let price = match strategy {
+            DedupeStrategy::Exact => Some((option.price.amount, option.price.currency.as_str())),
+            DedupeStrategy::CheapestPerRoomBoard => None,
+        };
+        let key = (
+            option.hotel_id.as_str(),
+            option.room_type.as_str(),
+            option.board_type.as_str(),
+            price,
+        );
+        match groups.get(&key) {
+            Some(&index) => {
+                if option.price.amount < kept[index].price.amount {
+                    kept[index] = option.clone();
+                }
+            }
+            None => {
+                groups.insert(key, kept.len());
+                kept.push(option.clone());
+            }
+        }
+    }
+
+    response.total_options = kept.len();
+    response.hotels = kept;
+    DedupeReport {
+        kept: response.total_options,
+        removed: total - response.total_options,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::money::Money;
+
+    fn response() -> ProcessedResponse {
+        let option = |hotel: &str, room: &str, board: &str, price: i64, token: &str| HotelOption {
+            hotel_id: hotel.to_string(),
+            room_type: room.to_string(),
+            board_type: board.to_string(),
+            price: Money::new(Decimal::from(price), "EUR"),
+            search_token: token.to_string(),
+            ..Default::default()
+        };
+        let hotels = vec![
+            option("H1", "DBL", "BB", 120, "a"),
+            option("H1", "DBL", "BB", 120, "b"),
+            option("H1", "DBL", "RO", 90, "c"),
+            option("H1", "DBL", "BB", 100, "d"),
+            option("H2", "DBL", "BB", 120, "e"),
+        ];
+        ProcessedResponse {
+            search_id: String::new(),
+            total_options: hotels.len(),
+            hotels,
+            currency: "EUR".to_string(),
+            nationality: String::new(),
+            check_in: None,
+            check_out: None,
+            markets: Vec::new(),
+        }
+    }
+
+    fn tokens(response: &ProcessedResponse) -> Vec<&str> {
+        response
+            .hotels
+            .iter()
+            .map(|option| option.search_token.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_exact_duplicates() {
+        let mut response = response();
+        let report = dedupe_options(&mut response, DedupeStrategy::Exact);
+        assert_eq!(
+            report,
+            DedupeReport {
+                kept: 4,
+                removed: 1
+            }
+        );
+        assert_eq!(tokens(&response), vec!["a", "c", "d", "e"]);
+        assert_eq!(response.total_options, 4);
+    }
+
+    #[test]
+    fn test_cheapest_per_room_and_board() {
+        let mut response = response();
+        let report = dedupe_options(&mut response, DedupeStrategy::CheapestPerRoomBoard);
+        assert_eq!(
+            report,
+            DedupeReport {
+                kept: 3,
+                removed: 2
+            }
+        );
+        assert_eq!(tokens(&response), vec!["d", "c", "e"]);
+
+        // Deduplicating again finds nothing
+        let report = dedupe_options(&mut response, DedupeStrategy::Exact);
+        assert_eq!(report.removed, 0);
+    }
+}
@@ -0,0 +1,214 @@
+// Response comparison
+// Availability is searched again periodically; comparing the new response with the previous
+// one tells which cached options to invalidate and which price movements to notify. Options
+// are matched by hotel, room and board. A response may hold several options for one of those
+// (different rate plans); the cheapest stands for them all, the one `CheapestPerRoomBoard`
+// dedupe keeps. Added and changed options are listed in the new response's order, removed ones
+// in the old response's.
+
+use super::{HotelOption, Price, ProcessedCancellationPolicy, ProcessedResponse};
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct OptionKey {
+    pub hotel_id: String,
+    pub room_type: String,
+    pub board_type: String,
+}
+
+impl OptionKey {
+    pub fn of(option: &HotelOption) -> Self {
+        Self {
+            hotel_id: option.hotel_id.clone(),
+            room_type: option.room_type.clone(),
+            board_type: option.board_type.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PriceChange {
+    pub key: OptionKey,
+    pub old: Price,
+    pub new: Price,
+}
+
+impl PriceChange {
+    // New minus old, None if the currency changed
+    pub fn difference(&self) -> Option<Decimal> {
+        (self.old.currency == self.new.currency).then(|| self.new.amount - self.old.amount)
+    }
+}
+
+// The refundability or any cancellation policy changed
+#[derive(Debug, Clone, PartialEq)]
+pub struct PolicyChange {
+    pub key: OptionKey,
+    pub old_refundable: bool,
+    pub new_refundable: bool,
+    pub old: Vec<ProcessedCancellationPolicy>,
+    pub new: Vec<ProcessedCancellationPolicy>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ResponseDiff {
+    pub added: Vec<OptionKey>,
+    pub removed: Vec<OptionKey>,
+    pub price_changes: Vec<PriceChange>,
+    pub policy_changes: Vec<PolicyChange>,
+}
+
+impl ResponseDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.price_changes.is_empty()
+            && self.policy_changes.is_empty()
+    }
+}
+
+pub fn diff_responses(old: &ProcessedResponse, new: &ProcessedResponse) -> ResponseDiff {
+    let old_options = cheapest_by_key(&old.hotels);
+    let new_options = cheapest_by_key(&new.hotels);
+    let old_index: HashMap<&OptionKey, &HotelOption> = old_options
+        .iter()
+        .map(|(key, option)| (key, *option))
+        .collect();
+    let new_index: HashMap<&OptionKey, &HotelOption> = new_options
+        .iter()
+        .map(|(key, option)| (key, *option))
+        .collect();
+
+    let mut diff = ResponseDiff::default();
+    for (key, new_option) in &new_options {
+        let Some(old_option) = old_index.get(key) else {
+            diff.added.push(key.clone());
+            continue;
+        };
+        if old_option.price != new_option.price {
+            diff.price_changes.push(PriceChange {
+                key: key.clone(),
+                old: old_option.price.clone(),
+                new: new_option.price.clone(),
+            });
+        }
+        if old_option.is_refundable != new_option.is_refundable
+            || old_option.cancellation_policies != new_option.cancellation_policies
+        {
+            diff.policy_changes.push(PolicyChange {
+                key: key.clone(),
+                old_refundable: old_option.is_refundable,
+                new_refundable: new_option.is_refundable,
+                old: old_option.cancellation_policies.clone(),
+                new: new_option.cancellation_policies.clone(),
+            });
+        }
+    }
+    diff.removed = old_options
+        .iter()
+        .filter(|(key, _)| !new_index.contains_key(key))
+        .map(|(key, _)| key.clone())
+        .collect();
+    diff
+}
+
+// The cheapest option of every key, in the order the keys first appear
+fn cheapest_by_key(options: &[HotelOption]) -> Vec<(OptionKey, &HotelOption)> {
+    let mut cheapest: Vec<(OptionKey, &HotelOption)> = Vec::new();
+    let mut positions: HashMap<OptionKey, usize> = HashMap::new();
+    for option in options {
+        let key = OptionKey::of(option);
+        match positions.get(&key) {
+            Some(&index) => {
+                if option.price.amount < cheapest[index].1.price.amount {
+                    cheapest[index].1 = option;
+                }
+            }
+            None => {
+                positions.insert(key.clone(), cheapest.len());
+                cheapest.push((key, option));
+            }
+        }
+    }
+    cheapest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::money::Money;
+
+    fn option(hotel: &str, room: &str, board: &str, price: i64, refundable: bool) -> HotelOption {
+        HotelOption {
+            hotel_id: hotel.to_string(),
+            room_type: room.to_string(),
+            board_type: board.to_string(),
+            price: Money::new(Decimal::from(price), "EUR"),
+            is_refundable: refundable,
+            ..Default::default()
+        }
+    }
+
+    fn response(hotels: Vec<HotelOption>) -> ProcessedResponse {
+        ProcessedResponse {
+            search_id: String::new(),
+            total_options: hotels.len(),
+            hotels,
+            currency: "EUR".to_string(),
+            nationality: String::new(),
+            check_in: None,
+            check_out: None,
+            markets: Vec::new(),
+        }
+    }
+
+    fn key(hotel: &str, room: &str, board: &str) -> OptionKey {
+        OptionKey {
+            hotel_id: hotel.to_string(),
+            room_type: room.to_string(),
+            board_type: board.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_diff_responses() {
+        let old = response(vec![
+            option("H1", "DBL", "BB", 120, true),
+            option("H1", "DBL", "RO", 90, true),
+            option("H2", "DBL", "BB", 150, true),
+            option("H3", "SGL", "RO", 60, true),
+        ]);
+        let new = response(vec![
+            option("H4", "DBL", "BB", 200, true),
+            // The cheaper of the two stands for the room and board
+            option("H1", "DBL", "BB", 130, true),
+            option("H1", "DBL", "BB", 110, true),
+            option("H1", "DBL", "RO", 90, false),
+            option("H2", "DBL", "BB", 150, true),
+        ]);
+
+        let diff = diff_responses(&old, &new);
+        assert_eq!(diff.added, vec![key("H4", "DBL", "BB")]);
+        assert_eq!(diff.removed, vec![key("H3", "SGL", "RO")]);
+        assert_eq!(diff.price_changes.len(), 1);
+        assert_eq!(diff.price_changes[0].key, key("H1", "DBL", "BB"));
+        assert_eq!(diff.price_changes[0].difference(), Some(Decimal::from(-10)));
+        assert_eq!(diff.policy_changes.len(), 1);
+        assert_eq!(diff.policy_changes[0].key, key("H1", "DBL", "RO"));
+        assert!(!diff.policy_changes[0].new_refundable);
+
+        assert!(diff_responses(&new, &new).is_empty());
+    }
+
+    #[test]
+    fn test_currency_change() {
+        let old = response(vec![option("H1", "DBL", "BB", 100, true)]);
+        let mut new = old.clone();
+        new.hotels[0].price = Money::new(Decimal::from(100), "GBP");
+
+        let diff = diff_responses(&old, &new);
+        assert_eq!(diff.price_changes.len(), 1);
+        assert_eq!(diff.price_changes[0].difference(), None);
+    }
+}
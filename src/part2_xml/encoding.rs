@@ -0,0 +1,229 @@
+// Character encodings and entities of supplier XML
+// Several suppliers send ISO-8859-1 (or Windows-1252) documents, sometimes declared as UTF-8 or
+// not declared at all, and write hotel names with bare ampersands or HTML entities such as
+// &eacute; that XML doesn't define. Either used to fail the whole response. `decode_document`
+// turns the raw bytes into text: a byte order mark decides first, then the encoding of the XML
+// declaration; bytes that aren't valid UTF-8 where UTF-8 is expected (declared or by default)
+// are read as Windows-1252, the superset of Latin-1 such documents are written in.
+// `repair_entities` rewrites HTML entities as character references and escapes ampersands that
+// don't start a reference; documents that need neither are passed through untouched.
+
+use super::ProcessingError;
+use encoding_rs::{Encoding, UTF_8, WINDOWS_1252};
+use std::borrow::Cow;
+
+const DECLARATION_START: &[u8] = b"<?xml";
+
+// Named entities of HTML seen in supplier hotel and room names; XML only knows amp, lt, gt,
+// quot and apos
+const HTML_ENTITIES: &[(&str, char)] = &[
+    ("nbsp", '\u{A0}'),
+    ("copy", '©'),
+    ("reg", '®'),
+    ("trade", '™'),
+    ("euro", '€'),
+    ("middot", '·'),
+    ("ndash", '–'),
+    ("mdash", '—'),
+    ("lsquo", '‘'),
+    ("rsquo", '’'),
+    ("ldquo", '“'),
+    ("rdquo", '”'),
+    ("laquo", '«'),
+    ("raquo", '»'),
+    ("szlig", 'ß'),
+    ("aacute", 'á'),
+    ("Aacute", 'Á'),
+    ("agrave", 'à'),
+    ("Agrave", 'À'),
+    ("acirc", 'â'),
+    ("Acirc", 'Â'),
+    ("auml", 'ä'),
+    ("Auml", 'Ä'),
+    ("atilde", 'ã'),
+    ("Atilde", 'Ã'),
+    ("aring", 'å'),
+    ("Aring", 'Å'),
+    ("aelig", 'æ'),
+    ("AElig", 'Æ'),
+    ("ccedil", 'ç'),
+    ("Ccedil", 'Ç'),
+    ("eacute", 'é'),
+    ("Eacute", 'É'),
+    ("egrave", 'è'),
+    ("Egrave", 'È'),
+    ("ecirc", 'ê'),
+    ("Ecirc", 'Ê'),
+    ("euml", 'ë'),
+    ("Euml", 'Ë'),
+    ("iacute", 'í'),
+    ("Iacute", 'Í'),
+    ("igrave", 'ì'),
+    ("Igrave", 'Ì'),
+    ("icirc", 'î'),
+    ("Icirc", 'Î'),
+    ("iuml", 'ï'),
+    ("Iuml", 'Ï'),
+    ("ntilde", 'ñ'),
+    ("Ntilde", 'Ñ'),
+    ("oacute", 'ó'),
+    ("Oacute", 'Ó'),
+    ("ograve", 'ò'),
+    ("Ograve", 'Ò'),
+    ("ocirc", 'ô'),
+    ("Ocirc", 'Ô'),
+    ("ouml", 'ö'),
+    ("Ouml", 'Ö'),
+    ("otilde", 'õ'),
+    ("Otilde", 'Õ'),
+    ("oslash", 'ø'),
+    ("Oslash", 'Ø'),
+    ("uacute", 'ú'),
+    ("Uacute", 'Ú'),
+    ("ugrave", 'ù'),
+    ("Ugrave", 'Ù'),
+    ("ucirc", 'û'),
+    ("Ucirc", 'Û'),
+    ("uuml", 'ü'),
+    ("Uuml", 'Ü'),
+];
+
+// Longest entity name considered, so a stray '&' doesn't make us scan the whole document
+const MAX_ENTITY_LENGTH: usize = 10;
+
+pub fn decode_document(bytes: &[u8]) -> Result<Cow<'_, str>, ProcessingError> {
+    if let Some((encoding, bom_length)) = Encoding::for_bom(bytes) {
+        return Ok(decode(encoding, &bytes[bom_length..]));
+    }
+    let encoding = match declared_encoding(bytes) {
+        Some(label) => Encoding::for_label(label.as_bytes()).ok_or_else(|| {
+            ProcessingError::InvalidFormat(format!("unsupported encoding {:?}", label))
+        })?,
+        None => UTF_8,
+    };
+    Ok(decode(encoding, bytes))
+}
+
+fn decode<'a>(encoding: &'static Encoding, bytes: &'a [u8]) -> Cow<'a, str> {
+    if encoding == UTF_8 {
+        return match std::str::from_utf8(bytes) {
+            Ok(text) => Cow::Borrowed(text),
+            Err(_) => WINDOWS_1252.decode_without_bom_handling(bytes).0,
+        };
+    }
+    encoding.decode_without_bom_handling(bytes).0
+}
+
+// The encoding attribute of the XML declaration, None without one
+fn declared_encoding(bytes: &[u8]) -> Option<String> {
+    if !bytes.starts_with(DECLARATION_START) {
+        return None;
+    }
+    let end = bytes.windows(2).position(|pair| pair == b"?>")?;
+    // The declaration is ASCII whatever the encoding of the document (UTF-16 has a BOM)
+    let declaration = std::str::from_utf8(&bytes[..end]).ok()?;
+    let value = declaration.split("encoding").nth(1)?.trim_start();
+    let value = value.strip_prefix('=')?.trim_start();
+    let quote = value.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+    let value = &value[1..];
+    Some(value[..value.find(quote)?].to_string())
+}
+
+pub fn repair_entities(text: &str) -> Cow<'_, str> {
+    let mut repaired = String::new();
+    let mut copied = 0;
+    for (at, _) in text.match_indices('&') {
+        let rest = &text[at + 1..];
+        let reference = rest
+            .char_indices()
+            .take(MAX_ENTITY_LENGTH + 2)
+            .find(|(_, c)| *c == ';')
+            .map(|(end, _)| &rest[..end]);
+        let (replacement, replaced_length) = match reference {
+            Some(name) if is_xml_reference(name) => continue,
+            Some(name) => match html_entity(name) {
+                Some(c) => (format!("&#{};", c as u32), name.len() + 2),
+                None => ("&amp;".to_string(), 1),
+            },
+            None => ("&amp;".to_string(), 1),
+        };
+        repaired.push_str(&text[copied..at]);
+        repaired.push_str(&replacement);
+        copied = at + replaced_length;
+    }
+    if copied == 0 {
+        return Cow::Borrowed(text);
+    }
+    repaired.push_str(&text[copied..]);
+    Cow::Owned(repaired)
+}
+
+fn is_xml_reference(name: &str) -> bool {
+    let code = match name.strip_prefix('#') {
+        None => return matches!(name, "amp" | "lt" | "gt" | "quot" | "apos"),
+        Some(code) => code,
+    };
+    let value = match code.strip_prefix('x') {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => code.parse(),
+    };
+    value.ok().and_then(char::from_u32).is_some()
+}
+
+fn html_entity(name: &str) -> Option<char> {
+    HTML_ENTITIES
+        .iter()
+        .find(|(entity, _)| *entity == name)
+        .map(|(_, c)| *c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_document() {
+        let utf8 = "<?xml version=\"1.0\"?><Hotel name=\"Hôtel\"/>";
+        assert!(matches!(
+            decode_document(utf8.as_bytes()).unwrap(),
+            Cow::Borrowed(_)
+        ));
+
+        let latin1 = b"<?xml version='1.0' encoding='ISO-8859-1'?><Hotel name=\"H\xf4tel\"/>";
+        assert!(decode_document(latin1)
+            .unwrap()
+            .ends_with("name=\"Hôtel\"/>"));
+
+        // Latin-1 declared as UTF-8, or undeclared
+        let mislabelled = b"<?xml version=\"1.0\" encoding=\"UTF-8\"?><Hotel name=\"Caf\xe9\"/>";
+        assert!(decode_document(mislabelled).unwrap().contains("Café"));
+        assert!(decode_document(b"<Hotel name=\"Caf\xe9\"/>")
+            .unwrap()
+            .contains("Café"));
+
+        let mut utf16 = vec![0xFF, 0xFE];
+        utf16.extend(
+            "<Hotel name=\"Hôtel\"/>"
+                .encode_utf16()
+                .flat_map(u16::to_le_bytes),
+        );
+        assert_eq!(decode_document(&utf16).unwrap(), "<Hotel name=\"Hôtel\"/>");
+
+        assert!(matches!(
+            decode_document(b"<?xml version=\"1.0\" encoding=\"EBCDIC-FR\"?><Hotel/>"),
+            Err(ProcessingError::InvalidFormat(_))
+        ));
+    }
+
+    #[test]
+    fn test_repair_entities() {
+        let valid = "<Hotel name=\"B&amp;B &#233;t&#xE9; &lt;3\"/>";
+        assert!(matches!(repair_entities(valid), Cow::Borrowed(_)));
+
+        assert_eq!(
+            repair_entities("<Hotel name=\"B&B Caf&eacute; &nbsp;& Spa &bogus; &#xZZ;\"/>"),
+            "<Hotel name=\"B&amp;B Caf&#233; &#160;&amp; Spa &amp;bogus; &amp;#xZZ;\"/>"
+        );
+        assert_eq!(repair_entities("Rock & Roll &"), "Rock &amp; Roll &amp;");
+    }
+}
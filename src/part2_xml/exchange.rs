@@ -0,0 +1,237 @@
+// Currency conversion of processed options
+// Suppliers price in their own currencies; the storefront shows everything in the customer's.
+// `convert_currency` rewrites option prices, selling prices, taxes and amount penalties into the
+// target currency at the provider's rates, rounded to the cent. Each option keeps the price
+// the supplier quoted as `source_price`, so converting again (e.g. the customer switches
+// currency) still starts from the original. Percentage penalties need no conversion.
+// If any rate is missing nothing is converted.
+
+use super::cancellation::PENALTY_PERCENTAGE;
+use super::{ProcessedResponse, ProcessingError};
+use crate::money::Money;
+use rust_decimal::{Decimal, RoundingStrategy};
+use std::collections::HashMap;
+
+pub trait ExchangeRateProvider: Send + Sync {
+    // How many units of `to` one unit of `from` buys, None if unknown
+    fn rate(&self, from: &str, to: &str) -> Option<Decimal>;
+}
+
+// Fixed rates, e.g. loaded once a day; the inverse of every rate is known too
+#[derive(Debug, Clone, Default)]
+pub struct StaticRates {
+    rates: HashMap<(String, String), Decimal>,
+}
+
+impl StaticRates {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_rate(
+        mut self,
+        from: impl Into<String>,
+        to: impl Into<String>,
+        rate: Decimal,
+    ) -> Self {
+        self.rates.insert((from.into(), to.into()), rate);
+        self
+    }
+}
+
+impl ExchangeRateProvider for StaticRates {
+    fn rate(&self, from: &str, to: &str) -> Option<Decimal> {
+        if from == to {
+            return Some(Decimal::ONE);
+        }
+        let key = |a: &str, b: &str| (a.to_string(), b.to_string());
+        self.rates.get(&key(from, to)).copied().or_else(|| {
+            self.rates
+                .get(&key(to, from))
+                .filter(|rate| !rate.is_zero())
+                .map(|rate| Decimal::ONE / rate)
+        })
+    }
+}
+
+pub fn convert_currency(
+    response: &mut ProcessedResponse,
+    target: &str,
+    provider: &dyn ExchangeRateProvider,
+) -> Result<(), ProcessingError> {
+    // Every rate needed, looked up before anything is changed
+    let mut rates: HashMap<String, Decimal> = HashMap::new();
+    for option in &response.hotels {
+        let source = option.source_price.as_ref().unwrap_or(&option.price);
+        let currencies = [Some(source), option.selling_price.as_ref()]
+            .into_iter()
+            .flatten()
+            .map(|price| price.currency.as_str())
+            .chain(
+                option
+                    .cancellation_policies
+                    .iter()
+                    .filter(|policy| policy.penalty_type != PENALTY_PERCENTAGE)
+                    .map(|policy| penalty_currency(&policy.currency, &option.price)),
+            )
+            .chain(option.taxes.iter().map(|tax| tax.amount.currency.as_str()));
+        for currency in currencies {
+            if !rates.contains_key(currency) {
+                let rate = provider.rate(currency, target).ok_or_else(|| {
+                    ProcessingError::ConversionError(format!(
+                        "no exchange rate from {:?} to {:?}",
+                        currency, target
+                    ))
+                })?;
+                rates.insert(currency.to_string(), rate);
+            }
+        }
+    }
+    let convert = |amount: &Money| {
+        let converted = amount.amount * rates[&amount.currency];
+        Money::new(
+            converted.round_dp_with_strategy(2, RoundingStrategy::MidpointAwayFromZero),
+            target,
+        )
+    };
+
+    for option in &mut response.hotels {
+        for policy in &mut option.cancellation_policies {
+            if policy.penalty_type != PENALTY_PERCENTAGE {
+                let currency = penalty_currency(&policy.currency, &option.price);
+                policy.penalty_amount =
+                    convert(&Money::new(policy.penalty_amount, currency)).amount;
+                policy.currency = target.to_string();
+            }
+        }
+        let source = option
+            .source_price
+            .get_or_insert_with(|| option.price.clone());
+        option.price = convert(source);
+        option.selling_price = option.selling_price.as_ref().map(convert);
+        for tax in &mut option.taxes {
+            tax.amount = convert(&tax.amount);
+        }
+    }
+    response.currency = target.to_string();
+    Ok(())
+}
+
+// Amount penalties without a currency are in the option's
+fn penalty_currency<'a>(currency: &'a str, price: &'a Money) -> &'a str {
+    if currency.is_empty() {
+        &price.currency
+    } else {
+        currency
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::part2_xml::cancellation::PENALTY_AMOUNT;
+    use crate::part2_xml::{HotelOption, ProcessedCancellationPolicy, Tax};
+    use std::str::FromStr;
+
+    fn amount(value: &str) -> Decimal {
+        Decimal::from_str(value).unwrap()
+    }
+
+    fn response() -> ProcessedResponse {
+        let policy =
+            |penalty: &str, currency: &str, penalty_type: &str| ProcessedCancellationPolicy {
+                deadline: Default::default(),
+                penalty_amount: amount(penalty),
+                currency: currency.to_string(),
+                hours_before: 0,
+                penalty_type: penalty_type.to_string(),
+            };
+        let hotels = vec![
+            HotelOption {
+                price: Money::new(amount("84.82"), "GBP"),
+                selling_price: Some(Money::new(amount("94.00"), "GBP")),
+                cancellation_policies: vec![
+                    policy("42.41", "GBP", PENALTY_AMOUNT),
+                    policy("10", "", PENALTY_AMOUNT),
+                    policy("100", "", PENALTY_PERCENTAGE),
+                ],
+                taxes: vec![Tax {
+                    name: "City tax".to_string(),
+                    included: false,
+                    amount: Money::new(amount("5"), "GBP"),
+                    pay_at_hotel: true,
+                }],
+                ..Default::default()
+            },
+            HotelOption {
+                price: Money::new(amount("120"), "EUR"),
+                ..Default::default()
+            },
+        ];
+        ProcessedResponse {
+            search_id: String::new(),
+            total_options: hotels.len(),
+            hotels,
+            currency: "GBP".to_string(),
+            nationality: String::new(),
+            check_in: None,
+            check_out: None,
+            markets: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_convert_currency() {
+        let rates = StaticRates::new()
+            .with_rate("GBP", "EUR", amount("1.17"))
+            .with_rate("EUR", "USD", amount("1.08"));
+        let mut response = response();
+        convert_currency(&mut response, "EUR", &rates).unwrap();
+
+        let first = &response.hotels[0];
+        assert_eq!(first.price.to_string(), "99.24 EUR");
+        assert_eq!(
+            first.source_price.as_ref().unwrap().to_string(),
+            "84.82 GBP"
+        );
+        assert_eq!(
+            first.selling_price.as_ref().unwrap().to_string(),
+            "109.98 EUR"
+        );
+        let penalties: Vec<(String, &str)> = first
+            .cancellation_policies
+            .iter()
+            .map(|policy| (policy.penalty_amount.to_string(), policy.currency.as_str()))
+            .collect();
+        assert_eq!(
+            penalties,
+            vec![
+                ("49.62".to_string(), "EUR"),
+                ("11.70".to_string(), "EUR"),
+                ("100".to_string(), ""),
+            ]
+        );
+        assert_eq!(first.taxes[0].amount.to_string(), "5.85 EUR");
+        assert_eq!(response.hotels[1].price.to_string(), "120.00 EUR");
+        assert_eq!(response.currency, "EUR");
+
+        // Converting again starts from the supplier's price
+        convert_currency(&mut response, "GBP", &rates).unwrap();
+        assert_eq!(response.hotels[0].price.to_string(), "84.82 GBP");
+        assert_eq!(response.hotels[1].price.to_string(), "102.56 GBP");
+        assert_eq!(
+            response.hotels[1].source_price.as_ref().unwrap().currency,
+            "EUR"
+        );
+    }
+
+    #[test]
+    fn test_missing_rate_changes_nothing() {
+        let rates = StaticRates::new().with_rate("GBP", "USD", amount("1.27"));
+        let mut response = response();
+        let error = convert_currency(&mut response, "USD", &rates).unwrap_err();
+        assert!(error.to_string().contains("\"EUR\""));
+        assert_eq!(response.hotels, self::response().hotels);
+        assert_eq!(response.currency, "GBP");
+    }
+}
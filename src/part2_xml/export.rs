@@ -0,0 +1,311 @@
+// Export of processed options for analytics
+// BI pipelines load search results into tables and had to flatten `HotelOption` themselves.
+// `export_options` writes options one row each, with the columns analysts use: hotel, room,
+// board, net price, currency, refundability and the free cancellation deadline (empty when
+// cancelling always costs something). CSV is always available. Parquet, behind the
+// `parquet-export` feature, types the columns: the price as DECIMAL(18,4) and the deadline as a
+// UTC timestamp in milliseconds, null when there is none.
+
+use super::{HotelOption, ProcessingError};
+use serde::Serialize;
+use std::io::Write;
+
+pub const EXPORT_COLUMNS: [&str; 8] = [
+    "hotel_id",
+    "hotel_name",
+    "room_type",
+    "board_type",
+    "price",
+    "currency",
+    "refundable",
+    "deadline",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    #[cfg(feature = "parquet-export")]
+    Parquet,
+}
+
+// One CSV row, in the order of `EXPORT_COLUMNS`
+#[derive(Serialize)]
+struct CsvRow<'a> {
+    hotel_id: &'a str,
+    hotel_name: &'a str,
+    room_type: &'a str,
+    board_type: &'a str,
+    // Decimals would be written as floats
+    price: String,
+    currency: &'a str,
+    refundable: bool,
+    deadline: Option<String>,
+}
+
+// Write `options` to `writer` as `format`, returning how many were written
+pub fn export_options<'a, W: Write + Send>(
+    options: impl IntoIterator<Item = &'a HotelOption>,
+    format: ExportFormat,
+    writer: W,
+) -> Result<usize, ProcessingError> {
+    match format {
+        ExportFormat::Csv => write_csv(options, writer),
+        #[cfg(feature = "parquet-export")]
+        ExportFormat::Parquet => parquet_writer::write_parquet(options, writer),
+    }
+}
+
+fn write_csv<'a>(
+    options: impl IntoIterator<Item = &'a HotelOption>,
+    writer: impl Write,
+) -> Result<usize, ProcessingError> {
+    let mut csv = csv::WriterBuilder::new()
+        .has_headers(false)
+        .from_writer(writer);
+    csv.write_record(EXPORT_COLUMNS).map_err(csv_error)?;
+    let mut count = 0;
+    for option in options {
+        csv.serialize(CsvRow {
+            hotel_id: &option.hotel_id,
+            hotel_name: &option.hotel_name,
+            room_type: &option.room_type,
+            board_type: &option.board_type,
+            price: option.price.amount.to_string(),
+            currency: &option.price.currency,
+            refundable: option.is_refundable,
+            deadline: option
+                .free_cancellation_until
+                .map(|deadline| deadline.format("%Y-%m-%dT%H:%M:%SZ").to_string()),
+        })
+        .map_err(csv_error)?;
+        count += 1;
+    }
+    csv.flush()?;
+    Ok(count)
+}
+
+fn csv_error(error: csv::Error) -> ProcessingError {
+    ProcessingError::ConversionError(error.to_string())
+}
+
+#[cfg(feature = "parquet-export")]
+mod parquet_writer {
+    use super::{HotelOption, ProcessingError};
+    use parquet::column::writer::ColumnWriter;
+    use parquet::data_type::ByteArray;
+    use parquet::errors::ParquetError;
+    use parquet::file::properties::WriterProperties;
+    use parquet::file::writer::SerializedFileWriter;
+    use parquet::schema::parser::parse_message_type;
+    use std::io::Write;
+    use std::sync::Arc;
+
+    // Columns in the order of `EXPORT_COLUMNS`
+    const SCHEMA: &str = "
+        message hotel_option {
+            REQUIRED BYTE_ARRAY hotel_id (UTF8);
+            REQUIRED BYTE_ARRAY hotel_name (UTF8);
+            REQUIRED BYTE_ARRAY room_type (UTF8);
+            REQUIRED BYTE_ARRAY board_type (UTF8);
+            REQUIRED INT64 price (DECIMAL(18,4));
+            REQUIRED BYTE_ARRAY currency (UTF8);
+            REQUIRED BOOLEAN refundable;
+            OPTIONAL INT64 deadline (TIMESTAMP(MILLIS,true));
+        }
+    ";
+    const PRICE_SCALE: u32 = 4;
+
+    // All options go in one row group; exports are a search response or a day of them
+    pub(super) fn write_parquet<'a, W: Write + Send>(
+        options: impl IntoIterator<Item = &'a HotelOption>,
+        writer: W,
+    ) -> Result<usize, ProcessingError> {
+        let options: Vec<&HotelOption> = options.into_iter().collect();
+        let prices = options
+            .iter()
+            .map(|option| unscaled_price(option))
+            .collect::<Result<Vec<i64>, _>>()?;
+        let deadlines: Vec<i64> = options
+            .iter()
+            .filter_map(|option| option.free_cancellation_until)
+            .map(|deadline| deadline.timestamp_millis())
+            .collect();
+        let deadline_levels: Vec<i16> = options
+            .iter()
+            .map(|option| option.free_cancellation_until.is_some() as i16)
+            .collect();
+
+        let schema = Arc::new(parse_message_type(SCHEMA).map_err(parquet_error)?);
+        let properties = Arc::new(WriterProperties::builder().build());
+        let mut file =
+            SerializedFileWriter::new(writer, schema, properties).map_err(parquet_error)?;
+        let mut row_group = file.next_row_group().map_err(parquet_error)?;
+        let mut index = 0;
+        while let Some(mut column) = row_group.next_column().map_err(parquet_error)? {
+            let written = match (index, column.untyped()) {
+                (0..=3 | 5, ColumnWriter::ByteArrayColumnWriter(writer)) => {
+                    let values: Vec<ByteArray> = options
+                        .iter()
+                        .map(|option| text_column(option, index).into())
+                        .collect();
+                    writer.write_batch(&values, None, None)
+                }
+                (4, ColumnWriter::Int64ColumnWriter(writer)) => {
+                    writer.write_batch(&prices, None, None)
+                }
+                (6, ColumnWriter::BoolColumnWriter(writer)) => {
+                    let values: Vec<bool> =
+                        options.iter().map(|option| option.is_refundable).collect();
+                    writer.write_batch(&values, None, None)
+                }
+                (7, ColumnWriter::Int64ColumnWriter(writer)) => {
+                    writer.write_batch(&deadlines, Some(&deadline_levels), None)
+                }
+                _ => Err(ParquetError::General(format!(
+                    "unexpected writer for column {}",
+                    index
+                ))),
+            };
+            written.map_err(parquet_error)?;
+            column.close().map_err(parquet_error)?;
+            index += 1;
+        }
+        row_group.close().map_err(parquet_error)?;
+        file.close().map_err(parquet_error)?;
+        Ok(options.len())
+    }
+
+    fn text_column(option: &HotelOption, index: usize) -> &str {
+        match index {
+            0 => &option.hotel_id,
+            1 => &option.hotel_name,
+            2 => &option.room_type,
+            3 => &option.board_type,
+            _ => &option.price.currency,
+        }
+    }
+
+    fn unscaled_price(option: &HotelOption) -> Result<i64, ProcessingError> {
+        let mut amount = option.price.amount.round_dp(PRICE_SCALE);
+        amount.rescale(PRICE_SCALE);
+        i64::try_from(amount.mantissa()).map_err(|_| {
+            ProcessingError::ConversionError(format!(
+                "price {} doesn't fit DECIMAL(18,4)",
+                option.price.amount
+            ))
+        })
+    }
+
+    fn parquet_error(error: ParquetError) -> ProcessingError {
+        ProcessingError::ConversionError(error.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::part2_xml::HotelSearchProcessor;
+
+    fn sample_options() -> Vec<HotelOption> {
+        let processor = HotelSearchProcessor::new();
+        let options = processor
+            .process_with_request(
+                &processor.load_sample_response().unwrap(),
+                &processor.load_sample_request().unwrap(),
+            )
+            .unwrap()
+            .hotels;
+        // Both kinds of deadline column
+        assert!(options
+            .iter()
+            .any(|option| option.free_cancellation_until.is_some()));
+        assert!(options
+            .iter()
+            .any(|option| option.free_cancellation_until.is_none()));
+        options
+    }
+
+    #[test]
+    fn test_export_csv() {
+        let options = sample_options();
+        let mut csv = Vec::new();
+        let count = export_options(&options, ExportFormat::Csv, &mut csv).unwrap();
+        assert_eq!(count, options.len());
+
+        let csv = String::from_utf8(csv).unwrap();
+        let mut lines = csv.lines();
+        assert_eq!(lines.next(), Some(EXPORT_COLUMNS.join(",").as_str()));
+        assert_eq!(lines.count(), options.len());
+
+        let mut reader = csv::Reader::from_reader(csv.as_bytes());
+        for (record, option) in reader.records().zip(&options) {
+            let record = record.unwrap();
+            assert_eq!(&record[0], option.hotel_id);
+            assert_eq!(&record[1], option.hotel_name);
+            assert_eq!(record[4].parse(), Ok(option.price.amount));
+            assert_eq!(&record[6], option.is_refundable.to_string());
+            assert_eq!(
+                record[7].is_empty(),
+                option.free_cancellation_until.is_none()
+            );
+        }
+
+        // Header only
+        let mut empty = Vec::new();
+        assert_eq!(
+            export_options(&[], ExportFormat::Csv, &mut empty).unwrap(),
+            0
+        );
+        assert_eq!(
+            String::from_utf8(empty).unwrap(),
+            format!("{}\n", EXPORT_COLUMNS.join(","))
+        );
+    }
+
+    #[cfg(feature = "parquet-export")]
+    #[test]
+    fn test_export_parquet() {
+        use ::parquet::file::reader::{FileReader, SerializedFileReader};
+        use ::parquet::record::Field;
+
+        let options = sample_options();
+        let mut buffer = Vec::new();
+        let count = export_options(&options, ExportFormat::Parquet, &mut buffer).unwrap();
+        assert_eq!(count, options.len());
+
+        let reader = SerializedFileReader::new(bytes::Bytes::from(buffer)).unwrap();
+        let columns: Vec<String> = reader
+            .metadata()
+            .file_metadata()
+            .schema_descr()
+            .columns()
+            .iter()
+            .map(|column| column.name().to_string())
+            .collect();
+        assert_eq!(columns, EXPORT_COLUMNS);
+
+        let rows: Vec<_> = reader
+            .get_row_iter(None)
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(rows.len(), options.len());
+        for (row, option) in rows.iter().zip(&options) {
+            let fields: Vec<&Field> = row.get_column_iter().map(|(_, field)| field).collect();
+            assert_eq!(fields[0], &Field::Str(option.hotel_id.clone()));
+            match fields[4] {
+                Field::Decimal(decimal) => assert_eq!(decimal.scale(), 4),
+                other => panic!("price is {:?}", other),
+            }
+            assert_eq!(fields[6], &Field::Bool(option.is_refundable));
+            match option.free_cancellation_until {
+                Some(deadline) => assert_eq!(
+                    fields[7],
+                    &Field::TimestampMillis(deadline.timestamp_millis())
+                ),
+                None => assert_eq!(fields[7], &Field::Null),
+            }
+        }
+    }
+}
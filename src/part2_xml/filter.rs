@@ -0,0 +1,235 @@
+// Filter criteria
+// `FilterCriteria` has a field per built-in criterion, most of them optional; the builder sets
+// only the ones wanted. Anything the built-in criteria don't cover is a custom `Filter`, any
+// `Fn(&HotelOption) -> bool` will do. An option is kept if it passes every criterion set and
+// every custom filter.
+
+use super::cancellation::free_cancellation_until;
+use super::{FilterCriteria, HotelOption};
+use chrono::{DateTime, NaiveDate, Utc};
+use rust_decimal::Decimal;
+use std::fmt;
+use std::sync::Arc;
+
+pub trait Filter: Send + Sync {
+    fn matches(&self, option: &HotelOption) -> bool;
+}
+
+impl<F: Fn(&HotelOption) -> bool + Send + Sync> Filter for F {
+    fn matches(&self, option: &HotelOption) -> bool {
+        self(option)
+    }
+}
+
+// Custom filters are opaque
+impl fmt::Debug for dyn Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Filter")
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct FilterCriteriaBuilder {
+    criteria: FilterCriteria,
+}
+
+impl FilterCriteria {
+    pub fn builder() -> FilterCriteriaBuilder {
+        FilterCriteriaBuilder::default()
+    }
+
+    // Whether `option` passes every criterion set; `refundable_at` resolves the option's
+    // policies against `check_in`
+    pub fn matches(&self, option: &HotelOption, check_in: Option<NaiveDate>) -> bool {
+        if self
+            .min_price
+            .is_some_and(|min| option.total_including_taxes().amount < min)
+        {
+            return false;
+        }
+
+        if self
+            .max_price
+            .is_some_and(|max| option.total_including_taxes().amount > max)
+        {
+            return false;
+        }
+
+        if !self
+            .board_types
+            .as_ref()
+            .is_none_or(|types| types.contains(&option.board_type))
+        {
+            return false;
+        }
+
+        if self.excluded_board_types.contains(&option.board_type) {
+            return false;
+        }
+
+        if self.free_cancellation && !option.is_refundable {
+            return false;
+        }
+
+        if let Some(at) = self.refundable_at {
+            let until = free_cancellation_until(&option.normalized_policies(check_in));
+            if !option.is_refundable || until.is_some_and(|until| at >= until) {
+                return false;
+            }
+        }
+
+        if !self
+            .hotel_ids
+            .as_ref()
+            .is_none_or(|ids| ids.contains(&option.hotel_id))
+        {
+            return false;
+        }
+
+        if !self
+            .room_type_contains
+            .as_ref()
+            .is_none_or(|substring| option.room_type.contains(substring))
+        {
+            return false;
+        }
+
+        if self
+            .payment_type
+            .as_ref()
+            .is_some_and(|payment_type| option.payment_type != *payment_type)
+        {
+            return false;
+        }
+
+        if self.has_promotion && !option.has_promotion() {
+            return false;
+        }
+
+        if (self.min_category.is_some() || self.max_category.is_some())
+            && !option.category.is_some_and(|category| {
+                self.min_category.is_none_or(|min| category >= min)
+                    && self.max_category.is_none_or(|max| category <= max)
+            })
+        {
+            return false;
+        }
+
+        if !self.destination_codes.as_ref().is_none_or(|codes| {
+            option
+                .destination_code
+                .as_ref()
+                .is_some_and(|code| codes.contains(code))
+        }) {
+            return false;
+        }
+
+        self.custom.iter().all(|filter| filter.matches(option))
+    }
+
+    // Whether a response searched for `markets` passes the market criterion
+    pub fn market_searched(&self, markets: &[String]) -> bool {
+        self.market
+            .as_ref()
+            .is_none_or(|market| markets.is_empty() || markets.contains(market))
+    }
+}
+
+impl FilterCriteriaBuilder {
+    pub fn min_price(mut self, min: Decimal) -> Self {
+        self.criteria.min_price = Some(min);
+        self
+    }
+
+    pub fn max_price(mut self, max: Decimal) -> Self {
+        self.criteria.max_price = Some(max);
+        self
+    }
+
+    // Both bounds included
+    pub fn price_range(self, min: Decimal, max: Decimal) -> Self {
+        self.min_price(min).max_price(max)
+    }
+
+    pub fn board_types<S: Into<String>>(mut self, types: impl IntoIterator<Item = S>) -> Self {
+        self.criteria.board_types = Some(types.into_iter().map(Into::into).collect());
+        self
+    }
+
+    pub fn exclude_board_types<S: Into<String>>(
+        mut self,
+        types: impl IntoIterator<Item = S>,
+    ) -> Self {
+        self.criteria
+            .excluded_board_types
+            .extend(types.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn free_cancellation(mut self) -> Self {
+        self.criteria.free_cancellation = true;
+        self
+    }
+
+    pub fn refundable_at(mut self, at: DateTime<Utc>) -> Self {
+        self.criteria.refundable_at = Some(at);
+        self
+    }
+
+    pub fn hotel_ids<S: Into<String>>(mut self, ids: impl IntoIterator<Item = S>) -> Self {
+        self.criteria.hotel_ids = Some(ids.into_iter().map(Into::into).collect());
+        self
+    }
+
+    pub fn room_type_contains(mut self, substring: impl Into<String>) -> Self {
+        self.criteria.room_type_contains = Some(substring.into());
+        self
+    }
+
+    pub fn payment_type(mut self, payment_type: impl Into<String>) -> Self {
+        self.criteria.payment_type = Some(payment_type.into());
+        self
+    }
+
+    pub fn has_promotion(mut self) -> Self {
+        self.criteria.has_promotion = true;
+        self
+    }
+
+    pub fn min_category(mut self, min: i32) -> Self {
+        self.criteria.min_category = Some(min);
+        self
+    }
+
+    pub fn max_category(mut self, max: i32) -> Self {
+        self.criteria.max_category = Some(max);
+        self
+    }
+
+    // Both bounds included
+    pub fn category_range(self, min: i32, max: i32) -> Self {
+        self.min_category(min).max_category(max)
+    }
+
+    pub fn destination_codes<S: Into<String>>(
+        mut self,
+        codes: impl IntoIterator<Item = S>,
+    ) -> Self {
+        self.criteria.destination_codes = Some(codes.into_iter().map(Into::into).collect());
+        self
+    }
+
+    pub fn market(mut self, market: impl Into<String>) -> Self {
+        self.criteria.market = Some(market.into());
+        self
+    }
+
+    pub fn matching(mut self, filter: impl Filter + 'static) -> Self {
+        self.criteria.custom.push(Arc::new(filter));
+        self
+    }
+
+    pub fn build(self) -> FilterCriteria {
+        self.criteria
+    }
+}
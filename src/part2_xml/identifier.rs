@@ -0,0 +1,143 @@
+// Option identifiers
+// Callers referencing an option between search, valuation and booking had to find it again by
+// hotel, room, board and price, which breaks on hotels with the same room twice. Every option now
+// has an `option_id`: a hash of hotel, rooms, board, rate key and net price. It's the same for the
+// same option in every search and process (SHA-256, unlike `DefaultHasher`, isn't tied to the
+// Rust release), and changes when the price does. Converted responses carry it as the "option_id"
+// parameter of each <Option>; responses without one get it computed when processed. All rooms
+// of a multi-room option share its ID, as they share its search token.
+
+use super::token::SearchToken;
+use crate::xml_response::{XmlOption, TOKEN_ROOM_SEPARATOR};
+use rust_decimal::Decimal;
+use sha2::{Digest, Sha256};
+use std::fmt::Write;
+
+pub const OPTION_ID_PARAMETER: &str = "option_id";
+pub const SEARCH_TOKEN_PARAMETER: &str = "search_token";
+// Hex digits kept of the hash
+const ID_LENGTH: usize = 16;
+
+// The ID of `option`, offered with `board_type` by `hotel_id`
+pub fn option_id(hotel_id: &str, board_type: &str, option: &XmlOption) -> String {
+    let rooms: Vec<&str> = option
+        .rooms
+        .rooms
+        .iter()
+        .map(|room| room.code.as_str())
+        .collect();
+    // The amount as a number, so 84.8 and 84.80 are the same price
+    let price = option
+        .price
+        .amount
+        .trim()
+        .parse::<Decimal>()
+        .map(|amount| amount.normalize().to_string())
+        .unwrap_or_else(|_| option.price.amount.clone());
+
+    let mut hasher = Sha256::new();
+    for field in [
+        hotel_id,
+        &rooms.join(TOKEN_ROOM_SEPARATOR),
+        board_type,
+        &rate_key(option),
+        &price,
+        &option.price.currency,
+    ] {
+        hasher.update(field.as_bytes());
+        hasher.update([0]);
+    }
+    let mut id = String::with_capacity(ID_LENGTH);
+    for byte in &hasher.finalize()[..ID_LENGTH / 2] {
+        let _ = write!(id, "{:02x}", byte);
+    }
+    id
+}
+
+// The option's "option_id" parameter, or its ID computed if it has none
+pub(crate) fn parameter_or_option_id(
+    hotel_id: &str,
+    board_type: &str,
+    option: &XmlOption,
+) -> String {
+    parameter(option, OPTION_ID_PARAMETER)
+        .filter(|id| !id.is_empty())
+        .map(str::to_string)
+        .unwrap_or_else(|| option_id(hotel_id, board_type, option))
+}
+
+pub(crate) fn parameter<'a>(option: &'a XmlOption, key: &str) -> Option<&'a str> {
+    option
+        .parameters
+        .parameters
+        .iter()
+        .find(|parameter| parameter.key == key)
+        .map(|parameter| parameter.value.as_str())
+}
+
+// The supplier's rate key from the search token; tokens without one (as the hub's) are taken
+// whole
+fn rate_key(option: &XmlOption) -> String {
+    let token = parameter(option, SEARCH_TOKEN_PARAMETER).unwrap_or_default();
+    match SearchToken::decode(token) {
+        Ok(decoded) if !decoded.rate_key.is_empty() => decoded.rate_key,
+        _ => token.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::part2_xml::HotelSearchProcessor;
+    use crate::xml_response::{XmlParameter, XmlProcessedResponse};
+
+    #[test]
+    fn test_option_id() {
+        let processor = HotelSearchProcessor::new();
+        let xml = processor
+            .convert_json_to_xml(&processor.load_sample_json().unwrap())
+            .unwrap();
+        let response: XmlProcessedResponse = quick_xml::de::from_str(&xml).unwrap();
+        let hotel = &response.hotels.hotels[0];
+        let meal_plan = &hotel.meal_plans.meal_plans[0];
+        let option = &meal_plan.options.options[0];
+
+        let id = option_id(&hotel.hotel_id, &meal_plan.code, option);
+        assert_eq!(id.len(), ID_LENGTH);
+        assert!(id.chars().all(|c| c.is_ascii_hexdigit()));
+        // Emitted by the conversion
+        assert_eq!(parameter(option, OPTION_ID_PARAMETER), Some(id.as_str()));
+        assert_eq!(option_id(&hotel.hotel_id, &meal_plan.code, option), id);
+
+        let mut changed = option.clone();
+        changed.price.amount = "84.8".to_string();
+        let price_id = option_id(&hotel.hotel_id, &meal_plan.code, &changed);
+        assert_ne!(price_id, id);
+        changed.price.amount = "84.80".to_string();
+        assert_eq!(
+            option_id(&hotel.hotel_id, &meal_plan.code, &changed),
+            price_id
+        );
+        assert_ne!(option_id(&hotel.hotel_id, "XX", option), id);
+        assert_ne!(option_id("other", &meal_plan.code, option), id);
+
+        // A parameter already there is kept, one missing is computed
+        let mut issued = option.clone();
+        issued
+            .parameters
+            .parameters
+            .retain(|p| p.key != OPTION_ID_PARAMETER);
+        assert_eq!(
+            parameter_or_option_id(&hotel.hotel_id, &meal_plan.code, &issued),
+            id
+        );
+        issued.parameters.parameters.push(XmlParameter {
+            key: OPTION_ID_PARAMETER.to_string(),
+            value: "upstream".to_string(),
+        });
+        assert_eq!(
+            parameter_or_option_id(&hotel.hotel_id, &meal_plan.code, &issued),
+            "upstream"
+        );
+    }
+}
@@ -0,0 +1,213 @@
+// Input sources
+// Documents reach the processor from files, sockets, request bodies or strings already in
+// memory. `InputSource` takes any of them to `process_from`/`convert_from`, which read the whole
+// source and process it. Reading stops one byte past `ParseLimits::max_document_bytes`, so an
+// endless source fails with `LimitExceeded` instead of filling memory. The async variants read
+// without blocking the runtime: files through tokio, blocking readers on the blocking thread
+// pool. The blocking variants can't read an `AsyncRead` source.
+
+use super::limits::ParseLimits;
+use super::ProcessingError;
+use std::fmt;
+use std::io::Read;
+use std::path::PathBuf;
+use std::pin::Pin;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+pub enum InputSource {
+    Path(PathBuf),
+    Reader(Box<dyn Read + Send>),
+    AsyncReader(Pin<Box<dyn AsyncRead + Send>>),
+    Text(String),
+}
+
+// Readers are opaque
+impl fmt::Debug for InputSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InputSource::Path(path) => f.debug_tuple("Path").field(path).finish(),
+            InputSource::Reader(_) => f.write_str("Reader"),
+            InputSource::AsyncReader(_) => f.write_str("AsyncReader"),
+            InputSource::Text(text) => write!(f, "Text({} bytes)", text.len()),
+        }
+    }
+}
+
+impl From<&str> for InputSource {
+    fn from(text: &str) -> Self {
+        InputSource::Text(text.to_string())
+    }
+}
+
+impl From<String> for InputSource {
+    fn from(text: String) -> Self {
+        InputSource::Text(text)
+    }
+}
+
+impl InputSource {
+    pub fn path(path: impl Into<PathBuf>) -> Self {
+        InputSource::Path(path.into())
+    }
+
+    pub fn reader(reader: impl Read + Send + 'static) -> Self {
+        InputSource::Reader(Box::new(reader))
+    }
+
+    pub fn async_reader(reader: impl AsyncRead + Send + 'static) -> Self {
+        InputSource::AsyncReader(Box::pin(reader))
+    }
+
+    // The whole source, failing past `limits.max_document_bytes`
+    pub fn read_bytes(self, limits: &ParseLimits) -> Result<Vec<u8>, ProcessingError> {
+        let bytes = match self {
+            InputSource::Path(path) => read_limited(std::fs::File::open(path)?, limits)?,
+            InputSource::Reader(reader) => read_limited(reader, limits)?,
+            InputSource::AsyncReader(_) => {
+                return Err(ProcessingError::InvalidFormat(
+                    "an async source can only be read with the async variants".to_string(),
+                ))
+            }
+            InputSource::Text(text) => text.into_bytes(),
+        };
+        within_limit(bytes, limits)
+    }
+
+    pub async fn read_bytes_async(self, limits: &ParseLimits) -> Result<Vec<u8>, ProcessingError> {
+        let bytes = match self {
+            InputSource::Path(path) => {
+                let file = tokio::fs::File::open(path).await?;
+                read_limited_async(file, limits).await?
+            }
+            InputSource::Reader(reader) => {
+                let limits = *limits;
+                tokio::task::spawn_blocking(move || read_limited(reader, &limits))
+                    .await
+                    .map_err(|e| ProcessingError::IoError(std::io::Error::other(e)))??
+            }
+            InputSource::AsyncReader(reader) => read_limited_async(reader, limits).await?,
+            InputSource::Text(text) => text.into_bytes(),
+        };
+        within_limit(bytes, limits)
+    }
+
+    // Like `read_bytes`, for text sources such as supplier JSON
+    pub fn read_string(self, limits: &ParseLimits) -> Result<String, ProcessingError> {
+        utf8(self.read_bytes(limits)?)
+    }
+
+    pub async fn read_string_async(self, limits: &ParseLimits) -> Result<String, ProcessingError> {
+        utf8(self.read_bytes_async(limits).await?)
+    }
+}
+
+// One byte more than the limit, so a source that is too large is told from one that fits
+fn read_limited(reader: impl Read, limits: &ParseLimits) -> std::io::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    reader
+        .take(limits.max_document_bytes.saturating_add(1) as u64)
+        .read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+async fn read_limited_async(
+    reader: impl AsyncRead + Unpin,
+    limits: &ParseLimits,
+) -> std::io::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    reader
+        .take(limits.max_document_bytes.saturating_add(1) as u64)
+        .read_to_end(&mut bytes)
+        .await?;
+    Ok(bytes)
+}
+
+fn within_limit(bytes: Vec<u8>, limits: &ParseLimits) -> Result<Vec<u8>, ProcessingError> {
+    if bytes.len() > limits.max_document_bytes {
+        return Err(limits.too_large());
+    }
+    Ok(bytes)
+}
+
+fn utf8(bytes: Vec<u8>) -> Result<String, ProcessingError> {
+    String::from_utf8(bytes).map_err(|e| ProcessingError::InvalidFormat(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::part2_xml::SAMPLE_XML_PATH;
+
+    #[test]
+    fn test_read_sources() {
+        let limits = ParseLimits::default();
+        let expected = std::fs::read(SAMPLE_XML_PATH).unwrap();
+        assert_eq!(
+            InputSource::path(SAMPLE_XML_PATH)
+                .read_bytes(&limits)
+                .unwrap(),
+            expected
+        );
+        assert_eq!(
+            InputSource::reader(std::io::Cursor::new(expected.clone()))
+                .read_bytes(&limits)
+                .unwrap(),
+            expected
+        );
+        assert_eq!(
+            InputSource::from("<AvailRS/>")
+                .read_string(&limits)
+                .unwrap(),
+            "<AvailRS/>"
+        );
+        assert!(InputSource::async_reader(tokio::io::empty())
+            .read_bytes(&limits)
+            .is_err());
+        assert!(matches!(
+            InputSource::path("samples/missing.xml").read_bytes(&limits),
+            Err(ProcessingError::IoError(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_read_sources_async() {
+        let limits = ParseLimits::default();
+        let expected = std::fs::read(SAMPLE_XML_PATH).unwrap();
+        assert_eq!(
+            InputSource::path(SAMPLE_XML_PATH)
+                .read_bytes_async(&limits)
+                .await
+                .unwrap(),
+            expected
+        );
+        assert_eq!(
+            InputSource::reader(std::io::Cursor::new(expected.clone()))
+                .read_bytes_async(&limits)
+                .await
+                .unwrap(),
+            expected
+        );
+        assert_eq!(
+            InputSource::async_reader(std::io::Cursor::new(expected.clone()))
+                .read_bytes_async(&limits)
+                .await
+                .unwrap(),
+            expected
+        );
+    }
+
+    #[tokio::test]
+    async fn test_endless_source() {
+        let limits = ParseLimits::new().max_document_bytes(1_024);
+        assert!(matches!(
+            InputSource::reader(std::io::repeat(b' ')).read_bytes(&limits),
+            Err(ProcessingError::LimitExceeded(_))
+        ));
+        assert!(matches!(
+            InputSource::async_reader(tokio::io::repeat(b' '))
+                .read_bytes_async(&limits)
+                .await,
+            Err(ProcessingError::LimitExceeded(_))
+        ));
+    }
+}
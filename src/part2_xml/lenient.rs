@@ -0,0 +1,262 @@
+// Lenient AvailRS processing
+// `process` fails the whole response on the first malformed option and quietly takes prices
+// that don't parse as zero. `process_lenient` maps every <Option> on its own instead: options
+// that don't deserialize, have no valid price or a deadline that doesn't parse are skipped,
+// values that can be replaced by a default are coerced, and either way a `ProcessingWarning`
+// records the element's path and why. Only XML that isn't well-formed still fails.
+
+use super::limits::ParseLimits;
+use super::stream::{read_element, EventReader};
+use super::{option_rooms, HotelOption, ProcessedResponse, ProcessingError, ResponseContext};
+use crate::money::Money;
+use crate::xml_response::XmlOption;
+use crate::XmlHotel;
+use quick_xml::de::from_str;
+use quick_xml::events::{BytesStart, Event};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WarningAction {
+    // The option was left out of the response
+    Skipped,
+    // The value was replaced by a default
+    Coerced,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcessingWarning {
+    // e.g. /AvailRS/Hotels[1]/Hotel[2]/MealPlans[1]/MealPlan[1]/Options[1]/Option[3]
+    pub path: String,
+    pub action: WarningAction,
+    pub reason: String,
+}
+
+// An open element and how many children of each name it had so far
+struct Frame {
+    path: String,
+    children: HashMap<String, usize>,
+}
+
+pub(crate) fn process(
+    xml: &str,
+    context: ResponseContext,
+    limits: ParseLimits,
+) -> Result<(ProcessedResponse, Vec<ProcessingWarning>), ProcessingError> {
+    let mut reader = EventReader::new(xml.as_bytes()).with_limits(limits);
+    let mut buf = Vec::new();
+    let mut stack: Vec<Frame> = Vec::new();
+    // Header of the current <Hotel> and code of the current <MealPlan>
+    let mut hotel = XmlHotel::default();
+    let mut board_type = String::new();
+    let mut options = Vec::new();
+    let mut warnings = Vec::new();
+
+    loop {
+        buf.clear();
+        let event = reader.read_event(&mut buf)?;
+        match event {
+            Event::Start(start) => {
+                let path = child_path(&mut stack, &start);
+                match start.local_name().as_ref() {
+                    b"Option" => {
+                        let start = start.into_owned();
+                        let element = read_element(&mut reader, &mut buf, start)?;
+                        let mapped = map_option(&element, &path, &hotel, &board_type);
+                        match mapped {
+                            Ok((rooms, coerced)) => {
+                                options.extend(rooms);
+                                warnings.extend(coerced);
+                            }
+                            Err(reason) => warnings.push(ProcessingWarning {
+                                path,
+                                action: WarningAction::Skipped,
+                                reason,
+                            }),
+                        }
+                        continue;
+                    }
+                    b"Hotel" => hotel = hotel_header(&start, &path, &mut warnings),
+                    b"MealPlan" => board_type = attribute(&start, "code").unwrap_or_default(),
+                    _ => {}
+                }
+                stack.push(Frame {
+                    path,
+                    children: HashMap::new(),
+                });
+            }
+            // Empty elements have no options, but count for the positions of their siblings
+            Event::Empty(start) => {
+                child_path(&mut stack, &start);
+            }
+            Event::End(_) => {
+                stack.pop();
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok((ProcessedResponse::from_options(options, context), warnings))
+}
+
+// The options of one <Option> element and the values coerced on the way, or why it's skipped
+fn map_option(
+    xml: &str,
+    path: &str,
+    hotel: &XmlHotel,
+    board_type: &str,
+) -> Result<(Vec<HotelOption>, Vec<ProcessingWarning>), String> {
+    let option: XmlOption = from_str(xml).map_err(|e| e.to_string())?;
+    if Money::parse(&option.price.amount, &option.price.currency).is_none() {
+        return Err(format!("price {:?} is not an amount", option.price.amount));
+    }
+    let mut coerced = Vec::new();
+    let rooms =
+        option_rooms(hotel, board_type, &option, &mut coerced).map_err(|e| e.to_string())?;
+    let warnings = coerced
+        .into_iter()
+        .map(|(relative, reason)| ProcessingWarning {
+            path: format!("{}/{}", path, relative),
+            action: WarningAction::Coerced,
+            reason,
+        })
+        .collect();
+    Ok((rooms, warnings))
+}
+
+// A <Hotel> without its meal plans; a category that isn't a number is left out
+fn hotel_header(
+    start: &BytesStart<'_>,
+    path: &str,
+    warnings: &mut Vec<ProcessingWarning>,
+) -> XmlHotel {
+    let category = attribute(start, "category").and_then(|category| {
+        category.trim().parse().map(Some).unwrap_or_else(|_| {
+            warnings.push(ProcessingWarning {
+                path: format!("{}/@category", path),
+                action: WarningAction::Coerced,
+                reason: format!("category {:?} is not a number, ignoring it", category),
+            });
+            None
+        })
+    });
+    XmlHotel {
+        hotel_id: attribute(start, "code").unwrap_or_default(),
+        hotel_name: attribute(start, "name").unwrap_or_default(),
+        category,
+        destination_code: attribute(start, "destinationCode"),
+        ..Default::default()
+    }
+}
+
+fn attribute(start: &BytesStart<'_>, name: &str) -> Option<String> {
+    start
+        .try_get_attribute(name)
+        .ok()
+        .flatten()
+        .and_then(|attribute| attribute.unescape_value().ok())
+        .map(|value| value.into_owned())
+}
+
+// The path of a new child of the innermost open element, with its position among siblings of
+// the same name
+fn child_path(stack: &mut [Frame], start: &BytesStart<'_>) -> String {
+    let name = String::from_utf8_lossy(start.name().as_ref()).into_owned();
+    match stack.last_mut() {
+        Some(parent) => {
+            let position = parent.children.entry(name.clone()).or_insert(0);
+            *position += 1;
+            format!("{}/{}[{}]", parent.path, name, position)
+        }
+        None => format!("/{}", name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::part2_xml::{HotelSearchProcessor, SMALL_SAMPLE_XML};
+
+    fn option(price: &str, penalty: &str, deadline: &str) -> String {
+        format!(
+            r#"<Option type="Hotel" paymentType="MerchantPay" status="OK">
+                 <Price currency="GBP" amount="{}"/>
+                 <Rooms><Room id="1#ND1" code="ND1" nonRefundable="false">
+                   <CancelPenalties nonRefundable="false"><CancelPenalty>
+                     <HoursBefore>N/A</HoursBefore>
+                     <Penalty type="Importe" currency="GBP">{}</Penalty>
+                     <Deadline>{}</Deadline>
+                   </CancelPenalty></CancelPenalties>
+                 </Room></Rooms>
+               </Option>"#,
+            price, penalty, deadline
+        )
+    }
+
+    #[test]
+    fn test_well_formed_response_has_no_warnings() {
+        let processor = HotelSearchProcessor::new();
+        for xml in [
+            SMALL_SAMPLE_XML.to_string(),
+            processor.load_sample_response().unwrap(),
+        ] {
+            let (response, warnings) = processor.process_lenient(&xml).unwrap();
+            assert_eq!(warnings, vec![]);
+            assert_eq!(response.hotels, processor.process(&xml).unwrap().hotels);
+        }
+    }
+
+    #[test]
+    fn test_bad_options_are_skipped() {
+        let xml = format!(
+            r#"<AvailRS><Hotels><Hotel code="H1" name="One" category="four"><MealPlans>
+                 <MealPlan code="RO"><Options>{}{}{}{}</Options></MealPlan>
+               </MealPlans></Hotel></Hotels></AvailRS>"#,
+            option("84.82", "10", "2025-06-10T10:00:00Z"),
+            option("free", "10", "2025-06-10T10:00:00Z"),
+            option("90.00", "ten", "2025-06-10T10:00:00Z"),
+            option("95.00", "10", "tomorrow"),
+        );
+        let processor = HotelSearchProcessor::new();
+        assert!(processor.process(&xml).is_err());
+
+        let (response, warnings) = processor.process_lenient(&xml).unwrap();
+        let prices: Vec<String> = response
+            .hotels
+            .iter()
+            .map(|option| option.price.to_string())
+            .collect();
+        assert_eq!(prices, vec!["84.82 GBP", "90.00 GBP"]);
+        assert_eq!(response.hotels[0].category, None);
+
+        let options = "/AvailRS/Hotels[1]/Hotel[1]/MealPlans[1]/MealPlan[1]/Options[1]";
+        let summary: Vec<(String, WarningAction)> = warnings
+            .iter()
+            .map(|warning| (warning.path.clone(), warning.action))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (
+                    "/AvailRS/Hotels[1]/Hotel[1]/@category".to_string(),
+                    WarningAction::Coerced
+                ),
+                (format!("{}/Option[2]", options), WarningAction::Skipped),
+                (
+                    format!(
+                        "{}/Option[3]/Rooms[1]/Room[1]/CancelPenalties[1]/CancelPenalty[1]/Penalty[1]",
+                        options
+                    ),
+                    WarningAction::Coerced
+                ),
+                (format!("{}/Option[4]", options), WarningAction::Skipped),
+            ]
+        );
+        assert!(warnings[3].reason.contains("tomorrow"));
+
+        assert!(matches!(
+            processor.process_lenient("<AvailRS><Hotels></AvailRS>"),
+            Err(ProcessingError::XmlSyntaxError { .. })
+        ));
+    }
+}
@@ -0,0 +1,227 @@
+// Limits on untrusted XML
+// Responses come from partners we don't control; a document that is huge, nested thousands of
+// levels deep or lists millions of options would exhaust memory (or the stack) before anything
+// looked at it. `ParseLimits` bound what the processor accepts, failing with
+// `ProcessingError::LimitExceeded` as soon as a limit is passed. Documents held in memory are
+// checked with one pass of the event reader before they are deserialized; streamed documents
+// are checked as they are read, so the input is never read past the byte limit. The defaults
+// are far above any real response; `unlimited` turns the checks off.
+
+use super::stream::EventReader;
+use super::ProcessingError;
+use quick_xml::events::Event;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseLimits {
+    pub max_document_bytes: usize,
+    // Elements open at once; AvailRS nests about ten deep, a SOAP envelope adds a few
+    pub max_depth: usize,
+    // <Option> elements in the document
+    pub max_options: usize,
+}
+
+impl Default for ParseLimits {
+    fn default() -> Self {
+        Self {
+            max_document_bytes: 256 * 1024 * 1024,
+            max_depth: 64,
+            max_options: 500_000,
+        }
+    }
+}
+
+impl ParseLimits {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn unlimited() -> Self {
+        Self {
+            max_document_bytes: usize::MAX,
+            max_depth: usize::MAX,
+            max_options: usize::MAX,
+        }
+    }
+
+    pub fn max_document_bytes(mut self, bytes: usize) -> Self {
+        self.max_document_bytes = bytes;
+        self
+    }
+
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = depth;
+        self
+    }
+
+    pub fn max_options(mut self, options: usize) -> Self {
+        self.max_options = options;
+        self
+    }
+
+    pub(crate) fn too_large(&self) -> ProcessingError {
+        ProcessingError::LimitExceeded(format!(
+            "document is larger than {} bytes",
+            self.max_document_bytes
+        ))
+    }
+
+    pub(crate) fn too_deep(&self) -> ProcessingError {
+        ProcessingError::LimitExceeded(format!(
+            "elements are nested deeper than {} levels",
+            self.max_depth
+        ))
+    }
+
+    pub(crate) fn too_many_options(&self) -> ProcessingError {
+        ProcessingError::LimitExceeded(format!("more than {} options", self.max_options))
+    }
+}
+
+// Check a document held in memory against `limits`
+pub fn check_limits(xml: &str, limits: &ParseLimits) -> Result<(), ProcessingError> {
+    if xml.len() > limits.max_document_bytes {
+        return Err(limits.too_large());
+    }
+    if *limits == ParseLimits::unlimited() {
+        return Ok(());
+    }
+    let mut reader = EventReader::new(xml.as_bytes()).with_limits(*limits);
+    let mut buf = Vec::new();
+    loop {
+        buf.clear();
+        if let Event::Eof = reader.read_event(&mut buf)? {
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::part2_xml::{HotelSearchProcessor, SMALL_SAMPLE_XML};
+    use proptest::prelude::*;
+
+    fn processor(limits: ParseLimits) -> HotelSearchProcessor {
+        HotelSearchProcessor::new().with_limits(limits)
+    }
+
+    fn is_limit_exceeded<T: std::fmt::Debug>(result: Result<T, ProcessingError>) -> bool {
+        matches!(result, Err(ProcessingError::LimitExceeded(_)))
+    }
+
+    fn nested(depth: usize) -> String {
+        format!(
+            "<AvailRS>{}{}</AvailRS>",
+            "<Hotels>".repeat(depth),
+            "</Hotels>".repeat(depth)
+        )
+    }
+
+    #[test]
+    fn test_sample_within_default_limits() {
+        let processor = HotelSearchProcessor::new();
+        let xml = processor.load_sample_response().unwrap();
+        assert!(check_limits(&xml, &ParseLimits::default()).is_ok());
+        assert!(processor.process(&xml).is_ok());
+    }
+
+    #[test]
+    fn test_document_size() {
+        let limits = ParseLimits::new().max_document_bytes(SMALL_SAMPLE_XML.len() - 1);
+        assert!(is_limit_exceeded(
+            processor(limits).process(SMALL_SAMPLE_XML)
+        ));
+
+        // Streams stop reading at the limit
+        let huge = format!("<AvailRS><Hotels>{}", " ".repeat(100_000));
+        let limits = ParseLimits::new().max_document_bytes(1_000);
+        let mut stream = processor(limits).process_stream(huge.as_bytes());
+        assert!(is_limit_exceeded(stream.next().unwrap()));
+        assert!(stream.next().is_none());
+
+        let limits = ParseLimits::new().max_document_bytes(SMALL_SAMPLE_XML.len());
+        assert!(processor(limits).process(SMALL_SAMPLE_XML).is_ok());
+    }
+
+    #[test]
+    fn test_depth() {
+        // Deep enough to overflow the stack of a recursive parser
+        let deep = nested(100_000);
+        let processor = HotelSearchProcessor::new();
+        assert!(is_limit_exceeded(processor.process(&deep)));
+        assert!(is_limit_exceeded(processor.process_lenient(&deep)));
+        assert!(is_limit_exceeded(
+            processor.process_stream(deep.as_bytes()).next().unwrap()
+        ));
+        assert!(is_limit_exceeded(processor.convert_xml_to_json(&deep)));
+
+        let limits = ParseLimits::new().max_depth(3);
+        assert!(is_limit_exceeded(check_limits(&nested(3), &limits)));
+        assert!(check_limits(&nested(3), &limits.max_depth(4)).is_ok());
+    }
+
+    #[test]
+    fn test_option_count() {
+        let option = SMALL_SAMPLE_XML
+            .split("<Options>")
+            .nth(1)
+            .and_then(|rest| rest.split("</Options>").next())
+            .unwrap();
+        let many = SMALL_SAMPLE_XML.replace(option, &option.repeat(5));
+        let limits = ParseLimits::new().max_options(4);
+        assert!(is_limit_exceeded(processor(limits).process(&many)));
+        assert!(is_limit_exceeded(processor(limits).process_lenient(&many)));
+        let streamed: Result<Vec<_>, _> =
+            processor(limits).process_stream(many.as_bytes()).collect();
+        assert!(is_limit_exceeded(streamed));
+
+        let response = processor(limits.max_options(5)).process(&many).unwrap();
+        assert_eq!(response.total_options, 5);
+    }
+
+    // XML-ish fragments, so generated documents get past the first few bytes
+    fn fragment() -> impl Strategy<Value = String> {
+        prop_oneof![
+            Just("<AvailRS>".to_string()),
+            Just("</AvailRS>".to_string()),
+            Just("<Hotels>".to_string()),
+            Just("</Hotels>".to_string()),
+            Just("<Hotel code=\"1\" name=\"H\">".to_string()),
+            Just("</Hotel>".to_string()),
+            Just("<Option type=\"Hotel\">".to_string()),
+            Just("</Option>".to_string()),
+            Just("<Price currency=\"EUR\" amount=\"1\"/>".to_string()),
+            Just("<![CDATA[".to_string()),
+            Just("<!--".to_string()),
+            Just("&amp;".to_string()),
+            "[ -~]{0,8}",
+        ]
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(256))]
+
+        // Whatever comes in, processing returns instead of panicking, and never gets past the
+        // limits
+        #[test]
+        fn prop_pathological_input(
+            fragments in prop::collection::vec(fragment(), 0..200),
+            repeat in 1usize..50,
+        ) {
+            let xml = fragments.concat().repeat(repeat);
+            let limits = ParseLimits::new().max_document_bytes(4_096).max_depth(16).max_options(8);
+            let processor = processor(limits);
+
+            let result = processor.process(&xml);
+            if xml.len() > limits.max_document_bytes {
+                prop_assert!(is_limit_exceeded(result));
+            } else if let Ok(response) = result {
+                prop_assert!(response.total_options <= limits.max_options);
+            }
+            let _ = processor.process_lenient(&xml);
+            for option in processor.process_stream(xml.as_bytes()).take(limits.max_options + 1) {
+                let _ = option;
+            }
+        }
+    }
+}
@@ -0,0 +1,389 @@
+// Config-driven supplier mapping
+// Most supplier schemas differ from ours only in where things are and what they are called.
+// A `SupplierMapping`, loaded from TOML, says where each `SupplierResponse` field is found in
+// the supplier's JSON, so onboarding such a supplier is a config change:
+//
+//   [response]
+//   search_id = "/meta/id"        # JSON pointers (RFC 6901), relative to the enclosing item
+//   currency = "/meta/currency"
+//   hotels = "/results"           # arrays of the next level
+//   [hotel]
+//   hotel_id = "/code"
+//   name = "/name"
+//   rooms = "/rooms"
+//   [room]
+//   ...
+//   [board_types]                 # supplier board names -> our codes
+//   "Bed & Breakfast" = "BB"
+//
+// Fields marked optional below may be left out, their values default. Strings and numbers are
+// accepted for any field as long as the value converts. Cancellation dates in a supplier
+// format (`date_format`, a chrono format string) are converted to RFC 3339.
+
+use super::adapter::SupplierAdapter;
+use super::ProcessingError;
+use crate::supplier::{
+    RoomCapacity, SupplierCancellationPolicy, SupplierHotel, SupplierRate, SupplierResponse,
+    SupplierRoom,
+};
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::Path;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct SupplierMapping {
+    pub response: ResponseMapping,
+    pub hotel: HotelMapping,
+    pub room: RoomMapping,
+    pub rate: RateMapping,
+    #[serde(default)]
+    pub cancellation: CancellationMapping,
+    // Board types not listed are passed through
+    #[serde(default)]
+    pub board_types: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ResponseMapping {
+    pub search_id: String,
+    pub currency: String,
+    pub timestamp: Option<String>,
+    pub hotels: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct HotelMapping {
+    pub hotel_id: String,
+    pub name: String,
+    pub category: Option<String>,
+    pub destination_code: Option<String>,
+    pub rooms: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct RoomMapping {
+    pub room_id: String,
+    pub name: String,
+    pub adults: String,
+    pub children: Option<String>,
+    pub rates: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct RateMapping {
+    pub rate_id: String,
+    pub board_type: String,
+    pub price: String,
+    pub booking_code: Option<String>,
+    pub cancellation_policies: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct CancellationMapping {
+    pub from_date: String,
+    pub amount: String,
+    // Format of `from_date` if it isn't RFC 3339, e.g. "%d/%m/%Y %H:%M" (taken as UTC)
+    pub date_format: Option<String>,
+}
+
+impl Default for CancellationMapping {
+    fn default() -> Self {
+        Self {
+            from_date: "/from_date".to_string(),
+            amount: "/amount".to_string(),
+            date_format: None,
+        }
+    }
+}
+
+impl SupplierMapping {
+    pub fn from_toml(toml: &str) -> Result<Self, ProcessingError> {
+        toml::from_str(toml).map_err(|e| ProcessingError::InvalidFormat(e.to_string()))
+    }
+}
+
+// `SupplierAdapter` applying a `SupplierMapping`
+#[derive(Debug, Clone)]
+pub struct MappingAdapter {
+    mapping: SupplierMapping,
+}
+
+impl MappingAdapter {
+    pub fn new(mapping: SupplierMapping) -> Self {
+        Self { mapping }
+    }
+
+    pub fn from_toml(toml: &str) -> Result<Self, ProcessingError> {
+        SupplierMapping::from_toml(toml).map(Self::new)
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, ProcessingError> {
+        Self::from_toml(&std::fs::read_to_string(path)?)
+    }
+
+    fn hotel(&self, value: &Value) -> Result<SupplierHotel, ProcessingError> {
+        let mapping = &self.mapping.hotel;
+        Ok(SupplierHotel {
+            hotel_id: string(value, &mapping.hotel_id)?,
+            name: string(value, &mapping.name)?,
+            category: optional(value, &mapping.category, integer)?,
+            rooms: items(value, &mapping.rooms)?
+                .iter()
+                .map(|room| self.room(room))
+                .collect::<Result<_, _>>()?,
+            destination_code: optional(value, &mapping.destination_code, string)?,
+        })
+    }
+
+    fn room(&self, value: &Value) -> Result<SupplierRoom, ProcessingError> {
+        let mapping = &self.mapping.room;
+        Ok(SupplierRoom {
+            room_id: string(value, &mapping.room_id)?,
+            name: string(value, &mapping.name)?,
+            rates: items(value, &mapping.rates)?
+                .iter()
+                .map(|rate| self.rate(rate))
+                .collect::<Result<_, _>>()?,
+            capacity: RoomCapacity {
+                adults: integer(value, &mapping.adults)?,
+                children: optional(value, &mapping.children, integer)?,
+            },
+        })
+    }
+
+    fn rate(&self, value: &Value) -> Result<SupplierRate, ProcessingError> {
+        let mapping = &self.mapping.rate;
+        let board_type = string(value, &mapping.board_type)?;
+        // Rates without cancellation fees may leave the array out
+        let cancellation_policies = match &mapping.cancellation_policies {
+            Some(path) if value.pointer(path).is_some_and(|found| !found.is_null()) => {
+                items(value, path)?
+                    .iter()
+                    .map(|policy| self.cancellation(policy))
+                    .collect::<Result<_, _>>()?
+            }
+            _ => Vec::new(),
+        };
+        Ok(SupplierRate {
+            rate_id: string(value, &mapping.rate_id)?,
+            board_type: self
+                .mapping
+                .board_types
+                .get(&board_type)
+                .cloned()
+                .unwrap_or(board_type),
+            price: decimal(value, &mapping.price)?,
+            cancellation_policies,
+            booking_code: optional(value, &mapping.booking_code, string)?,
+            taxes: Vec::new(),
+            promotions: Vec::new(),
+        })
+    }
+
+    fn cancellation(&self, value: &Value) -> Result<SupplierCancellationPolicy, ProcessingError> {
+        let mapping = &self.mapping.cancellation;
+        let from_date = string(value, &mapping.from_date)?;
+        Ok(SupplierCancellationPolicy {
+            from_date: match &mapping.date_format {
+                Some(format) => rfc3339(&from_date, format)?,
+                None => from_date,
+            },
+            amount: decimal(value, &mapping.amount)?,
+        })
+    }
+}
+
+impl SupplierAdapter for MappingAdapter {
+    fn parse(&self, raw: &str) -> Result<SupplierResponse, ProcessingError> {
+        let value: Value = serde_json::from_str(raw)
+            .map_err(|e| ProcessingError::JsonParseError(e.to_string()))?;
+        let mapping = &self.mapping.response;
+        Ok(SupplierResponse {
+            hotels: items(&value, &mapping.hotels)?
+                .iter()
+                .map(|hotel| self.hotel(hotel))
+                .collect::<Result<_, _>>()?,
+            search_id: string(&value, &mapping.search_id)?,
+            currency: string(&value, &mapping.currency)?,
+            timestamp: optional(&value, &mapping.timestamp, string)?,
+        })
+    }
+}
+
+fn field<'a>(value: &'a Value, path: &str) -> Result<&'a Value, ProcessingError> {
+    value
+        .pointer(path)
+        .filter(|found| !found.is_null())
+        .ok_or_else(|| ProcessingError::MissingRequiredField(path.to_string()))
+}
+
+// Optional fields default when unmapped or absent from the document
+fn optional<T: Default>(
+    value: &Value,
+    path: &Option<String>,
+    read: fn(&Value, &str) -> Result<T, ProcessingError>,
+) -> Result<T, ProcessingError> {
+    match path {
+        Some(path) if value.pointer(path).is_some_and(|found| !found.is_null()) => {
+            read(value, path)
+        }
+        _ => Ok(T::default()),
+    }
+}
+
+fn invalid(path: &str, found: &Value) -> ProcessingError {
+    ProcessingError::InvalidFormat(format!("{}: unexpected value {}", path, found))
+}
+
+fn string(value: &Value, path: &str) -> Result<String, ProcessingError> {
+    match field(value, path)? {
+        Value::String(s) => Ok(s.clone()),
+        Value::Number(n) => Ok(n.to_string()),
+        Value::Bool(b) => Ok(b.to_string()),
+        found => Err(invalid(path, found)),
+    }
+}
+
+fn integer(value: &Value, path: &str) -> Result<i32, ProcessingError> {
+    let found = field(value, path)?;
+    match found {
+        Value::Number(n) => n.as_i64().and_then(|n| i32::try_from(n).ok()),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+    .ok_or_else(|| invalid(path, found))
+}
+
+fn decimal(value: &Value, path: &str) -> Result<Decimal, ProcessingError> {
+    let found = field(value, path)?;
+    match found {
+        Value::Number(n) => Decimal::from_str(&n.to_string())
+            .or_else(|_| Decimal::from_scientific(&n.to_string()))
+            .ok(),
+        Value::String(s) => Decimal::from_str(s.trim()).ok(),
+        _ => None,
+    }
+    .ok_or_else(|| invalid(path, found))
+}
+
+fn items<'a>(value: &'a Value, path: &str) -> Result<&'a [Value], ProcessingError> {
+    let found = field(value, path)?;
+    found
+        .as_array()
+        .map(Vec::as_slice)
+        .ok_or_else(|| invalid(path, found))
+}
+
+// Date-times without a zone are taken as UTC; date-only formats give midnight
+fn rfc3339(date: &str, format: &str) -> Result<String, ProcessingError> {
+    NaiveDateTime::parse_from_str(date, format)
+        .or_else(|_| NaiveDate::parse_from_str(date, format).map(|d| d.and_time(NaiveTime::MIN)))
+        .map(|datetime| datetime.and_utc().format("%Y-%m-%dT%H:%M:%SZ").to_string())
+        .map_err(|_| ProcessingError::InvalidDate(date.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAPPING: &str = r#"
+        [response]
+        search_id = "/meta/id"
+        currency = "/meta/currency"
+        hotels = "/results"
+
+        [hotel]
+        hotel_id = "/property/code"
+        name = "/property/name"
+        category = "/property/stars"
+        rooms = "/units"
+
+        [room]
+        room_id = "/code"
+        name = "/label"
+        adults = "/occupancy/0"
+        children = "/occupancy/1"
+        rates = "/prices"
+
+        [rate]
+        rate_id = "/key"
+        board_type = "/meal"
+        price = "/total"
+        cancellation_policies = "/fees"
+
+        [cancellation]
+        from_date = "/since"
+        amount = "/fee"
+        date_format = "%d/%m/%Y %H:%M"
+
+        [board_types]
+        "Room only" = "RO"
+        "Bed & Breakfast" = "BB"
+    "#;
+
+    const JSON: &str = r#"{
+        "meta": {"id": 42, "currency": "EUR"},
+        "results": [{
+            "property": {"code": "H1", "name": "Hotel One", "stars": "4"},
+            "units": [{
+                "code": "DBL", "label": "Double", "occupancy": [2, 1],
+                "prices": [
+                    {"key": "R1", "meal": "Bed & Breakfast", "total": "120.50",
+                     "fees": [{"since": "01/06/2025 12:00", "fee": 60}]},
+                    {"key": "R2", "meal": "All inclusive", "total": 199.99}
+                ]
+            }]
+        }]
+    }"#;
+
+    #[test]
+    fn test_mapping_adapter() {
+        let response = MappingAdapter::from_toml(MAPPING)
+            .unwrap()
+            .parse(JSON)
+            .unwrap();
+        assert_eq!(
+            (response.search_id.as_str(), response.currency.as_str()),
+            ("42", "EUR")
+        );
+        assert_eq!(response.timestamp, "");
+
+        let hotel = &response.hotels[0];
+        assert_eq!((hotel.hotel_id.as_str(), hotel.category), ("H1", 4));
+        let room = &hotel.rooms[0];
+        assert_eq!((room.capacity.adults, room.capacity.children), (2, 1));
+        let boards: Vec<&str> = room.rates.iter().map(|r| r.board_type.as_str()).collect();
+        assert_eq!(boards, vec!["BB", "All inclusive"]);
+        assert_eq!(room.rates[0].price, Decimal::new(12050, 2));
+        assert_eq!(room.rates[1].price, Decimal::new(19999, 2));
+        let policy = &room.rates[0].cancellation_policies[0];
+        assert_eq!(policy.from_date, "2025-06-01T12:00:00Z");
+        assert_eq!(policy.amount, Decimal::from(60));
+        assert!(room.rates[1].cancellation_policies.is_empty());
+    }
+
+    #[test]
+    fn test_mapping_errors() {
+        let adapter = MappingAdapter::from_toml(MAPPING).unwrap();
+        assert!(matches!(
+            adapter.parse(&JSON.replace("\"H1\"", "null")),
+            Err(ProcessingError::MissingRequiredField(path)) if path == "/property/code"
+        ));
+        assert!(matches!(
+            adapter.parse(&JSON.replace("\"120.50\"", "\"a lot\"")),
+            Err(ProcessingError::InvalidFormat(_))
+        ));
+        assert!(matches!(
+            adapter.parse(&JSON.replace("01/06/2025", "2025-06-01")),
+            Err(ProcessingError::InvalidDate(_))
+        ));
+        assert!(matches!(
+            MappingAdapter::from_toml("[response]\nsearch_id = 1"),
+            Err(ProcessingError::InvalidFormat(_))
+        ));
+    }
+}
@@ -0,0 +1,173 @@
+// Occupancy matching
+// An AvailRQ asks for rooms by RoomCandidate: how many rooms and the ages of their guests.
+// Suppliers sometimes answer with rooms too small for the guests, which used to be shown
+// anyway. `match_occupancy` drops options whose room capacity can't host the candidate they
+// answer (their roomCandidateRefId), or, without a reference, any candidate of the request.
+// Guests under `ADULT_AGE` are children; children may take adult places but not the reverse.
+// Options without a known capacity are kept.
+
+use super::request::AvailRQ;
+use super::{HotelOption, ProcessedResponse};
+use crate::supplier::RoomCapacity;
+
+pub const ADULT_AGE: u32 = 18;
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct RoomOccupancy {
+    // The RoomCandidate's id, what options refer to as roomCandidateRefId
+    pub candidate_id: u32,
+    // Rooms wanted with this occupancy
+    pub quantity: u32,
+    pub adults: i32,
+    pub child_ages: Vec<u32>,
+}
+
+impl RoomOccupancy {
+    pub fn children(&self) -> i32 {
+        self.child_ages.len() as i32
+    }
+
+    pub fn fits(&self, capacity: &RoomCapacity) -> bool {
+        let spare_adult_places = capacity.adults - self.adults;
+        spare_adult_places >= 0 && self.children() <= capacity.children + spare_adult_places
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Occupancy {
+    pub rooms: Vec<RoomOccupancy>,
+}
+
+impl Occupancy {
+    pub fn from_request(request: &AvailRQ) -> Self {
+        let rooms = request
+            .room_candidates
+            .room_candidates
+            .iter()
+            .map(|candidate| {
+                let paxes = &candidate.paxes.paxes;
+                RoomOccupancy {
+                    candidate_id: candidate.id,
+                    quantity: candidate.quantity.max(1),
+                    adults: paxes.iter().filter(|pax| pax.age >= ADULT_AGE).count() as i32,
+                    child_ages: paxes
+                        .iter()
+                        .filter(|pax| pax.age < ADULT_AGE)
+                        .map(|pax| pax.age)
+                        .collect(),
+                }
+            })
+            .collect();
+        Self { rooms }
+    }
+
+    pub fn candidate(&self, id: u32) -> Option<&RoomOccupancy> {
+        self.rooms.iter().find(|room| room.candidate_id == id)
+    }
+
+    // Whether `option`'s room can host the guests it's offered for
+    pub fn hosts(&self, option: &HotelOption) -> bool {
+        let Some(capacity) = &option.capacity else {
+            return true;
+        };
+        match option
+            .room_candidate_ref_id
+            .and_then(|id| self.candidate(id))
+        {
+            Some(candidate) => candidate.fits(capacity),
+            None => self.rooms.iter().any(|room| room.fits(capacity)),
+        }
+    }
+}
+
+// Drop the options that can't host their guests, returns how many were dropped
+pub fn match_occupancy(response: &mut ProcessedResponse, occupancy: &Occupancy) -> usize {
+    if occupancy.rooms.is_empty() {
+        return 0;
+    }
+    let before = response.hotels.len();
+    response.hotels.retain(|option| occupancy.hosts(option));
+    response.total_options = response.hotels.len();
+    before - response.total_options
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn capacity(adults: i32, children: i32) -> RoomCapacity {
+        RoomCapacity { adults, children }
+    }
+
+    #[test]
+    fn test_occupancy_from_request() {
+        let date = NaiveDate::from_ymd_opt(2025, 7, 1).unwrap();
+        let request = AvailRQ::new("EUR", "ES", date, date)
+            .room(&[30, 28])
+            .room(&[40, 8, 17]);
+        let occupancy = Occupancy::from_request(&request);
+
+        let family = occupancy.candidate(2).unwrap();
+        assert_eq!((family.adults, family.child_ages.clone()), (1, vec![8, 17]));
+        assert!(family.fits(&capacity(1, 2)));
+        // Children can take adult places
+        assert!(family.fits(&capacity(3, 0)));
+        assert!(!family.fits(&capacity(2, 0)));
+
+        let couple = occupancy.candidate(1).unwrap();
+        assert!(couple.fits(&capacity(2, 0)));
+        assert!(!couple.fits(&capacity(1, 2)));
+    }
+
+    #[test]
+    fn test_match_occupancy() {
+        let date = NaiveDate::from_ymd_opt(2025, 7, 1).unwrap();
+        let occupancy = Occupancy::from_request(
+            &AvailRQ::new("EUR", "ES", date, date)
+                .room(&[30, 28])
+                .room(&[40, 8]),
+        );
+        let option =
+            |token: &str, capacity: Option<RoomCapacity>, candidate: Option<u32>| HotelOption {
+                search_token: token.to_string(),
+                capacity,
+                room_candidate_ref_id: candidate,
+                ..Default::default()
+            };
+        let hotels = vec![
+            option("single for the couple", Some(capacity(1, 0)), Some(1)),
+            option("double for the couple", Some(capacity(2, 0)), Some(1)),
+            option("single for the family", Some(capacity(1, 0)), Some(2)),
+            option("unknown capacity", None, Some(1)),
+            option("single for anyone", Some(capacity(1, 0)), None),
+            option("family room for anyone", Some(capacity(2, 1)), None),
+        ];
+        let mut response = ProcessedResponse {
+            search_id: String::new(),
+            total_options: hotels.len(),
+            hotels,
+            currency: "EUR".to_string(),
+            nationality: String::new(),
+            check_in: None,
+            check_out: None,
+            markets: Vec::new(),
+        };
+
+        assert_eq!(match_occupancy(&mut response, &occupancy), 3);
+        let kept: Vec<&str> = response
+            .hotels
+            .iter()
+            .map(|option| option.search_token.as_str())
+            .collect();
+        assert_eq!(
+            kept,
+            vec![
+                "double for the couple",
+                "unknown capacity",
+                "family room for anyone"
+            ]
+        );
+        assert_eq!(response.total_options, 3);
+    }
+}
@@ -0,0 +1,594 @@
+// OpenTravel OTA_HotelAvailRS
+// Some buyers only read the OTA standard message, not our AvailRS. `OtaHotelAvailRs` is built
+// from a converted AvailRS, so pricing, combined rooms and signed tokens come out the same in
+// both formats. Every option becomes a RoomStay (its rooms as room types, rate plans and room
+// rates, priced before and after the taxes not included); every hotel a HotelStay referring to
+// its room stays by RPH. Board codes are written as OTA meal plan type (MPT) codes where one
+// matches. Things OTA has no place for travel in TPA_Extensions: the search token to value and
+// book with, and the payment type.
+
+use super::cancellation::PENALTY_PERCENTAGE;
+use crate::xml_response::{XmlCancelPenalties, XmlOption, XmlProcessedResponse, XmlRoom};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::str::FromStr;
+
+pub const OTA_NAMESPACE: &str = "http://www.opentravel.org/OTA/2003/05";
+pub const OTA_VERSION: &str = "1.000";
+
+// OTA tax types
+const INCLUSIVE: &str = "Inclusive";
+const EXCLUSIVE: &str = "Exclusive";
+
+// OTA AgeQualifyingCode
+const ADULT: &str = "10";
+const CHILD: &str = "8";
+
+// Our board codes and the OTA MPT codes for them
+const MEAL_PLAN_CODES: &[(&str, &str)] = &[
+    ("AI", "1"),
+    ("BB", "3"),
+    ("FB", "10"),
+    ("HB", "12"),
+    ("RO", "14"),
+];
+
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename = "OTA_HotelAvailRS")]
+pub struct OtaHotelAvailRs {
+    #[serde(rename = "@xmlns")]
+    pub namespace: String,
+    #[serde(rename = "@Version")]
+    pub version: String,
+    #[serde(rename = "Success")]
+    pub success: (),
+    #[serde(rename = "HotelStays")]
+    pub hotel_stays: OtaHotelStays,
+    #[serde(rename = "RoomStays")]
+    pub room_stays: OtaRoomStays,
+}
+
+#[derive(Debug, PartialEq, Default, Serialize)]
+pub struct OtaHotelStays {
+    #[serde(rename = "HotelStay")]
+    pub hotel_stays: Vec<OtaHotelStay>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct OtaHotelStay {
+    // RPHs of the hotel's room stays, separated by spaces
+    #[serde(rename = "@RoomStayRPH")]
+    pub room_stay_rph: String,
+    #[serde(rename = "BasicPropertyInfo")]
+    pub property: OtaBasicPropertyInfo,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct OtaBasicPropertyInfo {
+    #[serde(rename = "@HotelCode")]
+    pub hotel_code: String,
+    #[serde(rename = "@HotelName")]
+    pub hotel_name: String,
+    #[serde(rename = "@HotelCityCode", skip_serializing_if = "Option::is_none")]
+    pub hotel_city_code: Option<String>,
+    #[serde(rename = "Award", skip_serializing_if = "Option::is_none")]
+    pub award: Option<OtaAward>,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct OtaAward {
+    #[serde(rename = "@Rating")]
+    pub rating: i32,
+}
+
+#[derive(Debug, PartialEq, Default, Serialize)]
+pub struct OtaRoomStays {
+    #[serde(rename = "RoomStay")]
+    pub room_stays: Vec<OtaRoomStay>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct OtaRoomStay {
+    #[serde(rename = "@RPH")]
+    pub rph: String,
+    #[serde(rename = "RoomTypes")]
+    pub room_types: OtaRoomTypes,
+    #[serde(rename = "RatePlans")]
+    pub rate_plans: OtaRatePlans,
+    #[serde(rename = "RoomRates")]
+    pub room_rates: OtaRoomRates,
+    #[serde(rename = "GuestCounts")]
+    pub guest_counts: OtaGuestCounts,
+    #[serde(rename = "Total")]
+    pub total: OtaTotal,
+    #[serde(rename = "BasicPropertyInfo")]
+    pub property: OtaBasicPropertyInfo,
+    #[serde(rename = "TPA_Extensions")]
+    pub extensions: OtaExtensions,
+}
+
+#[derive(Debug, PartialEq, Default, Serialize)]
+pub struct OtaRoomTypes {
+    #[serde(rename = "RoomType")]
+    pub room_types: Vec<OtaRoomType>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct OtaRoomType {
+    #[serde(rename = "@RoomTypeCode")]
+    pub room_type_code: String,
+    #[serde(rename = "@NumberOfUnits")]
+    pub number_of_units: String,
+    #[serde(rename = "RoomDescription")]
+    pub description: OtaDescription,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct OtaDescription {
+    #[serde(rename = "@Name")]
+    pub name: String,
+}
+
+#[derive(Debug, PartialEq, Default, Serialize)]
+pub struct OtaRatePlans {
+    #[serde(rename = "RatePlan")]
+    pub rate_plans: Vec<OtaRatePlan>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct OtaRatePlan {
+    #[serde(rename = "@RatePlanCode")]
+    pub rate_plan_code: String,
+    #[serde(rename = "@PromotionCode", skip_serializing_if = "Option::is_none")]
+    pub promotion_code: Option<String>,
+    #[serde(rename = "CancelPenalties")]
+    pub cancel_penalties: OtaCancelPenalties,
+    #[serde(rename = "MealsIncluded")]
+    pub meals_included: OtaMealsIncluded,
+}
+
+#[derive(Debug, PartialEq, Default, Serialize)]
+pub struct OtaCancelPenalties {
+    #[serde(rename = "CancelPenalty")]
+    pub cancel_penalties: Vec<OtaCancelPenalty>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct OtaCancelPenalty {
+    #[serde(rename = "@NonRefundable")]
+    pub non_refundable: bool,
+    #[serde(rename = "Deadline", skip_serializing_if = "Option::is_none")]
+    pub deadline: Option<OtaDeadline>,
+    #[serde(rename = "AmountPercent", skip_serializing_if = "Option::is_none")]
+    pub amount_percent: Option<OtaAmountPercent>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct OtaDeadline {
+    #[serde(rename = "@AbsoluteDeadline")]
+    pub absolute_deadline: String,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct OtaAmountPercent {
+    #[serde(rename = "@Amount", skip_serializing_if = "Option::is_none")]
+    pub amount: Option<String>,
+    #[serde(rename = "@Percent", skip_serializing_if = "Option::is_none")]
+    pub percent: Option<String>,
+    #[serde(rename = "@CurrencyCode", skip_serializing_if = "Option::is_none")]
+    pub currency_code: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct OtaMealsIncluded {
+    #[serde(rename = "@MealPlanCodes", skip_serializing_if = "Option::is_none")]
+    pub meal_plan_codes: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Default, Serialize)]
+pub struct OtaRoomRates {
+    #[serde(rename = "RoomRate")]
+    pub room_rates: Vec<OtaRoomRate>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct OtaRoomRate {
+    #[serde(rename = "@RoomTypeCode")]
+    pub room_type_code: String,
+    #[serde(rename = "@RatePlanCode")]
+    pub rate_plan_code: String,
+    #[serde(rename = "@NumberOfUnits")]
+    pub number_of_units: String,
+    #[serde(rename = "Total")]
+    pub total: OtaTotal,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct OtaTotal {
+    #[serde(rename = "@AmountBeforeTax")]
+    pub amount_before_tax: String,
+    #[serde(rename = "@AmountAfterTax")]
+    pub amount_after_tax: String,
+    #[serde(rename = "@CurrencyCode")]
+    pub currency_code: String,
+    #[serde(rename = "Taxes", skip_serializing_if = "OtaTaxes::is_empty")]
+    pub taxes: OtaTaxes,
+}
+
+#[derive(Debug, PartialEq, Default, Serialize)]
+pub struct OtaTaxes {
+    #[serde(rename = "Tax")]
+    pub taxes: Vec<OtaTax>,
+}
+
+impl OtaTaxes {
+    pub fn is_empty(&self) -> bool {
+        self.taxes.is_empty()
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct OtaTax {
+    // Inclusive or Exclusive
+    #[serde(rename = "@Type")]
+    pub tax_type: String,
+    #[serde(rename = "@Amount")]
+    pub amount: String,
+    #[serde(rename = "@CurrencyCode")]
+    pub currency_code: String,
+    #[serde(rename = "@Percent", skip_serializing_if = "Option::is_none")]
+    pub percent: Option<String>,
+    #[serde(rename = "TaxDescription")]
+    pub description: OtaDescription,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct OtaGuestCounts {
+    #[serde(rename = "GuestCount")]
+    pub guest_counts: Vec<OtaGuestCount>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct OtaGuestCount {
+    #[serde(rename = "@AgeQualifyingCode")]
+    pub age_qualifying_code: String,
+    #[serde(rename = "@Count")]
+    pub count: i32,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct OtaExtensions {
+    #[serde(rename = "SearchToken")]
+    pub search_token: String,
+    #[serde(rename = "PaymentType")]
+    pub payment_type: String,
+}
+
+impl OtaHotelAvailRs {
+    pub fn from_avail_rs(response: &XmlProcessedResponse) -> Self {
+        let mut hotel_stays = Vec::new();
+        let mut room_stays = Vec::new();
+        for hotel in &response.hotels.hotels {
+            let property = OtaBasicPropertyInfo {
+                hotel_code: hotel.hotel_id.clone(),
+                hotel_name: hotel.hotel_name.clone(),
+                hotel_city_code: hotel.destination_code.clone(),
+                award: hotel.category.map(|rating| OtaAward { rating }),
+            };
+            let mut rphs = Vec::new();
+            for meal_plan in &hotel.meal_plans.meal_plans {
+                for option in &meal_plan.options.options {
+                    let rph = room_stays.len().to_string();
+                    room_stays.push(room_stay(&rph, &meal_plan.code, option, &property));
+                    rphs.push(rph);
+                }
+            }
+            hotel_stays.push(OtaHotelStay {
+                room_stay_rph: rphs.join(" "),
+                property,
+            });
+        }
+        Self {
+            namespace: OTA_NAMESPACE.to_string(),
+            version: OTA_VERSION.to_string(),
+            success: (),
+            hotel_stays: OtaHotelStays { hotel_stays },
+            room_stays: OtaRoomStays { room_stays },
+        }
+    }
+}
+
+fn room_stay(
+    rph: &str,
+    board_code: &str,
+    option: &XmlOption,
+    property: &OtaBasicPropertyInfo,
+) -> OtaRoomStay {
+    let rooms = &option.rooms.rooms;
+    let room_rates: Vec<OtaRoomRate> = rooms
+        .iter()
+        .map(|room| OtaRoomRate {
+            room_type_code: room.code.clone(),
+            rate_plan_code: rate_plan_code(room),
+            number_of_units: room.number_of_units.clone(),
+            total: room_total(room),
+        })
+        .collect();
+    let excluded = excluded_taxes(room_rates.iter().flat_map(|rate| &rate.total.taxes.taxes));
+    let total = OtaTotal {
+        amount_before_tax: option.price.amount.clone(),
+        amount_after_tax: after_tax(&option.price.amount, excluded),
+        currency_code: option.price.currency.clone(),
+        taxes: OtaTaxes::default(),
+    };
+
+    OtaRoomStay {
+        rph: rph.to_string(),
+        room_types: OtaRoomTypes {
+            room_types: rooms
+                .iter()
+                .map(|room| OtaRoomType {
+                    room_type_code: room.code.clone(),
+                    number_of_units: room.number_of_units.clone(),
+                    description: OtaDescription {
+                        name: room.description.clone(),
+                    },
+                })
+                .collect(),
+        },
+        rate_plans: OtaRatePlans {
+            rate_plans: rooms
+                .iter()
+                .map(|room| OtaRatePlan {
+                    rate_plan_code: rate_plan_code(room),
+                    promotion_code: room.offers.offers.first().map(|offer| offer.code.clone()),
+                    cancel_penalties: cancel_penalties(&room.cancel_penalties),
+                    meals_included: OtaMealsIncluded {
+                        meal_plan_codes: meal_plan_code(board_code).map(str::to_string),
+                    },
+                })
+                .collect(),
+        },
+        room_rates: OtaRoomRates { room_rates },
+        guest_counts: guest_counts(rooms),
+        total,
+        property: property.clone(),
+        extensions: OtaExtensions {
+            search_token: option
+                .parameters
+                .parameters
+                .iter()
+                .find(|parameter| parameter.key == "search_token")
+                .map(|parameter| parameter.value.clone())
+                .unwrap_or_default(),
+            payment_type: option.payment_type.clone(),
+        },
+    }
+}
+
+// The rate's booking code, the room code for responses without one
+fn rate_plan_code(room: &XmlRoom) -> String {
+    room.booking_code
+        .clone()
+        .unwrap_or_else(|| room.code.clone())
+}
+
+fn meal_plan_code(board_code: &str) -> Option<&'static str> {
+    MEAL_PLAN_CODES
+        .iter()
+        .find(|(board, _)| *board == board_code)
+        .map(|(_, code)| *code)
+}
+
+fn room_total(room: &XmlRoom) -> OtaTotal {
+    let taxes: Vec<OtaTax> = room
+        .taxes
+        .taxes
+        .iter()
+        .map(|tax| OtaTax {
+            tax_type: if tax.included == "true" {
+                INCLUSIVE
+            } else {
+                EXCLUSIVE
+            }
+            .to_string(),
+            amount: tax.amount.clone(),
+            currency_code: tax.currency.clone(),
+            percent: tax.percent.clone(),
+            description: OtaDescription {
+                name: tax.name.clone(),
+            },
+        })
+        .collect();
+    let excluded = excluded_taxes(&taxes);
+    OtaTotal {
+        amount_before_tax: room.price.amount.clone(),
+        amount_after_tax: after_tax(&room.price.amount, excluded),
+        currency_code: room.price.currency.clone(),
+        taxes: OtaTaxes { taxes },
+    }
+}
+
+fn excluded_taxes<'a>(taxes: impl IntoIterator<Item = &'a OtaTax>) -> Decimal {
+    taxes
+        .into_iter()
+        .filter(|tax| tax.tax_type == EXCLUSIVE)
+        .filter_map(|tax| Decimal::from_str(&tax.amount).ok())
+        .sum()
+}
+
+// `amount` plus the taxes not included in it; the amount as written if it isn't a number
+fn after_tax(amount: &str, excluded_taxes: Decimal) -> String {
+    match Decimal::from_str(amount) {
+        Ok(value) if !excluded_taxes.is_zero() => {
+            format!("{:.2}", (value + excluded_taxes).round_dp(2))
+        }
+        _ => amount.to_string(),
+    }
+}
+
+fn cancel_penalties(penalties: &XmlCancelPenalties) -> OtaCancelPenalties {
+    if penalties.non_refundable == "true" {
+        return OtaCancelPenalties {
+            cancel_penalties: vec![OtaCancelPenalty {
+                non_refundable: true,
+                deadline: None,
+                amount_percent: None,
+            }],
+        };
+    }
+    OtaCancelPenalties {
+        cancel_penalties: penalties
+            .cancel_penalties
+            .iter()
+            .map(|penalty| {
+                let percentage = penalty.penalty.penalty_type == PENALTY_PERCENTAGE;
+                OtaCancelPenalty {
+                    non_refundable: false,
+                    deadline: Some(OtaDeadline {
+                        absolute_deadline: penalty.deadline.clone(),
+                    }),
+                    amount_percent: Some(OtaAmountPercent {
+                        amount: (!percentage).then(|| penalty.penalty.value.clone()),
+                        percent: percentage.then(|| penalty.penalty.value.clone()),
+                        currency_code: (!percentage).then(|| penalty.penalty.currency.clone()),
+                    }),
+                }
+            })
+            .collect(),
+    }
+}
+
+// Adults and children of all the option's rooms; rooms without counts add none
+fn guest_counts(rooms: &[XmlRoom]) -> OtaGuestCounts {
+    let adults: i32 = rooms.iter().filter_map(|room| room.adults).sum();
+    let children: i32 = rooms.iter().filter_map(|room| room.children).sum();
+    let mut guest_counts = vec![OtaGuestCount {
+        age_qualifying_code: ADULT.to_string(),
+        count: adults,
+    }];
+    if children > 0 {
+        guest_counts.push(OtaGuestCount {
+            age_qualifying_code: CHILD.to_string(),
+            count: children,
+        });
+    }
+    OtaGuestCounts { guest_counts }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::part2_xml::snapshot::assert_snapshot;
+    use crate::part2_xml::{
+        synthetic_response, synthetic_supplier_json, HotelSearchProcessor, OutputFormat,
+        XmlWriteOptions,
+    };
+    use crate::xml_response::{XmlPenalty, XmlTax};
+
+    #[test]
+    fn test_ota_snapshot() {
+        let processor =
+            HotelSearchProcessor::new().with_write_options(XmlWriteOptions::new().indent(2));
+        let mut xml = processor
+            .convert_json_to_xml_as(
+                &synthetic_supplier_json(3, 47),
+                OutputFormat::OtaHotelAvailRs,
+            )
+            .unwrap();
+        xml.push('\n');
+        assert_snapshot("ota_several_hotels", &xml);
+    }
+
+    #[test]
+    fn test_from_avail_rs() {
+        let response = XmlProcessedResponse::from(synthetic_response(3, 47));
+        let ota = OtaHotelAvailRs::from_avail_rs(&response);
+        assert_eq!(ota.room_stays.room_stays.len(), 47);
+        assert_eq!(ota.hotel_stays.hotel_stays.len(), 3);
+        assert!(ota.hotel_stays.hotel_stays[0]
+            .room_stay_rph
+            .starts_with("0 1 2 "));
+        assert_eq!(
+            ota.hotel_stays.hotel_stays[2].room_stay_rph,
+            (40..47)
+                .map(|rph| rph.to_string())
+                .collect::<Vec<_>>()
+                .join(" ")
+        );
+        for room_stay in &ota.room_stays.room_stays {
+            assert!(room_stay
+                .extensions
+                .search_token
+                .starts_with(&format!("{}|", room_stay.property.hotel_code)));
+            assert!(room_stay.rate_plans.rate_plans[0]
+                .meals_included
+                .meal_plan_codes
+                .is_some());
+        }
+    }
+
+    #[test]
+    fn test_taxes_and_penalties() {
+        let mut response = XmlProcessedResponse::from(synthetic_response(3, 1));
+        let room = &mut response.hotels.hotels[0].meal_plans.meal_plans[0]
+            .options
+            .options[0]
+            .rooms
+            .rooms[0];
+        room.price.amount = "100.00".to_string();
+        room.taxes.taxes = vec![
+            XmlTax {
+                name: "VAT".to_string(),
+                included: "true".to_string(),
+                amount: "10.00".to_string(),
+                currency: "EUR".to_string(),
+                ..Default::default()
+            },
+            XmlTax {
+                name: "City tax".to_string(),
+                included: "false".to_string(),
+                amount: "5.50".to_string(),
+                currency: "EUR".to_string(),
+                ..Default::default()
+            },
+        ];
+        room.cancel_penalties.non_refundable = "false".to_string();
+        room.cancel_penalties.cancel_penalties = vec![crate::xml_response::XmlCancelPenalty {
+            hours_before: "N/A".to_string(),
+            penalty: XmlPenalty {
+                penalty_type: PENALTY_PERCENTAGE.to_string(),
+                currency: String::new(),
+                value: "50".to_string(),
+            },
+            deadline: "2025-06-10T00:00:00Z".to_string(),
+        }];
+
+        let ota = OtaHotelAvailRs::from_avail_rs(&response);
+        let room_stay = &ota.room_stays.room_stays[0];
+        let total = &room_stay.room_rates.room_rates[0].total;
+        assert_eq!(total.amount_before_tax, "100.00");
+        assert_eq!(total.amount_after_tax, "105.50");
+        assert_eq!(total.taxes.taxes[0].tax_type, INCLUSIVE);
+        assert_eq!(total.taxes.taxes[1].tax_type, EXCLUSIVE);
+        let penalty = &room_stay.rate_plans.rate_plans[0]
+            .cancel_penalties
+            .cancel_penalties[0];
+        let amount_percent = penalty.amount_percent.as_ref().unwrap();
+        assert_eq!(amount_percent.percent.as_deref(), Some("50"));
+        assert_eq!(amount_percent.amount, None);
+    }
+
+    #[test]
+    fn test_non_refundable() {
+        let mut penalties = XmlCancelPenalties {
+            non_refundable: "true".to_string(),
+            ..Default::default()
+        };
+        let ota = cancel_penalties(&penalties);
+        assert_eq!(ota.cancel_penalties.len(), 1);
+        assert!(ota.cancel_penalties[0].non_refundable);
+        penalties.non_refundable = "false".to_string();
+        assert!(cancel_penalties(&penalties).cancel_penalties.is_empty());
+    }
+}
@@ -0,0 +1,183 @@
+// XML output options
+// `quick_xml::se::to_string` writes a compact document without an XML declaration, which is
+// what we send by default. Some partners reject that, so `XmlWriteOptions` can add a
+// declaration, indent the document and put AvailRS in a namespace. The document is always
+// UTF-8; `encoding` only changes how the declaration spells it.
+// `OutputFormat` chooses the message written: our AvailRS, or the OpenTravel OTA_HotelAvailRS
+// some buyers require, see `ota`. The write options apply to both; an OTA document is always
+// in a namespace, OpenTravel's unless `namespace` names another.
+
+use super::ota::OtaHotelAvailRs;
+use super::ProcessingError;
+use crate::XmlProcessedResponse;
+use quick_xml::escape::escape;
+use quick_xml::se::Serializer;
+use serde::Serialize;
+
+const ROOT: &str = "<AvailRS";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XmlWriteOptions {
+    // Spaces per level, None for a compact document
+    pub indent: Option<usize>,
+    // Write <?xml version="1.0" encoding="..."?>
+    pub declaration: bool,
+    pub encoding: String,
+    // Default namespace of AvailRS
+    pub namespace: Option<String>,
+}
+
+impl Default for XmlWriteOptions {
+    fn default() -> Self {
+        Self {
+            indent: None,
+            declaration: false,
+            encoding: "UTF-8".to_string(),
+            namespace: None,
+        }
+    }
+}
+
+impl XmlWriteOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn indent(mut self, spaces: usize) -> Self {
+        self.indent = Some(spaces);
+        self
+    }
+
+    pub fn declaration(mut self) -> Self {
+        self.declaration = true;
+        self
+    }
+
+    // Implies the declaration
+    pub fn encoding(mut self, encoding: impl Into<String>) -> Self {
+        self.encoding = encoding.into();
+        self.declaration = true;
+        self
+    }
+
+    pub fn namespace(mut self, namespace: impl Into<String>) -> Self {
+        self.namespace = Some(namespace.into());
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    // The hub's AvailRS
+    #[default]
+    AvailRs,
+    // OpenTravel OTA_HotelAvailRS
+    OtaHotelAvailRs,
+}
+
+pub fn write_response(
+    response: &XmlProcessedResponse,
+    format: OutputFormat,
+    options: &XmlWriteOptions,
+) -> Result<String, ProcessingError> {
+    match format {
+        OutputFormat::AvailRs => write_xml(response, options),
+        OutputFormat::OtaHotelAvailRs => {
+            let mut ota = OtaHotelAvailRs::from_avail_rs(response);
+            if let Some(namespace) = &options.namespace {
+                ota.namespace = namespace.clone();
+            }
+            let body = serialize(&ota, options)?;
+            Ok(with_declaration(body, options))
+        }
+    }
+}
+
+pub fn write_xml(
+    response: &XmlProcessedResponse,
+    options: &XmlWriteOptions,
+) -> Result<String, ProcessingError> {
+    let mut body = serialize(response, options)?;
+
+    // The serializer has no attributes of its own for the root, so the namespace is added to
+    // the start tag it wrote
+    if let Some(namespace) = &options.namespace {
+        let insert_at = body
+            .find(ROOT)
+            .map(|start| start + ROOT.len())
+            .ok_or_else(|| ProcessingError::ConversionError("no AvailRS element".to_string()))?;
+        body.insert_str(
+            insert_at,
+            &format!(" xmlns=\"{}\"", escape(namespace.as_str())),
+        );
+    }
+
+    Ok(with_declaration(body, options))
+}
+
+fn serialize(value: &impl Serialize, options: &XmlWriteOptions) -> Result<String, ProcessingError> {
+    let mut body = String::new();
+    let mut serializer = Serializer::new(&mut body);
+    if let Some(spaces) = options.indent {
+        serializer.indent(' ', spaces);
+    }
+    value
+        .serialize(serializer)
+        .map_err(|e| ProcessingError::ConversionError(e.to_string()))?;
+    Ok(body)
+}
+
+fn with_declaration(body: String, options: &XmlWriteOptions) -> String {
+    if !options.declaration {
+        return body;
+    }
+    let separator = if options.indent.is_some() { "\n" } else { "" };
+    format!(
+        "<?xml version=\"1.0\" encoding=\"{}\"?>{}{}",
+        escape(options.encoding.as_str()),
+        separator,
+        body
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::part2_xml::HotelSearchProcessor;
+
+    fn sample() -> XmlProcessedResponse {
+        let processor = HotelSearchProcessor::new();
+        quick_xml::de::from_str(&processor.load_sample_response().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_default_options_are_compact() {
+        let response = sample();
+        let xml = write_xml(&response, &XmlWriteOptions::default()).unwrap();
+        assert_eq!(xml, quick_xml::se::to_string(&response).unwrap());
+    }
+
+    #[test]
+    fn test_write_options() {
+        let options = XmlWriteOptions::new()
+            .indent(2)
+            .encoding("utf-8")
+            .namespace("http://www.example.com/availability");
+        let xml = write_xml(&sample(), &options).unwrap();
+
+        let mut lines = xml.lines();
+        assert_eq!(
+            lines.next(),
+            Some(r#"<?xml version="1.0" encoding="utf-8"?>"#)
+        );
+        assert_eq!(
+            lines.next(),
+            Some(r#"<AvailRS xmlns="http://www.example.com/availability">"#)
+        );
+        assert_eq!(lines.next(), Some("  <Hotels>"));
+
+        // Still the same response
+        let parsed: XmlProcessedResponse = quick_xml::de::from_str(&xml).unwrap();
+        assert_eq!(parsed, sample());
+    }
+}
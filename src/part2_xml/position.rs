@@ -0,0 +1,230 @@
+// Error positions
+// A syntax error is reported with the byte offset quick_xml stopped at, the line and column of
+// that offset and the path of the elements open there, e.g.
+//   XML syntax error at line 12, column 9 (byte 431) in /AvailRS/Hotels/Hotel: ...
+// Documents held in memory are located by scanning the source. Streamed input isn't kept, so
+// `LineCounter` counts lines as the reader consumes it, remembering recent line starts so an
+// error slightly behind the read position still gets its exact line.
+
+use super::ProcessingError;
+use quick_xml::events::Event;
+use quick_xml::reader::Reader;
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{self, BufRead, Read};
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct XmlPosition {
+    pub offset: u64,
+    // Both 1-based; columns count bytes
+    pub line: usize,
+    pub column: usize,
+    // Open elements, outermost first, e.g. "/AvailRS/Hotels/Hotel"; "/" outside the root
+    pub path: String,
+}
+
+impl fmt::Display for XmlPosition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {} (byte {}) in {}",
+            self.line, self.column, self.offset, self.path
+        )
+    }
+}
+
+pub(crate) fn element_path(open: &[String]) -> String {
+    if open.is_empty() {
+        "/".to_string()
+    } else {
+        format!("/{}", open.join("/"))
+    }
+}
+
+// The position of `offset` in a document held in memory, with the elements open there
+pub(crate) fn locate(source: &str, offset: u64) -> XmlPosition {
+    let before = &source.as_bytes()[..(offset as usize).min(source.len())];
+    let line_start = before
+        .iter()
+        .rposition(|&b| b == b'\n')
+        .map_or(0, |i| i + 1);
+
+    let mut reader = Reader::from_str(source);
+    let mut open = Vec::new();
+    while reader.buffer_position() < offset {
+        match reader.read_event() {
+            Ok(Event::Start(start)) => {
+                open.push(String::from_utf8_lossy(start.name().as_ref()).into_owned())
+            }
+            Ok(Event::End(_)) => {
+                open.pop();
+            }
+            Ok(Event::Eof) | Err(_) => break,
+            Ok(_) => {}
+        }
+    }
+
+    XmlPosition {
+        offset,
+        line: before.iter().filter(|&&b| b == b'\n').count() + 1,
+        column: before.len() - line_start + 1,
+        path: element_path(&open),
+    }
+}
+
+// The error quick_xml's `reader` stopped at while reading `source`
+pub(crate) fn syntax_error(
+    source: &str,
+    reader: &Reader<&[u8]>,
+    error: quick_xml::Error,
+) -> ProcessingError {
+    ProcessingError::XmlSyntaxError {
+        message: error.to_string(),
+        position: locate(source, reader.error_position()),
+    }
+}
+
+// Errors deserializing a document held in memory: syntax errors are located in `source`, the
+// rest (missing or mistyped content) have no position
+pub(crate) fn de_error(source: &str, error: quick_xml::DeError) -> ProcessingError {
+    if let quick_xml::DeError::InvalidXml(_) = error {
+        let mut reader = Reader::from_str(source);
+        loop {
+            match reader.read_event() {
+                Ok(Event::Eof) => break,
+                Err(e) => return syntax_error(source, &reader, e),
+                Ok(_) => {}
+            }
+        }
+    }
+    ProcessingError::XmlParseError(error.to_string())
+}
+
+// Line starts older than this many bytes behind the read position are only counted
+const LINE_WINDOW: u64 = 64 * 1024;
+
+// Counts the lines of the input consumed so far
+pub(crate) struct LineCounter<R> {
+    inner: R,
+    consumed: u64,
+    // Bytes that may be read, see `limits`; `exceeded` once more were offered
+    max_bytes: u64,
+    pub(crate) exceeded: bool,
+    // Offsets just after recent newlines, and how many were dropped from the front
+    line_starts: VecDeque<u64>,
+    dropped_lines: usize,
+    last_dropped: u64,
+}
+
+impl<R: BufRead> LineCounter<R> {
+    pub(crate) fn new(inner: R) -> Self {
+        Self {
+            inner,
+            consumed: 0,
+            max_bytes: u64::MAX,
+            exceeded: false,
+            line_starts: VecDeque::new(),
+            dropped_lines: 0,
+            last_dropped: 0,
+        }
+    }
+
+    pub(crate) fn limit(&mut self, max_bytes: u64) {
+        self.max_bytes = max_bytes;
+    }
+
+    // Line and column of `offset`, exact unless it's more than `LINE_WINDOW` behind
+    pub(crate) fn line_column(&self, offset: u64) -> (usize, usize) {
+        let recent = self.line_starts.partition_point(|&start| start <= offset);
+        let line_start = match recent {
+            0 => self.last_dropped,
+            n => self.line_starts[n - 1],
+        };
+        (
+            self.dropped_lines + recent + 1,
+            offset.saturating_sub(line_start) as usize + 1,
+        )
+    }
+}
+
+impl<R: BufRead> Read for LineCounter<R> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let count = available.len().min(out.len());
+        out[..count].copy_from_slice(&available[..count]);
+        self.consume(count);
+        Ok(count)
+    }
+}
+
+impl<R: BufRead> BufRead for LineCounter<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        let allowed = self.max_bytes - self.consumed;
+        let buffer = self.inner.fill_buf()?;
+        if allowed == 0 && !buffer.is_empty() {
+            self.exceeded = true;
+            return Err(io::Error::other("document too large"));
+        }
+        Ok(&buffer[..buffer.len().min(allowed.min(usize::MAX as u64) as usize)])
+    }
+
+    fn consume(&mut self, amount: usize) {
+        // The buffer is filled already, so this doesn't read
+        if let Ok(buffer) = self.inner.fill_buf() {
+            for (i, &byte) in buffer[..amount.min(buffer.len())].iter().enumerate() {
+                if byte == b'\n' {
+                    self.line_starts.push_back(self.consumed + i as u64 + 1);
+                }
+            }
+        }
+        self.inner.consume(amount);
+        self.consumed += amount as u64;
+        while let Some(&start) = self.line_starts.front() {
+            if start + LINE_WINDOW >= self.consumed {
+                break;
+            }
+            self.line_starts.pop_front();
+            self.dropped_lines += 1;
+            self.last_dropped = start;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_locate() {
+        let xml = "<AvailRS>\n  <Hotels>\n    <Hotel code=\"1\">\n  </Hotels>\n</AvailRS>";
+        let mut reader = Reader::from_str(xml);
+        let error = loop {
+            match reader.read_event() {
+                Err(e) => break syntax_error(xml, &reader, e),
+                Ok(Event::Eof) => panic!("document is malformed"),
+                Ok(_) => {}
+            }
+        };
+        match error {
+            ProcessingError::XmlSyntaxError { position, .. } => {
+                assert_eq!((position.line, position.column), (4, 3));
+                assert_eq!(position.path, "/AvailRS/Hotels/Hotel");
+            }
+            other => panic!("expected a syntax error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_line_counter() {
+        let xml = "<a>\n<b>\n\n<c/>";
+        let mut counter = LineCounter::new(xml.as_bytes());
+        let mut read = String::new();
+        counter.read_to_string(&mut read).unwrap();
+        assert_eq!(counter.line_column(0), (1, 1));
+        assert_eq!(counter.line_column(6), (2, 3));
+        assert_eq!(counter.line_column(9), (4, 1));
+        let position = locate(xml, 9);
+        assert_eq!((position.line, position.column), (4, 1));
+        assert_eq!(position.path, "/a/b");
+    }
+}
@@ -0,0 +1,171 @@
+// Markup and commission
+// Supplier prices are net: what we pay the supplier. `PricingRules` turn a net price into the
+// price the option is sold at: a percentage or fixed markup, rounded to the configured
+// increment and never below the minimum margin (rounding that would go under it rounds up
+// instead). Converted responses carry the selling price as minimumSellingPrice and the
+// difference as commission; processed options keep both prices, taking the selling price from
+// the response if it has one and from the processor's rules otherwise.
+
+use super::HotelOption;
+use crate::money::Money;
+use rust_decimal::{Decimal, RoundingStrategy};
+
+const HUNDRED: Decimal = Decimal::ONE_HUNDRED;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Markup {
+    // Percent of the net price, e.g. 12.5
+    Percentage(Decimal),
+    // Added to the net price, in the option's currency
+    Fixed(Decimal),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Rounding {
+    // To the cent, half-cents away from zero
+    #[default]
+    Cents,
+    // Up to a multiple of the increment, e.g. 1 for whole amounts
+    Up(Decimal),
+    // To the nearest multiple of the increment, halves up
+    Nearest(Decimal),
+}
+
+impl Rounding {
+    fn round(self, amount: Decimal) -> Decimal {
+        match self {
+            Rounding::Up(increment) if increment > Decimal::ZERO => {
+                (amount / increment).ceil() * increment
+            }
+            Rounding::Nearest(increment) if increment > Decimal::ZERO => {
+                (amount / increment)
+                    .round_dp_with_strategy(0, RoundingStrategy::MidpointAwayFromZero)
+                    * increment
+            }
+            // Increments that aren't positive round to the cent
+            _ => amount.round_dp_with_strategy(2, RoundingStrategy::MidpointAwayFromZero),
+        }
+    }
+
+    fn round_up(self, amount: Decimal) -> Decimal {
+        match self {
+            Rounding::Up(increment) | Rounding::Nearest(increment) if increment > Decimal::ZERO => {
+                (amount / increment).ceil() * increment
+            }
+            _ => amount.round_dp_with_strategy(2, RoundingStrategy::ToPositiveInfinity),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PricingRules {
+    pub markup: Markup,
+    pub rounding: Rounding,
+    // Percent of the net price the selling price is at least above it
+    pub min_margin: Decimal,
+}
+
+impl PricingRules {
+    pub fn new(markup: Markup) -> Self {
+        Self {
+            markup,
+            rounding: Rounding::default(),
+            min_margin: Decimal::ZERO,
+        }
+    }
+
+    pub fn rounding(mut self, rounding: Rounding) -> Self {
+        self.rounding = rounding;
+        self
+    }
+
+    pub fn min_margin(mut self, percent: Decimal) -> Self {
+        self.min_margin = percent;
+        self
+    }
+
+    pub fn selling_price(&self, net: &Money) -> Money {
+        let marked_up = match self.markup {
+            Markup::Percentage(percent) => net.amount + net.amount * percent / HUNDRED,
+            Markup::Fixed(amount) => net.amount + amount,
+        };
+        let floor = net.amount + net.amount * self.min_margin / HUNDRED;
+        let mut selling = self.rounding.round(marked_up.max(floor));
+        if selling < floor {
+            selling = self.rounding.round_up(floor);
+        }
+        Money::new(selling, &net.currency)
+    }
+
+    // What we keep of the selling price of `net`
+    pub fn commission(&self, net: &Money) -> Money {
+        let selling = self.selling_price(net);
+        Money::new(selling.amount - net.amount, &net.currency)
+    }
+
+    // Price options that don't have a selling price from the response yet
+    pub(crate) fn apply(&self, options: &mut [HotelOption]) {
+        for option in options {
+            if option.selling_price.is_none() {
+                option.selling_price = Some(self.selling_price(&option.price));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn amount(value: &str) -> Decimal {
+        Decimal::from_str(value).unwrap()
+    }
+
+    fn selling(rules: &PricingRules, net: &str) -> String {
+        rules
+            .selling_price(&Money::new(amount(net), "EUR"))
+            .xml_amount()
+    }
+
+    #[test]
+    fn test_markup_and_rounding() {
+        let percentage = PricingRules::new(Markup::Percentage(amount("12.5")));
+        assert_eq!(selling(&percentage, "84.82"), "95.42");
+        assert_eq!(
+            selling(
+                &percentage.clone().rounding(Rounding::Up(Decimal::ONE)),
+                "84.82"
+            ),
+            "96.00"
+        );
+        assert_eq!(
+            selling(
+                &percentage.rounding(Rounding::Nearest(amount("5"))),
+                "84.82"
+            ),
+            "95.00"
+        );
+
+        let fixed = PricingRules::new(Markup::Fixed(amount("7.5")));
+        assert_eq!(selling(&fixed, "100"), "107.50");
+        assert_eq!(
+            fixed.commission(&Money::new(amount("100"), "EUR")),
+            Money::new(amount("7.5"), "EUR")
+        );
+    }
+
+    #[test]
+    fn test_min_margin() {
+        // A fixed markup is a small margin on an expensive room
+        let fixed = PricingRules::new(Markup::Fixed(amount("5"))).min_margin(amount("3"));
+        assert_eq!(selling(&fixed, "1000"), "1030.00");
+        assert_eq!(selling(&fixed, "100"), "105.00");
+
+        // Rounding 104.00 to the nearest 10 would go under the margin
+        let nearest = PricingRules::new(Markup::Percentage(amount("4")))
+            .min_margin(amount("3"))
+            .rounding(Rounding::Nearest(amount("10")));
+        assert_eq!(selling(&nearest, "100"), "110.00");
+    }
+}
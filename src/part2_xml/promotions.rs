@@ -0,0 +1,70 @@
+// Promotions
+// Supplier rates may come with promotions such as early booking discounts or free nights.
+// The price already reflects them; they are passed through so the storefront can show them and
+// guests can look for them (`FilterCriteria::has_promotion`). The XML carries them as <Offers>
+// per room, and an option lists the promotions of its rooms once each.
+
+use crate::xml_response::XmlOption;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Promotion {
+    pub code: String,
+    pub name: String,
+}
+
+// The promotions of all rooms of an option, in order, without repeating a code
+pub(crate) fn option_promotions(option: &XmlOption) -> Vec<Promotion> {
+    let mut promotions: Vec<Promotion> = Vec::new();
+    for offer in option
+        .rooms
+        .rooms
+        .iter()
+        .flat_map(|room| &room.offers.offers)
+    {
+        if !promotions.iter().any(|known| known.code == offer.code) {
+            promotions.push(Promotion {
+                code: offer.code.clone(),
+                name: offer.name.clone(),
+            });
+        }
+    }
+    promotions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::xml_response::{XmlOffer, XmlOffers, XmlRoom, XmlRooms};
+
+    #[test]
+    fn test_option_promotions() {
+        let offer = |code: &str, name: &str| XmlOffer {
+            code: code.to_string(),
+            name: name.to_string(),
+        };
+        let room = |offers: Vec<XmlOffer>| XmlRoom {
+            offers: XmlOffers { offers },
+            ..Default::default()
+        };
+        let option = XmlOption {
+            rooms: XmlRooms {
+                rooms: vec![
+                    room(vec![offer("EB10", "Early booking -10%")]),
+                    room(vec![
+                        offer("EB10", "Early booking -10%"),
+                        offer("4X3", "Stay 4, pay 3"),
+                    ]),
+                ],
+            },
+            ..Default::default()
+        };
+        let codes: Vec<String> = option_promotions(&option)
+            .into_iter()
+            .map(|promotion| promotion.code)
+            .collect();
+        assert_eq!(codes, vec!["EB10", "4X3"]);
+        assert!(option_promotions(&XmlOption::default()).is_empty());
+    }
+}
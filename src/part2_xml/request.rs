@@ -0,0 +1,689 @@
+// AvailRQ search requests
+// Typed model of the request sent to suppliers, so occupancy, hotel codes and markets are
+// available instead of just the four values `extract_search_params` returns. Requests are parsed
+// bare or out of the hub's SOAP envelope, and can be built and serialized to search suppliers.
+// Dates are kept as sent (dd/mm/yyyy).
+// `AvailRQBuilder` builds requests toward suppliers from search parameters, checking them the way
+// suppliers do: the stay ends after it starts, currency is an ISO 4217 code, nationality and
+// markets ISO 3166 country codes, and there is at least one hotel and one room with an adult.
+// `SearchParams` is what a request asks for, typed: stay, party, hotels, markets and how long
+// the supplier may take. The timeout becomes the deadline of API client requests and the
+// markets a dimension of cache keys and a filter criterion.
+
+use super::dates::{self, request_date};
+use super::occupancy::{Occupancy, ADULT_AGE};
+use super::stream::find_element;
+use super::ProcessingError;
+use crate::part1_cache::SearchContext;
+use crate::xml_response::{XmlParameter, XmlParameters};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime};
+
+// Destination type of a single hotel, see `AvailRQ::hotel_codes`
+pub const HOTEL_DESTINATION: &str = "HOT";
+
+// Age given to the adults of an `Occupancy`, which only counts them
+pub const DEFAULT_ADULT_AGE: u32 = 30;
+
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+#[serde(default, rename = "AvailRQ", rename_all = "PascalCase")]
+pub struct AvailRQ {
+    // How long the supplier may take to answer
+    #[serde(
+        rename = "timeoutMilliseconds",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub timeout_milliseconds: Option<u64>,
+    pub configuration: XmlConfiguration,
+    pub currency: String,
+    pub nationality: String,
+    pub markets: XmlMarkets,
+    pub avail_destinations: XmlAvailDestinations,
+    // dd/mm/yyyy on the wire, ISO dates are accepted too
+    #[serde(with = "request_date")]
+    pub start_date: NaiveDate,
+    #[serde(with = "request_date")]
+    pub end_date: NaiveDate,
+    pub room_candidates: XmlRoomCandidates,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+#[serde(default, rename_all = "PascalCase")]
+pub struct XmlConfiguration {
+    pub url_generic: String,
+    pub parameters: XmlParameters,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct XmlMarkets {
+    #[serde(rename = "Market")]
+    pub markets: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct XmlAvailDestinations {
+    #[serde(rename = "Destination")]
+    pub destinations: Vec<XmlDestination>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct XmlDestination {
+    #[serde(rename = "@type")]
+    pub destination_type: String,
+    #[serde(rename = "@code")]
+    pub code: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct XmlRoomCandidates {
+    #[serde(rename = "RoomCandidate")]
+    pub room_candidates: Vec<XmlRoomCandidate>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+#[serde(default, rename_all = "PascalCase")]
+pub struct XmlRoomCandidate {
+    // Number of rooms with this occupancy
+    #[serde(rename = "@cantidade")]
+    pub quantity: u32,
+    #[serde(rename = "@id")]
+    pub id: u32,
+    pub paxes: XmlPaxes,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct XmlPaxes {
+    #[serde(rename = "Pax")]
+    pub paxes: Vec<XmlPax>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct XmlPax {
+    #[serde(rename = "@age")]
+    pub age: u32,
+    #[serde(rename = "@id")]
+    pub id: u32,
+}
+
+impl AvailRQ {
+    pub fn builder() -> AvailRQBuilder {
+        AvailRQBuilder::new()
+    }
+
+    pub fn new(
+        currency: impl Into<String>,
+        nationality: impl Into<String>,
+        start_date: NaiveDate,
+        end_date: NaiveDate,
+    ) -> Self {
+        Self {
+            currency: currency.into(),
+            nationality: nationality.into(),
+            start_date,
+            end_date,
+            ..Default::default()
+        }
+    }
+
+    pub fn market(mut self, market: impl Into<String>) -> Self {
+        self.markets.markets.push(market.into());
+        self
+    }
+
+    pub fn hotel(mut self, code: impl Into<String>) -> Self {
+        self.avail_destinations.destinations.push(XmlDestination {
+            destination_type: HOTEL_DESTINATION.to_string(),
+            code: code.into(),
+        });
+        self
+    }
+
+    // One room for guests of the given ages; candidates and paxes are numbered from 1
+    pub fn room(mut self, ages: &[u32]) -> Self {
+        let candidates = &mut self.room_candidates.room_candidates;
+        candidates.push(XmlRoomCandidate {
+            quantity: 1,
+            id: candidates.len() as u32 + 1,
+            paxes: XmlPaxes {
+                paxes: (1..)
+                    .zip(ages)
+                    .map(|(id, &age)| XmlPax { age, id })
+                    .collect(),
+            },
+        });
+        self
+    }
+
+    pub fn parameter(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.configuration.parameters.parameters.push(XmlParameter {
+            key: key.into(),
+            value: value.into(),
+        });
+        self
+    }
+
+    // Parse the first <AvailRQ> of the document, which may be a SOAP envelope
+    pub fn from_xml(xml: &str) -> Result<Self, ProcessingError> {
+        let request = find_element(xml.as_bytes(), "AvailRQ")?
+            .ok_or_else(|| ProcessingError::MissingRequiredField("AvailRQ".to_string()))?;
+        quick_xml::de::from_str(&request).map_err(dates::de_error)
+    }
+
+    pub fn to_xml(&self) -> Result<String, ProcessingError> {
+        quick_xml::se::to_string(self).map_err(|e| ProcessingError::ConversionError(e.to_string()))
+    }
+
+    // Codes of the hotels searched, other destination types are left out
+    pub fn hotel_codes(&self) -> Vec<&str> {
+        self.avail_destinations
+            .destinations
+            .iter()
+            .filter(|destination| destination.destination_type == HOTEL_DESTINATION)
+            .map(|destination| destination.code.as_str())
+            .collect()
+    }
+
+    // The guests of each room candidate, see `occupancy`
+    pub fn occupancy(&self) -> Occupancy {
+        Occupancy::from_request(self)
+    }
+
+    pub fn parameter_value(&self, key: &str) -> Option<&str> {
+        self.configuration
+            .parameters
+            .parameters
+            .iter()
+            .find(|parameter| parameter.key == key)
+            .map(|parameter| parameter.value.as_str())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SearchParams {
+    pub currency: String,
+    pub nationality: String,
+    pub check_in: NaiveDate,
+    pub check_out: NaiveDate,
+    pub hotel_codes: Vec<String>,
+    pub occupancy: Occupancy,
+    pub markets: Vec<String>,
+    // The request's timeoutMilliseconds, None without one
+    pub timeout: Option<Duration>,
+}
+
+impl SearchParams {
+    pub fn from_request(request: &AvailRQ) -> Self {
+        Self {
+            currency: request.currency.clone(),
+            nationality: request.nationality.clone(),
+            check_in: request.start_date,
+            check_out: request.end_date,
+            hotel_codes: request
+                .hotel_codes()
+                .into_iter()
+                .map(str::to_string)
+                .collect(),
+            occupancy: request.occupancy(),
+            markets: request.markets.markets.clone(),
+            timeout: request.timeout_milliseconds.map(Duration::from_millis),
+        }
+    }
+
+    // When an answer to a search sent at `sent_at` is no longer waited for
+    pub fn deadline(&self, sent_at: SystemTime) -> Option<SystemTime> {
+        self.timeout.map(|timeout| sent_at + timeout)
+    }
+
+    // Adults and children of every room asked for
+    pub fn guests(&self) -> (u32, u32) {
+        self.occupancy
+            .rooms
+            .iter()
+            .fold((0, 0), |(adults, children), room| {
+                (
+                    adults + room.quantity * room.adults.max(0) as u32,
+                    children + room.quantity * room.children().max(0) as u32,
+                )
+            })
+    }
+
+    // The cache dimensions of the search: nationality, currency, party and markets
+    pub fn cache_context(&self) -> SearchContext {
+        let (adults, children) = self.guests();
+        let mut context = SearchContext::new().occupancy(adults, children);
+        if !self.nationality.is_empty() {
+            context = context.nationality(&self.nationality);
+        }
+        if !self.currency.is_empty() {
+            context = context.currency(&self.currency);
+        }
+        self.markets
+            .iter()
+            .fold(context, |context, market| context.market(market))
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AvailRQBuilder {
+    dates: Option<(NaiveDate, NaiveDate)>,
+    currency: String,
+    nationality: String,
+    markets: Vec<String>,
+    hotel_codes: Vec<String>,
+    room_candidates: Vec<XmlRoomCandidate>,
+    timeout_milliseconds: Option<u64>,
+    parameters: Vec<XmlParameter>,
+}
+
+impl AvailRQBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn dates(mut self, start_date: NaiveDate, end_date: NaiveDate) -> Self {
+        self.dates = Some((start_date, end_date));
+        self
+    }
+
+    pub fn currency(mut self, currency: impl Into<String>) -> Self {
+        self.currency = currency.into();
+        self
+    }
+
+    pub fn nationality(mut self, nationality: impl Into<String>) -> Self {
+        self.nationality = nationality.into();
+        self
+    }
+
+    pub fn market(mut self, market: impl Into<String>) -> Self {
+        self.markets.push(market.into());
+        self
+    }
+
+    pub fn hotel(mut self, code: impl Into<String>) -> Self {
+        self.hotel_codes.push(code.into());
+        self
+    }
+
+    pub fn hotels<I, S>(mut self, codes: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.hotel_codes.extend(codes.into_iter().map(Into::into));
+        self
+    }
+
+    // One room for guests of the given ages
+    pub fn room(mut self, ages: &[u32]) -> Self {
+        self.push_room(1, ages.to_vec());
+        self
+    }
+
+    // A room candidate for every room of `occupancy`, adults aged `DEFAULT_ADULT_AGE`
+    pub fn occupancy(mut self, occupancy: &Occupancy) -> Self {
+        for room in &occupancy.rooms {
+            let ages = std::iter::repeat_n(DEFAULT_ADULT_AGE, room.adults.max(0) as usize)
+                .chain(room.child_ages.iter().copied())
+                .collect();
+            self.push_room(room.quantity, ages);
+        }
+        self
+    }
+
+    pub fn timeout_milliseconds(mut self, timeout: u64) -> Self {
+        self.timeout_milliseconds = Some(timeout);
+        self
+    }
+
+    pub fn parameter(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.parameters.push(XmlParameter {
+            key: key.into(),
+            value: value.into(),
+        });
+        self
+    }
+
+    // Candidates and paxes are numbered from 1
+    fn push_room(&mut self, quantity: u32, ages: Vec<u32>) {
+        self.room_candidates.push(XmlRoomCandidate {
+            quantity,
+            id: self.room_candidates.len() as u32 + 1,
+            paxes: XmlPaxes {
+                paxes: (1..)
+                    .zip(ages)
+                    .map(|(id, age)| XmlPax { age, id })
+                    .collect(),
+            },
+        });
+    }
+
+    pub fn build(self) -> Result<AvailRQ, ProcessingError> {
+        let (start_date, end_date) = self
+            .dates
+            .ok_or_else(|| ProcessingError::MissingRequiredField("StartDate".to_string()))?;
+        if end_date <= start_date {
+            return Err(ProcessingError::InvalidDate(format!(
+                "stay from {} to {} doesn't end after it starts",
+                start_date, end_date
+            )));
+        }
+        check_code("Currency", &self.currency, 3)?;
+        check_code("Nationality", &self.nationality, 2)?;
+        for market in &self.markets {
+            check_code("Market", market, 2)?;
+        }
+        if self.hotel_codes.is_empty() {
+            return Err(ProcessingError::MissingRequiredField(
+                "Destination".to_string(),
+            ));
+        }
+        if let Some(code) = self.hotel_codes.iter().find(|code| code.trim().is_empty()) {
+            return Err(ProcessingError::InvalidFormat(format!(
+                "hotel code {:?} is empty",
+                code
+            )));
+        }
+        if self.room_candidates.is_empty() {
+            return Err(ProcessingError::MissingRequiredField(
+                "RoomCandidate".to_string(),
+            ));
+        }
+        for candidate in &self.room_candidates {
+            if candidate.quantity == 0 {
+                return Err(ProcessingError::InvalidFormat(format!(
+                    "room candidate {} asks for no rooms",
+                    candidate.id
+                )));
+            }
+            if !candidate.paxes.paxes.iter().any(|pax| pax.age >= ADULT_AGE) {
+                return Err(ProcessingError::InvalidFormat(format!(
+                    "room candidate {} has no adult",
+                    candidate.id
+                )));
+            }
+        }
+        if self.timeout_milliseconds == Some(0) {
+            return Err(ProcessingError::InvalidFormat(
+                "timeoutMilliseconds must be positive".to_string(),
+            ));
+        }
+
+        Ok(AvailRQ {
+            timeout_milliseconds: self.timeout_milliseconds,
+            configuration: XmlConfiguration {
+                parameters: XmlParameters {
+                    parameters: self.parameters,
+                },
+                ..Default::default()
+            },
+            currency: self.currency,
+            nationality: self.nationality,
+            markets: XmlMarkets {
+                markets: self.markets,
+            },
+            avail_destinations: XmlAvailDestinations {
+                destinations: self
+                    .hotel_codes
+                    .into_iter()
+                    .map(|code| XmlDestination {
+                        destination_type: HOTEL_DESTINATION.to_string(),
+                        code,
+                    })
+                    .collect(),
+            },
+            start_date,
+            end_date,
+            room_candidates: XmlRoomCandidates {
+                room_candidates: self.room_candidates,
+            },
+        })
+    }
+
+    pub fn build_xml(self) -> Result<String, ProcessingError> {
+        self.build()?.to_xml()
+    }
+}
+
+// ISO 4217 currencies and ISO 3166-1 alpha-2 countries: `length` upper case letters
+fn check_code(element: &str, code: &str, length: usize) -> Result<(), ProcessingError> {
+    if code.is_empty() {
+        return Err(ProcessingError::MissingRequiredField(element.to_string()));
+    }
+    if code.len() != length || !code.bytes().all(|byte| byte.is_ascii_uppercase()) {
+        return Err(ProcessingError::InvalidFormat(format!(
+            "{} {:?} isn't an ISO code of {} upper case letters",
+            element, code, length
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::part2_xml::HotelSearchProcessor;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn test_parse_sample_request() {
+        let processor = HotelSearchProcessor::new();
+        let request = processor
+            .parse_request(&processor.load_sample_request().unwrap())
+            .unwrap();
+
+        assert_eq!(
+            (request.currency.as_str(), request.nationality.as_str()),
+            ("GBP", "US")
+        );
+        assert_eq!(request.markets.markets, vec!["US"]);
+        assert_eq!(request.hotel_codes().len(), 5);
+        assert_eq!(request.hotel_codes()[3], "39776757");
+        assert_eq!(
+            (request.start_date, request.end_date),
+            (date(2025, 6, 11), date(2025, 6, 12))
+        );
+        assert_eq!(request.parameter_value("username"), Some("abc"));
+        assert_eq!(request.timeout_milliseconds, Some(25000));
+
+        let candidate = &request.room_candidates.room_candidates[0];
+        assert_eq!((candidate.quantity, candidate.id), (1, 1));
+        assert_eq!(candidate.paxes.paxes, vec![XmlPax { age: 30, id: 1 }]);
+    }
+
+    #[test]
+    fn test_build_and_roundtrip() {
+        let request = AvailRQ::new("EUR", "ES", date(2025, 7, 1), date(2025, 7, 5))
+            .market("ES")
+            .hotel("12345")
+            .hotel("67890")
+            .room(&[30, 28])
+            .room(&[40, 8])
+            .parameter("username", "abc");
+
+        let xml = request.to_xml().unwrap();
+        assert!(xml.starts_with("<AvailRQ>"));
+        assert!(xml.contains("<StartDate>01/07/2025</StartDate>"));
+        assert!(xml.contains("<Destination type=\"HOT\" code=\"67890\"/>"));
+        assert!(xml.contains("<RoomCandidate cantidade=\"1\" id=\"2\">"));
+        assert_eq!(AvailRQ::from_xml(&xml).unwrap(), request);
+
+        // The tuple extraction reads the same values
+        let (currency, nationality, start_date, end_date) = HotelSearchProcessor::new()
+            .extract_search_params(&xml)
+            .unwrap();
+        assert_eq!(
+            (currency, nationality, start_date, end_date),
+            (
+                request.currency,
+                request.nationality,
+                "01/07/2025".to_string(),
+                "05/07/2025".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_builder() {
+        let occupancy = Occupancy {
+            rooms: vec![crate::part2_xml::RoomOccupancy {
+                candidate_id: 1,
+                quantity: 2,
+                adults: 2,
+                child_ages: vec![7],
+            }],
+        };
+        let xml = AvailRQ::builder()
+            .dates(date(2025, 7, 1), date(2025, 7, 5))
+            .currency("EUR")
+            .nationality("ES")
+            .market("ES")
+            .hotels(["12345", "67890"])
+            .occupancy(&occupancy)
+            .room(&[40])
+            .timeout_milliseconds(8_000)
+            .parameter("username", "abc")
+            .build_xml()
+            .unwrap();
+        assert!(xml.starts_with("<AvailRQ><timeoutMilliseconds>8000</timeoutMilliseconds>"));
+
+        let request = AvailRQ::from_xml(&xml).unwrap();
+        assert_eq!(request.timeout_milliseconds, Some(8_000));
+        assert_eq!(request.hotel_codes(), vec!["12345", "67890"]);
+        assert_eq!(request.markets.markets, vec!["ES"]);
+        assert_eq!(request.parameter_value("username"), Some("abc"));
+        let parsed = request.occupancy();
+        assert_eq!(parsed.rooms.len(), 2);
+        assert_eq!((parsed.rooms[0].quantity, parsed.rooms[0].adults), (2, 2));
+        assert_eq!(parsed.rooms[0].child_ages, vec![7]);
+        assert_eq!(
+            (parsed.rooms[1].candidate_id, parsed.rooms[1].adults),
+            (2, 1)
+        );
+
+        let (currency, nationality, start_date, end_date) = HotelSearchProcessor::new()
+            .extract_search_params(&xml)
+            .unwrap();
+        assert_eq!(
+            (
+                currency.as_str(),
+                nationality.as_str(),
+                start_date.as_str(),
+                end_date.as_str()
+            ),
+            ("EUR", "ES", "01/07/2025", "05/07/2025")
+        );
+    }
+
+    #[test]
+    fn test_builder_validation() {
+        let valid = || {
+            AvailRQ::builder()
+                .dates(date(2025, 7, 1), date(2025, 7, 5))
+                .currency("EUR")
+                .nationality("ES")
+                .hotel("12345")
+                .room(&[30])
+        };
+        assert!(valid().build().is_ok());
+
+        assert!(matches!(
+            valid().dates(date(2025, 7, 5), date(2025, 7, 5)).build(),
+            Err(ProcessingError::InvalidDate(_))
+        ));
+        assert!(matches!(
+            AvailRQ::builder().currency("EUR").build(),
+            Err(ProcessingError::MissingRequiredField(_))
+        ));
+        for invalid in [
+            valid().currency("eur"),
+            valid().currency("EURO"),
+            valid().nationality("ESP"),
+            valid().market("E1"),
+            valid().hotel(" "),
+            valid().room(&[8]),
+            valid().timeout_milliseconds(0),
+        ] {
+            assert!(matches!(
+                invalid.build(),
+                Err(ProcessingError::InvalidFormat(_))
+            ));
+        }
+        assert!(matches!(
+            valid().currency("").build(),
+            Err(ProcessingError::MissingRequiredField(_))
+        ));
+    }
+
+    #[test]
+    fn test_search_params() {
+        let processor = HotelSearchProcessor::new();
+        let params = processor
+            .search_params(&processor.load_sample_request().unwrap())
+            .unwrap();
+        assert_eq!(params.timeout, Some(Duration::from_millis(25_000)));
+        assert_eq!(params.markets, vec!["US"]);
+        assert_eq!(params.hotel_codes.len(), 5);
+        assert_eq!(
+            (params.check_in, params.check_out),
+            (date(2025, 6, 11), date(2025, 6, 12))
+        );
+        assert_eq!(params.guests(), (1, 0));
+
+        let sent_at = SystemTime::UNIX_EPOCH;
+        assert_eq!(
+            params.deadline(sent_at),
+            Some(sent_at + Duration::from_secs(25))
+        );
+        let context = params.cache_context();
+        assert_eq!(context.markets, vec!["US"]);
+        assert_eq!(
+            (context.nationality.as_deref(), context.currency.as_deref()),
+            (Some("US"), Some("GBP"))
+        );
+
+        let request = AvailRQ::new("EUR", "ES", date(2025, 7, 1), date(2025, 7, 5))
+            .room(&[30, 28, 6])
+            .room(&[40]);
+        let params = SearchParams::from_request(&request);
+        assert_eq!(params.timeout, None);
+        assert_eq!(params.deadline(sent_at), None);
+        assert_eq!(params.guests(), (3, 1));
+        assert!(params.cache_context().markets.is_empty());
+    }
+
+    #[test]
+    fn test_missing_request() {
+        assert!(matches!(
+            AvailRQ::from_xml("<AvailRS/>"),
+            Err(ProcessingError::MissingRequiredField(_))
+        ));
+    }
+
+    #[test]
+    fn test_dates_in_either_format() {
+        let xml =
+            "<AvailRQ><StartDate>2025-07-01</StartDate><EndDate>05/07/2025</EndDate></AvailRQ>";
+        let request = AvailRQ::from_xml(xml).unwrap();
+        assert_eq!(
+            (request.start_date, request.end_date),
+            (date(2025, 7, 1), date(2025, 7, 5))
+        );
+
+        let xml = "<AvailRQ><StartDate>2025-13-01</StartDate></AvailRQ>";
+        assert!(matches!(
+            AvailRQ::from_xml(xml),
+            Err(ProcessingError::InvalidDate(value)) if value == "2025-13-01"
+        ));
+    }
+}
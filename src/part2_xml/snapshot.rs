@@ -0,0 +1,141 @@
+// Golden-file snapshots
+// Mapping tests used to check converted XML with `contains`, which keeps passing whatever else
+// changes in the document. `assert_snapshot` compares a document with the one stored under
+// samples/snapshots and fails with the lines that differ. Converted documents are indented, one
+// element per line, so a changed attribute shows as a one-line change. A missing snapshot fails
+// the test too, so one that was never committed (or was deleted) can't pass by being written on
+// the spot; run the tests with UPDATE_SNAPSHOTS=1 to write new snapshots and overwrite the stored
+// ones with the current output, then review the change in git like any other.
+// The corpus is generated by `synthetic_response` from fixed seeds, so fixtures don't have to be
+// written by hand and stay the same from run to run (`StdRng` output may change with a rand
+// upgrade, which then needs UPDATE_SNAPSHOTS=1).
+
+use super::output::XmlWriteOptions;
+use super::synthetic::synthetic_supplier_json;
+use super::HotelSearchProcessor;
+use std::path::{Path, PathBuf};
+
+const SNAPSHOT_DIR: &str = "samples/snapshots";
+const UPDATE_VARIABLE: &str = "UPDATE_SNAPSHOTS";
+
+// Name, seed and number of options of every snapshot
+const CORPUS: &[(&str, u64, usize)] = &[
+    ("single_option", 1, 1),
+    ("one_hotel", 2, 20),
+    ("several_hotels", 3, 47),
+];
+
+pub(crate) fn assert_snapshot(name: &str, actual: &str) {
+    let path = PathBuf::from(SNAPSHOT_DIR).join(format!("{}.xml", name));
+    check_snapshot(&path, actual, std::env::var_os(UPDATE_VARIABLE).is_some());
+}
+
+// Compare `actual` with the snapshot at `path`, or write it there when `update` is set
+fn check_snapshot(path: &Path, actual: &str, update: bool) {
+    if update {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).unwrap();
+        }
+        std::fs::write(path, actual).unwrap();
+        return;
+    }
+    if !path.exists() {
+        panic!(
+            "{} doesn't exist; run with {}=1 to write it, then commit it",
+            path.display(),
+            UPDATE_VARIABLE
+        );
+    }
+    let expected = std::fs::read_to_string(path).unwrap();
+    if expected != actual {
+        panic!(
+            "{} differs from its snapshot (- stored, + actual); rerun with {}=1 to accept:\n{}",
+            path.display(),
+            UPDATE_VARIABLE,
+            diff_lines(&expected, actual)
+        );
+    }
+}
+
+// Converted XML of the corpus entry, indented for line diffs
+pub(crate) fn converted_snapshot(seed: u64, options: usize) -> String {
+    let processor =
+        HotelSearchProcessor::new().with_write_options(XmlWriteOptions::new().indent(2));
+    let mut xml = processor
+        .convert_json_to_xml(&synthetic_supplier_json(seed, options))
+        .unwrap();
+    xml.push('\n');
+    xml
+}
+
+// The lines only in `expected` (-) or only in `actual` (+), numbered as in their document, from
+// the longest common subsequence of the two
+fn diff_lines(expected: &str, actual: &str) -> String {
+    let old: Vec<&str> = expected.lines().collect();
+    let new: Vec<&str> = actual.lines().collect();
+    // common[i][j]: length of the longest common subsequence of old[i..] and new[j..]
+    let mut common = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let mut diff = String::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            i += 1;
+            j += 1;
+        } else if j == new.len() || (i < old.len() && common[i + 1][j] >= common[i][j + 1]) {
+            diff.push_str(&format!("-{:>5} {}\n", i + 1, old[i]));
+            i += 1;
+        } else {
+            diff.push_str(&format!("+{:>5} {}\n", j + 1, new[j]));
+            j += 1;
+        }
+    }
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_converted_snapshots() {
+        for (name, seed, options) in CORPUS {
+            assert_snapshot(name, &converted_snapshot(*seed, *options));
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "doesn't exist; run with UPDATE_SNAPSHOTS=1 to write it")]
+    fn test_missing_snapshot_fails() {
+        let path = std::env::temp_dir().join(format!("snapshot-{}.xml", rand::random::<u64>()));
+        check_snapshot(&path, "<A/>\n", false);
+    }
+
+    #[test]
+    fn test_missing_snapshot_is_written_on_update() {
+        let path = std::env::temp_dir().join(format!("snapshot-{}.xml", rand::random::<u64>()));
+        check_snapshot(&path, "<A/>\n", true);
+        check_snapshot(&path, "<A/>\n", false);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_diff_lines() {
+        let expected = "<A>\n  <B x=\"1\"/>\n  <C/>\n</A>\n";
+        let actual = "<A>\n  <B x=\"2\"/>\n  <C/>\n  <D/>\n</A>\n";
+        assert_eq!(
+            diff_lines(expected, actual),
+            "-    2   <B x=\"1\"/>\n+    2   <B x=\"2\"/>\n+    4   <D/>\n"
+        );
+        assert_eq!(diff_lines(expected, expected), "");
+    }
+}
@@ -0,0 +1,173 @@
+// Sorting and pagination of processed options
+// A search over a few hundred hotels yields thousands of options; API consumers show them a
+// page at a time in the order the guest picked. Sorting is stable, so options that tie keep
+// the supplier's order and sorting by one key after another breaks ties by the earlier key.
+// Options missing the sort value (no category) come last in either order.
+
+use super::{HotelOption, ProcessedResponse};
+use chrono::{DateTime, Utc};
+use std::cmp::Ordering;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    // The amount including taxes, whatever the currency
+    Price,
+    HotelName,
+    Category,
+    // `HotelOption::free_cancellation_until`; non-refundable options have the earliest
+    // deadline, options that are free to cancel at any time the latest
+    FreeCancellation,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortOrder {
+    #[default]
+    Ascending,
+    Descending,
+}
+
+pub fn sort_options(response: &mut ProcessedResponse, key: SortKey, order: SortOrder) {
+    let directed = |ordering: Ordering| match order {
+        SortOrder::Ascending => ordering,
+        SortOrder::Descending => ordering.reverse(),
+    };
+    response.hotels.sort_by(|a, b| match key {
+        SortKey::Price => directed(
+            a.total_including_taxes()
+                .amount
+                .cmp(&b.total_including_taxes().amount),
+        ),
+        SortKey::HotelName => directed(
+            a.hotel_name
+                .to_lowercase()
+                .cmp(&b.hotel_name.to_lowercase()),
+        ),
+        SortKey::Category => match (a.category, b.category) {
+            (Some(a), Some(b)) => directed(a.cmp(&b)),
+            (a, b) => b.is_some().cmp(&a.is_some()),
+        },
+        SortKey::FreeCancellation => {
+            directed(cancellation_deadline(a).cmp(&cancellation_deadline(b)))
+        }
+    });
+}
+
+fn cancellation_deadline(option: &HotelOption) -> DateTime<Utc> {
+    match option.free_cancellation_until {
+        _ if !option.is_refundable => DateTime::<Utc>::MIN_UTC,
+        Some(until) => until,
+        None => DateTime::<Utc>::MAX_UTC,
+    }
+}
+
+// One page of options, borrowed from the response
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Page<'a> {
+    pub options: &'a [HotelOption],
+    pub offset: usize,
+    // Options over all pages
+    pub total: usize,
+}
+
+impl Page<'_> {
+    pub fn has_more(&self) -> bool {
+        self.offset + self.options.len() < self.total
+    }
+}
+
+// Up to `limit` options from `offset` on; empty past the end
+pub fn paginate(options: &[HotelOption], offset: usize, limit: usize) -> Page<'_> {
+    let start = offset.min(options.len());
+    let end = start.saturating_add(limit).min(options.len());
+    Page {
+        options: &options[start..end],
+        offset,
+        total: options.len(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::money::Money;
+    use chrono::TimeZone;
+    use rust_decimal::Decimal;
+
+    fn option(name: &str, price: i64, category: Option<i32>, until: Option<u32>) -> HotelOption {
+        HotelOption {
+            hotel_name: name.to_string(),
+            price: Money::new(Decimal::from(price), "EUR"),
+            category,
+            is_refundable: until != Some(0),
+            free_cancellation_until: until
+                .filter(|&day| day > 0)
+                .map(|day| Utc.with_ymd_and_hms(2025, 6, day, 0, 0, 0).unwrap()),
+            ..Default::default()
+        }
+    }
+
+    fn response() -> ProcessedResponse {
+        let hotels = vec![
+            option("beta", 120, Some(3), Some(5)),
+            option("Alpha", 80, None, Some(0)),
+            option("gamma", 95, Some(5), None),
+            option("delta", 80, Some(4), Some(9)),
+        ];
+        ProcessedResponse {
+            search_id: String::new(),
+            total_options: hotels.len(),
+            hotels,
+            currency: "EUR".to_string(),
+            nationality: String::new(),
+            check_in: None,
+            check_out: None,
+            markets: Vec::new(),
+        }
+    }
+
+    fn names(response: &ProcessedResponse) -> Vec<&str> {
+        response
+            .hotels
+            .iter()
+            .map(|option| option.hotel_name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_sort_options() {
+        let mut response = response();
+        sort_options(&mut response, SortKey::Price, SortOrder::Ascending);
+        // Ties keep their order
+        assert_eq!(names(&response), vec!["Alpha", "delta", "gamma", "beta"]);
+
+        sort_options(&mut response, SortKey::HotelName, SortOrder::Descending);
+        assert_eq!(names(&response), vec!["gamma", "delta", "beta", "Alpha"]);
+
+        for order in [SortOrder::Ascending, SortOrder::Descending] {
+            sort_options(&mut response, SortKey::Category, order);
+            assert_eq!(response.hotels[3].hotel_name, "Alpha");
+        }
+
+        sort_options(
+            &mut response,
+            SortKey::FreeCancellation,
+            SortOrder::Descending,
+        );
+        assert_eq!(names(&response), vec!["gamma", "delta", "beta", "Alpha"]);
+    }
+
+    #[test]
+    fn test_paginate() {
+        let response = response();
+        let first = paginate(&response.hotels, 0, 3);
+        assert_eq!((first.options.len(), first.total), (3, 4));
+        assert!(first.has_more());
+
+        let last = paginate(&response.hotels, 3, 3);
+        assert_eq!(last.options[0].hotel_name, "delta");
+        assert!(!last.has_more());
+
+        let past_end = paginate(&response.hotels, 10, usize::MAX);
+        assert!(past_end.options.is_empty() && !past_end.has_more());
+    }
+}
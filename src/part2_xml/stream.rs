@@ -0,0 +1,321 @@
+// Streaming AvailRS processing
+// Supplier responses can run to tens of megabytes; deserializing them in one go holds the
+// whole document plus its object model in memory. The stream reads the document as quick_xml
+// events and only materializes one <Hotel> element at a time: its events are written back into
+// a small standalone document, deserialized into `XmlHotel` and mapped with the same
+// `hotel_options` as `process`, so both paths produce identical options.
+
+use super::content::{enrich_options, HotelContentProvider};
+use super::limits::ParseLimits;
+use super::position::{element_path, LineCounter, XmlPosition};
+use super::pricing::PricingRules;
+use super::{hotel_options, HotelOption, ProcessingError};
+use crate::XmlHotel;
+use quick_xml::de::from_str;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::reader::Reader;
+use quick_xml::writer::Writer;
+use std::collections::VecDeque;
+use std::io::BufRead;
+use std::sync::Arc;
+
+// Iterator over the options of an AvailRS document; stops after the first error
+pub struct HotelOptionStream<R> {
+    reader: EventReader<R>,
+    buf: Vec<u8>,
+    // Options of the current hotel not handed out yet
+    pending: VecDeque<HotelOption>,
+    pricing: Option<PricingRules>,
+    content_provider: Option<Arc<dyn HotelContentProvider>>,
+    done: bool,
+}
+
+impl<R: BufRead> HotelOptionStream<R> {
+    pub fn new(input: R) -> Self {
+        Self {
+            reader: EventReader::new(input),
+            buf: Vec::new(),
+            pending: VecDeque::new(),
+            pricing: None,
+            content_provider: None,
+            done: false,
+        }
+    }
+
+    pub(crate) fn with_pricing(mut self, pricing: Option<PricingRules>) -> Self {
+        self.pricing = pricing;
+        self
+    }
+
+    pub(crate) fn with_content_provider(
+        mut self,
+        provider: Option<Arc<dyn HotelContentProvider>>,
+    ) -> Self {
+        self.content_provider = provider;
+        self
+    }
+
+    pub(crate) fn with_limits(mut self, limits: ParseLimits) -> Self {
+        self.reader = self.reader.with_limits(limits);
+        self
+    }
+
+    // The next <Hotel> element, None at the end of the document
+    fn next_hotel(&mut self) -> Result<Option<XmlHotel>, ProcessingError> {
+        loop {
+            self.buf.clear();
+            match self.read_event()? {
+                Event::Start(start) if start.name().as_ref() == b"Hotel" => {
+                    let start = start.into_owned();
+                    return self.read_hotel(start).map(Some);
+                }
+                Event::Eof => return Ok(None),
+                // Hotels without content (<Hotel .../>) have no options
+                _ => {}
+            }
+        }
+    }
+
+    fn read_hotel(&mut self, start: BytesStart<'static>) -> Result<XmlHotel, ProcessingError> {
+        let xml = read_element(&mut self.reader, &mut self.buf, start)?;
+        from_str(&xml).map_err(|e| ProcessingError::XmlParseError(e.to_string()))
+    }
+
+    fn read_event(&mut self) -> Result<Event<'_>, ProcessingError> {
+        self.reader.read_event(&mut self.buf)
+    }
+}
+
+// A quick_xml reader keeping track of the open elements and the lines read, so syntax errors
+// carry their position
+pub(crate) struct EventReader<R> {
+    reader: Reader<LineCounter<R>>,
+    open: Vec<String>,
+    limits: ParseLimits,
+    // <Option> elements read so far
+    options: usize,
+}
+
+impl<R: BufRead> EventReader<R> {
+    pub(crate) fn new(input: R) -> Self {
+        Self {
+            reader: Reader::from_reader(LineCounter::new(input)),
+            open: Vec::new(),
+            limits: ParseLimits::unlimited(),
+            options: 0,
+        }
+    }
+
+    pub(crate) fn with_limits(mut self, limits: ParseLimits) -> Self {
+        self.reader
+            .get_mut()
+            .limit(limits.max_document_bytes.try_into().unwrap_or(u64::MAX));
+        self.limits = limits;
+        self
+    }
+
+    pub(crate) fn read_event<'b>(
+        &mut self,
+        buf: &'b mut Vec<u8>,
+    ) -> Result<Event<'b>, ProcessingError> {
+        match self.reader.read_event_into(buf) {
+            Ok(event) => {
+                match &event {
+                    Event::Start(start) => {
+                        if self.open.len() >= self.limits.max_depth {
+                            return Err(self.limits.too_deep());
+                        }
+                        self.open
+                            .push(String::from_utf8_lossy(start.name().as_ref()).into_owned())
+                    }
+                    Event::End(_) => {
+                        self.open.pop();
+                    }
+                    _ => {}
+                }
+                if let Event::Start(element) | Event::Empty(element) = &event {
+                    if element.local_name().as_ref() == b"Option" {
+                        self.options += 1;
+                        if self.options > self.limits.max_options {
+                            return Err(self.limits.too_many_options());
+                        }
+                    }
+                }
+                Ok(event)
+            }
+            Err(_) if self.reader.get_ref().exceeded => Err(self.limits.too_large()),
+            Err(e) => Err(self.syntax_error(e.to_string(), self.reader.error_position())),
+        }
+    }
+
+    pub(crate) fn syntax_error(&self, message: String, offset: u64) -> ProcessingError {
+        let (line, column) = self.reader.get_ref().line_column(offset);
+        ProcessingError::XmlSyntaxError {
+            message,
+            position: XmlPosition {
+                offset,
+                line,
+                column,
+                path: element_path(&self.open),
+            },
+        }
+    }
+}
+
+// Copy the events up to the end of the element opened by `start` into a document of its own
+pub(crate) fn read_element<R: BufRead>(
+    reader: &mut EventReader<R>,
+    buf: &mut Vec<u8>,
+    start: BytesStart<'static>,
+) -> Result<String, ProcessingError> {
+    let name = String::from_utf8_lossy(start.name().as_ref()).into_owned();
+    let mut writer = Writer::new(Vec::new());
+    writer.write_event(Event::Start(start))?;
+    let mut depth = 1;
+    while depth > 0 {
+        buf.clear();
+        let event = reader.read_event(buf)?;
+        match &event {
+            Event::Start(_) => depth += 1,
+            Event::End(_) => depth -= 1,
+            Event::Eof => {
+                let offset = reader.reader.buffer_position();
+                return Err(reader.syntax_error(format!("document ends inside <{}>", name), offset));
+            }
+            _ => {}
+        }
+        writer.write_event(event)?;
+    }
+    String::from_utf8(writer.into_inner())
+        .map_err(|e| ProcessingError::XmlParseError(e.to_string()))
+}
+
+// The first element named `name` anywhere in the document (e.g. inside a SOAP envelope) as a
+// document of its own
+pub(crate) fn find_element(
+    input: impl BufRead,
+    name: &str,
+) -> Result<Option<String>, ProcessingError> {
+    let mut reader = EventReader::new(input);
+    let mut buf = Vec::new();
+    loop {
+        buf.clear();
+        match reader.read_event(&mut buf)? {
+            Event::Start(start) if start.name().as_ref() == name.as_bytes() => {
+                let start = start.into_owned();
+                return read_element(&mut reader, &mut buf, start).map(Some);
+            }
+            Event::Empty(start) if start.name().as_ref() == name.as_bytes() => {
+                let mut writer = Writer::new(Vec::new());
+                writer.write_event(Event::Empty(start))?;
+                return String::from_utf8(writer.into_inner())
+                    .map(Some)
+                    .map_err(|e| ProcessingError::XmlParseError(e.to_string()));
+            }
+            Event::Eof => return Ok(None),
+            _ => {}
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for HotelOptionStream<R> {
+    type Item = Result<HotelOption, ProcessingError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(option) = self.pending.pop_front() {
+                return Some(Ok(option));
+            }
+            if self.done {
+                return None;
+            }
+            match self.next_hotel() {
+                Ok(Some(hotel)) => match hotel_options(&hotel) {
+                    Ok(mut options) => {
+                        if let Some(rules) = &self.pricing {
+                            rules.apply(&mut options);
+                        }
+                        if let Some(provider) = &self.content_provider {
+                            enrich_options(&mut options, provider.as_ref());
+                        }
+                        self.pending.extend(options)
+                    }
+                    Err(e) => {
+                        self.done = true;
+                        return Some(Err(e));
+                    }
+                },
+                Ok(None) => self.done = true,
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::part2_xml::{HotelSearchProcessor, SMALL_SAMPLE_XML};
+
+    // `count` copies of the sample hotel with distinct codes
+    fn large_document(count: usize) -> String {
+        let hotel_start = SMALL_SAMPLE_XML.find("<Hotel ").unwrap();
+        let hotel_end = SMALL_SAMPLE_XML.find("</Hotels>").unwrap();
+        let hotel = &SMALL_SAMPLE_XML[hotel_start..hotel_end];
+        let hotels: String = (0..count)
+            .map(|i| hotel.replace("39776757", &format!("H{}", i)))
+            .collect();
+        format!("<AvailRS><Hotels>{}</Hotels></AvailRS>", hotels)
+    }
+
+    #[test]
+    fn test_stream_matches_process() {
+        let processor = HotelSearchProcessor::new();
+        for xml in [
+            SMALL_SAMPLE_XML.to_string(),
+            processor.load_sample_response().unwrap(),
+        ] {
+            let streamed: Vec<HotelOption> = processor
+                .process_stream(xml.as_bytes())
+                .collect::<Result<_, _>>()
+                .unwrap();
+            assert_eq!(streamed, processor.process(&xml).unwrap().hotels);
+        }
+    }
+
+    #[test]
+    fn test_stream_many_hotels() {
+        let processor = HotelSearchProcessor::new();
+        let xml = large_document(2000);
+        let mut last = None;
+        let count = processor
+            .process_each(xml.as_bytes(), |option| last = Some(option.hotel_id))
+            .unwrap();
+        assert_eq!(count, 2000);
+        assert_eq!(last.as_deref(), Some("H1999"));
+    }
+
+    #[test]
+    fn test_stream_stops_at_malformed_hotel() {
+        let processor = HotelSearchProcessor::new();
+        let xml = large_document(2);
+        let truncated = &xml[..xml.rfind("</Rooms>").unwrap()];
+
+        let results: Vec<_> = processor.process_stream(truncated.as_bytes()).collect();
+        assert_eq!(results.len(), 2);
+        assert!(results[0].is_ok());
+        match &results[1] {
+            Err(ProcessingError::XmlSyntaxError { position, .. }) => {
+                assert_eq!(position.offset, truncated.len() as u64);
+                assert_eq!(position.line, truncated.lines().count());
+                assert!(position
+                    .path
+                    .ends_with("/Hotel/MealPlans/MealPlan/Options/Option/Rooms"));
+            }
+            other => panic!("expected a syntax error, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,173 @@
+// Per-hotel summaries of processed options
+// The results page shows one card per hotel rather than every room/board/rate option, so
+// options are grouped by hotel into what the card needs: the cheapest price per board type,
+// the price range, how many options are refundable and which room types there are. Hotels,
+// board types and room types are listed in the order they first appear in the response.
+
+use super::HotelOption;
+use crate::money::Money;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HotelSummary {
+    pub hotel_id: String,
+    pub hotel_name: String,
+    pub category: Option<i32>,
+    // (board type, cheapest price)
+    pub cheapest_by_board: Vec<(String, Money)>,
+    pub min_price: Money,
+    pub max_price: Money,
+    pub option_count: usize,
+    pub refundable_count: usize,
+    pub room_types: Vec<String>,
+}
+
+impl HotelSummary {
+    fn new(option: &HotelOption) -> Self {
+        Self {
+            hotel_id: option.hotel_id.clone(),
+            hotel_name: option.hotel_name.clone(),
+            category: option.category,
+            cheapest_by_board: Vec::new(),
+            min_price: option.price.clone(),
+            max_price: option.price.clone(),
+            option_count: 0,
+            refundable_count: 0,
+            room_types: Vec::new(),
+        }
+    }
+
+    fn add(&mut self, option: &HotelOption) {
+        let price = &option.price;
+        self.option_count += 1;
+        if option.is_refundable {
+            self.refundable_count += 1;
+        }
+        if price.amount < self.min_price.amount {
+            self.min_price = price.clone();
+        }
+        if price.amount > self.max_price.amount {
+            self.max_price = price.clone();
+        }
+        match self
+            .cheapest_by_board
+            .iter_mut()
+            .find(|(board_type, _)| *board_type == option.board_type)
+        {
+            Some((_, cheapest)) if price.amount < cheapest.amount => *cheapest = price.clone(),
+            Some(_) => {}
+            None => self
+                .cheapest_by_board
+                .push((option.board_type.clone(), price.clone())),
+        }
+        if !self.room_types.contains(&option.room_type) {
+            self.room_types.push(option.room_type.clone());
+        }
+    }
+
+    pub fn cheapest(&self, board_type: &str) -> Option<&Money> {
+        self.cheapest_by_board
+            .iter()
+            .find(|(board, _)| board == board_type)
+            .map(|(_, price)| price)
+    }
+}
+
+pub fn summarize_hotels(options: &[HotelOption]) -> Vec<HotelSummary> {
+    let mut summaries: Vec<HotelSummary> = Vec::new();
+    for option in options {
+        let index = match summaries
+            .iter()
+            .position(|summary| summary.hotel_id == option.hotel_id)
+        {
+            Some(index) => index,
+            None => {
+                summaries.push(HotelSummary::new(option));
+                summaries.len() - 1
+            }
+        };
+        summaries[index].add(option);
+    }
+    summaries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::part2_xml::HotelSearchProcessor;
+    use rust_decimal::Decimal;
+
+    #[test]
+    fn test_summarize_sample_response() {
+        let processor = HotelSearchProcessor::new();
+        let response = processor
+            .process(&processor.load_sample_response().unwrap())
+            .unwrap();
+        let summaries = processor.summarize_hotels(&response);
+
+        let ids: Vec<&str> = summaries.iter().map(|s| s.hotel_id.as_str()).collect();
+        let mut expected: Vec<&str> = Vec::new();
+        for option in &response.hotels {
+            if !expected.contains(&option.hotel_id.as_str()) {
+                expected.push(&option.hotel_id);
+            }
+        }
+        assert_eq!(ids, expected);
+        assert_eq!(
+            summaries.iter().map(|s| s.option_count).sum::<usize>(),
+            response.total_options
+        );
+
+        for summary in &summaries {
+            let options: Vec<&HotelOption> = response
+                .hotels
+                .iter()
+                .filter(|option| option.hotel_id == summary.hotel_id)
+                .collect();
+            let min = options.iter().map(|o| o.price.amount).min().unwrap();
+            assert_eq!(summary.min_price.amount, min);
+            for (board_type, cheapest) in &summary.cheapest_by_board {
+                let board_min = options
+                    .iter()
+                    .filter(|o| &o.board_type == board_type)
+                    .map(|o| o.price.amount)
+                    .min();
+                assert_eq!(Some(cheapest.amount), board_min);
+            }
+            assert!(summary.max_price.amount >= summary.min_price.amount);
+            assert_eq!(
+                summary.refundable_count,
+                options.iter().filter(|o| o.is_refundable).count()
+            );
+        }
+    }
+
+    #[test]
+    fn test_cheapest_per_board() {
+        let option = |room: &str, board: &str, price: i64, refundable: bool| HotelOption {
+            hotel_id: "H1".to_string(),
+            room_type: room.to_string(),
+            board_type: board.to_string(),
+            price: Money::new(Decimal::from(price), "EUR"),
+            is_refundable: refundable,
+            ..Default::default()
+        };
+        let summaries = summarize_hotels(&[
+            option("DBL", "BB", 120, true),
+            option("SGL", "RO", 70, false),
+            option("DBL", "BB", 110, false),
+            option("DBL", "RO", 90, true),
+        ]);
+
+        assert_eq!(summaries.len(), 1);
+        let summary = &summaries[0];
+        assert_eq!(summary.cheapest("BB").unwrap().amount, Decimal::from(110));
+        assert_eq!(summary.cheapest("RO").unwrap().amount, Decimal::from(70));
+        assert_eq!(summary.cheapest("HB"), None);
+        assert_eq!(
+            (summary.min_price.amount, summary.max_price.amount),
+            (Decimal::from(70), Decimal::from(120))
+        );
+        assert_eq!((summary.option_count, summary.refundable_count), (4, 2));
+        assert_eq!(summary.room_types, vec!["DBL", "SGL"]);
+    }
+}
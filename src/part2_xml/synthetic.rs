@@ -0,0 +1,144 @@
+// Synthetic supplier responses
+// Benchmarks and fixtures need supplier responses of any size that look like real ones:
+// several rooms per hotel, several rates per room, cancellation policies, now and then taxes
+// and promotions. `synthetic_response` builds one with exactly the number of rates asked for
+// (one option each when converted without a request). Values come from a generator seeded
+// with `seed`, so the same seed always gives the same response.
+
+use crate::supplier::{
+    RoomCapacity, SupplierCancellationPolicy, SupplierHotel, SupplierPromotion, SupplierRate,
+    SupplierResponse, SupplierRoom, SupplierTax,
+};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rust_decimal::Decimal;
+
+const ROOMS_PER_HOTEL: usize = 4;
+const RATES_PER_ROOM: usize = 5;
+const BOARD_TYPES: [&str; 5] = ["RO", "BB", "HB", "FB", "AI"];
+const ROOM_NAMES: [&str; 5] = ["SINGLE", "DOUBLE", "TWIN", "FAMILY", "SUITE"];
+const DESTINATIONS: [&str; 5] = ["PMI", "BCN", "MAD", "PAR", "NYC"];
+
+pub fn synthetic_response(seed: u64, options: usize) -> SupplierResponse {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let per_hotel = ROOMS_PER_HOTEL * RATES_PER_ROOM;
+    let hotels = (0..options.div_ceil(per_hotel))
+        .map(|index| {
+            let rates = per_hotel.min(options - index * per_hotel);
+            hotel(&mut rng, index, rates)
+        })
+        .collect();
+    SupplierResponse {
+        hotels,
+        search_id: format!("SYNTHETIC-{}", seed),
+        currency: "EUR".to_string(),
+        timestamp: "2025-06-01T12:00:00Z".to_string(),
+    }
+}
+
+pub fn synthetic_supplier_json(seed: u64, options: usize) -> String {
+    serde_json::to_string(&synthetic_response(seed, options)).expect("supplier responses serialize")
+}
+
+fn hotel(rng: &mut StdRng, index: usize, rates: usize) -> SupplierHotel {
+    let rooms = (0..rates.div_ceil(RATES_PER_ROOM))
+        .map(|room| {
+            let rate_count = RATES_PER_ROOM.min(rates - room * RATES_PER_ROOM);
+            let kind = rng.gen_range(0..ROOM_NAMES.len());
+            let adults = (kind as i32).clamp(1, 3);
+            SupplierRoom {
+                room_id: format!("R{}-{}", index, room),
+                name: format!("ROOM, {}", ROOM_NAMES[kind]),
+                rates: (0..rate_count)
+                    .map(|rate| self::rate(rng, index, room, rate))
+                    .collect(),
+                capacity: RoomCapacity {
+                    adults,
+                    children: if kind >= 3 { 2 } else { 0 },
+                },
+            }
+        })
+        .collect();
+    SupplierHotel {
+        hotel_id: format!("{}", 10_000_000 + index),
+        name: format!("Synthetic Hotel {}", index),
+        category: rng.gen_range(1..=5),
+        rooms,
+        destination_code: DESTINATIONS[rng.gen_range(0..DESTINATIONS.len())].to_string(),
+    }
+}
+
+fn rate(rng: &mut StdRng, hotel: usize, room: usize, rate: usize) -> SupplierRate {
+    let price = Decimal::new(rng.gen_range(4_000..90_000), 2);
+    let cancellation_policies = (0..rng.gen_range(0..3))
+        .map(|day| SupplierCancellationPolicy {
+            from_date: format!("2025-06-{:02}T00:00:00Z", 10 + day * 5),
+            amount: (price * Decimal::new(50 * (day as i64 + 1), 2)).round_dp(2),
+        })
+        .collect();
+    let taxes = if rng.gen_ratio(1, 4) {
+        vec![SupplierTax {
+            name: "City tax".to_string(),
+            included: false,
+            amount: None,
+            percent: Some(Decimal::new(rng.gen_range(5..15), 0)),
+            pay_at_hotel: rng.gen_bool(0.5),
+        }]
+    } else {
+        Vec::new()
+    };
+    let promotions = if rng.gen_ratio(1, 10) {
+        vec![SupplierPromotion {
+            code: "EB10".to_string(),
+            name: "Early booking -10%".to_string(),
+        }]
+    } else {
+        Vec::new()
+    };
+    let rate_id = format!("RATE-{}-{}-{}", hotel, room, rate);
+    SupplierRate {
+        booking_code: format!("BK{}", rate_id),
+        rate_id,
+        board_type: BOARD_TYPES[rng.gen_range(0..BOARD_TYPES.len())].to_string(),
+        price,
+        cancellation_policies,
+        taxes,
+        promotions,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::part2_xml::HotelSearchProcessor;
+
+    #[test]
+    fn test_synthetic_response() {
+        let response = synthetic_response(7, 47);
+        let rates: usize = response
+            .hotels
+            .iter()
+            .flat_map(|hotel| &hotel.rooms)
+            .map(|room| room.rates.len())
+            .sum();
+        assert_eq!(rates, 47);
+        assert_eq!(response.hotels.len(), 3);
+        assert!(synthetic_response(7, 0).hotels.is_empty());
+
+        // Seeded
+        assert_eq!(
+            synthetic_supplier_json(7, 47),
+            synthetic_supplier_json(7, 47)
+        );
+        assert_ne!(
+            synthetic_supplier_json(7, 47),
+            synthetic_supplier_json(8, 47)
+        );
+
+        let processor = HotelSearchProcessor::new();
+        let xml = processor
+            .convert_json_to_xml(&synthetic_supplier_json(7, 47))
+            .unwrap();
+        assert_eq!(processor.process(&xml).unwrap().total_options, 47);
+    }
+}
@@ -0,0 +1,56 @@
+// Taxes and fees
+// Some suppliers (most US ones) quote prices without taxes, which then look cheaper than
+// tax-inclusive prices of others. Rates may list their taxes and fees; included ones are part of
+// the price already, excluded ones come on top, paid with the booking or, if `pay_at_hotel`, at
+// the hotel. The XML carries them per room, percentages resolved to amounts of the room's price,
+// and an option lists the taxes of all its rooms since its price is theirs together.
+// Filtering and sorting by price use `HotelOption::total_including_taxes`.
+
+use crate::money::Money;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Tax {
+    pub name: String,
+    // Part of the option's price already
+    pub included: bool,
+    pub amount: Money,
+    pub pay_at_hotel: bool,
+}
+
+// What the excluded `taxes` add to `price`
+pub fn total_including_taxes(price: &Money, taxes: &[Tax]) -> Money {
+    let excluded = taxes
+        .iter()
+        .filter(|tax| !tax.included)
+        .map(|tax| tax.amount.amount)
+        .sum::<rust_decimal::Decimal>();
+    Money::new(price.amount + excluded, &price.currency)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_total_including_taxes() {
+        let tax = |included: bool, amount: &str, pay_at_hotel: bool| Tax {
+            name: "Tax".to_string(),
+            included,
+            amount: Money::parse(amount, "USD").unwrap(),
+            pay_at_hotel,
+        };
+        let price = Money::parse("200", "USD").unwrap();
+        assert_eq!(total_including_taxes(&price, &[]), price);
+        let taxes = [
+            tax(true, "18", false),
+            tax(false, "29.50", false),
+            tax(false, "25", true),
+        ];
+        assert_eq!(
+            total_including_taxes(&price, &taxes).to_string(),
+            "254.50 USD"
+        );
+    }
+}
@@ -0,0 +1,273 @@
+// Search tokens
+// Every option carries a "search_token" parameter that is sent back unchanged to value and book
+// it, so it has to identify what was searched: hotel, stay, occupancy, nationality, currency and
+// the supplier's rate key. Fields are '|'-separated in that order, followed by the search ID:
+//   39776757|2025-06-11|2025-06-12|2-0|US|GBP|RATE001|SEARCH123
+// Older tokens end after the currency and decode with an empty rate key and search ID.
+// A `TokenSigner` appends an HMAC-SHA256 of the token as a last field, so tokens handed to
+// clients can't be altered (e.g. to book another rate key at the quoted price) unnoticed. The
+// signature is checked in constant time, so response timing doesn't reveal how much of a forged
+// one was right.
+
+use super::dates::ISO_DATE_FORMAT;
+use super::ProcessingError;
+use chrono::NaiveDate;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+const SEPARATOR: char = '|';
+const SIGNATURE_PREFIX: &str = "sig=";
+// hotel, check-in, check-out, occupancy, nationality, currency
+const LEGACY_FIELDS: usize = 6;
+const FIELDS: usize = 8;
+
+#[derive(Debug, Clone, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchToken {
+    pub hotel_id: String,
+    pub check_in: Option<NaiveDate>,
+    pub check_out: Option<NaiveDate>,
+    // "{adults}-{children}", or as sent by the supplier
+    pub occupancy: String,
+    pub nationality: String,
+    pub currency: String,
+    pub rate_key: String,
+    pub search_id: String,
+}
+
+impl SearchToken {
+    pub fn new(hotel_id: impl Into<String>) -> Self {
+        Self {
+            hotel_id: hotel_id.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn stay(mut self, check_in: NaiveDate, check_out: NaiveDate) -> Self {
+        self.check_in = Some(check_in);
+        self.check_out = Some(check_out);
+        self
+    }
+
+    pub fn occupancy(mut self, adults: i32, children: i32) -> Self {
+        self.occupancy = format!("{}-{}", adults, children);
+        self
+    }
+
+    pub fn nationality(mut self, nationality: impl Into<String>) -> Self {
+        self.nationality = nationality.into();
+        self
+    }
+
+    pub fn currency(mut self, currency: impl Into<String>) -> Self {
+        self.currency = currency.into();
+        self
+    }
+
+    pub fn rate_key(mut self, rate_key: impl Into<String>) -> Self {
+        self.rate_key = rate_key.into();
+        self
+    }
+
+    pub fn search_id(mut self, search_id: impl Into<String>) -> Self {
+        self.search_id = search_id.into();
+        self
+    }
+
+    pub fn encode(&self) -> String {
+        let date = |date: Option<NaiveDate>| {
+            date.map(|date| date.format(ISO_DATE_FORMAT).to_string())
+                .unwrap_or_default()
+        };
+        [
+            escape(&self.hotel_id),
+            date(self.check_in),
+            date(self.check_out),
+            escape(&self.occupancy),
+            escape(&self.nationality),
+            escape(&self.currency),
+            escape(&self.rate_key),
+            escape(&self.search_id),
+        ]
+        .join("|")
+    }
+
+    // Decode a token, signed or not; the signature isn't checked, see `TokenSigner::verify`
+    pub fn decode(token: &str) -> Result<Self, ProcessingError> {
+        let (unsigned, _) = split_signature(token);
+        let fields: Vec<&str> = unsigned.split(SEPARATOR).collect();
+        if !(LEGACY_FIELDS..=FIELDS).contains(&fields.len()) {
+            return Err(ProcessingError::InvalidFormat(format!(
+                "search token has {} fields: {}",
+                fields.len(),
+                token
+            )));
+        }
+        let field = |index: usize| fields.get(index).map(|f| unescape(f)).unwrap_or_default();
+        let date = |index: usize| match fields[index] {
+            "" => Ok(None),
+            value => NaiveDate::parse_from_str(value, ISO_DATE_FORMAT)
+                .map(Some)
+                .map_err(|_| ProcessingError::InvalidDate(format!("{} in search token", value))),
+        };
+        let hotel_id = field(0);
+        if hotel_id.is_empty() {
+            return Err(ProcessingError::MissingRequiredField(
+                "search token hotel".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            hotel_id,
+            check_in: date(1)?,
+            check_out: date(2)?,
+            occupancy: field(3),
+            nationality: field(4),
+            currency: field(5),
+            rate_key: field(6),
+            search_id: field(7),
+        })
+    }
+}
+
+// Signs and verifies tokens with a secret key shared by every instance handing out tokens
+#[derive(Clone)]
+pub struct TokenSigner {
+    key: Vec<u8>,
+}
+
+impl TokenSigner {
+    pub fn new(key: impl Into<Vec<u8>>) -> Self {
+        Self { key: key.into() }
+    }
+
+    pub fn sign(&self, token: &SearchToken) -> String {
+        let encoded = token.encode();
+        let signature = self.signature(&encoded);
+        format!("{}|{}{}", encoded, SIGNATURE_PREFIX, signature)
+    }
+
+    // Decode a signed token, rejecting unsigned or altered ones
+    pub fn verify(&self, token: &str) -> Result<SearchToken, ProcessingError> {
+        let (unsigned, signature) = split_signature(token);
+        let signature = signature.ok_or_else(|| {
+            ProcessingError::MissingRequiredField("search token signature".to_string())
+        })?;
+        let mismatch =
+            || ProcessingError::InvalidFormat("search token signature mismatch".to_string());
+        let signature = hex::decode(signature).map_err(|_| mismatch())?;
+        self.mac(unsigned)
+            .verify_slice(&signature)
+            .map_err(|_| mismatch())?;
+        SearchToken::decode(unsigned)
+    }
+
+    fn mac(&self, unsigned: &str) -> Hmac<Sha256> {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.key).expect("HMAC accepts keys of any length");
+        mac.update(unsigned.as_bytes());
+        mac
+    }
+
+    fn signature(&self, unsigned: &str) -> String {
+        hex::encode(self.mac(unsigned).finalize().into_bytes())
+    }
+}
+
+// Keys aren't printed
+impl std::fmt::Debug for TokenSigner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TokenSigner").finish_non_exhaustive()
+    }
+}
+
+fn split_signature(token: &str) -> (&str, Option<&str>) {
+    match token.rsplit_once(SEPARATOR) {
+        Some((unsigned, last)) if last.starts_with(SIGNATURE_PREFIX) => {
+            (unsigned, Some(&last[SIGNATURE_PREFIX.len()..]))
+        }
+        _ => (token, None),
+    }
+}
+
+// Supplier codes may contain the separator; '%' is escaped so escaping can be undone
+fn escape(field: &str) -> String {
+    field.replace('%', "%25").replace(SEPARATOR, "%7C")
+}
+
+fn unescape(field: &str) -> String {
+    field.replace("%7C", "|").replace("%25", "%")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token() -> SearchToken {
+        SearchToken::new("39776757")
+            .stay(
+                NaiveDate::from_ymd_opt(2025, 6, 11).unwrap(),
+                NaiveDate::from_ymd_opt(2025, 6, 12).unwrap(),
+            )
+            .occupancy(2, 0)
+            .nationality("US")
+            .currency("GBP")
+            .rate_key("R|1%")
+            .search_id("S1")
+    }
+
+    #[test]
+    fn test_encode_decode() {
+        let encoded = token().encode();
+        assert_eq!(
+            encoded,
+            "39776757|2025-06-11|2025-06-12|2-0|US|GBP|R%7C1%25|S1"
+        );
+        assert_eq!(SearchToken::decode(&encoded).unwrap(), token());
+
+        let legacy = SearchToken::decode("39776757|2025-06-11|2025-06-12|A|US|GBP").unwrap();
+        assert_eq!(
+            (legacy.occupancy.as_str(), legacy.rate_key.as_str()),
+            ("A", "")
+        );
+
+        assert!(matches!(
+            SearchToken::decode("39776757|S1"),
+            Err(ProcessingError::InvalidFormat(_))
+        ));
+        assert!(matches!(
+            SearchToken::decode("39776757|11/06/2025||||"),
+            Err(ProcessingError::InvalidDate(_))
+        ));
+    }
+
+    #[test]
+    fn test_signed_tokens() {
+        let signer = TokenSigner::new("secret");
+        let signed = signer.sign(&token());
+        assert_eq!(signer.verify(&signed).unwrap(), token());
+        assert_eq!(SearchToken::decode(&signed).unwrap(), token());
+
+        let tampered = signed.replace("|GBP|", "|EUR|");
+        assert!(matches!(
+            signer.verify(&tampered),
+            Err(ProcessingError::InvalidFormat(_))
+        ));
+        assert!(TokenSigner::new("other").verify(&signed).is_err());
+        // Signatures of the wrong length, or not hex at all
+        let truncated = &signed[..signed.len() - 2];
+        let extended = format!("{}00", signed);
+        let not_hex = format!("{}|{}zz", token().encode(), SIGNATURE_PREFIX);
+        for forged in [truncated, &extended, &not_hex] {
+            assert!(matches!(
+                signer.verify(forged),
+                Err(ProcessingError::InvalidFormat(_))
+            ));
+        }
+        assert!(matches!(
+            signer.verify(&token().encode()),
+            Err(ProcessingError::MissingRequiredField(_))
+        ));
+    }
+}
@@ -0,0 +1,474 @@
+// Structural validation of AvailRS/AvailRQ documents (`--features validation`)
+// Deserializing a malformed document fails with one opaque serde message, or worse, succeeds
+// with defaults for whatever is missing. A `Validator` walks the raw document instead and
+// checks a set of rules (required attributes and child elements, date, currency and number
+// formats), reporting every violation with an XPath-like location such as
+// `/AvailRS/Hotels[1]/Hotel[2]/@code`. `Validator::avail_rs` and `Validator::avail_rq` bundle
+// the rules of the documents we generate and receive; custom rule sets are built the same way.
+// Rules match elements by local name, wherever they occur, so requests can be validated inside
+// the hub's SOAP envelope.
+
+use super::{dates, ProcessingError, Violation};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::reader::Reader;
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+
+// What a value must look like
+#[derive(Debug, Clone, Copy)]
+pub enum Check {
+    NotEmpty,
+    // yyyy-mm-dd or dd/mm/yyyy
+    Date,
+    // RFC 3339, see `dates::parse_datetime`
+    DateTime,
+    // Three upper-case letters (ISO 4217)
+    Currency,
+    Decimal,
+    Integer,
+    Boolean,
+    // Err with a description of what's wrong
+    Custom(fn(&str) -> Result<(), String>),
+}
+
+impl Check {
+    fn check(&self, value: &str) -> Result<(), String> {
+        let value = value.trim();
+        let valid = match self {
+            Check::NotEmpty => !value.is_empty(),
+            Check::Date => dates::parse_date(value).is_ok(),
+            Check::DateTime => dates::parse_datetime(value).is_ok(),
+            Check::Currency => value.len() == 3 && value.bytes().all(|b| b.is_ascii_uppercase()),
+            Check::Decimal => value.parse::<Decimal>().is_ok(),
+            Check::Integer => value.parse::<i64>().is_ok(),
+            Check::Boolean => matches!(value, "true" | "false"),
+            Check::Custom(check) => return check(value),
+        };
+        if valid {
+            Ok(())
+        } else {
+            Err(format!("{:?} expected, got {:?}", self, value))
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Target {
+    Attribute { name: String, required: bool },
+    Text,
+    Child(String),
+}
+
+#[derive(Debug, Clone)]
+struct Rule {
+    element: String,
+    target: Target,
+    check: Check,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Validator {
+    // The document element, which may be wrapped in an envelope
+    document: Option<String>,
+    rules: Vec<Rule>,
+}
+
+// An open element while walking the document
+struct Frame {
+    name: String,
+    path: String,
+    text: String,
+    // Child elements seen so far by local name, for positions and required children
+    children: HashMap<String, usize>,
+}
+
+impl Validator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn document(mut self, element: impl Into<String>) -> Self {
+        self.document = Some(element.into());
+        self
+    }
+
+    // `attribute` must be present on every `element` and pass `check`
+    pub fn attribute(
+        self,
+        element: impl Into<String>,
+        attribute: impl Into<String>,
+        check: Check,
+    ) -> Self {
+        self.attribute_rule(element.into(), attribute.into(), true, check)
+    }
+
+    // `attribute` is checked only where it's present
+    pub fn optional_attribute(
+        self,
+        element: impl Into<String>,
+        attribute: impl Into<String>,
+        check: Check,
+    ) -> Self {
+        self.attribute_rule(element.into(), attribute.into(), false, check)
+    }
+
+    pub fn text(mut self, element: impl Into<String>, check: Check) -> Self {
+        self.rules.push(Rule {
+            element: element.into(),
+            target: Target::Text,
+            check,
+        });
+        self
+    }
+
+    // Every `element` must contain at least one `child`
+    pub fn child(mut self, element: impl Into<String>, child: impl Into<String>) -> Self {
+        self.rules.push(Rule {
+            element: element.into(),
+            target: Target::Child(child.into()),
+            check: Check::NotEmpty,
+        });
+        self
+    }
+
+    fn attribute_rule(
+        mut self,
+        element: String,
+        name: String,
+        required: bool,
+        check: Check,
+    ) -> Self {
+        self.rules.push(Rule {
+            element,
+            target: Target::Attribute { name, required },
+            check,
+        });
+        self
+    }
+
+    // The AvailRS documents `convert_json_to_xml` generates and `process` reads
+    pub fn avail_rs() -> Self {
+        Self::new()
+            .document("AvailRS")
+            .attribute("Hotel", "code", Check::NotEmpty)
+            .attribute("Hotel", "name", Check::NotEmpty)
+            .optional_attribute("Hotel", "category", Check::Integer)
+            .attribute("MealPlan", "code", Check::NotEmpty)
+            .attribute("Option", "paymentType", Check::NotEmpty)
+            .child("Option", "Price")
+            .child("Option", "Rooms")
+            .attribute("Price", "currency", Check::Currency)
+            .attribute("Price", "amount", Check::Decimal)
+            .attribute("Room", "id", Check::NotEmpty)
+            .attribute("Room", "code", Check::NotEmpty)
+            .attribute("Room", "nonRefundable", Check::Boolean)
+            .optional_attribute("Room", "adults", Check::Integer)
+            .optional_attribute("Room", "children", Check::Integer)
+            .optional_attribute("CancelPenalties", "nonRefundable", Check::Boolean)
+            .child("CancelPenalty", "Penalty")
+            .child("CancelPenalty", "Deadline")
+            .attribute("Penalty", "type", Check::NotEmpty)
+            .attribute("Penalty", "currency", Check::Currency)
+            .text("Penalty", Check::Decimal)
+            .text("Deadline", Check::DateTime)
+            .attribute("Tax", "name", Check::NotEmpty)
+            .attribute("Tax", "included", Check::Boolean)
+            .attribute("Tax", "amount", Check::Decimal)
+            .attribute("Tax", "currency", Check::Currency)
+            .optional_attribute("Tax", "percent", Check::Decimal)
+            .attribute("Tax", "payAtHotel", Check::Boolean)
+            .attribute("Offer", "code", Check::NotEmpty)
+            .attribute("Parameter", "key", Check::NotEmpty)
+    }
+
+    // AvailRQ search requests, see `AvailRQ`
+    pub fn avail_rq() -> Self {
+        Self::new()
+            .document("AvailRQ")
+            .child("AvailRQ", "Currency")
+            .child("AvailRQ", "StartDate")
+            .child("AvailRQ", "EndDate")
+            .child("AvailRQ", "RoomCandidates")
+            .text("Currency", Check::Currency)
+            .text("Nationality", Check::NotEmpty)
+            .text("StartDate", Check::Date)
+            .text("EndDate", Check::Date)
+            .attribute("Destination", "type", Check::NotEmpty)
+            .attribute("Destination", "code", Check::NotEmpty)
+            .child("RoomCandidate", "Paxes")
+            .attribute("RoomCandidate", "id", Check::Integer)
+            .attribute("Pax", "age", Check::Integer)
+            .attribute("Parameter", "key", Check::NotEmpty)
+    }
+
+    // Err(ProcessingError::Invalid) listing the violations, if there are any
+    pub fn check(&self, xml: &str) -> Result<(), ProcessingError> {
+        let violations = self.validate(xml);
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(ProcessingError::Invalid(violations))
+        }
+    }
+
+    // Every violation in document order; empty if the document is valid. Malformed XML is
+    // reported where parsing stopped.
+    pub fn validate(&self, xml: &str) -> Vec<Violation> {
+        let mut violations = Vec::new();
+        let mut stack: Vec<Frame> = Vec::new();
+        let mut document_found = false;
+        let mut reader = Reader::from_str(xml);
+
+        loop {
+            let event = match reader.read_event() {
+                Ok(event) => event,
+                Err(e) => {
+                    violations.push(Violation {
+                        path: current_path(&stack),
+                        message: format!("malformed XML: {}", e),
+                    });
+                    break;
+                }
+            };
+            match event {
+                Event::Start(start) => {
+                    let frame = self.open(&start, &mut stack, &mut violations);
+                    document_found |= self.document.as_deref() == Some(frame.name.as_str());
+                    stack.push(frame);
+                }
+                Event::Empty(start) => {
+                    let frame = self.open(&start, &mut stack, &mut violations);
+                    document_found |= self.document.as_deref() == Some(frame.name.as_str());
+                    self.close(frame, &mut violations);
+                }
+                Event::Text(text) => {
+                    if let (Some(frame), Ok(text)) = (stack.last_mut(), text.decode()) {
+                        frame.text.push_str(&text);
+                    }
+                }
+                Event::CData(text) => {
+                    if let Some(frame) = stack.last_mut() {
+                        frame.text.push_str(&String::from_utf8_lossy(&text));
+                    }
+                }
+                Event::End(_) => {
+                    if let Some(frame) = stack.pop() {
+                        self.close(frame, &mut violations);
+                    }
+                }
+                Event::Eof => break,
+                _ => {}
+            }
+        }
+
+        if let Some(document) = self.document.as_ref().filter(|_| !document_found) {
+            violations.insert(
+                0,
+                Violation {
+                    path: "/".to_string(),
+                    message: format!("missing <{}>", document),
+                },
+            );
+        }
+        violations
+    }
+
+    fn rules_for<'a>(&'a self, element: &'a str) -> impl Iterator<Item = &'a Rule> + 'a {
+        self.rules
+            .iter()
+            .filter(move |rule| rule.element == element)
+    }
+
+    // Check the attributes of an element and the frame to push for it
+    fn open(
+        &self,
+        start: &BytesStart<'_>,
+        stack: &mut [Frame],
+        violations: &mut Vec<Violation>,
+    ) -> Frame {
+        let qualified = String::from_utf8_lossy(start.name().as_ref()).into_owned();
+        let name = String::from_utf8_lossy(start.local_name().as_ref()).into_owned();
+        let path = match stack.last_mut() {
+            Some(parent) => {
+                let position = parent.children.entry(name.clone()).or_insert(0);
+                *position += 1;
+                format!("{}/{}[{}]", parent.path, qualified, position)
+            }
+            None => format!("/{}", qualified),
+        };
+
+        let mut attributes = HashMap::new();
+        for attribute in start.attributes() {
+            match attribute {
+                Ok(attribute) => {
+                    let key =
+                        String::from_utf8_lossy(attribute.key.local_name().as_ref()).into_owned();
+                    let value = attribute
+                        .unescape_value()
+                        .map(|value| value.into_owned())
+                        .unwrap_or_default();
+                    attributes.insert(key, value);
+                }
+                Err(e) => violations.push(Violation {
+                    path: path.clone(),
+                    message: format!("malformed attribute: {}", e),
+                }),
+            }
+        }
+        for rule in self.rules_for(&name) {
+            if let Target::Attribute { name, required } = &rule.target {
+                let path = format!("{}/@{}", path, name);
+                match attributes.get(name) {
+                    Some(value) => {
+                        if let Err(message) = rule.check.check(value) {
+                            violations.push(Violation { path, message });
+                        }
+                    }
+                    None if *required => violations.push(Violation {
+                        path,
+                        message: "missing attribute".to_string(),
+                    }),
+                    None => {}
+                }
+            }
+        }
+
+        Frame {
+            name,
+            path,
+            text: String::new(),
+            children: HashMap::new(),
+        }
+    }
+
+    // Check the text and children of an element once it's complete
+    fn close(&self, frame: Frame, violations: &mut Vec<Violation>) {
+        for rule in self.rules_for(&frame.name) {
+            match &rule.target {
+                Target::Text => {
+                    if let Err(message) = rule.check.check(&frame.text) {
+                        violations.push(Violation {
+                            path: format!("{}/text()", frame.path),
+                            message,
+                        });
+                    }
+                }
+                Target::Child(child) if !frame.children.contains_key(child) => {
+                    violations.push(Violation {
+                        path: frame.path.clone(),
+                        message: format!("missing <{}>", child),
+                    })
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+fn current_path(stack: &[Frame]) -> String {
+    stack
+        .last()
+        .map(|frame| frame.path.clone())
+        .unwrap_or_else(|| "/".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::part2_xml::{HotelSearchProcessor, SMALL_SAMPLE_XML};
+
+    #[test]
+    fn test_valid_documents() {
+        let processor = HotelSearchProcessor::new();
+        let converted = processor
+            .convert_json_to_xml(&processor.load_sample_json().unwrap())
+            .unwrap();
+        assert_eq!(Validator::avail_rs().validate(&converted), vec![]);
+        assert_eq!(Validator::avail_rs().validate(SMALL_SAMPLE_XML), vec![]);
+        let request = processor.load_sample_request().unwrap();
+        assert_eq!(Validator::avail_rq().validate(&request), vec![]);
+    }
+
+    #[test]
+    fn test_violations_are_located() {
+        let xml = r#"<AvailRS><Hotels>
+            <Hotel code="1" name="One"/>
+            <Hotel name="Two" category="three"><MealPlans><MealPlan code="RO"><Options>
+              <Option type="Hotel" paymentType="MerchantPay">
+                <Price currency="gbp" amount="84.82"/>
+                <Rooms/>
+              </Option>
+            </Options></MealPlan></MealPlans></Hotel>
+        </Hotels></AvailRS>"#;
+        let violations: Vec<String> = Validator::avail_rs()
+            .validate(xml)
+            .iter()
+            .map(|violation| violation.path.clone())
+            .collect();
+        assert_eq!(
+            violations,
+            vec![
+                "/AvailRS/Hotels[1]/Hotel[2]/@code",
+                "/AvailRS/Hotels[1]/Hotel[2]/@category",
+                "/AvailRS/Hotels[1]/Hotel[2]/MealPlans[1]/MealPlan[1]/Options[1]/Option[1]/Price[1]/@currency",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_request_violations() {
+        let xml = r#"<soapenv:Envelope xmlns:soapenv="http://schemas.xmlsoap.org/soap/envelope/">
+            <soapenv:Body><AvailRQ>
+              <Currency>GBP</Currency>
+              <StartDate>2025-13-01</StartDate>
+              <RoomCandidates><RoomCandidate id="1"><Paxes><Pax age="adult"/></Paxes></RoomCandidate></RoomCandidates>
+            </AvailRQ></soapenv:Body>
+        </soapenv:Envelope>"#;
+        let violations = Validator::avail_rq().validate(xml);
+        let messages: Vec<String> = violations.iter().map(ToString::to_string).collect();
+        assert_eq!(messages.len(), 3, "{:?}", messages);
+        assert!(messages[0].starts_with(
+            "/soapenv:Envelope/soapenv:Body[1]/AvailRQ[1]/StartDate[1]/text(): Date expected"
+        ));
+        assert!(messages[1].ends_with("/Pax[1]/@age: Integer expected, got \"adult\""));
+        assert_eq!(
+            messages[2],
+            "/soapenv:Envelope/soapenv:Body[1]/AvailRQ[1]: missing <EndDate>"
+        );
+
+        let malformed = Validator::avail_rq().validate("<AvailRQ><Currency>GBP</AvailRQ>");
+        assert_eq!(malformed.len(), 1);
+        assert_eq!(malformed[0].path, "/AvailRQ/Currency[1]");
+        assert!(malformed[0].message.starts_with("malformed XML"));
+        assert_eq!(
+            Validator::avail_rq().validate("<AvailRS/>")[0].message,
+            "missing <AvailRQ>"
+        );
+    }
+
+    #[test]
+    fn test_validation_mode() {
+        let processor = HotelSearchProcessor::new().with_validation();
+        let request = processor.load_sample_request().unwrap();
+        assert!(processor.parse_request(&request).is_ok());
+
+        let invalid = request.replace("<Currency>GBP</Currency>", "<Currency>£</Currency>");
+        match processor.parse_request(&invalid) {
+            Err(ProcessingError::Invalid(violations)) => {
+                assert!(violations[0]
+                    .path
+                    .ends_with("/AvailRQ[1]/Currency[1]/text()"))
+            }
+            other => panic!("expected violations, got {:?}", other),
+        }
+        assert!(HotelSearchProcessor::new().parse_request(&invalid).is_ok());
+
+        let json = processor.load_sample_json().unwrap();
+        assert!(processor.convert_json_to_xml(&json).is_ok());
+        let json = json.replace("\"currency\": \"USD\"", "\"currency\": \"\"");
+        assert!(matches!(
+            processor.convert_json_to_xml(&json),
+            Err(ProcessingError::Invalid(_))
+        ));
+    }
+}
@@ -0,0 +1,202 @@
+// ValuationRQ/ValuationRS rate re-check
+// Search results can be minutes old by the time a guest books; between search and book the
+// option is valued again. The ValuationRQ identifies the option by its booking code (the search
+// token), the ValuationRS carries the current price and cancellation conditions, which
+// `ValuationCheck` compares with what the guest was shown.
+
+use super::booking::XmlAmount;
+use super::dates::{self, request_date};
+use super::request::XmlConfiguration;
+use super::stream::find_element;
+use super::{cancellation_policies, HotelOption, ProcessedCancellationPolicy, ProcessingError};
+use crate::money::Money;
+use crate::xml_response::{XmlCancelPenalties, XmlParameter};
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+#[serde(default, rename = "ValuationRQ", rename_all = "PascalCase")]
+pub struct ValuationRQ {
+    pub configuration: XmlConfiguration,
+    #[serde(with = "request_date")]
+    pub start_date: NaiveDate,
+    #[serde(with = "request_date")]
+    pub end_date: NaiveDate,
+    pub hotel_code: String,
+    pub meal_plan_code: String,
+    pub room_code: String,
+    // The option's search token
+    pub booking_code: String,
+}
+
+impl ValuationRQ {
+    pub fn new(
+        hotel_code: impl Into<String>,
+        booking_code: impl Into<String>,
+        start_date: NaiveDate,
+        end_date: NaiveDate,
+    ) -> Self {
+        Self {
+            hotel_code: hotel_code.into(),
+            booking_code: booking_code.into(),
+            start_date,
+            end_date,
+            ..Default::default()
+        }
+    }
+
+    pub fn for_option(option: &HotelOption, start_date: NaiveDate, end_date: NaiveDate) -> Self {
+        Self {
+            meal_plan_code: option.board_type.clone(),
+            room_code: option.room_type.clone(),
+            ..Self::new(&option.hotel_id, &option.search_token, start_date, end_date)
+        }
+    }
+
+    pub fn parameter(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.configuration.parameters.parameters.push(XmlParameter {
+            key: key.into(),
+            value: value.into(),
+        });
+        self
+    }
+
+    pub fn from_xml(xml: &str) -> Result<Self, ProcessingError> {
+        let request = find_element(xml.as_bytes(), "ValuationRQ")?
+            .ok_or_else(|| ProcessingError::MissingRequiredField("ValuationRQ".to_string()))?;
+        quick_xml::de::from_str(&request).map_err(dates::de_error)
+    }
+
+    pub fn to_xml(&self) -> Result<String, ProcessingError> {
+        quick_xml::se::to_string(self).map_err(|e| ProcessingError::ConversionError(e.to_string()))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+#[serde(default, rename = "ValuationRS", rename_all = "PascalCase")]
+pub struct ValuationRS {
+    pub status: String,
+    pub price: XmlAmount,
+    pub cancel_penalties: XmlCancelPenalties,
+    pub remarks: String,
+}
+
+impl ValuationRS {
+    pub fn from_xml(xml: &str) -> Result<Self, ProcessingError> {
+        let response = find_element(xml.as_bytes(), "ValuationRS")?
+            .ok_or_else(|| ProcessingError::MissingRequiredField("ValuationRS".to_string()))?;
+        quick_xml::de::from_str(&response)
+            .map_err(|e| ProcessingError::XmlParseError(e.to_string()))
+    }
+
+    pub fn to_xml(&self) -> Result<String, ProcessingError> {
+        quick_xml::se::to_string(self).map_err(|e| ProcessingError::ConversionError(e.to_string()))
+    }
+}
+
+// Outcome of valuing an option again, see `HotelSearchProcessor::check_valuation`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValuationCheck {
+    pub original_price: Money,
+    pub current_price: Money,
+    pub cancellation_policies: Vec<ProcessedCancellationPolicy>,
+    pub is_refundable: bool,
+    pub price_changed: bool,
+    // Different deadlines, penalties or refundability
+    pub conditions_changed: bool,
+    pub remarks: String,
+}
+
+impl ValuationCheck {
+    pub fn compare(option: &HotelOption, response: &ValuationRS) -> Result<Self, ProcessingError> {
+        let current_price = response
+            .price
+            .money()
+            .ok_or_else(|| ProcessingError::MissingRequiredField("Price".to_string()))?;
+        let policies = cancellation_policies(&response.cancel_penalties)?;
+        let is_refundable = !response
+            .cancel_penalties
+            .non_refundable
+            .eq_ignore_ascii_case("true");
+        Ok(Self {
+            price_changed: current_price != option.price,
+            conditions_changed: policies != option.cancellation_policies
+                || is_refundable != option.is_refundable,
+            original_price: option.price.clone(),
+            current_price,
+            cancellation_policies: policies,
+            is_refundable,
+            remarks: response.remarks.clone(),
+        })
+    }
+
+    // Positive if the option got more expensive
+    pub fn price_difference(&self) -> Decimal {
+        self.current_price.amount - self.original_price.amount
+    }
+
+    pub fn unchanged(&self) -> bool {
+        !self.price_changed && !self.conditions_changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::part2_xml::{HotelSearchProcessor, SMALL_SAMPLE_XML};
+
+    fn valuation(amount: &str, deadline: &str) -> String {
+        format!(
+            r#"<ValuationRS>
+              <Status>OK</Status>
+              <Price currency="GBP" amount="{}"/>
+              <CancelPenalties nonRefundable="false">
+                <CancelPenalty>
+                  <HoursBefore>26</HoursBefore>
+                  <Penalty type="Importe" currency="GBP">84.82</Penalty>
+                  <Deadline>{}</Deadline>
+                </CancelPenalty>
+              </CancelPenalties>
+            </ValuationRS>"#,
+            amount, deadline
+        )
+    }
+
+    #[test]
+    fn test_request_roundtrip() {
+        let processor = HotelSearchProcessor::new();
+        let option = &processor.process(SMALL_SAMPLE_XML).unwrap().hotels[0];
+        let date = |day| NaiveDate::from_ymd_opt(2025, 6, day).unwrap();
+
+        let request = ValuationRQ::for_option(option, date(11), date(12));
+        assert_eq!(request.booking_code, option.search_token);
+        assert_eq!(request.room_code, "ND1");
+        assert_eq!(
+            ValuationRQ::from_xml(&request.to_xml().unwrap()).unwrap(),
+            request
+        );
+    }
+
+    #[test]
+    fn test_compare_with_option() {
+        let processor = HotelSearchProcessor::new();
+        let option = &processor.process(SMALL_SAMPLE_XML).unwrap().hotels[0];
+
+        let same = valuation("84.82", "2025-06-10T10:00:00Z");
+        assert!(processor
+            .check_valuation(option, &same)
+            .unwrap()
+            .unchanged());
+
+        let dearer = ValuationRS::from_xml(&valuation("90.00", "2025-06-09T10:00:00Z")).unwrap();
+        let check = ValuationCheck::compare(option, &dearer).unwrap();
+        assert!(check.price_changed && check.conditions_changed);
+        assert_eq!(check.price_difference(), Decimal::new(518, 2));
+
+        assert!(matches!(
+            ValuationCheck::compare(option, &ValuationRS::default()),
+            Err(ProcessingError::MissingRequiredField(_))
+        ));
+    }
+}
@@ -1,18 +1,69 @@
 // Part 3: Rate-Limited API Client Implementation (Advanced Difficulty)
 // This component is our customer-facing API that must handle extreme traffic while maintaining reliability
 
+use crate::part2_xml::SearchParams;
 use async_trait::async_trait;
-use std::time::Duration;
+use parking_lot::{Mutex, RwLock};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::future::Future;
+use std::sync::{Arc, Weak};
+use std::time::{Duration, SystemTime};
 use thiserror::Error;
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+use tracing::Instrument;
+use transport::{Request, Response};
+
+pub mod bulkhead;
+pub mod cached_search;
+pub mod cancellation;
+pub mod circuit_breaker;
+pub mod health;
+pub mod idempotency;
+pub mod latency;
+pub mod metrics;
+#[cfg(feature = "otlp")]
+pub mod otlp;
+pub mod polling;
+pub mod rate_limiter;
+pub mod retry;
+pub mod router;
+pub mod scheduler;
+pub mod single_flight;
+pub mod stream;
+pub mod transport;
+
+pub use bulkhead::Bulkhead;
+pub use cached_search::CachedSearchClient;
+pub use cancellation::CancellationRegistry;
+pub use circuit_breaker::{CircuitBreaker, CircuitState};
+pub use health::{HealthMonitor, HealthProbe, HealthTransition};
+pub use idempotency::IdempotencyLayer;
+pub use latency::{LatencyRecorder, LatencyStats, Operation};
+pub use metrics::Exposition;
+pub use polling::PollPolicy;
+pub use rate_limiter::{RateLimiter, TokenBucket};
+pub use retry::Retrier;
+pub use router::SupplierRouter;
+pub use scheduler::{OverflowPolicies, OverflowPolicy, QueueAging, Scheduler};
+pub use single_flight::SingleFlight;
+pub use transport::{HttpTransport, Transport};
 
 // Enhanced error types for API client
-#[derive(Error, Debug)]
+#[derive(Error, Debug, Clone)]
 pub enum ApiError {
     #[error("Network error: {0}")]
     NetworkError(String),
 
-    #[error("Rate limit exceeded: {0}")]
-    RateLimitExceeded(String),
+    #[error("Rate limit exceeded: {message}")]
+    RateLimitExceeded {
+        message: String,
+        // How long the upstream asked to wait, if it said
+        retry_after_ms: Option<u64>,
+    },
 
     #[error("Request timeout after {0}ms")]
     Timeout(u64),
@@ -33,16 +84,36 @@ pub enum ApiError {
     #[error("Preempted by higher priority request")]
     RequestPreempted,
 
+    #[error("Request cancelled")]
+    RequestCancelled,
+
     #[error("Client error: {0}")]
     ClientError(String),
 
     #[error("Request queue full")]
     QueueFull,
 
+    #[error("Queued for {0}ms, past the maximum queue wait")]
+    QueueWaitExceeded(u64),
+
     #[error("Other error: {0}")]
     Other(String),
 }
 
+impl ApiError {
+    // Whether sending the request again may succeed: network errors, timeouts, rate limiting
+    // and API errors flagged retryable
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            ApiError::NetworkError(_)
+            | ApiError::Timeout(_)
+            | ApiError::RateLimitExceeded { .. } => true,
+            ApiError::ApiResponseError { is_retryable, .. } => *is_retryable,
+            _ => false,
+        }
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum ClientError {
     #[error("Configuration error: {0}")]
@@ -64,7 +135,15 @@ pub struct ClientConfig {
     pub retry_config: RetryConfig,
     pub circuit_breaker_config: CircuitBreakerConfig,
     pub queue_size_per_priority: usize,
+    // What a full queue does with one more request, per priority (see `scheduler`)
+    pub overflow_policies: OverflowPolicies,
+    // Promotion of requests waiting long, and how long they may wait at most (see `scheduler`)
+    pub queue_aging: QueueAging,
     pub health_check_interval_ms: u64,
+    // How the health checker probes the upstream, None for no health checks (see `health`)
+    pub health_check_probe: Option<HealthProbe>,
+    // Identical searches in flight together share one upstream call
+    pub coalesce_searches: bool,
 }
 
 // Enhanced retry configuration
@@ -75,6 +154,9 @@ pub struct RetryConfig {
     pub max_backoff_ms: u64,
     pub backoff_multiplier: f64,
     pub jitter_factor: f64,
+    // Longest wait taken from the upstream (Retry-After, rate limit reset), for the retry and
+    // the pause of the rate limiter; longer ones are cut to it
+    pub max_retry_after_ms: u64,
 }
 
 impl Default for RetryConfig {
@@ -85,6 +167,7 @@ impl Default for RetryConfig {
             max_backoff_ms: 10000,
             backoff_multiplier: 2.0,
             jitter_factor: 0.1,
+            max_retry_after_ms: 60000,
         }
     }
 }
@@ -92,10 +175,15 @@ impl Default for RetryConfig {
 // Circuit breaker configuration
 #[derive(Debug, Clone)]
 pub struct CircuitBreakerConfig {
+    // Consecutive failures
     pub failure_threshold: u32,
     pub success_threshold: u32,
     pub reset_timeout_ms: u64,
     pub half_open_max_requests: u32,
+    // Share of failures among the last `failure_rate_window` requests that also opens the
+    // breaker; None to only count consecutive failures
+    pub failure_rate_threshold: Option<f64>,
+    pub failure_rate_window: u32,
 }
 
 impl Default for CircuitBreakerConfig {
@@ -105,23 +193,30 @@ impl Default for CircuitBreakerConfig {
             success_threshold: 3,
             reset_timeout_ms: 30000,
             half_open_max_requests: 1,
+            failure_rate_threshold: None,
+            failure_rate_window: 20,
         }
     }
 }
 
 // Request priority levels
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
 pub enum RequestPriority {
     Low = 0,
+    #[default]
     Medium = 1,
     High = 2,
     Critical = 3,
 }
 
-impl Default for RequestPriority {
-    fn default() -> Self {
-        RequestPriority::Medium
-    }
+impl RequestPriority {
+    // Lowest first
+    pub const ALL: [RequestPriority; 4] = [
+        RequestPriority::Low,
+        RequestPriority::Medium,
+        RequestPriority::High,
+        RequestPriority::Critical,
+    ];
 }
 
 // Enhanced client statistics
@@ -133,17 +228,45 @@ pub struct ClientStats {
     pub requests_throttled: usize,
     pub requests_retried: usize,
     pub requests_preempted: usize,
+    // Refused because their queue was full
+    pub requests_rejected: usize,
+    // Moved up a priority after waiting `promote_after_ms`, once per promotion
+    pub requests_promoted: usize,
+    // Failed after waiting `max_queue_wait_ms` in the queue
+    pub requests_queue_wait_exceeded: usize,
     pub requests_timeout: usize,
+    // Dropped from the queue past their deadline, before being sent
+    pub requests_expired: usize,
+    // Cancelled with `cancel_request`, queued or in flight
+    pub requests_cancelled: usize,
+    // Bookings answered with the outcome of another with their idempotency key
+    pub requests_deduplicated: usize,
+    // Searches answered with the response of an identical one in flight
+    pub requests_coalesced: usize,
     pub requests_circuit_broken: usize,
     pub average_response_time_ms: f64,
     pub p95_response_time_ms: f64,
     pub p99_response_time_ms: f64,
     pub max_response_time_ms: f64,
+    pub search_latency: LatencyStats,
+    pub booking_latency: LatencyStats,
+    pub cancellation_latency: LatencyStats,
+    pub modification_latency: LatencyStats,
+    pub status_latency: LatencyStats,
+    // How long requests sent were queued
+    pub queue_wait: LatencyStats,
     pub active_requests: usize,
+    // Requests in flight per priority, lowest first
+    pub in_flight_by_priority: Vec<(RequestPriority, usize)>,
     pub queue_depth: usize,
+    // Requests queued per priority, lowest first
+    pub queue_depth_by_priority: Vec<(RequestPriority, usize)>,
     pub circuit_breaker_open: bool,
+    // State of the breaker of each operation
+    pub circuit_breakers: Vec<(String, CircuitState)>,
     pub current_rate_limit: u32,
     pub adaptive_rate_limit_multiplier: f64,
+    pub system_health: SystemHealth,
 }
 
 // Request and response types (enhanced for the assessment)
@@ -153,11 +276,37 @@ pub struct SearchRequest {
     pub check_in: String,
     pub check_out: String,
     pub guests: u32,
+    // ISO 3166-1 alpha-2 markets searched for; results are cached per set of markets
+    pub markets: Vec<String>,
+    // The supplier to search, when routed (see `router`); None to route by hotel
+    pub supplier: Option<String>,
     pub priority: RequestPriority,
     pub idempotency_key: Option<String>,
     pub context: RequestContext,
 }
 
+impl SearchRequest {
+    // A search for the hotels, stay, party and markets of a supplier request, given up on after
+    // its timeoutMilliseconds
+    pub fn from_search_params(params: &SearchParams, priority: RequestPriority) -> Self {
+        let (adults, children) = params.guests();
+        Self {
+            hotel_ids: params.hotel_codes.clone(),
+            check_in: params.check_in.to_string(),
+            check_out: params.check_out.to_string(),
+            guests: adults + children,
+            markets: params.markets.clone(),
+            supplier: None,
+            priority,
+            idempotency_key: None,
+            context: RequestContext {
+                request_deadline: params.deadline(SystemTime::now()),
+                ..Default::default()
+            },
+        }
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct RequestContext {
     pub user_id: Option<String>,
@@ -167,6 +316,23 @@ pub struct RequestContext {
     pub request_deadline: Option<std::time::SystemTime>,
 }
 
+impl RequestContext {
+    // Give up on the request `timeout` from now
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.request_deadline = Some(SystemTime::now() + timeout);
+        self
+    }
+
+    // Time left until the deadline, zero once it has passed
+    pub fn remaining(&self) -> Option<Duration> {
+        self.request_deadline.map(|deadline| {
+            deadline
+                .duration_since(SystemTime::now())
+                .unwrap_or(Duration::ZERO)
+        })
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ClientInfo {
     pub ip: String,
@@ -182,12 +348,27 @@ pub struct SearchResponse {
     pub processing_time_ms: u64,
 }
 
-#[derive(Debug, Clone)]
+impl SearchResponse {
+    // The search ID to book `hotel_id` with: the result's own if it came from an earlier search,
+    // the response's otherwise; None if the hotel isn't in the results
+    pub fn search_id_for(&self, hotel_id: &str) -> Option<&str> {
+        self.results
+            .iter()
+            .find(|result| result.hotel_id == hotel_id)
+            .map(|result| result.search_id.as_deref().unwrap_or(&self.search_id))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct SearchResult {
     pub hotel_id: String,
     pub available: bool,
     pub price: Option<f64>,
     pub currency: Option<String>,
+    // The earlier search the result was found by (results served from a cache); None when it
+    // belongs to the response's own search
+    #[serde(default)]
+    pub search_id: Option<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -212,20 +393,138 @@ pub struct PaymentInfo {
 #[derive(Debug, Clone)]
 pub struct BookingResponse {
     pub booking_id: String,
-    pub status: String,
+    pub status: BookingStatus,
+    pub confirmation_code: Option<String>,
+    pub rate_limit_remaining: Option<u32>,
+    pub processing_time_ms: u64,
+}
+
+// Where a booking stands. Suppliers confirm bookings asynchronously, so one may be Pending, or
+// in a state of the supplier's own, before it's Confirmed or Failed.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum BookingStatus {
+    Pending,
+    Confirmed,
+    Failed,
+    Cancelled,
+    // As the supplier named it
+    Other(String),
+}
+
+impl BookingStatus {
+    // Confirmed, failed or cancelled: it won't change by itself
+    pub fn is_terminal(&self) -> bool {
+        matches!(
+            self,
+            BookingStatus::Confirmed | BookingStatus::Failed | BookingStatus::Cancelled
+        )
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            BookingStatus::Pending => "pending",
+            BookingStatus::Confirmed => "confirmed",
+            BookingStatus::Failed => "failed",
+            BookingStatus::Cancelled => "cancelled",
+            BookingStatus::Other(status) => status,
+        }
+    }
+}
+
+// Whatever the case
+impl From<&str> for BookingStatus {
+    fn from(status: &str) -> Self {
+        match status.to_ascii_lowercase().as_str() {
+            "pending" => BookingStatus::Pending,
+            "confirmed" => BookingStatus::Confirmed,
+            "failed" => BookingStatus::Failed,
+            "cancelled" | "canceled" => BookingStatus::Cancelled,
+            _ => BookingStatus::Other(status.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for BookingStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CancelBookingRequest {
+    pub booking_id: String,
+    pub reason: Option<String>,
+    pub idempotency_key: String,
+    pub context: RequestContext,
+}
+
+#[derive(Debug, Clone)]
+pub struct CancelBookingResponse {
+    pub booking_id: String,
+    pub status: BookingStatus,
+    // Charged under the booking's cancellation policy
+    pub penalty_amount: f64,
+    // Paid back, what was paid less the penalty
+    pub refund_amount: f64,
+    pub currency: Option<String>,
+    pub rate_limit_remaining: Option<u32>,
+    pub processing_time_ms: u64,
+}
+
+// What to change of a booking; None keeps it as it is
+#[derive(Debug, Clone, Default)]
+pub struct ModifyBookingRequest {
+    pub booking_id: String,
+    pub check_in: Option<String>,
+    pub check_out: Option<String>,
+    pub guests: Option<u32>,
+    pub guest_name: Option<String>,
+    pub idempotency_key: String,
+    pub context: RequestContext,
+}
+
+impl ModifyBookingRequest {
+    // Whether it changes anything
+    pub fn has_changes(&self) -> bool {
+        self.check_in.is_some()
+            || self.check_out.is_some()
+            || self.guests.is_some()
+            || self.guest_name.is_some()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ModifyBookingResponse {
+    pub booking_id: String,
+    pub status: BookingStatus,
     pub confirmation_code: Option<String>,
+    // Charged for the change if positive, refunded if negative
+    pub price_difference: Option<f64>,
+    pub currency: Option<String>,
     pub rate_limit_remaining: Option<u32>,
     pub processing_time_ms: u64,
 }
 
 // Health status for adaptively adjusting rate limits
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum SystemHealth {
+    #[default]
     Healthy,
     Degraded,
     Unhealthy,
 }
 
+impl SystemHealth {
+    // Share of the configured request rate the upstream is sent
+    pub fn rate_multiplier(self) -> f64 {
+        match self {
+            SystemHealth::Healthy => 1.0,
+            SystemHealth::Degraded => 0.6,
+            SystemHealth::Unhealthy => 0.2,
+        }
+    }
+}
+
 // API client trait with enhanced requirements
 #[async_trait]
 pub trait ApiClient: Send + Sync + 'static {
@@ -235,6 +534,30 @@ pub trait ApiClient: Send + Sync + 'static {
     // Basic booking operation
     async fn book(&self, request: BookingRequest) -> Result<BookingResponse, ApiError>;
 
+    // Cancel a confirmed booking
+    async fn cancel_booking(
+        &self,
+        request: CancelBookingRequest,
+    ) -> Result<CancelBookingResponse, ApiError>;
+
+    // Change the dates, party or guest name of a confirmed booking
+    async fn modify_booking(
+        &self,
+        request: ModifyBookingRequest,
+    ) -> Result<ModifyBookingResponse, ApiError>;
+
+    // Where a booking stands now
+    async fn get_booking_status(&self, booking_id: &str) -> Result<BookingResponse, ApiError>;
+
+    // Poll a booking's status until it's confirmed, failed or cancelled (see `polling`)
+    async fn poll_until_confirmed(
+        &self,
+        booking_id: &str,
+        policy: &PollPolicy,
+    ) -> Result<BookingResponse, ApiError> {
+        polling::poll_until_confirmed(self, booking_id, policy).await
+    }
+
     // Get client statistics
     fn stats(&self) -> ClientStats;
 
@@ -255,62 +578,273 @@ pub trait ApiClient: Send + Sync + 'static {
     async fn reset_circuit_breakers(&self) -> usize;
 }
 
-// Booking API client to implement
+// Booking API client
+// Every request, search or booking, is queued by priority and sent when the scheduler hands
+// it a token from the rate limiter, so the upstream never sees more than the configured rate
+// and burst and bookings go first. Bookings are scheduled as High priority at least.
+// Requests go out through a `Transport`, HTTP to `base_url` unless the client is built with
+// another, with at most `max_concurrent_requests` in flight (see `bulkhead`).
+// Each attempt may take `timeout_ms`, and a request's `request_deadline` bounds all of it:
+// queueing, attempts and backoffs. Either way it fails with `Timeout` and the milliseconds it
+// took. `cancel_request` cancels requests by correlation ID wherever they are (see
+// `cancellation`). Response times are kept per operation (see `latency`). Bookings with the
+// same idempotency key are booked once (see `idempotency`).
+// Cancellations and modifications of bookings are Critical: a guest is waiting on them, and a
+// cancellation left in the queue may cost a penalty. They share the bookings' breaker, are
+// retried only with an idempotency key and are deduplicated by it like bookings.
+// Status reads of bookings are High priority, like bookings, and always retried.
+// With `coalesce_searches`, a search identical to one in flight (same hotels, stay, party,
+// markets and supplier, whatever the order) waits for it and shares its response instead of
+// calling the upstream (see `single_flight`). It still has its own deadline and can be
+// cancelled on its own; it's sent with the priority of the search it joined.
+// A search of many hotels can be streamed instead, each hotel's result as soon as its chunk of
+// hotels is back (see `stream`).
+// The upstream's health, which scales the request rate, can be left to a health checker (see
+// `health` and `spawn_health_checker`).
+// Every request runs in a `tracing` span, "request", with its correlation ID, priority and
+// hotel count and, once done, its outcome; each attempt is a child span, with the queue wait
+// and the transport call below it. Throttling, preemption, retries and breaker trips are
+// events. Where spans go is up to the application's subscriber; with the `otlp` feature they
+// can be exported to an OpenTelemetry collector to follow a booking end to end (see `otlp`).
+// Searches and bookings each have a circuit breaker, checked before queueing: while one is
+// open its requests fail fast without taking a token. Transient failures are retried with
+// backoff, see `retry`.
 pub struct BookingApiClient {
-    // TODO: Add appropriate fields here
-    // You'll likely need:
-    // - Rate limiters (token bucket or leaky bucket)
-    // - Priority queues for different request types
-    // - Circuit breakers for downstream dependencies
-    // - Request tracking for telemetry
-    // - Connection pools
-    // - Retry mechanisms with backoff and jitter
+    config: RwLock<Arc<ClientConfig>>,
+    limiter: Arc<RateLimiter>,
+    scheduler: Scheduler,
+    bulkhead: Bulkhead,
+    search_breaker: CircuitBreaker,
+    booking_breaker: CircuitBreaker,
+    transport: Arc<dyn Transport>,
+    // Requests queued or in flight, by correlation ID
+    requests: CancellationRegistry,
+    idempotency: IdempotencyLayer,
+    cancellations: IdempotencyLayer<CancelBookingResponse>,
+    modifications: IdempotencyLayer<ModifyBookingResponse>,
+    // Searches in flight, when coalescing
+    searches: SingleFlight<SearchKey, Result<SearchResponse, ApiError>>,
+    health: HealthMonitor,
+    latency: LatencyRecorder,
+    stats: Mutex<ClientStats>,
+}
+
+// What makes two searches identical
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct SearchKey {
+    hotel_ids: Vec<String>,
+    check_in: String,
+    check_out: String,
+    guests: u32,
+    markets: Vec<String>,
+    // Suppliers price the same stay differently
+    supplier: Option<String>,
+}
+
+impl SearchKey {
+    fn new(request: &SearchRequest) -> Self {
+        let sorted = |values: &[String]| {
+            let mut values = values.to_vec();
+            values.sort();
+            values
+        };
+        Self {
+            hotel_ids: sorted(&request.hotel_ids),
+            check_in: request.check_in.clone(),
+            check_out: request.check_out.clone(),
+            guests: request.guests,
+            markets: sorted(&request.markets),
+            supplier: request.supplier.clone(),
+        }
+    }
 }
 
 #[async_trait]
 impl ApiClient for BookingApiClient {
-    async fn search(&self, _request: SearchRequest) -> Result<SearchResponse, ApiError> {
-        // TODO: Implement with:
-        // - Rate limiting using token bucket algorithm
-        // - Priority-based queueing
-        // - Circuit breaker pattern
-        // - Retry with exponential backoff and jitter
-        // - Detailed telemetry collection
-        // - Adaptive throttling based on system health
-        Err(ApiError::Other("Not implemented".to_string()))
+    async fn search(&self, request: SearchRequest) -> Result<SearchResponse, ApiError> {
+        let request = &request;
+        self.execute(
+            Operation::Search,
+            request.priority,
+            request.hotel_ids.len(),
+            &request.context,
+            |deadline, cancellation| async move {
+                let attempts = self.attempts(
+                    Operation::Search,
+                    request.priority,
+                    deadline,
+                    cancellation,
+                    true,
+                    || self.send_search(request.clone()),
+                );
+                if !self.config().coalesce_searches {
+                    return attempts.await;
+                }
+                let (result, coalesced) = self
+                    .searches
+                    .run(SearchKey::new(request), || attempts)
+                    .await;
+                if coalesced {
+                    tracing::debug!("Coalesced with an identical search");
+                    self.stats.lock().requests_coalesced += 1;
+                }
+                result
+            },
+        )
+        .await
+    }
+
+    async fn book(&self, request: BookingRequest) -> Result<BookingResponse, ApiError> {
+        self.idempotency
+            .run(&request.idempotency_key, || {
+                self.execute(
+                    Operation::Booking,
+                    request.priority.max(RequestPriority::High),
+                    1,
+                    &request.context,
+                    |deadline, cancellation| {
+                        self.attempts(
+                            Operation::Booking,
+                            request.priority.max(RequestPriority::High),
+                            deadline,
+                            cancellation,
+                            !request.idempotency_key.is_empty(),
+                            || self.send_booking(request.clone()),
+                        )
+                    },
+                )
+            })
+            .await
+    }
+
+    async fn cancel_booking(
+        &self,
+        request: CancelBookingRequest,
+    ) -> Result<CancelBookingResponse, ApiError> {
+        if request.booking_id.is_empty() {
+            return Err(ApiError::ClientError("booking_id is empty".to_string()));
+        }
+        self.cancellations
+            .run(&request.idempotency_key, || {
+                self.execute(
+                    Operation::Cancellation,
+                    RequestPriority::Critical,
+                    1,
+                    &request.context,
+                    |deadline, cancellation| {
+                        self.attempts(
+                            Operation::Cancellation,
+                            RequestPriority::Critical,
+                            deadline,
+                            cancellation,
+                            !request.idempotency_key.is_empty(),
+                            || self.send_cancellation(request.clone()),
+                        )
+                    },
+                )
+            })
+            .await
+    }
+
+    async fn modify_booking(
+        &self,
+        request: ModifyBookingRequest,
+    ) -> Result<ModifyBookingResponse, ApiError> {
+        if request.booking_id.is_empty() {
+            return Err(ApiError::ClientError("booking_id is empty".to_string()));
+        }
+        if !request.has_changes() {
+            return Err(ApiError::ClientError(format!(
+                "nothing to change in booking {}",
+                request.booking_id
+            )));
+        }
+        self.modifications
+            .run(&request.idempotency_key, || {
+                self.execute(
+                    Operation::Modification,
+                    RequestPriority::Critical,
+                    1,
+                    &request.context,
+                    |deadline, cancellation| {
+                        self.attempts(
+                            Operation::Modification,
+                            RequestPriority::Critical,
+                            deadline,
+                            cancellation,
+                            !request.idempotency_key.is_empty(),
+                            || self.send_modification(request.clone()),
+                        )
+                    },
+                )
+            })
+            .await
     }
 
-    async fn book(&self, _request: BookingRequest) -> Result<BookingResponse, ApiError> {
-        // TODO: Implement with higher priority than search requests
-        // Bookings should be able to preempt search requests when needed
-        Err(ApiError::Other("Not implemented".to_string()))
+    async fn get_booking_status(&self, booking_id: &str) -> Result<BookingResponse, ApiError> {
+        if booking_id.is_empty() {
+            return Err(ApiError::ClientError("booking_id is empty".to_string()));
+        }
+        self.execute(
+            Operation::Status,
+            RequestPriority::High,
+            1,
+            &RequestContext::default(),
+            |deadline, cancellation| {
+                self.attempts(
+                    Operation::Status,
+                    RequestPriority::High,
+                    deadline,
+                    cancellation,
+                    true,
+                    || self.send_status(booking_id),
+                )
+            },
+        )
+        .await
     }
 
     fn stats(&self) -> ClientStats {
-        // TODO: Implement comprehensive statistics
-        ClientStats::default()
+        self.collect_stats(|operation| self.latency.stats(operation))
     }
 
+    // Healthy: the configured rate, Degraded: 60% of it, Unhealthy: 20%
     async fn set_system_health(&self, health: SystemHealth) -> f64 {
-        // TODO: Implement adaptive rate limiting based on system health
-        // - Healthy: 100% of configured rate
-        // - Degraded: 60% of configured rate
-        // - Unhealthy: 20% of configured rate
-        match health {
-            SystemHealth::Healthy => 1.0,
-            SystemHealth::Degraded => 0.6,
-            SystemHealth::Unhealthy => 0.2,
-        }
+        let multiplier = health.rate_multiplier();
+        self.limiter.set_multiplier(multiplier);
+        self.health.set(health);
+        multiplier
     }
 
-    async fn cancel_request(&self, _correlation_id: &str) -> bool {
-        // TODO: Implement request cancellation
-        false
+    // Returns whether any request queued or in flight had the ID
+    async fn cancel_request(&self, correlation_id: &str) -> bool {
+        if self.requests.cancel(correlation_id) == 0 {
+            return false;
+        }
+        self.scheduler.drop_cancelled();
+        true
     }
 
-    async fn update_config(&self, _config: ClientConfig) -> Result<(), ClientError> {
-        // TODO: Implement dynamic configuration updates
-        Err(ClientError::ConfigError("Not implemented".to_string()))
+    // Validated first, nothing is applied if it's invalid. Requests already sent carry on;
+    // the rest follow the new configuration from their next attempt.
+    async fn update_config(&self, config: ClientConfig) -> Result<(), ClientError> {
+        validate_config(&config)?;
+        let mut current = self.config.write();
+        self.transport.reconfigure(&config)?;
+        self.limiter
+            .reconfigure(config.max_requests_per_second, config.max_burst_size);
+        self.scheduler
+            .set_queue_size(config.queue_size_per_priority);
+        self.scheduler
+            .set_overflow_policies(config.overflow_policies);
+        self.scheduler.set_aging(config.queue_aging);
+        self.bulkhead
+            .resize(config.max_concurrent_requests as usize);
+        for breaker in self.breakers() {
+            breaker.set_config(config.circuit_breaker_config.clone());
+        }
+        *current = Arc::new(config);
+        Ok(())
     }
 
     async fn pause(&self, _drain: bool) -> Result<(), ClientError> {
@@ -323,22 +857,365 @@ impl ApiClient for BookingApiClient {
         Err(ClientError::ConfigError("Not implemented".to_string()))
     }
 
+    // Returns how many weren't closed
     async fn reset_circuit_breakers(&self) -> usize {
-        // TODO: Implement circuit breaker reset
-        0
+        self.breakers().filter(|breaker| breaker.reset()).count()
     }
 }
 
 impl BookingApiClient {
     // Create a new client with the given configuration
-    pub async fn new(_config: ClientConfig) -> Result<Self, ClientError> {
-        // TODO: Implement proper initialization of all components:
-        // - Token bucket rate limiters
-        // - Priority queues
-        // - Circuit breakers
-        // - Connection pools
-        // - Metrics collection
-        Ok(Self {})
+    pub async fn new(config: ClientConfig) -> Result<Self, ClientError> {
+        validate_config(&config)?;
+        let transport = Arc::new(HttpTransport::new(&config)?);
+        Self::with_transport(config, transport).await
+    }
+
+    // A client sending its requests through `transport`
+    pub async fn with_transport(
+        config: ClientConfig,
+        transport: Arc<dyn Transport>,
+    ) -> Result<Self, ClientError> {
+        validate_config(&config)?;
+        let limiter = Arc::new(RateLimiter::new(
+            config.max_requests_per_second,
+            config.max_burst_size,
+        ));
+        let scheduler = Scheduler::new(limiter.clone(), config.queue_size_per_priority);
+        scheduler.set_overflow_policies(config.overflow_policies);
+        scheduler.set_aging(config.queue_aging);
+        Ok(Self {
+            scheduler,
+            limiter,
+            bulkhead: Bulkhead::new(config.max_concurrent_requests as usize),
+            search_breaker: CircuitBreaker::new("search", config.circuit_breaker_config.clone()),
+            booking_breaker: CircuitBreaker::new("booking", config.circuit_breaker_config.clone()),
+            config: RwLock::new(Arc::new(config)),
+            transport,
+            requests: CancellationRegistry::new(),
+            idempotency: IdempotencyLayer::default(),
+            cancellations: IdempotencyLayer::default(),
+            modifications: IdempotencyLayer::default(),
+            searches: SingleFlight::new(),
+            health: HealthMonitor::new(),
+            latency: LatencyRecorder::new(),
+            stats: Mutex::new(ClientStats::default()),
+        })
+    }
+
+    // The configuration in effect
+    pub fn config(&self) -> Arc<ClientConfig> {
+        self.config.read().clone()
+    }
+
+    pub fn health(&self) -> SystemHealth {
+        self.health.health()
+    }
+
+    // Every change of health from now on, whether set by hand or by the health checker
+    pub fn subscribe_health(&self) -> broadcast::Receiver<HealthTransition> {
+        self.health.subscribe()
+    }
+
+    // Check the upstream's health every `health_check_interval_ms` in the background, until the
+    // client is dropped or the task aborted
+    pub fn spawn_health_checker(self: &Arc<Self>) -> JoinHandle<()> {
+        let client = Arc::downgrade(self);
+        tokio::spawn(async move {
+            while let Some(interval) = health_check_interval(&client) {
+                tokio::time::sleep(interval).await;
+                let Some(client) = client.upgrade() else {
+                    return;
+                };
+                client.check_health().await;
+            }
+        })
+    }
+
+    // Probe the upstream once with `health_check_probe` and set the health it shows; without
+    // a probe the health is left as it is
+    pub async fn check_health(&self) -> SystemHealth {
+        let config = self.config();
+        let Some(probe) = &config.health_check_probe else {
+            return self.health.health();
+        };
+        let request = match probe {
+            HealthProbe::Path(path) => Request::HealthCheck(path.clone()),
+            HealthProbe::Search(search) => Request::Search(search.as_ref().clone()),
+        };
+        let start = Instant::now();
+        let timeout = Duration::from_millis(config.timeout_ms);
+        let outcome = match tokio::time::timeout(timeout, self.transport.send(request)).await {
+            Ok(Ok(_)) => Ok(start.elapsed()),
+            Ok(Err(error)) => Err(error),
+            Err(_) => Err(ApiError::Timeout(elapsed_ms(start))),
+        };
+        let health = self.health.classify(&outcome, timeout);
+        self.set_system_health(health).await;
+        health
+    }
+
+    // As `stats`, but response times only over the last `window` (up to 15 minutes, in 10
+    // second steps); counters are still totals
+    pub fn stats_window(&self, window: Duration) -> ClientStats {
+        self.collect_stats(|operation| self.latency.stats_window(operation, window))
+    }
+
+    fn collect_stats(&self, latency: impl Fn(Option<Operation>) -> LatencyStats) -> ClientStats {
+        let mut stats = self.stats.lock().clone();
+        let all = latency(None);
+        stats.average_response_time_ms = all.average_ms;
+        stats.p95_response_time_ms = all.p95_ms;
+        stats.p99_response_time_ms = all.p99_ms;
+        stats.max_response_time_ms = all.max_ms;
+        stats.search_latency = latency(Some(Operation::Search));
+        stats.booking_latency = latency(Some(Operation::Booking));
+        stats.cancellation_latency = latency(Some(Operation::Cancellation));
+        stats.modification_latency = latency(Some(Operation::Modification));
+        stats.status_latency = latency(Some(Operation::Status));
+        stats.requests_throttled = self.limiter.throttled_count();
+        stats.requests_preempted = self.scheduler.preempted_count();
+        stats.requests_rejected = self.scheduler.rejected_count();
+        stats.requests_promoted = self.scheduler.promoted_count();
+        stats.requests_queue_wait_exceeded = self.scheduler.overdue_count();
+        stats.queue_wait = self.scheduler.queue_wait();
+        let queue_depths = self.scheduler.queue_depths();
+        stats.queue_depth = queue_depths.iter().sum();
+        stats.queue_depth_by_priority = RequestPriority::ALL
+            .iter()
+            .map(|priority| (*priority, queue_depths[*priority as usize]))
+            .collect();
+        stats.requests_expired = self.scheduler.expired_count();
+        stats.requests_cancelled = self.requests.cancelled_count();
+        stats.requests_deduplicated = self.idempotency.deduplicated_count()
+            + self.cancellations.deduplicated_count()
+            + self.modifications.deduplicated_count();
+        stats.active_requests = self.bulkhead.active();
+        stats.in_flight_by_priority = RequestPriority::ALL
+            .iter()
+            .map(|priority| (*priority, self.bulkhead.in_flight(*priority)))
+            .collect();
+        stats.circuit_breakers = self
+            .breakers()
+            .map(|breaker| (breaker.service_name().to_string(), breaker.state()))
+            .collect();
+        stats.circuit_breaker_open = stats
+            .circuit_breakers
+            .iter()
+            .any(|(_, state)| *state != CircuitState::Closed);
+        stats.requests_circuit_broken = self.breakers().map(CircuitBreaker::rejected_count).sum();
+        stats.current_rate_limit = self.limiter.current_rate().round() as u32;
+        stats.adaptive_rate_limit_multiplier = self.limiter.multiplier();
+        stats.system_health = self.health.health();
+        stats
+    }
+
+    fn breaker(&self, operation: Operation) -> &CircuitBreaker {
+        match operation {
+            Operation::Search => &self.search_breaker,
+            // Same upstream as bookings
+            Operation::Booking
+            | Operation::Cancellation
+            | Operation::Modification
+            | Operation::Status => &self.booking_breaker,
+        }
+    }
+
+    fn breakers(&self) -> impl Iterator<Item = &CircuitBreaker> {
+        [&self.search_breaker, &self.booking_breaker].into_iter()
+    }
+
+    // Run a request's `attempts`, given its deadline and cancellation token, until they end,
+    // the deadline passes or the request is cancelled; its response time and outcome are
+    // recorded either way
+    async fn execute<T, F, Fut>(
+        &self,
+        operation: Operation,
+        priority: RequestPriority,
+        hotels: usize,
+        context: &RequestContext,
+        attempts: F,
+    ) -> Result<T, ApiError>
+    where
+        F: FnOnce(Option<Instant>, CancellationToken) -> Fut,
+        Fut: Future<Output = Result<T, ApiError>>,
+    {
+        let span = tracing::info_span!(
+            "request",
+            operation = operation.name(),
+            correlation_id = %context.correlation_id,
+            priority = ?priority,
+            hotels,
+            outcome = tracing::field::Empty,
+        );
+        let start = Instant::now();
+        let deadline = deadline(context);
+        let registration = self.requests.register(&context.correlation_id);
+        let cancellation = registration.token();
+        let attempts = attempts(deadline, cancellation.clone());
+        let bounded = async {
+            match deadline {
+                Some(deadline) if deadline <= start => Err(ApiError::Timeout(0)),
+                Some(deadline) => tokio::time::timeout_at(deadline, attempts)
+                    .await
+                    .unwrap_or_else(|_| Err(ApiError::Timeout(elapsed_ms(start)))),
+                None => attempts.await,
+            }
+        };
+        let result = async {
+            tokio::select! {
+                biased;
+                _ = cancellation.cancelled() => Err(ApiError::RequestCancelled),
+                result = bounded => result,
+            }
+        }
+        .instrument(span.clone())
+        .await;
+        match &result {
+            Ok(_) => span.record("outcome", "ok"),
+            Err(error) => span.record("outcome", tracing::field::display(error)),
+        };
+        self.latency.record(operation, start.elapsed());
+        let mut stats = self.stats.lock();
+        match &result {
+            Ok(_) => stats.requests_succeeded += 1,
+            Err(error) => {
+                stats.requests_failed += 1;
+                if let ApiError::Timeout(_) = error {
+                    stats.requests_timeout += 1;
+                }
+            }
+        }
+        drop(stats);
+        result
+    }
+
+    // Attempts until one succeeds or the failure isn't worth retrying (see `retry`)
+    async fn attempts<T, F, Fut>(
+        &self,
+        operation: Operation,
+        priority: RequestPriority,
+        deadline: Option<Instant>,
+        cancellation: CancellationToken,
+        retry: bool,
+        send: F,
+    ) -> Result<T, ApiError>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<T, ApiError>>,
+    {
+        let config = self.config();
+        let mut retrier = Retrier::new(&config.retry_config, deadline);
+        if !retry {
+            retrier = retrier.disabled();
+        }
+        let breaker = self.breaker(operation);
+        loop {
+            let result = self
+                .attempt(breaker, priority, deadline, &cancellation, &send)
+                .instrument(tracing::info_span!(
+                    "attempt",
+                    number = retrier.retries() + 1
+                ))
+                .await;
+            let Err(error) = &result else {
+                return result;
+            };
+            match retrier.next_backoff(error) {
+                Some(backoff) => {
+                    tracing::debug!(
+                        backoff_ms = backoff.as_millis() as u64,
+                        "Retrying after {}",
+                        error
+                    );
+                    self.stats.lock().requests_retried += 1;
+                    tokio::time::sleep(backoff).await;
+                }
+                None => return result,
+            }
+        }
+    }
+
+    // One attempt: through the circuit breaker, the scheduler and the bulkhead, then sent within
+    // `timeout_ms`
+    async fn attempt<T, F, Fut>(
+        &self,
+        breaker: &CircuitBreaker,
+        priority: RequestPriority,
+        deadline: Option<Instant>,
+        cancellation: &CancellationToken,
+        send: &F,
+    ) -> Result<T, ApiError>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<T, ApiError>>,
+    {
+        let permit = breaker.try_acquire()?;
+        self.scheduler
+            .schedule(priority, deadline, Some(cancellation.clone()))
+            .instrument(tracing::info_span!("queue_wait"))
+            .await?;
+        let _slot = self.bulkhead.acquire(priority).await;
+        self.stats.lock().requests_sent += 1;
+        let start = Instant::now();
+        let timeout = Duration::from_millis(self.config().timeout_ms);
+        let result = tokio::time::timeout(timeout, send())
+            .instrument(tracing::info_span!("transport"))
+            .await
+            .unwrap_or_else(|_| Err(ApiError::Timeout(elapsed_ms(start))));
+        if let Err(ApiError::RateLimitExceeded {
+            retry_after_ms: Some(retry_after_ms),
+            ..
+        }) = &result
+        {
+            let pause_ms = (*retry_after_ms).min(self.config().retry_config.max_retry_after_ms);
+            tracing::info!(retry_after_ms, pause_ms, "Upstream rate limited, pausing");
+            self.limiter.pause_for(Duration::from_millis(pause_ms));
+        }
+        permit.record(&result);
+        result
+    }
+
+    async fn send_search(&self, request: SearchRequest) -> Result<SearchResponse, ApiError> {
+        match self.transport.send(Request::Search(request)).await? {
+            Response::Search(response) => Ok(response),
+            response => Err(unexpected_response(&response)),
+        }
+    }
+
+    async fn send_booking(&self, request: BookingRequest) -> Result<BookingResponse, ApiError> {
+        match self.transport.send(Request::Booking(request)).await? {
+            Response::Booking(response) => Ok(response),
+            response => Err(unexpected_response(&response)),
+        }
+    }
+
+    async fn send_cancellation(
+        &self,
+        request: CancelBookingRequest,
+    ) -> Result<CancelBookingResponse, ApiError> {
+        match self.transport.send(Request::CancelBooking(request)).await? {
+            Response::CancelBooking(response) => Ok(response),
+            response => Err(unexpected_response(&response)),
+        }
+    }
+
+    async fn send_modification(
+        &self,
+        request: ModifyBookingRequest,
+    ) -> Result<ModifyBookingResponse, ApiError> {
+        match self.transport.send(Request::ModifyBooking(request)).await? {
+            Response::ModifyBooking(response) => Ok(response),
+            response => Err(unexpected_response(&response)),
+        }
+    }
+
+    async fn send_status(&self, booking_id: &str) -> Result<BookingResponse, ApiError> {
+        let request = Request::BookingStatus(booking_id.to_string());
+        match self.transport.send(request).await? {
+            Response::Booking(response) => Ok(response),
+            response => Err(unexpected_response(&response)),
+        }
     }
 
     // Helper to calculate exponential backoff with jitter
@@ -355,14 +1232,93 @@ impl BookingApiClient {
     }
 }
 
+// Until the next health check, None once the client is gone
+fn health_check_interval(client: &Weak<BookingApiClient>) -> Option<Duration> {
+    let config = client.upgrade()?.config();
+    Some(Duration::from_millis(config.health_check_interval_ms))
+}
+
+// The request's deadline on the tokio clock
+fn deadline(context: &RequestContext) -> Option<Instant> {
+    context
+        .remaining()
+        .map(|remaining| Instant::now() + remaining)
+}
+
+fn elapsed_ms(start: Instant) -> u64 {
+    start.elapsed().as_millis() as u64
+}
+
+// A transport answered with the wrong kind of response
+fn unexpected_response(response: &Response) -> ApiError {
+    ApiError::Other(format!("unexpected response: {:?}", response))
+}
+
+fn validate_config(config: &ClientConfig) -> Result<(), ClientError> {
+    if config.max_requests_per_second == 0 {
+        return Err(ClientError::ConfigError(
+            "max_requests_per_second must be greater than 0".to_string(),
+        ));
+    }
+    if config.max_burst_size == 0 {
+        return Err(ClientError::ConfigError(
+            "max_burst_size must be greater than 0".to_string(),
+        ));
+    }
+    if config.max_concurrent_requests == 0 {
+        return Err(ClientError::ConfigError(
+            "max_concurrent_requests must be greater than 0".to_string(),
+        ));
+    }
+    if config.timeout_ms == 0 {
+        return Err(ClientError::ConfigError(
+            "timeout_ms must be greater than 0".to_string(),
+        ));
+    }
+    if config.health_check_probe.is_some() && config.health_check_interval_ms == 0 {
+        return Err(ClientError::ConfigError(
+            "health_check_interval_ms must be greater than 0".to_string(),
+        ));
+    }
+    if config.queue_size_per_priority == 0 {
+        return Err(ClientError::ConfigError(
+            "queue_size_per_priority must be greater than 0".to_string(),
+        ));
+    }
+    if config.queue_aging.promote_after_ms == Some(0)
+        || config.queue_aging.max_queue_wait_ms == Some(0)
+    {
+        return Err(ClientError::ConfigError(
+            "queue aging times must be greater than 0".to_string(),
+        ));
+    }
+    let breaker = &config.circuit_breaker_config;
+    if breaker.failure_threshold == 0
+        || breaker.success_threshold == 0
+        || breaker.half_open_max_requests == 0
+    {
+        return Err(ClientError::ConfigError(
+            "circuit breaker thresholds must be greater than 0".to_string(),
+        ));
+    }
+    if breaker
+        .failure_rate_threshold
+        .is_some_and(|rate| !(rate > 0.0 && rate <= 1.0))
+    {
+        return Err(ClientError::ConfigError(
+            "circuit breaker failure_rate_threshold must be within (0, 1]".to_string(),
+        ));
+    }
+    Ok(())
+}
+
 // Enhanced mock server for testing (you can modify or extend this)
 #[cfg(test)]
 pub mod mock_server {
     use super::*;
     use std::sync::atomic::{AtomicUsize, Ordering};
     // use std::sync::Arc;
-    use std::collections::HashMap;
-    use std::time::Instant;
+    use std::collections::{HashMap, HashSet, VecDeque};
     use tokio::sync::Mutex;
 
     #[derive(Debug, Clone, Copy)]
@@ -379,6 +1335,12 @@ pub mod mock_server {
         request_count: AtomicUsize,
         search_responses: Mutex<HashMap<String, SearchResponse>>,
         booking_responses: Mutex<HashMap<String, BookingResponse>>,
+        cancellation_responses: Mutex<HashMap<String, CancelBookingResponse>>,
+        modification_responses: Mutex<HashMap<String, ModifyBookingResponse>>,
+        // Booking IDs cancelled
+        cancelled: Mutex<HashSet<String>>,
+        // Statuses each booking reports, one per status request, the last from then on
+        booking_statuses: Mutex<HashMap<String, VecDeque<BookingStatus>>>,
         fail_next_requests: AtomicUsize,
         delay_ms: AtomicUsize,
         rate_limit: AtomicUsize,
@@ -387,6 +1349,12 @@ pub mod mock_server {
         dropped_request_count: AtomicUsize,
     }
 
+    impl Default for MockServer {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
     impl MockServer {
         pub fn new() -> Self {
             Self {
@@ -394,6 +1362,10 @@ pub mod mock_server {
                 request_count: AtomicUsize::new(0),
                 search_responses: Mutex::new(HashMap::new()),
                 booking_responses: Mutex::new(HashMap::new()),
+                cancellation_responses: Mutex::new(HashMap::new()),
+                modification_responses: Mutex::new(HashMap::new()),
+                cancelled: Mutex::new(HashSet::new()),
+                booking_statuses: Mutex::new(HashMap::new()),
                 fail_next_requests: AtomicUsize::new(0),
                 delay_ms: AtomicUsize::new(0),
                 rate_limit: AtomicUsize::new(100), // Default: 100 requests per window
@@ -427,6 +1399,11 @@ pub mod mock_server {
             self.fail_next_requests.store(count, Ordering::SeqCst);
         }
 
+        // Requests received, searches and bookings
+        pub fn request_count(&self) -> usize {
+            self.request_count.load(Ordering::SeqCst)
+        }
+
         pub async fn add_search_response(&self, hotel_id: &str, response: SearchResponse) {
             let mut responses = self.search_responses.lock().await;
             responses.insert(hotel_id.to_string(), response);
@@ -437,6 +1414,30 @@ pub mod mock_server {
             responses.insert(hotel_id.to_string(), response);
         }
 
+        // A booking reporting each of `statuses` in turn, then the last one
+        pub async fn set_booking_statuses(&self, booking_id: &str, statuses: Vec<BookingStatus>) {
+            let mut booking_statuses = self.booking_statuses.lock().await;
+            booking_statuses.insert(booking_id.to_string(), statuses.into());
+        }
+
+        pub async fn add_cancellation_response(
+            &self,
+            booking_id: &str,
+            response: CancelBookingResponse,
+        ) {
+            let mut responses = self.cancellation_responses.lock().await;
+            responses.insert(booking_id.to_string(), response);
+        }
+
+        pub async fn add_modification_response(
+            &self,
+            booking_id: &str,
+            response: ModifyBookingResponse,
+        ) {
+            let mut responses = self.modification_responses.lock().await;
+            responses.insert(booking_id.to_string(), response);
+        }
+
         // Enhanced implementation - check rate limits, simulate failures based on mode
         pub async fn handle_search(
             &self,
@@ -451,15 +1452,13 @@ pub mod mock_server {
                     // Complete outage
                     return Err(ApiError::NetworkError("Service unavailable".to_string()));
                 }
-                3 => {
-                    // Partial outage - 50% chance of failure
-                    if rand::random::<f32>() < 0.5 {
-                        return Err(ApiError::ApiResponseError {
-                            status_code: 503,
-                            message: "Service temporarily unavailable".to_string(),
-                            is_retryable: true,
-                        });
-                    }
+                // Partial outage - 50% chance of failure
+                3 if rand::random::<f32>() < 0.5 => {
+                    return Err(ApiError::ApiResponseError {
+                        status_code: 503,
+                        message: "Service temporarily unavailable".to_string(),
+                        is_retryable: true,
+                    });
                 }
                 _ => {}
             }
@@ -478,10 +1477,17 @@ pub mod mock_server {
             // Check if we've hit the rate limit
             if recent.len() >= limit {
                 self.dropped_request_count.fetch_add(1, Ordering::SeqCst);
-                return Err(ApiError::RateLimitExceeded(format!(
-                    "Rate limit of {} requests per {}ms exceeded",
-                    limit, window_ms
-                )));
+                // Until the oldest leaves the window
+                let retry_after = recent.first().map(|(timestamp, _)| {
+                    window_duration.saturating_sub(now.duration_since(*timestamp))
+                });
+                return Err(ApiError::RateLimitExceeded {
+                    message: format!(
+                        "Rate limit of {} requests per {}ms exceeded",
+                        limit, window_ms
+                    ),
+                    retry_after_ms: retry_after.map(|wait| wait.as_millis() as u64),
+                });
             }
 
             // Track this request
@@ -531,10 +1537,138 @@ pub mod mock_server {
         }
 
         // Similar to handle_search but for booking
+        // Answers after the mode's delay; fails in a partial or complete outage
+        pub async fn handle_health_check(&self) -> Result<(), ApiError> {
+            self.request_count.fetch_add(1, Ordering::SeqCst);
+            let mode = self.mode.load(Ordering::SeqCst);
+            match mode {
+                3 => {
+                    return Err(ApiError::ApiResponseError {
+                        status_code: 503,
+                        message: "Service Unavailable".to_string(),
+                        is_retryable: true,
+                    })
+                }
+                4 => return Err(ApiError::NetworkError("Service unavailable".to_string())),
+                _ => {}
+            }
+            let delay = self.delay_ms.load(Ordering::SeqCst) * (mode as usize + 1);
+            tokio::time::sleep(Duration::from_millis(delay as u64)).await;
+            Ok(())
+        }
+
         pub async fn handle_booking(
             &self,
             request: BookingRequest,
         ) -> Result<BookingResponse, ApiError> {
+            let delay = self.booking_conditions().await?;
+
+            // Return mock response
+            let responses = self.booking_responses.lock().await;
+            let response = match responses.get(&request.hotel_id) {
+                Some(response) => response.clone(),
+                // Default response
+                None => BookingResponse {
+                    booking_id: format!("booking-{}", rand::random::<u32>()),
+                    status: BookingStatus::Confirmed,
+                    confirmation_code: Some(format!("CONF{}", rand::random::<u16>())),
+                    rate_limit_remaining: None, // Bookings don't count against rate limit
+                    processing_time_ms: delay as u64,
+                },
+            };
+            self.booking_statuses
+                .lock()
+                .await
+                .entry(response.booking_id.clone())
+                .or_insert_with(|| VecDeque::from([response.status.clone()]));
+            Ok(response)
+        }
+
+        // As bookings; a booking is found once booked or given statuses
+        pub async fn handle_booking_status(
+            &self,
+            booking_id: &str,
+        ) -> Result<BookingResponse, ApiError> {
+            let delay = self.booking_conditions().await?;
+            let status = match self.cancelled.lock().await.contains(booking_id) {
+                true => BookingStatus::Cancelled,
+                false => {
+                    let mut booking_statuses = self.booking_statuses.lock().await;
+                    let statuses = booking_statuses
+                        .get_mut(booking_id)
+                        .ok_or_else(|| booking_not_found(booking_id))?;
+                    match statuses.len() > 1 {
+                        true => statuses.pop_front(),
+                        false => statuses.front().cloned(),
+                    }
+                    .ok_or_else(|| booking_not_found(booking_id))?
+                }
+            };
+            Ok(BookingResponse {
+                booking_id: booking_id.to_string(),
+                confirmation_code: (status == BookingStatus::Confirmed)
+                    .then(|| format!("CONF{}", rand::random::<u16>())),
+                status,
+                rate_limit_remaining: None,
+                processing_time_ms: delay as u64,
+            })
+        }
+
+        // As bookings; a booking can be cancelled once, and isn't found once cancelled
+        pub async fn handle_cancellation(
+            &self,
+            request: CancelBookingRequest,
+        ) -> Result<CancelBookingResponse, ApiError> {
+            let delay = self.booking_conditions().await?;
+            if !self
+                .cancelled
+                .lock()
+                .await
+                .insert(request.booking_id.clone())
+            {
+                return Err(booking_not_found(&request.booking_id));
+            }
+            let responses = self.cancellation_responses.lock().await;
+            if let Some(response) = responses.get(&request.booking_id) {
+                return Ok(response.clone());
+            }
+            // Free cancellation
+            Ok(CancelBookingResponse {
+                booking_id: request.booking_id,
+                status: BookingStatus::Cancelled,
+                penalty_amount: 0.0,
+                refund_amount: 0.0,
+                currency: None,
+                rate_limit_remaining: None,
+                processing_time_ms: delay as u64,
+            })
+        }
+
+        pub async fn handle_modification(
+            &self,
+            request: ModifyBookingRequest,
+        ) -> Result<ModifyBookingResponse, ApiError> {
+            let delay = self.booking_conditions().await?;
+            if self.cancelled.lock().await.contains(&request.booking_id) {
+                return Err(booking_not_found(&request.booking_id));
+            }
+            let responses = self.modification_responses.lock().await;
+            if let Some(response) = responses.get(&request.booking_id) {
+                return Ok(response.clone());
+            }
+            Ok(ModifyBookingResponse {
+                booking_id: request.booking_id,
+                status: BookingStatus::Confirmed,
+                confirmation_code: Some(format!("CONF{}", rand::random::<u16>())),
+                price_difference: None,
+                currency: None,
+                rate_limit_remaining: None,
+                processing_time_ms: delay as u64,
+            })
+        }
+
+        // The outages, delays and failures of the booking endpoints; returns the delay
+        async fn booking_conditions(&self) -> Result<usize, ApiError> {
             self.request_count.fetch_add(1, Ordering::SeqCst);
 
             // Prioritize bookings - they bypass rate limits but still affected by outages
@@ -571,80 +1705,1252 @@ pub mod mock_server {
                     is_retryable: true,
                 });
             }
+            Ok(delay)
+        }
+    }
 
-            // Return mock response
-            let responses = self.booking_responses.lock().await;
-            if let Some(response) = responses.get(&request.hotel_id) {
-                return Ok(response.clone());
-            }
+    fn booking_not_found(booking_id: &str) -> ApiError {
+        ApiError::ApiResponseError {
+            status_code: 404,
+            message: format!("Booking {} not found", booking_id),
+            is_retryable: false,
+        }
+    }
 
-            // Default response
-            Ok(BookingResponse {
-                booking_id: format!("booking-{}", rand::random::<u32>()),
-                status: "confirmed".to_string(),
-                confirmation_code: Some(format!("CONF{}", rand::random::<u16>())),
-                rate_limit_remaining: None, // Bookings don't count against rate limit
-                processing_time_ms: delay as u64,
-            })
+    // Serves the client in-process
+    #[async_trait]
+    impl Transport for MockServer {
+        async fn send(&self, request: Request) -> Result<Response, ApiError> {
+            match request {
+                Request::Search(request) => self.handle_search(request).await.map(Response::Search),
+                Request::Booking(request) => {
+                    self.handle_booking(request).await.map(Response::Booking)
+                }
+                Request::CancelBooking(request) => self
+                    .handle_cancellation(request)
+                    .await
+                    .map(Response::CancelBooking),
+                Request::ModifyBooking(request) => self
+                    .handle_modification(request)
+                    .await
+                    .map(Response::ModifyBooking),
+                Request::BookingStatus(booking_id) => self
+                    .handle_booking_status(&booking_id)
+                    .await
+                    .map(Response::Booking),
+                Request::HealthCheck(_) => self
+                    .handle_health_check()
+                    .await
+                    .map(|()| Response::HealthCheck),
+            }
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    // use super::*;
-    // use mock_server::{MockServer, ServerMode};
-    // use std::sync::Arc;
-    // use std::time::Instant;
+    use super::*;
+    use mock_server::{MockServer, ServerMode};
+
+    fn test_config() -> ClientConfig {
+        ClientConfig {
+            base_url: "https://api.example.com".to_string(),
+            api_key: "test_key".to_string(),
+            max_requests_per_second: 10,
+            max_burst_size: 2,
+            max_concurrent_requests: 5,
+            timeout_ms: 5000,
+            retry_config: RetryConfig::default(),
+            circuit_breaker_config: CircuitBreakerConfig::default(),
+            queue_size_per_priority: 100,
+            overflow_policies: OverflowPolicies::default(),
+            queue_aging: QueueAging::default(),
+            health_check_interval_ms: 30000,
+            health_check_probe: None,
+            coalesce_searches: false,
+        }
+    }
+
+    // A client served by a mock server
+    async fn mock_client(config: ClientConfig) -> (Arc<MockServer>, BookingApiClient) {
+        let server = Arc::new(MockServer::new());
+        let client = BookingApiClient::with_transport(config, server.clone())
+            .await
+            .unwrap();
+        (server, client)
+    }
+
+    fn search_request() -> SearchRequest {
+        SearchRequest {
+            hotel_ids: vec!["hotel1".to_string()],
+            check_in: "2025-06-01".to_string(),
+            check_out: "2025-06-05".to_string(),
+            guests: 2,
+            markets: Vec::new(),
+            supplier: None,
+            priority: RequestPriority::Medium,
+            idempotency_key: None,
+            context: RequestContext::default(),
+        }
+    }
 
     #[tokio::test]
+    async fn test_invalid_config() {
+        for config in [
+            ClientConfig {
+                max_requests_per_second: 0,
+                ..test_config()
+            },
+            ClientConfig {
+                max_burst_size: 0,
+                ..test_config()
+            },
+            ClientConfig {
+                max_concurrent_requests: 0,
+                ..test_config()
+            },
+            ClientConfig {
+                timeout_ms: 0,
+                ..test_config()
+            },
+            ClientConfig {
+                queue_size_per_priority: 0,
+                ..test_config()
+            },
+            ClientConfig {
+                queue_aging: QueueAging {
+                    promote_after_ms: Some(0),
+                    max_queue_wait_ms: None,
+                },
+                ..test_config()
+            },
+            ClientConfig {
+                circuit_breaker_config: CircuitBreakerConfig {
+                    success_threshold: 0,
+                    ..Default::default()
+                },
+                ..test_config()
+            },
+            ClientConfig {
+                circuit_breaker_config: CircuitBreakerConfig {
+                    failure_rate_threshold: Some(1.5),
+                    ..Default::default()
+                },
+                ..test_config()
+            },
+        ] {
+            assert!(matches!(
+                BookingApiClient::new(config).await,
+                Err(ClientError::ConfigError(_))
+            ));
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_requests_are_rate_limited() {
+        let (_, client) = mock_client(test_config()).await;
+        let start = Instant::now();
+        for _ in 0..4 {
+            let _ = client.search(search_request()).await;
+        }
+        // The burst of 2, then one every 100ms
+        assert_eq!(start.elapsed(), Duration::from_millis(200));
+        let stats = client.stats();
+        assert_eq!(stats.requests_throttled, 2);
+        assert_eq!(stats.current_rate_limit, 10);
+    }
+
+    #[tokio::test(start_paused = true)]
     async fn test_adaptive_rate_limiting() {
-        // TODO: Implement this test
-        // - Create a mock server that simulates different health states
-        // - Configure client with appropriate settings
-        // - Test that client adapts rate limits based on server health
-        // - Verify statistics reflect the adaptations
+        let (_, client) = mock_client(test_config()).await;
+        assert_eq!(client.stats().adaptive_rate_limit_multiplier, 1.0);
+
+        assert_eq!(client.set_system_health(SystemHealth::Degraded).await, 0.6);
+        let stats = client.stats();
+        assert_eq!(stats.current_rate_limit, 6);
+        assert_eq!(stats.adaptive_rate_limit_multiplier, 0.6);
+
+        // Two requests a second once the burst is spent
+        assert_eq!(client.set_system_health(SystemHealth::Unhealthy).await, 0.2);
+        assert_eq!(client.stats().current_rate_limit, 2);
+        for _ in 0..2 {
+            let _ = client.search(search_request()).await;
+        }
+        let start = Instant::now();
+        let _ = client.search(search_request()).await;
+        assert_eq!(start.elapsed(), Duration::from_millis(500));
+
+        client.set_system_health(SystemHealth::Healthy).await;
+        assert_eq!(client.stats().current_rate_limit, 10);
     }
 
-    #[tokio::test]
+    #[tokio::test(start_paused = true)]
+    async fn test_circuit_breaker_stats_and_reset() {
+        let (_, client) = mock_client(test_config()).await;
+        for _ in 0..CircuitBreakerConfig::default().failure_threshold {
+            client
+                .search_breaker
+                .try_acquire()
+                .unwrap()
+                .record(&Err::<(), _>(ApiError::Timeout(5000)));
+        }
+
+        // Searches fail fast without taking a token, bookings still go
+        for _ in 0..5 {
+            assert!(matches!(
+                client.search(search_request()).await,
+                Err(ApiError::CircuitBreakerOpen {
+                    retry_after_ms: Some(30000),
+                    ..
+                })
+            ));
+        }
+        assert_eq!(client.limiter.available(), 2);
+        assert!(!matches!(
+            client.book(booking_request()).await,
+            Err(ApiError::CircuitBreakerOpen { .. })
+        ));
+        let stats = client.stats();
+        assert!(stats.circuit_breaker_open);
+        assert_eq!(stats.requests_circuit_broken, 5);
+        assert_eq!(
+            stats.circuit_breakers,
+            vec![
+                ("search".to_string(), CircuitState::Open),
+                ("booking".to_string(), CircuitState::Closed),
+            ]
+        );
+
+        assert_eq!(client.reset_circuit_breakers().await, 1);
+        assert!(!client.stats().circuit_breaker_open);
+        assert!(!matches!(
+            client.search(search_request()).await,
+            Err(ApiError::CircuitBreakerOpen { .. })
+        ));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_timeouts_and_deadlines() {
+        let (server, client) = mock_client(ClientConfig {
+            timeout_ms: 100,
+            retry_config: RetryConfig {
+                max_retries: 0,
+                ..Default::default()
+            },
+            ..test_config()
+        })
+        .await;
+
+        // Each attempt
+        server.set_delay(300);
+        assert!(matches!(
+            client.search(search_request()).await,
+            Err(ApiError::Timeout(100))
+        ));
+
+        // The deadline, through queueing once the burst is spent
+        server.set_delay(0);
+        for _ in 0..2 {
+            client.search(search_request()).await.unwrap();
+        }
+        let start = Instant::now();
+        let expiring = SearchRequest {
+            context: RequestContext::default().with_timeout(Duration::from_millis(50)),
+            ..search_request()
+        };
+        assert!(matches!(
+            client.search(expiring).await,
+            Err(ApiError::Timeout(elapsed)) if elapsed <= 50
+        ));
+        assert!(start.elapsed() <= Duration::from_millis(50));
+
+        // Dropped rather than sent
+        client.search(search_request()).await.unwrap();
+        assert_eq!(server.request_count(), 4);
+        let expired = SearchRequest {
+            context: RequestContext {
+                request_deadline: Some(SystemTime::now() - Duration::from_secs(1)),
+                ..Default::default()
+            },
+            ..search_request()
+        };
+        assert!(matches!(
+            client.search(expired).await,
+            Err(ApiError::Timeout(0))
+        ));
+        let stats = client.stats();
+        assert_eq!(stats.requests_timeout, 3);
+        assert_eq!(stats.requests_expired, 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_concurrency_limits() {
+        let (server, client) = mock_client(ClientConfig {
+            max_requests_per_second: 100,
+            max_burst_size: 100,
+            max_concurrent_requests: 4,
+            ..test_config()
+        })
+        .await;
+        let client = Arc::new(client);
+        server.set_delay(100);
+
+        // A flood of Low searches gets half of the slots
+        let searches: Vec<_> = (0..10)
+            .map(|_| {
+                let client = client.clone();
+                tokio::spawn(async move {
+                    client
+                        .search(SearchRequest {
+                            priority: RequestPriority::Low,
+                            ..search_request()
+                        })
+                        .await
+                })
+            })
+            .collect();
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        let stats = client.stats();
+        assert_eq!(stats.active_requests, 2);
+        assert_eq!(stats.in_flight_by_priority[0], (RequestPriority::Low, 2));
+
+        // Bookings don't wait for them
+        let start = Instant::now();
+        let booking = BookingRequest {
+            priority: RequestPriority::Critical,
+            ..booking_request()
+        };
+        client.book(booking).await.unwrap();
+        assert_eq!(start.elapsed(), Duration::from_millis(100));
+        for search in searches {
+            search.await.unwrap().unwrap();
+        }
+        assert_eq!(client.stats().active_requests, 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_cancel_request() {
+        let (server, client) = mock_client(ClientConfig {
+            max_burst_size: 1,
+            ..test_config()
+        })
+        .await;
+        let client = Arc::new(client);
+        server.set_delay(200);
+        let search = |correlation_id: &str| {
+            let client = client.clone();
+            let request = SearchRequest {
+                context: RequestContext {
+                    correlation_id: correlation_id.to_string(),
+                    ..Default::default()
+                },
+                ..search_request()
+            };
+            tokio::spawn(async move { client.search(request).await })
+        };
+        let in_flight = search("in-flight");
+        let queued = search("queued");
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(client.stats().queue_depth, 1);
+        assert_eq!(client.stats().active_requests, 1);
+
+        // Queued: gone from the queue at once
+        assert!(client.cancel_request("queued").await);
+        assert_eq!(client.stats().queue_depth, 0);
+        assert!(matches!(
+            queued.await.unwrap(),
+            Err(ApiError::RequestCancelled)
+        ));
+
+        // In flight: abandoned
+        assert!(client.cancel_request("in-flight").await);
+        assert!(matches!(
+            in_flight.await.unwrap(),
+            Err(ApiError::RequestCancelled)
+        ));
+        assert!(!client.cancel_request("in-flight").await);
+        assert!(!client.cancel_request("unknown").await);
+
+        let stats = client.stats();
+        assert_eq!(stats.requests_cancelled, 2);
+        assert_eq!(stats.active_requests, 0);
+        assert_eq!(server.request_count(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_update_config() {
+        let (_, client) = mock_client(test_config()).await;
+        let client = Arc::new(client);
+        let invalid = ClientConfig {
+            max_burst_size: 0,
+            max_requests_per_second: 20,
+            ..test_config()
+        };
+        assert!(matches!(
+            client.update_config(invalid).await,
+            Err(ClientError::ConfigError(message)) if message.contains("max_burst_size")
+        ));
+        assert_eq!(client.stats().current_rate_limit, 10);
+
+        client
+            .update_config(ClientConfig {
+                max_requests_per_second: 20,
+                max_burst_size: 1,
+                queue_size_per_priority: 1,
+                circuit_breaker_config: CircuitBreakerConfig {
+                    failure_threshold: 1,
+                    ..Default::default()
+                },
+                ..test_config()
+            })
+            .await
+            .unwrap();
+        assert_eq!(client.config().max_requests_per_second, 20);
+        assert_eq!(client.stats().current_rate_limit, 20);
+
+        // One token at a time, one every 50ms, one request queued at most
+        client.search(search_request()).await.unwrap();
+        let queued = {
+            let client = client.clone();
+            tokio::spawn(async move { client.search(search_request()).await })
+        };
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(matches!(
+            client.search(search_request()).await,
+            Err(ApiError::QueueFull)
+        ));
+        let start = Instant::now();
+        queued.await.unwrap().unwrap();
+        assert_eq!(start.elapsed(), Duration::from_millis(40));
+
+        // A single failure opens the breakers
+        client
+            .search_breaker
+            .try_acquire()
+            .unwrap()
+            .record(&Err::<(), _>(ApiError::Timeout(5000)));
+        assert!(client.stats().circuit_breaker_open);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_response_times() {
+        let (server, client) = mock_client(test_config()).await;
+        server.set_delay(100);
+        for _ in 0..2 {
+            client.search(search_request()).await.unwrap();
+        }
+        server.set_delay(300);
+        client.book(booking_request()).await.unwrap();
+
+        let stats = client.stats();
+        assert_eq!((stats.requests_sent, stats.requests_succeeded), (3, 3));
+        assert_eq!(stats.search_latency.count, 2);
+        assert_eq!(stats.search_latency.max_ms, 100.0);
+        assert_eq!(stats.booking_latency.count, 1);
+        assert_eq!(stats.max_response_time_ms, 300.0);
+        assert!(
+            (stats.average_response_time_ms - 500.0 / 3.0).abs() < 5.0,
+            "{}",
+            stats.average_response_time_ms
+        );
+        assert!(stats.p99_response_time_ms >= stats.p95_response_time_ms);
+
+        // Only the last minute
+        tokio::time::sleep(Duration::from_secs(120)).await;
+        server.set_delay(50);
+        client.search(search_request()).await.unwrap();
+        let window = client.stats_window(Duration::from_secs(60));
+        assert_eq!(window.search_latency.count, 1);
+        assert_eq!(window.booking_latency.count, 0);
+        assert_eq!(window.max_response_time_ms, 50.0);
+        assert_eq!(window.requests_succeeded, 4);
+        assert_eq!(client.stats().search_latency.count, 3);
+    }
+
+    #[tokio::test(start_paused = true)]
     async fn test_circuit_breaker() {
-        // TODO: Implement this test
-        // - Create a mock server that consistently fails
-        // - Configure client with circuit breaker settings
-        // - Send requests until circuit breaker trips
-        // - Verify that subsequent requests fail fast with CircuitBreakerOpen
-        // - Wait for reset timeout
-        // - Verify circuit breaker allows half-open testing
+        let (server, client) = mock_client(ClientConfig {
+            retry_config: RetryConfig {
+                max_retries: 0,
+                ..Default::default()
+            },
+            circuit_breaker_config: CircuitBreakerConfig {
+                failure_threshold: 3,
+                success_threshold: 2,
+                reset_timeout_ms: 1000,
+                half_open_max_requests: 1,
+                ..Default::default()
+            },
+            ..test_config()
+        })
+        .await;
+        server.set_mode(ServerMode::CompleteOutage);
+        for _ in 0..3 {
+            assert!(matches!(
+                client.search(search_request()).await,
+                Err(ApiError::NetworkError(_))
+            ));
+        }
+
+        // Fails fast without reaching the server
+        assert!(matches!(
+            client.search(search_request()).await,
+            Err(ApiError::CircuitBreakerOpen { .. })
+        ));
+        assert_eq!(server.request_count(), 3);
+
+        // Half-open after the reset timeout, closed after two successful probes
+        tokio::time::sleep(Duration::from_millis(1000)).await;
+        server.set_mode(ServerMode::Normal);
+        assert!(client.search(search_request()).await.is_ok());
+        assert_eq!(
+            client.stats().circuit_breakers[0],
+            ("search".to_string(), CircuitState::HalfOpen)
+        );
+        assert!(client.search(search_request()).await.is_ok());
+        assert!(!client.stats().circuit_breaker_open);
+        assert_eq!(server.request_count(), 5);
     }
 
-    #[tokio::test]
+    fn booking_request() -> BookingRequest {
+        BookingRequest {
+            search_id: "search_123".to_string(),
+            hotel_id: "hotel1".to_string(),
+            guest_name: "John Doe".to_string(),
+            payment_info: PaymentInfo {
+                card_type: "VISA".to_string(),
+                last_four: "1234".to_string(),
+                expiry: "12/25".to_string(),
+                token: Some("token_123".to_string()),
+            },
+            priority: RequestPriority::Medium,
+            idempotency_key: "booking_123".to_string(),
+            context: RequestContext::default(),
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
     async fn test_prioritization_and_preemption() {
-        // TODO: Implement this test
-        // - Create a mock server with limited concurrency
-        // - Send many low priority requests to saturate the server
-        // - Then send high priority requests
-        // - Verify high priority requests complete before low priority ones
-        // - Verify some low priority requests were preempted
+        let client = Arc::new(mock_client(test_config()).await.1);
+        // Priorities of the requests sent, in order
+        let dispatched = Arc::new(Mutex::new(Vec::new()));
+        let record = {
+            let dispatched = dispatched.clone();
+            move |priority: RequestPriority, result: Result<(), ApiError>| match result {
+                Err(ApiError::RequestPreempted) => false,
+                _ => {
+                    dispatched.lock().push(priority);
+                    true
+                }
+            }
+        };
+
+        // Saturate the client with searches
+        let mut searches = Vec::new();
+        for priority in [RequestPriority::Low; 6]
+            .into_iter()
+            .chain([RequestPriority::Medium; 3])
+        {
+            let client = client.clone();
+            let record = record.clone();
+            searches.push(tokio::spawn(async move {
+                let result = client
+                    .search(SearchRequest {
+                        priority,
+                        ..search_request()
+                    })
+                    .await;
+                record(priority, result.map(drop))
+            }));
+            tokio::task::yield_now().await;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(client.stats().queue_depth, 7);
+
+        // Bookings are at least High priority; two distinct ones, so neither is coalesced
+        let bookings: Vec<_> = (0..2)
+            .map(|i| {
+                let client = client.clone();
+                let record = record.clone();
+                tokio::spawn(async move {
+                    let request = BookingRequest {
+                        idempotency_key: format!("booking_{}", i),
+                        ..booking_request()
+                    };
+                    let result = client.book(request).await;
+                    record(RequestPriority::High, result.map(drop))
+                })
+            })
+            .collect();
+        for booking in bookings {
+            assert!(booking.await.unwrap());
+        }
+        let mut sent = 0;
+        for search in searches {
+            sent += search.await.unwrap() as usize;
+        }
+
+        // The burst went to the first two searches; each booking preempted the oldest queued Low
+        // search, and the other two went last
+        assert_eq!(sent, 7);
+        let stats = client.stats();
+        assert_eq!(stats.requests_preempted, 2);
+        assert_eq!(stats.queue_depth, 0);
+        assert_eq!(
+            *dispatched.lock(),
+            [
+                [RequestPriority::Low; 2].as_slice(),
+                &[RequestPriority::High; 2],
+                &[RequestPriority::Medium; 3],
+                &[RequestPriority::Low; 2],
+            ]
+            .concat()
+        );
     }
 
-    #[tokio::test]
+    #[tokio::test(start_paused = true)]
     async fn test_retry_with_backoff() {
-        // TODO: Implement this test
-        // - Create a mock server that fails a specific number of times
-        // - Send a request that triggers retries
-        // - Measure time between retries to verify backoff
-        // - Verify request eventually succeeds
-        // - Check that retry statistics are updated
+        let (server, client) = mock_client(ClientConfig {
+            retry_config: RetryConfig {
+                max_retries: 3,
+                jitter_factor: 0.0,
+                ..Default::default()
+            },
+            ..test_config()
+        })
+        .await;
+        server.fail_next_requests(2);
+        let start = Instant::now();
+        assert!(client.search(search_request()).await.is_ok());
+        // 100ms, then 200ms
+        assert_eq!(start.elapsed(), Duration::from_millis(300));
+        assert_eq!(server.request_count(), 3);
+        assert_eq!(client.stats().requests_retried, 2);
+
+        // Gives up after max_retries
+        server.fail_next_requests(10);
+        assert!(matches!(
+            client.search(search_request()).await,
+            Err(ApiError::ApiResponseError {
+                status_code: 500,
+                ..
+            })
+        ));
+        assert_eq!(server.request_count(), 7);
+
+        // Bookings only with an idempotency key
+        server.set_mode(ServerMode::CompleteOutage);
+        let booking = BookingRequest {
+            idempotency_key: String::new(),
+            ..booking_request()
+        };
+        assert!(client.book(booking).await.is_err());
+        assert_eq!(server.request_count(), 8);
+        assert!(client.book(booking_request()).await.is_err());
+        assert_eq!(server.request_count(), 12);
+        assert_eq!(client.stats().requests_retried, 8);
     }
 
-    #[tokio::test]
+    #[tokio::test(start_paused = true)]
+    async fn test_idempotent_bookings() {
+        let (server, client) = mock_client(test_config()).await;
+        server.set_delay(100);
+
+        // Duplicates in flight together share one booking
+        let (first, second) = tokio::join!(
+            client.book(booking_request()),
+            client.book(booking_request())
+        );
+        let booking_id = first.unwrap().booking_id;
+        assert_eq!(second.unwrap().booking_id, booking_id);
+        assert_eq!(server.request_count(), 1);
+
+        // Repeats are replayed
+        let repeat = client.book(booking_request()).await.unwrap();
+        assert_eq!(repeat.booking_id, booking_id);
+        assert_eq!(server.request_count(), 1);
+
+        // Another key books again
+        let other = client
+            .book(BookingRequest {
+                idempotency_key: "booking_456".to_string(),
+                ..booking_request()
+            })
+            .await
+            .unwrap();
+        assert_ne!(other.booking_id, booking_id);
+        assert_eq!(server.request_count(), 2);
+
+        let stats = client.stats();
+        assert_eq!(stats.requests_deduplicated, 2);
+        assert_eq!(stats.requests_sent, 2);
+        assert_eq!(stats.requests_succeeded, 2);
+    }
+
+    fn cancel_request() -> CancelBookingRequest {
+        CancelBookingRequest {
+            booking_id: "BK1".to_string(),
+            reason: Some("Change of plans".to_string()),
+            idempotency_key: "cancel_123".to_string(),
+            context: RequestContext::default(),
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_cancel_and_modify_booking() {
+        let (server, client) = mock_client(test_config()).await;
+        server
+            .add_cancellation_response(
+                "BK1",
+                CancelBookingResponse {
+                    booking_id: "BK1".to_string(),
+                    status: BookingStatus::Cancelled,
+                    penalty_amount: 30.0,
+                    refund_amount: 70.0,
+                    currency: Some("EUR".to_string()),
+                    rate_limit_remaining: None,
+                    processing_time_ms: 0,
+                },
+            )
+            .await;
+
+        let cancelled = client.cancel_booking(cancel_request()).await.unwrap();
+        assert_eq!(cancelled.status, BookingStatus::Cancelled);
+        assert_eq!(
+            (cancelled.penalty_amount, cancelled.refund_amount),
+            (30.0, 70.0)
+        );
+        // Replayed for its key; with another it reaches the upstream, which has no such booking
+        let repeat = client.cancel_booking(cancel_request()).await.unwrap();
+        assert_eq!(repeat.refund_amount, 70.0);
+        assert_eq!(server.request_count(), 1);
+        let again = client
+            .cancel_booking(CancelBookingRequest {
+                idempotency_key: "cancel_456".to_string(),
+                ..cancel_request()
+            })
+            .await;
+        assert!(matches!(
+            again,
+            Err(ApiError::ApiResponseError {
+                status_code: 404,
+                ..
+            })
+        ));
+        assert_eq!(server.request_count(), 2);
+
+        let modification = ModifyBookingRequest {
+            booking_id: "BK2".to_string(),
+            check_out: Some("2025-06-07".to_string()),
+            idempotency_key: "modify_123".to_string(),
+            ..Default::default()
+        };
+        let modified = client.modify_booking(modification.clone()).await.unwrap();
+        assert_eq!(modified.booking_id, "BK2");
+        assert_eq!(
+            client
+                .modify_booking(modification.clone())
+                .await
+                .unwrap()
+                .confirmation_code,
+            modified.confirmation_code
+        );
+        assert_eq!(server.request_count(), 3);
+        // Nothing to change isn't sent
+        let unchanged = ModifyBookingRequest {
+            idempotency_key: "modify_456".to_string(),
+            check_out: None,
+            ..modification
+        };
+        assert!(matches!(
+            client.modify_booking(unchanged).await,
+            Err(ApiError::ClientError(_))
+        ));
+        assert_eq!(server.request_count(), 3);
+
+        let stats = client.stats();
+        assert_eq!(stats.requests_deduplicated, 2);
+        // Replays aren't timed
+        assert_eq!(stats.cancellation_latency.count, 2);
+        assert_eq!(stats.modification_latency.count, 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_booking_status_polling() {
+        // Polls aren't held back by the rate limiter
+        let (server, client) = mock_client(ClientConfig {
+            max_requests_per_second: 100,
+            ..test_config()
+        })
+        .await;
+        let on_request = BookingStatus::from("On_Request");
+        assert_eq!(on_request, BookingStatus::Other("On_Request".to_string()));
+        assert_eq!(BookingStatus::from("CONFIRMED"), BookingStatus::Confirmed);
+        server
+            .set_booking_statuses(
+                "BK1",
+                vec![
+                    BookingStatus::Pending,
+                    on_request.clone(),
+                    BookingStatus::Confirmed,
+                ],
+            )
+            .await;
+        let status = client.get_booking_status("BK1").await.unwrap();
+        assert_eq!(status.status, BookingStatus::Pending);
+        assert!(!status.status.is_terminal());
+
+        // Polled until confirmed, through the supplier's own state
+        let policy = PollPolicy {
+            initial_interval_ms: 100,
+            max_interval_ms: 150,
+            multiplier: 2.0,
+            timeout_ms: 1000,
+        };
+        let start = Instant::now();
+        let confirmed = client.poll_until_confirmed("BK1", &policy).await.unwrap();
+        assert_eq!(confirmed.status, BookingStatus::Confirmed);
+        assert!(confirmed.confirmation_code.is_some());
+        assert_eq!(start.elapsed(), Duration::from_millis(100));
+
+        // Given up on once the next poll would be past the timeout: 100ms, then 150ms apart
+        server
+            .set_booking_statuses("BK2", vec![BookingStatus::Pending])
+            .await;
+        let start = Instant::now();
+        assert!(matches!(
+            client.poll_until_confirmed("BK2", &policy).await,
+            Err(ApiError::Timeout(850))
+        ));
+        assert_eq!(start.elapsed(), Duration::from_millis(850));
+
+        // Unknown bookings aren't polled again
+        let requests = server.request_count();
+        assert!(matches!(
+            client.poll_until_confirmed("BK3", &policy).await,
+            Err(ApiError::ApiResponseError {
+                status_code: 404,
+                ..
+            })
+        ));
+        assert_eq!(server.request_count(), requests + 1);
+
+        // Cancelled is terminal
+        let booked = client.book(booking_request()).await.unwrap();
+        let status = client.get_booking_status(&booked.booking_id).await.unwrap();
+        assert_eq!(status.status, BookingStatus::Confirmed);
+        client
+            .cancel_booking(CancelBookingRequest {
+                booking_id: booked.booking_id.clone(),
+                ..cancel_request()
+            })
+            .await
+            .unwrap();
+        let cancelled = client
+            .poll_until_confirmed(&booked.booking_id, &policy)
+            .await
+            .unwrap();
+        assert_eq!(cancelled.status, BookingStatus::Cancelled);
+        assert_eq!(client.stats().status_latency.count, 13);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_cancellations_are_critical() {
+        let (server, client) = mock_client(test_config()).await;
+        let client = Arc::new(client);
+        // Take the burst so the rest queue
+        for _ in 0..2 {
+            client.search(search_request()).await.unwrap();
+        }
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let booking = tokio::spawn({
+            let (client, order) = (client.clone(), order.clone());
+            async move {
+                client.book(booking_request()).await.unwrap();
+                order.lock().push("booking");
+            }
+        });
+        tokio::task::yield_now().await;
+        let cancellation = tokio::spawn({
+            let (client, order) = (client.clone(), order.clone());
+            async move {
+                client.cancel_booking(cancel_request()).await.unwrap();
+                order.lock().push("cancellation");
+            }
+        });
+        booking.await.unwrap();
+        cancellation.await.unwrap();
+        assert_eq!(*order.lock(), ["cancellation", "booking"]);
+        assert_eq!(server.request_count(), 4);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_search_coalescing() {
+        let config = ClientConfig {
+            max_burst_size: 10,
+            coalesce_searches: true,
+            ..test_config()
+        };
+        let (server, client) = mock_client(config.clone()).await;
+        let client = Arc::new(client);
+        server.set_delay(100);
+        let search = |hotel_ids: &[&str], guests: u32, correlation_id: &str| {
+            let client = client.clone();
+            let request = SearchRequest {
+                hotel_ids: hotel_ids.iter().map(|id| id.to_string()).collect(),
+                guests,
+                context: RequestContext {
+                    correlation_id: correlation_id.to_string(),
+                    ..Default::default()
+                },
+                ..search_request()
+            };
+            tokio::spawn(async move { client.search(request).await })
+        };
+
+        // Same hotels in any order share one call, another party doesn't
+        let first = search(&["hotel1", "hotel2"], 2, "first");
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        let second = search(&["hotel2", "hotel1"], 2, "second");
+        let cancelled = search(&["hotel1", "hotel2"], 2, "cancelled");
+        let other = search(&["hotel1", "hotel2"], 3, "other");
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        // A waiting search is cancelled alone
+        assert!(client.cancel_request("cancelled").await);
+        assert!(matches!(
+            cancelled.await.unwrap(),
+            Err(ApiError::RequestCancelled)
+        ));
+        let search_id = first.await.unwrap().unwrap().search_id;
+        assert_eq!(second.await.unwrap().unwrap().search_id, search_id);
+        assert_ne!(other.await.unwrap().unwrap().search_id, search_id);
+        assert_eq!(server.request_count(), 2);
+        let stats = client.stats();
+        assert_eq!(stats.requests_coalesced, 1);
+        assert_eq!(stats.requests_succeeded, 3);
+
+        // Once done, or with coalescing off, searches go upstream
+        client.search(search_request()).await.unwrap();
+        client
+            .update_config(ClientConfig {
+                coalesce_searches: false,
+                ..config
+            })
+            .await
+            .unwrap();
+        let (first, second) = tokio::join!(
+            client.search(search_request()),
+            client.search(search_request())
+        );
+        assert!(first.is_ok() && second.is_ok());
+        assert_eq!(server.request_count(), 5);
+        assert_eq!(client.stats().requests_coalesced, 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_search_coalescing_by_supplier() {
+        let config = ClientConfig {
+            max_burst_size: 10,
+            coalesce_searches: true,
+            ..test_config()
+        };
+        let (server, client) = mock_client(config).await;
+        server.set_delay(100);
+        let search = |supplier: Option<&str>| SearchRequest {
+            supplier: supplier.map(str::to_string),
+            ..search_request()
+        };
+
+        // The same stay from another supplier is a search of its own
+        let (first, second, other, routed) = tokio::join!(
+            client.search(search(Some("supplier1"))),
+            client.search(search(Some("supplier1"))),
+            client.search(search(Some("supplier2"))),
+            client.search(search(None)),
+        );
+        let search_id = first.unwrap().search_id;
+        assert_eq!(second.unwrap().search_id, search_id);
+        assert_ne!(other.unwrap().search_id, search_id);
+        assert_ne!(routed.unwrap().search_id, search_id);
+        assert_eq!(server.request_count(), 3);
+        assert_eq!(client.stats().requests_coalesced, 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_health_checker() {
+        let config = ClientConfig {
+            timeout_ms: 1000,
+            health_check_interval_ms: 1000,
+            health_check_probe: Some(HealthProbe::Path("health".to_string())),
+            ..test_config()
+        };
+        let (server, client) = mock_client(config).await;
+        let client = Arc::new(client);
+        let mut transitions = client.subscribe_health();
+        let checker = client.spawn_health_checker();
+        server.set_delay(100);
+
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+        assert_eq!(server.request_count(), 1);
+        assert_eq!(client.health(), SystemHealth::Healthy);
+
+        // Slow, then down
+        server.set_mode(ServerMode::PartialOutage);
+        let transition = transitions.recv().await.unwrap();
+        assert_eq!(transition.to, SystemHealth::Degraded);
+        server.set_mode(ServerMode::CompleteOutage);
+        let transition = transitions.recv().await.unwrap();
+        assert_eq!(
+            (transition.from, transition.to),
+            (SystemHealth::Degraded, SystemHealth::Unhealthy)
+        );
+        let stats = client.stats();
+        assert_eq!(stats.system_health, SystemHealth::Unhealthy);
+        assert_eq!(stats.adaptive_rate_limit_multiplier, 0.2);
+
+        // Back up
+        server.set_mode(ServerMode::Normal);
+        let transition = transitions.recv().await.unwrap();
+        assert_eq!(transition.to, SystemHealth::Healthy);
+        assert_eq!(client.stats().adaptive_rate_limit_multiplier, 1.0);
+
+        // Ends with the client
+        drop(client);
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+        assert!(checker.is_finished());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_retry_after() {
+        let (server, client) = mock_client(ClientConfig {
+            max_burst_size: 10,
+            ..test_config()
+        })
+        .await;
+        server.set_rate_limit(1, 1000);
+        let start = Instant::now();
+        client.search(search_request()).await.unwrap();
+        tokio::time::advance(Duration::from_millis(200)).await;
+
+        // Retried once the upstream's window has room, whatever the backoff
+        client.search(search_request()).await.unwrap();
+        assert_eq!(start.elapsed(), Duration::from_millis(1000));
+        let stats = client.stats();
+        assert_eq!(stats.requests_retried, 1);
+        assert_eq!(stats.requests_sent, 3);
+        assert_eq!(client.limiter.paused_until(), None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_retry_after_is_capped() {
+        let (server, client) = mock_client(ClientConfig {
+            max_burst_size: 10,
+            retry_config: RetryConfig {
+                max_retries: 0,
+                max_retry_after_ms: 300,
+                ..Default::default()
+            },
+            ..test_config()
+        })
+        .await;
+        // The upstream asks for an hour
+        server.set_rate_limit(1, 3_600_000);
+        client.search(search_request()).await.unwrap();
+        assert!(matches!(
+            client.search(search_request()).await,
+            Err(ApiError::RateLimitExceeded { .. })
+        ));
+        assert_eq!(
+            client.limiter.paused_until(),
+            Some(Instant::now() + Duration::from_millis(300))
+        );
+    }
+
+    // Spans opened, with their parents and fields, and the messages of events
+    #[derive(Clone, Default)]
+    struct SpanRecorder {
+        spans: Arc<Mutex<Vec<RecordedSpan>>>,
+        events: Arc<Mutex<Vec<String>>>,
+        entered: Arc<Mutex<Vec<usize>>>,
+    }
+
+    #[derive(Debug, Clone)]
+    struct RecordedSpan {
+        name: &'static str,
+        parent: Option<&'static str>,
+        fields: Vec<(String, String)>,
+    }
+
+    struct FieldVisitor<'a>(&'a mut Vec<(String, String)>);
+
+    impl tracing::field::Visit for FieldVisitor<'_> {
+        fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+            self.0
+                .push((field.name().to_string(), format!("{:?}", value)));
+        }
+    }
+
+    impl tracing::Subscriber for SpanRecorder {
+        fn enabled(&self, _metadata: &tracing::Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, attributes: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+            let mut spans = self.spans.lock();
+            let parent = match attributes.parent() {
+                Some(parent) => Some(parent.into_u64() as usize),
+                None if attributes.is_contextual() => self.entered.lock().last().copied(),
+                None => None,
+            };
+            let parent = parent.map(|parent| spans[parent - 1].name);
+            let mut fields = Vec::new();
+            attributes.record(&mut FieldVisitor(&mut fields));
+            spans.push(RecordedSpan {
+                name: attributes.metadata().name(),
+                parent,
+                fields,
+            });
+            tracing::span::Id::from_u64(spans.len() as u64)
+        }
+
+        fn record(&self, span: &tracing::span::Id, values: &tracing::span::Record<'_>) {
+            let mut spans = self.spans.lock();
+            let fields = &mut spans[span.into_u64() as usize - 1].fields;
+            values.record(&mut FieldVisitor(fields));
+        }
+
+        fn record_follows_from(&self, _span: &tracing::span::Id, _follows: &tracing::span::Id) {}
+
+        fn event(&self, event: &tracing::Event<'_>) {
+            let mut fields = Vec::new();
+            event.record(&mut FieldVisitor(&mut fields));
+            if let Some((_, message)) = fields.into_iter().find(|(name, _)| name == "message") {
+                self.events.lock().push(message);
+            }
+        }
+
+        fn enter(&self, span: &tracing::span::Id) {
+            self.entered.lock().push(span.into_u64() as usize);
+        }
+
+        fn exit(&self, _span: &tracing::span::Id) {
+            self.entered.lock().pop();
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_tracing_spans() {
+        let recorder = SpanRecorder::default();
+        let _default = tracing::subscriber::set_default(recorder.clone());
+        let (server, client) = mock_client(test_config()).await;
+        server.fail_next_requests(1);
+        let request = SearchRequest {
+            context: RequestContext {
+                correlation_id: "corr-1".to_string(),
+                ..Default::default()
+            },
+            ..search_request()
+        };
+        client.search(request).await.unwrap();
+
+        let spans = recorder.spans.lock().clone();
+        let field = |span: &RecordedSpan, name: &str| {
+            span.fields
+                .iter()
+                .find(|(field, _)| field == name)
+                .map(|(_, value)| value.clone())
+        };
+        let request = spans.iter().find(|span| span.name == "request").unwrap();
+        assert_eq!(field(request, "operation").as_deref(), Some("\"search\""));
+        assert_eq!(field(request, "correlation_id").as_deref(), Some("corr-1"));
+        assert_eq!(field(request, "priority").as_deref(), Some("Medium"));
+        assert_eq!(field(request, "hotels").as_deref(), Some("1"));
+        assert_eq!(field(request, "outcome").as_deref(), Some("\"ok\""));
+
+        // Two attempts, each queued then sent
+        let children = |parent: &str| {
+            spans
+                .iter()
+                .filter(|span| span.parent == Some(parent))
+                .map(|span| span.name)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(children("request"), ["attempt", "attempt"]);
+        assert_eq!(
+            children("attempt"),
+            ["queue_wait", "transport", "queue_wait", "transport"]
+        );
+        let events = recorder.events.lock();
+        assert!(events
+            .iter()
+            .any(|event| event.starts_with("Retrying after API error: 500")));
+    }
+
+    #[tokio::test(start_paused = true)]
     async fn test_extreme_load_handling() {
-        // TODO: Implement this test
-        // - Create a client with limited capacity
-        // - Simultaneously send hundreds or thousands of requests
-        // - Verify client maintains stability
-        // - Check that low priority requests are rejected when overloaded
-        // - Verify high priority requests still get through
-        // - Check statistics for throughput and latency
+        let config = ClientConfig {
+            queue_size_per_priority: 10,
+            overflow_policies: OverflowPolicies::default().with_policy(
+                RequestPriority::Medium,
+                OverflowPolicy::DropOldestLowPriority,
+            ),
+            ..test_config()
+        };
+        let client = Arc::new(mock_client(config).await.1);
+        let search = |priority| {
+            let client = client.clone();
+            tokio::spawn(async move {
+                client
+                    .search(SearchRequest {
+                        priority,
+                        ..search_request()
+                    })
+                    .await
+            })
+        };
+
+        // A queue's worth gets in, the rest are turned away; the burst then sends two of them
+        let low: Vec<_> = (0..200).map(|_| search(RequestPriority::Low)).collect();
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        let stats = client.stats();
+        assert_eq!(stats.requests_rejected, 190);
+        assert_eq!(stats.queue_depth_by_priority[0], (RequestPriority::Low, 8));
+
+        // Medium fills its own queue, then takes the places of queued Low searches
+        let medium: Vec<_> = (0..18).map(|_| search(RequestPriority::Medium)).collect();
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        let stats = client.stats();
+        assert_eq!(
+            stats.queue_depth_by_priority,
+            vec![
+                (RequestPriority::Low, 0),
+                (RequestPriority::Medium, 18),
+                (RequestPriority::High, 0),
+                (RequestPriority::Critical, 0),
+            ]
+        );
+        assert_eq!(stats.requests_preempted, 8);
+
+        for search in medium {
+            assert!(search.await.unwrap().is_ok());
+        }
+        let mut outcomes = std::collections::HashMap::new();
+        for search in low {
+            let outcome = match search.await.unwrap() {
+                Ok(_) => "sent",
+                Err(ApiError::RequestPreempted) => "preempted",
+                Err(ApiError::QueueFull) => "rejected",
+                Err(error) => panic!("unexpected {:?}", error),
+            };
+            *outcomes.entry(outcome).or_insert(0) += 1;
+        }
+        assert_eq!(
+            outcomes,
+            std::collections::HashMap::from([("sent", 2), ("preempted", 8), ("rejected", 190)])
+        );
+        let stats = client.stats();
+        assert_eq!(stats.requests_succeeded, 20);
+        assert_eq!(stats.requests_rejected, 190);
+        assert_eq!(stats.queue_depth, 0);
     }
 }
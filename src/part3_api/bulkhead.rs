@@ -0,0 +1,219 @@
+// Concurrency limiting
+// At most `max_concurrent_requests` requests are in flight at once, and lower priorities get a
+// smaller share of them: Low requests may hold half the slots, Low and Medium together three
+// quarters, everything below Critical nine tenths, so however many searches are waiting there
+// are always slots left for bookings, and some for Critical ones alone.
+// Each share is a semaphore; a request takes a permit from the one of its priority and from
+// each above it, always in that order, so no two requests wait on each other. Shares are
+// rounded down but never below one slot.
+// Resizing (`resize`) adds slots at once; slots taken away that are in use are given up as
+// their requests end, so requests in flight are never interrupted.
+
+use super::RequestPriority;
+use parking_lot::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+const PRIORITIES: usize = RequestPriority::ALL.len();
+// Share of the slots priorities up to each may hold
+const SHARES: [f64; PRIORITIES] = [0.5, 0.75, 0.9, 1.0];
+
+pub struct Bulkhead {
+    // Indexed by priority
+    tiers: [Semaphore; PRIORITIES],
+    limits: Mutex<[usize; PRIORITIES]>,
+    // Slots to give up as they are given back, after shrinking
+    surplus: [AtomicUsize; PRIORITIES],
+    in_flight: [AtomicUsize; PRIORITIES],
+}
+
+// A slot; given back when dropped
+pub struct BulkheadPermit<'a> {
+    bulkhead: &'a Bulkhead,
+    priority: RequestPriority,
+    // Taken from the tier of the priority and each above
+    permits: Vec<SemaphorePermit<'a>>,
+}
+
+impl Bulkhead {
+    pub fn new(max_concurrent_requests: usize) -> Self {
+        let limits = limits(max_concurrent_requests);
+        Self {
+            tiers: limits.map(Semaphore::new),
+            limits: Mutex::new(limits),
+            surplus: Default::default(),
+            in_flight: Default::default(),
+        }
+    }
+
+    // Wait for a slot for a request of `priority`
+    pub async fn acquire(&self, priority: RequestPriority) -> BulkheadPermit<'_> {
+        let mut permits = Vec::with_capacity(PRIORITIES);
+        for tier in &self.tiers[priority as usize..] {
+            permits.push(
+                tier.acquire()
+                    .await
+                    .expect("bulkhead semaphores are never closed"),
+            );
+        }
+        self.in_flight[priority as usize].fetch_add(1, Ordering::SeqCst);
+        BulkheadPermit {
+            bulkhead: self,
+            priority,
+            permits,
+        }
+    }
+
+    pub fn resize(&self, max_concurrent_requests: usize) {
+        let mut limits = self.limits.lock();
+        let resized = self::limits(max_concurrent_requests);
+        for (tier, (old, new)) in limits.iter().zip(resized).enumerate() {
+            let semaphore = &self.tiers[tier];
+            let surplus = &self.surplus[tier];
+            if new > *old {
+                // Slots still due to be given up are kept instead
+                let kept = take_up_to(surplus, new - old);
+                semaphore.add_permits(new - old - kept);
+            } else if new < *old {
+                let forgotten = semaphore.forget_permits(old - new);
+                surplus.fetch_add(old - new - forgotten, Ordering::SeqCst);
+            }
+        }
+        *limits = resized;
+    }
+
+    // How many requests of `priority` and below may be in flight together
+    pub fn limit(&self, priority: RequestPriority) -> usize {
+        self.limits.lock()[priority as usize]
+    }
+
+    pub fn in_flight(&self, priority: RequestPriority) -> usize {
+        self.in_flight[priority as usize].load(Ordering::SeqCst)
+    }
+
+    // Requests in flight, all priorities
+    pub fn active(&self) -> usize {
+        RequestPriority::ALL
+            .iter()
+            .map(|priority| self.in_flight(*priority))
+            .sum()
+    }
+}
+
+impl Drop for BulkheadPermit<'_> {
+    fn drop(&mut self) {
+        let bulkhead = self.bulkhead;
+        bulkhead.in_flight[self.priority as usize].fetch_sub(1, Ordering::SeqCst);
+        for (tier, permit) in (self.priority as usize..).zip(self.permits.drain(..)) {
+            if take_up_to(&bulkhead.surplus[tier], 1) == 1 {
+                permit.forget();
+            }
+        }
+    }
+}
+
+fn limits(max_concurrent_requests: usize) -> [usize; PRIORITIES] {
+    SHARES.map(|share| {
+        ((max_concurrent_requests as f64 * share) as usize).clamp(1, max_concurrent_requests.max(1))
+    })
+}
+
+// Take up to `wanted` from `counter`; returns how much was taken
+fn take_up_to(counter: &AtomicUsize, wanted: usize) -> usize {
+    let previous = counter
+        .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |available| {
+            Some(available - available.min(wanted))
+        })
+        .unwrap_or_default();
+    previous.min(wanted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_limits() {
+        let bulkhead = Bulkhead::new(10);
+        let limits = RequestPriority::ALL.map(|priority| bulkhead.limit(priority));
+        assert_eq!(limits, [5, 7, 9, 10]);
+        let bulkhead = Bulkhead::new(1);
+        let limits = RequestPriority::ALL.map(|priority| bulkhead.limit(priority));
+        assert_eq!(limits, [1, 1, 1, 1]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_low_priority_cannot_exhaust_slots() {
+        let bulkhead = Bulkhead::new(4);
+        let low: Vec<_> =
+            futures::future::join_all((0..2).map(|_| bulkhead.acquire(RequestPriority::Low))).await;
+        // Half of the slots
+        let waiting = tokio::time::timeout(
+            Duration::from_millis(10),
+            bulkhead.acquire(RequestPriority::Low),
+        )
+        .await;
+        assert!(waiting.is_err());
+
+        let medium = bulkhead.acquire(RequestPriority::Medium).await;
+        assert!(tokio::time::timeout(
+            Duration::from_millis(10),
+            bulkhead.acquire(RequestPriority::Medium),
+        )
+        .await
+        .is_err());
+        let critical = bulkhead.acquire(RequestPriority::Critical).await;
+        assert_eq!(bulkhead.active(), 4);
+        assert_eq!(bulkhead.in_flight(RequestPriority::Low), 2);
+        assert_eq!(bulkhead.in_flight(RequestPriority::Critical), 1);
+
+        // Slots are given back
+        drop(low);
+        drop(medium);
+        drop(critical);
+        assert_eq!(bulkhead.active(), 0);
+        let _low = bulkhead.acquire(RequestPriority::Low).await;
+        assert_eq!(bulkhead.in_flight(RequestPriority::Low), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_resize() {
+        let bulkhead = Bulkhead::new(4);
+        let critical: Vec<_> =
+            futures::future::join_all((0..4).map(|_| bulkhead.acquire(RequestPriority::Critical)))
+                .await;
+
+        // Shrinking waits for the slots in use
+        bulkhead.resize(2);
+        assert_eq!(bulkhead.limit(RequestPriority::Critical), 2);
+        let mut critical = critical.into_iter();
+        drop(critical.next());
+        drop(critical.next());
+        assert!(tokio::time::timeout(
+            Duration::from_millis(10),
+            bulkhead.acquire(RequestPriority::Critical),
+        )
+        .await
+        .is_err());
+        drop(critical.next());
+        let third = bulkhead.acquire(RequestPriority::Critical).await;
+
+        // Growing adds slots at once
+        bulkhead.resize(3);
+        let fourth = bulkhead.acquire(RequestPriority::Critical).await;
+        assert_eq!(bulkhead.active(), 3);
+        assert!(tokio::time::timeout(
+            Duration::from_millis(10),
+            bulkhead.acquire(RequestPriority::Critical),
+        )
+        .await
+        .is_err());
+        drop((critical, third, fourth));
+        assert_eq!(bulkhead.active(), 0);
+        let all: Vec<_> =
+            futures::future::join_all((0..3).map(|_| bulkhead.acquire(RequestPriority::Critical)))
+                .await;
+        assert_eq!(all.len(), 3);
+    }
+}
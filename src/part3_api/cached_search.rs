@@ -0,0 +1,492 @@
+// Read-through search caching
+// Wraps an `ApiClient` with an `AvailabilityCache`: searches are answered per hotel from the
+// cache where possible and only the missing hotels are requested from the remote API. Fresh
+// results are cached (unavailable hotels as negative entries) and merged with the cached ones
+// in the order the hotels were requested. Everything except `search` goes straight through.
+// A cached result keeps the ID of the search that found it (`SearchResult::search_id`), as
+// that is the search a booking has to name; `SearchResponse::search_id_for` picks the right one.
+// The response's own ID is the remote search's, or the first cached result's when nothing had to
+// be asked. Entries cached without an ID are searched again.
+
+use super::{
+    ApiClient, ApiError, BookingRequest, BookingResponse, CancelBookingRequest,
+    CancelBookingResponse, ClientConfig, ClientError, ClientStats, ModifyBookingRequest,
+    ModifyBookingResponse, SearchRequest, SearchResponse, SearchResult, SystemHealth,
+};
+use crate::part1_cache::typed::Codec;
+use crate::part1_cache::{AvailabilityCache, CacheKey, CacheLookup, SearchContext};
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+pub struct CachedSearchClient<C, K> {
+    client: C,
+    cache: K,
+    // TTL of cached results (None uses the cache default)
+    ttl: Option<Duration>,
+    codec: Codec,
+}
+
+impl<C: ApiClient, K: AvailabilityCache> CachedSearchClient<C, K> {
+    pub fn new(client: C, cache: K) -> Self {
+        Self {
+            client,
+            cache,
+            ttl: None,
+            codec: Codec::default(),
+        }
+    }
+
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    pub fn with_codec(mut self, codec: Codec) -> Self {
+        self.codec = codec;
+        self
+    }
+
+    pub fn client(&self) -> &C {
+        &self.client
+    }
+
+    pub fn cache(&self) -> &K {
+        &self.cache
+    }
+
+    // Availability depends on the party size, the markets and the supplier routed to, so they
+    // are part of the key
+    fn cache_key(request: &SearchRequest, hotel_id: &str) -> CacheKey {
+        let mut context = request.markets.iter().fold(
+            SearchContext::new().occupancy(request.guests, 0),
+            |context, market| context.market(market),
+        );
+        if let Some(supplier) = &request.supplier {
+            context = context.supplier(supplier);
+        }
+        CacheKey::new(hotel_id, &request.check_in, &request.check_out).with_context(&context)
+    }
+
+    // The cached result for a lookup, None if the remote API has to be asked
+    fn cached_result(&self, hotel_id: &str, found: CacheLookup) -> Option<SearchResult> {
+        match found {
+            CacheLookup::Hit(data) => match self.codec.decode::<SearchResult>(&data) {
+                Ok(result) if result.search_id.is_some() => Some(result),
+                Ok(_) => None,
+                Err(e) => {
+                    tracing::warn!("Ignoring cached search result for {}: {}", hotel_id, e);
+                    None
+                }
+            },
+            CacheLookup::HitEmpty => Some(SearchResult {
+                hotel_id: hotel_id.to_string(),
+                available: false,
+                price: None,
+                currency: None,
+                search_id: None,
+            }),
+            CacheLookup::Miss => None,
+        }
+    }
+
+    // Cache a result of the remote search `search_id`
+    fn store_result(&self, request: &SearchRequest, search_id: &str, result: &SearchResult) {
+        let key = Self::cache_key(request, &result.hotel_id);
+        if !result.available {
+            self.cache.store_empty(key, self.ttl);
+            return;
+        }
+        let result = SearchResult {
+            search_id: Some(result.search_id.as_deref().unwrap_or(search_id).to_string()),
+            ..result.clone()
+        };
+        match self.codec.encode(&result) {
+            Ok(data) => {
+                self.cache.store_key(key, data, self.ttl);
+            }
+            Err(e) => tracing::warn!("Not caching search result for {}: {}", result.hotel_id, e),
+        }
+    }
+}
+
+#[async_trait]
+impl<C: ApiClient, K: AvailabilityCache> ApiClient for CachedSearchClient<C, K> {
+    // Remote errors are returned as is, even if some hotels were found in the cache
+    async fn search(&self, request: SearchRequest) -> Result<SearchResponse, ApiError> {
+        let started = Instant::now();
+        let keys: Vec<CacheKey> = request
+            .hotel_ids
+            .iter()
+            .map(|hotel_id| Self::cache_key(&request, hotel_id))
+            .collect();
+
+        let mut results: HashMap<String, SearchResult> = HashMap::new();
+        let mut misses = Vec::new();
+        for (hotel_id, found) in request
+            .hotel_ids
+            .iter()
+            .zip(self.cache.get_many_keys(&keys))
+        {
+            match self.cached_result(hotel_id, found) {
+                Some(result) => {
+                    results.insert(hotel_id.clone(), result);
+                }
+                None => misses.push(hotel_id.clone()),
+            }
+        }
+
+        // Only sold out hotels cached leaves nothing to book, and no search to name
+        let mut search_id = request
+            .hotel_ids
+            .iter()
+            .find_map(|hotel_id| results.get(hotel_id)?.search_id.clone())
+            .unwrap_or_default();
+        let mut rate_limit_remaining = None;
+        if !misses.is_empty() {
+            let remote = SearchRequest {
+                hotel_ids: misses,
+                ..request.clone()
+            };
+            let response = self.client.search(remote).await?;
+            for result in response.results {
+                self.store_result(&request, &response.search_id, &result);
+                results.insert(result.hotel_id.clone(), result);
+            }
+            search_id = response.search_id;
+            rate_limit_remaining = response.rate_limit_remaining;
+        }
+
+        // Hotels the API returned nothing for are left out, as in a plain search
+        Ok(SearchResponse {
+            search_id,
+            results: request
+                .hotel_ids
+                .iter()
+                .filter_map(|hotel_id| results.remove(hotel_id))
+                .collect(),
+            rate_limit_remaining,
+            processing_time_ms: started.elapsed().as_millis() as u64,
+        })
+    }
+
+    async fn book(&self, request: BookingRequest) -> Result<BookingResponse, ApiError> {
+        self.client.book(request).await
+    }
+
+    async fn cancel_booking(
+        &self,
+        request: CancelBookingRequest,
+    ) -> Result<CancelBookingResponse, ApiError> {
+        self.client.cancel_booking(request).await
+    }
+
+    async fn modify_booking(
+        &self,
+        request: ModifyBookingRequest,
+    ) -> Result<ModifyBookingResponse, ApiError> {
+        self.client.modify_booking(request).await
+    }
+
+    async fn get_booking_status(&self, booking_id: &str) -> Result<BookingResponse, ApiError> {
+        self.client.get_booking_status(booking_id).await
+    }
+
+    fn stats(&self) -> ClientStats {
+        self.client.stats()
+    }
+
+    async fn set_system_health(&self, health: SystemHealth) -> f64 {
+        self.client.set_system_health(health).await
+    }
+
+    async fn cancel_request(&self, correlation_id: &str) -> bool {
+        self.client.cancel_request(correlation_id).await
+    }
+
+    async fn update_config(&self, config: ClientConfig) -> Result<(), ClientError> {
+        self.client.update_config(config).await
+    }
+
+    async fn pause(&self, drain: bool) -> Result<(), ClientError> {
+        self.client.pause(drain).await
+    }
+
+    async fn resume(&self) -> Result<(), ClientError> {
+        self.client.resume().await
+    }
+
+    async fn reset_circuit_breakers(&self) -> usize {
+        self.client.reset_circuit_breakers().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::part1_cache::{CacheConfig, ExampleCache};
+    use crate::part3_api::{RequestContext, RequestPriority};
+    use std::sync::Mutex;
+
+    // Records the hotels each search asked for; odd hotel numbers are sold out
+    // Searches are numbered from search-1
+    #[derive(Default)]
+    struct RecordingClient {
+        searches: Mutex<Vec<Vec<String>>>,
+        fail: bool,
+    }
+
+    #[async_trait]
+    impl ApiClient for RecordingClient {
+        async fn search(&self, request: SearchRequest) -> Result<SearchResponse, ApiError> {
+            let search_id = {
+                let mut searches = self.searches.lock().unwrap();
+                searches.push(request.hotel_ids.clone());
+                format!("search-{}", searches.len())
+            };
+            if self.fail {
+                return Err(ApiError::NetworkError("Service unavailable".to_string()));
+            }
+            let results = request
+                .hotel_ids
+                .into_iter()
+                .map(|hotel_id| {
+                    let available =
+                        hotel_id.trim_start_matches("hotel").parse::<u32>().unwrap() % 2 == 0;
+                    SearchResult {
+                        hotel_id,
+                        available,
+                        price: available.then_some(100.0),
+                        currency: available.then(|| "EUR".to_string()),
+                        search_id: None,
+                    }
+                })
+                .collect();
+            Ok(SearchResponse {
+                search_id,
+                results,
+                rate_limit_remaining: Some(9),
+                processing_time_ms: 0,
+            })
+        }
+
+        async fn book(&self, _request: BookingRequest) -> Result<BookingResponse, ApiError> {
+            Err(ApiError::Other("Not supported".to_string()))
+        }
+
+        async fn cancel_booking(
+            &self,
+            _request: CancelBookingRequest,
+        ) -> Result<CancelBookingResponse, ApiError> {
+            Err(ApiError::Other("Not supported".to_string()))
+        }
+
+        async fn modify_booking(
+            &self,
+            _request: ModifyBookingRequest,
+        ) -> Result<ModifyBookingResponse, ApiError> {
+            Err(ApiError::Other("Not supported".to_string()))
+        }
+
+        async fn get_booking_status(&self, _booking_id: &str) -> Result<BookingResponse, ApiError> {
+            Err(ApiError::Other("Not supported".to_string()))
+        }
+
+        fn stats(&self) -> ClientStats {
+            ClientStats::default()
+        }
+
+        async fn set_system_health(&self, _health: SystemHealth) -> f64 {
+            1.0
+        }
+
+        async fn cancel_request(&self, _correlation_id: &str) -> bool {
+            false
+        }
+
+        async fn update_config(&self, _config: ClientConfig) -> Result<(), ClientError> {
+            Ok(())
+        }
+
+        async fn pause(&self, _drain: bool) -> Result<(), ClientError> {
+            Ok(())
+        }
+
+        async fn resume(&self) -> Result<(), ClientError> {
+            Ok(())
+        }
+
+        async fn reset_circuit_breakers(&self) -> usize {
+            0
+        }
+    }
+
+    fn request(hotel_ids: &[&str], guests: u32) -> SearchRequest {
+        SearchRequest {
+            hotel_ids: hotel_ids.iter().map(|id| id.to_string()).collect(),
+            check_in: "2025-06-01".to_string(),
+            check_out: "2025-06-05".to_string(),
+            guests,
+            markets: Vec::new(),
+            supplier: None,
+            priority: RequestPriority::Medium,
+            idempotency_key: None,
+            context: RequestContext {
+                correlation_id: "test_cached_search".to_string(),
+                ..Default::default()
+            },
+        }
+    }
+
+    fn cached_client(fail: bool) -> CachedSearchClient<RecordingClient, ExampleCache> {
+        CachedSearchClient::new(
+            RecordingClient {
+                fail,
+                ..Default::default()
+            },
+            ExampleCache::new(CacheConfig::default()),
+        )
+    }
+
+    fn hotel_ids(response: &SearchResponse) -> Vec<&str> {
+        response
+            .results
+            .iter()
+            .map(|result| result.hotel_id.as_str())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_only_misses_are_requested() {
+        let client = cached_client(false);
+
+        let first = client
+            .search(request(&["hotel1", "hotel2"], 2))
+            .await
+            .unwrap();
+        assert_eq!(first.search_id, "search-1");
+        assert_eq!(hotel_ids(&first), vec!["hotel1", "hotel2"]);
+
+        let second = client
+            .search(request(&["hotel3", "hotel2", "hotel1"], 2))
+            .await
+            .unwrap();
+        assert_eq!(hotel_ids(&second), vec!["hotel3", "hotel2", "hotel1"]);
+        assert_eq!(second.results[1].price, Some(100.0));
+        // The sold out hotel came from a negative entry
+        assert!(!second.results[2].available);
+        // The cached hotel is booked with the search that found it
+        assert_eq!(second.search_id, "search-2");
+        assert_eq!(second.search_id_for("hotel3"), Some("search-2"));
+        assert_eq!(second.search_id_for("hotel2"), Some("search-1"));
+
+        // Fully cached, the API isn't called at all
+        let third = client
+            .search(request(&["hotel3", "hotel2", "hotel1"], 2))
+            .await
+            .unwrap();
+        assert_eq!(third.search_id, "search-1");
+        assert_eq!(third.search_id_for("hotel2"), Some("search-1"));
+        assert_eq!(third.rate_limit_remaining, None);
+
+        assert_eq!(
+            *client.client().searches.lock().unwrap(),
+            vec![
+                vec!["hotel1".to_string(), "hotel2".to_string()],
+                vec!["hotel3".to_string()],
+            ]
+        );
+        assert_eq!(client.cache().stats().negative_hit_count, 3);
+    }
+
+    #[tokio::test]
+    async fn test_party_size_is_part_of_the_key() {
+        let client = cached_client(false);
+        client.search(request(&["hotel2"], 2)).await.unwrap();
+        client.search(request(&["hotel2"], 3)).await.unwrap();
+        assert_eq!(client.client().searches.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_markets_are_part_of_the_key() {
+        let client = cached_client(false);
+        let for_markets = |markets: &[&str]| SearchRequest {
+            markets: markets.iter().map(|market| market.to_string()).collect(),
+            supplier: None,
+            ..request(&["hotel2"], 2)
+        };
+        client.search(for_markets(&["US", "CA"])).await.unwrap();
+        client.search(for_markets(&["CA", "US"])).await.unwrap();
+        client.search(for_markets(&["ES"])).await.unwrap();
+        assert_eq!(client.client().searches.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_supplier_is_part_of_the_key() {
+        let client = cached_client(false);
+        let for_supplier = |supplier: Option<&str>| SearchRequest {
+            supplier: supplier.map(str::to_string),
+            ..request(&["hotel2"], 2)
+        };
+        client.search(for_supplier(Some("alpha"))).await.unwrap();
+        client.search(for_supplier(Some("beta"))).await.unwrap();
+        client.search(for_supplier(None)).await.unwrap();
+        let cached = client.search(for_supplier(Some("beta"))).await.unwrap();
+        assert_eq!(cached.search_id, "search-2");
+        assert_eq!(client.client().searches.lock().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_results_cached_without_search_id_are_searched_again() {
+        let client = cached_client(false);
+        let result = SearchResult {
+            hotel_id: "hotel2".to_string(),
+            available: true,
+            price: Some(90.0),
+            currency: Some("EUR".to_string()),
+            search_id: None,
+        };
+        client.cache().store_key(
+            CachedSearchClient::<RecordingClient, ExampleCache>::cache_key(
+                &request(&[], 2),
+                "hotel2",
+            ),
+            Codec::default().encode(&result).unwrap(),
+            None,
+        );
+
+        let response = client.search(request(&["hotel2"], 2)).await.unwrap();
+        assert_eq!(response.results[0].price, Some(100.0));
+        assert_eq!(response.search_id_for("hotel2"), Some("search-1"));
+    }
+
+    #[tokio::test]
+    async fn test_request_from_search_params() {
+        let processor = crate::part2_xml::HotelSearchProcessor::new();
+        let params = processor
+            .search_params(&processor.load_sample_request().unwrap())
+            .unwrap();
+        let before = std::time::SystemTime::now();
+        let request = SearchRequest::from_search_params(&params, RequestPriority::High);
+        assert_eq!(request.hotel_ids, params.hotel_codes);
+        assert_eq!(
+            (request.check_in.as_str(), request.check_out.as_str()),
+            ("2025-06-11", "2025-06-12")
+        );
+        assert_eq!(request.guests, 1);
+        assert_eq!(request.markets, vec!["US"]);
+        let deadline = request.context.request_deadline.unwrap();
+        assert!(deadline >= before + Duration::from_millis(25_000));
+        assert!(deadline <= std::time::SystemTime::now() + Duration::from_millis(25_000));
+    }
+
+    #[tokio::test]
+    async fn test_remote_errors_are_not_cached() {
+        let client = cached_client(true);
+        assert!(matches!(
+            client.search(request(&["hotel2"], 2)).await,
+            Err(ApiError::NetworkError(_))
+        ));
+        assert_eq!(client.cache().stats().items_count, 0);
+    }
+}
@@ -0,0 +1,141 @@
+// Request cancellation
+// Every request with a correlation ID is registered while queued or in flight, under a
+// `CancellationToken` shared by all requests with that ID. Cancelling the ID cancels the token:
+// the client drops queued requests from the scheduler at once and abandons in-flight ones,
+// which fail with `RequestCancelled`. A request registers when it starts and is removed when it
+// ends, whatever the outcome; requests without a correlation ID can't be cancelled.
+
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio_util::sync::CancellationToken;
+
+#[derive(Default)]
+pub struct CancellationRegistry {
+    requests: Mutex<HashMap<String, Entry>>,
+    cancelled: AtomicUsize,
+}
+
+struct Entry {
+    token: CancellationToken,
+    // Requests registered under the ID
+    count: usize,
+}
+
+// A registered request; removed from the registry when dropped
+pub struct Registration<'a> {
+    registry: &'a CancellationRegistry,
+    correlation_id: Option<String>,
+    token: CancellationToken,
+}
+
+impl CancellationRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(&self, correlation_id: &str) -> Registration<'_> {
+        if correlation_id.is_empty() {
+            return Registration {
+                registry: self,
+                correlation_id: None,
+                token: CancellationToken::new(),
+            };
+        }
+        let mut requests = self.requests.lock();
+        let entry = requests
+            .entry(correlation_id.to_string())
+            .or_insert_with(|| Entry {
+                token: CancellationToken::new(),
+                count: 0,
+            });
+        entry.count += 1;
+        Registration {
+            registry: self,
+            correlation_id: Some(correlation_id.to_string()),
+            token: entry.token.clone(),
+        }
+    }
+
+    // Cancel the requests registered under `correlation_id`; returns how many there were
+    pub fn cancel(&self, correlation_id: &str) -> usize {
+        let Some(entry) = self.requests.lock().remove(correlation_id) else {
+            return 0;
+        };
+        entry.token.cancel();
+        self.cancelled.fetch_add(entry.count, Ordering::SeqCst);
+        entry.count
+    }
+
+    // Requests queued or in flight
+    pub fn len(&self) -> usize {
+        self.requests.lock().values().map(|entry| entry.count).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.requests.lock().is_empty()
+    }
+
+    pub fn cancelled_count(&self) -> usize {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+impl Registration<'_> {
+    pub fn token(&self) -> &CancellationToken {
+        &self.token
+    }
+}
+
+impl Drop for Registration<'_> {
+    fn drop(&mut self) {
+        let Some(correlation_id) = &self.correlation_id else {
+            return;
+        };
+        // Already removed if cancelled
+        if self.token.is_cancelled() {
+            return;
+        }
+        let mut requests = self.registry.requests.lock();
+        if let Some(entry) = requests.get_mut(correlation_id) {
+            entry.count -= 1;
+            if entry.count == 0 {
+                requests.remove(correlation_id);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cancel_by_correlation_id() {
+        let registry = CancellationRegistry::new();
+        let first = registry.register("corr-1");
+        let second = registry.register("corr-1");
+        let other = registry.register("corr-2");
+        let anonymous = registry.register("");
+        assert_eq!(registry.len(), 3);
+
+        assert_eq!(registry.cancel("corr-1"), 2);
+        assert!(first.token().is_cancelled() && second.token().is_cancelled());
+        assert!(!other.token().is_cancelled());
+        assert_eq!(registry.cancel("corr-1"), 0);
+        assert_eq!(registry.cancel(""), 0);
+        assert!(!anonymous.token().is_cancelled());
+        assert_eq!(registry.cancelled_count(), 2);
+
+        // Reusing the ID registers a new request
+        drop(first);
+        let third = registry.register("corr-1");
+        drop(second);
+        assert!(!third.token().is_cancelled());
+        assert_eq!(registry.len(), 2);
+
+        drop(third);
+        drop(other);
+        assert!(registry.is_empty());
+    }
+}
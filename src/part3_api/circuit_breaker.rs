@@ -0,0 +1,407 @@
+// Circuit breaking
+// A breaker stops sending requests to an upstream that keeps failing, so callers fail fast
+// instead of waiting for timeouts and the upstream gets room to recover:
+// - Closed: requests go through. `failure_threshold` failures in a row, or a share of failures
+//   of at least `failure_rate_threshold` among the last `failure_rate_window` requests, open it.
+// - Open: requests fail at once with `CircuitBreakerOpen`, telling how long until it half-opens.
+//   After `reset_timeout_ms` it half-opens.
+// - Half-open: up to `half_open_max_requests` probes go through at a time, others fail fast.
+//   `success_threshold` successful probes close it, a failed one opens it again.
+// Only failures of the upstream count (network errors, timeouts, 5xx responses); rate limiting,
+// preemption and client errors don't say anything about its health. Results of requests let
+// through before the last change of state are ignored. Time is `tokio::time::Instant`.
+
+use super::{ApiError, CircuitBreakerConfig};
+use parking_lot::Mutex;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use tokio::time::Instant;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+pub struct CircuitBreaker {
+    service_name: String,
+    state: Mutex<BreakerState>,
+    // Requests failed fast
+    rejected: AtomicUsize,
+}
+
+struct BreakerState {
+    config: CircuitBreakerConfig,
+    circuit: Circuit,
+    // Bumped on every change of state
+    generation: u64,
+}
+
+enum Circuit {
+    Closed {
+        consecutive_failures: u32,
+        // Outcomes of the last requests, true for failures
+        recent: VecDeque<bool>,
+    },
+    Open {
+        until: Instant,
+    },
+    HalfOpen {
+        probes: u32,
+        successes: u32,
+    },
+}
+
+// A request let through; report how it went with `record`. Dropped without a result (the
+// request was never sent), it frees its probe slot.
+pub struct CircuitPermit<'a> {
+    breaker: &'a CircuitBreaker,
+    generation: u64,
+    probe: bool,
+    recorded: bool,
+}
+
+impl CircuitBreaker {
+    pub fn new(service_name: impl Into<String>, config: CircuitBreakerConfig) -> Self {
+        Self {
+            service_name: service_name.into(),
+            state: Mutex::new(BreakerState {
+                config,
+                circuit: Circuit::closed(),
+                generation: 0,
+            }),
+            rejected: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn service_name(&self) -> &str {
+        &self.service_name
+    }
+
+    // Let a request through, or fail it fast
+    pub fn try_acquire(&self) -> Result<CircuitPermit<'_>, ApiError> {
+        let now = Instant::now();
+        let mut state = self.state.lock();
+        if let Circuit::Open { until } = state.circuit {
+            if now < until {
+                return Err(self.reject(Some(until - now)));
+            }
+            state.transition(Circuit::HalfOpen {
+                probes: 0,
+                successes: 0,
+            });
+        }
+        let max_probes = state.config.half_open_max_requests;
+        let probe = match &mut state.circuit {
+            Circuit::HalfOpen { probes, .. } if *probes >= max_probes => {
+                return Err(self.reject(None));
+            }
+            Circuit::HalfOpen { probes, .. } => {
+                *probes += 1;
+                true
+            }
+            _ => false,
+        };
+        Ok(CircuitPermit {
+            breaker: self,
+            generation: state.generation,
+            probe,
+            recorded: false,
+        })
+    }
+
+    pub fn state(&self) -> CircuitState {
+        let state = self.state.lock();
+        match state.circuit {
+            // Half-opens on the next request
+            Circuit::Open { .. } => CircuitState::Open,
+            Circuit::HalfOpen { .. } => CircuitState::HalfOpen,
+            Circuit::Closed { .. } => CircuitState::Closed,
+        }
+    }
+
+    // Close the breaker whatever its state; returns whether it wasn't closed
+    pub fn reset(&self) -> bool {
+        let mut state = self.state.lock();
+        let was_closed = matches!(state.circuit, Circuit::Closed { .. });
+        state.transition(Circuit::closed());
+        !was_closed
+    }
+
+    // Applies from the next change of state
+    pub fn set_config(&self, config: CircuitBreakerConfig) {
+        self.state.lock().config = config;
+    }
+
+    // Requests failed fast
+    pub fn rejected_count(&self) -> usize {
+        self.rejected.load(Ordering::SeqCst)
+    }
+
+    fn reject(&self, retry_after: Option<Duration>) -> ApiError {
+        self.rejected.fetch_add(1, Ordering::SeqCst);
+        ApiError::CircuitBreakerOpen {
+            service_name: self.service_name.clone(),
+            retry_after_ms: retry_after.map(|wait| wait.as_millis() as u64),
+        }
+    }
+
+    fn record(&self, generation: u64, probe: bool, failed: bool) {
+        let mut state = self.state.lock();
+        if state.generation != generation {
+            return;
+        }
+        let config = state.config.clone();
+        let next = match &mut state.circuit {
+            Circuit::Closed {
+                consecutive_failures,
+                recent,
+            } => {
+                *consecutive_failures = if failed { *consecutive_failures + 1 } else { 0 };
+                recent.push_back(failed);
+                while recent.len() > config.failure_rate_window as usize {
+                    recent.pop_front();
+                }
+                let failures = recent.iter().filter(|failed| **failed).count();
+                let rate_exceeded = config.failure_rate_threshold.is_some_and(|threshold| {
+                    config.failure_rate_window > 0
+                        && recent.len() == config.failure_rate_window as usize
+                        && failures as f64 >= threshold * recent.len() as f64
+                });
+                (*consecutive_failures >= config.failure_threshold || rate_exceeded)
+                    .then(|| Circuit::open(&config))
+            }
+            Circuit::HalfOpen { probes, successes } if probe => {
+                *probes = probes.saturating_sub(1);
+                if failed {
+                    Some(Circuit::open(&config))
+                } else {
+                    *successes += 1;
+                    (*successes >= config.success_threshold).then(Circuit::closed)
+                }
+            }
+            _ => None,
+        };
+        if let Some(next) = next {
+            match next {
+                Circuit::Open { .. } => {
+                    tracing::warn!(service = %self.service_name, "Circuit breaker opened")
+                }
+                _ => tracing::info!(service = %self.service_name, "Circuit breaker closed"),
+            }
+            state.transition(next);
+        }
+    }
+
+    fn release_probe(&self, generation: u64) {
+        let mut state = self.state.lock();
+        if state.generation != generation {
+            return;
+        }
+        if let Circuit::HalfOpen { probes, .. } = &mut state.circuit {
+            *probes = probes.saturating_sub(1);
+        }
+    }
+}
+
+impl BreakerState {
+    fn transition(&mut self, circuit: Circuit) {
+        self.circuit = circuit;
+        self.generation += 1;
+    }
+}
+
+impl Circuit {
+    fn closed() -> Self {
+        Circuit::Closed {
+            consecutive_failures: 0,
+            recent: VecDeque::new(),
+        }
+    }
+
+    fn open(config: &CircuitBreakerConfig) -> Self {
+        Circuit::Open {
+            until: Instant::now() + Duration::from_millis(config.reset_timeout_ms),
+        }
+    }
+}
+
+impl CircuitPermit<'_> {
+    // Report the outcome of the request
+    pub fn record<T>(mut self, result: &Result<T, ApiError>) {
+        self.recorded = true;
+        let failed = result.as_ref().err().is_some_and(is_upstream_failure);
+        self.breaker.record(self.generation, self.probe, failed);
+    }
+}
+
+impl Drop for CircuitPermit<'_> {
+    fn drop(&mut self) {
+        if self.probe && !self.recorded {
+            self.breaker.release_probe(self.generation);
+        }
+    }
+}
+
+// Whether `error` says the upstream is unhealthy
+pub fn is_upstream_failure(error: &ApiError) -> bool {
+    match error {
+        ApiError::NetworkError(_) | ApiError::Timeout(_) => true,
+        ApiError::ApiResponseError { status_code, .. } => *status_code >= 500,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> CircuitBreakerConfig {
+        CircuitBreakerConfig {
+            failure_threshold: 3,
+            success_threshold: 2,
+            reset_timeout_ms: 1000,
+            half_open_max_requests: 1,
+            ..Default::default()
+        }
+    }
+
+    fn outcome(failed: bool) -> Result<(), ApiError> {
+        match failed {
+            true => Err(ApiError::NetworkError("connection reset".to_string())),
+            false => Ok(()),
+        }
+    }
+
+    fn send(breaker: &CircuitBreaker, failed: bool) -> Result<(), ApiError> {
+        breaker.try_acquire()?.record(&outcome(failed));
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_opens_and_closes() {
+        let breaker = CircuitBreaker::new("search", config());
+        send(&breaker, true).unwrap();
+        send(&breaker, true).unwrap();
+        // Not in a row
+        send(&breaker, false).unwrap();
+        for _ in 0..3 {
+            send(&breaker, true).unwrap();
+        }
+        assert_eq!(breaker.state(), CircuitState::Open);
+
+        match send(&breaker, false) {
+            Err(ApiError::CircuitBreakerOpen {
+                service_name,
+                retry_after_ms,
+            }) => {
+                assert_eq!(service_name, "search");
+                assert_eq!(retry_after_ms, Some(1000));
+            }
+            other => panic!("expected the circuit to be open, got {:?}", other),
+        }
+        tokio::time::advance(Duration::from_millis(400)).await;
+        assert!(matches!(
+            send(&breaker, false),
+            Err(ApiError::CircuitBreakerOpen {
+                retry_after_ms: Some(600),
+                ..
+            })
+        ));
+        assert_eq!(breaker.rejected_count(), 2);
+
+        // One probe at a time, two successes close it
+        tokio::time::advance(Duration::from_millis(600)).await;
+        let probe = breaker.try_acquire().unwrap();
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+        assert!(breaker.try_acquire().is_err());
+        probe.record(&outcome(false));
+        send(&breaker, false).unwrap();
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_failed_probe_reopens() {
+        let breaker = CircuitBreaker::new("booking", config());
+        for _ in 0..3 {
+            send(&breaker, true).unwrap();
+        }
+        tokio::time::advance(Duration::from_millis(1000)).await;
+
+        // A probe that was never sent frees its slot
+        drop(breaker.try_acquire().unwrap());
+        send(&breaker, false).unwrap();
+        send(&breaker, true).unwrap();
+        assert_eq!(breaker.state(), CircuitState::Open);
+        assert!(matches!(
+            send(&breaker, false),
+            Err(ApiError::CircuitBreakerOpen {
+                retry_after_ms: Some(1000),
+                ..
+            })
+        ));
+
+        assert!(breaker.reset());
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        assert!(!breaker.reset());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_failure_rate() {
+        let breaker = CircuitBreaker::new(
+            "search",
+            CircuitBreakerConfig {
+                failure_rate_threshold: Some(0.5),
+                failure_rate_window: 4,
+                ..config()
+            },
+        );
+        // Never three in a row, but half of the last four
+        for failed in [true, false, true] {
+            send(&breaker, failed).unwrap();
+        }
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        send(&breaker, false).unwrap();
+        assert_eq!(breaker.state(), CircuitState::Open);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_only_upstream_failures_count() {
+        let breaker = CircuitBreaker::new("search", config());
+        // Results of requests let through before it opened are ignored
+        let late = breaker.try_acquire().unwrap();
+        for error in [
+            ApiError::RateLimitExceeded {
+                message: "slow down".to_string(),
+                retry_after_ms: None,
+            },
+            ApiError::RequestPreempted,
+            ApiError::ApiResponseError {
+                status_code: 404,
+                message: "Not Found".to_string(),
+                is_retryable: false,
+            },
+        ] {
+            breaker.try_acquire().unwrap().record(&Err::<(), _>(error));
+        }
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        for _ in 0..3 {
+            breaker
+                .try_acquire()
+                .unwrap()
+                .record(&Err::<(), _>(ApiError::ApiResponseError {
+                    status_code: 503,
+                    message: "Service Unavailable".to_string(),
+                    is_retryable: true,
+                }));
+        }
+        assert_eq!(breaker.state(), CircuitState::Open);
+        tokio::time::advance(Duration::from_millis(1000)).await;
+        let probe = breaker.try_acquire().unwrap();
+        late.record(&outcome(true));
+        probe.record(&outcome(false));
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+    }
+}
@@ -0,0 +1,178 @@
+// Upstream health
+// The client keeps the upstream's `SystemHealth`, which scales the request rate (see
+// `SystemHealth::rate_multiplier`). It is set by hand with `set_system_health` or by the health
+// checker, which probes the upstream every `health_check_interval_ms` with
+// `health_check_probe`: a GET of a path under `base_url`, or a search of the caller's choosing.
+// Probes go straight to the transport, past the breakers and the rate limiter, so they still
+// see the upstream while the client holds back, within `timeout_ms`.
+// A probe answered in under half of `timeout_ms` is healthy, a slower one or rate limiting
+// degraded. A failed probe is degraded too, and unhealthy from the second in a row, so one lost
+// probe doesn't cut the rate to a fifth.
+// Every change of health is broadcast to subscribers.
+
+use super::{ApiError, SearchRequest, SystemHealth};
+use parking_lot::Mutex;
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+// Failed probes in a row after which the upstream is unhealthy
+const UNHEALTHY_AFTER: u32 = 2;
+// Transitions kept for subscribers that fall behind
+const CAPACITY: usize = 16;
+
+// How the health checker probes the upstream
+#[derive(Debug, Clone)]
+pub enum HealthProbe {
+    // GET this path under `base_url`; healthy on a success status
+    Path(String),
+    // Send this search; healthy if it succeeds
+    Search(Box<SearchRequest>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HealthTransition {
+    pub from: SystemHealth,
+    pub to: SystemHealth,
+}
+
+pub struct HealthMonitor {
+    state: Mutex<State>,
+    transitions: broadcast::Sender<HealthTransition>,
+}
+
+struct State {
+    health: SystemHealth,
+    // Failed probes in a row
+    failures: u32,
+}
+
+impl Default for HealthMonitor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HealthMonitor {
+    pub fn new() -> Self {
+        Self {
+            state: Mutex::new(State {
+                health: SystemHealth::Healthy,
+                failures: 0,
+            }),
+            transitions: broadcast::channel(CAPACITY).0,
+        }
+    }
+
+    pub fn health(&self) -> SystemHealth {
+        self.state.lock().health
+    }
+
+    // Returns whether it changed, telling subscribers if so
+    pub fn set(&self, health: SystemHealth) -> bool {
+        let mut state = self.state.lock();
+        let from = std::mem::replace(&mut state.health, health);
+        if from == health {
+            return false;
+        }
+        // No subscribers is fine
+        let _ = self.transitions.send(HealthTransition { from, to: health });
+        true
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<HealthTransition> {
+        self.transitions.subscribe()
+    }
+
+    // The health a probe's outcome (how long it took, or why it failed) shows, given the probe
+    // timeout
+    pub fn classify(
+        &self,
+        outcome: &Result<Duration, ApiError>,
+        timeout: Duration,
+    ) -> SystemHealth {
+        let mut state = self.state.lock();
+        match outcome {
+            Ok(elapsed) => {
+                state.failures = 0;
+                match *elapsed < timeout / 2 {
+                    true => SystemHealth::Healthy,
+                    false => SystemHealth::Degraded,
+                }
+            }
+            // Answering, if not to everyone
+            Err(ApiError::RateLimitExceeded { .. }) => {
+                state.failures = 0;
+                SystemHealth::Degraded
+            }
+            Err(_) => {
+                state.failures += 1;
+                match state.failures >= UNHEALTHY_AFTER {
+                    true => SystemHealth::Unhealthy,
+                    false => SystemHealth::Degraded,
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        let monitor = HealthMonitor::new();
+        let timeout = Duration::from_millis(1000);
+        let fast = Ok(Duration::from_millis(100));
+        let failed = Err(ApiError::NetworkError("connection refused".to_string()));
+        assert_eq!(monitor.classify(&fast, timeout), SystemHealth::Healthy);
+        assert_eq!(
+            monitor.classify(&Ok(Duration::from_millis(600)), timeout),
+            SystemHealth::Degraded
+        );
+        assert_eq!(
+            monitor.classify(
+                &Err(ApiError::RateLimitExceeded {
+                    message: "slow down".to_string(),
+                    retry_after_ms: None,
+                }),
+                timeout
+            ),
+            SystemHealth::Degraded
+        );
+
+        // Unhealthy from the second failure in a row
+        assert_eq!(monitor.classify(&failed, timeout), SystemHealth::Degraded);
+        assert_eq!(monitor.classify(&failed, timeout), SystemHealth::Unhealthy);
+        assert_eq!(monitor.classify(&failed, timeout), SystemHealth::Unhealthy);
+        assert_eq!(monitor.classify(&fast, timeout), SystemHealth::Healthy);
+        assert_eq!(monitor.classify(&failed, timeout), SystemHealth::Degraded);
+    }
+
+    #[test]
+    fn test_transitions() {
+        let monitor = HealthMonitor::new();
+        assert!(!monitor.set(SystemHealth::Healthy));
+        let mut transitions = monitor.subscribe();
+        assert!(monitor.set(SystemHealth::Degraded));
+        assert!(!monitor.set(SystemHealth::Degraded));
+        assert!(monitor.set(SystemHealth::Unhealthy));
+        assert_eq!(monitor.health(), SystemHealth::Unhealthy);
+
+        assert_eq!(
+            transitions.try_recv().unwrap(),
+            HealthTransition {
+                from: SystemHealth::Healthy,
+                to: SystemHealth::Degraded,
+            }
+        );
+        assert_eq!(
+            transitions.try_recv().unwrap(),
+            HealthTransition {
+                from: SystemHealth::Degraded,
+                to: SystemHealth::Unhealthy,
+            }
+        );
+        assert!(transitions.try_recv().is_err());
+    }
+}
@@ -0,0 +1,190 @@
+// Idempotent bookings
+// A booking retried with the same `idempotency_key` mustn't book twice. While a booking is in
+// flight, others with its key wait for it and get its outcome (see `single_flight`); once it
+// has succeeded, its `BookingResponse` is kept for `ttl` and replayed for the key without
+// calling the upstream again. Failures aren't kept: the next attempt with the key is sent.
+// Every booking answered without its own upstream call counts as deduplicated. Bookings
+// without a key are never deduplicated.
+// Cancellations and modifications of a booking are kept the same way, each in a layer of its
+// own response type.
+
+use super::single_flight::SingleFlight;
+use super::{ApiError, BookingResponse};
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use tokio::time::Instant;
+
+// How long a booking's response is replayed for
+pub const DEFAULT_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+pub struct IdempotencyLayer<T = BookingResponse> {
+    ttl: Duration,
+    // Outcomes of bookings in flight, and whether they were replayed
+    in_flight: SingleFlight<String, (Result<T, ApiError>, bool)>,
+    // Responses of bookings that succeeded, with when they expire
+    completed: Mutex<HashMap<String, (T, Instant)>>,
+    deduplicated: AtomicUsize,
+}
+
+impl<T: Clone> Default for IdempotencyLayer<T> {
+    fn default() -> Self {
+        Self::new(DEFAULT_TTL)
+    }
+}
+
+impl<T: Clone> IdempotencyLayer<T> {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            in_flight: SingleFlight::new(),
+            completed: Mutex::new(HashMap::new()),
+            deduplicated: AtomicUsize::new(0),
+        }
+    }
+
+    // Book with `book`, unless a booking with `key` is in flight or succeeded within the TTL
+    pub async fn run<F, Fut>(&self, key: &str, book: F) -> Result<T, ApiError>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, ApiError>>,
+    {
+        if key.is_empty() {
+            return book().await;
+        }
+        if let Some(response) = self.completed(key) {
+            self.deduplicated.fetch_add(1, Ordering::SeqCst);
+            return Ok(response);
+        }
+        let ((result, replayed), shared) = self
+            .in_flight
+            .run(key.to_string(), || async {
+                // Completed while this one was on its way
+                if let Some(response) = self.completed(key) {
+                    return (Ok(response), true);
+                }
+                let result = book().await;
+                if let Ok(response) = &result {
+                    self.complete(key, response.clone());
+                }
+                (result, false)
+            })
+            .await;
+        if replayed || shared {
+            self.deduplicated.fetch_add(1, Ordering::SeqCst);
+        }
+        result
+    }
+
+    pub fn deduplicated_count(&self) -> usize {
+        self.deduplicated.load(Ordering::SeqCst)
+    }
+
+    // Responses kept
+    pub fn len(&self) -> usize {
+        self.completed.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.completed.lock().is_empty()
+    }
+
+    fn completed(&self, key: &str) -> Option<T> {
+        let now = Instant::now();
+        let mut completed = self.completed.lock();
+        match completed.get(key) {
+            Some((response, expires)) if *expires > now => Some(response.clone()),
+            Some(_) => {
+                completed.remove(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn complete(&self, key: &str, response: T) {
+        let now = Instant::now();
+        let mut completed = self.completed.lock();
+        completed.retain(|_, (_, expires)| *expires > now);
+        completed.insert(key.to_string(), (response, now + self.ttl));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::part3_api::BookingStatus;
+
+    fn response(booking_id: &str) -> BookingResponse {
+        BookingResponse {
+            booking_id: booking_id.to_string(),
+            status: BookingStatus::Confirmed,
+            confirmation_code: None,
+            rate_limit_remaining: None,
+            processing_time_ms: 0,
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_duplicates_are_deduplicated() {
+        let layer = IdempotencyLayer::new(Duration::from_secs(60));
+        let bookings = AtomicUsize::new(0);
+        let book = |booking_id: &'static str| {
+            let bookings = &bookings;
+            move || async move {
+                bookings.fetch_add(1, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                Ok(response(booking_id))
+            }
+        };
+
+        // In flight together
+        let results = futures::future::join_all([
+            layer.run("key-1", book("BK1")),
+            layer.run("key-1", book("BK2")),
+        ])
+        .await;
+        let ids: Vec<_> = results
+            .into_iter()
+            .map(|result| result.unwrap().booking_id)
+            .collect();
+        assert_eq!(ids, ["BK1", "BK1"]);
+        assert_eq!(bookings.load(Ordering::SeqCst), 1);
+
+        // Replayed within the TTL, sent again past it
+        tokio::time::advance(Duration::from_secs(30)).await;
+        assert_eq!(
+            layer.run("key-1", book("BK3")).await.unwrap().booking_id,
+            "BK1"
+        );
+        assert_eq!(layer.deduplicated_count(), 2);
+        tokio::time::advance(Duration::from_secs(60)).await;
+        assert_eq!(
+            layer.run("key-1", book("BK4")).await.unwrap().booking_id,
+            "BK4"
+        );
+
+        // Without a key
+        layer.run("", book("BK5")).await.unwrap();
+        layer.run("", book("BK6")).await.unwrap();
+        assert_eq!(bookings.load(Ordering::SeqCst), 4);
+        assert_eq!(layer.deduplicated_count(), 2);
+        assert_eq!(layer.len(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_failures_are_not_kept() {
+        let layer: IdempotencyLayer = IdempotencyLayer::default();
+        let failed = layer
+            .run("key-1", || async { Err(ApiError::Timeout(5000)) })
+            .await;
+        assert!(matches!(failed, Err(ApiError::Timeout(5000))));
+        assert!(layer.is_empty());
+
+        let booked = layer.run("key-1", || async { Ok(response("BK1")) }).await;
+        assert_eq!(booked.unwrap().booking_id, "BK1");
+        assert_eq!(layer.deduplicated_count(), 0);
+    }
+}
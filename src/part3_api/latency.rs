@@ -0,0 +1,233 @@
+// Response times
+// Every request's response time, from the call until the caller gets its result (queueing,
+// retries and backoffs included, whatever the outcome), is recorded in a histogram of its
+// operation: searches, bookings, cancellations, modifications and status reads apart. The
+// histograms are the cache's HDR-style ones (see `part1_cache::histogram`): percentiles within
+// ~3% whatever the spread of the values.
+// Besides the totals, recent response times are kept in slots of `SLOT` for the last
+// `RETENTION`, so a dashboard can ask for the last minute or five (`stats_window`); a window is
+// rounded up to whole slots.
+
+use crate::part1_cache::{LatencyHistogram, LatencySummary};
+use parking_lot::Mutex;
+use std::collections::VecDeque;
+use std::time::Duration;
+use tokio::time::Instant;
+
+const SLOT: Duration = Duration::from_secs(10);
+const RETENTION: Duration = Duration::from_secs(15 * 60);
+const OPERATIONS: usize = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    Search = 0,
+    Booking = 1,
+    Cancellation = 2,
+    Modification = 3,
+    // Reads of a booking's status
+    Status = 4,
+}
+
+// Response times of one operation, or all, in milliseconds
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct LatencyStats {
+    pub count: u64,
+    pub average_ms: f64,
+    pub p95_ms: f64,
+    pub p99_ms: f64,
+    pub max_ms: f64,
+}
+
+pub struct LatencyRecorder {
+    // Indexed by operation
+    total: [LatencyHistogram; OPERATIONS],
+    recent: Mutex<VecDeque<Slot>>,
+    // Slots are counted from here
+    origin: Instant,
+}
+
+struct Slot {
+    index: u64,
+    histograms: [LatencyHistogram; OPERATIONS],
+}
+
+impl Operation {
+    pub const ALL: [Operation; OPERATIONS] = [
+        Operation::Search,
+        Operation::Booking,
+        Operation::Cancellation,
+        Operation::Modification,
+        Operation::Status,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Operation::Search => "search",
+            Operation::Booking => "booking",
+            Operation::Cancellation => "cancellation",
+            Operation::Modification => "modification",
+            Operation::Status => "status",
+        }
+    }
+}
+
+impl From<LatencySummary> for LatencyStats {
+    fn from(summary: LatencySummary) -> Self {
+        let millis = |ns: u64| ns as f64 / 1_000_000.0;
+        Self {
+            count: summary.count,
+            average_ms: millis(summary.mean_ns),
+            p95_ms: millis(summary.p95_ns),
+            p99_ms: millis(summary.p99_ns),
+            max_ms: millis(summary.max_ns),
+        }
+    }
+}
+
+impl Default for LatencyRecorder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LatencyRecorder {
+    pub fn new() -> Self {
+        Self {
+            total: Default::default(),
+            recent: Mutex::new(VecDeque::new()),
+            origin: Instant::now(),
+        }
+    }
+
+    pub fn record(&self, operation: Operation, response_time: Duration) {
+        let value_ns = response_time.as_nanos() as u64;
+        self.total[operation as usize].record(value_ns);
+
+        let index = self.slot_index(Instant::now());
+        let mut recent = self.recent.lock();
+        if recent.back().is_none_or(|slot| slot.index != index) {
+            recent.push_back(Slot {
+                index,
+                histograms: Default::default(),
+            });
+        }
+        let kept = slots(RETENTION);
+        while recent
+            .front()
+            .is_some_and(|slot| slot.index + kept <= index)
+        {
+            recent.pop_front();
+        }
+        if let Some(slot) = recent.back() {
+            slot.histograms[operation as usize].record(value_ns);
+        }
+    }
+
+    // Since the start, `operation` or all of them
+    pub fn stats(&self, operation: Option<Operation>) -> LatencyStats {
+        let histogram = LatencyHistogram::default();
+        for operation in selected(operation) {
+            histogram.merge(&self.total[operation as usize]);
+        }
+        histogram.summary().into()
+    }
+
+    // Every response time of `operation` since the start
+    pub fn histogram(&self, operation: Operation) -> &LatencyHistogram {
+        &self.total[operation as usize]
+    }
+
+    // Within the last `window`, up to `RETENTION`
+    pub fn stats_window(&self, operation: Option<Operation>, window: Duration) -> LatencyStats {
+        let index = self.slot_index(Instant::now());
+        let first = (index + 1).saturating_sub(slots(window.min(RETENTION)));
+        let histogram = LatencyHistogram::default();
+        for slot in self.recent.lock().iter().filter(|slot| slot.index >= first) {
+            for operation in selected(operation) {
+                histogram.merge(&slot.histograms[operation as usize]);
+            }
+        }
+        histogram.summary().into()
+    }
+
+    fn slot_index(&self, now: Instant) -> u64 {
+        (now.duration_since(self.origin).as_nanos() / SLOT.as_nanos()) as u64
+    }
+}
+
+// Slots covering `window`
+fn slots(window: Duration) -> u64 {
+    window.as_nanos().div_ceil(SLOT.as_nanos()) as u64
+}
+
+fn selected(operation: Option<Operation>) -> impl Iterator<Item = Operation> {
+    Operation::ALL
+        .into_iter()
+        .filter(move |candidate| operation.is_none_or(|operation| operation == *candidate))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Within the histogram's resolution
+    fn assert_close(reported: f64, expected: f64) {
+        assert!(
+            (reported - expected).abs() <= expected * 0.04,
+            "{} vs {}",
+            reported,
+            expected
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_per_operation() {
+        let recorder = LatencyRecorder::new();
+        for millis in 1..=100 {
+            recorder.record(Operation::Search, Duration::from_millis(millis));
+        }
+        recorder.record(Operation::Booking, Duration::from_millis(400));
+
+        let search = recorder.stats(Some(Operation::Search));
+        assert_eq!(search.count, 100);
+        assert_close(search.average_ms, 50.5);
+        assert_close(search.p95_ms, 95.0);
+        assert_close(search.p99_ms, 99.0);
+        assert_eq!(search.max_ms, 100.0);
+        let booking = recorder.stats(Some(Operation::Booking));
+        assert_eq!((booking.count, booking.max_ms), (1, 400.0));
+
+        let all = recorder.stats(None);
+        assert_eq!(all.count, 101);
+        assert_eq!(all.max_ms, 400.0);
+        assert_eq!(LatencyRecorder::new().stats(None), LatencyStats::default());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_window() {
+        let recorder = LatencyRecorder::new();
+        recorder.record(Operation::Search, Duration::from_millis(500));
+        tokio::time::advance(Duration::from_secs(90)).await;
+        recorder.record(Operation::Search, Duration::from_millis(20));
+        recorder.record(Operation::Booking, Duration::from_millis(30));
+
+        let last_minute = recorder.stats_window(None, Duration::from_secs(60));
+        assert_eq!(last_minute.count, 2);
+        assert_eq!(last_minute.max_ms, 30.0);
+        let searches = recorder.stats_window(Some(Operation::Search), Duration::from_secs(60));
+        assert_eq!((searches.count, searches.max_ms), (1, 20.0));
+        assert_eq!(
+            recorder
+                .stats_window(None, Duration::from_secs(5 * 60))
+                .count,
+            3
+        );
+
+        // Older than the retention
+        tokio::time::advance(RETENTION).await;
+        recorder.record(Operation::Search, Duration::from_millis(10));
+        assert_eq!(recorder.stats_window(None, RETENTION).count, 1);
+        assert_eq!(recorder.recent.lock().len(), 1);
+        assert_eq!(recorder.stats(None).count, 4);
+    }
+}
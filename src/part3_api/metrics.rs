@@ -0,0 +1,505 @@
+// Prometheus metrics
+// `ClientStats` and the response time histograms in the Prometheus text exposition format, for
+// a `/metrics` endpoint to serve as is: counters for what happened to requests, gauges for the
+// queues, slots, breakers, rate and health right now, and a histogram of response times per
+// operation, in seconds. The router renders every supplier's client into one exposition, told
+// apart by a `supplier` label.
+// Metric names start with `booking_api_`. The histogram buckets are cut from the client's own
+// HDR histograms (see `LatencyHistogram::cumulative_counts`), so they are exact to ~3%.
+
+use super::{
+    ApiClient, BookingApiClient, CircuitState, ClientStats, LatencyRecorder, LatencyStats,
+    Operation, RequestPriority, SupplierRouter, SystemHealth,
+};
+use std::fmt::Write;
+
+const PREFIX: &str = "booking_api_";
+// Upper bounds of the response time buckets, in seconds
+const BUCKETS: [f64; 12] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0,
+];
+
+// Metric families, each rendered once with the samples of every client
+#[derive(Default)]
+pub struct Exposition {
+    families: Vec<Family>,
+}
+
+struct Family {
+    name: &'static str,
+    kind: &'static str,
+    help: &'static str,
+    samples: Vec<String>,
+}
+
+impl Exposition {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Add a client's metrics, each sample with `labels`
+    pub fn add_client(&mut self, client: &BookingApiClient, labels: &[(&str, &str)]) {
+        self.add(&client.stats(), &client.latency, labels);
+    }
+
+    pub fn add(&mut self, stats: &ClientStats, latency: &LatencyRecorder, labels: &[(&str, &str)]) {
+        for (name, help, value) in [
+            (
+                "requests_sent_total",
+                "Requests sent upstream, retries included",
+                stats.requests_sent,
+            ),
+            (
+                "requests_succeeded_total",
+                "Requests that succeeded",
+                stats.requests_succeeded,
+            ),
+            (
+                "requests_failed_total",
+                "Requests that failed",
+                stats.requests_failed,
+            ),
+            (
+                "requests_throttled_total",
+                "Requests that waited for a rate limiter token",
+                stats.requests_throttled,
+            ),
+            (
+                "requests_retried_total",
+                "Retries made",
+                stats.requests_retried,
+            ),
+            (
+                "requests_preempted_total",
+                "Low priority requests preempted",
+                stats.requests_preempted,
+            ),
+            (
+                "requests_rejected_total",
+                "Requests refused because their queue was full",
+                stats.requests_rejected,
+            ),
+            (
+                "requests_promoted_total",
+                "Queued requests moved up a priority after waiting long",
+                stats.requests_promoted,
+            ),
+            (
+                "requests_queue_wait_exceeded_total",
+                "Requests failed after waiting the maximum queue wait",
+                stats.requests_queue_wait_exceeded,
+            ),
+            (
+                "requests_timeout_total",
+                "Requests that timed out",
+                stats.requests_timeout,
+            ),
+            (
+                "requests_expired_total",
+                "Requests dropped from the queue past their deadline",
+                stats.requests_expired,
+            ),
+            (
+                "requests_cancelled_total",
+                "Requests cancelled",
+                stats.requests_cancelled,
+            ),
+            (
+                "requests_deduplicated_total",
+                "Bookings deduplicated by idempotency key",
+                stats.requests_deduplicated,
+            ),
+            (
+                "requests_coalesced_total",
+                "Searches coalesced with an identical one",
+                stats.requests_coalesced,
+            ),
+            (
+                "requests_circuit_broken_total",
+                "Requests failed fast by an open circuit breaker",
+                stats.requests_circuit_broken,
+            ),
+        ] {
+            self.sample(name, "counter", help, labels, &[], value as f64);
+        }
+
+        self.sample(
+            "queue_depth",
+            "gauge",
+            "Requests queued",
+            labels,
+            &[],
+            stats.queue_depth as f64,
+        );
+        self.summary(
+            "queue_wait_seconds",
+            "How long requests sent were queued",
+            &stats.queue_wait,
+            labels,
+        );
+        for (priority, queued) in &stats.queue_depth_by_priority {
+            let priority = priority_name(*priority);
+            self.sample(
+                "queued_requests",
+                "gauge",
+                "Requests queued by priority",
+                labels,
+                &[("priority", priority)],
+                *queued as f64,
+            );
+        }
+        self.sample(
+            "active_requests",
+            "gauge",
+            "Requests in flight",
+            labels,
+            &[],
+            stats.active_requests as f64,
+        );
+        for (priority, in_flight) in &stats.in_flight_by_priority {
+            let priority = priority_name(*priority);
+            self.sample(
+                "in_flight_requests",
+                "gauge",
+                "Requests in flight by priority",
+                labels,
+                &[("priority", priority)],
+                *in_flight as f64,
+            );
+        }
+        for (service, state) in &stats.circuit_breakers {
+            for candidate in [
+                CircuitState::Closed,
+                CircuitState::Open,
+                CircuitState::HalfOpen,
+            ] {
+                let value = if *state == candidate { 1.0 } else { 0.0 };
+                self.sample(
+                    "circuit_breaker_state",
+                    "gauge",
+                    "Circuit breaker state, 1 for the current one",
+                    labels,
+                    &[("service", service), ("state", state_name(candidate))],
+                    value,
+                );
+            }
+        }
+        self.sample(
+            "rate_limit",
+            "gauge",
+            "Requests per second let through",
+            labels,
+            &[],
+            stats.current_rate_limit as f64,
+        );
+        self.sample(
+            "rate_limit_multiplier",
+            "gauge",
+            "Share of the configured rate let through",
+            labels,
+            &[],
+            stats.adaptive_rate_limit_multiplier,
+        );
+        for candidate in [
+            SystemHealth::Healthy,
+            SystemHealth::Degraded,
+            SystemHealth::Unhealthy,
+        ] {
+            let value = if stats.system_health == candidate {
+                1.0
+            } else {
+                0.0
+            };
+            self.sample(
+                "system_health",
+                "gauge",
+                "Upstream health, 1 for the current one",
+                labels,
+                &[("health", health_name(candidate))],
+                value,
+            );
+        }
+
+        for operation in Operation::ALL {
+            self.histogram(latency, operation, labels);
+        }
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        for family in &self.families {
+            let _ = writeln!(out, "# HELP {}{} {}", PREFIX, family.name, family.help);
+            let _ = writeln!(out, "# TYPE {}{} {}", PREFIX, family.name, family.kind);
+            for sample in &family.samples {
+                let _ = writeln!(out, "{}", sample);
+            }
+        }
+        out
+    }
+
+    fn histogram(
+        &mut self,
+        latency: &LatencyRecorder,
+        operation: Operation,
+        labels: &[(&str, &str)],
+    ) {
+        let name = "request_duration_seconds";
+        let help = "Response times, queueing and retries included";
+        let histogram = latency.histogram(operation);
+        let bounds: Vec<u64> = BUCKETS
+            .iter()
+            .map(|seconds| (seconds * 1e9) as u64)
+            .collect();
+        let operation = [("operation", operation.name())];
+        for (bound, count) in BUCKETS.iter().zip(histogram.cumulative_counts(&bounds)) {
+            let le = bound.to_string();
+            let extra = [operation[0], ("le", le.as_str())];
+            self.push(
+                name,
+                "histogram",
+                help,
+                format_sample(name, "_bucket", labels, &extra, count as f64),
+            );
+        }
+        let extra = [operation[0], ("le", "+Inf")];
+        let count = histogram.count() as f64;
+        self.push(
+            name,
+            "histogram",
+            help,
+            format_sample(name, "_bucket", labels, &extra, count),
+        );
+        let sum = histogram.sum() as f64 / 1e9;
+        self.push(
+            name,
+            "histogram",
+            help,
+            format_sample(name, "_sum", labels, &operation, sum),
+        );
+        self.push(
+            name,
+            "histogram",
+            help,
+            format_sample(name, "_count", labels, &operation, count),
+        );
+    }
+
+    fn summary(
+        &mut self,
+        name: &'static str,
+        help: &'static str,
+        latency: &LatencyStats,
+        labels: &[(&str, &str)],
+    ) {
+        for (quantile, millis) in [("0.95", latency.p95_ms), ("0.99", latency.p99_ms)] {
+            let extra = [("quantile", quantile)];
+            let sample = format_sample(name, "", labels, &extra, millis / 1e3);
+            self.push(name, "summary", help, sample);
+        }
+        let count = latency.count as f64;
+        let sum = latency.average_ms * count / 1e3;
+        self.push(
+            name,
+            "summary",
+            help,
+            format_sample(name, "_sum", labels, &[], sum),
+        );
+        self.push(
+            name,
+            "summary",
+            help,
+            format_sample(name, "_count", labels, &[], count),
+        );
+    }
+
+    fn sample(
+        &mut self,
+        name: &'static str,
+        kind: &'static str,
+        help: &'static str,
+        labels: &[(&str, &str)],
+        extra: &[(&str, &str)],
+        value: f64,
+    ) {
+        let sample = format_sample(name, "", labels, extra, value);
+        self.push(name, kind, help, sample);
+    }
+
+    fn push(&mut self, name: &'static str, kind: &'static str, help: &'static str, sample: String) {
+        let index = match self.families.iter().position(|family| family.name == name) {
+            Some(index) => index,
+            None => {
+                self.families.push(Family {
+                    name,
+                    kind,
+                    help,
+                    samples: Vec::new(),
+                });
+                self.families.len() - 1
+            }
+        };
+        self.families[index].samples.push(sample);
+    }
+}
+
+impl BookingApiClient {
+    // The client's metrics in the Prometheus text format
+    pub fn render_prometheus(&self) -> String {
+        let mut exposition = Exposition::new();
+        exposition.add_client(self, &[]);
+        exposition.render()
+    }
+}
+
+impl SupplierRouter<BookingApiClient> {
+    // Every supplier's metrics in the Prometheus text format, labelled with its name
+    pub fn render_prometheus(&self) -> String {
+        let mut exposition = Exposition::new();
+        for supplier in self.suppliers() {
+            if let Some(client) = self.client(supplier) {
+                exposition.add_client(client, &[("supplier", supplier)]);
+            }
+        }
+        exposition.render()
+    }
+}
+
+fn format_sample(
+    name: &str,
+    suffix: &str,
+    labels: &[(&str, &str)],
+    extra: &[(&str, &str)],
+    value: f64,
+) -> String {
+    let labels: Vec<String> = labels
+        .iter()
+        .chain(extra)
+        .map(|(label, value)| format!("{}=\"{}\"", label, escape(value)))
+        .collect();
+    let labels = match labels.is_empty() {
+        true => String::new(),
+        false => format!("{{{}}}", labels.join(",")),
+    };
+    format!("{}{}{}{} {}", PREFIX, name, suffix, labels, value)
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn priority_name(priority: RequestPriority) -> &'static str {
+    match priority {
+        RequestPriority::Low => "low",
+        RequestPriority::Medium => "medium",
+        RequestPriority::High => "high",
+        RequestPriority::Critical => "critical",
+    }
+}
+
+fn state_name(state: CircuitState) -> &'static str {
+    match state {
+        CircuitState::Closed => "closed",
+        CircuitState::Open => "open",
+        CircuitState::HalfOpen => "half_open",
+    }
+}
+
+fn health_name(health: SystemHealth) -> &'static str {
+    match health {
+        SystemHealth::Healthy => "healthy",
+        SystemHealth::Degraded => "degraded",
+        SystemHealth::Unhealthy => "unhealthy",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_render() {
+        let stats = ClientStats {
+            requests_sent: 3,
+            requests_succeeded: 2,
+            requests_failed: 1,
+            queue_depth: 4,
+            queue_depth_by_priority: vec![(RequestPriority::Low, 3), (RequestPriority::High, 1)],
+            queue_wait: LatencyStats {
+                count: 4,
+                average_ms: 250.0,
+                p95_ms: 500.0,
+                p99_ms: 750.0,
+                max_ms: 800.0,
+            },
+            circuit_breakers: vec![("search".to_string(), CircuitState::Open)],
+            system_health: SystemHealth::Degraded,
+            ..Default::default()
+        };
+        let latency = LatencyRecorder::new();
+        latency.record(Operation::Search, Duration::from_millis(20));
+        latency.record(Operation::Search, Duration::from_millis(400));
+        latency.record(Operation::Search, Duration::from_secs(60));
+
+        let mut exposition = Exposition::new();
+        exposition.add(&stats, &latency, &[("supplier", "acme")]);
+        exposition.add(
+            &ClientStats::default(),
+            &LatencyRecorder::new(),
+            &[("supplier", "a\"b")],
+        );
+        let text = exposition.render();
+        let lines: Vec<&str> = text.lines().collect();
+
+        // Each family described once, with the samples of both
+        let described = |line: &str| lines.iter().filter(|l| **l == line).count();
+        assert_eq!(
+            described("# TYPE booking_api_requests_sent_total counter"),
+            1
+        );
+        assert_eq!(described("# TYPE booking_api_queue_depth gauge"), 1);
+        assert_eq!(
+            described("# TYPE booking_api_request_duration_seconds histogram"),
+            1
+        );
+        for sample in [
+            "booking_api_requests_sent_total{supplier=\"acme\"} 3",
+            "booking_api_requests_failed_total{supplier=\"acme\"} 1",
+            "booking_api_requests_sent_total{supplier=\"a\\\"b\"} 0",
+            "booking_api_queue_depth{supplier=\"acme\"} 4",
+            "booking_api_queued_requests{supplier=\"acme\",priority=\"low\"} 3",
+            "booking_api_queue_wait_seconds{supplier=\"acme\",quantile=\"0.99\"} 0.75",
+            "booking_api_queue_wait_seconds_sum{supplier=\"acme\"} 1",
+            "booking_api_queue_wait_seconds_count{supplier=\"acme\"} 4",
+            "booking_api_circuit_breaker_state\
+             {supplier=\"acme\",service=\"search\",state=\"open\"} 1",
+            "booking_api_circuit_breaker_state\
+             {supplier=\"acme\",service=\"search\",state=\"closed\"} 0",
+            "booking_api_system_health{supplier=\"acme\",health=\"degraded\"} 1",
+        ] {
+            assert!(lines.contains(&sample), "missing {}", sample);
+        }
+
+        // Cumulative buckets, the slowest only in +Inf
+        let bucket = |le: &str| {
+            let prefix = format!(
+                "booking_api_request_duration_seconds_bucket\
+                 {{supplier=\"acme\",operation=\"search\",le=\"{}\"}} ",
+                le
+            );
+            let line = lines.iter().find(|line| line.starts_with(&prefix)).unwrap();
+            line[prefix.len()..].parse::<u64>().unwrap()
+        };
+        assert_eq!(bucket("0.01"), 0);
+        assert_eq!(bucket("0.025"), 1);
+        assert_eq!(bucket("0.5"), 2);
+        assert_eq!(bucket("30"), 2);
+        assert_eq!(bucket("+Inf"), 3);
+        assert!(lines.contains(
+            &"booking_api_request_duration_seconds_count{supplier=\"acme\",operation=\"search\"} 3"
+        ));
+    }
+}
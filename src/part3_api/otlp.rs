@@ -0,0 +1,170 @@
+// OTLP export
+// With the `otlp` feature the client's spans (see `BookingApiClient`) can be exported to an
+// OpenTelemetry collector over OTLP/gRPC, to follow a booking from the search that found it
+// through its queueing, retries and upstream calls. `init_tracing` installs a subscriber that
+// exports every span; an application with a subscriber of its own adds `layer` to it instead.
+// Spans are exported in batches from a background thread. The exporter connects lazily, so a
+// collector that is down costs lost spans, never failed requests. Both must be called within a
+// tokio runtime, which must outlive the export; `OtlpGuard` flushes the spans still pending and
+// shuts the exporter down when dropped.
+
+use opentelemetry::trace::TracerProvider;
+use opentelemetry_otlp::{ExporterBuildError, SpanExporter, WithExportConfig};
+use opentelemetry_sdk::trace::{SdkTracer, SdkTracerProvider};
+use opentelemetry_sdk::Resource;
+use thiserror::Error;
+use tracing_opentelemetry::OpenTelemetryLayer;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::{SubscriberInitExt, TryInitError};
+
+// Name of the instrumentation library on exported spans
+const TRACER_NAME: &str = "booking_api";
+
+#[derive(Error, Debug)]
+pub enum OtlpError {
+    #[error("OTLP exporter error: {0}")]
+    Exporter(#[from] ExporterBuildError),
+
+    #[error("Tracing subscriber error: {0}")]
+    Subscriber(#[from] TryInitError),
+}
+
+// Keeps the export running; flushes and shuts it down when dropped
+pub struct OtlpGuard {
+    provider: SdkTracerProvider,
+}
+
+impl Drop for OtlpGuard {
+    fn drop(&mut self) {
+        if let Err(error) = self.provider.shutdown() {
+            tracing::warn!("OTLP export shutdown failed: {}", error);
+        }
+    }
+}
+
+// Spans exported in batches to the collector at `endpoint` (e.g. "http://localhost:4317"),
+// as coming from `service_name`
+pub fn tracer_provider(
+    endpoint: &str,
+    service_name: &str,
+) -> Result<SdkTracerProvider, ExporterBuildError> {
+    let exporter = SpanExporter::builder()
+        .with_tonic()
+        .with_endpoint(endpoint)
+        .build()?;
+    Ok(SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(
+            Resource::builder()
+                .with_service_name(service_name.to_string())
+                .build(),
+        )
+        .build())
+}
+
+// A `tracing` layer handing spans to `provider`
+pub fn layer<S>(provider: &SdkTracerProvider) -> OpenTelemetryLayer<S, SdkTracer>
+where
+    S: tracing::Subscriber + for<'span> tracing_subscriber::registry::LookupSpan<'span>,
+{
+    tracing_opentelemetry::layer().with_tracer(provider.tracer(TRACER_NAME))
+}
+
+// Install a global subscriber exporting every span over OTLP; fails if one is already set
+pub fn init_tracing(endpoint: &str, service_name: &str) -> Result<OtlpGuard, OtlpError> {
+    let provider = tracer_provider(endpoint, service_name)?;
+    tracing_subscriber::registry()
+        .with(layer(&provider))
+        .try_init()?;
+    Ok(OtlpGuard { provider })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::part3_api::mock_server::MockServer;
+    use crate::part3_api::{
+        ApiClient, BookingApiClient, CircuitBreakerConfig, ClientConfig, OverflowPolicies,
+        QueueAging, RequestContext, RequestPriority, RetryConfig, SearchRequest,
+    };
+    use opentelemetry_sdk::trace::InMemorySpanExporter;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_request_spans_are_exported() {
+        let exporter = InMemorySpanExporter::default();
+        let provider = SdkTracerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .build();
+        let _subscriber = tracing_subscriber::registry()
+            .with(layer(&provider))
+            .set_default();
+
+        let config = ClientConfig {
+            base_url: "https://api.example.com".to_string(),
+            api_key: "test_key".to_string(),
+            max_requests_per_second: 10,
+            max_burst_size: 2,
+            max_concurrent_requests: 5,
+            timeout_ms: 5000,
+            retry_config: RetryConfig::default(),
+            circuit_breaker_config: CircuitBreakerConfig::default(),
+            queue_size_per_priority: 100,
+            overflow_policies: OverflowPolicies::default(),
+            queue_aging: QueueAging::default(),
+            health_check_interval_ms: 30000,
+            health_check_probe: None,
+            coalesce_searches: false,
+        };
+        let client = BookingApiClient::with_transport(config, Arc::new(MockServer::new()))
+            .await
+            .unwrap();
+        client
+            .search(SearchRequest {
+                hotel_ids: vec!["hotel1".to_string()],
+                check_in: "2025-06-01".to_string(),
+                check_out: "2025-06-05".to_string(),
+                guests: 2,
+                markets: Vec::new(),
+                supplier: None,
+                priority: RequestPriority::Medium,
+                idempotency_key: None,
+                context: RequestContext {
+                    correlation_id: "trace-1".to_string(),
+                    ..Default::default()
+                },
+            })
+            .await
+            .unwrap();
+
+        let spans = exporter.get_finished_spans().unwrap();
+        let span = |name: &str| {
+            spans
+                .iter()
+                .find(|span| span.name == name)
+                .unwrap_or_else(|| panic!("no {} span", name))
+        };
+        let request = span("request");
+        assert_eq!(
+            span("attempt").parent_span_id,
+            request.span_context.span_id()
+        );
+        assert_eq!(
+            span("transport").span_context.trace_id(),
+            request.span_context.trace_id()
+        );
+        assert!(request
+            .attributes
+            .iter()
+            .any(|attribute| attribute.key.as_str() == "correlation_id"
+                && attribute.value.as_str() == "trace-1"));
+    }
+
+    #[tokio::test]
+    async fn test_tracer_provider() {
+        // Connects lazily, so no collector is needed to build one
+        let provider = tracer_provider("http://127.0.0.1:4317", "booking-api").unwrap();
+        assert!(provider.shutdown().is_ok());
+        assert!(tracer_provider("not a url", "booking-api").is_err());
+    }
+}
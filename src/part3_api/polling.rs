@@ -0,0 +1,63 @@
+// Booking status polling
+// Suppliers confirm bookings asynchronously: a booking may come back Pending, or in a state of
+// the supplier's own, and only later be confirmed or failed. `poll_until_confirmed` asks for its
+// status until it's terminal (confirmed, failed or cancelled), waiting `initial_interval_ms`
+// after the first poll and `multiplier` times longer after each one, up to `max_interval_ms`.
+// It gives up with `Timeout` once the next poll would be past `timeout_ms`.
+// Each poll is a request of its own, retried by the client as any other; a poll that still
+// fails transiently is polled again at the next interval, any other failure ends polling.
+
+use super::{ApiClient, ApiError, BookingResponse};
+use std::time::Duration;
+use tokio::time::Instant;
+
+#[derive(Debug, Clone)]
+pub struct PollPolicy {
+    pub initial_interval_ms: u64,
+    pub max_interval_ms: u64,
+    pub multiplier: f64,
+    // From the first poll
+    pub timeout_ms: u64,
+}
+
+impl Default for PollPolicy {
+    fn default() -> Self {
+        Self {
+            initial_interval_ms: 500,
+            max_interval_ms: 5000,
+            multiplier: 1.5,
+            timeout_ms: 60_000,
+        }
+    }
+}
+
+// Poll `booking_id`'s status with `policy` until it's terminal
+pub async fn poll_until_confirmed<C: ApiClient + ?Sized>(
+    client: &C,
+    booking_id: &str,
+    policy: &PollPolicy,
+) -> Result<BookingResponse, ApiError> {
+    let start = Instant::now();
+    let deadline = start + Duration::from_millis(policy.timeout_ms);
+    let max_interval = Duration::from_millis(policy.max_interval_ms);
+    let mut interval = Duration::from_millis(policy.initial_interval_ms).min(max_interval);
+    let timeout = || ApiError::Timeout(start.elapsed().as_millis() as u64);
+    loop {
+        match tokio::time::timeout_at(deadline, client.get_booking_status(booking_id)).await {
+            Err(_) => return Err(timeout()),
+            Ok(Ok(response)) if response.status.is_terminal() => return Ok(response),
+            Ok(Ok(response)) => {
+                tracing::debug!(booking_id, status = %response.status, "Booking not settled yet")
+            }
+            Ok(Err(error)) if error.is_retryable() => {
+                tracing::debug!(booking_id, "Polling failed: {}", error)
+            }
+            Ok(Err(error)) => return Err(error),
+        }
+        if Instant::now() + interval >= deadline {
+            return Err(timeout());
+        }
+        tokio::time::sleep(interval).await;
+        interval = interval.mul_f64(policy.multiplier).min(max_interval);
+    }
+}
@@ -0,0 +1,305 @@
+// Token-bucket rate limiting
+// The upstream allows `max_requests_per_second` on average and short bursts of up to
+// `max_burst_size`. The bucket holds up to `max_burst_size` tokens, refilled continuously at
+// the rate, and every request takes one; a request finding the bucket empty waits for the next
+// token instead of failing. Waiters are served first come, first served: they queue on a fair
+// (FIFO) async mutex and only the one at the head sleeps until its token is due, so a steady
+// stream of new requests can't overtake one that has been waiting.
+// The rate is scaled by the system health multiplier (see `set_system_health`); the burst is
+// not, so a degraded upstream still gets the occasional spike but a lower sustained load.
+// Rate and burst can change at any time (`reconfigure`); tokens in the bucket are kept, up to
+// the new burst, and waiters carry on at the new rate.
+// When the upstream says it's rate limiting us and for how long (see `transport`), the limiter
+// pauses: no token is handed out until then, however many are in the bucket.
+// Time is `tokio::time::Instant`, so tests run on tokio's paused clock.
+
+use parking_lot::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use tokio::time::Instant;
+
+// Slack for the floating-point refill arithmetic
+const EPSILON: f64 = 1e-9;
+
+// Tokens refilled at `rate` per second, up to `capacity`
+#[derive(Debug, Clone)]
+pub struct TokenBucket {
+    rate: f64,
+    capacity: f64,
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl TokenBucket {
+    // A full bucket
+    pub fn new(rate: f64, capacity: u32, now: Instant) -> Self {
+        Self {
+            rate,
+            capacity: capacity as f64,
+            tokens: capacity as f64,
+            refilled_at: now,
+        }
+    }
+
+    // Take a token, or tell how long until one is available
+    pub fn try_take(&mut self, now: Instant) -> Result<(), Duration> {
+        self.refill(now);
+        if self.tokens >= 1.0 - EPSILON {
+            self.tokens = (self.tokens - 1.0).max(0.0);
+            return Ok(());
+        }
+        if self.rate <= 0.0 {
+            return Err(Duration::MAX);
+        }
+        Err(Duration::from_secs_f64((1.0 - self.tokens) / self.rate))
+    }
+
+    pub fn available(&mut self, now: Instant) -> u32 {
+        self.refill(now);
+        (self.tokens + EPSILON) as u32
+    }
+
+    // Tokens already in the bucket are kept, up to the new capacity
+    pub fn reconfigure(&mut self, rate: f64, capacity: u32, now: Instant) {
+        self.refill(now);
+        self.rate = rate;
+        self.capacity = capacity as f64;
+        self.tokens = self.tokens.min(self.capacity);
+    }
+
+    pub fn rate(&self) -> f64 {
+        self.rate
+    }
+
+    pub fn capacity(&self) -> u32 {
+        self.capacity as u32
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.refilled_at);
+        self.tokens = (self.tokens + elapsed.as_secs_f64() * self.rate).min(self.capacity);
+        self.refilled_at = now;
+    }
+}
+
+pub struct RateLimiter {
+    state: Mutex<LimiterState>,
+    // Held by the waiter at the head of the line
+    queue: tokio::sync::Mutex<()>,
+    throttled: AtomicUsize,
+}
+
+struct LimiterState {
+    bucket: TokenBucket,
+    // As configured, before the health multiplier
+    requests_per_second: u32,
+    multiplier: f64,
+    // No tokens until then
+    paused_until: Option<Instant>,
+}
+
+impl RateLimiter {
+    pub fn new(requests_per_second: u32, burst: u32) -> Self {
+        Self {
+            state: Mutex::new(LimiterState {
+                bucket: TokenBucket::new(requests_per_second as f64, burst, Instant::now()),
+                requests_per_second,
+                multiplier: 1.0,
+                paused_until: None,
+            }),
+            queue: tokio::sync::Mutex::new(()),
+            throttled: AtomicUsize::new(0),
+        }
+    }
+
+    // Wait for a token; returns whether the request had to wait (was throttled)
+    pub async fn acquire(&self) -> bool {
+        let _head = self.queue.lock().await;
+        let mut waited = false;
+        loop {
+            let due = self.state.lock().try_take(Instant::now());
+            match due {
+                Ok(()) => return waited,
+                Err(wait) => {
+                    if !waited {
+                        tracing::debug!(wait_ms = wait.as_millis() as u64, "Throttled");
+                        self.throttled.fetch_add(1, Ordering::SeqCst);
+                        waited = true;
+                    }
+                    tokio::time::sleep(wait).await;
+                }
+            }
+        }
+    }
+
+    // Scale the configured rate by `multiplier` (1.0 for a healthy upstream)
+    pub fn set_multiplier(&self, multiplier: f64) {
+        let mut state = self.state.lock();
+        state.multiplier = multiplier;
+        let (rate, capacity) = (
+            state.requests_per_second as f64 * multiplier,
+            state.bucket.capacity(),
+        );
+        state.bucket.reconfigure(rate, capacity, Instant::now());
+    }
+
+    // New configured rate and burst, still scaled by the multiplier
+    pub fn reconfigure(&self, requests_per_second: u32, burst: u32) {
+        let mut state = self.state.lock();
+        state.requests_per_second = requests_per_second;
+        let rate = requests_per_second as f64 * state.multiplier;
+        state.bucket.reconfigure(rate, burst, Instant::now());
+    }
+
+    // Hand out no tokens for `duration`; a longer pause already under way is kept
+    pub fn pause_for(&self, duration: Duration) {
+        let until = Instant::now() + duration;
+        let mut state = self.state.lock();
+        state.paused_until = state.paused_until.max(Some(until));
+    }
+
+    pub fn paused_until(&self) -> Option<Instant> {
+        let now = Instant::now();
+        self.state.lock().paused_until.filter(|until| *until > now)
+    }
+
+    pub fn multiplier(&self) -> f64 {
+        self.state.lock().multiplier
+    }
+
+    // The rate requests are let through at, in requests per second
+    pub fn current_rate(&self) -> f64 {
+        self.state.lock().bucket.rate()
+    }
+
+    pub fn available(&self) -> u32 {
+        if self.paused_until().is_some() {
+            return 0;
+        }
+        self.state.lock().bucket.available(Instant::now())
+    }
+
+    // Requests that had to wait for a token
+    pub fn throttled_count(&self) -> usize {
+        self.throttled.load(Ordering::SeqCst)
+    }
+}
+
+impl LimiterState {
+    fn try_take(&mut self, now: Instant) -> Result<(), Duration> {
+        match self.paused_until {
+            Some(until) if until > now => Err(until - now),
+            _ => self.bucket.try_take(now),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    // Waits in whole milliseconds, rounding away the float arithmetic
+    fn millis(wait: Result<(), Duration>) -> Result<(), u64> {
+        wait.map_err(|wait| (wait.as_secs_f64() * 1000.0).round() as u64)
+    }
+
+    #[test]
+    fn test_token_bucket() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(10.0, 3, start);
+
+        // The burst, then a token every 100ms
+        for _ in 0..3 {
+            assert_eq!(bucket.try_take(start), Ok(()));
+        }
+        assert_eq!(millis(bucket.try_take(start)), Err(100));
+        let later = start + Duration::from_millis(40);
+        assert_eq!(millis(bucket.try_take(later)), Err(60));
+        assert_eq!(bucket.try_take(start + Duration::from_millis(100)), Ok(()));
+
+        // Refills up to the burst only
+        let idle = start + Duration::from_secs(60);
+        assert_eq!(bucket.available(idle), 3);
+
+        bucket.reconfigure(2.0, 1, idle);
+        assert_eq!(bucket.available(idle), 1);
+        assert_eq!(bucket.try_take(idle), Ok(()));
+        assert_eq!(millis(bucket.try_take(idle)), Err(500));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_acquire_waits_for_tokens() {
+        let limiter = RateLimiter::new(10, 2);
+        let start = Instant::now();
+        assert!(!limiter.acquire().await);
+        assert!(!limiter.acquire().await);
+        assert!(limiter.acquire().await);
+        assert_eq!(start.elapsed(), Duration::from_millis(100));
+        assert!(limiter.acquire().await);
+        assert_eq!(start.elapsed(), Duration::from_millis(200));
+        assert_eq!(limiter.throttled_count(), 2);
+
+        // A tenth of the rate
+        limiter.set_multiplier(0.1);
+        assert_eq!(limiter.current_rate(), 1.0);
+        let before = Instant::now();
+        limiter.acquire().await;
+        assert_eq!(before.elapsed(), Duration::from_secs(1));
+
+        // Still scaled
+        limiter.reconfigure(50, 1);
+        assert_eq!(limiter.current_rate(), 5.0);
+        let before = Instant::now();
+        limiter.acquire().await;
+        assert_eq!(before.elapsed(), Duration::from_millis(200));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_pause() {
+        let limiter = RateLimiter::new(10, 2);
+        limiter.pause_for(Duration::from_secs(2));
+        // A shorter one doesn't cut it short
+        limiter.pause_for(Duration::from_secs(1));
+        assert_eq!(limiter.available(), 0);
+
+        let start = Instant::now();
+        assert!(limiter.acquire().await);
+        assert_eq!(start.elapsed(), Duration::from_secs(2));
+        // The bucket is still full
+        assert!(!limiter.acquire().await);
+        assert_eq!(limiter.paused_until(), None);
+        assert_eq!(limiter.available(), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_acquire_is_first_come_first_served() {
+        let limiter = Arc::new(RateLimiter::new(5, 1));
+        limiter.acquire().await;
+
+        let served = Arc::new(Mutex::new(Vec::new()));
+        let mut waiters = Vec::new();
+        for index in 0..5 {
+            let limiter = limiter.clone();
+            let served = served.clone();
+            waiters.push(tokio::spawn(async move {
+                limiter.acquire().await;
+                served.lock().push((index, Instant::now()));
+            }));
+            // Queue them in order
+            tokio::task::yield_now().await;
+        }
+        for waiter in waiters {
+            waiter.await.unwrap();
+        }
+
+        let served = served.lock();
+        let order: Vec<usize> = served.iter().map(|(index, _)| *index).collect();
+        assert_eq!(order, vec![0, 1, 2, 3, 4]);
+        // One every 200ms
+        for pair in served.windows(2) {
+            assert_eq!(pair[1].1 - pair[0].1, Duration::from_millis(200));
+        }
+        assert_eq!(limiter.throttled_count(), 5);
+    }
+}
@@ -0,0 +1,168 @@
+// Retries
+// Failed requests are sent again when the failure may be transient: network errors, timeouts,
+// rate limiting and API errors the upstream flags as retryable. Each retry waits
+// `BookingApiClient::calculate_backoff` for its attempt, up to `max_retries` retries, and none
+// is made if its backoff would end past the request's deadline. Every attempt goes through the
+// circuit breaker and the scheduler again, so retries are rate limited like any request and
+// stop once the breaker opens.
+// When the upstream rate limits a request and says how long to wait, the retry waits that long
+// instead of the backoff, up to `max_retry_after_ms`.
+// Bookings aren't idempotent: one is only retried when it has an idempotency key, so the
+// upstream can tell a retry from a second booking.
+
+use super::{ApiError, BookingApiClient, RetryConfig};
+use std::time::Duration;
+use tokio::time::Instant;
+
+pub struct Retrier<'a> {
+    config: &'a RetryConfig,
+    deadline: Option<Instant>,
+    // Retries made so far
+    retries: u32,
+    enabled: bool,
+}
+
+impl<'a> Retrier<'a> {
+    pub fn new(config: &'a RetryConfig, deadline: Option<Instant>) -> Self {
+        Self {
+            config,
+            deadline,
+            retries: 0,
+            enabled: true,
+        }
+    }
+
+    // Never retry
+    pub fn disabled(mut self) -> Self {
+        self.enabled = false;
+        self
+    }
+
+    // How long to wait before retrying after `error`, None to give up
+    pub fn next_backoff(&mut self, error: &ApiError) -> Option<Duration> {
+        if !self.enabled || self.retries >= self.config.max_retries || !error.is_retryable() {
+            return None;
+        }
+        let backoff = match error {
+            ApiError::RateLimitExceeded {
+                retry_after_ms: Some(retry_after_ms),
+                ..
+            } => Duration::from_millis((*retry_after_ms).min(self.config.max_retry_after_ms)),
+            _ => BookingApiClient::calculate_backoff(self.retries, self.config),
+        };
+        if self
+            .deadline
+            .is_some_and(|deadline| Instant::now() + backoff >= deadline)
+        {
+            return None;
+        }
+        self.retries += 1;
+        Some(backoff)
+    }
+
+    pub fn retries(&self) -> u32 {
+        self.retries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn server_error(is_retryable: bool) -> ApiError {
+        ApiError::ApiResponseError {
+            status_code: 500,
+            message: "Internal Server Error".to_string(),
+            is_retryable,
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_backoff_until_max_retries() {
+        let config = RetryConfig {
+            max_retries: 3,
+            jitter_factor: 0.0,
+            ..Default::default()
+        };
+        let mut retrier = Retrier::new(&config, None);
+        let backoffs: Vec<Option<Duration>> = (0..4)
+            .map(|_| retrier.next_backoff(&server_error(true)))
+            .collect();
+        assert_eq!(
+            backoffs,
+            vec![
+                Some(Duration::from_millis(100)),
+                Some(Duration::from_millis(200)),
+                Some(Duration::from_millis(400)),
+                None,
+            ]
+        );
+        assert_eq!(retrier.retries(), 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_only_transient_failures() {
+        let config = RetryConfig::default();
+        for error in [
+            ApiError::NetworkError("connection reset".to_string()),
+            ApiError::Timeout(5000),
+            ApiError::RateLimitExceeded {
+                message: "slow down".to_string(),
+                retry_after_ms: None,
+            },
+            server_error(true),
+        ] {
+            assert!(Retrier::new(&config, None).next_backoff(&error).is_some());
+            assert!(Retrier::new(&config, None)
+                .disabled()
+                .next_backoff(&error)
+                .is_none());
+        }
+        for error in [
+            server_error(false),
+            ApiError::RequestPreempted,
+            ApiError::QueueFull,
+            ApiError::CircuitBreakerOpen {
+                service_name: "search".to_string(),
+                retry_after_ms: Some(1000),
+            },
+        ] {
+            assert!(Retrier::new(&config, None).next_backoff(&error).is_none());
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_retry_after() {
+        let config = RetryConfig::default();
+        let mut retrier = Retrier::new(&config, Some(Instant::now() + Duration::from_secs(5)));
+        let rate_limited = |retry_after_ms| ApiError::RateLimitExceeded {
+            message: "slow down".to_string(),
+            retry_after_ms: Some(retry_after_ms),
+        };
+        assert_eq!(
+            retrier.next_backoff(&rate_limited(3000)),
+            Some(Duration::from_secs(3))
+        );
+        assert_eq!(retrier.next_backoff(&rate_limited(6000)), None);
+
+        // However long the upstream asks for
+        let mut retrier = Retrier::new(&config, None);
+        assert_eq!(
+            retrier.next_backoff(&rate_limited(u64::MAX)),
+            Some(Duration::from_millis(config.max_retry_after_ms))
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_deadline() {
+        let config = RetryConfig {
+            jitter_factor: 0.0,
+            ..Default::default()
+        };
+        let mut retrier = Retrier::new(&config, Some(Instant::now() + Duration::from_millis(250)));
+        assert!(retrier.next_backoff(&server_error(true)).is_some());
+        tokio::time::advance(Duration::from_millis(100)).await;
+        // 200ms would end past the deadline
+        assert!(retrier.next_backoff(&server_error(true)).is_none());
+    }
+}
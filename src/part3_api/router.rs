@@ -0,0 +1,456 @@
+// Supplier routing
+// Each supplier has its own client, with its own credentials, rate limits, breakers and stats;
+// the router picks the client for a request. A search naming a `supplier` goes to that one.
+// Otherwise each hotel goes to the supplier of the longest prefix of its ID that is routed
+// (`route_prefix`), or to the default supplier, and a search spanning several suppliers is sent
+// to all of them at once and merged: results in the order the hotels were requested, the
+// lowest `rate_limit_remaining`, the slowest time. The merged search ID lists each supplier's
+// as `supplier:search_id`, so a booking from it goes out with the ID of its own supplier.
+// A supplier failing doesn't fail the others: its hotels are left out, and the search only
+// fails if every supplier did. A hotel no supplier is routed for, or routed to one without a
+// client, fails the search before anything is sent.
+// Bookings are routed by hotel the same way.
+
+use super::{
+    ApiClient, ApiError, BookingApiClient, BookingRequest, BookingResponse, ClientStats,
+    SearchRequest, SearchResponse,
+};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+pub struct SupplierRouter<C = BookingApiClient> {
+    // By name
+    suppliers: BTreeMap<String, Arc<C>>,
+    // Hotel ID prefixes and their suppliers, longest first
+    prefixes: Vec<(String, String)>,
+    default: Option<String>,
+}
+
+impl<C> Default for SupplierRouter<C> {
+    fn default() -> Self {
+        Self {
+            suppliers: BTreeMap::new(),
+            prefixes: Vec::new(),
+            default: None,
+        }
+    }
+}
+
+impl<C: ApiClient> SupplierRouter<C> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_supplier(mut self, name: impl Into<String>, client: impl Into<Arc<C>>) -> Self {
+        self.suppliers.insert(name.into(), client.into());
+        self
+    }
+
+    // Hotels whose ID starts with `prefix` go to `supplier`
+    pub fn route_prefix(mut self, prefix: impl Into<String>, supplier: impl Into<String>) -> Self {
+        self.prefixes.push((prefix.into(), supplier.into()));
+        self.prefixes
+            .sort_by_key(|(prefix, _)| std::cmp::Reverse(prefix.len()));
+        self
+    }
+
+    // Hotels no prefix matches go to `supplier`
+    pub fn with_default(mut self, supplier: impl Into<String>) -> Self {
+        self.default = Some(supplier.into());
+        self
+    }
+
+    pub fn client(&self, supplier: &str) -> Option<&Arc<C>> {
+        self.suppliers.get(supplier)
+    }
+
+    pub fn suppliers(&self) -> impl Iterator<Item = &str> {
+        self.suppliers.keys().map(String::as_str)
+    }
+
+    // The supplier a hotel goes to
+    pub fn route(&self, hotel_id: &str) -> Option<&str> {
+        self.prefixes
+            .iter()
+            .find(|(prefix, _)| hotel_id.starts_with(prefix.as_str()))
+            .map(|(_, supplier)| supplier.as_str())
+            .or(self.default.as_deref())
+    }
+
+    pub async fn search(&self, request: SearchRequest) -> Result<SearchResponse, ApiError> {
+        if let Some(supplier) = &request.supplier {
+            return self.supplier(supplier)?.search(request.clone()).await;
+        }
+        let mut groups: Vec<(&str, Vec<String>)> = Vec::new();
+        for hotel_id in &request.hotel_ids {
+            let supplier = self.route_known(hotel_id)?;
+            match groups.iter_mut().find(|(name, _)| *name == supplier) {
+                Some((_, hotel_ids)) => hotel_ids.push(hotel_id.clone()),
+                None => groups.push((supplier, vec![hotel_id.clone()])),
+            }
+        }
+        if let [(supplier, _)] = groups.as_slice() {
+            return self.supplier(supplier)?.search(request).await;
+        }
+
+        let searches = groups.into_iter().map(|(supplier, hotel_ids)| {
+            let request = SearchRequest {
+                hotel_ids,
+                supplier: Some(supplier.to_string()),
+                ..request.clone()
+            };
+            async move {
+                let response = match self.supplier(supplier) {
+                    Ok(client) => client.search(request).await,
+                    Err(error) => Err(error),
+                };
+                (supplier, response)
+            }
+        });
+        let responses = futures::future::join_all(searches).await;
+        merge(&request, responses)
+    }
+
+    pub async fn book(&self, request: BookingRequest) -> Result<BookingResponse, ApiError> {
+        let supplier = self.route_known(&request.hotel_id)?;
+        let search_id = supplier_search_id(&request.search_id, supplier)
+            .unwrap_or(&request.search_id)
+            .to_string();
+        self.supplier(supplier)?
+            .book(BookingRequest {
+                search_id,
+                ..request
+            })
+            .await
+    }
+
+    // Each supplier's stats, by name
+    pub fn stats(&self) -> Vec<(String, ClientStats)> {
+        self.suppliers
+            .iter()
+            .map(|(name, client)| (name.clone(), client.stats()))
+            .collect()
+    }
+
+    fn supplier(&self, name: &str) -> Result<&C, ApiError> {
+        self.suppliers
+            .get(name)
+            .map(Arc::as_ref)
+            .ok_or_else(|| ApiError::ClientError(format!("unknown supplier {}", name)))
+    }
+
+    // The supplier a hotel goes to, which must have a client
+    fn route_known(&self, hotel_id: &str) -> Result<&str, ApiError> {
+        let supplier = self
+            .route(hotel_id)
+            .ok_or_else(|| ApiError::ClientError(format!("no supplier for hotel {}", hotel_id)))?;
+        self.supplier(supplier)?;
+        Ok(supplier)
+    }
+}
+
+fn merge(
+    request: &SearchRequest,
+    responses: Vec<(&str, Result<SearchResponse, ApiError>)>,
+) -> Result<SearchResponse, ApiError> {
+    let mut search_ids = Vec::new();
+    let mut results = HashMap::new();
+    let mut rate_limit_remaining: Option<u32> = None;
+    let mut processing_time_ms = 0;
+    let mut failure = None;
+    for (supplier, response) in responses {
+        let response = match response {
+            Ok(response) => response,
+            Err(error) => {
+                failure.get_or_insert(error);
+                continue;
+            }
+        };
+        search_ids.push(format!("{}:{}", supplier, response.search_id));
+        for result in response.results {
+            results.insert(result.hotel_id.clone(), result);
+        }
+        if let Some(remaining) = response.rate_limit_remaining {
+            rate_limit_remaining =
+                Some(rate_limit_remaining.map_or(remaining, |lowest| lowest.min(remaining)));
+        }
+        processing_time_ms = processing_time_ms.max(response.processing_time_ms);
+    }
+    if let Some(error) = failure.filter(|_| search_ids.is_empty()) {
+        return Err(error);
+    }
+    Ok(SearchResponse {
+        search_id: search_ids.join(","),
+        results: request
+            .hotel_ids
+            .iter()
+            .filter_map(|hotel_id| results.remove(hotel_id))
+            .collect(),
+        rate_limit_remaining,
+        processing_time_ms,
+    })
+}
+
+// `supplier`'s part of a merged search ID
+fn supplier_search_id<'a>(search_id: &'a str, supplier: &str) -> Option<&'a str> {
+    search_id.split(',').find_map(|part| {
+        part.strip_prefix(supplier)
+            .and_then(|rest| rest.strip_prefix(':'))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::part3_api::{
+        BookingStatus, CancelBookingRequest, CancelBookingResponse, ClientConfig, ClientError,
+        ModifyBookingRequest, ModifyBookingResponse, PaymentInfo, RequestContext, RequestPriority,
+        SearchResult, SystemHealth,
+    };
+    use async_trait::async_trait;
+    use std::sync::Mutex;
+
+    // Every hotel is available; records the hotels each search asked for and the search ID of
+    // each booking
+    #[derive(Default)]
+    struct RecordingSupplier {
+        searches: Mutex<Vec<Vec<String>>>,
+        bookings: Mutex<Vec<String>>,
+        rate_limit_remaining: u32,
+        fail: bool,
+    }
+
+    #[async_trait]
+    impl ApiClient for RecordingSupplier {
+        async fn search(&self, request: SearchRequest) -> Result<SearchResponse, ApiError> {
+            self.searches
+                .lock()
+                .unwrap()
+                .push(request.hotel_ids.clone());
+            if self.fail {
+                return Err(ApiError::NetworkError("Service unavailable".to_string()));
+            }
+            Ok(SearchResponse {
+                search_id: format!("search-{}", request.hotel_ids.len()),
+                results: request
+                    .hotel_ids
+                    .into_iter()
+                    .map(|hotel_id| SearchResult {
+                        hotel_id,
+                        available: true,
+                        price: Some(100.0),
+                        currency: Some("EUR".to_string()),
+                        search_id: None,
+                    })
+                    .collect(),
+                rate_limit_remaining: Some(self.rate_limit_remaining),
+                processing_time_ms: 0,
+            })
+        }
+
+        async fn book(&self, request: BookingRequest) -> Result<BookingResponse, ApiError> {
+            self.bookings.lock().unwrap().push(request.search_id);
+            Ok(BookingResponse {
+                booking_id: "booking-1".to_string(),
+                status: BookingStatus::Confirmed,
+                confirmation_code: None,
+                rate_limit_remaining: None,
+                processing_time_ms: 0,
+            })
+        }
+
+        async fn cancel_booking(
+            &self,
+            _request: CancelBookingRequest,
+        ) -> Result<CancelBookingResponse, ApiError> {
+            Err(ApiError::Other("Not supported".to_string()))
+        }
+
+        async fn modify_booking(
+            &self,
+            _request: ModifyBookingRequest,
+        ) -> Result<ModifyBookingResponse, ApiError> {
+            Err(ApiError::Other("Not supported".to_string()))
+        }
+
+        async fn get_booking_status(&self, _booking_id: &str) -> Result<BookingResponse, ApiError> {
+            Err(ApiError::Other("Not supported".to_string()))
+        }
+
+        fn stats(&self) -> ClientStats {
+            ClientStats {
+                requests_sent: self.searches.lock().unwrap().len(),
+                ..Default::default()
+            }
+        }
+
+        async fn set_system_health(&self, _health: SystemHealth) -> f64 {
+            1.0
+        }
+
+        async fn cancel_request(&self, _correlation_id: &str) -> bool {
+            false
+        }
+
+        async fn update_config(&self, _config: ClientConfig) -> Result<(), ClientError> {
+            Ok(())
+        }
+
+        async fn pause(&self, _drain: bool) -> Result<(), ClientError> {
+            Ok(())
+        }
+
+        async fn resume(&self) -> Result<(), ClientError> {
+            Ok(())
+        }
+
+        async fn reset_circuit_breakers(&self) -> usize {
+            0
+        }
+    }
+
+    fn supplier(rate_limit_remaining: u32, fail: bool) -> RecordingSupplier {
+        RecordingSupplier {
+            rate_limit_remaining,
+            fail,
+            ..Default::default()
+        }
+    }
+
+    fn router(fail: bool) -> SupplierRouter<RecordingSupplier> {
+        SupplierRouter::new()
+            .with_supplier("alpha", supplier(5, false))
+            .with_supplier("beta", supplier(3, fail))
+            .route_prefix("A", "alpha")
+            .route_prefix("AB", "beta")
+            .route_prefix("X", "gamma")
+            .with_default("beta")
+    }
+
+    fn request(hotel_ids: &[&str]) -> SearchRequest {
+        SearchRequest {
+            hotel_ids: hotel_ids.iter().map(|id| id.to_string()).collect(),
+            check_in: "2025-06-01".to_string(),
+            check_out: "2025-06-05".to_string(),
+            guests: 2,
+            markets: Vec::new(),
+            supplier: None,
+            priority: RequestPriority::Medium,
+            idempotency_key: None,
+            context: RequestContext::default(),
+        }
+    }
+
+    fn hotel_ids(response: &SearchResponse) -> Vec<&str> {
+        response
+            .results
+            .iter()
+            .map(|result| result.hotel_id.as_str())
+            .collect()
+    }
+
+    fn searches(router: &SupplierRouter<RecordingSupplier>, supplier: &str) -> Vec<Vec<String>> {
+        router
+            .client(supplier)
+            .unwrap()
+            .searches
+            .lock()
+            .unwrap()
+            .clone()
+    }
+
+    #[test]
+    fn test_route() {
+        let router = router(false);
+        assert_eq!(router.route("A1"), Some("alpha"));
+        // Longest prefix
+        assert_eq!(router.route("AB1"), Some("beta"));
+        assert_eq!(router.route("B1"), Some("beta"));
+        assert_eq!(router.suppliers().collect::<Vec<_>>(), ["alpha", "beta"]);
+        assert_eq!(SupplierRouter::<RecordingSupplier>::new().route("A1"), None);
+    }
+
+    #[tokio::test]
+    async fn test_fan_out() {
+        let router = router(false);
+        let response = router.search(request(&["A1", "B1", "A2"])).await.unwrap();
+        assert_eq!(hotel_ids(&response), ["A1", "B1", "A2"]);
+        assert_eq!(response.search_id, "alpha:search-2,beta:search-1");
+        assert_eq!(response.rate_limit_remaining, Some(3));
+        assert_eq!(searches(&router, "alpha"), [["A1", "A2"]]);
+        assert_eq!(searches(&router, "beta"), [["B1"]]);
+
+        // One supplier, or named: as it answers
+        let response = router.search(request(&["A3"])).await.unwrap();
+        assert_eq!(response.search_id, "search-1");
+        let named = SearchRequest {
+            supplier: Some("beta".to_string()),
+            ..request(&["A4"])
+        };
+        router.search(named).await.unwrap();
+        assert_eq!(searches(&router, "beta"), [vec!["B1"], vec!["A4"]]);
+
+        let stats = router.stats();
+        assert_eq!(stats[0].0, "alpha");
+        assert_eq!(stats[0].1.requests_sent, 2);
+        assert_eq!(stats[1].1.requests_sent, 2);
+    }
+
+    #[tokio::test]
+    async fn test_failures() {
+        let router = router(true);
+        // The other supplier's hotels are still found
+        let response = router.search(request(&["A1", "B1"])).await.unwrap();
+        assert_eq!(hotel_ids(&response), ["A1"]);
+        assert!(matches!(
+            router.search(request(&["B1"])).await,
+            Err(ApiError::NetworkError(_))
+        ));
+
+        // Routed to a supplier without a client
+        assert!(matches!(
+            router.search(request(&["A1", "X1"])).await,
+            Err(ApiError::ClientError(_))
+        ));
+        let unrouted = SupplierRouter::new().with_supplier("alpha", supplier(5, false));
+        assert!(matches!(
+            unrouted.search(request(&["A1"])).await,
+            Err(ApiError::ClientError(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_booking_goes_to_the_hotel_supplier() {
+        let router = router(false);
+        let booking = |hotel_id: &str, search_id: &str| BookingRequest {
+            search_id: search_id.to_string(),
+            hotel_id: hotel_id.to_string(),
+            guest_name: "John Doe".to_string(),
+            payment_info: PaymentInfo {
+                card_type: "VISA".to_string(),
+                last_four: "1234".to_string(),
+                expiry: "12/25".to_string(),
+                token: None,
+            },
+            priority: RequestPriority::High,
+            idempotency_key: String::new(),
+            context: RequestContext::default(),
+        };
+        let merged = "alpha:search-2,beta:search-1";
+        router.book(booking("B1", merged)).await.unwrap();
+        router.book(booking("A1", merged)).await.unwrap();
+        router.book(booking("A1", "search-9")).await.unwrap();
+
+        let bookings = |supplier| {
+            router
+                .client(supplier)
+                .unwrap()
+                .bookings
+                .lock()
+                .unwrap()
+                .clone()
+        };
+        assert_eq!(bookings("alpha"), ["search-2", "search-9"]);
+        assert_eq!(bookings("beta"), ["search-1"]);
+    }
+}
@@ -0,0 +1,957 @@
+// Priority scheduling
+// Requests don't take rate-limiter tokens themselves: they wait in a bounded queue per priority
+// (`queue_size_per_priority`) and a dispatcher task hands out the tokens. Each time it gets one
+// it lets the oldest request of the highest priority queued through, so Critical and High
+// requests always go before Medium and Low ones, however long those have been waiting.
+// When a request of High priority or above has to queue because the bucket is empty, the oldest
+// queued Low request is preempted: it fails at once with `RequestPreempted` rather than holding
+// its place until the booking is through, and its caller can retry or give up. One Low request
+// goes per such arrival, so a single booking doesn't fail every queued search.
+// A request whose caller stopped waiting is skipped when its turn comes. One whose deadline
+// has passed is dropped from its queue before it's dispatched, failed with `Timeout` and counted
+// as expired; one cancelled is dropped as soon as the client asks (`drop_cancelled`).
+// What happens to a request whose queue is full is up to its priority's `OverflowPolicy`:
+// - `RejectNew` fails it with `QueueFull`.
+// - `DropOldestLowPriority` makes room by failing the oldest request queued of the lowest
+//   priority below it with `RequestPreempted`, counted as preempted. The queue then holds more
+//   than its size, but all of them together never more than their sizes. With nothing of a lower
+//   priority queued, the request is rejected.
+// - `BlockWithTimeout` waits for room up to `timeout_ms` (or the request's deadline, if sooner),
+//   then rejects it. It's still dropped if cancelled meanwhile.
+// Rejected requests are counted. There is no policy spilling to disk: a queued request is its
+// caller's future waiting to be woken, which stays in memory wherever its place in line is
+// kept, so memory is bounded by the queue sizes and the policies above alone.
+// Under a steady stream of higher priority requests a Low one could wait forever, so with
+// `promote_after_ms` a request that has waited that long in its queue moves up to the next one,
+// behind the requests there queued before it, and on up every `promote_after_ms` more. A
+// promoted Low request is no longer preempted, and a promotion may take a queue past its size.
+// With `max_queue_wait_ms` a request still queued that long after it was queued fails at once
+// with `QueueWaitExceeded`, whatever its deadline. Promotions and requests failed so are counted,
+// and the queue wait of every request dispatched is recorded.
+
+use super::rate_limiter::RateLimiter;
+use super::{ApiError, LatencyStats, RequestPriority};
+use crate::part1_cache::LatencyHistogram;
+use parking_lot::Mutex;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{oneshot, Notify};
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+
+const PRIORITIES: usize = RequestPriority::ALL.len();
+
+// What a full queue does with one more request
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverflowPolicy {
+    #[default]
+    RejectNew,
+    DropOldestLowPriority,
+    BlockWithTimeout {
+        timeout_ms: u64,
+    },
+}
+
+// An `OverflowPolicy` per priority, `RejectNew` unless set
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct OverflowPolicies([OverflowPolicy; PRIORITIES]);
+
+impl OverflowPolicies {
+    // The same for every priority
+    pub fn new(policy: OverflowPolicy) -> Self {
+        Self([policy; PRIORITIES])
+    }
+
+    pub fn with_policy(mut self, priority: RequestPriority, policy: OverflowPolicy) -> Self {
+        self.0[priority as usize] = policy;
+        self
+    }
+
+    pub fn get(&self, priority: RequestPriority) -> OverflowPolicy {
+        self.0[priority as usize]
+    }
+}
+
+// Starvation prevention, nothing unless set
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct QueueAging {
+    // Waited in its queue before a request moves up a priority
+    pub promote_after_ms: Option<u64>,
+    // Waited in the queues before a request fails with `QueueWaitExceeded`
+    pub max_queue_wait_ms: Option<u64>,
+}
+
+pub struct Scheduler {
+    shared: Arc<Shared>,
+    dispatcher: JoinHandle<()>,
+}
+
+struct Shared {
+    queues: Mutex<Queues>,
+    // Signalled when a request is queued
+    queued: Notify,
+    // Signalled when requests leave their queue, for those waiting for room
+    dequeued: Notify,
+    limiter: Arc<RateLimiter>,
+    preempted: AtomicUsize,
+    rejected: AtomicUsize,
+    // Requests failed with `QueueWaitExceeded`
+    overdue: AtomicUsize,
+    // How long dispatched requests were queued
+    waits: LatencyHistogram,
+}
+
+struct Queues {
+    // Indexed by priority
+    waiting: [VecDeque<Waiter>; PRIORITIES],
+    queue_size: usize,
+    policies: OverflowPolicies,
+    aging: QueueAging,
+    // Requests dropped past their deadline
+    expired: usize,
+    promoted: usize,
+}
+
+struct Waiter {
+    dispatch: oneshot::Sender<Result<(), ApiError>>,
+    deadline: Option<Instant>,
+    cancellation: Option<CancellationToken>,
+    queued_at: Instant,
+    // When it was queued or last promoted
+    aged_at: Instant,
+}
+
+// What becomes of a request arriving at its queue
+enum Admission {
+    // Overdue from then, if ever
+    Queued(Option<Instant>),
+    Rejected,
+    // Wait for room until then
+    Blocked(Instant),
+}
+
+impl Scheduler {
+    // Starts the dispatcher on the current tokio runtime
+    pub fn new(limiter: Arc<RateLimiter>, queue_size: usize) -> Self {
+        let shared = Arc::new(Shared {
+            queues: Mutex::new(Queues {
+                waiting: Default::default(),
+                queue_size,
+                policies: OverflowPolicies::default(),
+                aging: QueueAging::default(),
+                expired: 0,
+                promoted: 0,
+            }),
+            queued: Notify::new(),
+            dequeued: Notify::new(),
+            limiter,
+            preempted: AtomicUsize::new(0),
+            rejected: AtomicUsize::new(0),
+            overdue: AtomicUsize::new(0),
+            waits: LatencyHistogram::default(),
+        });
+        Self {
+            dispatcher: tokio::spawn(dispatch(shared.clone())),
+            shared,
+        }
+    }
+
+    // Wait until the request may be sent; it's dropped if still queued at `deadline` or once
+    // `cancellation` is cancelled
+    pub async fn schedule(
+        &self,
+        priority: RequestPriority,
+        deadline: Option<Instant>,
+        cancellation: Option<CancellationToken>,
+    ) -> Result<(), ApiError> {
+        let (dispatch, mut dispatched) = oneshot::channel();
+        let now = Instant::now();
+        let mut waiter = Some(Waiter {
+            dispatch,
+            deadline,
+            cancellation: cancellation.clone(),
+            queued_at: now,
+            aged_at: now,
+        });
+        let mut blocked_until = None;
+        let overdue_at = loop {
+            let room = self.shared.dequeued.notified();
+            let mut room = std::pin::pin!(room);
+            // Before looking, so room made from now on isn't missed
+            room.as_mut().enable();
+            match self.admit(priority, &mut waiter, &mut blocked_until) {
+                Admission::Queued(overdue_at) => break overdue_at,
+                Admission::Rejected => {
+                    self.shared.rejected.fetch_add(1, Ordering::SeqCst);
+                    return Err(ApiError::QueueFull);
+                }
+                Admission::Blocked(until) => {
+                    tokio::select! {
+                        _ = room => {}
+                        _ = tokio::time::sleep_until(until) => {}
+                        _ = cancelled(cancellation.as_ref()) => {
+                            return Err(ApiError::RequestCancelled);
+                        }
+                    }
+                }
+            }
+        };
+        let queued_at = Instant::now();
+        self.shared.queued.notify_one();
+        let overdue = async {
+            match overdue_at {
+                Some(overdue_at) => tokio::time::sleep_until(overdue_at).await,
+                None => std::future::pending().await,
+            }
+        };
+        let waited = || ApiError::QueueWaitExceeded(queued_at.elapsed().as_millis() as u64);
+        let result = tokio::select! {
+            result = &mut dispatched => {
+                result.unwrap_or_else(|_| Err(ApiError::Other("scheduler stopped".to_string())))
+            }
+            _ = overdue => {
+                // Out of its queue, unless it was dispatched meanwhile
+                self.tidy(&mut self.shared.queues.lock());
+                dispatched.close();
+                dispatched.try_recv().unwrap_or_else(|_| Err(waited()))
+            }
+        };
+        match &result {
+            Ok(()) => self
+                .shared
+                .waits
+                .record(queued_at.elapsed().as_nanos() as u64),
+            Err(ApiError::QueueWaitExceeded(_)) => {
+                self.shared.overdue.fetch_add(1, Ordering::SeqCst);
+            }
+            Err(_) => {}
+        }
+        result
+    }
+
+    // Queue the waiter if there's room, or the overflow policy of its priority makes some
+    fn admit(
+        &self,
+        priority: RequestPriority,
+        waiter: &mut Option<Waiter>,
+        blocked_until: &mut Option<Instant>,
+    ) -> Admission {
+        let now = Instant::now();
+        let mut queues = self.shared.queues.lock();
+        self.tidy(&mut queues);
+        if queues.waiting[priority as usize].len() >= queues.queue_size {
+            match queues.policies.get(priority) {
+                OverflowPolicy::RejectNew => return Admission::Rejected,
+                OverflowPolicy::DropOldestLowPriority => {
+                    let Some(displaced) = queues.displace(priority) else {
+                        return Admission::Rejected;
+                    };
+                    if displaced
+                        .dispatch
+                        .send(Err(ApiError::RequestPreempted))
+                        .is_ok()
+                    {
+                        tracing::info!("Dropped the oldest lower priority request");
+                        self.shared.preempted.fetch_add(1, Ordering::SeqCst);
+                    }
+                }
+                OverflowPolicy::BlockWithTimeout { timeout_ms } => {
+                    let until = *blocked_until
+                        .get_or_insert_with(|| now + Duration::from_millis(timeout_ms));
+                    let waiting = waiter.as_ref().and_then(|waiter| waiter.deadline);
+                    let until = waiting.map_or(until, |deadline| deadline.min(until));
+                    return match now < until {
+                        true => Admission::Blocked(until),
+                        false => Admission::Rejected,
+                    };
+                }
+            }
+        }
+        if let Some(mut waiter) = waiter.take() {
+            waiter.queued_at = now;
+            waiter.aged_at = now;
+            queues.waiting[priority as usize].push_back(waiter);
+        }
+        if priority >= RequestPriority::High && self.shared.limiter.available() == 0 {
+            // Callers that stopped waiting don't count
+            let low = &mut queues.waiting[RequestPriority::Low as usize];
+            while let Some(waiter) = low.pop_front() {
+                if waiter
+                    .dispatch
+                    .send(Err(ApiError::RequestPreempted))
+                    .is_ok()
+                {
+                    tracing::info!("Preempted the oldest queued low priority request");
+                    self.shared.preempted.fetch_add(1, Ordering::SeqCst);
+                    self.shared.dequeued.notify_waiters();
+                    break;
+                }
+            }
+        }
+        let max_wait = queues.aging.max_queue_wait_ms.map(Duration::from_millis);
+        Admission::Queued(max_wait.map(|max_wait| now + max_wait))
+    }
+
+    // Requests already queued beyond a smaller size stay queued; new ones are refused until
+    // their queue is below it
+    pub fn set_queue_size(&self, queue_size: usize) {
+        self.shared.queues.lock().queue_size = queue_size;
+        self.shared.dequeued.notify_waiters();
+    }
+
+    // For requests arriving from now on
+    pub fn set_overflow_policies(&self, policies: OverflowPolicies) {
+        self.shared.queues.lock().policies = policies;
+    }
+
+    // Applies to requests already queued too
+    pub fn set_aging(&self, aging: QueueAging) {
+        self.shared.queues.lock().aging = aging;
+    }
+
+    pub fn queue_size(&self) -> usize {
+        self.shared.queues.lock().queue_size
+    }
+
+    // Requests waiting to be dispatched
+    pub fn queue_depth(&self) -> usize {
+        self.queue_depths().iter().sum()
+    }
+
+    // Requests waiting to be dispatched, indexed by priority
+    pub fn queue_depths(&self) -> [usize; PRIORITIES] {
+        let mut queues = self.shared.queues.lock();
+        self.tidy(&mut queues);
+        queues.waiting.each_ref().map(VecDeque::len)
+    }
+
+    pub fn preempted_count(&self) -> usize {
+        self.shared.preempted.load(Ordering::SeqCst)
+    }
+
+    // Requests refused with `QueueFull`
+    pub fn rejected_count(&self) -> usize {
+        self.shared.rejected.load(Ordering::SeqCst)
+    }
+
+    // Promotions made by aging; a request promoted twice counts twice
+    pub fn promoted_count(&self) -> usize {
+        let mut queues = self.shared.queues.lock();
+        self.tidy(&mut queues);
+        queues.promoted
+    }
+
+    // Requests failed with `QueueWaitExceeded`
+    pub fn overdue_count(&self) -> usize {
+        self.shared.overdue.load(Ordering::SeqCst)
+    }
+
+    // How long the requests dispatched were queued
+    pub fn queue_wait(&self) -> LatencyStats {
+        self.shared.waits.summary().into()
+    }
+
+    // Drop the queued requests that were cancelled; returns how many
+    pub fn drop_cancelled(&self) -> usize {
+        let mut queues = self.shared.queues.lock();
+        let mut dropped = 0;
+        for queue in &mut queues.waiting {
+            let (cancelled, kept): (VecDeque<_>, _) =
+                queue.drain(..).partition(Waiter::is_cancelled);
+            *queue = kept;
+            for waiter in cancelled {
+                let _ = waiter.dispatch.send(Err(ApiError::RequestCancelled));
+                dropped += 1;
+            }
+        }
+        if dropped > 0 {
+            self.shared.dequeued.notify_waiters();
+        }
+        dropped
+    }
+
+    // Requests dropped from their queue past their deadline
+    pub fn expired_count(&self) -> usize {
+        let mut queues = self.shared.queues.lock();
+        self.tidy(&mut queues);
+        queues.expired
+    }
+
+    fn tidy(&self, queues: &mut Queues) {
+        if queues.tidy(Instant::now()) {
+            self.shared.dequeued.notify_waiters();
+        }
+    }
+}
+
+impl Drop for Scheduler {
+    fn drop(&mut self) {
+        self.dispatcher.abort();
+    }
+}
+
+impl Queues {
+    fn is_empty(&self) -> bool {
+        self.waiting.iter().all(VecDeque::is_empty)
+    }
+
+    // The oldest request of the highest priority still within its deadline and not cancelled
+    fn pop(&mut self) -> Option<Waiter> {
+        self.tidy(Instant::now());
+        self.waiting.iter_mut().rev().find_map(|queue| {
+            while let Some(waiter) = queue.pop_front() {
+                if !waiter.is_cancelled() {
+                    return Some(waiter);
+                }
+            }
+            None
+        })
+    }
+
+    // Drop requests past their deadline or overdue and promote the ones due; returns whether
+    // any left their queue
+    fn tidy(&mut self, now: Instant) -> bool {
+        let expired = self.drop_expired(now);
+        let overdue = self.drop_overdue(now);
+        let promoted = self.age(now);
+        expired + overdue + promoted > 0
+    }
+
+    // Returns how many were dropped
+    fn drop_expired(&mut self, now: Instant) -> usize {
+        let mut dropped = 0;
+        for queue in &mut self.waiting {
+            let (expired, kept): (VecDeque<_>, _) = queue
+                .drain(..)
+                .partition(|waiter| waiter.deadline.is_some_and(|deadline| now >= deadline));
+            *queue = kept;
+            for waiter in expired {
+                let waited = now.duration_since(waiter.queued_at).as_millis() as u64;
+                let _ = waiter.dispatch.send(Err(ApiError::Timeout(waited)));
+                dropped += 1;
+            }
+        }
+        self.expired += dropped;
+        dropped
+    }
+
+    // Fail the requests queued `max_queue_wait_ms` ago or more; returns how many
+    fn drop_overdue(&mut self, now: Instant) -> usize {
+        let Some(max_wait) = self.aging.max_queue_wait_ms.map(Duration::from_millis) else {
+            return 0;
+        };
+        let mut dropped = 0;
+        for queue in &mut self.waiting {
+            let (overdue, kept): (VecDeque<_>, _) = queue
+                .drain(..)
+                .partition(|waiter| now.duration_since(waiter.queued_at) >= max_wait);
+            *queue = kept;
+            for waiter in overdue {
+                let waited = now.duration_since(waiter.queued_at).as_millis() as u64;
+                let _ = waiter
+                    .dispatch
+                    .send(Err(ApiError::QueueWaitExceeded(waited)));
+                dropped += 1;
+            }
+        }
+        dropped
+    }
+
+    // Move the requests that have waited `promote_after_ms` in their queue up to the next, as
+    // many times as they're due; returns how many promotions were made
+    fn age(&mut self, now: Instant) -> usize {
+        let Some(promote_after) = self.aging.promote_after_ms.map(Duration::from_millis) else {
+            return 0;
+        };
+        let mut promoted = 0;
+        // Lowest first, so a request due twice goes up twice
+        for priority in 0..PRIORITIES - 1 {
+            let (due, kept): (VecDeque<_>, _) = self.waiting[priority]
+                .drain(..)
+                .partition(|waiter| now.duration_since(waiter.aged_at) >= promote_after);
+            self.waiting[priority] = kept;
+            for mut waiter in due {
+                waiter.aged_at += promote_after;
+                // Queues are in the order requests were queued
+                let above = &mut self.waiting[priority + 1];
+                let position = above.partition_point(|other| other.queued_at <= waiter.queued_at);
+                above.insert(position, waiter);
+                promoted += 1;
+            }
+        }
+        if promoted > 0 {
+            tracing::debug!(promoted, "Promoted requests waiting too long");
+        }
+        self.promoted += promoted;
+        promoted
+    }
+
+    // Take the oldest request of the lowest priority below `priority` out of its queue
+    fn displace(&mut self, priority: RequestPriority) -> Option<Waiter> {
+        self.waiting[..priority as usize]
+            .iter_mut()
+            .find_map(VecDeque::pop_front)
+    }
+}
+
+// Until `token` is cancelled, forever without one
+async fn cancelled(token: Option<&CancellationToken>) {
+    match token {
+        Some(token) => token.cancelled().await,
+        None => std::future::pending().await,
+    }
+}
+
+impl Waiter {
+    fn is_cancelled(&self) -> bool {
+        self.cancellation
+            .as_ref()
+            .is_some_and(CancellationToken::is_cancelled)
+    }
+}
+
+async fn dispatch(shared: Arc<Shared>) {
+    // A token taken for requests whose callers had all gone
+    let mut holding_token = false;
+    loop {
+        loop {
+            let queued = shared.queued.notified();
+            if !shared.queues.lock().is_empty() {
+                break;
+            }
+            queued.await;
+        }
+        if !holding_token {
+            shared.limiter.acquire().await;
+        }
+        // Picked only now, so requests queued while waiting for the token are considered
+        holding_token = true;
+        while let Some(waiter) = shared.queues.lock().pop() {
+            shared.dequeued.notify_waiters();
+            if waiter.dispatch.send(Ok(())).is_ok() {
+                holding_token = false;
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_higher_priorities_first() {
+        let limiter = Arc::new(RateLimiter::new(10, 1));
+        let scheduler = Arc::new(Scheduler::new(limiter, 10));
+        // Spend the burst
+        scheduler
+            .schedule(RequestPriority::Medium, None, None)
+            .await
+            .unwrap();
+
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let mut requests = Vec::new();
+        for priority in [
+            RequestPriority::Medium,
+            RequestPriority::Medium,
+            RequestPriority::Critical,
+            RequestPriority::Medium,
+            RequestPriority::High,
+        ] {
+            let scheduler = scheduler.clone();
+            let order = order.clone();
+            requests.push(tokio::spawn(async move {
+                scheduler.schedule(priority, None, None).await.unwrap();
+                order.lock().push(priority);
+            }));
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(scheduler.queue_depth(), 5);
+        for request in requests {
+            request.await.unwrap();
+        }
+        // Everything was queued before the next token, one every 100ms
+        assert_eq!(
+            *order.lock(),
+            vec![
+                RequestPriority::Critical,
+                RequestPriority::High,
+                RequestPriority::Medium,
+                RequestPriority::Medium,
+                RequestPriority::Medium,
+            ]
+        );
+        assert_eq!(scheduler.queue_depth(), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_queue_full_and_preemption() {
+        let limiter = Arc::new(RateLimiter::new(1, 1));
+        let scheduler = Arc::new(Scheduler::new(limiter, 2));
+        scheduler
+            .schedule(RequestPriority::Low, None, None)
+            .await
+            .unwrap();
+
+        let low: Vec<_> = (0..2)
+            .map(|_| {
+                let scheduler = scheduler.clone();
+                tokio::spawn(
+                    async move { scheduler.schedule(RequestPriority::Low, None, None).await },
+                )
+            })
+            .collect();
+        tokio::task::yield_now().await;
+        assert!(matches!(
+            scheduler.schedule(RequestPriority::Low, None, None).await,
+            Err(ApiError::QueueFull)
+        ));
+        assert_eq!(scheduler.rejected_count(), 1);
+
+        // A booking needs the next token
+        let start = Instant::now();
+        scheduler
+            .schedule(RequestPriority::High, None, None)
+            .await
+            .unwrap();
+        assert_eq!(start.elapsed(), Duration::from_secs(1));
+        let mut low = low.into_iter();
+        assert!(matches!(
+            low.next().unwrap().await.unwrap(),
+            Err(ApiError::RequestPreempted)
+        ));
+        assert_eq!(scheduler.preempted_count(), 1);
+        // The other one keeps its place, behind the booking
+        assert!(low.next().unwrap().await.unwrap().is_ok());
+        assert_eq!(start.elapsed(), Duration::from_secs(2));
+
+        // Not while there are tokens
+        tokio::time::advance(Duration::from_secs(1)).await;
+        let queued = {
+            let scheduler = scheduler.clone();
+            tokio::spawn(async move { scheduler.schedule(RequestPriority::Low, None, None).await })
+        };
+        scheduler
+            .schedule(RequestPriority::Critical, None, None)
+            .await
+            .unwrap();
+        assert!(queued.await.unwrap().is_ok());
+        assert_eq!(scheduler.preempted_count(), 1);
+    }
+
+    // Queue `priority` from a task of its own
+    fn spawn_schedule(
+        scheduler: &Arc<Scheduler>,
+        priority: RequestPriority,
+    ) -> tokio::task::JoinHandle<Result<(), ApiError>> {
+        let scheduler = scheduler.clone();
+        tokio::spawn(async move { scheduler.schedule(priority, None, None).await })
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_drop_oldest_low_priority() {
+        let limiter = Arc::new(RateLimiter::new(1, 1));
+        let scheduler = Arc::new(Scheduler::new(limiter, 1));
+        scheduler.set_overflow_policies(
+            OverflowPolicies::new(OverflowPolicy::RejectNew)
+                .with_policy(RequestPriority::Low, OverflowPolicy::DropOldestLowPriority)
+                .with_policy(
+                    RequestPriority::Medium,
+                    OverflowPolicy::DropOldestLowPriority,
+                ),
+        );
+        scheduler
+            .schedule(RequestPriority::Low, None, None)
+            .await
+            .unwrap();
+
+        let low = spawn_schedule(&scheduler, RequestPriority::Low);
+        tokio::task::yield_now().await;
+        let medium = spawn_schedule(&scheduler, RequestPriority::Medium);
+        tokio::task::yield_now().await;
+        assert_eq!(scheduler.queue_depths(), [1, 1, 0, 0]);
+
+        // The oldest Low gives way
+        let displacing = spawn_schedule(&scheduler, RequestPriority::Medium);
+        tokio::task::yield_now().await;
+        assert!(matches!(
+            low.await.unwrap(),
+            Err(ApiError::RequestPreempted)
+        ));
+        assert_eq!(scheduler.queue_depths(), [0, 2, 0, 0]);
+        assert_eq!(scheduler.preempted_count(), 1);
+
+        // Nothing lower left to drop
+        assert!(matches!(
+            scheduler
+                .schedule(RequestPriority::Medium, None, None)
+                .await,
+            Err(ApiError::QueueFull)
+        ));
+        assert_eq!(scheduler.rejected_count(), 1);
+
+        for request in [medium, displacing] {
+            assert!(request.await.unwrap().is_ok());
+        }
+        assert_eq!(scheduler.queue_depth(), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_block_with_timeout() {
+        let limiter = Arc::new(RateLimiter::new(10, 1));
+        let scheduler = Arc::new(Scheduler::new(limiter, 1));
+        scheduler.set_overflow_policies(OverflowPolicies::new(OverflowPolicy::BlockWithTimeout {
+            timeout_ms: 150,
+        }));
+        scheduler
+            .schedule(RequestPriority::Medium, None, None)
+            .await
+            .unwrap();
+
+        // Queued as soon as the request ahead is dispatched, at the next token
+        let ahead = spawn_schedule(&scheduler, RequestPriority::Medium);
+        tokio::task::yield_now().await;
+        let start = Instant::now();
+        scheduler
+            .schedule(RequestPriority::Medium, None, None)
+            .await
+            .unwrap();
+        assert_eq!(start.elapsed(), Duration::from_millis(200));
+        assert!(ahead.await.unwrap().is_ok());
+
+        // No sooner than the request's deadline
+        let ahead = spawn_schedule(&scheduler, RequestPriority::Medium);
+        tokio::task::yield_now().await;
+        let start = Instant::now();
+        let deadline = start + Duration::from_millis(50);
+        assert!(matches!(
+            scheduler
+                .schedule(RequestPriority::Medium, Some(deadline), None)
+                .await,
+            Err(ApiError::QueueFull)
+        ));
+        assert_eq!(start.elapsed(), Duration::from_millis(50));
+
+        // Dropped once cancelled
+        let cancellation = CancellationToken::new();
+        let cancelled = {
+            let scheduler = scheduler.clone();
+            let cancellation = cancellation.clone();
+            tokio::spawn(async move {
+                scheduler
+                    .schedule(RequestPriority::Medium, None, Some(cancellation))
+                    .await
+            })
+        };
+        tokio::task::yield_now().await;
+        cancellation.cancel();
+        assert!(matches!(
+            cancelled.await.unwrap(),
+            Err(ApiError::RequestCancelled)
+        ));
+        assert!(ahead.await.unwrap().is_ok());
+
+        // Given up on when no room comes in time
+        scheduler.set_queue_size(0);
+        let start = Instant::now();
+        assert!(matches!(
+            scheduler
+                .schedule(RequestPriority::Medium, None, None)
+                .await,
+            Err(ApiError::QueueFull)
+        ));
+        assert_eq!(start.elapsed(), Duration::from_millis(150));
+        assert_eq!(scheduler.rejected_count(), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_aging() {
+        let limiter = Arc::new(RateLimiter::new(10, 1));
+        let scheduler = Arc::new(Scheduler::new(limiter, 10));
+        scheduler.set_aging(QueueAging {
+            promote_after_ms: Some(250),
+            max_queue_wait_ms: None,
+        });
+        scheduler
+            .schedule(RequestPriority::Medium, None, None)
+            .await
+            .unwrap();
+
+        // A fresh Medium request for every token
+        let start = Instant::now();
+        let low = spawn_schedule(&scheduler, RequestPriority::Low);
+        let mut medium = Vec::new();
+        for _ in 0..5 {
+            medium.push(spawn_schedule(&scheduler, RequestPriority::Medium));
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+
+        // Promoted at the first token after 250ms, ahead of the Medium requests queued since
+        assert!(low.is_finished());
+        assert!(low.await.unwrap().is_ok());
+        assert_eq!(scheduler.promoted_count(), 1);
+        for request in medium {
+            assert!(request.await.unwrap().is_ok());
+        }
+        assert_eq!(start.elapsed(), Duration::from_millis(600));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_max_queue_wait() {
+        let limiter = Arc::new(RateLimiter::new(1, 1));
+        let scheduler = Arc::new(Scheduler::new(limiter, 10));
+        scheduler.set_aging(QueueAging {
+            promote_after_ms: None,
+            max_queue_wait_ms: Some(300),
+        });
+        scheduler
+            .schedule(RequestPriority::Medium, None, None)
+            .await
+            .unwrap();
+
+        // Fails before its deadline, and leaves its queue
+        let start = Instant::now();
+        let deadline = start + Duration::from_secs(5);
+        assert!(matches!(
+            scheduler
+                .schedule(RequestPriority::High, Some(deadline), None)
+                .await,
+            Err(ApiError::QueueWaitExceeded(300))
+        ));
+        assert_eq!(start.elapsed(), Duration::from_millis(300));
+        assert_eq!(scheduler.overdue_count(), 1);
+        assert_eq!(scheduler.queue_depth(), 0);
+
+        // Without a maximum it waits for the next token, 700ms on
+        scheduler.set_aging(QueueAging::default());
+        scheduler
+            .schedule(RequestPriority::High, None, None)
+            .await
+            .unwrap();
+        let waits = scheduler.queue_wait();
+        assert_eq!(waits.count, 2);
+        assert!((690.0..=710.0).contains(&waits.max_ms), "{:?}", waits);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_abandoned_requests_are_skipped() {
+        let limiter = Arc::new(RateLimiter::new(10, 1));
+        let scheduler = Arc::new(Scheduler::new(limiter, 10));
+        scheduler
+            .schedule(RequestPriority::Medium, None, None)
+            .await
+            .unwrap();
+
+        // Given up before its turn
+        let abandoned = tokio::time::timeout(
+            Duration::from_millis(50),
+            scheduler.schedule(RequestPriority::High, None, None),
+        )
+        .await;
+        assert!(abandoned.is_err());
+
+        let start = Instant::now();
+        scheduler
+            .schedule(RequestPriority::Low, None, None)
+            .await
+            .unwrap();
+        assert_eq!(start.elapsed(), Duration::from_millis(50));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_expired_requests_time_out() {
+        let limiter = Arc::new(RateLimiter::new(10, 1));
+        let scheduler = Arc::new(Scheduler::new(limiter, 1));
+        scheduler
+            .schedule(RequestPriority::Medium, None, None)
+            .await
+            .unwrap();
+
+        // Its caller hears why rather than seeing the scheduler stop
+        let deadline = Instant::now() + Duration::from_millis(50);
+        let expiring = {
+            let scheduler = scheduler.clone();
+            tokio::spawn(async move {
+                scheduler
+                    .schedule(RequestPriority::Medium, Some(deadline), None)
+                    .await
+            })
+        };
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert_eq!(scheduler.queue_depth(), 0);
+        assert!(matches!(
+            expiring.await.unwrap(),
+            Err(ApiError::Timeout(60))
+        ));
+        assert_eq!(scheduler.expired_count(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_expired_requests_are_dropped() {
+        let limiter = Arc::new(RateLimiter::new(10, 1));
+        let scheduler = Arc::new(Scheduler::new(limiter, 1));
+        scheduler
+            .schedule(RequestPriority::Medium, None, None)
+            .await
+            .unwrap();
+
+        let deadline = Instant::now() + Duration::from_millis(50);
+        let expiring = {
+            let scheduler = scheduler.clone();
+            tokio::spawn(async move {
+                tokio::time::timeout_at(
+                    deadline,
+                    scheduler.schedule(RequestPriority::High, Some(deadline), None),
+                )
+                .await
+            })
+        };
+        tokio::task::yield_now().await;
+        assert_eq!(scheduler.queue_depth(), 1);
+        assert!(expiring.await.unwrap().is_err());
+
+        // Its place is free and it isn't dispatched
+        assert_eq!(scheduler.queue_depth(), 0);
+        assert_eq!(scheduler.expired_count(), 1);
+        let start = Instant::now();
+        scheduler
+            .schedule(RequestPriority::High, None, None)
+            .await
+            .unwrap();
+        assert_eq!(start.elapsed(), Duration::from_millis(50));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_cancelled_requests_are_dropped() {
+        let limiter = Arc::new(RateLimiter::new(10, 1));
+        let scheduler = Arc::new(Scheduler::new(limiter, 10));
+        scheduler
+            .schedule(RequestPriority::Medium, None, None)
+            .await
+            .unwrap();
+
+        let cancellation = CancellationToken::new();
+        let cancelled = {
+            let scheduler = scheduler.clone();
+            let cancellation = cancellation.clone();
+            tokio::spawn(async move {
+                scheduler
+                    .schedule(RequestPriority::High, None, Some(cancellation))
+                    .await
+            })
+        };
+        tokio::task::yield_now().await;
+        assert_eq!(scheduler.drop_cancelled(), 0);
+        cancellation.cancel();
+        assert_eq!(scheduler.drop_cancelled(), 1);
+        assert_eq!(scheduler.queue_depth(), 0);
+        assert!(matches!(
+            cancelled.await.unwrap(),
+            Err(ApiError::RequestCancelled)
+        ));
+    }
+}
@@ -0,0 +1,160 @@
+// Single-flight calls
+// Concurrent calls with the same key share one execution: the first caller (the leader) runs
+// it, the others wait and get a copy of its result. The key is free again as soon as the call
+// ends, so later callers run it afresh; keeping results is up to the caller.
+// A leader that gives up (its future is dropped, e.g. the request was cancelled) doesn't take
+// the others down with it: one of them becomes the leader and runs the call again.
+
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::future::Future;
+use std::hash::Hash;
+use tokio::sync::watch;
+
+pub struct SingleFlight<K, V> {
+    calls: Mutex<HashMap<K, watch::Receiver<Option<V>>>>,
+}
+
+enum Role<V> {
+    Leader(watch::Sender<Option<V>>),
+    Follower(watch::Receiver<Option<V>>),
+}
+
+// Frees the key when the leader is done or gives up
+struct Flight<'a, K: Eq + Hash, V> {
+    calls: &'a Mutex<HashMap<K, watch::Receiver<Option<V>>>>,
+    key: &'a K,
+    sender: &'a watch::Sender<Option<V>>,
+}
+
+impl<K: Eq + Hash + Clone, V: Clone> Default for SingleFlight<K, V> {
+    fn default() -> Self {
+        Self {
+            calls: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl<K: Eq + Hash + Clone, V: Clone> SingleFlight<K, V> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Run `call` unless one with `key` is already running, then wait for that one instead;
+    // returns the result and whether it came from another caller's call
+    pub async fn run<F, Fut>(&self, key: K, call: F) -> (V, bool)
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = V>,
+    {
+        loop {
+            let role = {
+                let mut calls = self.calls.lock();
+                match calls.get(&key) {
+                    Some(receiver) => Role::Follower(receiver.clone()),
+                    None => {
+                        let (sender, receiver) = watch::channel(None);
+                        calls.insert(key.clone(), receiver);
+                        Role::Leader(sender)
+                    }
+                }
+            };
+            match role {
+                Role::Leader(sender) => {
+                    let flight = Flight {
+                        calls: &self.calls,
+                        key: &key,
+                        sender: &sender,
+                    };
+                    let value = call().await;
+                    drop(flight);
+                    sender.send_replace(Some(value.clone()));
+                    return (value, false);
+                }
+                Role::Follower(mut receiver) => {
+                    if let Ok(value) = receiver.wait_for(Option::is_some).await {
+                        if let Some(value) = value.clone() {
+                            return (value, true);
+                        }
+                    }
+                    // The leader gave up
+                }
+            }
+        }
+    }
+
+    // Calls running
+    pub fn len(&self) -> usize {
+        self.calls.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.calls.lock().is_empty()
+    }
+}
+
+impl<K: Eq + Hash, V> Drop for Flight<'_, K, V> {
+    fn drop(&mut self) {
+        let mut calls = self.calls.lock();
+        // Unless a new leader took the key already
+        if calls
+            .get(self.key)
+            .is_some_and(|receiver| receiver.same_channel(&self.sender.subscribe()))
+        {
+            calls.remove(self.key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_concurrent_calls_are_shared() {
+        let flight = SingleFlight::new();
+        let calls = AtomicUsize::new(0);
+        let call = |value: u32| {
+            let calls = &calls;
+            move || async move {
+                calls.fetch_add(1, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                value
+            }
+        };
+
+        let results = futures::future::join_all([
+            flight.run("a", call(1)),
+            flight.run("a", call(2)),
+            flight.run("a", call(3)),
+            flight.run("b", call(4)),
+        ])
+        .await;
+        assert_eq!(results, vec![(1, false), (1, true), (1, true), (4, false)]);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        assert!(flight.is_empty());
+
+        // Run again once done
+        assert_eq!(flight.run("a", call(5)).await, (5, false));
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_leader_giving_up() {
+        let flight = SingleFlight::new();
+        let slow = || async {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            "slow"
+        };
+        let leader = tokio::time::timeout(Duration::from_millis(10), flight.run("a", slow));
+        let follower = flight.run("a", || async { "follower" });
+        let (leader, follower) = tokio::join!(leader, follower);
+
+        assert!(leader.is_err());
+        // Ran its own call once the leader was gone
+        assert_eq!(follower, ("follower", false));
+        assert!(flight.is_empty());
+    }
+}
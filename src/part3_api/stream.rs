@@ -0,0 +1,189 @@
+// Streaming searches
+// A search of many hotels waits for the slowest of them before anything comes back. A streamed
+// search is split into searches of `CHUNK_SIZE` hotels, sent as requests of their own, and each
+// hotel's result is yielded as soon as the search of its chunk completes; results come in chunk
+// completion order, and in the upstream's order within a chunk.
+// At most `MAX_CHUNKS_IN_FLIGHT` chunks (and no more than the queue of a priority holds) are
+// searched at a time, the next one starting as one completes, so a search of many hotels can't
+// fill the queue by itself: its own chunks would be rejected, or push out other callers'.
+// Each chunk goes through the client as any search: queued by the request's priority, rate
+// limited, retried, coalesced and under its deadline. A chunk that fails yields its error and
+// the others carry on. The chunks share the request's correlation ID, so `cancel_request`
+// cancels all of them; the stream then yields `RequestCancelled` once and ends. Dropping the
+// stream abandons the chunks still queued or in flight.
+// Each chunk takes a rate limiter token, so a streamed search costs more of the request rate
+// than one search of all the hotels.
+
+use super::{ApiClient, ApiError, BookingApiClient, SearchRequest, SearchResult};
+use futures::stream::{self, Stream, StreamExt};
+
+// Hotels per upstream search
+pub const CHUNK_SIZE: usize = 10;
+// Chunks of one stream searched at a time
+pub const MAX_CHUNKS_IN_FLIGHT: usize = 4;
+
+impl BookingApiClient {
+    // Each hotel's result as the search of its chunk completes
+    pub fn search_stream(
+        &self,
+        request: SearchRequest,
+    ) -> impl Stream<Item = Result<SearchResult, ApiError>> + '_ {
+        let in_flight = MAX_CHUNKS_IN_FLIGHT.min(self.config().queue_size_per_priority);
+        let chunks: Vec<Vec<String>> = request
+            .hotel_ids
+            .chunks(CHUNK_SIZE)
+            .map(<[String]>::to_vec)
+            .collect();
+        stream::iter(chunks)
+            .map(move |hotel_ids| {
+                self.search(SearchRequest {
+                    hotel_ids,
+                    ..request.clone()
+                })
+            })
+            .buffer_unordered(in_flight)
+            .flat_map(|response| match response {
+                Ok(response) => stream::iter(response.results.into_iter().map(Ok)).left_stream(),
+                Err(error) => stream::iter([Err(error)]).right_stream(),
+            })
+            // One cancellation for all the chunks
+            .scan(false, |cancelled, item| {
+                if *cancelled {
+                    return futures::future::ready(None);
+                }
+                *cancelled = matches!(item, Err(ApiError::RequestCancelled));
+                futures::future::ready(Some(item))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::part3_api::mock_server::MockServer;
+    use crate::part3_api::{
+        CircuitBreakerConfig, ClientConfig, OverflowPolicies, QueueAging, RequestContext,
+        RequestPriority, RetryConfig, SearchResponse,
+    };
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::time::Instant;
+
+    fn hotel_ids(count: usize) -> Vec<String> {
+        (0..count).map(|hotel| format!("hotel{}", hotel)).collect()
+    }
+
+    async fn client(hotels: usize) -> (Arc<MockServer>, BookingApiClient) {
+        client_with_queue(hotels, 100).await
+    }
+
+    // A client whose server knows every chunk of `hotels` hotels
+    async fn client_with_queue(
+        hotels: usize,
+        queue_size: usize,
+    ) -> (Arc<MockServer>, BookingApiClient) {
+        let server = Arc::new(MockServer::new());
+        for chunk in hotel_ids(hotels).chunks(CHUNK_SIZE) {
+            let response = SearchResponse {
+                search_id: format!("search-{}", chunk[0]),
+                results: chunk
+                    .iter()
+                    .map(|hotel_id| SearchResult {
+                        hotel_id: hotel_id.clone(),
+                        available: true,
+                        price: Some(100.0),
+                        currency: Some("EUR".to_string()),
+                        search_id: None,
+                    })
+                    .collect(),
+                rate_limit_remaining: None,
+                processing_time_ms: 0,
+            };
+            server.add_search_response(&chunk[0], response).await;
+        }
+        let config = ClientConfig {
+            base_url: "https://api.example.com".to_string(),
+            api_key: "test_key".to_string(),
+            max_requests_per_second: 10,
+            max_burst_size: 2,
+            max_concurrent_requests: 5,
+            timeout_ms: 5000,
+            retry_config: RetryConfig::default(),
+            circuit_breaker_config: CircuitBreakerConfig::default(),
+            queue_size_per_priority: queue_size,
+            overflow_policies: OverflowPolicies::default(),
+            queue_aging: QueueAging::default(),
+            health_check_interval_ms: 30000,
+            health_check_probe: None,
+            coalesce_searches: false,
+        };
+        let client = BookingApiClient::with_transport(config, server.clone())
+            .await
+            .unwrap();
+        (server, client)
+    }
+
+    fn request(hotels: usize, correlation_id: &str) -> SearchRequest {
+        SearchRequest {
+            hotel_ids: hotel_ids(hotels),
+            check_in: "2025-06-01".to_string(),
+            check_out: "2025-06-05".to_string(),
+            guests: 2,
+            markets: Vec::new(),
+            supplier: None,
+            priority: RequestPriority::Medium,
+            idempotency_key: None,
+            context: RequestContext {
+                correlation_id: correlation_id.to_string(),
+                ..Default::default()
+            },
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_results_as_chunks_complete() {
+        let (server, client) = client(25).await;
+        let start = Instant::now();
+        let mut arrivals = Vec::new();
+        let mut stream = std::pin::pin!(client.search_stream(request(25, "")));
+        while let Some(result) = stream.next().await {
+            arrivals.push((result.unwrap().hotel_id, start.elapsed()));
+        }
+
+        let mut hotels: Vec<_> = arrivals.iter().map(|(hotel, _)| hotel.clone()).collect();
+        hotels.sort();
+        let mut expected = hotel_ids(25);
+        expected.sort();
+        assert_eq!(hotels, expected);
+        assert_eq!(server.request_count(), 3);
+        // The burst's two chunks at once, the third once the limiter has a token again
+        assert_eq!(arrivals[19].1, Duration::ZERO);
+        assert_eq!(arrivals[20].1, Duration::from_millis(100));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_more_chunks_than_the_queue_holds() {
+        let (server, client) = client_with_queue(60, 2).await;
+        let results: Vec<_> = client.search_stream(request(60, "")).collect().await;
+        assert_eq!(results.len(), 60);
+        assert!(results.iter().all(Result::is_ok));
+        assert_eq!(server.request_count(), 6);
+        assert_eq!(client.stats().requests_rejected, 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_cancelled_stream() {
+        let (_server, client) = client(25).await;
+        let mut stream = std::pin::pin!(client.search_stream(request(25, "stream-1")));
+        for _ in 0..CHUNK_SIZE * 2 {
+            assert!(stream.next().await.unwrap().is_ok());
+        }
+        // The third chunk is still queued
+        assert!(client.cancel_request("stream-1").await);
+        assert!(matches!(
+            stream.next().await,
+            Some(Err(ApiError::RequestCancelled))
+        ));
+        assert!(stream.next().await.is_none());
+    }
+}
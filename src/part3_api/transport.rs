@@ -0,0 +1,929 @@
+// Transport
+// The client decides when and how often a request is sent; a `Transport` sends it. Production
+// uses `HttpTransport`, JSON over HTTP with reqwest: a pooled connection per host (up to
+// `max_concurrent_requests` idle ones kept), `timeout_ms` for the whole exchange and `api_key`
+// as a bearer token on every request. Tests plug in the in-process `MockServer` instead, so the
+// same scheduling, retry and circuit-breaking logic runs against a test double.
+// Searches are POSTed to `search` and bookings to `bookings`; a booking is cancelled with a POST
+// to `bookings/{id}/cancel` and modified with a PATCH of `bookings/{id}`, whose status a GET
+// of it reads.
+// Failures map onto `ApiError` so retries and the breakers can tell them apart: timeouts and
+// connection failures as `Timeout` and `NetworkError`, 429 as `RateLimitExceeded`, other error
+// statuses as `ApiResponseError`, retryable for 408 and 5xx.
+// Rate limit headers are read on every reply: `X-RateLimit-Remaining` fills
+// `rate_limit_remaining`, and a 429 carries how long the upstream wants us to wait, from
+// `Retry-After` (seconds or an HTTP date) or else `X-RateLimit-Reset` (seconds, or a Unix time
+// past 2001; one already passed means no wait). The client pauses its token bucket for that long
+// and retries after it instead of its own backoff, both cut to `max_retry_after_ms`.
+// A transport may take a new configuration at runtime (`reconfigure`): `HttpTransport` builds a
+// new reqwest client when the URL, key, timeout or pool size change, and requests already sent
+// finish on the old one.
+
+use super::{
+    ApiError, BookingRequest, BookingResponse, BookingStatus, CancelBookingRequest,
+    CancelBookingResponse, ClientConfig, ClientError, ModifyBookingRequest, ModifyBookingResponse,
+    SearchRequest, SearchResponse, SearchResult,
+};
+use async_trait::async_trait;
+use parking_lot::RwLock;
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION, RETRY_AFTER};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::time::Instant;
+
+pub const CORRELATION_ID_HEADER: &str = "X-Correlation-ID";
+pub const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+pub const RATE_LIMIT_REMAINING_HEADER: &str = "X-RateLimit-Remaining";
+pub const RATE_LIMIT_RESET_HEADER: &str = "X-RateLimit-Reset";
+
+// `X-RateLimit-Reset` values above this are Unix times (2001-09-09 on), not seconds to wait
+const EPOCH_RESET_MIN: u64 = 1_000_000_000;
+
+pub enum Request {
+    Search(SearchRequest),
+    Booking(BookingRequest),
+    CancelBooking(CancelBookingRequest),
+    ModifyBooking(ModifyBookingRequest),
+    // Of this booking ID, answered with a `Response::Booking`
+    BookingStatus(String),
+    // A GET of this path, answered with no content
+    HealthCheck(String),
+}
+
+#[derive(Debug)]
+pub enum Response {
+    Search(SearchResponse),
+    Booking(BookingResponse),
+    CancelBooking(CancelBookingResponse),
+    ModifyBooking(ModifyBookingResponse),
+    HealthCheck,
+}
+
+#[async_trait]
+pub trait Transport: Send + Sync + 'static {
+    // Send one request to the upstream, once
+    async fn send(&self, request: Request) -> Result<Response, ApiError>;
+
+    // Apply a new client configuration
+    fn reconfigure(&self, _config: &ClientConfig) -> Result<(), ClientError> {
+        Ok(())
+    }
+}
+
+pub struct HttpTransport {
+    connection: RwLock<Arc<Connection>>,
+}
+
+struct Connection {
+    client: reqwest::Client,
+    // What the client was built from
+    settings: Settings,
+    base_url: String,
+}
+
+#[derive(PartialEq)]
+struct Settings {
+    base_url: String,
+    api_key: String,
+    timeout_ms: u64,
+    max_idle_connections: u32,
+}
+
+// A decoded reply and the requests the upstream still allows
+struct Reply<T> {
+    body: T,
+    rate_limit_remaining: Option<u32>,
+}
+
+#[derive(Serialize)]
+struct SearchBody<'a> {
+    hotel_ids: &'a [String],
+    check_in: &'a str,
+    check_out: &'a str,
+    guests: u32,
+    #[serde(skip_serializing_if = "no_markets")]
+    markets: &'a [String],
+}
+
+#[derive(Deserialize)]
+struct SearchReply {
+    search_id: String,
+    #[serde(default)]
+    results: Vec<SearchResult>,
+}
+
+#[derive(Serialize)]
+struct BookingBody<'a> {
+    search_id: &'a str,
+    hotel_id: &'a str,
+    guest_name: &'a str,
+    card_type: &'a str,
+    last_four: &'a str,
+    expiry: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    payment_token: Option<&'a str>,
+}
+
+#[derive(Deserialize)]
+struct BookingReply {
+    booking_id: String,
+    status: String,
+    #[serde(default)]
+    confirmation_code: Option<String>,
+}
+
+#[derive(Serialize)]
+struct CancelBody<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<&'a str>,
+}
+
+#[derive(Deserialize)]
+struct CancelReply {
+    booking_id: String,
+    status: String,
+    #[serde(default)]
+    penalty_amount: f64,
+    #[serde(default)]
+    refund_amount: f64,
+    #[serde(default)]
+    currency: Option<String>,
+}
+
+// Only what changes
+#[derive(Serialize)]
+struct ModifyBody<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    check_in: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    check_out: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    guests: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    guest_name: Option<&'a str>,
+}
+
+#[derive(Deserialize)]
+struct ModifyReply {
+    booking_id: String,
+    status: String,
+    #[serde(default)]
+    confirmation_code: Option<String>,
+    #[serde(default)]
+    price_difference: Option<f64>,
+    #[serde(default)]
+    currency: Option<String>,
+}
+
+impl HttpTransport {
+    pub fn new(config: &ClientConfig) -> Result<Self, ClientError> {
+        Ok(Self {
+            connection: RwLock::new(Arc::new(Connection::new(Settings::from(config))?)),
+        })
+    }
+}
+
+impl Connection {
+    fn new(settings: Settings) -> Result<Self, ClientError> {
+        reqwest::Url::parse(&settings.base_url).map_err(|error| {
+            ClientError::ConfigError(format!("invalid base_url {}: {}", settings.base_url, error))
+        })?;
+        let mut headers = HeaderMap::new();
+        if !settings.api_key.is_empty() {
+            let mut authorization = HeaderValue::from_str(&format!("Bearer {}", settings.api_key))
+                .map_err(|_| ClientError::ConfigError("invalid api_key".to_string()))?;
+            authorization.set_sensitive(true);
+            headers.insert(AUTHORIZATION, authorization);
+        }
+        let client = reqwest::Client::builder()
+            .default_headers(headers)
+            .timeout(Duration::from_millis(settings.timeout_ms))
+            .pool_max_idle_per_host(settings.max_idle_connections as usize)
+            .build()
+            .map_err(|error| ClientError::InitError(error.to_string()))?;
+        Ok(Self {
+            client,
+            base_url: settings.base_url.trim_end_matches('/').to_string(),
+            settings,
+        })
+    }
+
+    async fn search(&self, request: SearchRequest) -> Result<SearchResponse, ApiError> {
+        let start = Instant::now();
+        let body = SearchBody {
+            hotel_ids: &request.hotel_ids,
+            check_in: &request.check_in,
+            check_out: &request.check_out,
+            guests: request.guests,
+            markets: &request.markets,
+        };
+        let builder = self.post("search", &request.context.correlation_id);
+        let reply: Reply<SearchReply> = self.exchange(builder.json(&body)).await?;
+        Ok(SearchResponse {
+            search_id: reply.body.search_id,
+            results: reply.body.results,
+            rate_limit_remaining: reply.rate_limit_remaining,
+            processing_time_ms: start.elapsed().as_millis() as u64,
+        })
+    }
+
+    async fn book(&self, request: BookingRequest) -> Result<BookingResponse, ApiError> {
+        let start = Instant::now();
+        let payment = &request.payment_info;
+        let body = BookingBody {
+            search_id: &request.search_id,
+            hotel_id: &request.hotel_id,
+            guest_name: &request.guest_name,
+            card_type: &payment.card_type,
+            last_four: &payment.last_four,
+            expiry: &payment.expiry,
+            payment_token: payment.token.as_deref(),
+        };
+        let builder = self.post("bookings", &request.context.correlation_id);
+        let builder = idempotent(builder, &request.idempotency_key);
+        let reply: Reply<BookingReply> = self.exchange(builder.json(&body)).await?;
+        Ok(BookingResponse {
+            booking_id: reply.body.booking_id,
+            status: BookingStatus::from(reply.body.status.as_str()),
+            confirmation_code: reply.body.confirmation_code,
+            rate_limit_remaining: reply.rate_limit_remaining,
+            processing_time_ms: start.elapsed().as_millis() as u64,
+        })
+    }
+
+    async fn booking_status(&self, booking_id: &str) -> Result<BookingResponse, ApiError> {
+        let start = Instant::now();
+        let url = self.booking_url(booking_id, None)?;
+        let reply: Reply<BookingReply> = self.exchange(self.client.get(url)).await?;
+        Ok(BookingResponse {
+            booking_id: reply.body.booking_id,
+            status: BookingStatus::from(reply.body.status.as_str()),
+            confirmation_code: reply.body.confirmation_code,
+            rate_limit_remaining: reply.rate_limit_remaining,
+            processing_time_ms: start.elapsed().as_millis() as u64,
+        })
+    }
+
+    async fn cancel_booking(
+        &self,
+        request: CancelBookingRequest,
+    ) -> Result<CancelBookingResponse, ApiError> {
+        let start = Instant::now();
+        let url = self.booking_url(&request.booking_id, Some("cancel"))?;
+        let builder = correlated(self.client.post(url), &request.context.correlation_id);
+        let builder = idempotent(builder, &request.idempotency_key);
+        let body = CancelBody {
+            reason: request.reason.as_deref(),
+        };
+        let reply: Reply<CancelReply> = self.exchange(builder.json(&body)).await?;
+        Ok(CancelBookingResponse {
+            booking_id: reply.body.booking_id,
+            status: BookingStatus::from(reply.body.status.as_str()),
+            penalty_amount: reply.body.penalty_amount,
+            refund_amount: reply.body.refund_amount,
+            currency: reply.body.currency,
+            rate_limit_remaining: reply.rate_limit_remaining,
+            processing_time_ms: start.elapsed().as_millis() as u64,
+        })
+    }
+
+    async fn modify_booking(
+        &self,
+        request: ModifyBookingRequest,
+    ) -> Result<ModifyBookingResponse, ApiError> {
+        let start = Instant::now();
+        let url = self.booking_url(&request.booking_id, None)?;
+        let builder = correlated(self.client.patch(url), &request.context.correlation_id);
+        let builder = idempotent(builder, &request.idempotency_key);
+        let body = ModifyBody {
+            check_in: request.check_in.as_deref(),
+            check_out: request.check_out.as_deref(),
+            guests: request.guests,
+            guest_name: request.guest_name.as_deref(),
+        };
+        let reply: Reply<ModifyReply> = self.exchange(builder.json(&body)).await?;
+        Ok(ModifyBookingResponse {
+            booking_id: reply.body.booking_id,
+            status: BookingStatus::from(reply.body.status.as_str()),
+            confirmation_code: reply.body.confirmation_code,
+            price_difference: reply.body.price_difference,
+            currency: reply.body.currency,
+            rate_limit_remaining: reply.rate_limit_remaining,
+            processing_time_ms: start.elapsed().as_millis() as u64,
+        })
+    }
+
+    async fn health_check(&self, path: &str) -> Result<(), ApiError> {
+        let url = format!("{}/{}", self.base_url, path.trim_start_matches('/'));
+        let response = self
+            .client
+            .get(url)
+            .send()
+            .await
+            .map_err(|error| self.request_error(error))?;
+        if !response.status().is_success() {
+            return Err(reply_error(response).await);
+        }
+        Ok(())
+    }
+
+    fn post(&self, path: &str, correlation_id: &str) -> reqwest::RequestBuilder {
+        let builder = self.client.post(format!("{}/{}", self.base_url, path));
+        correlated(builder, correlation_id)
+    }
+
+    // `bookings/{booking_id}`, then `action` if any, with the ID escaped
+    fn booking_url(
+        &self,
+        booking_id: &str,
+        action: Option<&str>,
+    ) -> Result<reqwest::Url, ApiError> {
+        if booking_id.is_empty() {
+            return Err(ApiError::ClientError("booking_id is empty".to_string()));
+        }
+        let mut url = reqwest::Url::parse(&self.base_url)
+            .map_err(|error| ApiError::ClientError(error.to_string()))?;
+        url.path_segments_mut()
+            .map_err(|()| ApiError::ClientError(format!("invalid base_url {}", self.base_url)))?
+            .pop_if_empty()
+            .push("bookings")
+            .push(booking_id)
+            .extend(action);
+        Ok(url)
+    }
+
+    // Send the request and decode the JSON reply
+    async fn exchange<T: for<'de> Deserialize<'de>>(
+        &self,
+        builder: reqwest::RequestBuilder,
+    ) -> Result<Reply<T>, ApiError> {
+        let response = builder
+            .send()
+            .await
+            .map_err(|error| self.request_error(error))?;
+        if !response.status().is_success() {
+            return Err(reply_error(response).await);
+        }
+        let rate_limit_remaining = header(response.headers(), RATE_LIMIT_REMAINING_HEADER)
+            .and_then(|value| value.parse().ok());
+        let body = response
+            .json()
+            .await
+            .map_err(|error| match error.is_decode() {
+                true => ApiError::Other(format!("invalid response: {}", error)),
+                false => self.request_error(error),
+            })?;
+        Ok(Reply {
+            body,
+            rate_limit_remaining,
+        })
+    }
+
+    fn request_error(&self, error: reqwest::Error) -> ApiError {
+        match error.is_timeout() {
+            true => ApiError::Timeout(self.settings.timeout_ms),
+            false => ApiError::NetworkError(error.to_string()),
+        }
+    }
+}
+
+#[async_trait]
+impl Transport for HttpTransport {
+    async fn send(&self, request: Request) -> Result<Response, ApiError> {
+        let connection = self.connection.read().clone();
+        match request {
+            Request::Search(request) => connection.search(request).await.map(Response::Search),
+            Request::Booking(request) => connection.book(request).await.map(Response::Booking),
+            Request::CancelBooking(request) => connection
+                .cancel_booking(request)
+                .await
+                .map(Response::CancelBooking),
+            Request::ModifyBooking(request) => connection
+                .modify_booking(request)
+                .await
+                .map(Response::ModifyBooking),
+            Request::BookingStatus(booking_id) => connection
+                .booking_status(&booking_id)
+                .await
+                .map(Response::Booking),
+            Request::HealthCheck(path) => connection
+                .health_check(&path)
+                .await
+                .map(|()| Response::HealthCheck),
+        }
+    }
+
+    fn reconfigure(&self, config: &ClientConfig) -> Result<(), ClientError> {
+        let settings = Settings::from(config);
+        if self.connection.read().settings == settings {
+            return Ok(());
+        }
+        let connection = Connection::new(settings)?;
+        *self.connection.write() = Arc::new(connection);
+        Ok(())
+    }
+}
+
+impl From<&ClientConfig> for Settings {
+    fn from(config: &ClientConfig) -> Self {
+        Self {
+            base_url: config.base_url.clone(),
+            api_key: config.api_key.clone(),
+            timeout_ms: config.timeout_ms,
+            max_idle_connections: config.max_concurrent_requests,
+        }
+    }
+}
+
+fn no_markets(markets: &&[String]) -> bool {
+    markets.is_empty()
+}
+
+fn correlated(builder: reqwest::RequestBuilder, correlation_id: &str) -> reqwest::RequestBuilder {
+    match correlation_id.is_empty() {
+        true => builder,
+        false => builder.header(CORRELATION_ID_HEADER, correlation_id),
+    }
+}
+
+fn idempotent(builder: reqwest::RequestBuilder, idempotency_key: &str) -> reqwest::RequestBuilder {
+    match idempotency_key.is_empty() {
+        true => builder,
+        false => builder.header(IDEMPOTENCY_KEY_HEADER, idempotency_key),
+    }
+}
+
+async fn reply_error(response: reqwest::Response) -> ApiError {
+    let status = response.status();
+    let retry_after = retry_after(response.headers(), SystemTime::now());
+    let message = response.text().await.unwrap_or_default();
+    status_error(status, message, retry_after)
+}
+
+fn status_error(status: StatusCode, message: String, retry_after: Option<Duration>) -> ApiError {
+    let message = match message.trim() {
+        "" => status.canonical_reason().unwrap_or_default().to_string(),
+        message => message.to_string(),
+    };
+    if status == StatusCode::TOO_MANY_REQUESTS {
+        return ApiError::RateLimitExceeded {
+            message,
+            retry_after_ms: retry_after.map(|wait| wait.as_millis().try_into().unwrap_or(u64::MAX)),
+        };
+    }
+    ApiError::ApiResponseError {
+        status_code: status.as_u16(),
+        message,
+        is_retryable: status.is_server_error() || status == StatusCode::REQUEST_TIMEOUT,
+    }
+}
+
+// How long the upstream asks to wait, as of `now`
+fn retry_after(headers: &HeaderMap, now: SystemTime) -> Option<Duration> {
+    if let Some(value) = header(headers, RETRY_AFTER.as_str()) {
+        if let Ok(seconds) = value.parse::<u64>() {
+            return Some(Duration::from_secs(seconds));
+        }
+        let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+        let date = UNIX_EPOCH + Duration::from_secs(date.timestamp().try_into().ok()?);
+        return Some(date.duration_since(now).unwrap_or(Duration::ZERO));
+    }
+    let reset: u64 = header(headers, RATE_LIMIT_RESET_HEADER)?.parse().ok()?;
+    let since_epoch = now.duration_since(UNIX_EPOCH).unwrap_or_default();
+    // A Unix time already passed (or skewed clocks) means no wait, not 54 years of one
+    match reset > EPOCH_RESET_MIN {
+        true => Some(Duration::from_secs(reset).saturating_sub(since_epoch)),
+        false => Some(Duration::from_secs(reset)),
+    }
+}
+
+fn header<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name)?.to_str().ok().map(str::trim)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::part3_api::{
+        CircuitBreakerConfig, OverflowPolicies, PaymentInfo, QueueAging, RequestContext,
+        RequestPriority, RetryConfig,
+    };
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use tokio::task::JoinHandle;
+
+    fn config(base_url: String) -> ClientConfig {
+        ClientConfig {
+            base_url,
+            api_key: "test_key".to_string(),
+            max_requests_per_second: 10,
+            max_burst_size: 2,
+            max_concurrent_requests: 5,
+            timeout_ms: 5000,
+            retry_config: RetryConfig::default(),
+            circuit_breaker_config: CircuitBreakerConfig::default(),
+            queue_size_per_priority: 100,
+            overflow_policies: OverflowPolicies::default(),
+            queue_aging: QueueAging::default(),
+            health_check_interval_ms: 30000,
+            health_check_probe: None,
+            coalesce_searches: false,
+        }
+    }
+
+    // Answers one request per connection with each of `replies` in turn, returning the
+    // requests received
+    async fn serve(replies: Vec<(u16, &'static str)>) -> (String, JoinHandle<Vec<String>>) {
+        serve_with_headers(
+            replies
+                .into_iter()
+                .map(|(status, body)| (status, "", body))
+                .collect(),
+        )
+        .await
+    }
+
+    // As `serve`, each reply with extra header lines
+    async fn serve_with_headers(
+        replies: Vec<(u16, &'static str, &'static str)>,
+    ) -> (String, JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}/v1/", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let mut received = Vec::new();
+            for (status, headers, body) in replies {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                received.push(read_request(&mut stream).await);
+                let reply = format!(
+                    "HTTP/1.1 {} X\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+                     {}Connection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    headers,
+                    body
+                );
+                stream.write_all(reply.as_bytes()).await.unwrap();
+            }
+            received
+        });
+        (base_url, server)
+    }
+
+    async fn read_request(stream: &mut tokio::net::TcpStream) -> String {
+        let mut request = Vec::new();
+        let mut buffer = [0; 4096];
+        loop {
+            let read = stream.read(&mut buffer).await.unwrap();
+            request.extend_from_slice(&buffer[..read]);
+            let text = String::from_utf8_lossy(&request).to_lowercase();
+            if let Some(end) = text.find("\r\n\r\n") {
+                let length = text
+                    .lines()
+                    .find_map(|line| line.strip_prefix("content-length: "))
+                    .map_or(0, |length| length.trim().parse().unwrap());
+                if request.len() >= end + 4 + length || read == 0 {
+                    return String::from_utf8_lossy(&request).into_owned();
+                }
+            }
+        }
+    }
+
+    fn search_request() -> SearchRequest {
+        SearchRequest {
+            hotel_ids: vec!["hotel1".to_string()],
+            check_in: "2025-06-01".to_string(),
+            check_out: "2025-06-05".to_string(),
+            guests: 2,
+            markets: Vec::new(),
+            supplier: None,
+            priority: RequestPriority::Medium,
+            idempotency_key: None,
+            context: RequestContext {
+                correlation_id: "corr-1".to_string(),
+                ..Default::default()
+            },
+        }
+    }
+
+    fn booking_request() -> BookingRequest {
+        BookingRequest {
+            search_id: "search_123".to_string(),
+            hotel_id: "hotel1".to_string(),
+            guest_name: "John Doe".to_string(),
+            payment_info: PaymentInfo {
+                card_type: "VISA".to_string(),
+                last_four: "1234".to_string(),
+                expiry: "12/25".to_string(),
+                token: Some("token_123".to_string()),
+            },
+            priority: RequestPriority::High,
+            idempotency_key: "booking_123".to_string(),
+            context: RequestContext::default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_http_transport() {
+        let (base_url, server) = serve(vec![
+            (
+                200,
+                r#"{"search_id":"s1","results":[{"hotel_id":"hotel1","available":true,"price":120.5,"currency":"EUR"}]}"#,
+            ),
+            (
+                201,
+                r#"{"booking_id":"b1","status":"confirmed","confirmation_code":"CONF1"}"#,
+            ),
+            (503, ""),
+            (429, "slow down"),
+            (404, "unknown hotel"),
+        ])
+        .await;
+        let transport = HttpTransport::new(&config(base_url)).unwrap();
+
+        let Response::Search(search) = transport
+            .send(Request::Search(search_request()))
+            .await
+            .unwrap()
+        else {
+            panic!("expected a search response");
+        };
+        assert_eq!(search.search_id, "s1");
+        assert_eq!(search.results[0].price, Some(120.5));
+        let Response::Booking(booking) = transport
+            .send(Request::Booking(booking_request()))
+            .await
+            .unwrap()
+        else {
+            panic!("expected a booking response");
+        };
+        assert_eq!(booking.confirmation_code.as_deref(), Some("CONF1"));
+
+        assert!(matches!(
+            transport.send(Request::Search(search_request())).await,
+            Err(ApiError::ApiResponseError {
+                status_code: 503,
+                is_retryable: true,
+                ..
+            })
+        ));
+        assert!(matches!(
+            transport.send(Request::Search(search_request())).await,
+            Err(ApiError::RateLimitExceeded {
+                message,
+                retry_after_ms: None,
+            }) if message == "slow down"
+        ));
+        assert!(matches!(
+            transport.send(Request::Booking(booking_request())).await,
+            Err(ApiError::ApiResponseError {
+                status_code: 404,
+                is_retryable: false,
+                ..
+            })
+        ));
+
+        let received = server.await.unwrap();
+        let search = received[0].to_lowercase();
+        assert!(search.starts_with("post /v1/search http/1.1"));
+        assert!(search.contains("authorization: bearer test_key"));
+        assert!(search.contains("x-correlation-id: corr-1"));
+        assert!(search.ends_with(
+            r#"{"hotel_ids":["hotel1"],"check_in":"2025-06-01","check_out":"2025-06-05","guests":2}"#
+        ));
+        let booking = received[1].to_lowercase();
+        assert!(booking.starts_with("post /v1/bookings http/1.1"));
+        assert!(booking.contains("idempotency-key: booking_123"));
+        assert!(!booking.contains("x-correlation-id"));
+    }
+
+    #[tokio::test]
+    async fn test_http_cancel_and_modify() {
+        let (base_url, server) = serve(vec![
+            (
+                200,
+                r#"{"booking_id":"b/1","status":"cancelled","penalty_amount":30.0,"refund_amount":70.0,"currency":"EUR"}"#,
+            ),
+            (
+                200,
+                r#"{"booking_id":"b2","status":"confirmed","price_difference":-20.0}"#,
+            ),
+        ])
+        .await;
+        let transport = HttpTransport::new(&config(base_url)).unwrap();
+
+        let Response::CancelBooking(cancelled) = transport
+            .send(Request::CancelBooking(CancelBookingRequest {
+                booking_id: "b/1".to_string(),
+                reason: None,
+                idempotency_key: "cancel_123".to_string(),
+                context: RequestContext::default(),
+            }))
+            .await
+            .unwrap()
+        else {
+            panic!("expected a cancellation response");
+        };
+        assert_eq!(
+            (cancelled.penalty_amount, cancelled.refund_amount),
+            (30.0, 70.0)
+        );
+        let Response::ModifyBooking(modified) = transport
+            .send(Request::ModifyBooking(ModifyBookingRequest {
+                booking_id: "b2".to_string(),
+                guests: Some(3),
+                ..Default::default()
+            }))
+            .await
+            .unwrap()
+        else {
+            panic!("expected a modification response");
+        };
+        assert_eq!(modified.price_difference, Some(-20.0));
+        assert!(matches!(
+            transport
+                .send(Request::ModifyBooking(ModifyBookingRequest::default()))
+                .await,
+            Err(ApiError::ClientError(_))
+        ));
+
+        let received = server.await.unwrap();
+        let cancellation = received[0].to_lowercase();
+        assert!(cancellation.starts_with("post /v1/bookings/b%2f1/cancel http/1.1"));
+        assert!(cancellation.contains("idempotency-key: cancel_123"));
+        assert!(cancellation.ends_with("{}"));
+        let modification = received[1].to_lowercase();
+        assert!(modification.starts_with("patch /v1/bookings/b2 http/1.1"));
+        assert!(modification.ends_with(r#"{"guests":3}"#));
+    }
+
+    #[tokio::test]
+    async fn test_http_booking_status() {
+        let (base_url, server) = serve(vec![
+            (200, r#"{"booking_id":"b1","status":"Pending"}"#),
+            (200, r#"{"booking_id":"b1","status":"on_request"}"#),
+        ])
+        .await;
+        let transport = HttpTransport::new(&config(base_url)).unwrap();
+        let mut statuses = Vec::new();
+        for _ in 0..2 {
+            let Response::Booking(booking) = transport
+                .send(Request::BookingStatus("b1".to_string()))
+                .await
+                .unwrap()
+            else {
+                panic!("expected a booking response");
+            };
+            statuses.push(booking.status);
+        }
+        assert_eq!(
+            statuses,
+            [
+                BookingStatus::Pending,
+                BookingStatus::Other("on_request".to_string())
+            ]
+        );
+        let received = server.await.unwrap();
+        assert!(received[0]
+            .to_lowercase()
+            .starts_with("get /v1/bookings/b1 http/1.1"));
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_headers() {
+        let (base_url, _server) = serve_with_headers(vec![
+            (
+                200,
+                "X-RateLimit-Remaining: 7\r\n",
+                r#"{"search_id":"s1","results":[]}"#,
+            ),
+            (429, "Retry-After: 2\r\nX-RateLimit-Remaining: 0\r\n", ""),
+        ])
+        .await;
+        let transport = HttpTransport::new(&config(base_url)).unwrap();
+        let Response::Search(search) = transport
+            .send(Request::Search(search_request()))
+            .await
+            .unwrap()
+        else {
+            panic!("expected a search response");
+        };
+        assert_eq!(search.rate_limit_remaining, Some(7));
+        assert!(matches!(
+            transport.send(Request::Search(search_request())).await,
+            Err(ApiError::RateLimitExceeded {
+                retry_after_ms: Some(2000),
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_retry_after() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let headers = |pairs: &[(&'static str, &'static str)]| {
+            let mut headers = HeaderMap::new();
+            for (name, value) in pairs {
+                headers.insert(*name, HeaderValue::from_static(value));
+            }
+            headers
+        };
+        let wait = |pairs| retry_after(&headers(pairs), now);
+        assert_eq!(wait(&[("retry-after", "3")]), Some(Duration::from_secs(3)));
+        // 2023-11-14T22:13:20Z plus 90 seconds
+        assert_eq!(
+            wait(&[("retry-after", "Tue, 14 Nov 2023 22:14:50 GMT")]),
+            Some(Duration::from_secs(90))
+        );
+        assert_eq!(
+            wait(&[("retry-after", "Tue, 14 Nov 2023 22:00:00 GMT")]),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(
+            wait(&[("x-ratelimit-reset", "1700000030")]),
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(
+            wait(&[("x-ratelimit-reset", "15")]),
+            Some(Duration::from_secs(15))
+        );
+        assert_eq!(
+            wait(&[("retry-after", "5"), ("x-ratelimit-reset", "15")]),
+            Some(Duration::from_secs(5))
+        );
+        // Already passed, by a second of clock skew
+        assert_eq!(
+            wait(&[("x-ratelimit-reset", "1699999999")]),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(wait(&[("retry-after", "soon")]), None);
+        assert_eq!(wait(&[]), None);
+
+        // An absurd wait saturates rather than wrapping around
+        let absurd = wait(&[("retry-after", "18446744073709551615")]);
+        assert_eq!(absurd, Some(Duration::from_secs(u64::MAX)));
+        assert!(matches!(
+            status_error(StatusCode::TOO_MANY_REQUESTS, String::new(), absurd),
+            ApiError::RateLimitExceeded {
+                retry_after_ms: Some(u64::MAX),
+                ..
+            }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_http_health_check() {
+        let (base_url, server) = serve(vec![(204, ""), (503, "maintenance")]).await;
+        let transport = HttpTransport::new(&config(base_url)).unwrap();
+        let health_check = || Request::HealthCheck("/status/health".to_string());
+        assert!(matches!(
+            transport.send(health_check()).await,
+            Ok(Response::HealthCheck)
+        ));
+        assert!(matches!(
+            transport.send(health_check()).await,
+            Err(ApiError::ApiResponseError {
+                status_code: 503,
+                ..
+            })
+        ));
+        let received = server.await.unwrap();
+        assert!(received[0]
+            .to_lowercase()
+            .starts_with("get /v1/status/health http/1.1"));
+    }
+
+    #[tokio::test]
+    async fn test_http_transport_failures() {
+        // Accepts, never answers
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let transport = HttpTransport::new(&ClientConfig {
+            timeout_ms: 50,
+            ..config(format!("http://{}", listener.local_addr().unwrap()))
+        })
+        .unwrap();
+        assert!(matches!(
+            transport.send(Request::Search(search_request())).await,
+            Err(ApiError::Timeout(50))
+        ));
+
+        // Nothing listening
+        let address = listener.local_addr().unwrap();
+        drop(listener);
+        let transport = HttpTransport::new(&config(format!("http://{}", address))).unwrap();
+        assert!(matches!(
+            transport.send(Request::Search(search_request())).await,
+            Err(ApiError::NetworkError(_))
+        ));
+
+        assert!(matches!(
+            HttpTransport::new(&config("not a url".to_string())),
+            Err(ClientError::ConfigError(_))
+        ));
+        assert!(matches!(
+            transport.reconfigure(&config("not a url".to_string())),
+            Err(ClientError::ConfigError(_))
+        ));
+    }
+}
@@ -27,9 +27,10 @@ impl ApiClient for ExampleBookingApiClient {
                 if now.duration_since(last)
                     < Duration::from_millis(1000 / self.config.max_requests_per_second as u64)
                 {
-                    return Err(ApiError::RateLimitExceeded(
-                        "Rate limit exceeded".to_string(),
-                    ));
+                    return Err(ApiError::RateLimitExceeded {
+                        message: "Rate limit exceeded".to_string(),
+                        retry_after_ms: None,
+                    });
                 }
             }
 
@@ -56,6 +57,7 @@ impl ApiClient for ExampleBookingApiClient {
                 available: true,
                 price: Some(100.0),
                 currency: Some("USD".to_string()),
+                search_id: None,
             })
             .collect();
 
@@ -80,13 +82,82 @@ impl ApiClient for ExampleBookingApiClient {
 
         Ok(BookingResponse {
             booking_id: format!("booking_{}", rand::random::<u32>()),
-            status: "confirmed".to_string(),
+            status: BookingStatus::Confirmed,
             confirmation_code: Some(format!("CONF{}", rand::random::<u16>())),
             rate_limit_remaining: None, // Bookings don't count against rate limit
             processing_time_ms: 100,
         })
     }
 
+    async fn cancel_booking(
+        &self,
+        request: CancelBookingRequest,
+    ) -> Result<CancelBookingResponse, ApiError> {
+        // Cancellations are as urgent as bookings
+        sleep(Duration::from_millis(100)).await;
+
+        // Update stats
+        {
+            let mut stats = self.stats.lock().unwrap();
+            stats.requests_sent += 1;
+            stats.requests_succeeded += 1;
+        }
+
+        Ok(CancelBookingResponse {
+            booking_id: request.booking_id,
+            status: BookingStatus::Cancelled,
+            penalty_amount: 0.0,
+            refund_amount: 100.0,
+            currency: Some("USD".to_string()),
+            rate_limit_remaining: None,
+            processing_time_ms: 100,
+        })
+    }
+
+    async fn modify_booking(
+        &self,
+        request: ModifyBookingRequest,
+    ) -> Result<ModifyBookingResponse, ApiError> {
+        sleep(Duration::from_millis(100)).await;
+
+        // Update stats
+        {
+            let mut stats = self.stats.lock().unwrap();
+            stats.requests_sent += 1;
+            stats.requests_succeeded += 1;
+        }
+
+        Ok(ModifyBookingResponse {
+            booking_id: request.booking_id,
+            status: BookingStatus::Confirmed,
+            confirmation_code: Some(format!("CONF{}", rand::random::<u16>())),
+            price_difference: None,
+            currency: None,
+            rate_limit_remaining: None,
+            processing_time_ms: 100,
+        })
+    }
+
+    async fn get_booking_status(&self, booking_id: &str) -> Result<BookingResponse, ApiError> {
+        sleep(Duration::from_millis(50)).await;
+
+        // Update stats
+        {
+            let mut stats = self.stats.lock().unwrap();
+            stats.requests_sent += 1;
+            stats.requests_succeeded += 1;
+        }
+
+        // Every booking is confirmed straight away
+        Ok(BookingResponse {
+            booking_id: booking_id.to_string(),
+            status: BookingStatus::Confirmed,
+            confirmation_code: None,
+            rate_limit_remaining: None,
+            processing_time_ms: 50,
+        })
+    }
+
     fn stats(&self) -> ClientStats {
         self.stats.lock().unwrap().clone()
     }
@@ -152,7 +223,11 @@ mod tests {
             retry_config: RetryConfig::default(),
             circuit_breaker_config: CircuitBreakerConfig::default(),
             queue_size_per_priority: 100,
+            overflow_policies: OverflowPolicies::default(),
+            queue_aging: QueueAging::default(),
             health_check_interval_ms: 30000,
+            health_check_probe: None,
+            coalesce_searches: false,
         };
 
         let client = ExampleBookingApiClient::new(config).await.unwrap();
@@ -162,6 +237,8 @@ mod tests {
             check_in: "2025-06-01".to_string(),
             check_out: "2025-06-05".to_string(),
             guests: 2,
+            markets: Vec::new(),
+            supplier: None,
             priority: RequestPriority::Medium,
             idempotency_key: None,
             context: RequestContext {
@@ -195,7 +272,11 @@ mod tests {
             retry_config: RetryConfig::default(),
             circuit_breaker_config: CircuitBreakerConfig::default(),
             queue_size_per_priority: 100,
+            overflow_policies: OverflowPolicies::default(),
+            queue_aging: QueueAging::default(),
             health_check_interval_ms: 30000,
+            health_check_probe: None,
+            coalesce_searches: false,
         };
 
         let client = ExampleBookingApiClient::new(config).await.unwrap();
@@ -223,7 +304,7 @@ mod tests {
 
         let response = result.unwrap();
         assert!(response.booking_id.starts_with("booking_"));
-        assert_eq!(response.status, "confirmed");
+        assert_eq!(response.status, BookingStatus::Confirmed);
         assert!(response.confirmation_code.is_some());
     }
 
@@ -239,7 +320,11 @@ mod tests {
             retry_config: RetryConfig::default(),
             circuit_breaker_config: CircuitBreakerConfig::default(),
             queue_size_per_priority: 100,
+            overflow_policies: OverflowPolicies::default(),
+            queue_aging: QueueAging::default(),
             health_check_interval_ms: 30000,
+            health_check_probe: None,
+            coalesce_searches: false,
         };
 
         let client = ExampleBookingApiClient::new(config).await.unwrap();
@@ -249,6 +334,8 @@ mod tests {
             check_in: "2025-06-01".to_string(),
             check_out: "2025-06-05".to_string(),
             guests: 2,
+            markets: Vec::new(),
+            supplier: None,
             priority: RequestPriority::Medium,
             idempotency_key: None,
             context: RequestContext {
@@ -265,7 +352,7 @@ mod tests {
         let result2 = client.search(request.clone()).await;
         assert!(result2.is_err());
 
-        if let Err(ApiError::RateLimitExceeded(_)) = result2 {
+        if let Err(ApiError::RateLimitExceeded { .. }) = result2 {
             // Expected
         } else {
             panic!("Expected rate limit error");
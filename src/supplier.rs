@@ -1,3 +1,5 @@
+use crate::money::Money;
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 
 // Data structures for supplier JSON response
@@ -26,7 +28,7 @@ pub struct SupplierRoom {
     pub capacity: RoomCapacity,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Default, Deserialize, Serialize)]
 pub struct RoomCapacity {
     pub adults: i32,
     pub children: i32,
@@ -36,13 +38,67 @@ pub struct RoomCapacity {
 pub struct SupplierRate {
     pub rate_id: String,
     pub board_type: String,
-    pub price: f64,
+    // Amounts are JSON numbers, in the response's currency
+    #[serde(with = "rust_decimal::serde::float")]
+    pub price: Decimal,
     pub cancellation_policies: Vec<SupplierCancellationPolicy>,
     pub booking_code: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub taxes: Vec<SupplierTax>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub promotions: Vec<SupplierPromotion>,
+}
+
+impl SupplierRate {
+    pub fn price_in(&self, currency: impl Into<String>) -> Money {
+        Money::new(self.price, currency)
+    }
+}
+
+// A tax or fee of a rate, either an amount or a percentage of the rate's price
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct SupplierTax {
+    pub name: String,
+    // Part of the rate's price already
+    pub included: bool,
+    #[serde(
+        default,
+        with = "rust_decimal::serde::float_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub amount: Option<Decimal>,
+    #[serde(
+        default,
+        with = "rust_decimal::serde::float_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub percent: Option<Decimal>,
+    #[serde(default)]
+    pub pay_at_hotel: bool,
+}
+
+impl SupplierTax {
+    // The tax on a rate of `price`, rounded to the cent
+    pub fn amount_on(&self, price: Decimal) -> Decimal {
+        match (self.amount, self.percent) {
+            (Some(amount), _) => amount,
+            (None, Some(percent)) => (price * percent / Decimal::ONE_HUNDRED).round_dp(2),
+            (None, None) => Decimal::ZERO,
+        }
+    }
+}
+
+// A promotion the rate's price already reflects, e.g. an early booking discount
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct SupplierPromotion {
+    pub code: String,
+    #[serde(default)]
+    pub name: String,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct SupplierCancellationPolicy {
     pub from_date: String,
-    pub amount: f64,
+    #[serde(with = "rust_decimal::serde::float")]
+    pub amount: Decimal,
 }
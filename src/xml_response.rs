@@ -1,4 +1,18 @@
-use crate::supplier::SupplierResponse;
+use crate::money::{format_amount, Money};
+use crate::part2_xml::cancellation::PENALTY_PERCENTAGE;
+use crate::part2_xml::combination::{combine_rooms, requested_rooms, CombinationLimits, RoomPick};
+use crate::part2_xml::dates;
+use crate::part2_xml::identifier::{option_id, OPTION_ID_PARAMETER, SEARCH_TOKEN_PARAMETER};
+use crate::part2_xml::occupancy::Occupancy;
+use crate::part2_xml::pricing::PricingRules;
+use crate::part2_xml::token::{SearchToken, TokenSigner};
+use crate::part2_xml::ProcessingError;
+use crate::supplier::{
+    RoomCapacity, SupplierCancellationPolicy, SupplierHotel, SupplierPromotion, SupplierRate,
+    SupplierResponse, SupplierRoom, SupplierTax,
+};
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 
 // Structures for XML deserialization
@@ -9,100 +23,88 @@ pub struct XmlProcessedResponse {
     pub hotels: XmlHotels,
 }
 
+// Rooms of a multi-room option in the rate key and occupancy of its search token
+pub const TOKEN_ROOM_SEPARATOR: &str = "+";
+
+// How `XmlProcessedResponse::from_supplier` makes supplier rates into options
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ConversionOptions<'a> {
+    // Signs the search tokens
+    pub signer: Option<&'a TokenSigner>,
+    // Fills in selling prices and commissions, left unknown (-1) without
+    pub pricing: Option<&'a PricingRules>,
+    // The rooms requested: options combine a room for each, see `combination`. Without it
+    // every room and rate is an option of its own.
+    pub occupancy: Option<&'a Occupancy>,
+    pub limits: CombinationLimits,
+}
+
 impl From<SupplierResponse> for XmlProcessedResponse {
     fn from(item: SupplierResponse) -> Self {
+        Self::from_supplier(item, &ConversionOptions::default())
+    }
+}
+
+impl XmlProcessedResponse {
+    pub fn from_supplier(item: SupplierResponse, options: &ConversionOptions) -> Self {
+        let requested = options.occupancy.map(requested_rooms);
         let mut xml_hotels = Vec::new();
+        let searched_at = dates::parse_datetime(&item.timestamp).unwrap_or_else(|_| Utc::now());
 
-        for hotel in item.hotels {
+        for hotel in &item.hotels {
             let mut meal_plans = Vec::new();
 
-            // Group rooms by board type
-            let mut board_types = std::collections::HashMap::new();
+            // Group rooms by board type, in the order board types first appear so the output
+            // is the same on every run
+            let mut board_types: Vec<(String, Vec<_>)> = Vec::new();
 
             for room in &hotel.rooms {
                 for rate in &room.rates {
-                    let entries = board_types
-                        .entry(rate.board_type.clone())
-                        .or_insert_with(Vec::new);
-                    entries.push((room, rate));
+                    match board_types
+                        .iter_mut()
+                        .find(|(board_type, _)| *board_type == rate.board_type)
+                    {
+                        Some((_, entries)) => entries.push((room, rate)),
+                        None => board_types.push((rate.board_type.clone(), vec![(room, rate)])),
+                    }
                 }
             }
 
+            let mut hotel_limit = options.limits.per_hotel;
             for (board_type, room_rates) in board_types {
-                let mut options = Vec::new();
-
-                let xml_option = XmlOption {
-                    option_type: "Hotel".to_string(),
-                    payment_type: "MerchantPay".to_string(),
-                    status: "OK".to_string(),
-                    price: XmlPrice {
-                        currency: item.currency.clone(),
-                        amount: room_rates
-                            .first()
-                            .map_or("0.0".to_string(), |(_, rate)| rate.price.to_string()),
-                        binding: "false".to_string(),
-                        commission: "-1".to_string(),
-                        minimum_selling_price: "-1".to_string(),
-                    },
-                    rooms: XmlRooms {
-                        rooms: room_rates
-                            .iter()
-                            .map(|(room, rate)| {
-                                let cancel_penalties = XmlCancelPenalties {
-                                    non_refundable: "false".to_string(),
-                                    cancel_penalties: rate
-                                        .cancellation_policies
-                                        .iter()
-                                        .map(|cp| XmlCancelPenalty {
-                                            hours_before: "N/A".to_string(),
-                                            penalty: XmlPenalty {
-                                                penalty_type: "Importe".to_string(),
-                                                currency: item.currency.clone(),
-                                                value: cp.amount.to_string(),
-                                            },
-                                            deadline: cp.from_date.clone(),
-                                        })
-                                        .collect(),
-                                };
-
-                                XmlRoom {
-                                    id: format!("1#{}", room.room_id),
-                                    room_candidate_ref_id: "1".to_string(),
-                                    code: room.room_id.clone(),
-                                    description: room.name.clone(),
-                                    number_of_units: "1".to_string(),
-                                    non_refundable: "false".to_string(),
-                                    price: XmlPrice {
-                                        currency: item.currency.clone(),
-                                        amount: rate.price.to_string(),
-                                        binding: "false".to_string(),
-                                        commission: "-1".to_string(),
-                                        minimum_selling_price: "-1".to_string(),
-                                    },
-                                    cancel_penalties,
-                                }
-                            })
-                            .collect(),
-                    },
-                    parameters: XmlParameters {
-                        parameters: vec![XmlParameter {
-                            key: "search_token".to_string(),
-                            value: format!("{}|||||{}", hotel.hotel_id, item.search_id),
-                        }],
-                    },
+                let combinations: Vec<Vec<RoomPick>> = match &requested {
+                    Some(requested) => {
+                        let limit = options.limits.per_board_type.min(hotel_limit);
+                        combine_rooms(requested, &room_rates, limit)
+                    }
+                    // One option per room and rate, priced at that rate
+                    None => room_rates
+                        .into_iter()
+                        .map(|(room, rate)| vec![(1, room, rate)])
+                        .collect(),
                 };
-                options.push(xml_option);
-
-                let xml_mealplan = XmlMealPlan {
+                if combinations.is_empty() {
+                    continue;
+                }
+                hotel_limit = hotel_limit.saturating_sub(combinations.len());
+                let hotel_options = combinations
+                    .iter()
+                    .map(|picks| rooms_option(&item, &hotel.hotel_id, picks, searched_at, options))
+                    .collect();
+                meal_plans.push(XmlMealPlan {
                     code: board_type,
-                    options: XmlOptions { options },
-                };
-                meal_plans.push(xml_mealplan);
+                    options: XmlOptions {
+                        options: hotel_options,
+                    },
+                });
             }
 
             xml_hotels.push(XmlHotel {
                 hotel_id: hotel.hotel_id.clone(),
                 hotel_name: hotel.name.clone(),
+                category: Some(hotel.category),
+                destination_code: Some(hotel.destination_code.clone())
+                    .filter(|code| !code.is_empty()),
                 meal_plans: XmlMealPlans { meal_plans },
             });
         }
@@ -111,6 +113,311 @@ impl From<SupplierResponse> for XmlProcessedResponse {
             hotels: XmlHotels { hotels: xml_hotels },
         }
     }
+
+    // The inverse of `from_supplier`: every room of an option becomes a rate of that room, with
+    // the meal plan as board type and the rate key and search ID taken from the search token.
+    // Rooms are listed in the order they first appear, their rates by board type. The search
+    // time isn't carried in the XML, so the timestamp is the time of the conversion.
+    pub fn to_supplier(&self) -> Result<SupplierResponse, ProcessingError> {
+        let mut currency: Option<&str> = None;
+        let mut search_id = String::new();
+        let mut hotels = Vec::new();
+
+        for xml_hotel in &self.hotels.hotels {
+            let mut rooms: Vec<SupplierRoom> = Vec::new();
+            for meal_plan in &xml_hotel.meal_plans.meal_plans {
+                for option in &meal_plan.options.options {
+                    match currency {
+                        None => currency = Some(&option.price.currency),
+                        Some(currency) if currency != option.price.currency => {
+                            return Err(ProcessingError::InvalidFormat(format!(
+                                "options priced in both {} and {}",
+                                currency, option.price.currency
+                            )))
+                        }
+                        Some(_) => {}
+                    }
+                    let token = option
+                        .parameters
+                        .parameters
+                        .iter()
+                        .find(|p| p.key == SEARCH_TOKEN_PARAMETER)
+                        .and_then(|p| SearchToken::decode(&p.value).ok())
+                        .unwrap_or_default();
+                    if search_id.is_empty() {
+                        search_id = token.search_id.clone();
+                    }
+
+                    // Multi-room options list the rate of each room
+                    let rate_keys: Vec<&str> = token.rate_key.split(TOKEN_ROOM_SEPARATOR).collect();
+                    for (index, room) in option.rooms.rooms.iter().enumerate() {
+                        let rate_key = match rate_keys.len() == option.rooms.rooms.len() {
+                            true => rate_keys[index],
+                            false => &token.rate_key,
+                        };
+                        let rate = room_rate(&meal_plan.code, option, room, rate_key)?;
+                        match rooms.iter_mut().find(|r| r.room_id == room.code) {
+                            // Combined into several options, the rate is listed once
+                            Some(supplier_room)
+                                if supplier_room.rates.iter().any(|listed| {
+                                    listed.rate_id == rate.rate_id
+                                        && listed.board_type == rate.board_type
+                                }) => {}
+                            Some(supplier_room) => supplier_room.rates.push(rate),
+                            None => rooms.push(SupplierRoom {
+                                room_id: room.code.clone(),
+                                name: room.description.clone(),
+                                rates: vec![rate],
+                                capacity: RoomCapacity {
+                                    adults: room.adults.unwrap_or(0),
+                                    children: room.children.unwrap_or(0),
+                                },
+                            }),
+                        }
+                    }
+                }
+            }
+
+            hotels.push(SupplierHotel {
+                hotel_id: xml_hotel.hotel_id.clone(),
+                name: xml_hotel.hotel_name.clone(),
+                category: xml_hotel.category.unwrap_or(0),
+                rooms,
+                destination_code: xml_hotel.destination_code.clone().unwrap_or_default(),
+            });
+        }
+
+        Ok(SupplierResponse {
+            hotels,
+            search_id,
+            currency: currency.unwrap_or_default().to_string(),
+            timestamp: Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+        })
+    }
+}
+
+// A room of an option as a supplier rate, priced at the room's price if it has one and the
+// option's otherwise; percentage penalties become amounts of that price
+fn room_rate(
+    board_type: &str,
+    option: &XmlOption,
+    room: &XmlRoom,
+    rate_key: &str,
+) -> Result<SupplierRate, ProcessingError> {
+    let price = Money::parse(&room.price.amount, &option.price.currency)
+        .or_else(|| Money::parse(&option.price.amount, &option.price.currency))
+        .ok_or_else(|| {
+            ProcessingError::InvalidFormat(format!(
+                "price {:?} is not an amount",
+                option.price.amount
+            ))
+        })?;
+    let cancellation_policies = room
+        .cancel_penalties
+        .cancel_penalties
+        .iter()
+        .map(|cp| {
+            let value: Decimal = cp.penalty.value.trim().parse().map_err(|_| {
+                ProcessingError::InvalidFormat(format!(
+                    "penalty {:?} is not an amount",
+                    cp.penalty.value
+                ))
+            })?;
+            let amount = if cp.penalty.penalty_type == PENALTY_PERCENTAGE {
+                (price.amount * value / Decimal::ONE_HUNDRED).round_dp(2)
+            } else {
+                value
+            };
+            Ok(SupplierCancellationPolicy {
+                from_date: cp.deadline.clone(),
+                amount,
+            })
+        })
+        .collect::<Result<_, ProcessingError>>()?;
+
+    let taxes = room
+        .taxes
+        .taxes
+        .iter()
+        .map(|tax| {
+            let percent = tax.percent.as_deref().and_then(|p| p.trim().parse().ok());
+            let amount = match percent {
+                Some(_) => None,
+                None => Some(tax.amount.trim().parse().map_err(|_| {
+                    ProcessingError::InvalidFormat(format!("tax {:?} is not an amount", tax.amount))
+                })?),
+            };
+            Ok(SupplierTax {
+                name: tax.name.clone(),
+                included: tax.included == "true",
+                amount,
+                percent,
+                pay_at_hotel: tax.pay_at_hotel == "true",
+            })
+        })
+        .collect::<Result<_, ProcessingError>>()?;
+
+    Ok(SupplierRate {
+        rate_id: rate_key.to_string(),
+        board_type: board_type.to_string(),
+        price: price.amount,
+        cancellation_policies,
+        booking_code: room.booking_code.clone().unwrap_or_default(),
+        taxes,
+        promotions: room
+            .offers
+            .offers
+            .iter()
+            .map(|offer| SupplierPromotion {
+                code: offer.code.clone(),
+                name: offer.name.clone(),
+            })
+            .collect(),
+    })
+}
+
+// A rate is non-refundable if cancelling it right away, at the time of the search, already
+// costs something
+fn non_refundable(rate: &SupplierRate, searched_at: DateTime<Utc>) -> bool {
+    rate.cancellation_policies.iter().any(|cp| {
+        cp.amount > Decimal::ZERO
+            && dates::parse_datetime(&cp.from_date).is_ok_and(|from| from <= searched_at)
+    })
+}
+
+// An option of one or more rooms, each with the candidate it's for, priced at their sum
+fn rooms_option(
+    item: &SupplierResponse,
+    hotel_id: &str,
+    picks: &[RoomPick],
+    searched_at: DateTime<Utc>,
+    options: &ConversionOptions,
+) -> XmlOption {
+    let net = Money::new(
+        picks.iter().map(|(_, _, rate)| rate.price).sum(),
+        &item.currency,
+    );
+    let joined = |field: &dyn Fn(&RoomPick) -> String| {
+        picks
+            .iter()
+            .map(field)
+            .collect::<Vec<_>>()
+            .join(TOKEN_ROOM_SEPARATOR)
+    };
+    // The supplier response doesn't carry the stay or nationality searched
+    let mut token = SearchToken::new(hotel_id)
+        .currency(&item.currency)
+        .rate_key(joined(&|(_, _, rate)| rate.rate_id.clone()))
+        .search_id(&item.search_id);
+    token.occupancy =
+        joined(&|(_, room, _)| format!("{}-{}", room.capacity.adults, room.capacity.children));
+
+    let rooms = picks
+        .iter()
+        .enumerate()
+        .map(|(index, &(candidate, room, rate))| {
+            let non_refundable = non_refundable(rate, searched_at).to_string();
+            let cancel_penalties = XmlCancelPenalties {
+                non_refundable: non_refundable.clone(),
+                cancel_penalties: rate
+                    .cancellation_policies
+                    .iter()
+                    .map(|cp| XmlCancelPenalty {
+                        hours_before: "N/A".to_string(),
+                        penalty: XmlPenalty {
+                            penalty_type: "Importe".to_string(),
+                            currency: item.currency.clone(),
+                            value: format_amount(cp.amount),
+                        },
+                        deadline: cp.from_date.clone(),
+                    })
+                    .collect(),
+            };
+            XmlRoom {
+                id: format!("{}#{}", index + 1, room.room_id),
+                adults: Some(room.capacity.adults),
+                children: Some(room.capacity.children),
+                booking_code: Some(rate.booking_code.clone()).filter(|code| !code.is_empty()),
+                room_candidate_ref_id: candidate.to_string(),
+                code: room.room_id.clone(),
+                description: room.name.clone(),
+                number_of_units: "1".to_string(),
+                non_refundable,
+                price: xml_price(&rate.price_in(&item.currency), options.pricing),
+                cancel_penalties,
+                taxes: XmlTaxes {
+                    taxes: rate
+                        .taxes
+                        .iter()
+                        .map(|tax| XmlTax {
+                            name: tax.name.clone(),
+                            included: tax.included.to_string(),
+                            amount: format_amount(tax.amount_on(rate.price)),
+                            currency: item.currency.clone(),
+                            percent: tax.percent.map(format_amount),
+                            pay_at_hotel: tax.pay_at_hotel.to_string(),
+                        })
+                        .collect(),
+                },
+                offers: XmlOffers {
+                    offers: rate
+                        .promotions
+                        .iter()
+                        .map(|promotion| XmlOffer {
+                            code: promotion.code.clone(),
+                            name: promotion.name.clone(),
+                        })
+                        .collect(),
+                },
+            }
+        })
+        .collect();
+
+    let mut option = XmlOption {
+        option_type: "Hotel".to_string(),
+        payment_type: "MerchantPay".to_string(),
+        status: "OK".to_string(),
+        price: xml_price(&net, options.pricing),
+        rooms: XmlRooms { rooms },
+        parameters: XmlParameters {
+            parameters: vec![XmlParameter {
+                key: SEARCH_TOKEN_PARAMETER.to_string(),
+                value: match options.signer {
+                    Some(signer) => signer.sign(&token),
+                    None => token.encode(),
+                },
+            }],
+        },
+    };
+    // Combined rooms all have the same board type
+    let board_type = picks
+        .first()
+        .map(|(_, _, rate)| rate.board_type.as_str())
+        .unwrap_or_default();
+    option.parameters.parameters.push(XmlParameter {
+        key: OPTION_ID_PARAMETER.to_string(),
+        value: option_id(hotel_id, board_type, &option),
+    });
+    option
+}
+
+// Without `pricing` the selling price and commission are unknown (-1)
+fn xml_price(net: &Money, pricing: Option<&PricingRules>) -> XmlPrice {
+    let (commission, minimum_selling_price) = match pricing {
+        Some(rules) => {
+            let selling = rules.selling_price(net);
+            let commission = format_amount(selling.amount - net.amount);
+            (commission, selling.xml_amount())
+        }
+        None => ("-1".to_string(), "-1".to_string()),
+    };
+    XmlPrice {
+        currency: net.currency.clone(),
+        amount: net.xml_amount(),
+        binding: "false".to_string(),
+        commission,
+        minimum_selling_price,
+    }
 }
 
 #[derive(Debug, PartialEq, Default, Deserialize, Serialize)]
@@ -127,6 +434,11 @@ pub struct XmlHotel {
     pub hotel_id: String,
     #[serde(rename = "@name")]
     pub hotel_name: String,
+    // Not part of the hub's AvailRS, carried over from supplier responses
+    #[serde(rename = "@category", skip_serializing_if = "Option::is_none")]
+    pub category: Option<i32>,
+    #[serde(rename = "@destinationCode", skip_serializing_if = "Option::is_none")]
+    pub destination_code: Option<String>,
     pub meal_plans: XmlMealPlans,
 }
 
@@ -200,8 +512,70 @@ pub struct XmlRoom {
     pub number_of_units: String,
     #[serde(rename = "@nonRefundable")]
     pub non_refundable: String,
+    // Room capacity and the rate's booking code, carried over from supplier responses
+    #[serde(rename = "@adults", skip_serializing_if = "Option::is_none")]
+    pub adults: Option<i32>,
+    #[serde(rename = "@children", skip_serializing_if = "Option::is_none")]
+    pub children: Option<i32>,
+    #[serde(rename = "@bookingCode", skip_serializing_if = "Option::is_none")]
+    pub booking_code: Option<String>,
     pub price: XmlPrice,
     pub cancel_penalties: XmlCancelPenalties,
+    // Taxes and fees of the room's rate, see `part2_xml::taxes`
+    #[serde(skip_serializing_if = "XmlTaxes::is_empty")]
+    pub taxes: XmlTaxes,
+    // Promotions of the room's rate, see `part2_xml::promotions`
+    #[serde(skip_serializing_if = "XmlOffers::is_empty")]
+    pub offers: XmlOffers,
+}
+#[derive(Debug, PartialEq, Default, Deserialize, Clone, Serialize)]
+#[serde(default, rename_all = "PascalCase")]
+pub struct XmlTaxes {
+    #[serde(rename = "Tax")]
+    pub taxes: Vec<XmlTax>,
+}
+
+impl XmlTaxes {
+    pub fn is_empty(&self) -> bool {
+        self.taxes.is_empty()
+    }
+}
+#[derive(Debug, PartialEq, Default, Deserialize, Clone, Serialize)]
+#[serde(default, rename_all = "PascalCase")]
+pub struct XmlTax {
+    #[serde(rename = "@name")]
+    pub name: String,
+    #[serde(rename = "@included")]
+    pub included: String,
+    // Percentages are resolved against the room's price
+    #[serde(rename = "@amount")]
+    pub amount: String,
+    #[serde(rename = "@currency")]
+    pub currency: String,
+    #[serde(rename = "@percent", skip_serializing_if = "Option::is_none")]
+    pub percent: Option<String>,
+    #[serde(rename = "@payAtHotel")]
+    pub pay_at_hotel: String,
+}
+#[derive(Debug, PartialEq, Default, Deserialize, Clone, Serialize)]
+#[serde(default, rename_all = "PascalCase")]
+pub struct XmlOffers {
+    #[serde(rename = "Offer")]
+    pub offers: Vec<XmlOffer>,
+}
+
+impl XmlOffers {
+    pub fn is_empty(&self) -> bool {
+        self.offers.is_empty()
+    }
+}
+#[derive(Debug, PartialEq, Default, Deserialize, Clone, Serialize)]
+#[serde(default, rename_all = "PascalCase")]
+pub struct XmlOffer {
+    #[serde(rename = "@code")]
+    pub code: String,
+    #[serde(rename = "@name")]
+    pub name: String,
 }
 #[derive(Debug, PartialEq, Default, Deserialize, Clone, Serialize)]
 #[serde(default, rename_all = "PascalCase")]